@@ -35,7 +35,7 @@ const results: BenchmarkResult[] = [];
 // ============================================
 
 function cleanup() {
-    const files = [DB_FILE, `${DB_FILE}.wal`, `${DB_FILE}.tmp`];
+    const files = [DB_FILE, `${DB_FILE}.wal`, `${DB_FILE}.tmp`, `${DB_FILE}.age.idx`];
     for (const f of files) {
         if (existsSync(f)) unlinkSync(f);
     }
@@ -338,6 +338,43 @@ async function runParallelBenchmarks(db: JSONDatabase, mode: string) {
 // Results Generation
 // ============================================
 
+async function runBulkLoadBenchmarks(mode: string) {
+    console.log(`\n🚚 Bulk Load (${mode})`);
+    console.log('─'.repeat(50));
+
+    const DOCS = 1_000_000;
+    const load = async (bulk: boolean) => {
+        cleanup();
+        const db = new JSONDatabase(DB_FILE, {
+            wal: mode === 'WAL',
+            indices: [{ name: 'age', path: 'people', field: 'age' }]
+        });
+        const start = performance.now();
+        if (bulk) db.beginBulkLoad();
+        for (let i = 0; i < DOCS; i++) {
+            await db.set(`people.p${i}`, { id: i, age: i % 90 });
+        }
+        if (bulk) await db.endBulkLoad();
+        else await db.save();
+        const totalTimeMs = performance.now() - start;
+        await db.close();
+
+        const name = bulk ? 'load 1M docs (bulk load)' : 'load 1M docs (set)';
+        const opsPerSecond = Math.round((DOCS / totalTimeMs) * 1000);
+        console.log(`  ✅ ${name}: ${formatNumber(opsPerSecond)} docs/s (${(totalTimeMs / 1000).toFixed(2)}s, index and save included)`);
+        results.push({
+            name,
+            mode,
+            iterations: DOCS,
+            totalTimeMs: Math.round(totalTimeMs * 100) / 100,
+            avgLatencyMs: Math.round((totalTimeMs / DOCS) * 10000) / 10000,
+            opsPerSecond
+        });
+    };
+    await load(false);
+    await load(true);
+}
+
 function generateMarkdownReport(): string {
     const now = new Date().toISOString();
     
@@ -481,6 +518,8 @@ async function main() {
     await runParallelBenchmarks(dbWal2, 'WAL');
     await dbWal2.close(); // Release lock
 
+    await runBulkLoadBenchmarks('WAL');
+
     cleanup();
     console.log('\n🎉 Benchmark complete!');
 }
@@ -2,11 +2,131 @@ import { EventEmitter } from 'events';
 
 /* auto-generated by NAPI-RS */
 
+/** Stable code prefixed to every native error message (`"ERR_...: details"`) */
+export type DbErrorCode =
+  | 'ERR_DB_LOCKED'
+  | 'ERR_WAL'
+  | 'ERR_WAL_BUSY'
+  | 'ERR_IO'
+  | 'ERR_CORRUPT'
+  | 'ERR_PATH_NOT_FOUND'
+  | 'ERR_NOT_AN_ARRAY'
+  | 'ERR_PATH_BLOCKED'
+  | 'ERR_INVALID_PATH'
+  | 'ERR_INVALID_ARGUMENT'
+  | 'ERR_VALIDATION'
+  | 'ERR_NO_TRANSACTION'
+  | 'ERR_TRANSACTION_ACTIVE'
+  | 'ERR_READ_ONLY'
+  | 'ERR_FROZEN'
+  | 'ERR_CONFLICT'
+  | 'ERR_NOT_FOUND'
+  | 'ERR_LIMIT'
+  | 'ERR_QUOTA'
+  | 'ERR_CONSTRAINT'
+  | 'ERR_RECOVERY_PENDING'
+  | 'ERR_INDEX'
+  | 'ERR_THROTTLED'
+  | 'ERR_CLOSED'
+/** One failure found by `validateValue`; `path` is dotted below the value ("" for the value itself) */
+export interface SchemaIssue {
+  path: string
+  keyword: string
+  message: string
+}
+/**
+ * Validate any value against an ad-hoc schema without a database instance.
+ * Returns every issue (empty when valid); throws ERR_INVALID_ARGUMENT if the schema itself is invalid.
+ */
+export declare function validateValue(value: any, schemaJson: string): Array<SchemaIssue>
+/**
+ * A value the schema accepts: defaults, first enum members, lowest numbers, minLength strings,
+ * minItems arrays and every required property. Throws ERR_INVALID_ARGUMENT if the schema is invalid.
+ */
+export declare function generateSample(schemaJson: string): any
 /** Query filter for parallel batch queries */
 export interface QueryFilter {
   field: string
   op: 'eq' | 'ne' | 'gt' | 'gte' | 'lt' | 'lte' | 'contains' | 'startswith' | 'endswith' | 'in' | 'notin' | 'regex' | 'containsAll' | 'containsAny'
+  /** Literal, or `{ $field: 'other.path' }` to compare against a sibling field of the same document */
   value: any
+  /**
+   * Documents without the field: 'exclude' (default) never match, 'match' match
+   * negative operators (ne, notin), 'as_null' compare as if the field were null
+   */
+  missing?: 'exclude' | 'match' | 'as_null'
+  /**
+   * "strict" compares values as stored, "canonical" treats `1` and `1.0` as
+   * equal (`eq`, `ne`, `in`, `notin`, `containsAll`, `containsAny`).
+   * Default: the database's `setEquality` mode
+   */
+  equality?: 'strict' | 'canonical'
+}
+/** How parallelQuery would run, from explainQuery */
+export interface QueryPlan {
+  strategy: 'index' | 'scan'
+  /** Index supplying the candidates; null for a scan */
+  index: string | null
+  /** The first filter's field and operator, which the index answers */
+  field: string | null
+  op: string | null
+  /** Documents the remaining filters are checked against; null for a scan */
+  candidates: number | null
+  /** What the planner expected from the stats; null without an index plan or stats */
+  estimatedCandidates: number | null
+  collectionSize: number
+  /** The collection's stats with just the first filter's field; null if never analyzed */
+  stats: (Omit<CollectionStats, 'fields'> & { field: FieldStats | null }) | null
+  /** Why there is no index plan; null when there is one */
+  reason: string | null
+}
+/** Planner statistics of one indexed field, from analyze */
+export interface FieldStats {
+  index: string
+  indexed: number
+  cardinality: number
+  min: string | null
+  max: string | null
+  mostCommon: Array<{ key: string; docs: number }>
+}
+export interface CollectionStats {
+  entries: number
+  analyzedAt: number
+  staleAfter: number
+  fields: Record<string, FieldStats>
+  currentEntries: number
+  writes: number
+  stale: boolean
+}
+/** Options for `analyze` */
+export interface AnalyzeOptions {
+  /** Writes under the collection after which its stats count as stale (default: a fifth of its entries, at least 1) */
+  staleAfter?: number
+}
+export interface QueryOptions {
+  /**
+   * 'values' (default) returns matching documents, 'entries' returns `[{ key, value }]`,
+   * 'object' returns matches under their original keys. Array keys are indices.
+   * 'indexed' returns `[{ index, value }]` with numeric indexes and needs an array collection.
+   */
+  emit?: 'values' | 'entries' | 'object' | 'indexed'
+}
+/** Options for parallelQueryMulti */
+export interface MultiQueryOptions {
+  /** Add the path of its collection to each matching object as `_source` */
+  tagSource?: boolean
+  /** Field (dot notation) to sort all matches by; collection order otherwise */
+  sortBy?: string
+  descending?: boolean
+  /** Matches to skip after the sort */
+  offset?: number
+  /** Most matches to return after the sort */
+  limit?: number
+}
+export interface MultiQueryResult<T = any> {
+  results: Array<T>
+  /** Listed collections that do not exist */
+  missing: Array<string>
 }
 /** Batch query request */
 export interface BatchQuery {
@@ -18,6 +138,148 @@ export interface ParallelResult {
   success: boolean
   count: number
   error?: string
+  /** Values that failed schema validation: `[{ index, path, error }]` */
+  invalid?: Array<any>
+}
+/** Documents sharing one tuple of constrained values */
+export interface ConstraintViolation {
+  values: unknown[]
+  keys: string[]
+}
+/** One entry of `checkConstraints` */
+export interface ConstraintReport {
+  name: string
+  fields: string[]
+  /** Writes refused since the database was opened */
+  rejected: number
+  violations: ConstraintViolation[]
+}
+export type CascadeAction = 'delete' | 'nullify' | 'restrict'
+export interface CascadeRule {
+  parent: string
+  child: string
+  field: string
+  action: CascadeAction
+}
+/** What cascade rules did during one delete, by rule name */
+export type CascadeCounts = Record<string, { deleted: number; nullified: number }>
+/** Result of buildIndex */
+export interface BuildIndexResult {
+  /** Documents indexed */
+  indexed: number
+  /** Documents without the indexed field, left out */
+  skipped: number
+  /** Entries the index held before the build */
+  cleared: number
+}
+/** One entry of listIndexes */
+export interface IndexInfo {
+  name: string
+  field: string
+  /** Collection the index covers; null for one fed by hand */
+  collection: string | null
+  custom: boolean
+  /** Null fields are left out */
+  sparse: boolean
+  /** Indexed documents */
+  entries: number
+  /** Distinct keys */
+  keys: number
+  /** Size of the index file; null until the index is first saved */
+  fileBytes: number | null
+}
+export interface CustomIndexOptions {
+  /** "skip" (default) leaves a document the extractor throws on out of the index; "reject" fails the write */
+  onError?: 'skip' | 'reject'
+  /** Rebuild even when entries were loaded from the index file */
+  rebuild?: boolean
+}
+export interface TTLIndexInfo {
+  /** Index name, `ttl.<collection>.<field>` */
+  index: string
+  collection: string
+  field: string
+  sweepIntervalMs: number
+  sweeps: number
+  /** Documents deleted by sweeps */
+  expired: number
+  /** Expired documents left for a later sweep because they were frozen or leased */
+  skipped: number
+  lastSweepAt: number | null
+  lastError: string | null
+}
+export interface CustomIndexStatus {
+  collection: string
+  onError: 'skip' | 'reject'
+  entries: number
+  keys: number
+  /** Documents written since the extractor last ran, or "all" */
+  pending: number | 'all'
+  errors: number
+  lastError: string | null
+  rejected: number
+}
+export interface CustomIndexVerification {
+  entries: number
+  keys: number
+  /** Index entries for documents that no longer exist */
+  missingDocs: number
+  /** Documents indexed under other keys than the extractor yields, or left out */
+  mismatched: number
+  extractorErrors: number
+  ok: boolean
+}
+export interface IndexVerification {
+  name: string
+  collection: string
+  /** Documents in the collection */
+  documents: number
+  /** Index entries before any repair */
+  entries: number
+  /** Documents with the field but no entry */
+  missing: number
+  /** Entries whose document is gone or no longer has the field */
+  stale: number
+  /** Entries filed under another key than the field's value */
+  wrongKeys: number
+  /** The first paths of each kind of difference */
+  samples: { missing: Array<string>; stale: Array<string>; wrongKeys: Array<{ path: string; indexed: string; expected: string }> }
+  ok: boolean
+  /** The differences were fixed and the index saved */
+  repaired: boolean
+}
+/** Where insertBefore / insertAfter / moveKey placed a key */
+export interface KeyPlacement {
+  key: string
+  /** Index of the key in its collection's key order */
+  position: number
+  /** The reference key was missing, so the key went last */
+  appended: boolean
+}
+/** Result of `setQuota` / `quotaStatus` */
+export interface QuotaStatus {
+  collection: string
+  entries: number
+  /** Approximate bytes */
+  bytes: number
+  maxEntries: number | null
+  maxBytes: number | null
+  policy: 'reject' | 'evict_oldest'
+  orderField: string | null
+  exceeded: boolean
+  /** Writes refused since the database was opened */
+  rejected: number
+  /** Entries evicted since the database was opened */
+  evicted: number
+}
+/** Options for `batch_set_parallel` */
+export interface BatchSetOptions {
+  /** Validate values against registered schemas before writing (default: true) */
+  validate?: boolean
+  /** "reject" (default) fails the whole batch on any invalid value, "skip" writes the rest */
+  onInvalid?: 'reject' | 'skip'
+  /** Transaction to record the writes in (see `WriteOptions::txn`) */
+  txn?: number
 }
 /** Parallel left outer join config */
 export interface JoinConfig {
@@ -27,6 +289,556 @@ export interface JoinConfig {
     foreignField: string;
     as: string;
 }
+/** Options accepted by write operations */
+export interface WriteOptions {
+  /** Permit writes into the reserved `__jsondb__` namespace */
+  allowInternal?: boolean
+  /** Lease owner performing the write (checked when lease enforcement is on) */
+  owner?: string
+  /** Why a delete happens (default 'explicit'); reported to delete listeners and by changesSince */
+  reason?: DeleteReason
+  /** Array padding for this write, overriding the database's */
+  arrayPadding?: ArrayPadding
+  /** Permit replacing or clearing the whole tree with the root path "" */
+  allowRootOverwrite?: boolean
+  /** Transaction the write belongs to, from `beginTransaction` */
+  txn?: number
+}
+/**
+ * A check evaluated by `atomic` against the value at `path`: any filter
+ * operator, or "exists" / "notexists" (which ignore `value`)
+ */
+export interface Condition {
+  path: string
+  op: string
+  value?: any
+  /** "strict" or "canonical" (default: the database's `setEquality` mode) */
+  equality?: 'strict' | 'canonical'
+}
+/**
+ * One write applied by `atomic`: "set", "delete", "push" (skipped if the array
+ * already holds the value, like `push`), "add" or "subtract" (missing counts as 0)
+ */
+export interface BatchOp {
+  type: string
+  path: string
+  value?: any
+}
+/** What a write to an array index past the end does */
+export type ArrayPadding = 'pad_null' | 'error' | 'append'
+/** Options for JSONDatabase.set */
+export interface SetOptions {
+  /** Array padding for this write, overriding the database's */
+  arrayPadding?: ArrayPadding
+  /** Permit replacing the whole tree with the root path '' (default: false) */
+  allowRootOverwrite?: boolean
+  /** Lease owner performing the write (checked when lease enforcement is on) */
+  owner?: string
+  /** Transaction the write belongs to (`Transaction.id`) */
+  txn?: number
+}
+/** Options for atomic */
+export interface AtomicOptions {
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+}
+/** Options for atomicUpdate and atomicUpdateMany */
+export interface AtomicUpdateOptions {
+  /** Longest the updater may run; past it nothing is written and the call fails with ERR_LIMIT (default: 1000) */
+  maxCallbackMs?: number
+  /** Lease owner performing the write (checked when lease enforcement is on) */
+  owner?: string
+}
+/** Options accepted by read operations */
+export interface ReadOptions {
+  /** Include the reserved `__jsondb__` namespace in results */
+  includeInternal?: boolean
+  /** Transaction the read is part of: it sees the writes of an exclusive transaction that other reads do not */
+  txn?: number
+}
+/** Contiguous block of sequence values handed out by reserveSequence (inclusive) */
+export interface SequenceRange {
+  start: number
+  end: number
+}
+export interface TransactionInfo {
+  active: boolean
+  /** Id of the transaction described */
+  id?: number
+  isolation?: 'read_uncommitted' | 'exclusive'
+  /** Transactions open in this database */
+  open: number
+  /** Undo entries recorded (at most one per path per savepoint) */
+  entries: number
+  /** Approximate bytes of old values held in memory */
+  undoBytes: number
+  /** Bytes of old values spilled to the temp file */
+  spilledBytes: number
+  savepoints: number
+  maxUndoBytes?: number
+}
+/** Options for mergeThreeWay */
+export interface MergeOptions {
+  /** 'report' (default) leaves conflicts untouched, 'ours' keeps current values, 'theirs' takes incoming ones */
+  policy?: 'report' | 'ours' | 'theirs'
+}
+export interface PageOptions {
+  /** 'key' (default) walks documents by key; anything else names a field to walk by, the key breaking ties */
+  stableBy?: string
+  /** The `next` cursor of the previous page */
+  after?: string
+  /** Documents per page (default 100) */
+  limit?: number
+  /** Only documents matching every filter */
+  filters?: Array<QueryFilter>
+}
+export interface UpdateManyOptions {
+  /** 'merge' (default) shallow-merges the patch into object documents, 'replace' swaps them */
+  mode?: 'merge' | 'replace'
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+  /** Transaction to record the writes in (see `WriteOptions::txn`) */
+  txn?: number
+}
+export interface DeleteManyOptions {
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+  /** Transaction to record the removals in (see `WriteOptions::txn`) */
+  txn?: number
+}
+export interface UpsertOptions {
+  /** 'replace' (default) swaps the whole document, 'merge' shallow-merges into it */
+  mode?: 'replace' | 'merge'
+  /** Validate documents against registered schemas (default: true) */
+  validate?: boolean
+  /** Documents written per write-lock hold (default: 1000) */
+  batchSize?: number
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+}
+export interface IndexKeysOptions {
+  /** Lowest key returned (null: no lower bound); numeric bounds only match numeric keys */
+  start?: any
+  /** Highest key returned (null: no upper bound) */
+  end?: any
+  /** Leave out a key equal to start */
+  startExclusive?: boolean
+  /** Leave out a key equal to end */
+  endExclusive?: boolean
+  /** Only keys starting with this string */
+  prefix?: string
+  /** Keys to skip (after prefix and ordering are applied) */
+  offset?: number
+  /** Maximum number of keys returned */
+  limit?: number
+  /** Descending order; with limit 1 this is the last (largest) key */
+  reverse?: boolean
+}
+export interface IndexOptions {
+  /** Leave out documents whose field is null, as well as those without it */
+  sparse?: boolean
+}
+export interface IndexRangeOptions {
+  /** Leave out keys equal to start */
+  startExclusive?: boolean
+  /** Leave out keys equal to end */
+  endExclusive?: boolean
+  /** Maximum number of paths returned */
+  limit?: number
+}
+export interface ImportRule {
+  from: string
+  to: string
+  keyField?: string
+}
+export interface ImportOptions {
+  /** Existing target keys: 'error' (default, nothing is imported), 'skip' or 'overwrite' */
+  onDuplicate?: 'error' | 'skip' | 'overwrite'
+  /** Skip items that fail a registered schema (default: true) */
+  validate?: boolean
+  /** Build the report without writing anything */
+  dryRun?: boolean
+}
+export interface DeletePrefixOptions {
+  /** Only count what would be removed */
+  dryRun?: boolean
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+  /** Transaction to record the removal in (see `WriteOptions::txn`) */
+  txn?: number
+}
+/** Options for renameField */
+export interface RenameFieldOptions {
+  /** Overwrite the target field when it already exists (default: skip the document) */
+  overwrite?: boolean
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+  /** Transaction to record the renames in (see `WriteOptions::txn`) */
+  txn?: number
+}
+/** Result of a renameField migration */
+export interface RenameFieldResult {
+  changed: number
+  skipped: number
+  /** Registered indexes on the old field name that need re-registering/reindexing */
+  affectedIndexes: Array<string>
+}
+/** Options for mapCollection */
+export interface MapCollectionOptions {
+  /** Return the would-be changes instead of applying them */
+  dryRun?: boolean
+  /** Documents handed to the mapper per read/write lock cycle (default: 500) */
+  batchSize?: number
+  /** Lease owner, checked when lease enforcement is on */
+  owner?: string
+}
+/** Options for forEach */
+export interface ForEachOptions {
+  /** Documents cloned per read-lock hold (default: 500) */
+  batchSize?: number
+  /** 'sync' (default) returns the result; 'threadsafe' iterates on a worker thread and returns a Promise */
+  mode?: 'sync' | 'threadsafe'
+}
+/** Result of forEach; `skipped` counts keys deleted during the iteration */
+export interface ForEachResult {
+  processed: number
+  skipped: number
+  errors: Array<{ key: string; error: string }>
+}
+/** WAL commit queue state; only `enabled` is present when the WAL is off */
+export interface WalPressure {
+  enabled: boolean
+  queueDepth?: number
+  queueCapacity?: number
+  /** queueDepth / queueCapacity */
+  utilization?: number
+  policy?: 'block' | 'error' | 'drop_oldest'
+  /** Writes that waited for room under 'block', and the total time they waited */
+  blockedAppends?: number
+  blockedMs?: number
+  /** Writes failed with ERR_WAL_BUSY under 'error' */
+  rejected?: number
+  /** Queued writes discarded under 'drop_oldest' */
+  dropped?: number
+}
+/** Options for `setWriteRateLimit` */
+export interface WriteRateLimitOptions {
+  /** 'block' (default) waits for tokens; 'error' fails with ERR_THROTTLED */
+  policy?: 'block' | 'error'
+  /** Longest a blocked write waits before failing with ERR_THROTTLED (default 1000) */
+  maxWaitMs?: number
+}
+/** Options for `setMaxValueBytes` */
+export interface ValueLimitOptions {
+  /** Limit only values written under this path prefix (default: every path) */
+  pathPrefix?: string
+  /** 'reject' (default) fails with ERR_LIMIT; 'warn' writes the value and reports an 'oversizedValue' trace span */
+  mode?: 'reject' | 'warn'
+}
+/** Options for `freezePath` */
+export interface FreezeOptions {
+  /** Keep the freeze in the reserved namespace so it survives a restart (default: false) */
+  persist?: boolean
+}
+/** Write limiter state; the counters are present even when no limit is set */
+export interface WriteThrottleStatus {
+  enabled: boolean
+  opsPerSec?: number
+  burst?: number
+  /** Tokens available now; negative while blocked writes hold reservations */
+  tokens?: number
+  policy?: 'block' | 'error'
+  maxWaitMs?: number
+  /** Writes failed with ERR_THROTTLED */
+  throttled: number
+  /** Writes delayed under 'block', and the total time they waited */
+  waits: number
+  waitedMs: number
+}
+/** One WAL record; `context` is the write context set when it was logged */
+export interface WalChange {
+  lsn: number
+  timestamp: number
+  /** 'begin', 'commit' and 'abort' mark transactions; they have no path or value */
+  op: 'set' | 'delete' | 'begin' | 'commit' | 'abort'
+  path: string
+  context: unknown
+  /** Deletes only */
+  reason?: DeleteReason
+  value?: unknown
+  /** Transaction the record belongs to, if any */
+  txn?: number
+}
+export type DeleteReason = 'explicit' | 'ttl' | 'quota' | 'cascade'
+/** Result of `changesFor`; with `resyncRequired` changes were dropped and `changes` is empty */
+export interface ConsumerChanges {
+  changes: WalChange[]
+  position: number
+  resyncRequired: boolean
+  headLsn: number
+}
+export interface ConsumerInfo {
+  name: string
+  position: number
+  ackedAt: number
+  resyncRequired: boolean
+}
+/** One removed path, as passed to the onDelete callback */
+export interface DeleteEvent {
+  path: string
+  reason: DeleteReason
+  /** WAL record that removed the path; null when it was not logged on its own */
+  lsn: number | null
+  /** Only when the listener asked for values */
+  value?: unknown
+}
+export type TraceOp =
+  | 'get' | 'query' | 'queryMulti' | 'queryString' | 'snapshotQuery' | 'aggregate' | 'snapshotAggregate'
+  | 'indexAggregate' | 'lookup' | 'save' | 'recovery' | 'maintenance' | 'oversizedValue'
+/** One finished operation, as passed to the setTraceCallback callback */
+export interface TraceSpan {
+  op: TraceOp
+  /** Collection or value path; the index name for indexAggregate, '' for save and recovery */
+  path: string
+  durationUs: number
+  /** Items the operation looked at (WAL records for recovery), null if not applicable */
+  itemsScanned: number | null
+  /** Items in the result (operations applied for recovery), null if not applicable */
+  itemsReturned: number | null
+  /** Whether the work was large enough to run on the thread pool */
+  parallel: boolean
+  /** The error the operation failed with, if it did */
+  error: string | null
+}
+export interface TraceOptions {
+  /** Size (approximate, in bytes) a get result must reach to be traced (default 65536) */
+  getThresholdBytes?: number
+}
+export interface TransactionOptions {
+  /**
+   * 'read_uncommitted' (default): every read sees the writes as they land. 'exclusive': no other
+   * transaction may be open, writes outside it fail with ERR_TRANSACTION_ACTIVE, and get/getSerialized/has
+   * outside it (reader handles included) see the data as it was when it began
+   */
+  isolation?: 'read_uncommitted' | 'exclusive'
+}
+export interface BulkLoadOptions {
+  /** Keep validating writes against registered schemas (default false) */
+  keepSchemas?: boolean
+}
+/** What endBulkLoad did */
+export interface BulkLoadReport {
+  writes: number
+  loadMs: number
+  rebuildMs: number
+  saveMs: number
+  totalMs: number
+  indexesRebuilt: number
+  countersRebuilt: number
+}
+export interface DeleteListenerOptions {
+  /** Include each removed value in its event (default: false; values can be large) */
+  includeValues?: boolean
+}
+export interface WalInspectOptions {
+  /** Only records with a greater LSN */
+  fromLsn?: number
+  /** At most this many records (oldest first) */
+  limit?: number
+  /** Include each record's value (default: false) */
+  includeValues?: boolean
+}
+/** Reported periodically while the WAL is replayed; records are read first, then applied */
+export interface RecoveryProgress {
+  opsReplayed: number
+  bytesRead: number
+  totalBytes: number
+}
+/** State of a deferred WAL recovery */
+export interface RecoveryStatus extends RecoveryProgress {
+  state: 'pending' | 'running' | 'done'
+}
+/** What one save wrote and how long it took */
+/** One lazy collection in lazyStatus() */
+export interface LazyCollectionStatus {
+  /** Keys in the on-disk index */
+  stored: number
+  resident: number
+  pinned: number
+  /** Entries written since the last save (never evicted) */
+  dirty: number
+  /** Replaced as a whole since the last save */
+  replaced: boolean
+  /** Fully loaded; exempt from eviction until the next save */
+  complete: boolean
+  dataBytes: number
+}
+/** Only `enabled` is present when no lazy collections are configured */
+export interface LazyStatus {
+  enabled: boolean
+  maxResident?: number
+  resident?: number
+  loads?: number
+  evictions?: number
+  fullLoads?: number
+  collections?: Record<string, LazyCollectionStatus>
+}
+export interface SaveReport {
+  /** Completion time (ms since epoch) */
+  at: number
+  bytes: number
+  /** Size of the file the save replaced, null if there was none */
+  previousBytes: number | null
+  serializeMs: number
+  fsyncMs: number
+  totalMs: number
+  indexesSaved: number
+  /** Indexes unchanged since their last save */
+  indexesSkipped: number
+  walOpsTruncated: number
+  /** Size of the WAL the save truncated: the changes it persisted */
+  walBytesTruncated: number
+  /** Saves keep no backup generations, so this is always false */
+  backupRotated: boolean
+}
+export type MaintenanceTaskName = 'checkpoint' | 'save_if_dirty' | 'purge_expired' | 'prune_backups' | 'refresh_view'
+export interface MaintenanceTask {
+  task: MaintenanceTaskName
+  /** Time from the end of one run to the start of the next (at least 10) */
+  intervalMs: number
+  /** prune_backups: `keep` (default 5) and `maxAgeMs`; refresh_view: the view `name` */
+  args?: { keep?: number; maxAgeMs?: number; name?: string }
+}
+export interface MaintenanceTaskStatus {
+  task: MaintenanceTaskName
+  intervalMs: number
+  args: MaintenanceTask['args'] | null
+  running: boolean
+  runs: number
+  failures: number
+  lastRunAt: number | null
+  lastDurationMs: number | null
+  /** Error of the latest run; null once a run succeeds */
+  lastError: string | null
+  /** What the latest successful run returned, e.g. a SaveReport or `{ skipped }` */
+  lastResult: unknown
+  /** Null while the task is running */
+  nextRunAt: number | null
+}
+export interface MaintenanceStatus {
+  running: boolean
+  tasks: Array<MaintenanceTaskStatus>
+}
+/** Thresholds for `advisorReport`; unset fields keep their defaults */
+export interface AdvisorOptions {
+  /** How far back save history is considered (default: one hour) */
+  windowMs?: number
+  /** Size below which byte-based findings are not reported (default: 1MB) */
+  minBytes?: number
+  /** Bytes rewritten by saves per byte of logged changes (default: 100) */
+  saveAmplification?: number
+  /** WAL bytes per byte of data file growth (default: 50) */
+  walGrowth?: number
+  /** Share of the data file that is whitespace (default: 0.25) */
+  prettyOverhead?: number
+  /** Index size per byte of the indexed collection (default: 1) */
+  indexRatio?: number
+}
+/** Options for `exportArchive` */
+export interface ExportOptions {
+  /** Replace an existing file at the output path (default: false) */
+  overwrite?: boolean
+  /** zstd-compress the entries (default: false) */
+  compress?: boolean
+}
+export interface ArchiveEntry {
+  name: string
+  kind: 'data' | 'index'
+  /** Indexed field (indexes only) */
+  field?: string
+  /** Collection holding the indexed documents (indexes only, absent while empty) */
+  collection?: string
+  /** Set for sparse indexes */
+  sparse?: boolean
+  bytes: number
+  /** Size in the archive; smaller than `bytes` when compressed */
+  stored: number
+  crc32: number
+}
+export interface ArchiveManifest {
+  format: number
+  dataFormat: number
+  indexFormat: number
+  engineVersion: string
+  createdAt: number
+  source: string
+  compressed: boolean
+  entries: Array<ArchiveEntry>
+  schemas: Record<string, Schema>
+}
+/** How insert() keys new documents of a collection */
+export type IdStrategy =
+  | 'uuid4'
+  | 'ulid'
+  | 'nanoid'
+  | { type: 'uuid4' | 'ulid' }
+  | { type: 'nanoid'; length?: number }
+  | { type: 'sequence'; name: string }
+  | { type: 'field'; field: string }
+export type AdvisorCode =
+  | 'SAVE_AMPLIFICATION'
+  | 'PRETTY_FILE_OVERHEAD'
+  | 'WAL_OUTGROWS_DATA'
+  | 'WAL_BACKLOG'
+  | 'WAL_BACKPRESSURE'
+  | 'INDEX_LARGER_THAN_COLLECTION'
+export interface AdvisorFinding {
+  code: AdvisorCode
+  severity: 'info' | 'warning' | 'critical'
+  message: string
+  metrics: Record<string, unknown>
+}
+export interface AdvisorReport {
+  generatedAt: number
+  windowMs: number
+  /** Most severe first */
+  findings: Array<AdvisorFinding>
+  /** The numbers the findings were derived from: saves, file, wal, indexes, stats */
+  inputs: Record<string, unknown>
+}
+/** Options for `scanAnomalies` */
+export interface ScanAnomaliesOptions {
+  /** Findings listed per kind; all are counted (default: 100) */
+  limit?: number
+  /** Levels walked below the scanned path before reporting deep nesting (default: the path depth limit) */
+  maxDepth?: number
+  /** Remove the trailing nulls of every array that has them, as logged writes */
+  fixTrailingNulls?: boolean
+}
+export type AnomalyKind =
+  | 'unsafeInteger'
+  | 'replacementChar'
+  | 'controlChar'
+  | 'emptyKey'
+  | 'unaddressableKey'
+  | 'trailingNulls'
+  | 'deepNesting'
+export interface Anomaly {
+  /** Where it was found; key findings name the object holding the key */
+  path: string
+  kind: AnomalyKind
+  detail: string
+}
+export interface AnomalyReport {
+  /** Grouped by kind, at most `limit` per kind */
+  anomalies: Array<Anomaly>
+  /** Every finding counted, by kind */
+  counts: Partial<Record<AnomalyKind, number>>
+  /** Whether some kind had more findings than listed */
+  truncated: boolean
+  /** Arrays whose trailing nulls were trimmed */
+  fixed: Array<string>
+}
 /** System resource info */
 export interface SystemInfo {
   availableCores: number
@@ -42,26 +854,185 @@ export declare class NativeDb {
     lockMode: string,
     durability: string,
     walBatchSize?: number,
-    walFlushMs?: number
+    walFlushMs?: number,
+    maxUndoBytes?: number,
+    undoSpill?: boolean,
+    openRetries?: number,
+    deferRecovery?: boolean,
+    walQueueCapacity?: number,
+    walOverflowPolicy?: 'block' | 'error' | 'drop_oldest',
+    maxPathDepth?: number,
+    maxKeyLength?: number,
+    forbiddenPathChars?: string,
+    parallelRecovery?: boolean,
+    walArchive?: boolean,
+    lazyCollections?: Array<string>,
+    lazyMaxResident?: number,
+    enforceSchemas?: boolean
   ): NativeDb
   /** Get system resource information for adaptive parallelism */
   getSystemInfo(): SystemInfo
   load(): void
+  /**
+   * Replay the WAL of a database opened with deferRecovery; until then reads and
+   * writes fail with ERR_RECOVERY_PENDING. Returns the replay report.
+   */
+  recover(progress?: (progress: RecoveryProgress) => void): any
+  /** recover() on a background thread */
+  recoverAsync(progress?: (progress: RecoveryProgress) => void): Promise<any>
+  recoveryStatus(): RecoveryStatus
   save(): void
+  /** save() returning a SaveReport; the latest is kept for persistenceStatus() */
+  saveWithReport(): SaveReport
+  /** Save history of this process */
+  persistenceStatus(): { saves: number; bytesWritten: number; lastSave: SaveReport | null }
+  /**
+   * Run maintenance tasks on one background thread, replacing any schedule
+   * already running (an empty list just stops it). Saves are skipped while a
+   * transaction or bulk load is open; every run is traced as "maintenance".
+   * close() stops the thread after any running task.
+   */
+  scheduleMaintenance(tasks: Array<MaintenanceTask>): void
+  /** What the scheduled maintenance tasks last did */
+  maintenanceStatus(): MaintenanceStatus
+  /** Findings about the usage pattern with stable codes, severities and the numbers behind them */
+  advisorReport(options?: AdvisorOptions | undefined | null): AdvisorReport
+  /** Likely bad data under `path` (default: everything), grouped by kind; only fixTrailingNulls writes */
+  scanAnomalies(path?: string | undefined | null, options?: ScanAnomaliesOptions | undefined | null): AnomalyReport
+  /** Drop a lazy entry, or all of a lazy collection, from memory; returns how many entries were dropped */
+  evict(path: string): number
+  /** Load a lazy entry and keep it resident until unpin() */
+  pin(path: string): boolean
+  unpin(path: string): boolean
+  lazyStatus(): LazyStatus
+  /** Value at a path as of a timestamp, from the WAL archive (needs walArchive) */
+  valueAt(path: string, timestampMs: number): any
+  /** Drop archived history before a timestamp, keeping the checkpoint that covers it */
+  pruneArchive(beforeMs: number): { checkpointsRemoved: number; segmentsRemoved: number; oldest: number | null }
+  /**
+   * Write the whole engine state to one archive file: the in-memory data
+   * (including the reserved namespace and every lazy entry), all indexes and
+   * registered schemas, with a manifest of format versions and checksums.
+   * Fails with ERR_TRANSACTION_ACTIVE while an exclusive transaction is open.
+   */
+  exportArchive(outPath: string, options?: ExportOptions | undefined | null): ArchiveManifest
+  /**
+   * Restore an `exportArchive` file as a new database at `targetPath` and open it
+   * with default options. A damaged archive fails with ERR_CORRUPT and leaves no
+   * files behind. The target must not exist.
+   */
+  static importArchive(archivePath: string, targetPath: string): NativeDb
+  /** The manifest of an archive; entries are verified by importArchive */
+  static archiveManifest(archivePath: string): ArchiveManifest
   /** v4.5: Explicit sync for durability */
   sync(): void
   /** v4.5: Get WAL status */
   walStatus(): any
+  walPressure(): WalPressure
+  /** Token-bucket write rate limit; null removes it. Reads and rollbacks are never throttled. */
+  setWriteRateLimit(opsPerSec: number | undefined | null, burst: number, options?: WriteRateLimitOptions | undefined | null): void
+  writeThrottleStatus(): WriteThrottleStatus
+  /** Attach `context` to every following WAL record (at most 1KB serialized); null clears it */
+  setWriteContext(context: any): void
+  clearWriteContext(): void
+  /** WAL records since the last save, oldest first */
+  walInspect(options?: WalInspectOptions | undefined | null): { records: WalChange[]; stopReason: string | null }
+  /**
+   * Event per removed path from delete, deleteMany, deletePrefix and quota
+   * evictions, queued onto the event loop in WAL order. No callback removes it.
+   */
+  onDelete(callback?: (event: DeleteEvent) => void, options?: DeleteListenerOptions): void
+  /**
+   * Span per significant operation, queued once its locks are released and
+   * delivered on the event loop. No callback removes it.
+   */
+  setTraceCallback(callback?: (span: TraceSpan) => void, options?: TraceOptions): void
+  /**
+   * Until endBulkLoad, writes skip the WAL, coalescing, delete events and the
+   * upkeep of counters, indexes and views; schemas only apply with keepSchemas
+   */
+  beginBulkLoad(options?: BulkLoadOptions | undefined | null): void
+  /** Rebuild indexes and counters in parallel, then save */
+  endBulkLoad(): BulkLoadReport
+  isBulkLoading(): boolean
+  /** WAL records after `lsn`, with values */
+  changesSince(lsn: number, limit?: number | undefined | null): WalChange[]
+  /** Refuse writes touching a path prefix with ERR_FROZEN; false if already frozen that way */
+  freezePath(pathPrefix: string, options?: FreezeOptions | undefined | null): boolean
+  unfreezePath(pathPrefix: string): boolean
+  frozenPaths(): Array<{ path: string; persisted: boolean }>
+  /** Whether a write at `path` would fail with ERR_FROZEN */
+  isFrozen(path: string): boolean
+  /** Persist a consumer's position in the change feed (an LSN), creating it if new */
+  ackChanges(consumer: string, seq: number): void
+  /** Last position acked by a consumer, or null */
+  consumerPosition(consumer: string): any
+  /** Changes after a consumer's acked position, across saves and restarts */
+  changesFor(consumer: string, limit?: number | undefined | null): ConsumerChanges
+  listConsumers(): ConsumerInfo[]
+  /** Forget a consumer; returns false if unknown */
+  dropConsumer(consumer: string): boolean
+  /** Most WAL records a save keeps for consumers that have not acked them (default 100000) */
+  setChangeRetention(maxRecords: number): void
+  /**
+   * Override durability for writes under a path prefix (longest prefix wins).
+   * 'sync' waits for fsync per write; 'none' skips the WAL (lost on crash until save());
+   * 'default' removes the override
+   */
+  setPathDurability(pathPrefix: string, mode: 'none' | 'lazy' | 'batched' | 'sync' | 'default'): void
+  /** Configured per-path durability overrides as { prefix: mode } */
+  durabilityConfig(): Record<string, string>
+  /**
+   * Stage sets under the prefixes and apply only the latest value per path every window.
+   * Staged writes are not in the WAL until applied (flushCoalesced, save, sync, close,
+   * a transaction, or any other read/write touching a staged path; reading the
+   * staged path itself returns its staged value).
+   */
+  enableWriteCoalescing(windowMs: number, pathPrefixes: Array<string>): void
+  /** Apply pending staged writes and stop coalescing; returns paths written */
+  disableWriteCoalescing(): number
+  /** Apply pending staged writes now; returns paths written */
+  flushCoalesced(): number
   /**
    * Execute batch set operations in parallel when beneficial
    * Automatically falls back to sequential for small batches
    */
-  batchSetParallel(operations: Array<[string, any]>): ParallelResult
+  /**
+   * Execute batch set operations in parallel when beneficial. Values are
+   * validated against registered schemas on the rayon pool before the write
+   * lock is taken.
+   */
+  batchSetParallel(operations: Array<[string, any]>, options?: BatchSetOptions | undefined | null): ParallelResult
   /**
    * Parallel filter/query on a collection
-   * Uses rayon for CPU-bound filtering when data is large enough
+   * Uses rayon for CPU-bound filtering when data is large enough, and an index
+   * on the first filter's field when there is one
+   */
+  parallelQuery(path: string, filters: Array<QueryFilter>, options?: QueryOptions | undefined | null): any
+  /**
+   * The same filters over several collections; matches are concatenated in
+   * `paths` order, then sorted, offset and limited as one list
+   */
+  parallelQueryMulti(paths: Array<string>, filters: Array<QueryFilter>, options?: MultiQueryOptions | undefined | null): MultiQueryResult
+  /** How parallelQuery would run: from an index or by a scan, and why */
+  explainQuery(path: string, filters: Array<QueryFilter>, options?: QueryOptions | undefined | null): QueryPlan
+  /** Scan a collection and store planner statistics for its indexed fields */
+  analyze(collectionPath: string, options?: AnalyzeOptions | undefined | null): CollectionStats
+  /** Stats stored by analyze, with writes since and staleness; null if never analyzed */
+  collectionStats(path: string): CollectionStats | null
+  /** Run a string query against a collection: matches, sorted and paged */
+  queryString(path: string, query: string): any
+  /**
+   * One page of a stable walk over an object collection: writes between pages
+   * never repeat or skip documents. Pass `next` as `after` for the following page.
    */
-  parallelQuery(path: string, filters: Array<QueryFilter>): any
+  getPage(path: string, options?: PageOptions | undefined | null): { items: Array<{ key: string; value: any }>; next: string | null }
+  /** Parse a string query without running it */
+  static parseQuery(query: string): ParsedQuery
+  /** Rewrite the value at `path` in canonical form (sorted keys, normalized numbers) as one logged write */
+  canonicalize(path: string): void
+  /** Canonical serialization: keys sorted at every depth, normalized numbers, no whitespace */
+  static canonicalString(value: any): string
   /** Parallel aggregation operations */
   parallelAggregate(path: string, operation: string, field?: string | undefined | null): any
   /** 
@@ -69,22 +1040,352 @@ export declare class NativeDb {
    * Returns collection with embedded matches
    */
   parallelLookup(leftPath: string, rightPath: string, leftField: string, rightField: string, asField: string): any
-  get(path: string): any
-  set(path: string, value: any): void
-  has(path: string): boolean
-  delete(path: string): void
-  push(path: string, value: any): void
-  registerIndex(name: string, field: string): void
+  get(path: string, options?: ReadOptions | undefined | null): any
+  /** `get` returning a JSON string; not subject to the result size cap */
+  getSerialized(path: string, options?: ReadOptions | undefined | null): string
+  /** Approximate size in bytes of the value at `path` (0 if missing) */
+  estimateSize(path: string): number
+  /** Cap the approximate size of results converted to JS values (0 = unlimited, default 512MB) */
+  setMaxResultBytes(maxBytes: number): void
+  /** Cap the estimated serialized size of values written under a path prefix (default: every path); null removes the limit */
+  setMaxValueBytes(maxBytes?: number | undefined | null, options?: ValueLimitOptions | undefined | null): void
+  set(path: string, value: any, options?: WriteOptions | undefined | null): void
+  /** Resolve several relative pointers (dot or slash notation) below a base path; null for misses */
+  getPointers(basePath: string, pointers: Array<string>): Record<string, any>
+  /** Store a value compressed (zstd); get and query filters see the original value */
+  setCompressed(path: string, value: any, options?: WriteOptions | undefined | null): void
+  /** Make set() compress string values of at least `bytes` bytes (0 = off) */
+  setCompressThreshold(bytes: number): void
+  /** What writes to an array index past the end do: 'pad_null' (default), 'error' or 'append' */
+  setArrayPadding(policy: string): void
+  /** The current array padding policy */
+  arrayPadding(): string
+  /**
+   * Rebuild the subtree at `path` (default: whole tree) with exactly-sized
+   * allocations, one child at a time under the write lock
+   */
+  compactMemory(path?: string | undefined | null): { path: string; chunks: number; beforeBytes: number; afterBytes: number; reclaimedBytes: number; ms: number }
+  /** Make `delete` compact the parent when the removed subtree is at least `bytes` large (0 = off) */
+  setReclaimThreshold(bytes: number): void
+  /** Apply several writes relative to a base path under one lock */
+  setPointers(basePath: string, entries: Array<[string, any]>, options?: WriteOptions | undefined | null): void
+  /**
+   * Check every condition and, only if all pass, apply every op, all under
+   * one hold of the write lock. Returns whether the ops were applied. Needs
+   * no transaction, so concurrent callers never contend for one. If an op
+   * fails (a quota, constraint or type error), the ops before it are undone.
+   */
+  atomic(conditions: Array<Condition>, ops: Array<BatchOp>, options?: AtomicOptions | undefined | null): boolean
+  /**
+   * Read-modify-write of one path with a synchronous updater `(current) => next`,
+   * called with the write lock held. Returning undefined leaves the value as it is.
+   * Returns the value now stored (null if still missing).
+   */
+  atomicUpdate(path: string, updater: (current: any) => any, options?: AtomicUpdateOptions | undefined | null): any
+  /** atomicUpdate over several paths: the updater gets and returns an array of values in `paths` order */
+  atomicUpdateMany(paths: Array<string>, updater: (current: Array<any>) => Array<any> | undefined, options?: AtomicUpdateOptions | undefined | null): Array<any>
+  has(path: string, options?: ReadOptions | undefined | null): boolean
+  /** Delete a path; returns what cascade rules did */
+  delete(path: string, options?: WriteOptions | undefined | null): CascadeCounts
+  /**
+   * Bulk delete: `a.b` / `a.b.*` clears every child of `a.b`, `a.b_*` removes children of `a`
+   * whose key starts with `b_`. Returns the number of children removed, with cascade counts.
+   */
+  deletePrefix(pathPrefix: string, options?: DeletePrefixOptions | undefined | null): { deleted: number; cascaded?: CascadeCounts }
+  /** Insert or update documents keyed by `keyField`; returns `{inserted, updated, failed: [{index, error}]}` */
+  /**
+   * Choose how `insert` keys new documents of a collection: "uuid4" (the
+   * default), "ulid", "nanoid" (`{type: "nanoid", length}`), a sequence
+   * (`{type: "sequence", name}`) or a field of the document (`{type: "field",
+   * field}`). Persisted with the data; null restores the default.
+   */
+  setIdStrategy(collectionPath: string, strategy: IdStrategy | null): void
+  /** The effective ID strategy of a collection, with `configured: false` for the uuid4 default */
+  idStrategy(collectionPath: string): { type: string; configured: boolean; length?: number; name?: string; field?: string }
+  /** Add a document to an object-map collection under a key from its ID strategy, returning the key */
+  insert(collectionPath: string, doc: any, options?: WriteOptions | undefined | null): string
+  /** `insert` for several documents; ERR_CONFLICT if a key already exists */
+  insertMany(collectionPath: string, docs: Array<any>, options?: WriteOptions | undefined | null): Array<string>
+  upsertMany(collectionPath: string, docs: Array<any>, keyField: string, options?: UpsertOptions | undefined | null): any
+  /**
+   * Update every document matching the filters; returns `{updated, indices}` for array
+   * collections (updated in place) or `{updated, keys}` for object collections
+   */
+  updateMany(collectionPath: string, filters: Array<QueryFilter>, patch: any, options?: UpdateManyOptions | undefined | null): any
+  /**
+   * Delete every document matching the filters; array elements are removed in descending
+   * index order. Returns `{deleted, indices}` or `{deleted, keys}`.
+   */
+  deleteMany(collectionPath: string, filters: Array<QueryFilter>, options?: DeleteManyOptions | undefined | null): any
+  push(path: string, value: any, options?: WriteOptions | undefined | null): void
+  /** Append several values to an array, skipping ones already present */
+  pushMany(path: string, values: Array<any>, options?: WriteOptions | undefined | null): void
+  /** Remove `deleteCount` elements at `start`, insert `items` there, and return the removed elements */
+  splice(path: string, start: number, deleteCount: number, items?: Array<any> | undefined | null, options?: WriteOptions | undefined | null): Array<any>
+  /** Move one array element to a new position atomically */
+  reorder(path: string, fromIndex: number, toIndex: number, options?: WriteOptions | undefined | null): void
+  /** Rearrange an array by a full permutation of its current indices */
+  reorderAll(path: string, newOrder: Array<number>, options?: WriteOptions | undefined | null): void
+  /** Insert a new key into an object collection before `referenceKey` (appended if it is missing) */
+  insertBefore(collectionPath: string, newKey: string, value: any, referenceKey: string, options?: WriteOptions | undefined | null): KeyPlacement
+  /** Insert a new key into an object collection after `referenceKey` (appended if it is missing) */
+  insertAfter(collectionPath: string, newKey: string, value: any, referenceKey: string, options?: WriteOptions | undefined | null): KeyPlacement
+  /** Move an existing key before `beforeKey`, or last when it is omitted or missing */
+  moveKey(collectionPath: string, key: string, beforeKey?: string | undefined | null, options?: WriteOptions | undefined | null): KeyPlacement
+  /**
+   * Three-way merge of an edited subtree against the revision it started from.
+   * Returns {applied, resolved, conflicts: [{path, base, ours, theirs}]}
+   */
+  mergeThreeWay(path: string, base: any, theirs: any, options?: MergeOptions | undefined | null): any
+  /** Rename a (dot-notation) field in every document of a collection */
+  renameField(collectionPath: string, fromField: string, toField: string, options?: RenameFieldOptions | undefined | null): RenameFieldResult
+  /**
+   * Transform documents with a synchronous mapper; returning undefined skips the document.
+   * Returns {updated, unchanged, conflicts} or, for dry runs, {dryRun, changes, unchanged}
+   */
+  mapCollection(collectionPath: string, filters: Array<QueryFilter> | undefined | null, mapper: (doc: any, key: string) => any, options?: MapCollectionOptions | undefined | null): any
+  /**
+   * Call `callback(doc, key)` for every matching document, cloning one batch at
+   * a time under a short read lock. Keys deleted meanwhile are skipped.
+   */
+  forEach(collectionPath: string, filters: Array<QueryFilter> | undefined | null, callback: (doc: any, key: string) => unknown, options?: (ForEachOptions & { mode?: 'sync' }) | undefined | null): ForEachResult
+  forEach(collectionPath: string, filters: Array<QueryFilter> | undefined | null, callback: (doc: any, key: string) => unknown, options: ForEachOptions & { mode: 'threadsafe' }): Promise<ForEachResult>
+  /** Increment a named sequence and return the new value */
+  /**
+   * One-time migration from a lowdb / plain JSON file. Rules copy `from` in the source
+   * under `to`; arrays are re-keyed by `keyField` into an object map.
+   */
+  importFile(sourcePath: string, mapping: Array<ImportRule>, options?: ImportOptions | undefined | null): any
+  nextSequence(name: string): number
+  /** Reserve `count` consecutive values of a sequence in one step */
+  reserveSequence(name: string, count: number): SequenceRange
+  /** Current value of a sequence (0 if it has never been used) */
+  currentSequence(name: string): number
+  /** Reset a sequence to an explicit value */
+  setSequence(name: string, value: number): void
+  /** Try to lease a path; false if another owner holds an overlapping lease */
+  acquireLease(path: string, owner: string, ttlMs: number): boolean
+  /** Release a lease held by owner */
+  releaseLease(path: string, owner: string): boolean
+  /** Active lease on exactly `path` as { owner, expiresAt }, or null */
+  leaseInfo(path: string): any
+  /** Reject writes overlapping a lease held by a different owner */
+  setLeaseEnforcement(enabled: boolean): void
+  /**
+   * Accept filters written for the original, unchecked evaluation: unknown
+   * operators match every document, a regex that does not compile and a
+   * non-array `in` match none. Off by default, when such filters fail the
+   * query with ERR_INVALID_ARGUMENT.
+   */
+  setPermissiveFilters(enabled: boolean): void
+  /**
+   * Compare values in filters, `push` dedupe and `atomic` conditions as stored
+   * ('strict', the default: `1` and `1.0` differ) or by canonical form
+   * ('canonical'). Object key order never matters. A filter's own `equality`
+   * overrides this; views and counters keep the mode current when they were defined.
+   */
+  setEquality(mode: 'strict' | 'canonical'): void
+  /**
+   * Define (or redefine) a materialized view:
+   * `{ path, filters?, aggregate?: 'count' | 'sum' | 'avg' | 'min' | 'max', field?, autoRefresh?: { onWriteUnder } }`
+   */
+  createView(name: string, definition: any): void
+  /** Recompute a view, store the result and return it */
+  refreshView(name: string): any
+  /** Stored result of a view as `{ result, refreshedAt, stale }` */
+  getView(name: string): any
+  /** All views as `[{ name, definition, refreshedAt, stale }]` */
+  listViews(): any
+  /** Remove a view; false if it didn't exist */
+  dropView(name: string): boolean
+  /**
+   * Maintain a count of documents in a collection matching `filters`; writes only mark
+   * documents dirty, so reading the value never rescans. Returns the initial count.
+   */
+  registerCounter(name: string, collectionPath: string, filters: Array<QueryFilter>): number
+  /** Current value of a counter */
+  counterValue(name: string): number
+  /** Recompute a counter with a full scan */
+  rebuildCounter(name: string): number
+  /** Compare a counter with a fresh scan: `{ name, count, actual, consistent }` */
+  verifyCounter(name: string): { name: string; count: number; actual: number; consistent: boolean }
+  /** All counters as `{ name: { collection, filters, count, dirty } }` */
+  listCounters(): Record<string, { collection: string; filters: QueryFilter[]; count: number; dirty: boolean }>
+  /** Remove a counter; false if it didn't exist */
+  dropCounter(name: string): boolean
+  /**
+   * Cap a collection's entry count and/or approximate size. 'reject' fails
+   * writes that would exceed it with ERR_QUOTA; 'evict_oldest' deletes the
+   * oldest entries (insertion order, or by `orderField`) to make room.
+   */
+  setQuota(collectionPath: string, maxEntries: number | undefined | null, maxBytes: number | undefined | null, policy: 'reject' | 'evict_oldest', orderField?: string | undefined | null): QuotaStatus
+  /** Remove a collection's quota; false if it had none */
+  removeQuota(collectionPath: string): boolean
+  /** Usage of a collection against its quota */
+  quotaStatus(collectionPath: string): QuotaStatus
+  /**
+   * Forbid two documents of a collection from sharing the same values of `fields`;
+   * violating writes fail with ERR_CONSTRAINT. Returns violations already present.
+   */
+  registerConstraint(name: string, collectionPath: string, fields: Array<string>): ConstraintViolation[]
+  /** Remove a constraint; false if it did not exist */
+  dropConstraint(name: string): boolean
+  /** Audit a collection against its constraints */
+  checkConstraints(collectionPath: string): ConstraintReport[]
+  /**
+   * Act on documents of `childCollection` whose `childField` holds the key of a deleted
+   * document of `parentCollection`: delete them, nullify the field, or restrict the
+   * delete (ERR_CONSTRAINT). Rules forming a cycle are rejected.
+   */
+  registerCascade(name: string, parentCollection: string, childCollection: string, childField: string, action: CascadeAction): void
+  /** Remove a cascade rule; false if it did not exist */
+  dropCascade(name: string): boolean
+  cascades(): Record<string, CascadeRule>
+  /** Freeze the current state and return a handle for consistent reads */
+  snapshotOpen(): number
+  /** Release a snapshot; false for unknown handles */
+  snapshotClose(handle: number): boolean
+  /** Limit how many snapshots may be open at once (default: 16) */
+  setMaxSnapshots(maxOpen: number): void
+  snapshotGet(handle: number, path: string): any
+  snapshotQuery(handle: number, path: string, filters: Array<QueryFilter>, options?: QueryOptions | undefined | null): any
+  snapshotAggregate(handle: number, path: string, operation: string, field?: string | undefined | null): any
+  /** Engine statistics (open snapshots and their approximate memory) */
+  dbStats(): any
+  /**
+   * State after open: main file, WAL replay (ops, LSN range, skipped records) and
+   * per-index load status; `deep` also verifies every index entry against the data
+   */
+  integrityReport(deep?: boolean | undefined | null): any
+  /**
+   * Rewrite a WAL file without records superseded by later writes, keeping
+   * surviving LSNs. Offline only: no process may have the database open.
+   */
+  static compactWal(walPath: string): { before: number; after: number; removed: number; stopReason: string | null }
+  /**
+   * Register an index on field. With collectionPath the engine keeps it up to date
+   * with every write under the collection, so updateIndex is never needed.
+   * A sparse index also leaves out documents whose field is null.
+   */
+  registerIndex(name: string, field: string, collectionPath?: string | undefined | null, options?: IndexOptions | undefined | null): void
   updateIndex(name: string, key: any, path: string, isDelete: boolean): void
   findIndexPaths(name: string, key: any): Array<string>
+  /**
+   * Paths with an index key in [start, end] (null for an open end), in key order.
+   * Numeric bounds compare keys numerically.
+   */
+  findIndexRange(name: string, start?: any, end?: any, options?: IndexRangeOptions | undefined | null): Array<string>
+  /**
+   * Index the documents of collectionPath under the keys a synchronous extractor
+   * (doc, key) => keys returns. Writes mark documents dirty; the extractor catches
+   * up when the index is read, saved or verified. Returns its status.
+   */
+  registerCustomIndex(name: string, collectionPath: string, extract: (doc: any, key: string) => Array<string | number> | null | undefined, options?: CustomIndexOptions | undefined | null): CustomIndexStatus
+  customIndexStatus(name: string): CustomIndexStatus
+  /** Run the extractor over the whole collection again, replacing every entry */
+  rebuildCustomIndex(name: string): CustomIndexStatus
+  /** Compare the index with what the extractor yields for the collection now */
+  verifyCustomIndex(name: string): CustomIndexVerification
+  /** Distinct keys of an index in sorted order with their document counts */
+  indexKeys(name: string, options?: IndexKeysOptions | undefined | null): Array<{ key: string; count: number }>
+  /** Distinct keys of an index in sorted order with the paths indexed under them */
+  indexEntries(name: string, options?: IndexKeysOptions | undefined | null): Array<[string, string[]]>
+  /**
+   * Count indexed documents ("count") or distinct keys ("distinct") in
+   * [start, end] straight from the index. `bucket` ("prefix:N" or
+   * "interval:N") groups the result as [{ bucket, count }].
+   */
+  indexAggregate(name: string, operation: 'count' | 'distinct', start?: any, end?: any, bucket?: string | undefined | null): number | Array<{ bucket: string | number; count: number }>
   clearIndex(name: string): void
-  registerSchema(path: string, schemaJson: string): void
+  /**
+   * Fill a registered index from the documents of `collectionPath` in one pass,
+   * dropping whatever it held, then save it. The index then tracks the collection.
+   */
+  buildIndex(name: string, collectionPath: string): BuildIndexResult
+  /**
+   * Compare a field index with what `collectionPath` holds now: documents
+   * missing from it, stale entries and entries under the wrong key. With
+   * `repair` the differences are fixed in place and the index saved.
+   */
+  verifyIndex(name: string, collectionPath: string, repair?: boolean | undefined | null): IndexVerification
+  /** Registered indexes, custom ones included, in name order; tracked and custom ones are brought up to date first */
+  listIndexes(): Array<IndexInfo>
+  /** Unregister an index and delete its file (a TTL index stops being swept); false if there was no such index */
+  dropIndex(name: string): boolean
+  /**
+   * Expire the documents of an object collection by an epoch-millis field: a
+   * background thread sweeps its index every sweepIntervalMs (default 1000)
+   * and deletes them with reason "ttl"; until then get and parallelQuery leave
+   * them out. Returns the index name.
+   */
+  registerTtlIndex(collectionPath: string, field: string, sweepIntervalMs?: number | undefined | null): string
+  ttlIndexes(): Array<TTLIndexInfo>
+  registerSchema(path: string, schemaJson: string, options?: SchemaOptions | undefined | null): void
+  /** Paths with a registered schema, sorted */
+  listSchemas(): Array<string>
+  /** The schema registered at exactly `path`, or null */
+  getSchema(path: string): any
+  /** `{path, exact, applyDefaults, schema, resolved}` for the schema a write at `path` is checked against, or null */
+  resolveSchemaFor(path: string): any
+  /** A value the schema governing `path` accepts there; ERR_NOT_FOUND if no schema describes `path` */
+  generateFromSchema(path: string): any
+  unregisterSchema(path: string): boolean
   validatePath(path: string, value: any): void
-  begin_transaction(): void
-  commit_transaction(): void
-  rollback_transaction(): void
-  create_savepoint(name: string): void
-  rollback_to_savepoint(name: string): void
+  /** Validate any value against an ad-hoc schema; returns every issue instead of throwing */
+  validateValue(value: any, schemaJson: string): Array<SchemaIssue>
+  /** Check a path against the path rules without writing; names the first rule broken */
+  validatePathString(path: string): { valid: boolean; depth: number; segment?: string | null; reason?: string }
+  /**
+   * Open a transaction and return its id. Writes pass it as `txn`; one without
+   * joins no transaction. Writes overlapping
+   * another open transaction's paths fail with ERR_CONFLICT, untagged ones too. An exclusive transaction
+   * must be the only one open (ERR_TRANSACTION_ACTIVE otherwise).
+   */
+  beginTransaction(options?: TransactionOptions | undefined | null): number
+  /** Commit `txn`, or the only open transaction */
+  commitTransaction(txn?: number | undefined | null): void
+  /** Undo every write of `txn`, or of the only open transaction */
+  rollbackTransaction(txn?: number | undefined | null): void
+  createSavepoint(name: string, txn?: number | undefined | null): void
+  rollbackToSavepoint(name: string, txn?: number | undefined | null): void
+  /** State and undo log size of `txn`, or of the only open transaction */
+  transactionInfo(txn?: number | undefined | null): TransactionInfo
+  /** Value of `path` as of the start of `txn` (same as `get` outside a transaction) */
+  getOriginal(path: string, txn?: number | undefined | null): any
+  /** Paths written by `txn`, in first-touch order */
+  touchedPaths(txn?: number | undefined | null): Array<string>
+  /** Read-only handle on the live data for this thread (shares data and indexes) */
+  createReader(): ReaderHandle
+  /** Token for ReaderHandle.open() in a worker thread; valid until close() */
+  readerToken(): number
+  /**
+   * Apply coalesced writes, roll back an open transaction, save dirty indexes and
+   * release the lock and WAL. Later calls fail with ERR_CLOSED, except close(),
+   * isClosed(), recoveryStatus(), persistenceStatus() and getSystemInfo()
+   */
+  close(): void
+  isClosed(): boolean
+}
+/**
+ * Read-only view of a database opened in this process. Shares the primary's
+ * data and indexes and sees its writes; keeps the data alive after the primary closes.
+ */
+export declare class ReaderHandle {
+  /** Open a reader from a primary's readerToken(), typically in a worker thread */
+  static open(token: number): ReaderHandle
+  get(path: string, options?: ReadOptions | undefined | null): any
+  getSerialized(path: string, options?: ReadOptions | undefined | null): string
+  has(path: string): boolean
+  parallelQuery(path: string, filters: Array<QueryFilter>, options?: QueryOptions | undefined | null): any
+  getPage(path: string, options?: PageOptions | undefined | null): { items: Array<{ key: string; value: any }>; next: string | null }
+  queryString(path: string, query: string): any
+  parallelAggregate(path: string, operation: string, field?: string | undefined | null): any
+  parallelLookup(leftPath: string, rightPath: string, leftField: string, rightField: string, asField: string): any
+  findIndexPaths(name: string, key: any): Array<string>
+  findIndexRange(name: string, start?: any | undefined | null, end?: any | undefined | null, options?: IndexRangeOptions | undefined | null): Array<string>
+  indexKeys(name: string, options?: IndexKeysOptions | undefined | null): any
+  indexEntries(name: string, options?: IndexKeysOptions | undefined | null): any
+  indexAggregate(name: string, operation: string, start?: any | undefined | null, end?: any | undefined | null, bucket?: string | undefined | null): any
 }
 
 /* TypeScript Wrapper Types */
@@ -93,6 +1394,8 @@ export interface IndexConfig {
     name: string;
     path: string;
     field: string;
+    /** Leave out documents whose field is null, as well as those without it */
+    sparse?: boolean;
 }
 export interface MiddlewareContext<T = unknown> {
     path: string;
@@ -105,21 +1408,71 @@ export type MiddlewareFn<T = unknown> = (ctx: MiddlewareContext<T>) => Middlewar
 export type SchemaType = 'object' | 'array' | 'string' | 'number' | 'boolean' | 'null';
 
 export interface Schema {
-    type: SchemaType;
+    /** Any type when left out, e.g. in a schema that only composes others */
+    type?: SchemaType;
     properties?: Record<string, Schema>;
     required?: string[];
+    /** Properties that, when present, require others: `{ cardNumber: ['expiry'] }` */
+    dependentRequired?: Record<string, string[]>;
+    /** Schemas for the properties whose names match a regex */
+    patternProperties?: Record<string, Schema>;
+    /** Properties neither listed nor matched by a pattern: false forbids them, a schema validates them */
+    additionalProperties?: boolean | Schema;
+    /** Schema every property name must pass, as a string */
+    propertyNames?: Schema;
     minLength?: number;
     maxLength?: number;
     pattern?: string;
+    /** date, date-time (RFC 3339), email, ipv4, ipv6, uri or uuid; other names are ignored */
+    format?: string;
+    /** Refuse the schema if it or any schema below it names an unknown format */
+    strictFormats?: boolean;
     minimum?: number;
     maximum?: number;
     exclusiveMinimum?: number;
     exclusiveMaximum?: number;
-    items?: Schema;
+    /** Schemas for the first items, by position */
+    prefixItems?: Schema[];
+    /** Items after prefixItems (every item without it); false allows none */
+    items?: Schema | boolean;
     minItems?: number;
     maxItems?: number;
     uniqueItems?: boolean;
     enum?: unknown[];
+    /** Stored for this property when it is missing, if the schema was registered with applyDefaults */
+    default?: unknown;
+    /** The value must pass exactly one of these schemas */
+    oneOf?: Schema[];
+    /** The value must pass at least one of these schemas */
+    anyOf?: Schema[];
+    /** The value must pass every one of these schemas */
+    allOf?: Schema[];
+    /** A value passing `if` must pass `then`; any other must pass `else` */
+    if?: Schema;
+    then?: Schema;
+    else?: Schema;
+    /** Named schemas for $ref ('$defs' is accepted too) */
+    definitions?: Record<string, Schema>;
+    $defs?: Record<string, Schema>;
+    /** '#/definitions/name', or another registered schema: 'path' or 'path#/definitions/name' */
+    $ref?: string;
+}
+
+export interface SchemaOptions {
+    /** Fill in `default` values for missing properties on set() (default: false) */
+    applyDefaults?: boolean;
+}
+
+/** The schema a write at some path is checked against, from `resolveSchemaFor` */
+export interface SchemaMatch {
+    /** Where the schema is registered: the path itself or its deepest ancestor with one */
+    path: string;
+    /** Registered at the path itself */
+    exact: boolean;
+    applyDefaults: boolean;
+    schema: Schema;
+    /** The part of `schema` describing the path; null if it describes nothing there */
+    resolved: Schema | null;
 }
 
 export interface DBOptions {
@@ -137,6 +1490,52 @@ export interface DBOptions {
     walBatchSize?: number;
     /** v4.5: WAL flush interval in ms */
     walFlushMs?: number;
+    /** Writes queued for the WAL commit thread before walOverflowPolicy applies (default: 100000) */
+    walQueueCapacity?: number;
+    /** Full WAL queue: 'block' (default) waits, 'error' fails with ERR_WAL_BUSY, 'drop_oldest' discards (lazy durability only) */
+    walOverflowPolicy?: 'block' | 'error' | 'drop_oldest';
+    /** Maximum segments in a written path (default: 64) */
+    maxPathDepth?: number;
+    /** Maximum characters per path segment (default: 1024) */
+    maxKeyLength?: number;
+    /** Characters rejected in path segments besides control characters */
+    forbiddenPathChars?: string;
+    /** Replay the WAL in parallel across top-level keys (default: only for 10000+ operations) */
+    parallelRecovery?: boolean;
+    /** Archive truncated WAL and a checkpoint per save for valueAt() (default: false) */
+    walArchive?: boolean;
+    /** Top-level collections stored in `<file>.lazy/` and loaded per entry on first use */
+    lazyCollections?: string[];
+    /** Lazy entries kept in memory before the least recently used are dropped (default: 10000) */
+    lazyMaxResident?: number;
+    /** Validate set, setPointers, atomic and batch writes natively, before they are logged (default: false) */
+    enforceSchemas?: boolean;
+    /** Include the removed value in 'delete' events (default: false) */
+    deleteEventValues?: boolean;
+    /** Cap on old values held by a transaction's undo log, in bytes */
+    maxUndoBytes?: number;
+    /** Spill undo entries past maxUndoBytes to a temp file instead of failing */
+    undoSpill?: boolean;
+    /** Extra attempts to read a database file that fails to parse (default: 3) */
+    openRetries?: number;
+    /** Load only the database file; replay the WAL later with recover() or recoverAsync() */
+    deferRecovery?: boolean;
+    /** Called periodically while the WAL is replayed */
+    onRecoveryProgress?: (progress: RecoveryProgress) => void;
+    /** Store string values at least this many bytes long compressed (default: 0, off) */
+    compressThresholdBytes?: number;
+    /** What writes to an array index past the end do (default: 'pad_null') */
+    arrayPadding?: ArrayPadding;
+    /** Deleting a subtree at least this many bytes large compacts its parent's memory (default: 0, off) */
+    reclaimThresholdBytes?: number;
+    /** Evaluate malformed filters as before validation, unknown operators matching everything (default: false) */
+    permissiveFilters?: boolean;
+    /** How filters, push dedupe and atomic conditions compare values: as stored or by canonical form (default: 'strict') */
+    equality?: Equality;
+    /** Reject writes of values whose serialized JSON is larger than this (estimated; default: unlimited) */
+    maxValueBytes?: number;
+    /** Most WAL records a save keeps for consumers that have not acked them (default: 100000) */
+    changeRetention?: number;
     /** v5.1: Path-based schemas */
     schemas?: Record<string, Schema>;
     /** v5.1: Slow query threshold in ms */
@@ -158,15 +1557,68 @@ export interface PaginationResult<T> {
     data: T[];
     meta: PaginationMeta;
 }
+export interface Page<T> {
+    items: {
+        key: string;
+        value: T;
+    }[];
+    /** Pass as `after` for the following page; null after the last one */
+    next: string | null;
+}
 export interface BatchOperation {
     type: 'set' | 'delete' | 'push' | 'add' | 'subtract';
     path: string;
     value?: unknown;
 }
+/** Handle passed to the transaction() callback */
+export interface Transaction {
+    /** Native transaction id, for the `txn` option of writes */
+    id: number;
+    /** Read including this transaction's writes, which other reads of an exclusive one do not see */
+    get<T = unknown>(path: string, defaultValue?: T | null): Promise<T>;
+    savepoint(name: string): Promise<void>;
+    rollbackTo(name: string): Promise<void>;
+    /** Writes recorded in this transaction even while others are open */
+    set(path: string, value: unknown, options?: SetOptions): Promise<void>;
+    delete(path: string, options?: { reason?: DeleteReason; allowRootOverwrite?: boolean }): Promise<CascadeCounts>;
+    push(path: string, ...items: unknown[]): Promise<void>;
+}
+/** A check made by atomic() against the value at `path` */
+export interface AtomicCondition {
+    path: string;
+    op: QueryFilter['op'] | 'exists' | 'notexists';
+    /** Operand for filter operators; ignored by exists / notexists */
+    value?: unknown;
+    /** Overrides the database's equality for this condition */
+    equality?: Equality;
+}
+/** 'strict' compares values as stored; 'canonical' also treats `1` and `1.0` as equal */
+export type Equality = 'strict' | 'canonical';
 export type SortDirection = 1 | -1;
 export interface SortOptions {
     [key: string]: SortDirection;
 }
+/** A filter tree produced by parseQuery() */
+export type QueryExpr = {
+    field: string;
+    op: string;
+    value: unknown;
+} | {
+    and: QueryExpr[];
+} | {
+    or: QueryExpr[];
+} | {
+    not: QueryExpr;
+};
+export interface ParsedQuery {
+    where: QueryExpr | null;
+    orderBy: {
+        field: string;
+        direction: 'asc' | 'desc';
+    }[];
+    limit: number | null;
+    offset: number | null;
+}
 export interface ParallelConfig {
     /** Enable parallel processing (auto-detected by default) */
     enabled?: boolean;
@@ -242,9 +1694,74 @@ export declare class JSONDatabase extends EventEmitter {
 
     constructor(filePath: string, options?: DBOptions);
     /**
-     * Force save to disk immediately
+     * Rewrite a WAL file without records superseded by later writes.
+     * Offline only: no process may have the database open.
+     */
+    static compactWal(walPath: string): { before: number; after: number; removed: number; stopReason: string | null };
+    /** Open a database, replaying its WAL on a background thread */
+    static open(filePath: string, options?: DBOptions): Promise<JSONDatabase>;
+    /** Replay the WAL of a database opened with deferRecovery */
+    recover(onProgress?: (progress: RecoveryProgress) => void): any;
+    /** recover() on a background thread */
+    recoverAsync(onProgress?: (progress: RecoveryProgress) => void): Promise<any>;
+    recoveryStatus(): RecoveryStatus;
+    /** Validate a value against an ad-hoc schema; returns every issue (empty when valid) */
+    static validateValue(value: unknown, schema: Schema): SchemaIssue[];
+    /** A value the schema accepts, built from its defaults, enums and lowest bounds */
+    static generateSample<T = unknown>(schema: Schema): T;
+    /** Register the schema for `path`; with applyDefaults, set() fills in missing properties' defaults */
+    registerSchema(path: string, schema: Schema, options?: SchemaOptions): void;
+    /** Paths with a registered schema, sorted */
+    listSchemas(): string[];
+    /** The schema registered at exactly `path` ($refs resolved), or null */
+    getSchema(path: string): Schema | null;
+    /** The schema a write at `path` is checked against: where it is registered and the part describing `path` */
+    resolveSchemaFor(path: string): SchemaMatch | null;
+    /** A value the schema governing `path` accepts there; throws ERR_NOT_FOUND if no schema describes `path` */
+    generateFromSchema<T = unknown>(path: string): T;
+    /** Remove the schema registered at exactly `path`; false if there was none */
+    unregisterSchema(path: string): boolean;
+    /** Check user input against the path rules (maxPathDepth, maxKeyLength, forbiddenPathChars) */
+    validatePathString(path: string): { valid: boolean; depth: number; segment?: string | null; reason?: string };
+    /**
+     * Coalesce rapid sets under the prefixes, applying only the latest value per path
+     * once per window. Staged writes are not in the WAL until applied.
+     */
+    enableWriteCoalescing(windowMs: number, pathPrefixes: string[]): void;
+    /** Apply pending coalesced writes and stop coalescing */
+    disableWriteCoalescing(): number;
+    /** Apply pending coalesced writes now; returns the number of paths written */
+    flushCoalesced(): number;
+    /** Set what writes to an array index past the end do: 'pad_null' (default), 'error' or 'append' */
+    setArrayPadding(policy: ArrayPadding): void;
+    /** The current array padding policy */
+    getArrayPadding(): ArrayPadding;
+    /**
+     * Force save to disk immediately. Fails with ERR_TRANSACTION_ACTIVE while
+     * a transaction is open.
      */
     save(): Promise<void>;
+    /** Save now and report what was written; autosaves emit the same report as a 'save' event */
+    saveWithReport(): Promise<SaveReport | null>;
+    persistenceStatus(): { saves: number; bytesWritten: number; lastSave: SaveReport | null };
+    /** Run checkpoint, save_if_dirty, purge_expired, prune_backups and refresh_view tasks on an engine thread */
+    scheduleMaintenance(tasks: MaintenanceTask[]): void;
+    /** What each scheduled maintenance task last did */
+    maintenanceStatus(): MaintenanceStatus;
+    /** Findings about the usage pattern with stable codes, severities and the numbers behind them */
+    advisorReport(options?: AdvisorOptions): AdvisorReport;
+    /** Likely bad data under `path` (default: everything), grouped by kind; only fixTrailingNulls writes */
+    scanAnomalies(path?: string, options?: ScanAnomaliesOptions): AnomalyReport;
+    /** Drop a lazy entry, or all of a lazy collection, from memory; returns how many entries were dropped */
+    evict(path: string): number;
+    /** Load a lazy entry and keep it resident until unpin(); false if it does not exist */
+    pin(path: string): boolean;
+    unpin(path: string): boolean;
+    /** Resident, pinned and dirty entries per lazy collection, with load and eviction counts */
+    lazyStatus(): LazyStatus;
+    /** Read a path as it was at a past time (needs walArchive); null if it did not exist then */
+    valueAt<T = unknown>(path: string, timestamp: number | Date): Promise<T | null>;
+    pruneArchive(before: number | Date): { checkpointsRemoved: number; segmentsRemoved: number; oldest: number | null };
     /**
      * v4.5: Explicit sync for durability
      */
@@ -256,10 +1773,50 @@ export declare class JSONDatabase extends EventEmitter {
         enabled: boolean;
         committedLsn?: number;
     };
+    /** WAL commit queue depth and overflow counters */
+    walPressure(): WalPressure;
+    /** Limit writes per second (token bucket); null removes the limit */
+    setWriteRateLimit(opsPerSec: number | null, burst: number, options?: WriteRateLimitOptions): void;
+    /** Write limiter state: tokens, throttled and delayed writes */
+    writeThrottleStatus(): WriteThrottleStatus;
+    /** Cap the serialized size of written values, per path prefix; 'warn' mode only reports them. null removes the limit */
+    setMaxValueBytes(maxBytes: number | null, options?: ValueLimitOptions): void;
+    /** Refuse writes touching a path prefix with ERR_FROZEN until unfrozen; false if already frozen that way */
+    freezePath(pathPrefix: string, options?: FreezeOptions): boolean;
+    unfreezePath(pathPrefix: string): boolean;
+    frozenPaths(): Array<{ path: string; persisted: boolean }>;
+    /** Attach `context` to following writes' WAL records, change events and subscriber callbacks; null clears it */
+    setWriteContext(context: unknown): void;
+    clearWriteContext(): void;
+    /** WAL records after `lsn`, with values and write contexts */
+    changesSince(lsn: number, limit?: number): WalChange[];
+    /** Persist a consumer's position in the change feed, creating it if new */
+    ackChanges(consumer: string, seq: number): void;
+    /** Last position acked by a consumer, or null */
+    consumerPosition(consumer: string): number | null;
+    /** Changes after a consumer's acked position, across saves and restarts */
+    changesFor(consumer: string, limit?: number): ConsumerChanges;
+    listConsumers(): ConsumerInfo[];
+    /** Forget a consumer so its unacked changes are no longer kept */
+    dropConsumer(consumer: string): boolean;
+    /** Report a span per significant operation to `callback`; no callback stops tracing */
+    setTraceCallback(callback?: (span: TraceSpan) => void, options?: TraceOptions): void;
     /**
-     * Close the database gracefully
+     * Start a bulk load: writes skip the WAL (lost on crash until endBulkLoad),
+     * subscribers and index/counter upkeep; schemas only apply with keepSchemas
+     */
+    beginBulkLoad(options?: BulkLoadOptions): void;
+    /** Rebuild indexes and counters, save, and notify subscribers once per top-level key */
+    endBulkLoad(): Promise<BulkLoadReport>;
+    isBulkLoading(): boolean;
+    /**
+     * Close the database gracefully: roll back an open transaction, end a bulk
+     * load, save and release the lock and WAL. Later calls fail with ERR_CLOSED;
+     * closing again waits for the same close
      */
     close(): Promise<void>;
+    /** Whether close() has been called */
+    isClosed(): boolean;
     before<T = unknown>(method: string, pathPattern: string, fn: MiddlewareFn<T>): void;
     after<T = unknown>(method: string, pathPattern: string, fn: MiddlewareFn<T>): void;
     /**
@@ -268,7 +1825,7 @@ export declare class JSONDatabase extends EventEmitter {
      * @param callback - Function called when value changes
      * @returns Unsubscribe function
      */
-    subscribe(pathPattern: string, callback: (value: unknown, oldValue: unknown) => void): () => void;
+    subscribe(pathPattern: string, callback: (value: unknown, oldValue: unknown, context: unknown) => void): () => void;
     /**
      * Set a key with TTL (expires after specified seconds)
      */
@@ -289,26 +1846,106 @@ export declare class JSONDatabase extends EventEmitter {
      * Check if a key has TTL set
      */
     hasTTL(path: string): boolean;
-    set(path: string, value: unknown): Promise<void>;
+    set(path: string, value: unknown, options?: SetOptions): Promise<void>;
+    /** Like set(), but stores the value compressed (zstd); reads return the original */
+    setCompressed(path: string, value: unknown): Promise<void>;
+    /** Rewrite the value at `path` in canonical form: sorted keys, 1.0 as 1, -0 as 0 */
+    canonicalize(path: string): Promise<void>;
+    /** Canonical, whitespace-free JSON; throws ERR_INVALID_ARGUMENT for NaN and ±Infinity */
+    static canonicalString(value: unknown): string;
     get<T = unknown>(path: string, defaultValue?: T): Promise<T>;
     has(path: string): Promise<boolean>;
-    delete(path: string): Promise<void>;
+    /** Delete a path; `reason` (default 'explicit') is reported in 'delete'/'change' events and changesSince() */
+    delete(path: string, options?: {
+        reason?: DeleteReason;
+        /** Permit clearing the whole tree with the root path '' (default: false) */
+        allowRootOverwrite?: boolean;
+        /** Transaction the delete belongs to (`Transaction.id`) */
+        txn?: number;
+    }): Promise<CascadeCounts>;
     push(path: string, ...items: unknown[]): Promise<void>;
     pull(path: string, ...items: unknown[]): Promise<void>;
     add(path: string, amount: number): Promise<number>;
     subtract(path: string, amount: number): Promise<number>;
     findByIndex<T = unknown>(indexName: string, value: unknown): Promise<T | null>;
+    /** Documents with an index key between start and end (null for an open end), in key order */
+    findByIndexRange<T = unknown>(indexName: string, start: unknown, end: unknown, options?: IndexRangeOptions): Promise<T[]>;
+    /** Index the documents of collectionPath under the keys extract(doc, key) returns */
+    registerCustomIndex(name: string, collectionPath: string, extract: (doc: any, key: string) => Array<string | number> | null | undefined, options?: CustomIndexOptions): CustomIndexStatus;
+    customIndexStatus(name: string): CustomIndexStatus;
+    /** Run the extractor over the whole collection again */
+    rebuildCustomIndex(name: string): CustomIndexStatus;
+    /** Compare a custom index with what its extractor yields now */
+    verifyCustomIndex(name: string): CustomIndexVerification;
     /**
      * Manually trigger index rebuild
      */
     rebuildIndex(): void;
+    /** Fill a registered index from its collection in one pass, dropping stale entries, and save it */
+    buildIndex(name: string, collectionPath: string): BuildIndexResult;
+    /** Compare a field index with its collection; `repair` fixes the differences and saves */
+    verifyIndex(name: string, collectionPath: string, repair?: boolean): IndexVerification;
+    /** Every registered index, custom ones included, in name order */
+    listIndexes(): IndexInfo[];
+    /** Unregister an index and delete its file; false if there was no such index */
+    dropIndex(name: string): boolean;
+    /** Expire documents by an epoch-millis field, swept in the background; returns the index name */
+    registerTTLIndex(collectionPath: string, field: string, sweepIntervalMs?: number): string;
+    /** Registered TTL indexes and what their sweeps did */
+    ttlIndexes(): TTLIndexInfo[];
     query<T = unknown>(path: string): QueryBuilder<T>;
     find<T = unknown>(path: string, predicate: ((item: T) => boolean) | Record<string, unknown>): Promise<T | undefined>;
     findAll<T = unknown>(path: string, predicate: ((item: T) => boolean) | Record<string, unknown>): Promise<T[]>;
     paginate<T = unknown>(path: string, page: number, limit: number): Promise<PaginationResult<T>>;
+    /**
+     * One page of a walk over an object collection in a stable order (by key,
+     * or by `stableBy` field with the key breaking ties). Unlike `paginate`,
+     * writes between pages never repeat a document or skip one that was
+     * already there; documents inserted behind the cursor are not returned.
+     */
+    getPage<T = unknown>(path: string, options?: Omit<PageOptions, 'after'> & {
+        after?: string | null;
+    }): Promise<Page<T>>;
     batch(ops: BatchOperation[]): Promise<void>;
-    transaction<T = unknown>(fn: (data: T) => Promise<T> | T): Promise<T>;
+    /** Apply `ops` as one indivisible step only if every condition holds; resolves to whether they were applied */
+    atomic(conditions: AtomicCondition[], ops: BatchOperation[], options?: AtomicOptions): Promise<boolean>;
+    /**
+     * Read-modify-write of `path` with no other write able to land in between;
+     * the updater runs synchronously while every other writer waits
+     */
+    atomicUpdate<T = unknown>(path: string, updater: (current: T | undefined) => T | undefined, options?: AtomicUpdateOptions): Promise<T | null>;
+    /** atomicUpdate over several paths at once; either every path is written or none is */
+    atomicUpdateMany<T extends unknown[] = unknown[]>(paths: string[], updater: (current: Partial<T>) => Partial<T> | undefined, options?: AtomicUpdateOptions): Promise<T>;
+    /** Add a document under a key from the collection's ID strategy; resolves to the key */
+    insert(collectionPath: string, doc: unknown): Promise<string>;
+    insertMany(collectionPath: string, docs: unknown[]): Promise<string[]>;
+    setIdStrategy(collectionPath: string, strategy: IdStrategy | null): void;
+    idStrategy(collectionPath: string): { type: string; configured: boolean; length?: number; name?: string; field?: string };
+    /** Delete, nullify or restrict documents of `childCollection` referencing a deleted parent */
+    registerCascade(name: string, parentCollection: string, childCollection: string, childField: string, action: CascadeAction): void;
+    dropCascade(name: string): boolean;
+    cascades(): Record<string, CascadeRule>;
+    upsertMany(
+        collectionPath: string,
+        docs: Record<string, unknown>[],
+        keyField: string,
+        options?: UpsertOptions
+    ): Promise<{ inserted: number; updated: number; failed: { index: number; error: string }[] }>;
+    updateMany(
+        collectionPath: string,
+        filters: QueryFilter[],
+        patch: unknown,
+        options?: UpdateManyOptions
+    ): Promise<{ updated: number; indices?: number[]; keys?: string[] }>;
+    deleteMany(collectionPath: string, filters: QueryFilter[], options?: DeleteManyOptions): Promise<{ deleted: number; indices?: number[]; keys?: string[]; cascaded?: CascadeCounts }>;
+    /** Run `fn` in a native transaction (a savepoint when nested); only writes through `tx` are recorded in it */
+    transaction<T = unknown>(fn: (tx: Transaction) => Promise<T> | T, options?: TransactionOptions): Promise<T>;
     createSnapshot(name: string): Promise<string>;
+    /** Write data, indexes, schemas and engine metadata to one checksummed archive */
+    exportArchive(outPath: string, options?: ExportOptions): Promise<ArchiveManifest>;
+    /** Restore an archive as a new database at `targetPath`; nothing is written if it is damaged */
+    static importArchive(archivePath: string, targetPath: string, options?: DBOptions): Promise<JSONDatabase>;
+    static archiveManifest(archivePath: string): ArchiveManifest;
     restoreSnapshot(snapshotPath: string): Promise<void>;
     /**
      * Get all keys under a path
@@ -346,7 +1983,11 @@ export declare class JSONDatabase extends EventEmitter {
      * Uses multiple CPU cores when workload is large enough (≥100 items).
      * Falls back to sequential processing for small batches to avoid overhead.
      *
+     * Values are checked against registered schemas (in parallel) before anything
+     * is written; invalid values are listed in `result.invalid`.
+     *
      * @param operations - Array of {path, value} objects to set
+     * @param options - Schema validation behaviour
      * @returns ParallelResult with success status and count of operations completed
      *
      * @example
@@ -362,7 +2003,7 @@ export declare class JSONDatabase extends EventEmitter {
     batchSetParallel(operations: Array<{
         path: string;
         value: unknown;
-    }>): Promise<ParallelResult>;
+    }>, options?: BatchSetOptions): Promise<ParallelResult>;
     /**
      * Execute parallel query with native Rust filtering.
      * More efficient than JS-based queries for large datasets (≥100 items).
@@ -380,7 +2021,24 @@ export declare class JSONDatabase extends EventEmitter {
      * ]);
      * ```
      */
-    parallelQuery<T = unknown>(path: string, filters: QueryFilter[]): Promise<T[]>;
+    parallelQuery<T = unknown>(path: string, filters: QueryFilter[], options?: QueryOptions): Promise<T[]>;
+    /** The same filters over several collections, merged; sort, offset and limit apply to the merged matches */
+    parallelQueryMulti<T = unknown>(paths: string[], filters: QueryFilter[], options?: MultiQueryOptions): Promise<MultiQueryResult<T>>;
+    /** How parallelQuery would run: from an index or by a scan, and why */
+    explainQuery(path: string, filters: QueryFilter[], options?: QueryOptions): QueryPlan;
+    /** Scan a collection and store planner statistics for its indexed fields */
+    analyze(collectionPath: string, options?: AnalyzeOptions): CollectionStats;
+    /** Stats stored by analyze(), with writes since and staleness; null if never analyzed */
+    collectionStats(path: string): CollectionStats | null;
+    /** Query a collection with a string such as `age > 30 AND role IN ["admin"] ORDER BY name LIMIT 10` */
+    queryString<T = unknown>(path: string, query: string): Promise<T[]>;
+    /** Parse a string query without running it: `{ where, orderBy, limit, offset }` */
+    static parseQuery(query: string): ParsedQuery;
+    /** Read-only handle on the live data for this thread (shares data and indexes) */
+    createReader(): ReaderHandle;
+    /** Token for JSONDatabase.openReader() in a worker thread; valid until close() */
+    readerToken(): number;
+    static openReader(token: number): ReaderHandle;
     /**
      * Parallel aggregation operations using native Rust processing.
      * Efficiently computes sum, avg, min, max, or count over large datasets.
@@ -322,6 +322,8 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { NativeDb } = nativeBinding
+const { NativeDb, ReaderHandle, validateValue } = nativeBinding
 
 module.exports.NativeDb = NativeDb
+module.exports.ReaderHandle = ReaderHandle
+module.exports.validateValue = validateValue
@@ -6,7 +6,7 @@ import { performance } from 'perf_hooks';
 
 // Load native binding
 // @ts-ignore
-import { NativeDb } from './index.js';
+import { NativeDb, ReaderHandle, validateValue, generateSample } from './index.js';
 
 // ============================================
 // TYPES & INTERFACES
@@ -16,6 +16,7 @@ export interface IndexConfig {
     name: string;
     path: string; // e.g. 'users'
     field: string; // e.g. 'email'
+    sparse?: boolean; // leave out documents whose field is null
 }
 
 export interface JoinConfig {
@@ -35,21 +36,78 @@ export interface SubqueryConfig {
 export type SchemaType = 'object' | 'array' | 'string' | 'number' | 'boolean' | 'null';
 
 export interface Schema {
-    type: SchemaType;
+    /** Any type when left out, e.g. in a schema that only composes others */
+    type?: SchemaType;
     properties?: Record<string, Schema>;
     required?: string[];
+    /** Properties that, when present, require others: `{ cardNumber: ['expiry'] }` */
+    dependentRequired?: Record<string, string[]>;
+    /** Schemas for the properties whose names match a regex */
+    patternProperties?: Record<string, Schema>;
+    /** Properties neither listed nor matched by a pattern: false forbids them, a schema validates them */
+    additionalProperties?: boolean | Schema;
+    /** Schema every property name must pass, as a string */
+    propertyNames?: Schema;
     minLength?: number;
     maxLength?: number;
     pattern?: string;
+    /** date, date-time (RFC 3339), email, ipv4, ipv6, uri or uuid; other names are ignored */
+    format?: string;
+    /** Refuse the schema if it or any schema below it names an unknown format */
+    strictFormats?: boolean;
     minimum?: number;
     maximum?: number;
     exclusiveMinimum?: number;
     exclusiveMaximum?: number;
-    items?: Schema;
+    /** Schemas for the first items, by position */
+    prefixItems?: Schema[];
+    /** Items after prefixItems (every item without it); false allows none */
+    items?: Schema | boolean;
     minItems?: number;
     maxItems?: number;
     uniqueItems?: boolean;
     enum?: unknown[];
+    /** Stored for this property when it is missing, if the schema was registered with applyDefaults */
+    default?: unknown;
+    /** The value must pass exactly one of these schemas */
+    oneOf?: Schema[];
+    /** The value must pass at least one of these schemas */
+    anyOf?: Schema[];
+    /** The value must pass every one of these schemas */
+    allOf?: Schema[];
+    /** A value passing `if` must pass `then`; any other must pass `else` */
+    if?: Schema;
+    then?: Schema;
+    else?: Schema;
+    /** Named schemas for $ref ('$defs' is accepted too) */
+    definitions?: Record<string, Schema>;
+    $defs?: Record<string, Schema>;
+    /** '#/definitions/name', or another registered schema: 'path' or 'path#/definitions/name' */
+    $ref?: string;
+}
+
+export interface SchemaOptions {
+    /** Fill in `default` values for missing properties on set() (default: false) */
+    applyDefaults?: boolean;
+}
+
+/** The schema a write at some path is checked against, from `resolveSchemaFor` */
+export interface SchemaMatch {
+    /** Where the schema is registered: the path itself or its deepest ancestor with one */
+    path: string;
+    /** Registered at the path itself */
+    exact: boolean;
+    applyDefaults: boolean;
+    schema: Schema;
+    /** The part of `schema` describing the path; null if it describes nothing there */
+    resolved: Schema | null;
+}
+
+/** One failure reported by `validateValue`; `path` is dotted below the value ("" for the value itself) */
+export interface SchemaIssue {
+    path: string;
+    keyword: string;
+    message: string;
 }
 
 export interface MiddlewareContext<T = unknown> {
@@ -108,6 +166,159 @@ export interface DBOptions {
      * Default: 10ms
      */
     walFlushMs?: number;
+
+    /**
+     * Writes queued for the WAL commit thread before walOverflowPolicy applies
+     * Default: 100000
+     */
+    walQueueCapacity?: number;
+
+    /**
+     * What a write does when the WAL queue is full: 'block' waits for room,
+     * 'error' fails with ERR_WAL_BUSY (retry later), 'drop_oldest' discards the
+     * oldest queued write (lost on a crash; only for 'lazy' durability)
+     * Default: 'block'
+     */
+    walOverflowPolicy?: 'block' | 'error' | 'drop_oldest';
+
+    /**
+     * Path hygiene for writes: maximum segments per path, maximum characters per
+     * segment, and characters rejected in segments besides control characters.
+     * Violations fail with ERR_INVALID_PATH; WAL replay skips them too.
+     * Defaults: 64, 1024, none
+     */
+    maxPathDepth?: number;
+    maxKeyLength?: number;
+    forbiddenPathChars?: string;
+
+    /**
+     * Apply WAL replay in parallel across top-level keys. Root-level writes
+     * are applied on their own between parallel phases, so the result is the
+     * same as a sequential replay.
+     * Default: parallel only for replays of 10000+ operations on multi-core machines
+     */
+    parallelRecovery?: boolean;
+
+    /**
+     * Keep the WAL each save truncates, plus a copy of the saved file, in
+     * `<file>.archive/` so valueAt() can read past states. Grows until
+     * pruneArchive() is called.
+     * Default: false
+     */
+    walArchive?: boolean;
+
+    /**
+     * Top-level collections kept out of the main file in `<file>.lazy/`. Only
+     * their key index is read on open; an entry loads the first time a path
+     * below it is used. Reading or querying a whole lazy collection loads all
+     * of it (with a warning on stderr).
+     * Default: none
+     */
+    lazyCollections?: string[];
+
+    /**
+     * Lazy collection entries kept in memory before the least recently used
+     * clean ones are dropped.
+     * Default: 10000
+     */
+    lazyMaxResident?: number;
+
+    /**
+     * Validate writes against registered schemas in the native layer, before
+     * anything is logged: set, setCompressed, setPointers and atomic ops
+     * (transactions included), and batchSetParallel even with validate: false.
+     * A write the schema rejects fails with ERR_VALIDATION.
+     * Default: false (set validates from JS; push and batches still validate)
+     */
+    enforceSchemas?: boolean;
+
+    /**
+     * Include the removed value in 'delete' events. Values can be large, so
+     * events carry only the path and reason unless this is set.
+     * Default: false
+     */
+    deleteEventValues?: boolean;
+
+    /**
+     * Cap on old values held by a transaction's undo log, in bytes.
+     * Writes that would exceed it fail with ERR_LIMIT unless undoSpill is set.
+     * Default: unlimited
+     */
+    maxUndoBytes?: number;
+
+    /**
+     * Spill undo entries past maxUndoBytes to a temp file instead of failing
+     * Default: false
+     */
+    undoSpill?: boolean;
+
+    /**
+     * Extra attempts to read a database file that fails to parse, e.g. while
+     * another process is mid-save. Falls back to the newest .bak afterwards.
+     * Default: 3
+     */
+    openRetries?: number;
+
+    /**
+     * Store string values at least this many bytes long compressed (zstd).
+     * Reads return the original value. Default: 0 (off)
+     */
+    compressThresholdBytes?: number;
+
+    /**
+     * What writing to an array index past the end does: 'pad_null' fills the
+     * gap with nulls, 'error' rejects the write, 'append' writes at the end
+     * instead. Default: 'pad_null'
+     */
+    arrayPadding?: ArrayPadding;
+
+    /**
+     * Deleting a subtree at least this many bytes (approximate) large rebuilds
+     * its parent so the memory can be returned. Default: 0 (off)
+     */
+    reclaimThresholdBytes?: number;
+
+    /**
+     * Evaluate malformed query filters the way versions before filter
+     * validation did: an unknown operator matches every document, an invalid
+     * regex or a non-array `in` matches none. Default: false (such filters
+     * fail with ERR_INVALID_ARGUMENT)
+     */
+    permissiveFilters?: boolean;
+
+    /**
+     * How filters, push dedupe and atomic conditions compare values. 'strict'
+     * compares them as stored, so `1` and `1.0` (e.g. from a JSON file, or a
+     * large integer passed from JS) differ; 'canonical' compares canonical
+     * forms, so they are equal. Object key order never matters. Default: 'strict'
+     */
+    equality?: Equality;
+
+    /**
+     * Reject writes of values whose serialized JSON is larger than this many
+     * bytes (estimated) with ERR_LIMIT. See setMaxValueBytes() for per-path
+     * limits and the warn mode. Default: unlimited
+     */
+    maxValueBytes?: number;
+
+    /**
+     * Most WAL records a save keeps for consumers (see ackChanges()) that have
+     * not acked them; a consumer behind the oldest kept record must resync.
+     * Default: 100000
+     */
+    changeRetention?: number;
+
+    /**
+     * Load only the database file and leave the WAL unreplayed until recover()
+     * or recoverAsync(). Reads and writes fail with ERR_RECOVERY_PENDING until
+     * then. Default: false
+     */
+    deferRecovery?: boolean;
+
+    /**
+     * Called periodically while the WAL is replayed
+     */
+    onRecoveryProgress?: (progress: RecoveryProgress) => void;
     
     /**
      * Path-based schemas for validation
@@ -141,13 +352,77 @@ export interface PaginationResult<T> {
     meta: PaginationMeta;
 }
 
+export interface PageOptions {
+    /** "key" (default) walks documents by key; anything else names a field to walk by */
+    stableBy?: 'key' | string;
+    /** The `next` cursor of the previous page */
+    after?: string | null;
+    /** Documents per page (default 100) */
+    limit?: number;
+    filters?: QueryFilter[];
+}
+
+export interface Page<T> {
+    items: { key: string; value: T }[];
+    /** Pass as `after` for the following page; null after the last one */
+    next: string | null;
+}
+
 export interface BatchOperation {
     type: 'set' | 'delete' | 'push' | 'add' | 'subtract';
     path: string;
     value?: unknown;
 }
 
+/** A check made by atomic() against the value at `path` */
+export interface AtomicCondition {
+    path: string;
+    op: QueryFilter['op'] | 'exists' | 'notexists';
+    /** Operand for filter operators; ignored by exists / notexists */
+    value?: unknown;
+    /** Overrides the database's equality for this condition */
+    equality?: Equality;
+}
+
+/** 'strict' compares values as stored; 'canonical' also treats `1` and `1.0` as equal */
+export type Equality = 'strict' | 'canonical';
+
+/** What a write to an array index past the end does */
+export type ArrayPadding = 'pad_null' | 'error' | 'append';
+
+export interface SetOptions {
+    /** Array padding for this write, overriding the database's */
+    arrayPadding?: ArrayPadding;
+    /** Permit replacing the whole tree with the root path '' (default: false) */
+    allowRootOverwrite?: boolean;
+    /** Lease owner performing the write (checked when lease enforcement is on) */
+    owner?: string;
+    /** Transaction the write belongs to (`Transaction.id`) */
+    txn?: number;
+}
+
+export interface AtomicUpdateOptions {
+    /** Longest the updater may run before the call fails with ERR_LIMIT, writing nothing (default 1000) */
+    maxCallbackMs?: number;
+    /** Lease owner performing the write (checked when lease enforcement is on) */
+    owner?: string;
+}
+
 export type SortDirection = 1 | -1;
+/** A filter tree produced by parseQuery() */
+export type QueryExpr =
+    | { field: string; op: string; value: unknown }
+    | { and: QueryExpr[] }
+    | { or: QueryExpr[] }
+    | { not: QueryExpr };
+
+export interface ParsedQuery {
+    where: QueryExpr | null;
+    orderBy: { field: string; direction: 'asc' | 'desc' }[];
+    limit: number | null;
+    offset: number | null;
+}
+
 export interface SortOptions {
     [key: string]: SortDirection;
 }
@@ -156,6 +431,465 @@ export interface SortOptions {
 // PARALLEL PROCESSING INTERFACES
 // ============================================
 
+export interface WalPressure {
+    enabled: boolean;
+    queueDepth?: number;
+    queueCapacity?: number;
+    utilization?: number;
+    policy?: 'block' | 'error' | 'drop_oldest';
+    blockedAppends?: number;
+    blockedMs?: number;
+    rejected?: number;
+    dropped?: number;
+}
+
+export interface WriteRateLimitOptions {
+    /** 'block' (default) waits for tokens; 'error' fails with ERR_THROTTLED */
+    policy?: 'block' | 'error';
+    /** Longest a blocked write waits before failing (default 1000) */
+    maxWaitMs?: number;
+}
+
+export interface ValueLimitOptions {
+    /** Limit only values written under this path prefix (default: every path) */
+    pathPrefix?: string;
+    /** 'reject' (default) fails with ERR_LIMIT; 'warn' writes the value and reports an 'oversizedValue' trace span */
+    mode?: 'reject' | 'warn';
+}
+
+export interface FreezeOptions {
+    /** Keep the freeze across restarts (default: false) */
+    persist?: boolean;
+}
+
+export interface FrozenPath {
+    path: string;
+    persisted: boolean;
+}
+
+export interface TTLIndexInfo {
+    /** Index name, `ttl.<collection>.<field>` */
+    index: string;
+    collection: string;
+    field: string;
+    sweepIntervalMs: number;
+    sweeps: number;
+    /** Documents deleted by sweeps */
+    expired: number;
+    /** Expired documents left for a later sweep because they were frozen or leased */
+    skipped: number;
+    lastSweepAt: number | null;
+    lastError: string | null;
+}
+
+export interface WriteThrottleStatus {
+    enabled: boolean;
+    opsPerSec?: number;
+    burst?: number;
+    tokens?: number;
+    policy?: 'block' | 'error';
+    maxWaitMs?: number;
+    throttled: number;
+    waits: number;
+    waitedMs: number;
+}
+
+export interface WalChange {
+    lsn: number;
+    timestamp: number;
+    /** 'begin', 'commit' and 'abort' mark transactions; they have no path or value */
+    op: 'set' | 'delete' | 'begin' | 'commit' | 'abort';
+    path: string;
+    context: unknown;
+    /** Deletes only */
+    reason?: DeleteReason;
+    value?: unknown;
+    /** Transaction the record belongs to, if any */
+    txn?: number;
+}
+
+export type DeleteReason = 'explicit' | 'ttl' | 'quota' | 'cascade';
+
+export interface ConsumerChanges {
+    /** User writes after the consumer's position, oldest first */
+    changes: WalChange[];
+    /** Ack this once `changes` are processed */
+    position: number;
+    /** Changes the consumer never saw were dropped; `changes` is empty and it must resync from the data */
+    resyncRequired: boolean;
+    headLsn: number;
+}
+
+export interface ConsumerInfo {
+    name: string;
+    position: number;
+    ackedAt: number;
+    resyncRequired: boolean;
+}
+
+/** Emitted as 'delete' for every removed path, whatever removed it */
+export interface DeleteEvent {
+    path: string;
+    reason: DeleteReason;
+    /** WAL record that removed the path; null when it was not logged on its own */
+    lsn: number | null;
+    /** Only with the deleteEventValues option */
+    value?: unknown;
+}
+
+export type TraceOp =
+    | 'get' | 'query' | 'queryMulti' | 'queryString' | 'snapshotQuery' | 'aggregate' | 'snapshotAggregate'
+    | 'indexAggregate' | 'lookup' | 'save' | 'recovery' | 'maintenance' | 'oversizedValue';
+
+/** One finished operation, as passed to the setTraceCallback callback */
+export interface TraceSpan {
+    op: TraceOp;
+    /** Collection or value path; the index name for indexAggregate, '' for save and recovery */
+    path: string;
+    durationUs: number;
+    /** Items the operation looked at (WAL records for recovery), null if not applicable */
+    itemsScanned: number | null;
+    /** Items in the result (operations applied for recovery), null if not applicable */
+    itemsReturned: number | null;
+    /** Whether the work was large enough to run on the thread pool */
+    parallel: boolean;
+    /** The error the operation failed with, if it did */
+    error: string | null;
+}
+
+export interface TraceOptions {
+    /** Size (approximate, in bytes) a get() result must reach to be traced. Default: 65536 */
+    getThresholdBytes?: number;
+}
+
+export interface BulkLoadOptions {
+    /** Keep validating writes against registered schemas. Default: false */
+    keepSchemas?: boolean;
+}
+
+/** What endBulkLoad() did */
+export interface BulkLoadReport {
+    /** Writes applied during the load */
+    writes: number;
+    /** From beginBulkLoad() to endBulkLoad() */
+    loadMs: number;
+    /** Rebuilding tracked indexes and counters */
+    rebuildMs: number;
+    /** The checkpoint save (custom index extractors included) */
+    saveMs: number;
+    totalMs: number;
+    indexesRebuilt: number;
+    countersRebuilt: number;
+}
+
+export type CascadeAction = 'delete' | 'nullify' | 'restrict';
+
+export interface CascadeRule {
+    parent: string;
+    child: string;
+    field: string;
+    action: CascadeAction;
+}
+
+/** What cascade rules did during one delete, by rule name */
+export type CascadeCounts = Record<string, { deleted: number; nullified: number }>;
+
+export interface CustomIndexOptions {
+    /** "skip" (default) leaves a document the extractor throws on out of the index; "reject" fails the write */
+    onError?: 'skip' | 'reject';
+    /** Rebuild even when entries were loaded from the index file */
+    rebuild?: boolean;
+}
+
+export interface CustomIndexStatus {
+    collection: string;
+    onError: 'skip' | 'reject';
+    entries: number;
+    keys: number;
+    /** Documents written since the extractor last ran, or "all" */
+    pending: number | 'all';
+    errors: number;
+    lastError: string | null;
+    rejected: number;
+}
+
+export interface CustomIndexVerification {
+    entries: number;
+    keys: number;
+    /** Index entries for documents that no longer exist */
+    missingDocs: number;
+    /** Documents indexed under other keys than the extractor yields, or left out */
+    mismatched: number;
+    extractorErrors: number;
+    ok: boolean;
+}
+
+export interface IndexVerification {
+    name: string;
+    collection: string;
+    /** Documents in the collection */
+    documents: number;
+    /** Index entries before any repair */
+    entries: number;
+    /** Documents with the field but no entry */
+    missing: number;
+    /** Entries whose document is gone or no longer has the field */
+    stale: number;
+    /** Entries filed under another key than the field's value */
+    wrongKeys: number;
+    /** The first paths of each kind of difference */
+    samples: {
+        missing: string[];
+        stale: string[];
+        wrongKeys: Array<{ path: string; indexed: string; expected: string }>;
+    };
+    ok: boolean;
+    /** The differences were fixed and the index saved */
+    repaired: boolean;
+}
+
+export interface BuildIndexResult {
+    /** Documents indexed */
+    indexed: number;
+    /** Documents without the indexed field, left out */
+    skipped: number;
+    /** Entries the index held before the build */
+    cleared: number;
+}
+
+export interface IndexInfo {
+    name: string;
+    field: string;
+    /** Collection the index covers; null for one fed by hand */
+    collection: string | null;
+    custom: boolean;
+    /** Null fields are left out */
+    sparse: boolean;
+    /** Indexed documents */
+    entries: number;
+    /** Distinct keys */
+    keys: number;
+    /** Size of the index file; null until the index is first saved */
+    fileBytes: number | null;
+}
+
+export interface MultiQueryOptions {
+    /** Add the path of its collection to each matching object as `_source` */
+    tagSource?: boolean;
+    /** Field (dot notation) to sort all matches by; collection order otherwise */
+    sortBy?: string;
+    descending?: boolean;
+    /** Matches to skip after the sort */
+    offset?: number;
+    /** Most matches to return after the sort */
+    limit?: number;
+}
+
+export interface MultiQueryResult<T> {
+    results: T[];
+    /** Listed collections that do not exist */
+    missing: string[];
+}
+
+export interface RecoveryProgress {
+    opsReplayed: number;
+    bytesRead: number;
+    totalBytes: number;
+}
+
+export interface RecoveryStatus extends RecoveryProgress {
+    state: 'pending' | 'running' | 'done';
+}
+
+export interface LazyCollectionStatus {
+    /** Keys in the on-disk index */
+    stored: number;
+    resident: number;
+    pinned: number;
+    /** Entries written since the last save */
+    dirty: number;
+    /** Replaced as a whole since the last save */
+    replaced: boolean;
+    /** Fully loaded */
+    complete: boolean;
+    dataBytes: number;
+}
+
+export interface LazyStatus {
+    enabled: boolean;
+    maxResident?: number;
+    resident?: number;
+    loads?: number;
+    evictions?: number;
+    fullLoads?: number;
+    collections?: Record<string, LazyCollectionStatus>;
+}
+
+export interface SaveReport {
+    at: number;
+    bytes: number;
+    previousBytes: number | null;
+    serializeMs: number;
+    fsyncMs: number;
+    totalMs: number;
+    indexesSaved: number;
+    indexesSkipped: number;
+    walOpsTruncated: number;
+    walBytesTruncated: number;
+    backupRotated: boolean;
+}
+
+export type MaintenanceTaskName = 'checkpoint' | 'save_if_dirty' | 'purge_expired' | 'prune_backups' | 'refresh_view';
+
+export interface MaintenanceTask {
+    task: MaintenanceTaskName;
+    /** Time from the end of one run to the start of the next (at least 10) */
+    intervalMs: number;
+    /** prune_backups: `keep` (default 5) and `maxAgeMs`; refresh_view: the view `name` */
+    args?: { keep?: number; maxAgeMs?: number; name?: string };
+}
+
+export interface MaintenanceTaskStatus {
+    task: MaintenanceTaskName;
+    intervalMs: number;
+    args: MaintenanceTask['args'] | null;
+    running: boolean;
+    runs: number;
+    failures: number;
+    lastRunAt: number | null;
+    lastDurationMs: number | null;
+    /** Error of the latest run; null once a run succeeds */
+    lastError: string | null;
+    /** What the latest successful run returned, e.g. a SaveReport or `{ skipped }` */
+    lastResult: unknown;
+    /** Null while the task is running */
+    nextRunAt: number | null;
+}
+
+export interface MaintenanceStatus {
+    running: boolean;
+    tasks: MaintenanceTaskStatus[];
+}
+
+export interface ArchiveEntry {
+    name: string;
+    kind: 'data' | 'index';
+    /** Indexed field (indexes only) */
+    field?: string;
+    /** Collection holding the indexed documents (indexes only, absent while empty) */
+    collection?: string;
+    /** Set for sparse indexes */
+    sparse?: boolean;
+    bytes: number;
+    /** Size in the archive; smaller than `bytes` when compressed */
+    stored: number;
+    crc32: number;
+}
+
+export interface ArchiveManifest {
+    format: number;
+    dataFormat: number;
+    indexFormat: number;
+    engineVersion: string;
+    createdAt: number;
+    source: string;
+    compressed: boolean;
+    entries: ArchiveEntry[];
+    schemas: Record<string, Schema>;
+}
+
+export interface ExportOptions {
+    /** Replace an existing file at the output path (default: false) */
+    overwrite?: boolean;
+    /** zstd-compress the entries (default: false) */
+    compress?: boolean;
+}
+
+/** How insert() keys new documents of a collection */
+export type IdStrategy =
+    | 'uuid4'
+    | 'ulid'
+    | 'nanoid'
+    | { type: 'uuid4' | 'ulid' }
+    | { type: 'nanoid'; length?: number }
+    | { type: 'sequence'; name: string }
+    | { type: 'field'; field: string };
+
+export type AdvisorCode =
+    | 'SAVE_AMPLIFICATION'
+    | 'PRETTY_FILE_OVERHEAD'
+    | 'WAL_OUTGROWS_DATA'
+    | 'WAL_BACKLOG'
+    | 'WAL_BACKPRESSURE'
+    | 'INDEX_LARGER_THAN_COLLECTION';
+
+export interface AdvisorFinding {
+    code: AdvisorCode;
+    severity: 'info' | 'warning' | 'critical';
+    message: string;
+    metrics: Record<string, unknown>;
+}
+
+export interface AdvisorOptions {
+    /** How far back save history is considered (default: one hour) */
+    windowMs?: number;
+    /** Size below which byte-based findings are not reported (default: 1MB) */
+    minBytes?: number;
+    /** Bytes rewritten by saves per byte of logged changes (default: 100) */
+    saveAmplification?: number;
+    /** WAL bytes per byte of data file growth (default: 50) */
+    walGrowth?: number;
+    /** Share of the data file that is whitespace (default: 0.25) */
+    prettyOverhead?: number;
+    /** Index size per byte of the indexed collection (default: 1) */
+    indexRatio?: number;
+}
+
+export interface AdvisorReport {
+    generatedAt: number;
+    windowMs: number;
+    /** Most severe first */
+    findings: AdvisorFinding[];
+    /** The numbers the findings were derived from */
+    inputs: Record<string, unknown>;
+}
+
+export interface ScanAnomaliesOptions {
+    /** Findings listed per kind; all are counted (default: 100) */
+    limit?: number;
+    /** Levels walked below the scanned path before reporting deep nesting (default: the path depth limit) */
+    maxDepth?: number;
+    /** Trim the trailing nulls of every array that has them, as logged writes */
+    fixTrailingNulls?: boolean;
+}
+
+export type AnomalyKind =
+    | 'unsafeInteger'
+    | 'replacementChar'
+    | 'controlChar'
+    | 'emptyKey'
+    | 'unaddressableKey'
+    | 'trailingNulls'
+    | 'deepNesting';
+
+export interface Anomaly {
+    /** Where it was found; key findings name the object holding the key */
+    path: string;
+    kind: AnomalyKind;
+    detail: string;
+}
+
+export interface AnomalyReport {
+    /** Grouped by kind, at most `limit` per kind */
+    anomalies: Anomaly[];
+    /** Every finding counted, by kind */
+    counts: Partial<Record<AnomalyKind, number>>;
+    /** Whether some kind had more findings than listed */
+    truncated: boolean;
+    /** Arrays whose trailing nulls were trimmed */
+    fixed: string[];
+}
+
 export interface SystemInfo {
     availableCores: number;
     parallelEnabled: boolean;
@@ -174,18 +908,99 @@ export interface ParallelConfig {
 export interface QueryFilter {
     field: string;
     op: 'eq' | 'ne' | 'gt' | 'gte' | 'lt' | 'lte' | 'contains' | 'startswith' | 'endswith' | 'in' | 'notin' | 'regex' | 'containsAll' | 'containsAny';
+    /** Literal, or `{ $field: 'other.path' }` to compare against a sibling field of the same document */
     value: unknown;
+    /**
+     * Documents without the field: 'exclude' (default) never match, 'match' match
+     * negative operators (ne, notin), 'as_null' compare as if the field were null
+     */
+    missing?: 'exclude' | 'match' | 'as_null';
+    /** Overrides the database's equality for eq, ne, in, notin, containsAll and containsAny */
+    equality?: Equality;
+}
+
+/** How parallelQuery would run, from explainQuery() */
+export interface QueryPlan {
+    strategy: 'index' | 'scan';
+    /** Index supplying the candidates; null for a scan */
+    index: string | null;
+    /** The first filter's field and operator, which the index answers */
+    field: string | null;
+    op: string | null;
+    /** Documents the filters are checked against; null for a scan */
+    candidates: number | null;
+    /** What the planner expected from the stats; null without an index plan or stats */
+    estimatedCandidates: number | null;
+    collectionSize: number;
+    /** The collection's stats with just the first filter's field; null if never analyzed */
+    stats: (Omit<CollectionStats, 'fields'> & { field: FieldStats | null }) | null;
+    /** Why there is no index plan; null when there is one */
+    reason: string | null;
+}
+
+/** Planner statistics of one indexed field, from analyze() */
+export interface FieldStats {
+    index: string;
+    /** Documents with a key */
+    indexed: number;
+    /** Distinct keys */
+    cardinality: number;
+    /** Lowest and highest key in index order */
+    min: string | null;
+    max: string | null;
+    mostCommon: Array<{ key: string; docs: number }>;
+}
+
+export interface CollectionStats {
+    /** Entries when analyzed */
+    entries: number;
+    analyzedAt: number;
+    /** Writes after which the stats count as stale */
+    staleAfter: number;
+    fields: Record<string, FieldStats>;
+    currentEntries: number;
+    /** Writes under the collection since the analyze */
+    writes: number;
+    stale: boolean;
 }
 
 export interface ParallelResult {
     success: boolean;
     count: number;
     error?: string;
+    /** Values that failed schema validation */
+    invalid?: Array<{ index: number; path: string; error: string }>;
+}
+
+export interface BatchSetOptions {
+    /** Validate values against registered schemas before writing (default: true) */
+    validate?: boolean;
+    /** 'reject' (default) fails the whole batch on any invalid value, 'skip' writes the rest */
+    onInvalid?: 'reject' | 'skip';
+    /** Transaction to record the writes in (a Transaction's `id`) */
+    txn?: number;
+}
+
+export interface TransactionOptions {
+    /**
+     * 'read_uncommitted' (default): other reads see the writes as they land.
+     * 'exclusive': no other transaction runs alongside, other writes fail with
+     * ERR_TRANSACTION_ACTIVE, and other reads see the data as it was until commit.
+     */
+    isolation?: 'read_uncommitted' | 'exclusive';
 }
 
 export interface Transaction {
+    /** Native transaction id, for the `txn` option of writes */
+    id: number;
+    /** Read including this transaction's writes, which other reads of an exclusive one do not see */
+    get<T = unknown>(path: string, defaultValue?: T | null): Promise<T>;
     savepoint(name: string): Promise<void>;
     rollbackTo(name: string): Promise<void>;
+    /** Writes recorded in this transaction even while others are open */
+    set(path: string, value: unknown, options?: SetOptions): Promise<void>;
+    delete(path: string, options?: { reason?: DeleteReason; allowRootOverwrite?: boolean }): Promise<CascadeCounts>;
+    push(path: string, ...items: unknown[]): Promise<void>;
 }
 
 // ============================================
@@ -223,6 +1038,9 @@ function matchesPattern(pattern: string, path: string): boolean {
     return regex.test(path);
 }
 
+/** Sets per engine call when batch() runs during a bulk load */
+const BULK_CHUNK = 10000;
+
 // ============================================
 // ENCRYPTION HELPERS
 // ============================================
@@ -636,7 +1454,9 @@ export class QueryBuilder<T = unknown> {
         if (this.db && this.queryFilters.length > 0) {
             for (const f of this.queryFilters) {
                 if (f.op === 'eq') {
-                    const index = (this.db as any).indices.find((idx: any) => idx.path === this.path && idx.field === f.field);
+                    // A sparse index leaves out null fields
+                    const index = (this.db as any).indices.find((idx: any) =>
+                        idx.path === this.path && idx.field === f.field && !(idx.sparse && f.value === null));
                     if (index) {
                         const paths = (this.db as any).native.findIndexPaths(index.name, f.value);
                         if (paths) {
@@ -742,6 +1562,9 @@ export class JSONDatabase extends EventEmitter {
     private afterMiddlewares: Map<string, MiddlewareFn[]> = new Map();
     private wal: boolean = false;
     private saveTimeout: NodeJS.Timeout | null = null;
+    /** Set by the first close() */
+    private closing: Promise<void> | null = null;
+    private coalesceTimer: NodeJS.Timeout | null = null;
     private autoSaveInterval: number;
     private encryptionKey?: string;
     
@@ -758,6 +1581,11 @@ export class JSONDatabase extends EventEmitter {
     private walBatchSize: number;
     private walFlushMs: number;
     private slowQueryThresholdMs: number;
+    private enforceSchemas: boolean;
+
+    // Origin attached to WAL records and change notifications
+    private writeContext: unknown = null;
+    private onRecoveryProgress?: (progress: RecoveryProgress) => void;
 
     constructor(private filePath: string, options: DBOptions = {}) {
         super();
@@ -771,6 +1599,10 @@ export class JSONDatabase extends EventEmitter {
         this.walBatchSize = options.walBatchSize ?? 1000;
         this.walFlushMs = options.walFlushMs ?? 10;
         this.slowQueryThresholdMs = options.slowQueryThresholdMs ?? 100;
+        this.enforceSchemas = options.enforceSchemas ?? false;
+        this.onRecoveryProgress = options.onRecoveryProgress;
+        // Replaying through recover() is what reports progress
+        const deferRecovery = options.deferRecovery || !!options.onRecoveryProgress;
         
         // v4.5: Use new constructor with options if available
         if (typeof (NativeDb as any).newWithOptions === 'function') {
@@ -779,13 +1611,49 @@ export class JSONDatabase extends EventEmitter {
                 this.lockMode,
                 this.durability,
                 this.walBatchSize,
-                this.walFlushMs
+                this.walFlushMs,
+                options.maxUndoBytes,
+                options.undoSpill,
+                options.openRetries,
+                deferRecovery,
+                options.walQueueCapacity,
+                options.walOverflowPolicy,
+                options.maxPathDepth,
+                options.maxKeyLength,
+                options.forbiddenPathChars,
+                options.parallelRecovery,
+                options.walArchive,
+                options.lazyCollections,
+                options.lazyMaxResident,
+                this.enforceSchemas
             );
         } else {
             // Fallback to legacy constructor
             this.native = new NativeDb(filePath, this.wal);
         }
         
+        if (options.compressThresholdBytes) {
+            this.native.setCompressThreshold(options.compressThresholdBytes);
+        }
+        if (options.reclaimThresholdBytes) {
+            this.native.setReclaimThreshold(options.reclaimThresholdBytes);
+        }
+        if (options.arrayPadding) {
+            this.native.setArrayPadding(options.arrayPadding);
+        }
+        if (options.permissiveFilters) {
+            this.native.setPermissiveFilters(true);
+        }
+        if (options.equality) {
+            this.native.setEquality(options.equality);
+        }
+        if (options.maxValueBytes) {
+            this.native.setMaxValueBytes(options.maxValueBytes);
+        }
+        if (options.changeRetention) {
+            this.native.setChangeRetention(options.changeRetention);
+        }
+
         if (options.schemas && typeof this.native.registerSchema === 'function') {
             for (const [path, schema] of Object.entries(options.schemas)) {
                 this.native.registerSchema(path, JSON.stringify(schema));
@@ -793,24 +1661,60 @@ export class JSONDatabase extends EventEmitter {
         }
         
         this.loadData();
+        if (typeof this.native.onDelete === 'function') {
+            this.native.onDelete((event: DeleteEvent) => this.emit('delete', event), { includeValues: options.deleteEventValues });
+        }
         
         if (options.indices) {
             this.indices = options.indices;
             if (typeof this.native.registerIndex === 'function') {
+                // Native keeps each index in step with writes under its collection
                 for (const idx of this.indices) {
-                     this.native.registerIndex(idx.name, idx.field);
-                     const idxPath = `${this.filePath}.${idx.name}.idx`;
-                     if (!existsSync(idxPath)) {
-                          this.rebuildIndexByName(idx);
-                     }
+                     this.native.registerIndex(idx.name, idx.field, idx.path, idx.sparse ? { sparse: true } : undefined);
                 }
             }
         }
         
+        if (deferRecovery && !options.deferRecovery) {
+            this.recover();
+        }
+        
         // Cleanup on process exit
         process.on('beforeExit', () => this.close());
     }
 
+    /**
+     * Open a database, replaying its WAL on a background thread instead of
+     * blocking the constructor. With `deferRecovery` the replay is left to the caller.
+     */
+    public static async open(filePath: string, options: DBOptions = {}): Promise<JSONDatabase> {
+        const db = new JSONDatabase(filePath, { ...options, deferRecovery: true });
+        if (!options.deferRecovery) {
+            await db.recoverAsync();
+        }
+        return db;
+    }
+
+    /**
+     * Replay the WAL of a database opened with `deferRecovery`. Returns the
+     * replay report; once recovery has run it returns the report again.
+     */
+    public recover(onProgress = this.onRecoveryProgress): any {
+        return this.native.recover(onProgress);
+    }
+
+    /**
+     * recover() on a background thread. Reads and writes fail with
+     * ERR_RECOVERY_PENDING until the promise resolves.
+     */
+    public async recoverAsync(onProgress = this.onRecoveryProgress): Promise<any> {
+        return this.native.recoverAsync(onProgress);
+    }
+
+    public recoveryStatus(): RecoveryStatus {
+        return this.native.recoveryStatus();
+    }
+
     private loadData(): void {
         if (this.encryptionKey && existsSync(this.filePath)) {
             try {
@@ -833,67 +1737,186 @@ export class JSONDatabase extends EventEmitter {
     }
 
     private triggerSave(): void {
+        // endBulkLoad() saves
+        if (this.bulkLoad) return;
         if (this.wal) {
             // WAL mode: immediate writes are appended to WAL by Rust.
             // Periodic checkpoint to consolidate
             if (this.saveTimeout) clearTimeout(this.saveTimeout);
             this.saveTimeout = setTimeout(() => {
-                this.saveInternal();
+                this.autosave();
             }, this.autoSaveInterval * 5); // Less frequent checkpoints in WAL mode
         } else {
             // In-Memory mode: Debounce save
             if (this.saveTimeout) clearTimeout(this.saveTimeout);
             this.saveTimeout = setTimeout(() => {
-                this.saveInternal();
+                this.autosave();
             }, this.autoSaveInterval);
         }
     }
 
-    private saveInternal(): void {
+    private autosave(): void {
+        this.saveTimeout = null;
+        // Uncommitted writes must not reach the file: retry once it has ended
+        if (this.transactionId !== null) {
+            this.triggerSave();
+            return;
+        }
+        this.saveInternal();
+    }
+
+    private saveInternal(): SaveReport | null {
         if (this.encryptionKey) {
             // Get data, encrypt, and write
-            const data = this.native.get('');
+            const data = this.native.get('', { includeInternal: true });
             const jsonStr = JSON.stringify(data, null, 2);
             const encrypted = encrypt(jsonStr, this.encryptionKey);
             writeFileSync(this.filePath, encrypted);
-        } else {
-            this.native.save();
+            return null;
         }
+        const report: SaveReport = this.native.saveWithReport();
+        this.emit('save', report);
+        return report;
     }
 
     /**
-     * Force save to disk immediately
+     * Force save to disk immediately. Fails with ERR_TRANSACTION_ACTIVE while
+     * a transaction is open.
      */
     public async save(): Promise<void> {
+        await this.saveWithReport();
+    }
+
+    /**
+     * Save to disk immediately and report what was written. Autosaves and WAL
+     * checkpoints emit the same report as a 'save' event. Returns null for
+     * encrypted databases, which are written from JS.
+     */
+    public async saveWithReport(): Promise<SaveReport | null> {
         if (this.saveTimeout) {
             clearTimeout(this.saveTimeout);
             this.saveTimeout = null;
         }
-        this.saveInternal();
+        return this.saveInternal();
     }
 
     /**
-     * v4.5: Explicit sync for durability
-     * 
-     * Ensures all pending writes are flushed to the WAL and fsynced.
-     * Use this when you need guaranteed durability before continuing.
-     * 
-     * @example
-     * await db.set('critical.data', value);
-     * await db.sync(); // Guaranteed durable
+     * Check user input against this database's path rules before building a
+     * write from it. Never throws for a bad path; `segment` and `reason` name
+     * the first rule broken.
      */
-    public async sync(): Promise<void> {
-        if (typeof this.native.sync === 'function') {
-            await this.native.sync();
-        }
+    public validatePathString(path: string): { valid: boolean; depth: number; segment?: string | null; reason?: string } {
+        return this.native.validatePathString(path);
     }
 
     /**
-     * v4.5: Get WAL status
-     * 
-     * Returns information about the Write-Ahead Log state.
+     * Read `path` as it was at `timestamp` (needs `walArchive`). Resolves to null
+     * if the path did not exist then; rejects with ERR_NOT_FOUND when that
+     * history was never archived or has been pruned.
      */
-    public walStatus(): { enabled: boolean; committedLsn?: number } {
+    public async valueAt<T = unknown>(path: string, timestamp: number | Date): Promise<T | null> {
+        return this.native.valueAt(path, timestamp instanceof Date ? timestamp.getTime() : timestamp) as T | null;
+    }
+
+    /**
+     * Drop archived history older than `before`, keeping the latest checkpoint
+     * at or before it so valueAt() still works from that point on.
+     */
+    public pruneArchive(before: number | Date): { checkpointsRemoved: number; segmentsRemoved: number; oldest: number | null } {
+        return this.native.pruneArchive(before instanceof Date ? before.getTime() : before);
+    }
+
+    /**
+     * Drop a lazy entry (`events.<key>`), or all of a lazy collection, from
+     * memory. Pinned entries and entries written since the last save stay.
+     * Returns how many entries were dropped.
+     */
+    public evict(path: string): number {
+        return this.native.evict(path);
+    }
+
+    /** Load a lazy entry and keep it in memory until unpin(). Returns whether it exists. */
+    public pin(path: string): boolean {
+        return this.native.pin(path);
+    }
+
+    public unpin(path: string): boolean {
+        return this.native.unpin(path);
+    }
+
+    /** Resident, pinned and dirty entries of each lazy collection, plus load and eviction counts */
+    public lazyStatus(): LazyStatus {
+        return this.native.lazyStatus();
+    }
+
+    /**
+     * Save history of this process: number of saves, total bytes written and the
+     * latest save report (null before the first save).
+     */
+    public persistenceStatus(): { saves: number; bytesWritten: number; lastSave: SaveReport | null } {
+        return this.native.persistenceStatus();
+    }
+
+    /**
+     * Run maintenance tasks (checkpoint, save_if_dirty, purge_expired,
+     * prune_backups, refresh_view) on one background thread in the engine,
+     * replacing any schedule already running; an empty list stops it. A task
+     * never overlaps itself, and a failing one records its error and runs
+     * again on schedule. close() stops the thread.
+     */
+    public scheduleMaintenance(tasks: MaintenanceTask[]): void {
+        this.native.scheduleMaintenance(tasks);
+    }
+
+    /** What each scheduled maintenance task last did, with run and failure counts */
+    public maintenanceStatus(): MaintenanceStatus {
+        return this.native.maintenanceStatus();
+    }
+
+    /**
+     * Findings about the usage pattern (saves rewriting far more than changed,
+     * a WAL outpacing the data, oversized indexes...), each with a stable code,
+     * a severity and the numbers behind it. Sizes the data once; meant for
+     * monitoring, not for every request.
+     */
+    public advisorReport(options?: AdvisorOptions): AdvisorReport {
+        return this.native.advisorReport(options);
+    }
+
+    /**
+     * Look under `path` (default: everything) for data that is valid JSON but
+     * likely bad: integers beyond 2^53, replacement or control characters,
+     * empty or unaddressable keys, arrays padded with trailing nulls and
+     * excessive nesting. Only `fixTrailingNulls` changes anything.
+     */
+    public scanAnomalies(path?: string, options?: ScanAnomaliesOptions): AnomalyReport {
+        const report: AnomalyReport = this.native.scanAnomalies(path, options);
+        if (report.fixed.length > 0) this.triggerSave();
+        return report;
+    }
+
+    /**
+     * v4.5: Explicit sync for durability
+     * 
+     * Ensures all pending writes are flushed to the WAL and fsynced.
+     * Use this when you need guaranteed durability before continuing.
+     * 
+     * @example
+     * await db.set('critical.data', value);
+     * await db.sync(); // Guaranteed durable
+     */
+    public async sync(): Promise<void> {
+        if (typeof this.native.sync === 'function') {
+            await this.native.sync();
+        }
+    }
+
+    /**
+     * v4.5: Get WAL status
+     * 
+     * Returns information about the Write-Ahead Log state.
+     */
+    public walStatus(): { enabled: boolean; committedLsn?: number } {
         if (typeof this.native.walStatus === 'function') {
             return this.native.walStatus();
         }
@@ -901,101 +1924,260 @@ export class JSONDatabase extends EventEmitter {
     }
 
     /**
-     * Close the database gracefully
+     * WAL commit queue depth and overflow counters. Shed load as `utilization`
+     * approaches 1, before writes start to block or fail.
      */
-    public async close(): Promise<void> {
-        if (!this.native) return;
-        
-        // Clear all TTL timers
+    public walPressure(): WalPressure {
+        return this.native.walPressure();
+    }
+
+    /**
+     * Limit writes to `opsPerSec` per second with bursts of up to `burst`.
+     * Batch methods spend one token per item. An empty bucket delays the
+     * write, or fails it with ERR_THROTTLED under the 'error' policy or when
+     * the wait would exceed `maxWaitMs`. Reads and rollbacks are never
+     * throttled. Pass null to remove the limit.
+     */
+    public setWriteRateLimit(opsPerSec: number | null, burst: number, options?: WriteRateLimitOptions): void {
+        this.native.setWriteRateLimit(opsPerSec ?? undefined, burst, options);
+    }
+
+    /**
+     * Cap the serialized size of values written under `options.pathPrefix`
+     * (every path by default; the longest matching prefix applies). Larger
+     * writes fail with ERR_LIMIT before taking any lock, or in 'warn' mode are
+     * written and reported to the trace callback as 'oversizedValue'. Pass
+     * null to remove the limit.
+     */
+    public setMaxValueBytes(maxBytes: number | null, options?: ValueLimitOptions): void {
+        this.native.setMaxValueBytes(maxBytes ?? undefined, options);
+    }
+
+    /**
+     * Refuse every write touching `pathPrefix` (itself, anything under it and
+     * its ancestors) with ERR_FROZEN until unfreezePath(), whichever method it
+     * comes through. Expiring TTLs under it wait. Returns false if it was
+     * already frozen that way.
+     */
+    public freezePath(pathPrefix: string, options?: FreezeOptions): boolean {
+        return this.native.freezePath(pathPrefix, options);
+    }
+
+    public unfreezePath(pathPrefix: string): boolean {
+        return this.native.unfreezePath(pathPrefix);
+    }
+
+    public frozenPaths(): FrozenPath[] {
+        return this.native.frozenPaths();
+    }
+
+    /** Write limiter state, including current tokens and how many writes were throttled */
+    public writeThrottleStatus(): WriteThrottleStatus {
+        return this.native.writeThrottleStatus();
+    }
+
+    /**
+     * Attach `context` (e.g. `{ user, requestId }`) to every following write:
+     * its WAL record, change events and subscriber callbacks. At most 1KB
+     * serialized; null clears it.
+     */
+    public setWriteContext(context: unknown): void {
+        this.native.setWriteContext(context);
+        this.writeContext = context ?? null;
+    }
+
+    public clearWriteContext(): void {
+        this.native.clearWriteContext();
+        this.writeContext = null;
+    }
+
+    /**
+     * WAL records written after `lsn`, with values and write contexts. The WAL
+     * only holds writes since the last save.
+     */
+    public changesSince(lsn: number, limit?: number): WalChange[] {
+        return this.native.changesSince(lsn, limit);
+    }
+
+    /**
+     * Persist `consumer`'s position in the change feed, creating the consumer
+     * if new. Saves keep the changes it has not acked for changesFor().
+     */
+    public ackChanges(consumer: string, seq: number): void {
+        this.native.ackChanges(consumer, seq);
+    }
+
+    /** Last position acked by `consumer`, or null */
+    public consumerPosition(consumer: string): number | null {
+        return this.native.consumerPosition(consumer);
+    }
+
+    /**
+     * Changes after `consumer`'s acked position, across saves and restarts.
+     * Ack the returned `position` once they are processed.
+     */
+    public changesFor(consumer: string, limit?: number): ConsumerChanges {
+        return this.native.changesFor(consumer, limit);
+    }
+
+    public listConsumers(): ConsumerInfo[] {
+        return this.native.listConsumers();
+    }
+
+    /** Forget `consumer` so its unacked changes are no longer kept */
+    public dropConsumer(consumer: string): boolean {
+        return this.native.dropConsumer(consumer);
+    }
+
+    /**
+     * Report a span for each significant operation (large gets, queries,
+     * aggregates, lookups, saves and WAL recovery) to `callback`, e.g. to turn
+     * into APM spans. Spans are delivered on the event loop after the operation
+     * has finished, never synchronously. No callback stops tracing, which then
+     * costs nothing.
+     */
+    public setTraceCallback(callback?: (span: TraceSpan) => void, options?: TraceOptions): void {
+        this.native.setTraceCallback(callback, options);
+    }
+
+    /**
+     * Rewrite a WAL file without records superseded by later writes.
+     * Offline only: no process may have the database open.
+     */
+    public static compactWal(walPath: string): { before: number; after: number; removed: number; stopReason: string | null } {
+        return (NativeDb as any).compactWal(walPath);
+    }
+
+    /**
+     * Coalesce rapid sets under the given prefixes: only the latest value per path
+     * is applied and WAL-logged, once per window. A read of a staged path returns
+     * its staged value; reads above or below one, save(), sync() and close()
+     * apply pending writes first.
+     *
+     * Staged writes are not in the WAL until applied; a crash loses up to one window.
+     */
+    public enableWriteCoalescing(windowMs: number, pathPrefixes: string[]): void {
+        this.native.enableWriteCoalescing(windowMs, pathPrefixes);
+        if (this.coalesceTimer) clearInterval(this.coalesceTimer);
+        this.coalesceTimer = setInterval(() => this.native?.flushCoalesced(), Math.max(windowMs, 1));
+        this.coalesceTimer.unref?.();
+    }
+
+    /** Apply pending coalesced writes and stop coalescing */
+    public disableWriteCoalescing(): number {
+        if (this.coalesceTimer) {
+            clearInterval(this.coalesceTimer);
+            this.coalesceTimer = null;
+        }
+        return this.native.disableWriteCoalescing();
+    }
+
+    /** Apply pending coalesced writes now; returns the number of paths written */
+    public flushCoalesced(): number {
+        return this.native.flushCoalesced();
+    }
+
+    /**
+     * Set what writes to an array index past the end do, for set(),
+     * batchSetParallel() and atomic writes: 'pad_null' (the default) fills the
+     * gap with nulls, 'error' throws ERR_INVALID_ARGUMENT, 'append' writes at
+     * the end instead. WAL replay repeats the indexes actually written.
+     */
+    public setArrayPadding(policy: ArrayPadding): void {
+        this.native.setArrayPadding(policy);
+    }
+
+    /** The current array padding policy */
+    public getArrayPadding(): ArrayPadding {
+        return this.native.arrayPadding() as ArrayPadding;
+    }
+
+    /**
+     * Close the database gracefully: roll back an open transaction, end a bulk
+     * load, save and release the lock and WAL. Every later call except close()
+     * and isClosed() fails with ERR_CLOSED. Closing again, or while a close is
+     * in progress, waits for the same close.
+     */
+    public close(): Promise<void> {
+        this.closing ??= this.shutdown();
+        return this.closing;
+    }
+
+    /** Whether close() has been called */
+    public isClosed(): boolean {
+        return this.closing !== null;
+    }
+
+    private async shutdown(): Promise<void> {
+        // Clear all TTL timers and a pending autosave
         for (const timeout of this.ttlMap.values()) {
             clearTimeout(timeout);
         }
         this.ttlMap.clear();
+        this.ttlEntries.clear();
+        if (this.saveTimeout) {
+            clearTimeout(this.saveTimeout);
+            this.saveTimeout = null;
+        }
+
+        if (this.coalesceTimer) {
+            clearInterval(this.coalesceTimer);
+            this.coalesceTimer = null;
+        }
         
-        // Force save
-        await this.save();
-        
-        // Clear subscriptions
-        this.subscriptions.clear();
-        this.removeAllListeners();
-        
-        // v4.5: Release native resources (locks, WAL handles)
-        if (this.native && typeof this.native.close === 'function') {
-            this.native.close();
-        }
-        (this as any).native = null;
-    }
-
-    private rebuildIndexByName(idx: IndexConfig): void {
-        if (typeof this.native.clearIndex !== 'function') return;
-        this.native.clearIndex(idx.name);
-        const collection = this.native.get(idx.path);
-        if (collection && typeof collection === 'object') {
-            for (const [key, item] of Object.entries(collection as Record<string, unknown>)) {
-                if (item && typeof item === 'object') {
-                    const val = (item as Record<string, unknown>)[idx.field];
-                    if (val !== undefined && typeof this.native.updateIndex === 'function') {
-                        this.native.updateIndex(idx.name, val, `${idx.path}.${key}`, false);
-                    }
+        try {
+            // No scheduled save may run alongside the final one
+            this.native.scheduleMaintenance([]);
+            // Force save (applies coalesced writes first); an unrecovered
+            // database must not overwrite its file or truncate the WAL
+            if (this.native.recoveryStatus().state === 'done') {
+                // Uncommitted writes must not reach the file
+                if (this.transactionId !== null) {
+                    this.native.rollbackTransaction(this.transactionId);
+                    this.transactionId = null;
                 }
+                if (this.bulkLoad) await this.endBulkLoad();
+                await this.save();
+            }
+        } finally {
+            // Clear subscriptions
+            this.subscriptions.clear();
+            this.removeAllListeners();
+            
+            // v4.5: Release native resources (locks, WAL handles), even after a failed save
+            if (typeof this.native.close === 'function') {
+                this.native.close();
             }
         }
     }
-    
-    // Legacy method name kept for internal compatibility references, replaced implementation
-    private rebuildIndices(): void {
-        for (const idx of this.indices) {
-            this.rebuildIndexByName(idx);
+
+    /** Fail like the native calls do once close() has been called */
+    private assertOpen(): void {
+        if (this.closing) {
+            throw new Error('ERR_CLOSED: Database is closed');
         }
     }
 
-    /**
-     * Incrementally update indices for a single path change
-     * Much faster than full rebuild for single-item operations
-     */
-    /**
-     * Incrementally update indices for a single path change
-     */
-    private updateIndicesForPath(path: string, value: unknown, isDelete: boolean = false): void {
-        if (this.indices.length === 0) return;
-        if (typeof this.native.updateIndex !== 'function') return;
-        
-        const parts = path.split('.');
-        if (parts.length < 2) return;
-        
-        const collectionPath = parts.slice(0, -1).join('.');
-        
+    /** Re-derive every index from the data */
+    private rebuildIndices(): void {
         for (const idx of this.indices) {
-            // Check if path matches collection (e.g. users.123 updates index on users)
-            if (collectionPath === idx.path) {
-                if (isDelete) {
-                    if (value && typeof value === 'object') {
-                         const fieldValue = (value as Record<string, unknown>)[idx.field];
-                         if (fieldValue !== undefined) {
-                             this.native.updateIndex(idx.name, fieldValue, path, true);
-                         }
-                    }
-                } else if (value && typeof value === 'object') {
-                    const fieldValue = (value as Record<string, unknown>)[idx.field];
-                    if (fieldValue !== undefined) {
-                        this.native.updateIndex(idx.name, fieldValue, path, false);
-                    }
-                }
-            }
+            this.native.buildIndex(idx.name, idx.path);
         }
     }
-    
+
     // ============================================
     // MIDDLEWARE
     // ============================================
     
     public before<T = unknown>(method: string, pathPattern: string, fn: MiddlewareFn<T>): void {
+        this.assertOpen();
         const key = `${method}:${pathPattern}`;
         if (!this.beforeMiddlewares.has(key)) this.beforeMiddlewares.set(key, []);
         this.beforeMiddlewares.get(key)!.push(fn as MiddlewareFn);
     }
     
     public after<T = unknown>(method: string, pathPattern: string, fn: MiddlewareFn<T>): void {
+        this.assertOpen();
         const key = `${method}:${pathPattern}`;
         if (!this.afterMiddlewares.has(key)) this.afterMiddlewares.set(key, []);
         this.afterMiddlewares.get(key)!.push(fn as MiddlewareFn);
@@ -1040,8 +2222,9 @@ export class JSONDatabase extends EventEmitter {
      */
     public subscribe(
         pathPattern: string, 
-        callback: (value: unknown, oldValue: unknown) => void
+        callback: (value: unknown, oldValue: unknown, context: unknown) => void
     ): () => void {
+        this.assertOpen();
         if (!this.subscriptions.has(pathPattern)) {
             this.subscriptions.set(pathPattern, new Set());
         }
@@ -1058,12 +2241,17 @@ export class JSONDatabase extends EventEmitter {
         };
     }
 
-    private notifySubscribers(path: string, newValue: unknown, oldValue: unknown): void {
+    private notifySubscribers(path: string, newValue: unknown, oldValue: unknown, reason?: DeleteReason): void {
+        if (this.bulkLoad) {
+            const dot = path.indexOf('.');
+            this.bulkLoad.touched.add(dot < 0 ? path : path.slice(0, dot));
+            return;
+        }
         for (const [pattern, callbacks] of this.subscriptions) {
             if (matchesPattern(pattern, path)) {
                 for (const callback of callbacks) {
                     try {
-                        callback(newValue, oldValue);
+                        callback(newValue, oldValue, this.writeContext);
                     } catch (err) {
                         this.emit('error', err);
                     }
@@ -1072,7 +2260,9 @@ export class JSONDatabase extends EventEmitter {
         }
         
         // Also emit generic events
-        this.emit('change', { path, value: newValue, oldValue });
+        this.emit('change', reason
+            ? { path, value: newValue, oldValue, context: this.writeContext, reason }
+            : { path, value: newValue, oldValue, context: this.writeContext });
     }
 
     // ============================================
@@ -1091,6 +2281,7 @@ export class JSONDatabase extends EventEmitter {
      * Set TTL on an existing key
      */
     public setTTL(path: string, ttlSeconds: number): void {
+        this.assertOpen();
         // Clear existing TTL if any
         this.clearTTL(path);
         
@@ -1100,7 +2291,13 @@ export class JSONDatabase extends EventEmitter {
         const timeout = setTimeout(async () => {
             this.ttlMap.delete(path);
             this.ttlEntries.delete(path);
-            await this.delete(path);
+            if (this.native.isFrozen(path)) {
+                // Counted as a skipped purge; tried again a TTL later
+                this.native.delete(path, { reason: 'ttl' });
+                this.setTTL(path, ttlSeconds);
+                return;
+            }
+            await this.delete(path, { reason: 'ttl' });
             this.emit('ttl:expired', { path });
         }, ttlSeconds * 1000);
         
@@ -1121,6 +2318,7 @@ export class JSONDatabase extends EventEmitter {
      * Remove TTL from a key (make it persistent)
      */
     public clearTTL(path: string): void {
+        this.assertOpen();
         const timeout = this.ttlMap.get(path);
         if (timeout) {
             clearTimeout(timeout);
@@ -1133,9 +2331,25 @@ export class JSONDatabase extends EventEmitter {
      * Check if a key has TTL set
      */
     public hasTTL(path: string): boolean {
+        this.assertOpen();
         return this.ttlEntries.has(path);
     }
 
+    /**
+     * Expire the documents of an object collection by an epoch-millis field.
+     * A background thread sweeps the index every `sweepIntervalMs` (default
+     * 1000) and deletes expired documents with reason 'ttl'; until then get()
+     * and parallelQuery() leave them out. Returns the index name.
+     */
+    public registerTTLIndex(collectionPath: string, field: string, sweepIntervalMs?: number): string {
+        return this.native.registerTtlIndex(collectionPath, field, sweepIntervalMs);
+    }
+
+    /** Registered TTL indexes and what their sweeps did */
+    public ttlIndexes(): TTLIndexInfo[] {
+        return this.native.ttlIndexes();
+    }
+
     // ============================================
     // CORE API
     // ============================================
@@ -1145,18 +2359,130 @@ export class JSONDatabase extends EventEmitter {
         return (val === null || val === undefined ? defaultValue : val) as T;
     }
 
-    public async set(path: string, value: unknown): Promise<void> {
-        // Run validation (if native module supports it)
-        if (typeof this.native.validatePath === 'function') {
+    public async set(path: string, value: unknown, options?: SetOptions): Promise<void> {
+        // Run validation (schemas are off during a bulk load unless kept; enforced ones are checked natively)
+        if (!this.enforceSchemas && typeof this.native.validatePath === 'function' && (!this.bulkLoad || this.bulkLoad.keepSchemas)) {
             this.native.validatePath(path, value);
         }
         
+        // Nobody hears the old value during a bulk load
+        const oldValue = this.bulkLoad ? undefined : this.native.get(path, options?.txn ? { txn: options.txn } : undefined);
+        value = this.runMiddleware('before', 'set', path, value);
+        this.native.set(path, value, options?.arrayPadding || options?.allowRootOverwrite || options?.owner || options?.txn
+            ? { arrayPadding: options.arrayPadding, allowRootOverwrite: options.allowRootOverwrite, owner: options.owner, txn: options.txn }
+            : undefined);
+        this.runMiddleware('after', 'set', path, value);
+        this.triggerSave();
+        this.notifySubscribers(path, value, oldValue);
+    }
+
+    /**
+     * Rewrite the value at `path` in canonical form: object keys sorted,
+     * integer-valued floats stored as integers, -0 as 0. Logged as one write.
+     */
+    public async canonicalize(path: string): Promise<void> {
+        const oldValue = this.native.get(path);
+        this.native.canonicalize(path);
+        const value = this.native.get(path);
+        this.triggerSave();
+        this.notifySubscribers(path, value, oldValue);
+    }
+
+    /**
+     * Canonical, whitespace-free JSON for hashing, diffing and deduplication:
+     * equal data always yields the same string. Throws ERR_INVALID_ARGUMENT
+     * for NaN and ±Infinity, which JSON cannot represent.
+     */
+    public static canonicalString(value: unknown): string {
+        const check = (v: unknown): void => {
+            if (typeof v === 'number' && !Number.isFinite(v)) {
+                throw new Error(`ERR_INVALID_ARGUMENT: ${v} has no JSON representation`);
+            }
+            if (v && typeof v === 'object') Object.values(v).forEach(check);
+        };
+        check(value);
+        return (NativeDb as any).canonicalString(value);
+    }
+
+    /**
+     * Validate a value against an ad-hoc schema without storing it.
+     * Returns every issue found (empty when valid); throws only if the schema is invalid.
+     */
+    public static validateValue(value: unknown, schema: Schema): SchemaIssue[] {
+        return validateValue(value, JSON.stringify(schema));
+    }
+
+    /**
+     * A value the schema accepts: each `default` where declared, else the first
+     * `enum` member, the lowest allowed number, a `minLength` string (or an example
+     * of its `format`), `minItems` items and every required property. Composition
+     * branches are tried in order. Strings are not generated to match a `pattern`.
+     */
+    public static generateSample<T = unknown>(schema: Schema): T {
+        return generateSample(JSON.stringify(schema));
+    }
+
+    /**
+     * Register (or replace) the schema for `path` and the paths below it. With
+     * `applyDefaults`, set() fills in the `default` of every missing property,
+     * nested objects and array items included, before validating and storing.
+     */
+    public registerSchema(path: string, schema: Schema, options?: SchemaOptions): void {
+        this.native.registerSchema(path, JSON.stringify(schema), options);
+    }
+
+    /** Paths with a registered schema, sorted */
+    public listSchemas(): string[] {
+        return this.native.listSchemas();
+    }
+
+    /**
+     * The schema registered at exactly `path`, as stored: `$ref`s resolved,
+     * unset keywords left out. Null if there is none.
+     */
+    public getSchema(path: string): Schema | null {
+        return this.native.getSchema(path);
+    }
+
+    /**
+     * The schema a write at `path` is checked against: the one registered at
+     * `path`, or else at its deepest ancestor with one (`exact` tells which),
+     * and the part of it describing `path`. Null if no schema covers `path`.
+     */
+    public resolveSchemaFor(path: string): SchemaMatch | null {
+        return this.native.resolveSchemaFor(path);
+    }
+
+    /**
+     * A value the schema governing `path` accepts at `path`, as generateSample()
+     * builds it. Throws ERR_NOT_FOUND if no registered schema describes `path`.
+     */
+    public generateFromSchema<T = unknown>(path: string): T {
+        return this.native.generateFromSchema(path);
+    }
+
+    /**
+     * Remove the schema registered at exactly `path`. Schemas that `$ref` it
+     * keep the copy taken when they were registered. False if there was none.
+     */
+    public unregisterSchema(path: string): boolean {
+        return this.native.unregisterSchema(path);
+    }
+
+    /**
+     * Like set(), but stores the value compressed (zstd). get() and query filters
+     * see the original value.
+     */
+    public async setCompressed(path: string, value: unknown): Promise<void> {
+        if (!this.enforceSchemas && typeof this.native.validatePath === 'function') {
+            this.native.validatePath(path, value);
+        }
+
         const oldValue = this.native.get(path);
         value = this.runMiddleware('before', 'set', path, value);
-        this.native.set(path, value);
+        this.native.setCompressed(path, value);
         this.runMiddleware('after', 'set', path, value);
         this.triggerSave();
-        this.updateIndicesForPath(path, value, false);
         this.notifySubscribers(path, value, oldValue);
     }
 
@@ -1164,31 +2490,34 @@ export class JSONDatabase extends EventEmitter {
         return this.native.has(path);
     }
 
-    public async delete(path: string): Promise<void> {
-        const oldValue = this.native.get(path);
+    /**
+     * Delete a path. `reason` (default 'explicit') is reported in the 'delete'
+     * and 'change' events and by changesSince(); use 'cascade' for removals that
+     * follow from another delete. Resolves to what cascade rules did.
+     */
+    public async delete(path: string, options?: { reason?: DeleteReason; allowRootOverwrite?: boolean; txn?: number }): Promise<CascadeCounts> {
+        const oldValue = this.native.get(path, options?.txn ? { txn: options.txn } : undefined);
         this.runMiddleware('before', 'delete', path, undefined);
-        this.native.delete(path);
+        const cascaded: CascadeCounts = this.native.delete(path, options?.reason || options?.allowRootOverwrite || options?.txn
+            ? { reason: options.reason, allowRootOverwrite: options.allowRootOverwrite, txn: options.txn }
+            : undefined);
         this.runMiddleware('after', 'delete', path, undefined);
         this.triggerSave();
-        this.updateIndicesForPath(path, oldValue, true);
         this.clearTTL(path);
-        this.notifySubscribers(path, undefined, oldValue);
+        this.notifySubscribers(path, undefined, oldValue, options?.reason ?? 'explicit');
+        return cascaded;
     }
 
     public async push(path: string, ...items: unknown[]): Promise<void> {
-        // Validation for each item if path matches a schema
-        // Note: push adds to array, so we should ideally validate the item against schema.items 
-        // if the path itself is a collection with a schema.
-        // For simplicity, we can validate the whole new collection after pushing? 
-        // No, let's validate each item if possible, or skip for now and rely on full collection validation.
-        
-        const oldValue = this.native.get(path);
-        for (const item of items) {
-             // Basic validation attempt: we don't know if 'item' matches 'path' or 'path' is parent.
-             // Native validatePath handles prefix matching.
-            this.native.push(path, item);
-        }
-        const newValue = this.native.get(path);
+        return this.pushItems(path, items);
+    }
+
+    private async pushItems(path: string, items: unknown[], txn?: number): Promise<void> {
+        // Native pushMany validates the new elements against the array's schema
+        // (items, minItems/maxItems, uniqueItems) and applies all-or-nothing
+        const oldValue = this.native.get(path, txn ? { txn } : undefined);
+        this.native.pushMany(path, items, txn ? { txn } : undefined);
+        const newValue = this.native.get(path, txn ? { txn } : undefined);
         this.triggerSave();
         // Arrays don't need index updates (indices are for object collections)
         this.notifySubscribers(path, newValue, oldValue);
@@ -1230,13 +2559,93 @@ export class JSONDatabase extends EventEmitter {
         return null;
     }
 
+    /**
+     * Documents with an index key between `start` and `end` (null for an open
+     * end), in key order. Bounds are inclusive unless `startExclusive` /
+     * `endExclusive`; numeric bounds compare keys as numbers. Page with `limit`
+     * and the last key seen as an exclusive `start`.
+     */
+    public async findByIndexRange<T = unknown>(
+        indexName: string,
+        start: unknown,
+        end: unknown,
+        options?: { startExclusive?: boolean; endExclusive?: boolean; limit?: number }
+    ): Promise<T[]> {
+        const paths: string[] = this.native.findIndexRange(indexName, start ?? null, end ?? null, options);
+        return paths.map(path => this.native.get(path) as T);
+    }
+
+    /**
+     * Index the documents of `collectionPath` under the keys `extract(doc, key)`
+     * returns (strings or numbers; null for none), then query it like any other
+     * index. The extractor must be synchronous, fast and must not touch the
+     * database; it runs for written documents when the index is next read.
+     */
+    public registerCustomIndex(
+        name: string,
+        collectionPath: string,
+        extract: (doc: any, key: string) => Array<string | number> | null | undefined,
+        options?: CustomIndexOptions
+    ): CustomIndexStatus {
+        return this.native.registerCustomIndex(name, collectionPath, extract, options);
+    }
+
+    public customIndexStatus(name: string): CustomIndexStatus {
+        return this.native.customIndexStatus(name);
+    }
+
+    /** Run the extractor over the whole collection again */
+    public rebuildCustomIndex(name: string): CustomIndexStatus {
+        return this.native.rebuildCustomIndex(name);
+    }
+
+    /** Compare a custom index with what its extractor yields now */
+    public verifyCustomIndex(name: string): CustomIndexVerification {
+        return this.native.verifyCustomIndex(name);
+    }
+
     /**
      * Manually trigger index rebuild
      */
     public rebuildIndex(): void {
+        this.assertOpen();
         this.rebuildIndices();
     }
 
+    /**
+     * Fill the registered index `name` from the documents of `collectionPath`
+     * in one pass, dropping any stale entries, and save it. The index keeps
+     * tracking the collection afterwards.
+     */
+    public buildIndex(name: string, collectionPath: string): BuildIndexResult {
+        return this.native.buildIndex(name, collectionPath);
+    }
+
+    /**
+     * Compare the field index `name` with the documents of `collectionPath`,
+     * e.g. after a crash. With `repair` the differences are fixed in place and
+     * the index saved.
+     */
+    public verifyIndex(name: string, collectionPath: string, repair = false): IndexVerification {
+        return this.native.verifyIndex(name, collectionPath, repair);
+    }
+
+    /** Every registered index, custom ones included, in name order */
+    public listIndexes(): IndexInfo[] {
+        return this.native.listIndexes();
+    }
+
+    /**
+     * Unregister an index and delete its file. Returns false if there was no
+     * such index.
+     */
+    public dropIndex(name: string): boolean {
+        const dropped: boolean = this.native.dropIndex(name);
+        // rebuildIndex() must not bring it back
+        this.indices = this.indices.filter(idx => idx.name !== name);
+        return dropped;
+    }
+
     // ============================================
     // QUERY
     // ============================================
@@ -1334,20 +2743,42 @@ export class JSONDatabase extends EventEmitter {
         };
     }
 
+    /**
+     * One page of a walk over an object collection in a stable order (by key,
+     * or by `stableBy` field with the key breaking ties). Unlike `paginate`,
+     * writes between pages never repeat a document or skip one that was
+     * already there; documents inserted behind the cursor are not returned.
+     */
+    public async getPage<T = unknown>(path: string, options: PageOptions = {}): Promise<Page<T>> {
+        return this.native.getPage(path, { ...options, after: options.after ?? undefined });
+    }
+
     // ============================================
     // BATCH OPERATIONS
     // ============================================
 
     public async batch(ops: BatchOperation[]): Promise<void> {
-        for (const op of ops) {
+        for (let i = 0; i < ops.length; i++) {
+            const op = ops[i];
+            if (this.bulkLoad && op.type === 'set') {
+                // A run of sets is applied under one write lock per chunk
+                const chunk: [string, unknown][] = [];
+                for (; i < ops.length && ops[i].type === 'set' && chunk.length < BULK_CHUNK; i++) {
+                    chunk.push([ops[i].path, ops[i].value]);
+                }
+                i--;
+                const result = this.native.batchSetParallel(chunk);
+                if (!result.success) throw new Error(result.error);
+                if (result.invalid?.length) throw new Error(result.invalid[0].error);
+                for (const [path] of chunk) this.notifySubscribers(path, undefined, undefined);
+                continue;
+            }
             switch (op.type) {
                 case 'set':
                     this.native.set(op.path, op.value);
-                    this.updateIndicesForPath(op.path, op.value, false);
                     break;
                 case 'delete':
                     this.native.delete(op.path);
-                    this.updateIndicesForPath(op.path, undefined, true);
                     break;
                 case 'push':
                     this.native.push(op.path, op.value);
@@ -1356,14 +2787,12 @@ export class JSONDatabase extends EventEmitter {
                     const val = (this.native.get(op.path) as number) ?? 0;
                     const newVal = val + (op.value as number);
                     this.native.set(op.path, newVal);
-                    this.updateIndicesForPath(op.path, newVal, false);
                     break;
                 }
                 case 'subtract': {
                     const val = (this.native.get(op.path) as number) ?? 0;
                     const newVal = val - (op.value as number);
                     this.native.set(op.path, newVal);
-                    this.updateIndicesForPath(op.path, newVal, false);
                     break;
                 }
             }
@@ -1372,67 +2801,288 @@ export class JSONDatabase extends EventEmitter {
         this.emit('batch', { operations: ops });
     }
 
+    /**
+     * Check every condition and, only if all pass, apply every op as one
+     * indivisible step. Resolves to whether the ops were applied; no
+     * transaction is needed, so concurrent callers never conflict.
+     */
+    public async atomic(conditions: AtomicCondition[], ops: BatchOperation[], options: { owner?: string } = {}): Promise<boolean> {
+        const committed = this.native.atomic(conditions, ops, options);
+        if (!committed) return false;
+        this.triggerSave();
+        this.emit('batch', { operations: ops });
+        return true;
+    }
+
+    /**
+     * Read-modify-write of `path` with no other write able to land in between.
+     * `updater` gets the current value (undefined when missing) and returns the
+     * new one, or undefined to leave it; resolves to the value now stored.
+     * The updater runs synchronously while every other writer waits, so keep
+     * it short and don't use the database from inside it.
+     */
+    public async atomicUpdate<T = unknown>(
+        path: string,
+        updater: (current: T | undefined) => T | undefined,
+        options?: AtomicUpdateOptions
+    ): Promise<T | null> {
+        let oldValue: unknown;
+        const value = this.native.atomicUpdate(path, (current: T | undefined) => {
+            oldValue = current;
+            return updater(current);
+        }, options);
+        this.triggerSave();
+        this.notifySubscribers(path, value, oldValue);
+        return value;
+    }
+
+    /**
+     * `atomicUpdate` over several paths at once, for invariants spanning keys.
+     * `updater` gets the current values in `paths` order and returns an array
+     * of new ones (undefined entries leave their path), or undefined for no
+     * change. Either every path is written or none is.
+     */
+    public async atomicUpdateMany<T extends unknown[] = unknown[]>(
+        paths: string[],
+        updater: (current: Partial<T>) => Partial<T> | undefined,
+        options?: AtomicUpdateOptions
+    ): Promise<T> {
+        let oldValues: unknown[] = [];
+        const values = this.native.atomicUpdateMany(paths, (current: Partial<T>) => {
+            oldValues = [...current];
+            return updater(current);
+        }, options);
+        this.triggerSave();
+        paths.forEach((path, i) => this.notifySubscribers(path, values[i], oldValues[i]));
+        return values as T;
+    }
+
+    /**
+     * Add a document under a key from the collection's ID strategy (uuid4
+     * unless set with setIdStrategy) and resolve to the key
+     */
+    public async insert(collectionPath: string, doc: unknown): Promise<string> {
+        const [key] = await this.insertMany(collectionPath, [doc]);
+        return key;
+    }
+
+    /**
+     * Insert several documents, resolving to their keys in order. Throws
+     * ERR_CONFLICT, writing nothing, if a key already exists.
+     */
+    public async insertMany(collectionPath: string, docs: unknown[]): Promise<string[]> {
+        const keys: string[] = this.native.insertMany(collectionPath, docs);
+        keys.forEach((key, i) => {
+            const path = `${collectionPath}.${key}`;
+            this.notifySubscribers(path, docs[i], undefined);
+        });
+        this.triggerSave();
+        return keys;
+    }
+
+    /**
+     * Choose how insert() keys new documents of a collection: 'uuid4' (default),
+     * 'ulid' (sortable by time), 'nanoid' (URL-safe, `length` 4-64, default 21),
+     * `{ type: 'sequence', name }` or `{ type: 'field', field }`. Persisted with
+     * the data; null restores the default.
+     */
+    public setIdStrategy(collectionPath: string, strategy: IdStrategy | null): void {
+        this.native.setIdStrategy(collectionPath, strategy);
+    }
+
+    /** The effective ID strategy of a collection, and whether it was configured */
+    public idStrategy(collectionPath: string): { type: string; configured: boolean; length?: number; name?: string; field?: string } {
+        return this.native.idStrategy(collectionPath);
+    }
+
+    /**
+     * When a document of `parentCollection` is deleted, act on the documents of
+     * `childCollection` whose `childField` holds its key: 'delete' them too,
+     * 'nullify' the field, or 'restrict' (refuse the delete with ERR_CONSTRAINT).
+     * Applies to delete, deleteMany and deletePrefix; rules forming a cycle are
+     * rejected. Persisted with the data.
+     */
+    public registerCascade(
+        name: string,
+        parentCollection: string,
+        childCollection: string,
+        childField: string,
+        action: CascadeAction
+    ): void {
+        this.native.registerCascade(name, parentCollection, childCollection, childField, action);
+    }
+
+    /** Remove a cascade rule; false if it did not exist */
+    public dropCascade(name: string): boolean {
+        return this.native.dropCascade(name);
+    }
+
+    public cascades(): Record<string, CascadeRule> {
+        return this.native.cascades();
+    }
+
+    /**
+     * Insert or update documents of `collectionPath` keyed by `keyField`.
+     * Documents missing the key or failing validation are reported in `failed`,
+     * unless the collection has an ID strategy: then they get a generated key.
+     */
+    public async upsertMany(
+        collectionPath: string,
+        docs: Record<string, unknown>[],
+        keyField: string,
+        options: { mode?: 'replace' | 'merge'; validate?: boolean; batchSize?: number; owner?: string } = {}
+    ): Promise<{ inserted: number; updated: number; failed: { index: number; error: string }[] }> {
+        const result = this.native.upsertMany(collectionPath, docs, keyField, options);
+        this.triggerSave();
+        return result;
+    }
+
+    /**
+     * Update every document of a collection matching the filters. Object documents
+     * are shallow-merged with `patch` unless `mode` is 'replace'. Array collections
+     * are updated in place and report the matched `indices`; object collections
+     * report `keys`.
+     */
+    public async updateMany(
+        collectionPath: string,
+        filters: QueryFilter[],
+        patch: unknown,
+        options: { mode?: 'merge' | 'replace'; owner?: string; txn?: number } = {}
+    ): Promise<{ updated: number; indices?: number[]; keys?: string[] }> {
+        const result = this.native.updateMany(collectionPath, filters, patch, options);
+        this.triggerSave();
+        return result;
+    }
+
+    /**
+     * Delete every document of a collection matching the filters. Matching array
+     * elements are removed in descending index order, so the reported `indices`
+     * are positions in the array as it was before the call.
+     */
+    public async deleteMany(
+        collectionPath: string,
+        filters: QueryFilter[],
+        options: { owner?: string; txn?: number } = {}
+    ): Promise<{ deleted: number; indices?: number[]; keys?: string[]; cascaded?: CascadeCounts }> {
+        const result = this.native.deleteMany(collectionPath, filters, options);
+        this.triggerSave();
+        return result;
+    }
+
+    // ============================================
+    // BULK LOAD
+    // ============================================
+
+    /** The bulk load in progress: top-level keys written (for its change events) and its options */
+    private bulkLoad: { touched: Set<string>; keepSchemas: boolean } | null = null;
+
+    /**
+     * Start a bulk load for a large import. Until endBulkLoad(), writes skip the
+     * WAL (a crash loses the whole load), subscribers and change events, and the
+     * upkeep of counters, indexes and views; schemas are not enforced unless
+     * `keepSchemas` is set. Runs of set ops in batch() go to the engine in
+     * chunks. Fails inside a transaction, and transactions fail during a load.
+     */
+    public beginBulkLoad(options?: BulkLoadOptions): void {
+        this.native.beginBulkLoad(options);
+        this.bulkLoad = { touched: new Set(), keepSchemas: options?.keepSchemas ?? false };
+    }
+
+    /**
+     * End the bulk load: rebuild indexes and counters, save (the load is durable
+     * from here) and notify subscribers once per top-level key written, with
+     * its new value.
+     */
+    public async endBulkLoad(): Promise<BulkLoadReport> {
+        const report: BulkLoadReport = this.native.endBulkLoad();
+        const touched = this.bulkLoad?.touched ?? new Set<string>();
+        this.bulkLoad = null;
+        for (const key of touched) {
+            this.notifySubscribers(key, this.native.get(key), undefined);
+        }
+        this.emit('bulkLoad', report);
+        return report;
+    }
+
+    public isBulkLoading(): boolean {
+        return this.native.isBulkLoading();
+    }
+
     // ============================================
     // TRANSACTIONS
     // ============================================
 
-    private inTransaction = false;
+    /** Native id of the transaction() running on this instance */
+    private transactionId: number | null = null;
 
     /**
      * Execute a function within a transaction.
      * Supports nested transactions using savepoints.
-     * All operations (set, delete, etc.) called on this DB instance within the 
-     * transaction function will be atomic.
+     * Writes through `tx.set`/`tx.delete`/`tx.push` (or passing `txn: tx.id`)
+     * within the transaction function are recorded in it and will be atomic.
+     * Other writes join no transaction: they fail with ERR_CONFLICT on a path
+     * an open transaction has written and are not undone by a rollback.
+     *
+     * With `isolation: 'exclusive'` writes must go through `tx` (or pass
+     * `txn: tx.id`), and reads that should see them through `tx.get`; any
+     * other write fails with ERR_TRANSACTION_ACTIVE until it ends, and other
+     * reads see the data as it was.
      */
     public async transaction<T = unknown>(
-        fn: (tx: Transaction) => Promise<T> | T
+        fn: (tx: Transaction) => Promise<T> | T,
+        options?: TransactionOptions
     ): Promise<T> {
         // Check if native module supports transactions
         const hasNativeTransactions = typeof this.native.beginTransaction === 'function';
+        const handle = (id: number): Transaction => ({
+            id,
+            get: async <V>(path: string, defaultValue: V | null = null) => {
+                const val = this.native.get(path, { txn: id });
+                return (val === null || val === undefined ? defaultValue : val) as V;
+            },
+            savepoint: async (name) => hasNativeTransactions && this.native.createSavepoint(name, id),
+            rollbackTo: async (name) => hasNativeTransactions && this.native.rollbackToSavepoint(name, id),
+            set: (path, value, options) => this.set(path, value, { ...options, txn: id }),
+            delete: (path, options) => this.delete(path, { ...options, txn: id }),
+            push: (path, ...items) => this.pushItems(path, items, id),
+        });
         
-        if (this.inTransaction) {
+        if (this.transactionId !== null) {
             // Nested transaction: use a savepoint
+            const id = this.transactionId;
             const savepointName = `nested_${Math.random().toString(36).slice(2, 9)}`;
             if (hasNativeTransactions) {
-                this.native.createSavepoint(savepointName);
+                this.native.createSavepoint(savepointName, id);
             }
             try {
-                const result = await fn({
-                    savepoint: async (name) => hasNativeTransactions && this.native.createSavepoint(name),
-                    rollbackTo: async (name) => hasNativeTransactions && this.native.rollbackToSavepoint(name)
-                });
-                return result;
+                return await fn(handle(id));
             } catch (error) {
                 if (hasNativeTransactions) {
-                    this.native.rollbackToSavepoint(savepointName);
+                    this.native.rollbackToSavepoint(savepointName, id);
                 }
                 throw error;
             }
         }
 
-        this.inTransaction = true;
-        if (hasNativeTransactions) {
-            this.native.beginTransaction();
-        }
-        
-        const tx: Transaction = {
-            savepoint: async (name) => hasNativeTransactions && this.native.createSavepoint(name),
-            rollbackTo: async (name) => hasNativeTransactions && this.native.rollbackToSavepoint(name)
-        };
+        // Fails during a bulk load, before this instance counts as in one
+        const id: number = hasNativeTransactions ? this.native.beginTransaction(options) : 0;
+        this.transactionId = id;
 
         try {
-            const result = await fn(tx);
+            const result = await fn(handle(id));
+            // close() rolled the transaction back
+            this.assertOpen();
             if (hasNativeTransactions) {
-                this.native.commitTransaction();
+                this.native.commitTransaction(id);
             }
-            this.inTransaction = false;
+            this.transactionId = null;
             this.emit('transaction:commit');
             return result;
         } catch (error) {
-            if (hasNativeTransactions) {
-                this.native.rollbackTransaction();
+            if (hasNativeTransactions && !this.closing) {
+                this.native.rollbackTransaction(id);
             }
-            this.inTransaction = false;
+            this.transactionId = null;
             this.emit('transaction:rollback', { error });
             throw error;
         }
@@ -1451,7 +3101,46 @@ export class JSONDatabase extends EventEmitter {
         return backupPath;
     }
 
+    /**
+     * Write data, indexes, schemas and engine metadata to one checksummed
+     * archive: the artifact to back up or move a database with. Reflects the
+     * in-memory state, unsaved writes included. Fails with
+     * ERR_TRANSACTION_ACTIVE while an exclusive transaction is open.
+     */
+    public async exportArchive(outPath: string, options: ExportOptions = {}): Promise<ArchiveManifest> {
+        return this.native.exportArchive(outPath, options);
+    }
+
+    /**
+     * Restore an archive as a new database at `targetPath` and open it. Entries
+     * are verified before anything is written, so a damaged archive throws
+     * ERR_CORRUPT and leaves nothing at the target. Archived schemas and indexes
+     * are registered alongside any given in `options`.
+     */
+    public static async importArchive(archivePath: string, targetPath: string, options: DBOptions = {}): Promise<JSONDatabase> {
+        (NativeDb as any).importArchive(archivePath, targetPath).close();
+        const manifest = JSONDatabase.archiveManifest(archivePath);
+        const indices = [...(options.indices ?? [])];
+        for (const entry of manifest.entries) {
+            if (entry.kind !== 'index' || entry.collection === undefined) continue;
+            if (indices.some(idx => idx.name === entry.name)) continue;
+            indices.push({ name: entry.name, path: entry.collection, field: entry.field ?? '', sparse: entry.sparse });
+        }
+        return new JSONDatabase(targetPath, {
+            ...options,
+            indices,
+            schemas: { ...manifest.schemas, ...options.schemas },
+        });
+    }
+
+    /** Read an archive's manifest without unpacking it */
+    public static archiveManifest(archivePath: string): ArchiveManifest {
+        return (NativeDb as any).archiveManifest(archivePath);
+    }
+
     public async restoreSnapshot(snapshotPath: string): Promise<void> {
+        // The file may belong to another process by now
+        this.assertOpen();
         if (!existsSync(snapshotPath)) {
             throw new Error(`Snapshot not found: ${snapshotPath}`);
         }
@@ -1504,7 +3193,7 @@ export class JSONDatabase extends EventEmitter {
      * Clear all data
      */
     public async clear(): Promise<void> {
-        await this.set('', {});
+        await this.set('', {}, { allowRootOverwrite: true });
     }
 
     /**
@@ -1552,7 +3241,11 @@ export class JSONDatabase extends EventEmitter {
      * Uses multiple CPU cores when workload is large enough (≥100 items).
      * Falls back to sequential processing for small batches to avoid overhead.
      * 
+     * Values are checked against registered schemas (in parallel) before anything
+     * is written; invalid values are listed in `result.invalid`.
+     * 
      * @param operations - Array of {path, value} objects to set
+     * @param options - Schema validation behaviour
      * @returns ParallelResult with success status and count of operations completed
      * 
      * @example
@@ -1566,25 +3259,33 @@ export class JSONDatabase extends EventEmitter {
      * ```
      */
     public async batchSetParallel(
-        operations: Array<{ path: string; value: unknown }>
+        operations: Array<{ path: string; value: unknown }>,
+        options?: BatchSetOptions
     ): Promise<ParallelResult> {
         // Convert to tuple array for native call
         const tuples: Array<[string, unknown]> = operations.map(op => [op.path, op.value]);
         
-        const result = this.native.batchSetParallel(tuples);
+        const result = this.native.batchSetParallel(tuples, options);
+        if (!result.success) {
+            return { success: false, count: 0, error: result.error, invalid: result.invalid };
+        }
         
         // Trigger save after batch
         this.triggerSave();
         
-        // Emit batch event
+        // Emit batch event for the values actually written
+        const skipped = new Set((result.invalid ?? []).map((issue: { index: number }) => issue.index));
         this.emit('batch', { 
-            operations: operations.map(op => ({ type: 'set', path: op.path, value: op.value }))
+            operations: operations
+                .filter((_, i) => !skipped.has(i))
+                .map(op => ({ type: 'set', path: op.path, value: op.value }))
         });
         
         return {
             success: result.success,
             count: result.count,
-            error: result.error
+            error: result.error,
+            invalid: result.invalid
         };
     }
 
@@ -1607,12 +3308,94 @@ export class JSONDatabase extends EventEmitter {
      */
     public async parallelQuery<T = unknown>(
         path: string, 
-        filters: QueryFilter[]
+        filters: QueryFilter[],
+        options?: { emit?: 'values' | 'entries' | 'object' | 'indexed' }
     ): Promise<T[]> {
-        const result = this.native.parallelQuery(path, filters);
+        const result = this.native.parallelQuery(path, filters, options);
         return result as T[];
     }
 
+    /**
+     * Run the same filters over several collections (e.g. `events_2023`,
+     * `events_2024`, `events_current`) and merge the matches. Sorting, offset
+     * and limit apply to the merged list, so "latest 50 across all years" is
+     * one call. Collections that do not exist are listed in `missing`.
+     */
+    public async parallelQueryMulti<T = unknown>(
+        paths: string[],
+        filters: QueryFilter[],
+        options?: MultiQueryOptions
+    ): Promise<MultiQueryResult<T>> {
+        return this.native.parallelQueryMulti(paths, filters, options);
+    }
+
+    /**
+     * How parallelQuery would run. When the first filter is an eq on a scalar
+     * or a gt/gte/lt/lte on a number, and an index registered on that
+     * collection covers its field, the index supplies the candidates and only
+     * those are checked against the filters; otherwise the collection is
+     * scanned and `reason` says why.
+     */
+    public explainQuery(
+        path: string,
+        filters: QueryFilter[],
+        options?: { emit?: 'values' | 'entries' | 'object' | 'indexed' }
+    ): QueryPlan {
+        return this.native.explainQuery(path, filters, options);
+    }
+
+    /**
+     * Scan a collection and store planner statistics for its indexed fields.
+     * With them the planner scans instead of using an index expected to
+     * select more than half of the collection.
+     */
+    public analyze(collectionPath: string, options?: { staleAfter?: number }): CollectionStats {
+        return this.native.analyze(collectionPath, options);
+    }
+
+    /** Stats stored by analyze(), with writes since and staleness; null if never analyzed */
+    public collectionStats(path: string): CollectionStats | null {
+        return this.native.collectionStats(path);
+    }
+
+    /**
+     * Query a collection with a string such as
+     * `status = "active" AND (age > 30 OR role IN ["admin","ops"]) ORDER BY createdAt DESC LIMIT 20`.
+     * Parse errors carry the character position and the expected token.
+     */
+    public async queryString<T = unknown>(path: string, query: string): Promise<T[]> {
+        return this.native.queryString(path, query) as T[];
+    }
+
+    /**
+     * Parse a string query without running it, for debugging:
+     * `{ where, orderBy, limit, offset }`
+     */
+    public static parseQuery(query: string): ParsedQuery {
+        return (NativeDb as any).parseQuery(query);
+    }
+
+    /**
+     * Read-only handle on the live data for this thread: shares data and
+     * indexes without copying and exposes only reads and queries.
+     */
+    public createReader(): InstanceType<typeof ReaderHandle> {
+        return this.native.createReader();
+    }
+
+    /**
+     * Token to post to a worker thread, which opens its own reader with
+     * `JSONDatabase.openReader(token)`. Valid until this database is closed;
+     * readers already open keep the data in memory after that.
+     */
+    public readerToken(): number {
+        return this.native.readerToken();
+    }
+
+    public static openReader(token: number): InstanceType<typeof ReaderHandle> {
+        return ReaderHandle.open(token);
+    }
+
     /**
      * Parallel aggregation operations using native Rust processing.
      * Efficiently computes sum, avg, min, max, or count over large datasets.
@@ -0,0 +1,292 @@
+//! Storage advisor
+//!
+//! Correlates the save history, WAL size and pressure, the data file and the
+//! indexes into findings about how the database is used: each carries a
+//! stable `code` to alert on, a severity, a sentence with the numbers behind
+//! it, and those numbers as `metrics`. Byte-based findings stay quiet below a
+//! floor (`min_bytes`), so a small database never looks pathological.
+
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+use crate::persistence::SaveReport;
+use crate::wal::WalPressure;
+
+/// Limits beyond which a pattern is reported
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// How far back save history is considered
+    pub window_ms: u64,
+    /// Byte-based findings need at least this much data behind them
+    pub min_bytes: u64,
+    /// Bytes rewritten by saves per byte of logged changes
+    pub save_amplification: f64,
+    /// WAL bytes per byte of data file growth
+    pub wal_growth: f64,
+    /// Share of the data file that is pretty-printing whitespace
+    pub pretty_overhead: f64,
+    /// Index size relative to the collection it indexes
+    pub index_ratio: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            window_ms: 60 * 60 * 1000,
+            min_bytes: 1024 * 1024,
+            save_amplification: 100.0,
+            wal_growth: 50.0,
+            pretty_overhead: 0.25,
+            index_ratio: 1.0,
+        }
+    }
+}
+
+/// Serialized size of one index and of the collection it covers
+pub struct IndexSize {
+    pub name: String,
+    pub collection: Option<String>,
+    pub entries: usize,
+    pub bytes: u64,
+    pub collection_bytes: u64,
+}
+
+/// What the advisor looks at, gathered under the database's locks
+pub struct Inputs<'a> {
+    /// Saves inside the window, oldest first
+    pub saves: &'a [SaveReport],
+    /// None when no data file has been written yet
+    pub file_bytes: Option<u64>,
+    /// The data file's content serialized without whitespace
+    pub compact_bytes: u64,
+    /// Unsaved WAL on disk; None with the WAL disabled
+    pub wal_bytes: Option<u64>,
+    pub wal_pressure: Option<WalPressure>,
+    pub indexes: Vec<IndexSize>,
+}
+
+/// Counts bytes written to it, to size a serialization without keeping it
+#[derive(Default)]
+pub struct ByteCount(pub u64);
+
+impl Write for ByteCount {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serialized (compact) size of `value`
+pub fn serialized_bytes<T: serde::Serialize + ?Sized>(value: &T) -> u64 {
+    let mut count = ByteCount::default();
+    let _ = serde_json::to_writer(&mut count, value);
+    count.0
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+struct Finding {
+    code: &'static str,
+    severity: Severity,
+    message: String,
+    metrics: Value,
+}
+
+/// `12KB`, `1.9GB`: sizes as they read in a message
+fn bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = n as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || size >= 100.0 {
+        format!("{:.0}{}", size, UNITS[unit])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+fn duration(ms: u64) -> String {
+    match ms {
+        ms if ms % 3_600_000 == 0 => match ms / 3_600_000 {
+            1 => "hour".to_string(),
+            h => format!("{} hours", h),
+        },
+        ms if ms % 60_000 == 0 => format!("{} minutes", ms / 60_000),
+        ms => format!("{}s", ms as f64 / 1000.0),
+    }
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    a as f64 / b.max(1) as f64
+}
+
+/// Every finding, most severe first
+pub fn findings(inputs: &Inputs, t: &Thresholds) -> Vec<Value> {
+    let mut found = Vec::new();
+    let window = duration(t.window_ms);
+    let rewritten: u64 = inputs.saves.iter().map(|s| s.bytes).sum();
+    let logged: u64 = inputs.saves.iter().map(|s| s.wal_bytes_truncated).sum();
+
+    // Without a WAL there is no record of how much actually changed
+    if inputs.wal_bytes.is_some() && !inputs.saves.is_empty() && rewritten >= t.min_bytes {
+        let amplification = ratio(rewritten, logged);
+        if amplification >= t.save_amplification {
+            found.push(Finding {
+                code: "SAVE_AMPLIFICATION",
+                severity: if amplification >= t.save_amplification * 100.0 { Severity::Critical } else { Severity::Warning },
+                message: format!(
+                    "save() rewrote {} to persist {} of changes in the last {} ({} saves) — save less often, \
+                     or move large collections to lazyCollections so saves skip them",
+                    bytes(rewritten), bytes(logged), window, inputs.saves.len()
+                ),
+                metrics: json!({
+                    "saves": inputs.saves.len(),
+                    "bytesRewritten": rewritten,
+                    "walBytes": logged,
+                    "amplification": amplification,
+                }),
+            });
+        }
+    }
+
+    if let Some(file_bytes) = inputs.file_bytes.filter(|&b| b >= t.min_bytes) {
+        let whitespace = file_bytes.saturating_sub(inputs.compact_bytes);
+        let share = ratio(whitespace, file_bytes);
+        if share >= t.pretty_overhead {
+            found.push(Finding {
+                code: "PRETTY_FILE_OVERHEAD",
+                severity: if share >= 0.5 { Severity::Warning } else { Severity::Info },
+                message: format!(
+                    "{} of the {} data file is pretty-printing whitespace ({:.0}%) — deeply nested data \
+                     pays for its indentation; lazyCollections are stored compact",
+                    bytes(whitespace), bytes(file_bytes), share * 100.0
+                ),
+                metrics: json!({
+                    "fileBytes": file_bytes,
+                    "compactBytes": inputs.compact_bytes,
+                    "whitespaceBytes": whitespace,
+                    "share": share,
+                }),
+            });
+        }
+    }
+
+    // Data growth over the window: from the file the first save replaced to the last one
+    if let (Some(first), Some(last)) = (inputs.saves.first(), inputs.saves.last()) {
+        let growth = last.bytes.saturating_sub(first.previous_bytes.unwrap_or(0));
+        let growth_ratio = ratio(logged, growth);
+        if logged >= t.min_bytes && growth_ratio >= t.wal_growth {
+            found.push(Finding {
+                code: "WAL_OUTGROWS_DATA",
+                severity: Severity::Warning,
+                message: format!(
+                    "The WAL grew {} while the data file grew {} in the last {} ({:.0}x) — every write logs \
+                     the whole value it sets; write narrower paths instead of rewriting documents",
+                    bytes(logged), bytes(growth), window, growth_ratio
+                ),
+                metrics: json!({
+                    "walBytes": logged,
+                    "dataGrowthBytes": growth,
+                    "ratio": growth_ratio,
+                }),
+            });
+        }
+    }
+
+    if let Some(wal_bytes) = inputs.wal_bytes.filter(|&b| b >= t.min_bytes) {
+        let file_bytes = inputs.file_bytes.unwrap_or(0);
+        if wal_bytes >= file_bytes {
+            found.push(Finding {
+                code: "WAL_BACKLOG",
+                severity: Severity::Warning,
+                message: format!(
+                    "The WAL holds {} of unsaved writes, more than the {} data file — opening the database \
+                     replays all of it; call save() more often",
+                    bytes(wal_bytes), bytes(file_bytes)
+                ),
+                metrics: json!({
+                    "walBytes": wal_bytes,
+                    "fileBytes": file_bytes,
+                    "ratio": ratio(wal_bytes, file_bytes),
+                }),
+            });
+        }
+    }
+
+    if let Some(p) = &inputs.wal_pressure {
+        if p.rejected > 0 || p.dropped > 0 || p.blocked_appends > 0 {
+            let lost = p.rejected + p.dropped;
+            found.push(Finding {
+                code: "WAL_BACKPRESSURE",
+                severity: if lost > 0 { Severity::Critical } else { Severity::Warning },
+                message: format!(
+                    "The WAL commit queue filled up: {} appends blocked for {:.0}ms, {} rejected, {} dropped — \
+                     writes outpace the disk; batch them or raise walQueueCapacity",
+                    p.blocked_appends, p.blocked_ns as f64 / 1e6, p.rejected, p.dropped
+                ),
+                metrics: json!({
+                    "blockedAppends": p.blocked_appends,
+                    "blockedMs": p.blocked_ns as f64 / 1e6,
+                    "rejected": p.rejected,
+                    "dropped": p.dropped,
+                    "queueCapacity": p.queue_capacity,
+                }),
+            });
+        }
+    }
+
+    for idx in &inputs.indexes {
+        let size_ratio = ratio(idx.bytes, idx.collection_bytes);
+        if idx.bytes >= t.min_bytes && size_ratio > t.index_ratio {
+            found.push(Finding {
+                code: "INDEX_LARGER_THAN_COLLECTION",
+                severity: Severity::Info,
+                message: format!(
+                    "Index '{}' takes {} for the {} of '{}' it indexes — index a more selective field, \
+                     or drop it if queries do not use it",
+                    idx.name, bytes(idx.bytes), bytes(idx.collection_bytes), idx.collection.as_deref().unwrap_or("")
+                ),
+                metrics: json!({
+                    "index": idx.name,
+                    "collection": idx.collection,
+                    "entries": idx.entries,
+                    "indexBytes": idx.bytes,
+                    "collectionBytes": idx.collection_bytes,
+                    "ratio": size_ratio,
+                }),
+            });
+        }
+    }
+
+    found.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    found.into_iter()
+        .map(|f| json!({
+            "code": f.code,
+            "severity": f.severity.as_str(),
+            "message": f.message,
+            "metrics": f.metrics,
+        }))
+        .collect()
+}
@@ -0,0 +1,182 @@
+//! Anomaly scanner
+//!
+//! Walks stored data for values that are valid JSON but most likely bad data
+//! that slipped in from JS. Each finding has a stable `kind`:
+//!
+//! - `unsafeInteger`: an integral number beyond ±(2^53 - 1), which JS cannot
+//!   hold exactly, so it has probably lost precision on the way in or will on
+//!   the way out
+//! - `replacementChar`: a string (or key) holding U+FFFD, what lone surrogates
+//!   and invalid UTF-8 turn into
+//! - `controlChar`: a string (or key) holding a control character other than
+//!   tab, newline or carriage return
+//! - `emptyKey`: an object key that is the empty string
+//! - `unaddressableKey`: an object key containing `.` or `/`, which paths
+//!   cannot address
+//! - `trailingNulls`: an array ending in nulls, what setting an index past the
+//!   end pads with
+//! - `deepNesting`: a value nested deeper than the limit (not walked further)
+//!
+//! NaN and ±Infinity have no JSON form and cannot be stored, so they never
+//! show up: by the time a write reaches the engine they are already null.
+
+use rayon::prelude::*;
+use serde_json::{json, Map, Value};
+
+const KINDS: [&str; 7] = [
+    "unsafeInteger",
+    "replacementChar",
+    "controlChar",
+    "emptyKey",
+    "unaddressableKey",
+    "trailingNulls",
+    "deepNesting",
+];
+
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Findings kept per kind (all are counted)
+    pub per_kind: usize,
+    /// Levels below the scanned path walked before reporting `deepNesting`
+    pub max_depth: usize,
+}
+
+/// Findings of a walk: per kind, how many and the first `per_kind` of them
+pub struct Scan {
+    limits: Limits,
+    found: Vec<(u64, Vec<Value>)>,
+    /// Every array with trailing nulls, uncapped, for fixing
+    pub trailing_nulls: Vec<String>,
+}
+
+impl Scan {
+    fn new(limits: Limits) -> Self {
+        Scan { limits, found: vec![(0, Vec::new()); KINDS.len()], trailing_nulls: Vec::new() }
+    }
+
+    fn report(&mut self, kind: &'static str, path: &str, detail: String) {
+        let slot = KINDS.iter().position(|k| *k == kind).expect("known anomaly kind");
+        let (count, items) = &mut self.found[slot];
+        *count += 1;
+        if items.len() < self.limits.per_kind {
+            items.push(json!({ "path": path, "kind": kind, "detail": detail }));
+        }
+    }
+
+    fn merge(&mut self, other: Scan) {
+        for ((count, items), (other_count, other_items)) in self.found.iter_mut().zip(other.found) {
+            *count += other_count;
+            let room = self.limits.per_kind.saturating_sub(items.len());
+            items.extend(other_items.into_iter().take(room));
+        }
+        self.trailing_nulls.extend(other.trailing_nulls);
+    }
+
+    /// `{anomalies: [{path, kind, detail}], counts: {kind: n}, truncated}`,
+    /// findings grouped by kind
+    pub fn to_json(&self) -> Value {
+        let mut counts = Map::new();
+        let mut anomalies = Vec::new();
+        let mut truncated = false;
+        for (kind, (count, items)) in KINDS.iter().zip(&self.found) {
+            if *count == 0 {
+                continue;
+            }
+            counts.insert(kind.to_string(), json!(count));
+            truncated |= *count as usize > items.len();
+            anomalies.extend(items.iter().cloned());
+        }
+        json!({ "anomalies": anomalies, "counts": counts, "truncated": truncated })
+    }
+}
+
+fn child_path(base: &str, key: &str) -> String {
+    if base.is_empty() { key.to_string() } else { format!("{}.{}", base, key) }
+}
+
+/// Scan `root`, found at `base`, leaving out the top-level key `skip`. With
+/// `parallel`, the top-level keys are walked on the rayon pool.
+pub fn scan_tree(root: &Value, base: &str, skip: Option<&str>, limits: Limits, parallel: bool) -> Scan {
+    let Value::Object(map) = root else {
+        let mut out = Scan::new(limits);
+        walk(root, base, 0, &mut out);
+        return out;
+    };
+    let scan_entry = |(key, value): (&String, &Value)| {
+        let mut out = Scan::new(limits);
+        check_key(key, base, &mut out);
+        walk(value, &child_path(base, key), 1, &mut out);
+        out
+    };
+    let entries = map.iter().filter(|(key, _)| Some(key.as_str()) != skip);
+    let parts: Vec<Scan> = if parallel {
+        entries.collect::<Vec<_>>().into_par_iter().map(scan_entry).collect()
+    } else {
+        entries.map(scan_entry).collect()
+    };
+    parts.into_iter().fold(Scan::new(limits), |mut all, part| {
+        all.merge(part);
+        all
+    })
+}
+
+fn walk(value: &Value, path: &str, depth: usize, out: &mut Scan) {
+    if depth > out.limits.max_depth && matches!(value, Value::Object(_) | Value::Array(_)) {
+        out.report("deepNesting", path, format!("nested deeper than {} levels", out.limits.max_depth));
+        return;
+    }
+    match value {
+        Value::Number(n) => {
+            let unsafe_int = match (n.as_i64(), n.as_u64(), n.as_f64()) {
+                (Some(i), _, _) => i.unsigned_abs() as f64 > MAX_SAFE_INTEGER,
+                (_, Some(_), _) => true,
+                (_, _, Some(f)) => f.fract() == 0.0 && f.abs() > MAX_SAFE_INTEGER,
+                _ => false,
+            };
+            if unsafe_int {
+                out.report("unsafeInteger", path, format!("{} is beyond ±2^53 and may have lost precision", n));
+            }
+        }
+        Value::String(s) => check_text(s, path, || "string".to_string(), out),
+        Value::Array(items) => {
+            let nulls = items.iter().rev().take_while(|v| v.is_null()).count();
+            if nulls > 0 {
+                out.report("trailingNulls", path, format!("ends in {} null(s) of {} elements", nulls, items.len()));
+                out.trailing_nulls.push(path.to_string());
+            }
+            for (i, item) in items.iter().enumerate() {
+                walk(item, &child_path(path, &i.to_string()), depth + 1, out);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                check_key(key, path, out);
+                walk(item, &child_path(path, key), depth + 1, out);
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+}
+
+/// Key findings are reported at the object holding the key
+fn check_key(key: &str, parent: &str, out: &mut Scan) {
+    if key.is_empty() {
+        out.report("emptyKey", parent, "has an empty key".to_string());
+        return;
+    }
+    if let Some(c) = key.chars().find(|c| *c == '.' || *c == '/') {
+        out.report("unaddressableKey", parent, format!("key '{}' contains '{}', which paths cannot address", key, c));
+    }
+    check_text(key, parent, || format!("key {:?}", key), out);
+}
+
+fn check_text(text: &str, path: &str, what: impl Fn() -> String, out: &mut Scan) {
+    if text.contains('\u{FFFD}') {
+        out.report("replacementChar", path, format!("{} contains U+FFFD (a lone surrogate or invalid UTF-8 upstream)", what()));
+    }
+    if let Some(c) = text.chars().find(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r')) {
+        out.report("controlChar", path, format!("{} contains control character U+{:04X}", what(), c as u32));
+    }
+}
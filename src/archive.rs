@@ -0,0 +1,172 @@
+//! WAL archive
+//!
+//! With archiving on, a save keeps the WAL it makes redundant: the records are
+//! copied into `<db>.archive/` as a segment and the freshly saved base file is
+//! copied beside them as a checkpoint. `manifest.json` lists both in order, so
+//! the state at any archived moment is the latest checkpoint at or before it
+//! plus the records logged after that checkpoint, up to the moment. Nothing
+//! is removed until `prune` is called.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::DbError;
+use crate::wal::{apply_wal_op, read_wal_records, WalOp};
+
+/// A copy of the base file as written by one save
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub file: String,
+    pub at: u64,
+    /// Committed WAL LSN when the checkpoint was written (LSNs continue across restarts)
+    pub lsn: u64,
+    /// Segments archived before this checkpoint; all of them are contained in it
+    pub segments: usize,
+}
+
+/// WAL records truncated by one save
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Segment {
+    pub file: String,
+    pub archived_at: u64,
+    pub first_ts: u64,
+    pub last_ts: u64,
+    pub records: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub checkpoints: Vec<Checkpoint>,
+    pub segments: Vec<Segment>,
+    /// Numbers archive file names, so two saves in one millisecond do not collide
+    pub next_id: u64,
+}
+
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    pub fn new(db_path: &str) -> Self {
+        Archive { dir: PathBuf::from(format!("{}.archive", db_path)) }
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.dir.join("manifest.json")
+    }
+
+    pub fn load(&self) -> io::Result<Manifest> {
+        match File::open(self.manifest_path()) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).map_err(io::Error::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn store(&self, manifest: &Manifest) -> io::Result<()> {
+        let tmp = self.dir.join("manifest.json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(serde_json::to_string_pretty(manifest)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, self.manifest_path())
+    }
+
+    /// Archive the WAL about to be truncated and the base file just written.
+    /// Called by save between the two, so the checkpoint contains the segment.
+    pub fn checkpoint(&self, db_path: &str, wal_path: Option<&str>, at: u64, lsn: u64) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut manifest = self.load()?;
+
+        if let Some(wal_path) = wal_path.filter(|p| fs::metadata(p).is_ok_and(|m| m.len() > 0)) {
+            let (records, _) = read_wal_records(wal_path)?;
+            if !records.is_empty() {
+                let file = format!("segment-{:08}.wal", manifest.next_id);
+                manifest.next_id += 1;
+                fs::copy(wal_path, self.dir.join(&file))?;
+                manifest.segments.push(Segment {
+                    file,
+                    archived_at: at,
+                    first_ts: records.iter().map(|(_, op)| op.timestamp).min().unwrap_or(at),
+                    last_ts: records.iter().map(|(_, op)| op.timestamp).max().unwrap_or(at),
+                    records: records.len() as u64,
+                });
+            }
+        }
+
+        let file = format!("checkpoint-{:08}.json", manifest.next_id);
+        manifest.next_id += 1;
+        fs::copy(db_path, self.dir.join(&file))?;
+        let segments = manifest.segments.len();
+        manifest.checkpoints.push(Checkpoint { file, at, lsn, segments });
+        self.store(&manifest)
+    }
+
+    /// The value at `path` as of `timestamp` (null if it did not exist then).
+    /// `live_wal` holds the records logged since the last save.
+    pub fn value_at(&self, path: &str, timestamp: u64, live_wal: &str) -> Result<Value, DbError> {
+        let manifest = self.load()?;
+        let Some(first) = manifest.checkpoints.first() else {
+            return Err(DbError::NotFound("No archived checkpoints: history starts at the first save with walArchive enabled".to_string()));
+        };
+        let Some(checkpoint) = manifest.checkpoints.iter().rev().find(|c| c.at <= timestamp) else {
+            return Err(DbError::NotFound(format!(
+                "History before {} is not archived or has been pruned (requested {})", first.at, timestamp
+            )));
+        };
+
+        let file = File::open(self.dir.join(&checkpoint.file))?;
+        let mut root: Value = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| DbError::Corrupt(format!("Archived checkpoint {}: {}", checkpoint.file, e)))?;
+
+        // Only records touching the path, an ancestor or a descendant matter
+        let mut apply = |op: &WalOp| {
+            if op.timestamp <= timestamp && crate::paths_overlap(&op.path, path) {
+                apply_wal_op(&mut root, op);
+            }
+        };
+        for segment in &manifest.segments[checkpoint.segments.min(manifest.segments.len())..] {
+            if segment.first_ts > timestamp {
+                break;
+            }
+            let (records, _) = read_wal_records(&self.dir.join(&segment.file).to_string_lossy())?;
+            records.iter().for_each(|(_, op)| apply(op));
+        }
+        if Path::new(live_wal).exists() {
+            let (records, _) = read_wal_records(live_wal)?;
+            records.iter().for_each(|(_, op)| apply(op));
+        }
+
+        let ptr = if path.is_empty() { String::new() } else { format!("/{}", path.replace('.', "/")) };
+        let mut value = crate::compress::pointer(&root, &ptr).map(|v| v.into_owned()).unwrap_or(Value::Null);
+        crate::compress::expand(&mut value);
+        Ok(value)
+    }
+
+    /// Drop history before `before`: every checkpoint older than the latest one
+    /// at or before it, and the segments those checkpoints cover.
+    pub fn prune(&self, before: u64) -> io::Result<Value> {
+        let mut manifest = self.load()?;
+        let Some(keep) = manifest.checkpoints.iter().rposition(|c| c.at <= before) else {
+            return Ok(json!({ "checkpointsRemoved": 0, "segmentsRemoved": 0, "oldest": manifest.checkpoints.first().map(|c| c.at) }));
+        };
+        let segments = manifest.checkpoints[keep].segments;
+        let removed_checkpoints: Vec<Checkpoint> = manifest.checkpoints.drain(..keep).collect();
+        let removed_segments: Vec<Segment> = manifest.segments.drain(..segments).collect();
+        for checkpoint in manifest.checkpoints.iter_mut() {
+            checkpoint.segments -= segments;
+        }
+        // Manifest first: a crash then leaves orphaned files, never dangling entries
+        self.store(&manifest)?;
+        for file in removed_checkpoints.iter().map(|c| &c.file).chain(removed_segments.iter().map(|s| &s.file)) {
+            let _ = fs::remove_file(self.dir.join(file));
+        }
+        Ok(json!({
+            "checkpointsRemoved": removed_checkpoints.len(),
+            "segmentsRemoved": removed_segments.len(),
+            "oldest": manifest.checkpoints.first().map(|c| c.at),
+        }))
+    }
+}
@@ -0,0 +1,280 @@
+//! Portable backups
+//!
+//! `export_archive` packs everything needed to reopen a database elsewhere
+//! into one file: the in-memory data (reserved namespace included), every
+//! index, the registered schemas, and a manifest with format versions and a
+//! CRC32 per entry. Layout:
+//!
+//! ```text
+//! magic "JDBARCH\0" | u32 format | u32 manifest length | u32 manifest crc32
+//! manifest JSON | entry payloads, back to back in manifest order
+//! ```
+//!
+//! Integers are little-endian. Payloads are zstd-compressed when the manifest
+//! says so; checksums cover the uncompressed bytes. Unpacking verifies every
+//! entry into a staging directory before anything is moved to the target, so
+//! a truncated or corrupt archive leaves the target untouched.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::DbError;
+
+const MAGIC: &[u8; 8] = b"JDBARCH\0";
+pub const FORMAT: u32 = 1;
+/// Format of the data entry: a database file as written by `save`
+pub const DATA_FORMAT: u32 = 1;
+/// Format of index entries: an index file as written by `BTreeIndex::save`
+/// (binary since 2; format 1 archives hold JSON index files, which still load)
+pub const INDEX_FORMAT: u32 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Data,
+    Index,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryInfo {
+    pub name: String,
+    pub kind: EntryKind,
+    /// Indexed field and the collection the indexed documents live in (indexes only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Set for sparse indexes, which leave out documents whose field is null
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<bool>,
+    pub bytes: u64,
+    /// Size in the archive (differs from `bytes` when compressed)
+    pub stored: u64,
+    pub crc32: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub format: u32,
+    pub data_format: u32,
+    pub index_format: u32,
+    pub engine_version: String,
+    pub created_at: u64,
+    pub source: String,
+    pub compressed: bool,
+    pub entries: Vec<EntryInfo>,
+    pub schemas: Map<String, Value>,
+}
+
+/// One entry to pack
+pub struct Entry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub field: Option<String>,
+    pub collection: Option<String>,
+    pub sparse: Option<bool>,
+    pub content: Vec<u8>,
+}
+
+fn corrupt(archive: &Path, what: impl std::fmt::Display) -> DbError {
+    DbError::Corrupt(format!("Archive {} is damaged: {}", archive.display(), what))
+}
+
+/// Write the archive to `<out>.tmp` and rename it into place
+pub fn write(out: &Path, source: &str, created_at: u64, schemas: Map<String, Value>, entries: Vec<Entry>, compress: bool) -> Result<Manifest, DbError> {
+    let mut payloads = Vec::with_capacity(entries.len());
+    let mut infos = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let crc32 = crc32fast::hash(&entry.content);
+        let bytes = entry.content.len() as u64;
+        let payload = if compress {
+            zstd::encode_all(entry.content.as_slice(), 3).map_err(DbError::from)?
+        } else {
+            entry.content
+        };
+        infos.push(EntryInfo {
+            name: entry.name,
+            kind: entry.kind,
+            field: entry.field,
+            collection: entry.collection,
+            sparse: entry.sparse,
+            bytes,
+            stored: payload.len() as u64,
+            crc32,
+        });
+        payloads.push(payload);
+    }
+    let manifest = Manifest {
+        format: FORMAT,
+        data_format: DATA_FORMAT,
+        index_format: INDEX_FORMAT,
+        engine_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at,
+        source: source.to_string(),
+        compressed: compress,
+        entries: infos,
+        schemas,
+    };
+    let header = serde_json::to_vec(&manifest).map_err(|e| DbError::Io(e.to_string()))?;
+
+    let tmp = PathBuf::from(format!("{}.tmp", out.display()));
+    let written = (|| -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&FORMAT.to_le_bytes())?;
+        file.write_all(&(header.len() as u32).to_le_bytes())?;
+        file.write_all(&crc32fast::hash(&header).to_le_bytes())?;
+        file.write_all(&header)?;
+        for payload in &payloads {
+            file.write_all(payload)?;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, out)
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(DbError::Io(format!("Failed to write archive {}: {}", out.display(), e)));
+    }
+    Ok(manifest)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Read and check the header, leaving `reader` at the first payload
+fn read_header(archive: &Path, reader: &mut impl Read) -> Result<Manifest, DbError> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic).map_err(|_| corrupt(archive, "too short for a header"))?;
+    if &magic != MAGIC {
+        return Err(DbError::InvalidArgument(format!("{} is not a database archive", archive.display())));
+    }
+    let header = (|| -> io::Result<(u32, Vec<u8>, u32)> {
+        let format = read_u32(reader)?;
+        let len = read_u32(reader)?;
+        let crc = read_u32(reader)?;
+        let mut manifest = vec![0u8; len as usize];
+        reader.read_exact(&mut manifest)?;
+        Ok((format, manifest, crc))
+    })();
+    let (format, manifest, crc) = header.map_err(|_| corrupt(archive, "truncated header"))?;
+    if format > FORMAT {
+        return Err(DbError::InvalidArgument(format!(
+            "Archive format {} is newer than this engine supports ({})", format, FORMAT
+        )));
+    }
+    if crc32fast::hash(&manifest) != crc {
+        return Err(corrupt(archive, "manifest checksum mismatch"));
+    }
+    let manifest: Manifest = serde_json::from_slice(&manifest).map_err(|e| corrupt(archive, e))?;
+    if manifest.data_format > DATA_FORMAT || manifest.index_format > INDEX_FORMAT {
+        return Err(DbError::InvalidArgument(format!(
+            "Archive data/index formats {}/{} are newer than this engine supports ({}/{})",
+            manifest.data_format, manifest.index_format, DATA_FORMAT, INDEX_FORMAT
+        )));
+    }
+    if manifest.entries.iter().filter(|e| e.kind == EntryKind::Data).count() != 1 {
+        return Err(corrupt(archive, "manifest must list exactly one data entry"));
+    }
+    // Index names become file names next to the target
+    if let Some(entry) = manifest.entries.iter().find(|e| {
+        e.kind == EntryKind::Index && (e.name.is_empty() || e.name.contains(['/', '\\']) || e.name.contains(".."))
+    }) {
+        return Err(corrupt(archive, format!("invalid index name '{}'", entry.name)));
+    }
+    Ok(manifest)
+}
+
+/// The manifest of an archive, with only the manifest's own checksum verified
+pub fn read_manifest(archive: &Path) -> Result<Manifest, DbError> {
+    let file = File::open(archive).map_err(|e| DbError::Io(format!("Cannot open archive {}: {}", archive.display(), e)))?;
+    read_header(archive, &mut BufReader::new(file))
+}
+
+/// Verify every entry and write it to `staging/<position>`. On failure the
+/// staging directory is removed and nothing else has been touched.
+pub fn unpack(archive: &Path, staging: &Path) -> Result<Manifest, DbError> {
+    let file = File::open(archive).map_err(|e| DbError::Io(format!("Cannot open archive {}: {}", archive.display(), e)))?;
+    let mut reader = BufReader::new(file);
+    let manifest = read_header(archive, &mut reader)?;
+    fs::create_dir_all(staging).map_err(DbError::from)?;
+    let unpacked = (|| -> Result<(), DbError> {
+        for (i, entry) in manifest.entries.iter().enumerate() {
+            let mut payload = vec![0u8; entry.stored as usize];
+            reader.read_exact(&mut payload).map_err(|_| corrupt(archive, format!("entry '{}' is truncated", entry.name)))?;
+            let content = if manifest.compressed {
+                zstd::decode_all(payload.as_slice()).map_err(|e| corrupt(archive, format!("entry '{}': {}", entry.name, e)))?
+            } else {
+                payload
+            };
+            if content.len() as u64 != entry.bytes || crc32fast::hash(&content) != entry.crc32 {
+                return Err(corrupt(archive, format!("entry '{}' checksum mismatch", entry.name)));
+            }
+            let mut out = File::create(staging.join(i.to_string())).map_err(DbError::from)?;
+            out.write_all(&content).map_err(DbError::from)?;
+            out.sync_all().map_err(DbError::from)?;
+        }
+        let mut trailing = [0u8; 1];
+        if reader.read(&mut trailing).map_err(DbError::from)? != 0 {
+            return Err(corrupt(archive, "unexpected bytes after the last entry"));
+        }
+        Ok(())
+    })();
+    if let Err(e) = unpacked {
+        let _ = fs::remove_dir_all(staging);
+        return Err(e);
+    }
+    Ok(manifest)
+}
+
+/// Move unpacked entries into place, the data file last so the target only
+/// becomes a database once everything else is there. Undoes the moves
+/// already made if one fails.
+pub fn install(manifest: &Manifest, staging: &Path, target: &str) -> Result<(), DbError> {
+    let mut moves: Vec<(PathBuf, PathBuf)> = manifest.entries.iter().enumerate()
+        .map(|(i, entry)| {
+            let dest = match entry.kind {
+                EntryKind::Data => PathBuf::from(target),
+                EntryKind::Index => PathBuf::from(format!("{}.{}.idx", target, entry.name)),
+            };
+            (staging.join(i.to_string()), dest)
+        })
+        .collect();
+    moves.sort_by_key(|(_, dest)| dest.as_os_str() == target);
+
+    let mut done: Vec<&PathBuf> = Vec::new();
+    for (from, to) in &moves {
+        if let Err(e) = fs::rename(from, to) {
+            for moved in done {
+                let _ = fs::remove_file(moved);
+            }
+            let _ = fs::remove_dir_all(staging);
+            return Err(DbError::Io(format!("Failed to install {}: {}", to.display(), e)));
+        }
+        done.push(to);
+    }
+    let _ = fs::remove_dir_all(staging);
+    Ok(())
+}
+
+/// Files `install` would create for `target`
+pub fn target_files(manifest: &Manifest, target: &str) -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from(target), PathBuf::from(format!("{}.wal", target))];
+    files.extend(manifest.entries.iter()
+        .filter(|e| e.kind == EntryKind::Index)
+        .map(|e| PathBuf::from(format!("{}.{}.idx", target, e.name))));
+    files
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
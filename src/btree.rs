@@ -1,18 +1,48 @@
-use std::collections::BTreeMap;
-use std::fs::{self, File};
-use std::io::{self, BufReader, BufWriter, Write};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::ops::Bound;
 use std::path::Path;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
 use serde_json::Value;
 
 // Simple Persistent B-Tree Index (In-Memory BTreeMap backed by disk)
 // This solves the startup time issue by loading pre-computed indexes.
 // It matches the "in-memory speed" philosophy.
+//
+// Index files are binary (older JSON files still load, and are rewritten as
+// binary by their next save). Integers are little-endian; strings are a u32
+// byte length then UTF-8:
+//
+//   magic "JDBIDX\0" | u8 version | u32 crc32 of the rest
+//   name | field | u8 flags (bit 0: tracked, then the collection; bit 1: sparse)
+//   u32 key count | keys, in index order
+//   u32 doc count | per doc, in path order: path | u8 kind
+//       (bit 0: u32 key number; bit 1: u32 count | u32 key numbers)
+//   per key: u32 count | u32 doc numbers, in the key's list order
+//
+// Every path and key is stored once and referred to by number after that,
+// and every map comes out sorted, so loading builds each in one pass.
+
+const MAGIC: &[u8; 7] = b"JDBIDX\0";
+const VERSION: u8 = 1;
 
 #[derive(Debug)]
 pub enum IndexError {
     Io(io::Error),
     Serialization(serde_json::Error),
+    Corrupt(String),
+}
+
+impl std::fmt::Display for IndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexError::Io(e) => write!(f, "IO error: {}", e),
+            IndexError::Serialization(e) => write!(f, "Serialization error: {}", e),
+            IndexError::Corrupt(e) => write!(f, "Corrupt index file: {}", e),
+        }
+    }
 }
 
 impl From<io::Error> for IndexError {
@@ -25,19 +55,103 @@ impl From<serde_json::Error> for IndexError {
 
 type Result<T> = std::result::Result<T, IndexError>;
 
+/// An index key, kept as the text it is stored and looked up by. Keys that
+/// read as JSON numbers sort first, in numeric order (so 9 < 10 and -2.5 < -1),
+/// then every other key in string order. Index files hold only the text, so
+/// files written before keys were ordered this way load in the new order.
+#[derive(Clone, Debug)]
+pub struct Key {
+    text: String,
+    num: Option<f64>,
+}
+
+impl Key {
+    pub fn new(text: String) -> Self {
+        let num = text.parse::<serde_json::Number>().ok().and_then(|n| n.as_f64());
+        Key { text, num }
+    }
+
+    /// A range bound placed among the non-numeric keys where `text` would sort
+    fn text_bound(text: &str) -> Self {
+        Key { text: text.to_string(), num: None }
+    }
+
+    /// Below every non-numeric key and above every numeric one
+    fn after_numbers() -> Self {
+        Self::text_bound("")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.num, other.num) {
+            (Some(a), Some(b)) => a.total_cmp(&b).then_with(|| self.text.cmp(&other.text)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => self.text.cmp(&other.text),
+        }
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Key {}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Key::new)
+    }
+}
+
+/// Whether a range bound is a number, which restricts the range to numeric keys
+fn is_numeric_bound(bound: Bound<&Value>) -> bool {
+    matches!(bound, Bound::Included(v) | Bound::Excluded(v) if v.is_number())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BTreeIndex {
     name: String,
     field: String,
-    // Key (as string representation) -> List of Doc Paths
-    map: BTreeMap<String, Vec<String>>,
+    // Key -> List of Doc Paths
+    map: BTreeMap<Key, Vec<String>>,
     // Doc Path -> Key (for O(1) updates/removals)
     #[serde(default)] // For backward compatibility if someone had old index file
     reverse_map: BTreeMap<String, String>,
+    // Doc Path -> Keys, for documents of custom indexes (any number of keys each)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    multi_map: BTreeMap<String, Vec<String>>,
+    // Collection the index was registered on, for indexes kept up to date by the engine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tracked: Option<String>,
+    // Null fields are left out, so documents only enter with a value
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    sparse: bool,
     #[serde(skip)]
     path: String,
     #[serde(skip)]
     dirty: bool,
+    #[serde(skip)]
+    loaded_from_disk: bool,
 }
 
 impl BTreeIndex {
@@ -48,8 +162,12 @@ impl BTreeIndex {
             field,
             map: BTreeMap::new(),
             reverse_map: BTreeMap::new(),
+            multi_map: BTreeMap::new(),
+            tracked: None,
+            sparse: false,
             path,
             dirty: false,
+            loaded_from_disk: false,
         }
     }
 
@@ -58,41 +176,313 @@ impl BTreeIndex {
         let p = Path::new(&path);
         
         if p.exists() {
-            let file = File::open(p)?;
-            let reader = BufReader::new(file);
-            let mut index: BTreeIndex = serde_json::from_reader(reader)?;
-            index.path = path;
-            index.dirty = false;
-            // Ensure reverse_map is populated if loaded from old version (though we just added it)
-            if index.reverse_map.is_empty() && !index.map.is_empty() {
-                for (k, v) in &index.map {
-                    for doc in v {
-                        index.reverse_map.insert(doc.clone(), k.clone());
+            let bytes = fs::read(p)?;
+            let mut index = if bytes.starts_with(MAGIC) {
+                Self::decode(&bytes)?
+            } else {
+                let mut index: BTreeIndex = serde_json::from_slice(&bytes)?;
+                // Ensure reverse_map is populated if loaded from old version (though we just added it)
+                if index.reverse_map.is_empty() && index.multi_map.is_empty() && !index.map.is_empty() {
+                    for (k, v) in &index.map {
+                        for doc in v {
+                            index.reverse_map.insert(doc.clone(), k.as_str().to_string());
+                        }
                     }
                 }
-            }
+                // Rewritten in the binary format by the next save
+                index.dirty = true;
+                index
+            };
+            index.path = path;
+            index.loaded_from_disk = true;
             Ok(index)
         } else {
             Ok(Self::new(name, field, base_path))
         }
     }
 
-    pub fn save(&mut self) -> Result<()> {
+    /// The index in the binary file format
+    pub fn encode(&self) -> Vec<u8> {
+        fn put_u32(out: &mut Vec<u8>, n: usize) {
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        fn put_str(out: &mut Vec<u8>, s: &str) {
+            put_u32(out, s.len());
+            out.extend_from_slice(s.as_bytes());
+        }
+        let key_numbers: HashMap<&str, usize> = self.map.keys().enumerate().map(|(i, k)| (k.as_str(), i)).collect();
+        let mut docs: Vec<&str> = self.reverse_map.keys().chain(self.multi_map.keys()).map(String::as_str).collect();
+        docs.sort_unstable();
+        docs.dedup();
+        let doc_numbers: HashMap<&str, usize> = docs.iter().enumerate().map(|(i, d)| (*d, i)).collect();
+
+        let mut out = Vec::with_capacity(64 + self.map.len() * 16 + docs.len() * 32);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&[0; 4]);
+        put_str(&mut out, &self.name);
+        put_str(&mut out, &self.field);
+        out.push(self.tracked.is_some() as u8 | (self.sparse as u8) << 1);
+        if let Some(collection) = &self.tracked {
+            put_str(&mut out, collection);
+        }
+        put_u32(&mut out, self.map.len());
+        for key in self.map.keys() {
+            put_str(&mut out, key.as_str());
+        }
+        put_u32(&mut out, docs.len());
+        for doc in &docs {
+            put_str(&mut out, doc);
+            let single = self.reverse_map.get(*doc);
+            let custom = self.multi_map.get(*doc);
+            out.push(single.is_some() as u8 | (custom.is_some() as u8) << 1);
+            if let Some(key) = single {
+                put_u32(&mut out, key_numbers[key.as_str()]);
+            }
+            if let Some(keys) = custom {
+                put_u32(&mut out, keys.len());
+                for key in keys {
+                    put_u32(&mut out, key_numbers[key.as_str()]);
+                }
+            }
+        }
+        for paths in self.map.values() {
+            put_u32(&mut out, paths.len());
+            for path in paths {
+                put_u32(&mut out, doc_numbers[path.as_str()]);
+            }
+        }
+        let crc = crc32fast::hash(&out[MAGIC.len() + 5..]);
+        out[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// Read an index written by `encode`
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        struct Reader<'a>(&'a [u8]);
+        impl<'a> Reader<'a> {
+            fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+                if self.0.len() < n {
+                    return Err(IndexError::Corrupt("truncated".to_string()));
+                }
+                let (head, rest) = self.0.split_at(n);
+                self.0 = rest;
+                Ok(head)
+            }
+            fn u8(&mut self) -> Result<u8> {
+                Ok(self.take(1)?[0])
+            }
+            fn u32(&mut self) -> Result<usize> {
+                Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+            }
+            fn str(&mut self) -> Result<&'a str> {
+                let len = self.u32()?;
+                std::str::from_utf8(self.take(len)?).map_err(|_| IndexError::Corrupt("string is not UTF-8".to_string()))
+            }
+            /// A count of items at least `size` bytes each, checked against what is left
+            fn count(&mut self, size: usize) -> Result<usize> {
+                let n = self.u32()?;
+                if n > self.0.len() / size {
+                    return Err(IndexError::Corrupt("truncated".to_string()));
+                }
+                Ok(n)
+            }
+            /// The `of` entry a stored number refers to
+            fn pick<'t, T>(&mut self, of: &'t [T]) -> Result<&'t T> {
+                let i = self.u32()?;
+                of.get(i).ok_or_else(|| IndexError::Corrupt(format!("entry {} is out of range", i)))
+            }
+        }
+
+        let mut r = Reader(&bytes[MAGIC.len()..]);
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(IndexError::Corrupt(format!("format version {} is not supported (expected {})", version, VERSION)));
+        }
+        let crc = u32::from_le_bytes(r.take(4)?.try_into().unwrap());
+        if crc32fast::hash(r.0) != crc {
+            return Err(IndexError::Corrupt("checksum mismatch".to_string()));
+        }
+        let name = r.str()?.to_string();
+        let field = r.str()?.to_string();
+        let flags = r.u8()?;
+        let tracked = match flags & 1 {
+            0 => None,
+            _ => Some(r.str()?.to_string()),
+        };
+        let key_count = r.count(4)?;
+        let mut keys = Vec::with_capacity(key_count);
+        for _ in 0..key_count {
+            keys.push(Key::new(r.str()?.to_string()));
+        }
+        // Paths stay borrowed from the file until a map takes a copy
+        let doc_count = r.count(5)?;
+        let (mut docs, mut reverse_map, mut multi_map) = (Vec::with_capacity(doc_count), Vec::new(), Vec::new());
+        for _ in 0..doc_count {
+            let doc = r.str()?;
+            let kind = r.u8()?;
+            if kind & 1 != 0 {
+                reverse_map.push((doc.to_string(), r.pick(&keys)?.as_str().to_string()));
+            }
+            if kind & 2 != 0 {
+                let n = r.count(4)?;
+                let doc_keys = (0..n).map(|_| Ok(r.pick(&keys)?.as_str().to_string())).collect::<Result<Vec<_>>>()?;
+                multi_map.push((doc.to_string(), doc_keys));
+            }
+            docs.push(doc);
+        }
+        let mut map = Vec::with_capacity(keys.len());
+        for key in keys {
+            let n = r.count(4)?;
+            let paths = (0..n).map(|_| r.pick(&docs).map(|doc| doc.to_string())).collect::<Result<Vec<_>>>()?;
+            map.push((key, paths));
+        }
+        if !r.0.is_empty() {
+            return Err(IndexError::Corrupt("trailing bytes".to_string()));
+        }
+        // Every list is in order already, which lets each map be built in one pass
+        Ok(BTreeIndex {
+            map: map.into_iter().collect(),
+            reverse_map: reverse_map.into_iter().collect(),
+            multi_map: multi_map.into_iter().collect(),
+            tracked,
+            sparse: flags & 2 != 0,
+            ..Self::new(name, field, "")
+        })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// File the index is saved to
+    pub fn file(&self) -> &str {
+        &self.path
+    }
+
+    /// Indexed documents
+    pub fn len(&self) -> usize {
+        self.reverse_map.len() + self.multi_map.len()
+    }
+
+    /// Distinct keys
+    pub fn key_count(&self) -> usize {
+        self.map.len()
+    }
+
+    /// The collection holding the indexed documents: the one the index tracks, or
+    /// else the parent of their paths (None while the index is empty)
+    pub fn collection(&self) -> Option<String> {
+        if let Some(tracked) = &self.tracked {
+            return Some(tracked.clone());
+        }
+        let doc_path = self.reverse_map.keys().next().or_else(|| self.multi_map.keys().next())?;
+        Some(doc_path.rsplit_once('.').map_or("", |(parent, _)| parent).to_string())
+    }
+
+    /// Every indexed document with its keys
+    pub fn entries(&self) -> impl Iterator<Item = (&str, Vec<&str>)> {
+        self.reverse_map.iter()
+            .map(|(doc, key)| (doc.as_str(), vec![key.as_str()]))
+            .chain(self.multi_map.iter().map(|(doc, keys)| (doc.as_str(), keys.iter().map(String::as_str).collect())))
+    }
+
+    /// Index a document under exactly `keys` (none removes it), replacing its
+    /// previous entries. Used by custom indexes, whose extractors yield key lists.
+    pub fn set_keys(&mut self, doc_path: &str, mut keys: Vec<String>) {
+        keys.sort_unstable();
+        keys.dedup();
+        if self.multi_map.get(doc_path) == Some(&keys) {
+            return;
+        }
+        self.remove(&Value::Null, doc_path);
+        if keys.is_empty() {
+            return;
+        }
+        for key in &keys {
+            self.map.entry(Key::new(key.clone())).or_default().push(doc_path.to_string());
+        }
+        self.multi_map.insert(doc_path.to_string(), keys);
+        self.dirty = true;
+    }
+
+    /// The collection the engine keeps this index up to date with
+    pub fn tracked(&self) -> Option<&str> {
+        self.tracked.as_deref()
+    }
+
+    pub fn track(&mut self, collection: String) {
+        if self.tracked.as_ref() != Some(&collection) {
+            self.tracked = Some(collection);
+            self.dirty = true;
+        }
+    }
+
+    pub fn sparse(&self) -> bool {
+        self.sparse
+    }
+
+    /// Make the index sparse or not; returns whether that changed it. Entries
+    /// already there are kept, so a changed index needs building again.
+    pub fn set_sparse(&mut self, sparse: bool) -> bool {
+        if self.sparse == sparse {
+            return false;
+        }
+        self.sparse = sparse;
+        self.dirty = true;
+        true
+    }
+
+    /// Whether this index was loaded from an existing index file (vs. created fresh)
+    pub fn loaded_from_disk(&self) -> bool {
+        self.loaded_from_disk
+    }
+
+    /// Check every indexed entry against the data: the document must exist and its
+    /// field must still produce the indexed key
+    pub fn verify(&self, root: &Value) -> Value {
+        let mut missing_docs = 0u64;
+        let mut mismatched = 0u64;
+        for (doc_path, key) in &self.reverse_map {
+            let ptr = format!("/{}", doc_path.replace('.', "/"));
+            let Some(doc) = root.pointer(&ptr) else {
+                missing_docs += 1;
+                continue;
+            };
+            let field_ptr = format!("/{}", self.field.replace('.', "/"));
+            match doc.pointer(&field_ptr) {
+                Some(v) if self.key_to_string(v) == *key => {}
+                _ => mismatched += 1,
+            }
+        }
+        serde_json::json!({
+            "entries": self.reverse_map.len(),
+            "keys": self.map.len(),
+            "missingDocs": missing_docs,
+            "mismatched": mismatched,
+            "ok": missing_docs == 0 && mismatched == 0,
+        })
+    }
+
+    /// Write the index if it changed since its last save; returns whether it did
+    pub fn save(&mut self) -> Result<bool> {
         if !self.dirty {
-            return Ok(());
+            return Ok(false);
         }
         
         let path_tmp = format!("{}.tmp", self.path);
-        let file = File::create(&path_tmp)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, &self)?;
+        fs::write(&path_tmp, self.encode())?;
         fs::rename(path_tmp, &self.path)?;
         self.dirty = false;
-        Ok(())
+        Ok(true)
     }
 
-    // Insert or Update
+    // Insert or Update; a sparse index drops the document instead of indexing null
     pub fn insert(&mut self, key: &Value, doc_path: String) {
+        if self.sparse && key.is_null() {
+            self.remove(key, &doc_path);
+            return;
+        }
         let new_key = self.key_to_string(key);
         
         // Check if doc exists and has different key
@@ -101,35 +491,42 @@ impl BTreeIndex {
                 return; // No change
             }
             // Remove from old key
-            if let Some(list) = self.map.get_mut(old_key) {
+            let old_key = Key::new(old_key.clone());
+            if let Some(list) = self.map.get_mut(&old_key) {
                 if let Some(pos) = list.iter().position(|x| x == &doc_path) {
                     list.remove(pos);
                 }
-            }
-            // Cleanup empty
-            if let Some(list) = self.map.get(old_key) {
+                // Cleanup empty
                 if list.is_empty() {
-                    let old_key_clone = old_key.clone(); // Split borrow
-                    self.map.remove(&old_key_clone);
+                    self.map.remove(&old_key);
                 }
             }
         }
         
         self.reverse_map.insert(doc_path.clone(), new_key.clone());
-        self.map.entry(new_key).or_default().push(doc_path);
+        self.map.entry(Key::new(new_key)).or_default().push(doc_path);
         self.dirty = true;
     }
 
     // Remove by path (key is optional/ignored, simpler API)
     pub fn remove(&mut self, _key: &Value, doc_path: &str) {
+        for old_key in self.multi_map.remove(doc_path).unwrap_or_default() {
+            let old_key = Key::new(old_key);
+            if let Some(list) = self.map.get_mut(&old_key) {
+                list.retain(|x| x != doc_path);
+                if list.is_empty() {
+                    self.map.remove(&old_key);
+                }
+            }
+            self.dirty = true;
+        }
         if let Some(old_key) = self.reverse_map.remove(doc_path) {
+            let old_key = Key::new(old_key);
             if let Some(list) = self.map.get_mut(&old_key) {
                  if let Some(pos) = list.iter().position(|x| x == doc_path) {
                     list.remove(pos);
                     self.dirty = true;
                 }
-            }
-            if let Some(list) = self.map.get(&old_key) {
                 if list.is_empty() {
                     self.map.remove(&old_key);
                 }
@@ -137,6 +534,20 @@ impl BTreeIndex {
         }
     }
     
+    /// Remove every entry whose document path matches `pred`; returns how many were removed
+    pub fn remove_where(&mut self, pred: impl Fn(&str) -> bool) -> usize {
+        let doomed: Vec<String> = self.reverse_map.keys().chain(self.multi_map.keys()).filter(|p| pred(p)).cloned().collect();
+        for doc_path in &doomed {
+            self.remove(&Value::Null, doc_path);
+        }
+        doomed.len()
+    }
+
+    /// The key `value` is indexed under, or None if the index leaves it out
+    pub fn key_of(&self, value: &Value) -> Option<String> {
+        (!(self.sparse && value.is_null())).then(|| self.key_to_string(value))
+    }
+
     fn key_to_string(&self, key: &Value) -> String {
         match key {
             Value::String(s) => s.clone(),
@@ -148,32 +559,112 @@ impl BTreeIndex {
     }
 
     pub fn find(&self, key: &Value) -> Option<&Vec<String>> {
-        let k = self.key_to_string(key);
-        self.map.get(&k)
+        // "null" would find the string "null", which a sparse index still holds
+        if self.sparse && key.is_null() {
+            return None;
+        }
+        self.map.get(&Key::new(self.key_to_string(key)))
     }
 
-    pub fn range(&self, start: Option<&Value>, end: Option<&Value>) -> Vec<String> {
-        let start_k = start.map(|k| self.key_to_string(k));
-        let end_k = end.map(|k| self.key_to_string(k));
-        
-        let mut results = Vec::new();
-        
-        use std::ops::Bound;
-        let range = self.map.range::<str, _>((
-            start_k.as_ref().map(|k| Bound::Included(k.as_str())).unwrap_or(Bound::Unbounded),
-            end_k.as_ref().map(|k| Bound::Included(k.as_str())).unwrap_or(Bound::Unbounded)
-        ));
+    /// `(start, end)` as key bounds; None when nothing can fall between them.
+    /// A numeric bound leaves out every non-numeric key.
+    fn key_bounds(&self, start: Bound<&Value>, end: Bound<&Value>) -> Option<(Bound<Key>, Bound<Key>)> {
+        let numeric = is_numeric_bound(start) || is_numeric_bound(end);
+        let to_key = |b: Bound<&Value>| match b {
+            // In a numeric range a non-numeric bound leaves that end open
+            Bound::Included(v) | Bound::Excluded(v) if numeric && !v.is_number() => Bound::Unbounded,
+            b => b.map(|v| Key::new(self.key_to_string(v))),
+        };
+        let start = to_key(start);
+        let end = match to_key(end) {
+            Bound::Unbounded if numeric => Bound::Excluded(Key::after_numbers()),
+            end => end,
+        };
+        // BTreeMap::range panics on an inverted or empty exclusive range
+        match (&start, &end) {
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e))
+                if s > e || (s == e && !matches!((&start, &end), (Bound::Included(_), Bound::Included(_)))) => None,
+            _ => Some((start, end)),
+        }
+    }
 
-        for (_k, v) in range {
-            results.extend(v.iter().cloned());
+    /// Document paths with a key within the bounds, in key order, at most `limit`.
+    /// Numeric bounds only match numeric keys; see `Key` for the order.
+    pub fn range(&self, start: Bound<&Value>, end: Bound<&Value>, limit: usize) -> Vec<String> {
+        let Some(bounds) = self.key_bounds(start, end) else { return Vec::new() };
+        self.first_seen(self.map.range(bounds).flat_map(|(_, v)| v.iter()), limit)
+    }
+
+    /// Up to `limit` paths, each once: a document of a custom index can sit under
+    /// several keys of one range
+    fn first_seen<'a>(&self, paths: impl Iterator<Item = &'a String>, limit: usize) -> Vec<String> {
+        if self.multi_map.is_empty() {
+            return paths.take(limit).cloned().collect();
         }
-        
-        results
+        let mut seen = std::collections::HashSet::new();
+        paths.filter(|p| seen.insert(*p)).take(limit).cloned().collect()
     }
-    
+
+    /// Keys within `[start, end]` with their document counts, in key order.
+    /// Numeric bounds only match numeric keys.
+    pub fn key_counts_between(&self, start: Option<&Value>, end: Option<&Value>) -> Vec<(&str, usize)> {
+        let (start, end) = (start.map_or(Bound::Unbounded, Bound::Included), end.map_or(Bound::Unbounded, Bound::Included));
+        let Some(bounds) = self.key_bounds(start, end) else { return Vec::new() };
+        self.map.range(bounds).map(|(k, v)| (k.as_str(), v.len())).collect()
+    }
+
+    /// Distinct keys in sorted order (descending if `reverse`) with their document
+    /// counts, optionally restricted to keys starting with `prefix`
+    pub fn keys<'a>(&'a self, prefix: Option<&'a str>, reverse: bool) -> Box<dyn Iterator<Item = (&'a str, usize)> + 'a> {
+        Box::new(self.entries_between(Bound::Unbounded, Bound::Unbounded, prefix, reverse).map(|(k, v)| (k, v.len())))
+    }
+
+    /// Distinct keys within the bounds with the paths indexed under them, in
+    /// sorted order (descending if `reverse`), optionally restricted to keys
+    /// starting with `prefix`. Numeric bounds only match numeric keys.
+    pub fn entries_between<'a>(
+        &'a self,
+        start: Bound<&Value>,
+        end: Bound<&Value>,
+        prefix: Option<&'a str>,
+        reverse: bool,
+    ) -> Box<dyn Iterator<Item = (&'a str, &'a Vec<String>)> + 'a> {
+        let entry = |(k, v): (&'a Key, &'a Vec<String>)| (k.as_str(), v);
+        let Some(bounds) = self.key_bounds(start, end) else { return Box::new(std::iter::empty()) };
+        match (prefix, bounds) {
+            (Some(p), (Bound::Unbounded, Bound::Unbounded)) => {
+                // Numeric keys sharing a prefix are scattered through the numeric
+                // order; the others sit together in string order
+                let numbers = self.map
+                    .range(..Key::after_numbers())
+                    .filter(move |(k, _)| k.as_str().starts_with(p));
+                let others = self.map
+                    .range((Bound::Included(Key::text_bound(p)), Bound::Unbounded))
+                    .take_while(move |(k, _)| k.as_str().starts_with(p));
+                let matching = numbers.chain(others).map(entry);
+                if reverse {
+                    Box::new(matching.collect::<Vec<_>>().into_iter().rev())
+                } else {
+                    Box::new(matching)
+                }
+            }
+            (prefix, bounds) => {
+                let matching = self.map.range(bounds)
+                    .filter(move |(k, _)| prefix.is_none_or(|p| k.as_str().starts_with(p)))
+                    .map(entry);
+                if reverse {
+                    Box::new(matching.rev())
+                } else {
+                    Box::new(matching)
+                }
+            }
+        }
+    }
+
     pub fn clear(&mut self) {
         self.map.clear();
         self.reverse_map.clear();
+        self.multi_map.clear();
         self.dirty = true;
     }
 }
@@ -0,0 +1,37 @@
+//! Bulk load mode
+//!
+//! Between `begin_bulk_load` and `end_bulk_load`, writes go to memory only.
+//! Nothing is logged to the WAL, so a crash before `end_bulk_load` loses the
+//! whole load: the database reopens as of its last save. Derived state is not
+//! kept up write by write either. Counters, tracked and custom indexes and
+//! auto-refresh views are rebuilt once when the load ends, delete listeners
+//! are not called, and schemas are not enforced unless the load keeps them.
+//! Path rules, leases, quotas and constraints are checked as usual.
+//!
+//! `end_bulk_load` rebuilds the derived state, then saves, which is the point
+//! the load becomes durable.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+pub struct BulkLoad {
+    pub started: Instant,
+    /// Schemas are still enforced
+    pub keep_schemas: bool,
+    writes: AtomicU64,
+}
+
+impl BulkLoad {
+    pub fn new(keep_schemas: bool) -> Self {
+        BulkLoad { started: Instant::now(), keep_schemas, writes: AtomicU64::new(0) }
+    }
+
+    /// Count a write the WAL did not see
+    pub fn note_write(&self) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,157 @@
+//! Canonical JSON
+//!
+//! One form per value, so equal data hashes, diffs and dedupes equally:
+//!
+//! - object keys sorted by Unicode code point, at every depth
+//! - integer-valued floats within ±2^53 become integers (`1.0` -> `1`)
+//! - `-0` becomes `0`
+//! - NaN and ±Infinity are rejected (JSON cannot represent them)
+//! - serialized without whitespace
+//!
+//! Array order is data and is kept. Larger integer-valued floats stay floats,
+//! since not every integer beyond 2^53 survives the round trip through f64.
+//! Anything that hashes or compares serialized values should go through
+//! `write` so its output is stable by construction; `equal` compares two
+//! values by canonical form without serializing either.
+
+use serde_json::{Map, Number, Value};
+use std::io::Write;
+
+use crate::error::DbError;
+
+/// Largest magnitude below which every integer is exactly representable as f64
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// The canonical form of one number
+pub fn number(n: &Number) -> Result<Number, DbError> {
+    if n.is_i64() || n.is_u64() {
+        return Ok(n.clone());
+    }
+    let Some(f) = n.as_f64() else {
+        return Err(DbError::InvalidArgument(format!("Cannot canonicalize number {}", n)));
+    };
+    if !f.is_finite() {
+        return Err(DbError::InvalidArgument(format!("{} has no JSON representation", f)));
+    }
+    if f.fract() == 0.0 && f.abs() <= MAX_EXACT_INTEGER {
+        // Also maps -0.0 to 0
+        return Ok(Number::from(f as i64));
+    }
+    Number::from_f64(f).ok_or_else(|| DbError::InvalidArgument(format!("{} has no JSON representation", f)))
+}
+
+/// A copy of `value` in canonical form
+pub fn canonicalize(value: &Value) -> Result<Value, DbError> {
+    Ok(match value {
+        Value::Number(n) => Value::Number(number(n)?),
+        Value::Array(items) => Value::Array(items.iter().map(canonicalize).collect::<Result<_, _>>()?),
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            let mut sorted = Map::with_capacity(entries.len());
+            for (key, item) in entries {
+                sorted.insert(key.clone(), canonicalize(item)?);
+            }
+            Value::Object(sorted)
+        }
+        other => other.clone(),
+    })
+}
+
+/// Serialize `value` in canonical form, without building a canonical copy
+pub fn write<W: Write>(value: &Value, out: &mut W) -> Result<(), DbError> {
+    let io = |e: std::io::Error| DbError::Io(e.to_string());
+    match value {
+        Value::Number(n) => serde_json::to_writer(&mut *out, &number(n)?).map_err(|e| DbError::Io(e.to_string())),
+        Value::Array(items) => {
+            out.write_all(b"[").map_err(io)?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",").map_err(io)?;
+                }
+                write(item, out)?;
+            }
+            out.write_all(b"]").map_err(io)
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            out.write_all(b"{").map_err(io)?;
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",").map_err(io)?;
+                }
+                serde_json::to_writer(&mut *out, key).map_err(|e| DbError::Io(e.to_string()))?;
+                out.write_all(b":").map_err(io)?;
+                write(item, out)?;
+            }
+            out.write_all(b"}").map_err(io)
+        }
+        // Strings, booleans and null already have a single serialization
+        other => serde_json::to_writer(&mut *out, other).map_err(|e| DbError::Io(e.to_string())),
+    }
+}
+
+/// Canonical serialization of `value`
+pub fn to_string(value: &Value) -> Result<String, DbError> {
+    let mut out = Vec::new();
+    write(value, &mut out)?;
+    // Only valid UTF-8 is ever written
+    Ok(String::from_utf8(out).unwrap_or_default())
+}
+
+/// How filters, `push` dedupe and `atomic` conditions compare values
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Equality {
+    /// Values as stored: `1` and `1.0` differ (object key order never matters)
+    #[default]
+    Strict,
+    /// Canonical forms: `1`, `1.0` and `-0.0` are equal too
+    Canonical,
+}
+
+impl Equality {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "strict" => Some(Equality::Strict),
+            "canonical" => Some(Equality::Canonical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Equality::Strict => "strict",
+            Equality::Canonical => "canonical",
+        }
+    }
+
+    pub fn same(self, a: &Value, b: &Value) -> bool {
+        match self {
+            Equality::Strict => a == b,
+            Equality::Canonical => equal(a, b),
+        }
+    }
+
+    pub fn contains(self, items: &[Value], value: &Value) -> bool {
+        match self {
+            Equality::Strict => items.contains(value),
+            Equality::Canonical => items.iter().any(|item| equal(item, value)),
+        }
+    }
+}
+
+/// Whether `a` and `b` have the same canonical form, without building either
+pub fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (number(x), number(y)) {
+            (Ok(x), Ok(y)) => x == y,
+            _ => x == y,
+        },
+        (Value::Array(x), Value::Array(y)) => x.len() == y.len() && x.iter().zip(y).all(|(x, y)| equal(x, y)),
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(key, item)| y.get(key).is_some_and(|other| equal(item, other)))
+        }
+        _ => a == b,
+    }
+}
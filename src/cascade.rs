@@ -0,0 +1,161 @@
+//! Cascade rules
+//!
+//! A rule ties the documents of a child collection to those of a parent
+//! collection through a field holding the parent's key. Deleting a parent
+//! document (a direct child of the parent collection) then:
+//!
+//! - `delete`: deletes the referencing children, which may cascade further
+//! - `nullify`: sets the children's field to null
+//! - `restrict`: refuses the delete while any child references the parent
+//!
+//! Rules live in the reserved namespace under "cascades". The graph of rules
+//! (parent collection -> child collection) must stay acyclic, so a cascade
+//! always terminates and never comes back to the collection it started from.
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::error::DbError;
+use crate::{resolve_field, split_field};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Delete,
+    Nullify,
+    Restrict,
+}
+
+impl Action {
+    pub fn parse(action: &str) -> Result<Self, DbError> {
+        match action {
+            "delete" => Ok(Action::Delete),
+            "nullify" => Ok(Action::Nullify),
+            "restrict" => Ok(Action::Restrict),
+            other => Err(DbError::InvalidArgument(format!(
+                "Unknown cascade action '{}': use 'delete', 'nullify' or 'restrict'", other
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::Nullify => "nullify",
+            Action::Restrict => "restrict",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub parent: String,
+    pub child: String,
+    pub field: String,
+    pub action: Action,
+    parts: Vec<String>,
+}
+
+impl Rule {
+    pub fn new(parent: String, child: String, field: String, action: Action) -> Self {
+        Rule { parts: split_field(&field), parent, child, field, action }
+    }
+
+    pub fn from_stored(stored: &Value) -> Option<Self> {
+        let text = |key: &str| stored.get(key).and_then(Value::as_str).map(String::from);
+        let action = Action::parse(stored.get("action")?.as_str()?).ok()?;
+        Some(Rule::new(text("parent")?, text("child")?, text("field")?, action))
+    }
+
+    pub fn to_stored(&self) -> Value {
+        json!({
+            "parent": self.parent,
+            "child": self.child,
+            "field": self.field,
+            "action": self.action.as_str(),
+        })
+    }
+
+    /// Whether `doc` references the parent document `key`. Numeric fields match
+    /// their decimal form, so `{userId: 7}` references `users.7`.
+    pub fn references(&self, doc: &Value, key: &str) -> bool {
+        match resolve_field(doc, &self.parts).as_deref() {
+            Some(Value::String(s)) => s == key,
+            Some(Value::Number(n)) => n.to_string() == key,
+            _ => false,
+        }
+    }
+}
+
+/// Every rule kept in the reserved namespace, by name
+pub fn load(stored: Option<&Value>) -> Vec<(String, Rule)> {
+    let Some(Value::Object(stored)) = stored else { return Vec::new() };
+    stored.iter()
+        .filter_map(|(name, stored)| Some((name.clone(), Rule::from_stored(stored)?)))
+        .collect()
+}
+
+/// A cycle of collections the rules would form, as `[a, b, ..., a]`
+pub fn find_cycle<'a>(rules: impl Iterator<Item = &'a Rule>) -> Option<Vec<String>> {
+    let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+    for rule in rules {
+        edges.entry(rule.parent.as_str()).or_default().push(rule.child.as_str());
+    }
+    let mut starts: Vec<&str> = edges.keys().copied().collect();
+    starts.sort_unstable();
+
+    // Depth-first search; a collection met again on the current stack closes a cycle
+    let mut done: HashSet<&str> = HashSet::new();
+    for start in starts {
+        if done.contains(start) {
+            continue;
+        }
+        let mut stack: Vec<(&str, usize)> = vec![(start, 0)];
+        while let Some(&(node, next)) = stack.last() {
+            let children = edges.get(node).map(Vec::as_slice).unwrap_or(&[]);
+            let Some(&child) = children.get(next) else {
+                done.insert(node);
+                stack.pop();
+                continue;
+            };
+            stack.last_mut().unwrap().1 += 1;
+            if let Some(pos) = stack.iter().position(|(n, _)| *n == child) {
+                let mut cycle: Vec<String> = stack[pos..].iter().map(|(n, _)| n.to_string()).collect();
+                cycle.push(child.to_string());
+                return Some(cycle);
+            }
+            if !done.contains(child) {
+                stack.push((child, 0));
+            }
+        }
+    }
+    None
+}
+
+/// What a delete cascades into, worked out before anything is written
+#[derive(Default)]
+pub struct Plan {
+    /// (rule, document path) to delete, parents before their children
+    pub deletes: Vec<(String, String)>,
+    /// (rule, document path, field) to set to null
+    pub nullifies: Vec<(String, String, String)>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.deletes.is_empty() && self.nullifies.is_empty()
+    }
+
+    /// `{rule: {deleted, nullified}}`
+    pub fn counts(&self) -> Value {
+        let mut counts = serde_json::Map::new();
+        for (rule, _) in &self.deletes {
+            let entry = counts.entry(rule.clone()).or_insert_with(|| json!({ "deleted": 0, "nullified": 0 }));
+            entry["deleted"] = json!(entry["deleted"].as_u64().unwrap_or(0) + 1);
+        }
+        for (rule, _, _) in &self.nullifies {
+            let entry = counts.entry(rule.clone()).or_insert_with(|| json!({ "deleted": 0, "nullified": 0 }));
+            entry["nullified"] = json!(entry["nullified"].as_u64().unwrap_or(0) + 1);
+        }
+        Value::Object(counts)
+    }
+}
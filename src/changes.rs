@@ -0,0 +1,120 @@
+//! Retained changes for consumers
+//!
+//! `changes_for` serves a named consumer the WAL records after the position it
+//! last acked. The WAL only holds writes since the last save, so while any
+//! consumer exists a save first copies the records some consumer has not yet
+//! acked into `<db>.changes` (the WAL's own record format), keeping at most
+//! `max_records` of them, oldest dropped first. Without consumers nothing is
+//! copied and the file is removed.
+//!
+//! Once a save has seen a consumer, `<db>.changes.json` records two LSNs at
+//! every save: the last one handed out, so numbering continues across saves
+//! and restarts instead of starting over, and the highest one no longer
+//! available anywhere. A consumer whose position is below that has missed
+//! records and has to resync. Databases that never had consumers get no files.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::wal::{self, read_wal_records, WalRecord};
+
+/// Records kept for consumers by default
+pub const DEFAULT_MAX_RECORDS: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct State {
+    /// Highest LSN handed out as of the last save
+    pub last_lsn: u64,
+    /// Highest LSN whose record was dropped without being retained
+    pub discarded_through: u64,
+}
+
+pub struct ChangeLog {
+    log_path: String,
+    state_path: String,
+}
+
+impl ChangeLog {
+    pub fn new(db_path: &str) -> Self {
+        ChangeLog {
+            log_path: format!("{}.changes", db_path),
+            state_path: format!("{}.changes.json", db_path),
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.read_state().unwrap_or_default()
+    }
+
+    fn read_state(&self) -> Option<State> {
+        fs::read(&self.state_path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    /// Highest LSN no longer available. Without a state file no save has seen
+    /// a consumer, so that is whatever this process truncated, `truncated`.
+    pub fn discarded_through(&self, truncated: u64) -> u64 {
+        self.read_state().map_or(truncated, |state| state.discarded_through)
+    }
+
+    /// Highest LSN this database has handed out before, as far as these files tell
+    pub fn last_lsn(&self) -> u64 {
+        self.state().last_lsn.max(wal::last_lsn(&self.log_path))
+    }
+
+    /// Retained records after `lsn`, oldest first
+    pub fn read_after(&self, lsn: u64) -> io::Result<Vec<WalRecord>> {
+        if !Path::new(&self.log_path).exists() {
+            return Ok(Vec::new());
+        }
+        let (mut records, _) = read_wal_records(&self.log_path)?;
+        records.retain(|(record_lsn, _)| *record_lsn > lsn);
+        Ok(records)
+    }
+
+    /// Called by a save before it truncates the WAL at `wal_path` through
+    /// `truncated`. `floor` is the lowest consumer position, None without
+    /// consumers; records at or below it are acked by everyone and dropped.
+    pub fn checkpoint(&self, wal_path: &str, truncated: u64, floor: Option<u64>, max_records: usize) -> io::Result<State> {
+        let mut state = self.state();
+        state.last_lsn = state.last_lsn.max(truncated);
+        let Some(floor) = floor else {
+            if Path::new(&self.log_path).exists() {
+                fs::remove_file(&self.log_path)?;
+            }
+            if Path::new(&self.state_path).exists() {
+                state.discarded_through = state.last_lsn;
+                self.write_state(state)?;
+            }
+            return Ok(state);
+        };
+
+        let mut kept = self.read_after(0)?;
+        let newest = kept.last().map_or(0, |(lsn, _)| *lsn);
+        if Path::new(wal_path).exists() {
+            let (live, _) = read_wal_records(wal_path)?;
+            // A save that failed after copying leaves records already kept
+            kept.extend(live.into_iter().filter(|(lsn, _)| *lsn > newest && *lsn <= truncated));
+        }
+        let mut dropped = kept.iter().take_while(|(lsn, _)| *lsn <= floor).count();
+        dropped = dropped.max(kept.len().saturating_sub(max_records));
+        if let Some((lsn, _)) = dropped.checked_sub(1).map(|i| &kept[i]) {
+            state.discarded_through = state.discarded_through.max(*lsn);
+        }
+        // Records acked by every consumer may also have been truncated before
+        state.discarded_through = state.discarded_through.max(floor.min(truncated));
+        wal::write_records(&self.log_path, &kept[dropped..])?;
+        self.write_state(state)?;
+        Ok(state)
+    }
+
+    fn write_state(&self, state: State) -> io::Result<()> {
+        let tmp = format!("{}.tmp", self.state_path);
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(&state).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)?;
+        file.sync_all()?;
+        fs::rename(tmp, &self.state_path)
+    }
+}
@@ -0,0 +1,111 @@
+//! Write coalescing
+//!
+//! Holds the latest value written to each path under the configured prefixes
+//! until the window closes or something touching the staged paths needs the
+//! tree, so a path written many times per window costs one WAL record and one
+//! tree update. A read of a staged path itself is answered from here.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::{path_within, paths_overlap};
+
+pub struct Coalescer {
+    window: Duration,
+    prefixes: Vec<String>,
+    /// Staged writes in first-write order, with an index by path
+    staged: Vec<(String, Value)>,
+    positions: HashMap<String, usize>,
+    window_start: Instant,
+    staged_writes: u64,
+    coalesced: u64,
+    flushes: u64,
+    flushed_writes: u64,
+}
+
+impl Coalescer {
+    pub fn new(window_ms: u32, prefixes: Vec<String>) -> Self {
+        Coalescer {
+            window: Duration::from_millis(window_ms as u64),
+            prefixes,
+            staged: Vec::new(),
+            positions: HashMap::new(),
+            window_start: Instant::now(),
+            staged_writes: 0,
+            coalesced: 0,
+            flushes: 0,
+            flushed_writes: 0,
+        }
+    }
+
+    /// Whether writes to `path` are staged rather than applied
+    pub fn covers(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| path_within(path, prefix))
+    }
+
+    /// Whether any staged path is `path`, an ancestor, or a descendant
+    pub fn overlaps(&self, path: &str) -> bool {
+        self.staged.iter().any(|(staged, _)| paths_overlap(staged, path))
+    }
+
+    /// Whether staging `path` would reorder it against a different staged path
+    pub fn conflicts(&self, path: &str) -> bool {
+        self.staged.iter().any(|(staged, _)| staged != path && paths_overlap(staged, path))
+    }
+
+    /// The pending value of `path` itself, if it is staged
+    pub fn staged(&self, path: &str) -> Option<&Value> {
+        self.positions.get(path).map(|&i| &self.staged[i].1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+
+    pub fn window_elapsed(&self) -> bool {
+        !self.staged.is_empty() && self.window_start.elapsed() >= self.window
+    }
+
+    /// Stage a write, replacing any pending value for the same path
+    pub fn stage(&mut self, path: String, value: Value) {
+        if self.staged.is_empty() {
+            self.window_start = Instant::now();
+        }
+        self.staged_writes += 1;
+        match self.positions.get(&path) {
+            Some(&i) => {
+                self.staged[i].1 = value;
+                self.coalesced += 1;
+            }
+            None => {
+                self.positions.insert(path.clone(), self.staged.len());
+                self.staged.push((path, value));
+            }
+        }
+    }
+
+    /// Remove every staged write, oldest first
+    pub fn take(&mut self) -> Vec<(String, Value)> {
+        self.positions.clear();
+        let staged = std::mem::take(&mut self.staged);
+        if !staged.is_empty() {
+            self.flushes += 1;
+            self.flushed_writes += staged.len() as u64;
+        }
+        staged
+    }
+
+    pub fn stats(&self) -> Value {
+        json!({
+            "enabled": true,
+            "windowMs": self.window.as_millis() as u64,
+            "prefixes": self.prefixes,
+            "pending": self.staged.len(),
+            "stagedWrites": self.staged_writes,
+            "coalescedWrites": self.coalesced,
+            "flushes": self.flushes,
+            "flushedWrites": self.flushed_writes,
+        })
+    }
+}
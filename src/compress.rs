@@ -0,0 +1,118 @@
+//! Value-level compression
+//!
+//! A compressed value is stored in the tree as the envelope
+//! `{"__compressed": "zstd", "data": "<base64>"}` holding the zstd-compressed
+//! JSON of the original value. Reads expand envelopes back transparently.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+
+use crate::error::DbError;
+
+pub const ENVELOPE_KEY: &str = "__compressed";
+const ALGORITHM: &str = "zstd";
+const LEVEL: i32 = 3;
+
+pub fn is_envelope(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => {
+            map.len() == 2
+                && map.get(ENVELOPE_KEY).and_then(Value::as_str) == Some(ALGORITHM)
+                && map.get("data").is_some_and(Value::is_string)
+        }
+        _ => false,
+    }
+}
+
+/// Wrap a value in a compressed envelope
+pub fn compress(value: &Value) -> Result<Value, DbError> {
+    let raw = serde_json::to_vec(value).map_err(|e| DbError::InvalidArgument(format!("Cannot serialize value: {}", e)))?;
+    let packed = zstd::bulk::compress(&raw, LEVEL).map_err(|e| DbError::Io(format!("Compression failed: {}", e)))?;
+    Ok(json!({ ENVELOPE_KEY: ALGORITHM, "data": STANDARD.encode(packed) }))
+}
+
+/// Original value of an envelope; None if the payload is damaged
+pub fn decompress(envelope: &Value) -> Option<Value> {
+    let packed = STANDARD.decode(envelope.get("data")?.as_str()?).ok()?;
+    let raw = zstd::decode_all(&packed[..]).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+/// Replace every envelope in `value` with its original value, returning
+/// whether any was found. Damaged envelopes are left as they are.
+pub fn expand(value: &mut Value) -> bool {
+    if is_envelope(value) {
+        return match decompress(value) {
+            Some(original) => {
+                *value = original;
+                // The original may itself contain envelopes
+                expand(value);
+                true
+            }
+            None => false,
+        };
+    }
+    match value {
+        Value::Object(map) => map.values_mut().fold(false, |found, v| expand(v) | found),
+        Value::Array(arr) => arr.iter_mut().fold(false, |found, v| expand(v) | found),
+        _ => false,
+    }
+}
+
+/// `Value::pointer` that steps through envelopes on the way down
+pub fn pointer<'a>(root: &'a Value, ptr: &str) -> Option<Cow<'a, Value>> {
+    if ptr.is_empty() {
+        return Some(Cow::Borrowed(root));
+    }
+    let tokens = ptr.strip_prefix('/')?.split('/').map(|t| t.replace("~1", "/").replace("~0", "~"));
+    step_all(Cow::Borrowed(root), tokens)
+}
+
+/// Follow object keys / array indexes through envelopes, decompressing any
+/// envelope passed through or landed on
+pub fn step_all<'a, I, S>(start: Cow<'a, Value>, parts: I) -> Option<Cow<'a, Value>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut current = start;
+    for part in parts {
+        if is_envelope(&current) {
+            current = Cow::Owned(decompress(&current)?);
+        }
+        current = match current {
+            Cow::Borrowed(v) => Cow::Borrowed(child(v, part.as_ref())?),
+            Cow::Owned(v) => Cow::Owned(child(&v, part.as_ref())?.clone()),
+        };
+    }
+    if is_envelope(&current) {
+        current = Cow::Owned(decompress(&current)?);
+    }
+    Some(current)
+}
+
+fn child<'a>(value: &'a Value, part: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map.get(part),
+        Value::Array(arr) => arr.get(part.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+/// Envelope count, stored payload bytes and original bytes under `value`
+pub fn stats(value: &Value) -> (u64, u64, u64) {
+    if is_envelope(value) {
+        let data = value["data"].as_str().unwrap_or_default();
+        let raw = STANDARD.decode(data).ok()
+            .and_then(|packed| zstd::zstd_safe::get_frame_content_size(&packed).ok().flatten())
+            .unwrap_or(0);
+        return (1, data.len() as u64, raw);
+    }
+    let children: Box<dyn Iterator<Item = &Value>> = match value {
+        Value::Object(map) => Box::new(map.values()),
+        Value::Array(arr) => Box::new(arr.iter()),
+        _ => return (0, 0, 0),
+    };
+    children.map(stats).fold((0, 0, 0), |acc, s| (acc.0 + s.0, acc.1 + s.1, acc.2 + s.2))
+}
@@ -0,0 +1,160 @@
+//! Uniqueness constraints
+//!
+//! A constraint forbids two documents of one collection from sharing the same
+//! tuple of field values. It is meant for small collections that don't warrant
+//! an index: the tuple of every document is tracked in a hash map maintained
+//! the way a counter is (writes mark keys dirty, the next check re-reads only
+//! those). Documents missing any of the fields are not constrained.
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{paths_overlap, resolve_field, split_field};
+
+pub struct Constraint {
+    pub collection: String,
+    pub fields: Vec<String>,
+    parts: Vec<Vec<String>>,
+    /// Serialized tuple -> keys of the documents holding it
+    owners: HashMap<String, Vec<String>>,
+    /// Document key -> its serialized tuple
+    tuples: HashMap<String, String>,
+    /// Keys written since the last refresh
+    dirty: HashSet<String>,
+    /// The collection itself (or an ancestor) was replaced
+    rescan: bool,
+    pub rejected: u64,
+}
+
+impl Constraint {
+    pub fn new(collection: String, fields: Vec<String>) -> Self {
+        Constraint {
+            parts: fields.iter().map(|f| split_field(f)).collect(),
+            collection,
+            fields,
+            owners: HashMap::new(),
+            tuples: HashMap::new(),
+            dirty: HashSet::new(),
+            rescan: true,
+            rejected: 0,
+        }
+    }
+
+    pub fn from_stored(stored: &Value) -> Option<Self> {
+        let collection = stored.get("collection")?.as_str()?.to_string();
+        let fields = stored.get("fields")?.as_array()?
+            .iter()
+            .map(|f| f.as_str().map(String::from))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Constraint::new(collection, fields))
+    }
+
+    pub fn to_stored(&self) -> Value {
+        json!({ "collection": self.collection, "fields": self.fields })
+    }
+
+    /// Record a write at `path`
+    pub fn note_write(&mut self, path: &str) {
+        if !paths_overlap(&self.collection, path) {
+            return;
+        }
+        match path.strip_prefix(self.collection.as_str()).and_then(|rest| rest.strip_prefix('.')) {
+            Some(rest) => {
+                let key = rest.split('.').next().unwrap_or(rest);
+                self.dirty.insert(key.to_string());
+            }
+            None => self.rescan = true,
+        }
+    }
+
+    /// Whether a write at `inner` (a path inside one document) can change its tuple
+    pub fn touches(&self, inner: &str) -> bool {
+        inner.is_empty() || self.fields.iter().any(|field| paths_overlap(field, inner))
+    }
+
+    /// The document's tuple, serialized, or None if it lacks one of the fields
+    pub fn tuple_of(&self, doc: &Value) -> Option<String> {
+        let values = self.parts.iter()
+            .map(|parts| resolve_field(doc, parts).map(|v| v.into_owned()))
+            .collect::<Option<Vec<Value>>>()?;
+        serde_json::to_string(&values).ok()
+    }
+
+    /// Bring the tracked tuples up to date with `collection` (the current value
+    /// at the collection path)
+    pub fn refresh(&mut self, collection: Option<&Value>) {
+        if self.rescan {
+            self.owners.clear();
+            self.tuples.clear();
+            for (key, doc) in entries(collection) {
+                self.track(key, doc);
+            }
+        } else {
+            for key in std::mem::take(&mut self.dirty) {
+                self.untrack(&key);
+                if let Some(doc) = get_entry(collection, &key) {
+                    self.track(key, doc);
+                }
+            }
+        }
+        self.dirty.clear();
+        self.rescan = false;
+    }
+
+    fn track(&mut self, key: String, doc: &Value) {
+        if let Some(tuple) = self.tuple_of(doc) {
+            self.owners.entry(tuple.clone()).or_default().push(key.clone());
+            self.tuples.insert(key, tuple);
+        }
+    }
+
+    fn untrack(&mut self, key: &str) {
+        let Some(tuple) = self.tuples.remove(key) else { return };
+        if let Some(keys) = self.owners.get_mut(&tuple) {
+            keys.retain(|k| k != key);
+            if keys.is_empty() {
+                self.owners.remove(&tuple);
+            }
+        }
+    }
+
+    /// Another key already holding `tuple`, if any (tracking must be fresh)
+    pub fn holder(&self, tuple: &str, key: &str) -> Option<&str> {
+        self.owners.get(tuple)?.iter().map(String::as_str).find(|k| *k != key)
+    }
+
+    /// Groups of keys sharing a tuple within a collection value:
+    /// `[{values, keys}]`, ordered by tuple
+    pub fn duplicates(&self, collection: Option<&Value>) -> Vec<Value> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (key, doc) in entries(collection) {
+            if let Some(tuple) = self.tuple_of(doc) {
+                groups.entry(tuple).or_default().push(key);
+            }
+        }
+        groups.into_iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .map(|(tuple, keys)| {
+                let values: Value = serde_json::from_str(&tuple).unwrap_or(Value::Null);
+                json!({ "values": values, "keys": keys })
+            })
+            .collect()
+    }
+}
+
+/// (key, document) pairs of an object or array collection
+fn entries(collection: Option<&Value>) -> Vec<(String, &Value)> {
+    match collection {
+        Some(Value::Object(map)) => map.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        Some(Value::Array(arr)) => arr.iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn get_entry<'a>(collection: Option<&'a Value>, key: &str) -> Option<&'a Value> {
+    match collection? {
+        Value::Object(map) => map.get(key),
+        Value::Array(arr) => arr.get(key.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
@@ -0,0 +1,112 @@
+//! Maintained counters
+//!
+//! A counter tracks which documents of a collection match its filters. Writes
+//! only mark the touched document keys dirty; the next read re-evaluates those
+//! documents, so a poll costs O(documents written since the last poll) rather
+//! than a scan.
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::{paths_overlap, QueryFilter};
+
+pub struct Counter {
+    pub collection: String,
+    pub filters: Vec<QueryFilter>,
+    /// Keys of documents currently matching
+    matched: HashSet<String>,
+    /// Keys written since the last refresh
+    dirty: HashSet<String>,
+    /// The collection itself (or an ancestor) was replaced
+    rescan: bool,
+}
+
+impl Counter {
+    pub fn new(collection: String, filters: Vec<QueryFilter>) -> Self {
+        Counter { collection, filters, matched: HashSet::new(), dirty: HashSet::new(), rescan: true }
+    }
+
+    /// Restore a counter persisted by `to_stored`
+    pub fn from_stored(stored: &Value) -> Option<Self> {
+        let collection = stored.get("collection")?.as_str()?.to_string();
+        let filters = serde_json::from_value(stored.get("filters")?.clone()).ok()?;
+        let matched: HashSet<String> = stored.get("keys")?.as_array()?
+            .iter()
+            .filter_map(|k| k.as_str().map(String::from))
+            .collect();
+        Some(Counter { collection, filters, matched, dirty: HashSet::new(), rescan: false })
+    }
+
+    pub fn to_stored(&self) -> Value {
+        let mut keys: Vec<&String> = self.matched.iter().collect();
+        keys.sort();
+        json!({
+            "collection": self.collection,
+            "filters": self.filters,
+            "count": self.matched.len(),
+            "keys": keys,
+        })
+    }
+
+    /// Record a write at `path`
+    pub fn note_write(&mut self, path: &str) {
+        if !paths_overlap(&self.collection, path) {
+            return;
+        }
+        match path.strip_prefix(self.collection.as_str()).and_then(|rest| rest.strip_prefix('.')) {
+            Some(rest) => {
+                let key = rest.split('.').next().unwrap_or(rest);
+                self.dirty.insert(key.to_string());
+            }
+            None => self.rescan = true,
+        }
+    }
+
+    /// Force a full re-evaluation on the next read
+    pub fn invalidate(&mut self) {
+        self.rescan = true;
+    }
+
+    pub fn is_clean(&self) -> bool {
+        !self.rescan && self.dirty.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        self.matched.len()
+    }
+
+    /// Bring the matched set up to date with `collection` (the current value at
+    /// the collection path). Array collections are always rescanned, since a
+    /// removal shifts every later index.
+    pub fn refresh(&mut self, collection: Option<&Value>, matches: impl Fn(&Value) -> bool) {
+        if self.is_clean() {
+            return;
+        }
+        match collection {
+            Some(Value::Object(map)) if !self.rescan => {
+                for key in self.dirty.drain() {
+                    match map.get(&key) {
+                        Some(doc) if matches(doc) => self.matched.insert(key),
+                        _ => self.matched.remove(&key),
+                    };
+                }
+            }
+            Some(Value::Object(map)) => {
+                self.matched = map.iter()
+                    .filter(|(_, doc)| matches(doc))
+                    .map(|(key, _)| key.clone())
+                    .collect();
+            }
+            Some(Value::Array(arr)) => {
+                self.matched = arr.iter()
+                    .enumerate()
+                    .filter(|(_, doc)| matches(doc))
+                    .map(|(i, _)| i.to_string())
+                    .collect();
+            }
+            _ => self.matched.clear(),
+        }
+        self.dirty.clear();
+        self.rescan = false;
+    }
+}
@@ -0,0 +1,182 @@
+//! Custom indexes
+//!
+//! An index whose keys come from a JS extractor `(doc, key) => string[]`
+//! instead of one field, so callers can build their own structures (trigrams,
+//! geohash buckets, tags) on the engine's key -> paths storage and query them
+//! with the usual index lookups.
+//!
+//! The extractor contract:
+//!
+//! - It is synchronous, called on the thread that registered it, and must be
+//!   fast and pure: it sees the document and its key and returns the keys to
+//!   index it under (strings or numbers; null or undefined for none).
+//! - It must not call back into the database.
+//! - Writes only mark the touched documents dirty, the way counters do; the
+//!   extractor runs for those documents when the index is next read, rebuilt
+//!   or verified. Writes from other threads are picked up the same way.
+//! - With `onError: "reject"` it also runs before each write to the
+//!   collection (with the write lock held) and a throw rejects the write.
+//!   With `"skip"` (the default) a throw leaves the document out of the index
+//!   and is counted.
+
+use napi::bindgen_prelude::*;
+use napi::{sys, Env, JsFunction, NapiRaw, NapiValue};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::thread::ThreadId;
+
+use crate::error::DbError;
+use crate::paths_overlap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnError {
+    Reject,
+    Skip,
+}
+
+impl OnError {
+    pub fn parse(on_error: Option<&str>) -> std::result::Result<Self, DbError> {
+        match on_error {
+            None | Some("skip") => Ok(OnError::Skip),
+            Some("reject") => Ok(OnError::Reject),
+            Some(other) => Err(DbError::InvalidArgument(format!(
+                "Unknown onError '{}': use 'reject' or 'skip'", other
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OnError::Reject => "reject",
+            OnError::Skip => "skip",
+        }
+    }
+}
+
+/// A JS function kept alive across calls. Only ever called, and released, on
+/// the thread that created it; elsewhere it is inert.
+pub struct Extractor {
+    env: sys::napi_env,
+    func: sys::napi_ref,
+    thread: ThreadId,
+}
+
+// The raw handles are only dereferenced on `thread`
+unsafe impl Send for Extractor {}
+unsafe impl Sync for Extractor {}
+
+impl Extractor {
+    pub fn new(env: &Env, extract: JsFunction) -> Result<Self> {
+        let mut func = std::ptr::null_mut();
+        let status = unsafe { sys::napi_create_reference(env.raw(), extract.raw(), 1, &mut func) };
+        if status != sys::Status::napi_ok {
+            return Err(Error::new(Status::from(status), "Failed to keep the extractor".to_string()));
+        }
+        Ok(Extractor { env: env.raw(), func, thread: std::thread::current().id() })
+    }
+
+    /// Whether the extractor can be called from the current thread
+    pub fn callable(&self) -> bool {
+        std::thread::current().id() == self.thread
+    }
+
+    /// The keys of one document, or the extractor's error. None off the
+    /// registering thread.
+    pub fn extract(&self, doc: &Value, key: &str) -> Option<std::result::Result<Vec<String>, String>> {
+        if !self.callable() {
+            return None;
+        }
+        let env = unsafe { Env::from_raw(self.env) };
+        Some(self.call(&env, doc, key).map_err(|e| e.reason))
+    }
+
+    fn call(&self, env: &Env, doc: &Value, key: &str) -> Result<Vec<String>> {
+        let mut func = std::ptr::null_mut();
+        let status = unsafe { sys::napi_get_reference_value(self.env, self.func, &mut func) };
+        if status != sys::Status::napi_ok || func.is_null() {
+            return Err(Error::new(Status::GenericFailure, "The extractor is no longer available".to_string()));
+        }
+        let func = unsafe { JsFunction::from_raw_unchecked(self.env, func) };
+        let args = [env.to_js_value(doc)?, env.create_string(key)?.into_unknown()];
+        let result = func.call(None, &args)?;
+        let keys: Value = match result.get_type()? {
+            ValueType::Undefined | ValueType::Null => return Ok(Vec::new()),
+            _ => env.from_js_value(result)?,
+        };
+        let Value::Array(keys) = keys else {
+            return Err(Error::new(Status::InvalidArg, format!("extractor returned {} instead of an array of keys", keys)));
+        };
+        keys.into_iter()
+            .map(|k| match k {
+                Value::String(s) => Ok(s),
+                Value::Number(n) => Ok(n.to_string()),
+                other => Err(Error::new(Status::InvalidArg, format!("extractor returned a non-string key {}", other))),
+            })
+            .collect()
+    }
+}
+
+impl Drop for Extractor {
+    fn drop(&mut self) {
+        if self.callable() {
+            unsafe { sys::napi_delete_reference(self.env, self.func) };
+        }
+    }
+}
+
+pub struct CustomIndex {
+    pub collection: String,
+    pub on_error: OnError,
+    pub extractor: std::sync::Arc<Extractor>,
+    /// Keys of documents written since the last refresh
+    pub dirty: HashSet<String>,
+    /// The collection itself (or an ancestor) was replaced
+    pub rescan: bool,
+    /// Extractor failures while indexing (documents left out)
+    pub errors: u64,
+    pub last_error: Option<String>,
+    /// Writes the extractor rejected
+    pub rejected: u64,
+}
+
+impl CustomIndex {
+    pub fn new(collection: String, on_error: OnError, extractor: Extractor) -> Self {
+        CustomIndex {
+            collection,
+            on_error,
+            extractor: std::sync::Arc::new(extractor),
+            dirty: HashSet::new(),
+            rescan: true,
+            errors: 0,
+            last_error: None,
+            rejected: 0,
+        }
+    }
+
+    /// Record a write at `path`
+    pub fn note_write(&mut self, path: &str) {
+        if !paths_overlap(&self.collection, path) {
+            return;
+        }
+        match path.strip_prefix(self.collection.as_str()).and_then(|rest| rest.strip_prefix('.')) {
+            Some(rest) => {
+                let key = rest.split('.').next().unwrap_or(rest);
+                self.dirty.insert(key.to_string());
+            }
+            None => self.rescan = true,
+        }
+    }
+
+    pub fn status(&self, entries: usize, keys: usize) -> Value {
+        json!({
+            "collection": self.collection,
+            "onError": self.on_error.as_str(),
+            "entries": entries,
+            "keys": keys,
+            "pending": if self.rescan { Value::String("all".to_string()) } else { json!(self.dirty.len()) },
+            "errors": self.errors,
+            "lastError": self.last_error,
+            "rejected": self.rejected,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+//! Typed database errors
+//!
+//! Every failure crossing the napi boundary carries a stable, machine-readable
+//! code as the prefix of its reason, e.g. `"ERR_PATH_NOT_FOUND: Path does not exist: users.1"`.
+//! Callers match on the code; the text after it is for humans and may change.
+//!
+//! | Code                      | Meaning                                            |
+//! |---------------------------|----------------------------------------------------|
+//! | `ERR_DB_LOCKED`           | Another process holds the database lock            |
+//! | `ERR_WAL`                 | WAL append/sync/open failed                        |
+//! | `ERR_WAL_BUSY`            | WAL queue full under the "error" overflow policy   |
+//! | `ERR_IO`                  | Filesystem error reading or writing database files |
+//! | `ERR_CORRUPT`             | On-disk data could not be parsed                   |
+//! | `ERR_PATH_NOT_FOUND`      | The addressed path does not exist                  |
+//! | `ERR_NOT_AN_ARRAY`        | Array operation on a non-array value               |
+//! | `ERR_PATH_BLOCKED`        | Path traverses a primitive or mismatched container |
+//! | `ERR_INVALID_PATH`        | Path breaks the depth, length or character rules   |
+//! | `ERR_INVALID_ARGUMENT`    | An argument was malformed or out of range          |
+//! | `ERR_VALIDATION`          | A value failed schema validation                   |
+//! | `ERR_NO_TRANSACTION`      | Transaction operation without an active one       |
+//! | `ERR_TRANSACTION_ACTIVE`  | A transaction is already active                    |
+//! | `ERR_READ_ONLY`           | Write into a path that does not accept writes      |
+//! | `ERR_FROZEN`              | Write touches a frozen path                        |
+//! | `ERR_CONFLICT`            | Write conflicts with a lease or concurrent change  |
+//! | `ERR_NOT_FOUND`           | Named object (savepoint, snapshot, ...) is unknown |
+//! | `ERR_LIMIT`               | A configured limit was exceeded                    |
+//! | `ERR_QUOTA`               | A write would exceed a collection quota            |
+//! | `ERR_CONSTRAINT`          | A write would violate a uniqueness constraint      |
+//! | `ERR_RECOVERY_PENDING`    | Deferred WAL recovery has not completed yet        |
+//! | `ERR_INDEX`               | Index load/save failure                            |
+//! | `ERR_THROTTLED`           | Write rate limit exceeded under the "error" policy |
+//! | `ERR_CLOSED`              | The database was closed                            |
+
+use crate::btree::IndexError;
+use crate::fs_lock::LockError;
+use napi::{Error, Status};
+
+#[derive(Debug)]
+pub enum DbError {
+    LockContended(String),
+    WalFailure(String),
+    WalBusy(String),
+    Io(String),
+    Corrupt(String),
+    PathNotFound(String),
+    NotAnArray(String),
+    PathBlocked(String),
+    InvalidPath(String),
+    InvalidArgument(String),
+    ValidationFailed { path: String, details: String },
+    TransactionInactive,
+    TransactionActive,
+    ReadOnly(String),
+    Frozen(String),
+    Conflict(String),
+    NotFound(String),
+    LimitExceeded(String),
+    QuotaExceeded(String),
+    ConstraintViolation(String),
+    RecoveryPending(String),
+    Index(String),
+    Throttled(String),
+    Closed,
+}
+
+impl DbError {
+    /// Stable machine-readable code
+    pub fn code(&self) -> &'static str {
+        match self {
+            DbError::LockContended(_) => "ERR_DB_LOCKED",
+            DbError::WalFailure(_) => "ERR_WAL",
+            DbError::WalBusy(_) => "ERR_WAL_BUSY",
+            DbError::Io(_) => "ERR_IO",
+            DbError::Corrupt(_) => "ERR_CORRUPT",
+            DbError::PathNotFound(_) => "ERR_PATH_NOT_FOUND",
+            DbError::NotAnArray(_) => "ERR_NOT_AN_ARRAY",
+            DbError::PathBlocked(_) => "ERR_PATH_BLOCKED",
+            DbError::InvalidPath(_) => "ERR_INVALID_PATH",
+            DbError::InvalidArgument(_) => "ERR_INVALID_ARGUMENT",
+            DbError::ValidationFailed { .. } => "ERR_VALIDATION",
+            DbError::TransactionInactive => "ERR_NO_TRANSACTION",
+            DbError::TransactionActive => "ERR_TRANSACTION_ACTIVE",
+            DbError::ReadOnly(_) => "ERR_READ_ONLY",
+            DbError::Frozen(_) => "ERR_FROZEN",
+            DbError::Conflict(_) => "ERR_CONFLICT",
+            DbError::NotFound(_) => "ERR_NOT_FOUND",
+            DbError::LimitExceeded(_) => "ERR_LIMIT",
+            DbError::QuotaExceeded(_) => "ERR_QUOTA",
+            DbError::ConstraintViolation(_) => "ERR_CONSTRAINT",
+            DbError::RecoveryPending(_) => "ERR_RECOVERY_PENDING",
+            DbError::Index(_) => "ERR_INDEX",
+            DbError::Throttled(_) => "ERR_THROTTLED",
+            DbError::Closed => "ERR_CLOSED",
+        }
+    }
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::ValidationFailed { path, details } => write!(f, "Validation failed at {}: {}", path, details),
+            DbError::TransactionInactive => write!(f, "No active transaction"),
+            DbError::TransactionActive => write!(f, "Transaction already active"),
+            DbError::Closed => write!(f, "Database is closed"),
+            DbError::LockContended(msg)
+            | DbError::WalFailure(msg)
+            | DbError::WalBusy(msg)
+            | DbError::Io(msg)
+            | DbError::Corrupt(msg)
+            | DbError::PathNotFound(msg)
+            | DbError::NotAnArray(msg)
+            | DbError::PathBlocked(msg)
+            | DbError::InvalidPath(msg)
+            | DbError::InvalidArgument(msg)
+            | DbError::ReadOnly(msg)
+            | DbError::Frozen(msg)
+            | DbError::Conflict(msg)
+            | DbError::NotFound(msg)
+            | DbError::LimitExceeded(msg)
+            | DbError::QuotaExceeded(msg)
+            | DbError::ConstraintViolation(msg)
+            | DbError::RecoveryPending(msg)
+            | DbError::Index(msg)
+            | DbError::Throttled(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<DbError> for Error {
+    fn from(e: DbError) -> Self {
+        Error::new(Status::GenericFailure, format!("{}: {}", e.code(), e))
+    }
+}
+
+impl From<std::io::Error> for DbError {
+    fn from(e: std::io::Error) -> Self {
+        DbError::Io(e.to_string())
+    }
+}
+
+impl From<LockError> for DbError {
+    fn from(e: LockError) -> Self {
+        match e {
+            LockError::AlreadyLocked => DbError::LockContended(e.to_string()),
+            _ => DbError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<IndexError> for DbError {
+    fn from(e: IndexError) -> Self {
+        DbError::Index(e.to_string())
+    }
+}
@@ -0,0 +1,45 @@
+//! String formats for the schema `format` keyword
+//!
+//! Each known format has a validator; `check` returns None for any other
+//! name, which schemas ignore unless they set `strictFormats`.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Format names `check` knows
+pub const KNOWN: &[&str] = &["date", "date-time", "email", "ipv4", "ipv6", "uri", "uuid"];
+
+/// Dot-atom local part, then a domain of hostname labels with at least one dot
+static EMAIL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[A-Za-z0-9!#$%&'*+/=?^_`{|}~-]+(\.[A-Za-z0-9!#$%&'*+/=?^_`{|}~-]+)*@([A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?\.)+[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?$").unwrap()
+});
+
+/// RFC 3986 absolute URI: a scheme, then no whitespace and only well-formed
+/// percent escapes
+static URI: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:([A-Za-z0-9\-._~!$&'()*+,;=:@/?#\[\]]|%[0-9A-Fa-f]{2})*$").unwrap()
+});
+
+/// Whether `value` is a valid `format`; None if the format is unknown
+pub fn check(format: &str, value: &str) -> Option<bool> {
+    Some(match format {
+        "date" => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() && value.len() == 10,
+        "date-time" => chrono::DateTime::parse_from_rfc3339(value).is_ok(),
+        "email" => value.len() <= 254 && EMAIL.is_match(value),
+        "ipv4" => value.parse::<Ipv4Addr>().is_ok(),
+        "ipv6" => value.parse::<Ipv6Addr>().is_ok(),
+        "uri" => URI.is_match(value),
+        "uuid" => is_uuid(value),
+        _ => return None,
+    })
+}
+
+/// 8-4-4-4-12 hex digits, either case
+fn is_uuid(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 36 && bytes.iter().enumerate().all(|(i, b)| match i {
+        8 | 13 | 18 | 23 => *b == b'-',
+        _ => b.is_ascii_hexdigit(),
+    })
+}
@@ -0,0 +1,136 @@
+//! Frozen paths
+//!
+//! A frozen prefix refuses every write to a path equal to, under or above it
+//! (replacing an ancestor replaces the frozen subtree too) with `ERR_FROZEN`,
+//! whichever method the write comes through. TTL deletes are skipped and
+//! counted instead of failing. Freezes live in memory; persisted ones are also
+//! kept in the reserved namespace and come back when the database is opened.
+//!
+//! Prefixes are kept sorted by first segment, so a write only compares itself
+//! with the prefixes sharing its first segment.
+
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::error::DbError;
+
+struct Frozen {
+    first: String,
+    path: String,
+    persisted: bool,
+}
+
+#[derive(Default)]
+pub struct Freezes {
+    /// Any prefix is frozen; writes skip the lookup otherwise
+    enabled: AtomicBool,
+    /// Sorted by (first segment, path)
+    frozen: RwLock<Vec<Frozen>>,
+    rejected: AtomicU64,
+    purges_skipped: AtomicU64,
+}
+
+fn first_segment(path: &str) -> &str {
+    path.split_once('.').map_or(path, |(first, _)| first)
+}
+
+/// Whether a write at `path` touches the frozen `prefix`
+fn overlaps(path: &str, prefix: &str) -> bool {
+    let (short, long) = if path.len() < prefix.len() { (path, prefix) } else { (prefix, path) };
+    short.is_empty() || long == short || (long.starts_with(short) && long.as_bytes()[short.len()] == b'.')
+}
+
+impl Freezes {
+    /// Freeze `prefix`, or change whether it is persisted. Returns false if it
+    /// was already frozen that way.
+    pub fn freeze(&self, prefix: &str, persisted: bool) -> bool {
+        let mut frozen = self.frozen.write();
+        let first = first_segment(prefix);
+        let at = frozen.partition_point(|f| (f.first.as_str(), f.path.as_str()) < (first, prefix));
+        let changed = match frozen.get_mut(at).filter(|f| f.path == prefix) {
+            Some(existing) => std::mem::replace(&mut existing.persisted, persisted) != persisted,
+            None => {
+                frozen.insert(at, Frozen { first: first.to_string(), path: prefix.to_string(), persisted });
+                true
+            }
+        };
+        self.enabled.store(true, Ordering::Release);
+        changed
+    }
+
+    /// Unfreeze exactly `prefix`; Some(persisted) if it was frozen
+    pub fn unfreeze(&self, prefix: &str) -> Option<bool> {
+        let mut frozen = self.frozen.write();
+        let at = frozen.iter().position(|f| f.path == prefix)?;
+        let removed = frozen.remove(at);
+        self.enabled.store(!frozen.is_empty(), Ordering::Release);
+        Some(removed.persisted)
+    }
+
+    /// Replace the persisted freezes with `paths`, keeping in-memory ones
+    pub fn load(&self, paths: &[String]) {
+        self.frozen.write().retain(|f| !f.persisted);
+        for path in paths {
+            self.freeze(path, true);
+        }
+        self.enabled.store(!self.frozen.read().is_empty(), Ordering::Release);
+    }
+
+    /// Persisted prefixes, for the reserved namespace
+    pub fn persisted(&self) -> Vec<String> {
+        self.frozen.read().iter().filter(|f| f.persisted).map(|f| f.path.clone()).collect()
+    }
+
+    /// The frozen prefix a write at `path` would touch
+    pub fn blocking(&self, path: &str) -> Option<String> {
+        if !self.enabled.load(Ordering::Acquire) {
+            return None;
+        }
+        let frozen = self.frozen.read();
+        if path.is_empty() {
+            return frozen.first().map(|f| f.path.clone());
+        }
+        // The root, if frozen, sorts first
+        if let Some(root) = frozen.first().filter(|f| f.path.is_empty()) {
+            return Some(root.path.clone());
+        }
+        let first = first_segment(path);
+        let start = frozen.partition_point(|f| f.first.as_str() < first);
+        frozen[start..].iter()
+            .take_while(|f| f.first == first)
+            .find(|f| overlaps(path, &f.path))
+            .map(|f| f.path.clone())
+    }
+
+    pub fn check(&self, path: &str) -> Result<(), DbError> {
+        match self.blocking(path) {
+            None => Ok(()),
+            Some(prefix) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(DbError::Frozen(format!("Path '{}' is frozen by '{}'", path, prefix)))
+            }
+        }
+    }
+
+    /// Count a TTL delete skipped because its path is frozen
+    pub fn skip_purge(&self) {
+        self.purges_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// `[{path, persisted}]` in sorted order
+    pub fn list(&self) -> Value {
+        Value::Array(self.frozen.read().iter()
+            .map(|f| json!({ "path": f.path, "persisted": f.persisted }))
+            .collect())
+    }
+
+    /// `{paths, rejected, purgesSkipped}`
+    pub fn stats(&self) -> Value {
+        json!({
+            "paths": self.frozen.read().len(),
+            "rejected": self.rejected.load(Ordering::Relaxed),
+            "purgesSkipped": self.purges_skipped.load(Ordering::Relaxed),
+        })
+    }
+}
@@ -0,0 +1,125 @@
+//! Document ID strategies
+//!
+//! How `insert` names new documents of a collection. The strategy of each
+//! collection is kept in the reserved namespace, so it survives restarts:
+//!
+//! - `uuid4`: random RFC 4122 UUID (the default)
+//! - `ulid`: 26-character Crockford base32, sortable by creation time and
+//!   monotonic within one process
+//! - `nanoid`: URL-safe random key of a given length (default 21)
+//! - `sequence`: the next value of a named sequence, as a decimal key
+//! - `field`: the value of a named field of the document
+//!
+//! Sequence values come from the crash-safe sequence generator, so generated
+//! keys are never handed out twice, even across a crash and WAL replay.
+
+use parking_lot::Mutex;
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::error::DbError;
+
+const NANOID_ALPHABET: &[u8; 64] = b"useandom-26T198340PX75pxJACKVERYMINDBUSHWOLF_GQZbfghjklqvwyzrict";
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const DEFAULT_NANOID_LENGTH: usize = 21;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdStrategy {
+    Uuid4,
+    Ulid,
+    Nanoid { length: usize },
+    Sequence { name: String },
+    Field { field: String },
+}
+
+impl IdStrategy {
+    /// `"ulid"`, or an object naming the type and its parameter:
+    /// `{type: "nanoid", length}`, `{type: "sequence", name}`, `{type: "field", field}`
+    pub fn parse(spec: &Value) -> Result<Self, DbError> {
+        let (kind, params) = match spec {
+            Value::String(kind) => (kind.as_str(), None),
+            Value::Object(map) => match map.get("type").and_then(Value::as_str) {
+                Some(kind) => (kind, Some(map)),
+                None => return Err(DbError::InvalidArgument("ID strategy object needs a 'type'".to_string())),
+            },
+            other => return Err(DbError::InvalidArgument(format!("Invalid ID strategy: {}", other))),
+        };
+        let param = |key: &str| params.and_then(|p| p.get(key));
+        let name_param = |key: &str| match param(key).and_then(Value::as_str) {
+            Some(name) if !name.is_empty() => Ok(name.to_string()),
+            _ => Err(DbError::InvalidArgument(format!("ID strategy '{}' needs a '{}'", kind, key))),
+        };
+        Ok(match kind {
+            "uuid4" => IdStrategy::Uuid4,
+            "ulid" => IdStrategy::Ulid,
+            "nanoid" => {
+                let length = match param("length") {
+                    None | Some(Value::Null) => DEFAULT_NANOID_LENGTH,
+                    Some(v) => v.as_u64().filter(|n| (4..=64).contains(n)).ok_or_else(|| {
+                        DbError::InvalidArgument(format!("nanoid length must be between 4 and 64, got {}", v))
+                    })? as usize,
+                };
+                IdStrategy::Nanoid { length }
+            }
+            "sequence" => IdStrategy::Sequence { name: name_param("name")? },
+            "field" => IdStrategy::Field { field: name_param("field")? },
+            other => return Err(DbError::InvalidArgument(format!(
+                "Unknown ID strategy '{}': use 'uuid4', 'ulid', 'nanoid', 'sequence' or 'field'", other
+            ))),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            IdStrategy::Uuid4 => json!({ "type": "uuid4" }),
+            IdStrategy::Ulid => json!({ "type": "ulid" }),
+            IdStrategy::Nanoid { length } => json!({ "type": "nanoid", "length": length }),
+            IdStrategy::Sequence { name } => json!({ "type": "sequence", "name": name }),
+            IdStrategy::Field { field } => json!({ "type": "field", "field": field }),
+        }
+    }
+
+    /// A fresh random key; None for strategies that need the database or the document
+    pub fn generate(&self) -> Option<String> {
+        match self {
+            IdStrategy::Uuid4 => Some(uuid4()),
+            IdStrategy::Ulid => Some(ulid()),
+            IdStrategy::Nanoid { length } => Some(nanoid(*length)),
+            IdStrategy::Sequence { .. } | IdStrategy::Field { .. } => None,
+        }
+    }
+}
+
+fn uuid4() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+/// Time and randomness of the last ULID, so keys made in one millisecond still sort
+static LAST_ULID: Mutex<(u64, u128)> = Mutex::new((0, 0));
+
+fn ulid() -> String {
+    let now = crate::now_ms();
+    let value = {
+        let mut last = LAST_ULID.lock();
+        let random = if now <= last.0 {
+            // Same millisecond (or the clock stepped back): keep the time, bump the randomness
+            last.1 = (last.1 + 1) & ((1u128 << 80) - 1);
+            last.1
+        } else {
+            last.0 = now;
+            last.1 = rand::thread_rng().gen::<u128>() & ((1u128 << 80) - 1);
+            last.1
+        };
+        ((last.0 as u128 & ((1u128 << 48) - 1)) << 80) | random
+    };
+    (0..26).rev().map(|i| CROCKFORD[((value >> (i * 5)) & 31) as usize] as char).collect()
+}
+
+fn nanoid(length: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..length).map(|_| NANOID_ALPHABET[rng.gen_range(0..64)] as char).collect()
+}
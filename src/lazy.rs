@@ -0,0 +1,625 @@
+//! Lazy collections
+//!
+//! A lazy collection is a top-level object kept out of the base file. Each
+//! save appends the entries written since the previous save to
+//! `<db>.lazy/<name>.data` and rewrites `<name>.idx`, which maps every key to
+//! the offset and length of its latest version. Opening the database loads
+//! only the index; an entry is read into the tree the first time a path
+//! below it is touched, and the least recently used clean entries are dropped
+//! again once more than `max_resident` are in memory.
+//!
+//! Touching the collection itself (or the root) loads it in full, with a
+//! warning: a query over a lazy collection has to see every entry. A fully
+//! loaded collection stays in memory until the next save or an explicit
+//! `evict`. Entries written since the last save are never evicted, so
+//! nothing is lost between the write and the save that persists it.
+
+use parking_lot::Mutex;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::DbError;
+use crate::meta;
+use crate::wal::read_wal_records;
+
+/// Compact the data file once stale versions outweigh live ones by this much
+const COMPACT_MIN_GARBAGE: u64 = 1 << 20;
+
+/// `<name>.idx`
+#[derive(serde::Serialize, Deserialize, Default)]
+struct IndexFile {
+    entries: HashMap<String, (u64, u64)>,
+}
+
+#[derive(Default)]
+struct Collection {
+    /// Latest stored version of each key: (offset, length) in the data file
+    index: HashMap<String, (u64, u64)>,
+    data_bytes: u64,
+    /// Whether the collection exists on disk (an empty one still has an index)
+    stored: bool,
+    /// Every stored entry is in the tree; exempt from LRU eviction
+    complete: bool,
+    /// Loaded entries and the tick they were last touched
+    resident: HashMap<String, u64>,
+    pinned: HashSet<String>,
+}
+
+/// Writes since the last save. Kept apart from the store so writers can
+/// record them while holding the data lock.
+#[derive(Default)]
+pub struct Dirty {
+    entries: HashMap<String, HashSet<String>>,
+    /// Collections replaced as a whole
+    replaced: HashSet<String>,
+}
+
+impl Dirty {
+    fn is_dirty(&self, name: &str, key: &str) -> bool {
+        self.replaced.contains(name) || self.entries.get(name).is_some_and(|keys| keys.contains(key))
+    }
+}
+
+pub struct LazyStore {
+    dir: PathBuf,
+    collections: HashMap<String, Collection>,
+    max_resident: usize,
+    tick: u64,
+    /// Least recently touched first: tick -> (collection, key)
+    lru: BTreeMap<u64, (String, String)>,
+    loads: u64,
+    evictions: u64,
+    full_loads: u64,
+}
+
+/// Lazy state of one database, shared with its readers. Lock order: `store`,
+/// then the data lock, then `dirty` (writers note writes holding the data lock).
+pub struct LazyCollections {
+    pub names: Vec<String>,
+    pub store: Mutex<LazyStore>,
+    pub dirty: Mutex<Dirty>,
+}
+
+impl LazyCollections {
+    /// Whether `path` is the root or lies in a lazy collection
+    pub fn touches(&self, path: &str) -> bool {
+        path.is_empty() || self.names.iter().any(|name| path.split('.').next() == Some(name.as_str()))
+    }
+
+    pub fn note_write(&self, path: &str) {
+        if self.touches(path) {
+            LazyStore::note_write(&self.names, &mut self.dirty.lock(), path);
+        }
+    }
+}
+
+/// Which part of a lazy collection a path touches
+enum Touch<'a> {
+    None,
+    Entry(&'a str, &'a str),
+    Collection(&'a str),
+    All,
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+impl LazyStore {
+    /// Load the indexes of `names` and prepare `root`: a collection found in
+    /// the base file stays loaded and is moved out of it by the next save.
+    pub fn open(db_path: &str, names: &[String], max_resident: usize, root: &mut Value, dirty: &mut Dirty) -> Result<Self, DbError> {
+        let dir = PathBuf::from(format!("{}.lazy", db_path));
+        let mut collections = HashMap::new();
+        for name in names {
+            if name.is_empty() || name.starts_with('.') || name.contains(['.', '/', '\\']) || name == meta::META_KEY {
+                return Err(DbError::InvalidArgument(format!("'{}' cannot be a lazy collection: use a plain top-level key", name)));
+            }
+            let mut collection = Collection::default();
+            let idx_path = dir.join(format!("{}.idx", name));
+            match File::open(&idx_path) {
+                Ok(file) => {
+                    let stored: IndexFile = serde_json::from_reader(BufReader::new(file))
+                        .map_err(|e| DbError::Corrupt(format!("Lazy index {}: {}", idx_path.display(), e)))?;
+                    collection.index = stored.entries;
+                    collection.data_bytes = fs::metadata(dir.join(format!("{}.data", name))).map_or(0, |m| m.len());
+                    collection.stored = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            let map = root.as_object_mut().ok_or_else(|| DbError::Corrupt("Database root is not an object".to_string()))?;
+            if map.contains_key(name) {
+                // Written before the collection was lazy, or saved by a build without this option
+                collection.complete = true;
+                dirty.replaced.insert(name.clone());
+            } else if collection.stored {
+                map.insert(name.clone(), Value::Object(Map::new()));
+                collection.complete = collection.index.is_empty();
+            } else {
+                collection.complete = true;
+            }
+            collections.insert(name.clone(), collection);
+        }
+        Ok(LazyStore {
+            dir,
+            collections,
+            max_resident: max_resident.max(1),
+            tick: 0,
+            lru: BTreeMap::new(),
+            loads: 0,
+            evictions: 0,
+            full_loads: 0,
+        })
+    }
+
+    fn touch_of<'a>(&self, path: &'a str) -> Touch<'a> {
+        if path.is_empty() {
+            return Touch::All;
+        }
+        let mut segments = path.splitn(3, '.');
+        let name = segments.next().unwrap_or_default();
+        if !self.collections.contains_key(name) {
+            return Touch::None;
+        }
+        match segments.next() {
+            Some(key) => Touch::Entry(name, key),
+            None => Touch::Collection(name),
+        }
+    }
+
+    /// Note a write at `path` for the next save
+    pub fn note_write(names: &[String], dirty: &mut Dirty, path: &str) {
+        if path.is_empty() {
+            dirty.replaced.extend(names.iter().cloned());
+            return;
+        }
+        let mut segments = path.splitn(3, '.');
+        let name = segments.next().unwrap_or_default();
+        if !names.iter().any(|n| n == name) {
+            return;
+        }
+        match segments.next() {
+            Some(key) => {
+                dirty.entries.entry(name.to_string()).or_default().insert(key.to_string());
+            }
+            None => {
+                dirty.replaced.insert(name.to_string());
+            }
+        }
+    }
+
+    /// Whether `path` may need entries loaded. Entries already in memory are
+    /// marked used, so the caller can skip the data lock when nothing is missing.
+    pub fn needs_load(&mut self, path: &str) -> bool {
+        match self.touch_of(path) {
+            Touch::None => false,
+            Touch::Entry(name, key) => {
+                let collection = &self.collections[name];
+                if collection.resident.contains_key(key) {
+                    let (name, key) = (name.to_string(), key.to_string());
+                    self.touch(&name, &key);
+                    false
+                } else {
+                    !collection.complete && collection.index.contains_key(key)
+                }
+            }
+            Touch::Collection(name) => !self.collections[name].complete,
+            Touch::All => self.collections.values().any(|c| !c.complete),
+        }
+    }
+
+    /// Load whatever `path` needs into `root`. Returns the collections that
+    /// had to be loaded in full, with how many entries that read.
+    pub fn fault_in(&mut self, path: &str, root: &mut Value, dirty: &Dirty) -> Result<Vec<(String, usize)>, DbError> {
+        let mut full = Vec::new();
+        match self.touch_of(path) {
+            Touch::None => {}
+            Touch::Entry(name, key) => {
+                let (name, key) = (name.to_string(), key.to_string());
+                self.load_entry(&name, &key, root, dirty)?;
+                self.trim(root, dirty);
+            }
+            Touch::Collection(name) => {
+                let name = name.to_string();
+                if let Some(count) = self.load_all(&name, root, dirty)? {
+                    full.push((name, count));
+                }
+            }
+            Touch::All => {
+                let mut names: Vec<String> = self.collections.keys().cloned().collect();
+                names.sort();
+                for name in names {
+                    if let Some(count) = self.load_all(&name, root, dirty)? {
+                        full.push((name, count));
+                    }
+                }
+            }
+        }
+        Ok(full)
+    }
+
+    fn touch(&mut self, name: &str, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some(collection) = self.collections.get_mut(name) else { return };
+        if let Some(old) = collection.resident.insert(key.to_string(), tick) {
+            self.lru.remove(&old);
+        }
+        self.lru.insert(tick, (name.to_string(), key.to_string()));
+    }
+
+    fn read_entry(&self, name: &str, offset: u64, len: u64) -> io::Result<Value> {
+        let mut file = File::open(self.dir.join(format!("{}.data", name)))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len as usize];
+        file.read_exact(&mut bytes)?;
+        serde_json::from_slice(&bytes).map_err(invalid)
+    }
+
+    fn load_entry(&mut self, name: &str, key: &str, root: &mut Value, dirty: &Dirty) -> Result<(), DbError> {
+        let Some(collection) = self.collections.get(name) else { return Ok(()) };
+        if collection.resident.contains_key(key) {
+            self.touch(name, key);
+            return Ok(());
+        }
+        // A key written since the last save is already in the tree (or was deleted)
+        if collection.complete || dirty.is_dirty(name, key) {
+            return Ok(());
+        }
+        let Some(&(offset, len)) = collection.index.get(key) else { return Ok(()) };
+        let value = self.read_entry(name, offset, len)
+            .map_err(|e| DbError::Corrupt(format!("Lazy entry {}.{}: {}", name, key, e)))?;
+        if let Some(Value::Object(map)) = root.get_mut(name) {
+            map.insert(key.to_string(), value);
+            self.loads += 1;
+            self.touch(name, key);
+        }
+        Ok(())
+    }
+
+    /// Load every stored entry not yet in the tree; None if there were none
+    fn load_all(&mut self, name: &str, root: &mut Value, dirty: &Dirty) -> Result<Option<usize>, DbError> {
+        let Some(collection) = self.collections.get(name) else { return Ok(None) };
+        if collection.complete {
+            return Ok(None);
+        }
+        let mut missing: Vec<(&String, &(u64, u64))> = collection.index.iter()
+            .filter(|(key, _)| !collection.resident.contains_key(*key) && !dirty.is_dirty(name, key))
+            .collect();
+        // File order approximates the order keys were first saved in
+        missing.sort_unstable_by_key(|(_, (offset, _))| *offset);
+        let mut loaded = Vec::with_capacity(missing.len());
+        if !missing.is_empty() {
+            let mut file = BufReader::new(File::open(self.dir.join(format!("{}.data", name)))?);
+            for (key, &(offset, len)) in missing {
+                file.seek(SeekFrom::Start(offset))?;
+                let mut bytes = vec![0; len as usize];
+                file.read_exact(&mut bytes)?;
+                let value: Value = serde_json::from_slice(&bytes)
+                    .map_err(|e| DbError::Corrupt(format!("Lazy entry {}.{}: {}", name, key, e)))?;
+                loaded.push((key.clone(), value));
+            }
+        }
+        let count = loaded.len();
+        if let Some(Value::Object(map)) = root.get_mut(name) {
+            for (key, value) in loaded {
+                map.insert(key.clone(), value);
+                self.touch(name, &key);
+            }
+        }
+        self.loads += count as u64;
+        self.full_loads += 1;
+        if let Some(collection) = self.collections.get_mut(name) {
+            collection.complete = true;
+        }
+        Ok(Some(count))
+    }
+
+    /// Drop least recently used clean entries beyond `max_resident`
+    fn trim(&mut self, root: &mut Value, dirty: &Dirty) {
+        let resident: usize = self.collections.values().map(|c| c.resident.len()).sum();
+        let excess = resident.saturating_sub(self.max_resident);
+        if excess == 0 {
+            return;
+        }
+        let victims: Vec<(u64, String, String)> = self.lru.iter()
+            .filter(|(_, (name, key))| {
+                let collection = &self.collections[name];
+                !collection.complete && !collection.pinned.contains(key) && !dirty.is_dirty(name, key)
+            })
+            .map(|(tick, (name, key))| (*tick, name.clone(), key.clone()))
+            .take(excess)
+            .collect();
+        for (tick, name, key) in victims {
+            self.drop_entry(&name, &key, tick, root);
+        }
+    }
+
+    fn drop_entry(&mut self, name: &str, key: &str, tick: u64, root: &mut Value) {
+        self.lru.remove(&tick);
+        if let Some(collection) = self.collections.get_mut(name) {
+            collection.resident.remove(key);
+            collection.complete = false;
+        }
+        if let Some(Value::Object(map)) = root.get_mut(name) {
+            map.shift_remove(key);
+        }
+        self.evictions += 1;
+    }
+
+    /// Drop an entry, or every entry of a collection, from memory. Entries
+    /// that are pinned or written since the last save stay. Returns how many
+    /// were dropped.
+    pub fn evict(&mut self, path: &str, root: &mut Value, dirty: &Dirty) -> Result<u32, DbError> {
+        let targets: Vec<(String, String, u64)> = match self.touch_of(path) {
+            Touch::Entry(name, key) => self.collections[name].resident.get(key)
+                .map(|&tick| vec![(name.to_string(), key.to_string(), tick)])
+                .unwrap_or_default(),
+            Touch::Collection(name) => {
+                let collection = &self.collections[name];
+                // Entries loaded by a full load are tracked too, so this covers them all
+                collection.resident.iter().map(|(key, &tick)| (name.to_string(), key.clone(), tick)).collect()
+            }
+            _ => return Err(DbError::InvalidArgument(format!("'{}' is not a lazy collection or one of its entries", path))),
+        };
+        let mut dropped = 0;
+        for (name, key, tick) in targets {
+            if self.collections[&name].pinned.contains(&key) || dirty.is_dirty(&name, &key) {
+                continue;
+            }
+            self.drop_entry(&name, &key, tick, root);
+            dropped += 1;
+        }
+        Ok(dropped)
+    }
+
+    /// Keep an entry resident until unpinned; loads it if needed. Returns
+    /// whether the entry exists.
+    pub fn pin(&mut self, path: &str, pinned: bool, root: &mut Value, dirty: &Dirty) -> Result<bool, DbError> {
+        let Touch::Entry(name, key) = self.touch_of(path) else {
+            return Err(DbError::InvalidArgument(format!("'{}' is not an entry of a lazy collection", path)));
+        };
+        let (name, key) = (name.to_string(), key.to_string());
+        if !pinned {
+            return Ok(self.collections.get_mut(&name).is_some_and(|c| c.pinned.remove(&key)));
+        }
+        self.load_entry(&name, &key, root, dirty)?;
+        let exists = root.get(&name).and_then(|c| c.get(&key)).is_some();
+        if exists {
+            if let Some(collection) = self.collections.get_mut(&name) {
+                collection.pinned.insert(key.clone());
+            }
+            self.touch(&name, &key);
+        }
+        Ok(exists)
+    }
+
+    /// Bring in everything the WAL touches before it is replayed, and mark it
+    /// dirty: replayed writes are not in the lazy files yet.
+    pub fn prime(&mut self, wal_path: &str, wal_enabled: bool, root: &mut Value, dirty: &mut Dirty) -> Result<(), DbError> {
+        let names: Vec<String> = self.collections.keys().cloned().collect();
+        let paths: Vec<String> = if wal_enabled {
+            if !Path::new(wal_path).exists() {
+                return Ok(());
+            }
+            read_wal_records(wal_path)?.0.into_iter()
+                .filter(|(_, op)| !op.op_type.is_marker())
+                .map(|(_, op)| op.path)
+                .collect()
+        } else if Path::new(wal_path).exists() {
+            // Legacy WAL lines are not worth parsing twice: load everything
+            vec![String::new()]
+        } else {
+            return Ok(());
+        };
+        for path in paths {
+            self.fault_in(&path, root, dirty)?;
+            Self::note_write(&names, dirty, &path);
+        }
+        Ok(())
+    }
+
+    /// Write the entries dirtied since the last save. Returns the collections
+    /// the base file must leave out (those stored here).
+    pub fn persist(&mut self, root: &Value, dirty: &mut Dirty) -> Result<HashSet<String>, DbError> {
+        let mut stored_here = HashSet::new();
+        let names: Vec<String> = self.collections.keys().cloned().collect();
+        for name in names {
+            let replaced = dirty.replaced.contains(&name);
+            let keys = dirty.entries.remove(&name).unwrap_or_default();
+            match root.get(&name) {
+                Some(Value::Object(map)) => {
+                    fs::create_dir_all(&self.dir)?;
+                    let collection = self.collections.get_mut(&name).expect("known collection");
+                    if replaced || !collection.stored {
+                        Self::rewrite(&self.dir, &name, collection, map)?;
+                    } else if !keys.is_empty() {
+                        Self::append(&self.dir, &name, collection, map, &keys)?;
+                        let live: u64 = collection.index.values().map(|(_, len)| len + 1).sum();
+                        let garbage = collection.data_bytes.saturating_sub(live);
+                        if garbage > live && garbage > COMPACT_MIN_GARBAGE {
+                            Self::compact(&self.dir, &name, collection)?;
+                        }
+                    }
+                    stored_here.insert(name.clone());
+                }
+                // Absent, or no longer an object: nothing for this store to hold
+                _ => {
+                    let collection = self.collections.get_mut(&name).expect("known collection");
+                    if collection.stored {
+                        let _ = fs::remove_file(self.dir.join(format!("{}.data", name)));
+                        let _ = fs::remove_file(self.dir.join(format!("{}.idx", name)));
+                    }
+                    collection.index.clear();
+                    collection.data_bytes = 0;
+                    collection.stored = false;
+                }
+            }
+            dirty.replaced.remove(&name);
+        }
+
+        // Everything in the tree is on disk now: track it for eviction
+        for name in &stored_here {
+            let keys: Vec<String> = match root.get(name) {
+                Some(Value::Object(map)) => map.keys().filter(|k| !self.collections[name].resident.contains_key(*k)).cloned().collect(),
+                _ => Vec::new(),
+            };
+            for key in keys {
+                self.touch(name, &key);
+            }
+        }
+        // Fully loaded collections become evictable again
+        for collection in self.collections.values_mut() {
+            collection.complete = collection.index.is_empty() || !collection.stored;
+        }
+        Ok(stored_here)
+    }
+
+    /// Evict beyond the resident cap once a save has made entries clean
+    pub fn trim_after_save(&mut self, root: &mut Value, dirty: &Dirty) {
+        // Drop tracking of keys no longer in the tree (deleted before the save)
+        let mut gone = Vec::new();
+        for (name, collection) in &self.collections {
+            for (key, &tick) in &collection.resident {
+                if root.get(name).and_then(|c| c.get(key)).is_none() {
+                    gone.push((name.clone(), key.clone(), tick));
+                }
+            }
+        }
+        for (name, key, tick) in gone {
+            self.lru.remove(&tick);
+            if let Some(collection) = self.collections.get_mut(&name) {
+                collection.resident.remove(&key);
+                collection.pinned.remove(&key);
+            }
+        }
+        self.trim(root, dirty);
+    }
+
+    fn write_index(dir: &Path, name: &str, collection: &Collection) -> io::Result<()> {
+        let tmp = dir.join(format!("{}.idx.tmp", name));
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer(&mut file, &IndexFile { entries: collection.index.clone() }).map_err(invalid)?;
+        file.sync_all()?;
+        fs::rename(tmp, dir.join(format!("{}.idx", name)))
+    }
+
+    /// Replace the data file with the entries of `map`
+    fn rewrite(dir: &Path, name: &str, collection: &mut Collection, map: &Map<String, Value>) -> io::Result<()> {
+        let tmp = dir.join(format!("{}.data.tmp", name));
+        let mut file = io::BufWriter::new(File::create(&tmp)?);
+        let mut index = HashMap::with_capacity(map.len());
+        let mut offset = 0;
+        for (key, value) in map {
+            let bytes = serde_json::to_vec(value).map_err(invalid)?;
+            file.write_all(&bytes)?;
+            file.write_all(b"\n")?;
+            index.insert(key.clone(), (offset, bytes.len() as u64));
+            offset += bytes.len() as u64 + 1;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(tmp, dir.join(format!("{}.data", name)))?;
+        collection.index = index;
+        collection.data_bytes = offset;
+        collection.stored = true;
+        Self::write_index(dir, name, collection)
+    }
+
+    /// Append new versions of `keys` (removing deleted ones from the index)
+    fn append(dir: &Path, name: &str, collection: &mut Collection, map: &Map<String, Value>, keys: &HashSet<String>) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(dir.join(format!("{}.data", name)))?;
+        let mut offset = file.metadata()?.len();
+        let mut out = Vec::new();
+        for key in keys {
+            match map.get(key) {
+                Some(value) => {
+                    let start = out.len();
+                    serde_json::to_writer(&mut out, value).map_err(invalid)?;
+                    let len = (out.len() - start) as u64;
+                    out.push(b'\n');
+                    collection.index.insert(key.clone(), (offset, len));
+                    offset += len + 1;
+                }
+                None => {
+                    collection.index.remove(key);
+                }
+            }
+        }
+        file.write_all(&out)?;
+        file.sync_all()?;
+        collection.data_bytes = offset;
+        Self::write_index(dir, name, collection)
+    }
+
+    /// Rewrite the data file without superseded versions
+    fn compact(dir: &Path, name: &str, collection: &mut Collection) -> io::Result<()> {
+        let mut source = File::open(dir.join(format!("{}.data", name)))?;
+        let tmp = dir.join(format!("{}.data.tmp", name));
+        let mut file = io::BufWriter::new(File::create(&tmp)?);
+        let mut entries: Vec<(String, (u64, u64))> = collection.index.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        entries.sort_unstable_by_key(|(_, (offset, _))| *offset);
+        let mut offset = 0;
+        for (key, (old, len)) in entries {
+            source.seek(SeekFrom::Start(old))?;
+            let mut bytes = vec![0; len as usize];
+            source.read_exact(&mut bytes)?;
+            file.write_all(&bytes)?;
+            file.write_all(b"\n")?;
+            collection.index.insert(key, (offset, len));
+            offset += len + 1;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(tmp, dir.join(format!("{}.data", name)))?;
+        collection.data_bytes = offset;
+        Self::write_index(dir, name, collection)
+    }
+
+    /// `{maxResident, resident, loads, evictions, fullLoads, collections: {name: {...}}}`
+    pub fn status(&self, dirty: &Dirty) -> Value {
+        let collections: Map<String, Value> = self.collections.iter().map(|(name, c)| {
+            let dirty_count = dirty.entries.get(name).map_or(0, HashSet::len);
+            (name.clone(), json!({
+                "stored": c.index.len(),
+                "resident": c.resident.len(),
+                "pinned": c.pinned.len(),
+                "dirty": dirty_count,
+                "replaced": dirty.replaced.contains(name),
+                "complete": c.complete,
+                "dataBytes": c.data_bytes,
+            }))
+        }).collect();
+        json!({
+            "maxResident": self.max_resident,
+            "resident": self.collections.values().map(|c| c.resident.len()).sum::<usize>(),
+            "loads": self.loads,
+            "evictions": self.evictions,
+            "fullLoads": self.full_loads,
+            "collections": collections,
+        })
+    }
+}
+
+/// The root as the base file stores it: without collections held by the lazy store
+pub struct BaseView<'a> {
+    pub root: &'a Value,
+    pub skip: &'a HashSet<String>,
+}
+
+impl Serialize for BaseView<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Value::Object(map) = self.root else {
+            return self.root.serialize(serializer);
+        };
+        let mut out = serializer.serialize_map(None)?;
+        for (key, value) in map.iter().filter(|(key, _)| !self.skip.contains(*key)) {
+            out.serialize_entry(key, value)?;
+        }
+        out.end()
+    }
+}
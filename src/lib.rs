@@ -2,12 +2,16 @@
 
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
+use napi::JsUnknown;
+use napi::threadsafe_function::{ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::borrow::Cow;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use parking_lot::RwLock as PLRwLock;
 use rayon::prelude::*;
 
@@ -16,43 +20,438 @@ mod fs_lock;
 mod wal;
 mod btree;
 mod schema;
+mod format;
+mod sample;
+mod meta;
+mod error;
+mod undo;
+mod coalesce;
+mod compress;
+mod counter;
+mod reclaim;
+mod quota;
+mod constraint;
+mod recovery;
+mod persistence;
+mod path_rules;
+mod archive;
+mod query_parse;
+mod reader;
+mod throttle;
+mod canonical;
+mod lazy;
+mod advisor;
+mod backup;
+mod ids;
+mod cascade;
+mod custom_index;
+mod upkeep;
+mod paging;
+mod anomaly;
+mod padding;
+mod trace;
+mod planner;
+mod bulk;
+mod maintenance;
+mod value_limit;
+mod changes;
+mod freeze;
+mod stats;
+mod ttl;
 
 use btree::BTreeIndex;
-use schema::{Schema, validate};
+use schema::{CompiledSchema, Schema, validate, validate_array_append, validate_array_write};
 use std::collections::HashMap;
 use parking_lot::Mutex;
 
 struct TransactionState {
-    undo_log: Vec<(String, Option<Value>)>,
+    undo: UndoLog,
     savepoints: HashMap<String, usize>,
 }
 
+/// Open transactions by id. A write is recorded in the transaction its
+/// `txn` option names; one without a `txn` joins none, and fails only when
+/// its path overlaps one an open transaction wrote, which a rollback would
+/// otherwise overwrite without a trace in the WAL.
+#[derive(Default)]
+pub struct Transactions {
+    next_id: u32,
+    open: std::collections::BTreeMap<u32, TransactionState>,
+    /// The open transaction begun with `isolation: "exclusive"`: while it is
+    /// open it is the only one, writes outside it fail and reads outside it
+    /// see the data as it was before it
+    exclusive: Option<u32>,
+}
+
+impl Transactions {
+    fn is_active(&self) -> bool {
+        !self.open.is_empty()
+    }
+
+    /// The transaction a write with `txn` is recorded in, if any
+    fn recording(&mut self, txn: Option<u32>) -> Option<(u32, &mut TransactionState)> {
+        let id = txn?;
+        self.open.get_mut(&id).map(|state| (id, state))
+    }
+
+    /// Remove the transaction `id`, ending its exclusivity
+    fn close(&mut self, id: u32) -> Option<TransactionState> {
+        if self.exclusive == Some(id) {
+            self.exclusive = None;
+        }
+        self.open.remove(&id)
+    }
+
+    /// Fails while an exclusive transaction is open and the write running on
+    /// this thread is not part of it
+    fn check_exclusive(&self) -> std::result::Result<(), DbError> {
+        match self.exclusive {
+            Some(id) if WRITE_TXN.with(|current| current.get()) != Some(id) => Err(DbError::TransactionActive),
+            _ => Ok(()),
+        }
+    }
+
+    /// Undo entries overlapping `path` of the exclusive transaction, unless
+    /// the read is part of it (`txn`); applied newest first to the live data
+    /// they give what was committed
+    fn uncommitted(&mut self, path: &str, txn: Option<u32>) -> std::result::Result<Vec<(String, Restore)>, DbError> {
+        match self.exclusive {
+            Some(id) if txn != Some(id) => match self.open.get_mut(&id) {
+                Some(state) => state.undo.overlapping(path),
+                None => Ok(Vec::new()),
+            },
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// The transaction a call names with `txn`, or else the only open one
+    fn target(&mut self, txn: Option<u32>) -> std::result::Result<(u32, &mut TransactionState), DbError> {
+        if txn.is_none() && self.open.len() > 1 {
+            return Err(DbError::InvalidArgument("Several transactions are open; pass a transaction id".to_string()));
+        }
+        match txn {
+            Some(_) => self.recording(txn),
+            None => self.open.iter_mut().next().map(|(id, state)| (*id, state)),
+        }.ok_or(DbError::TransactionInactive)
+    }
+}
+
+/// Facts observed while opening the database, kept for `integrity_report`
+#[derive(Default)]
+struct LoadReport {
+    file_exists: bool,
+    file_size: u64,
+    /// Reads of the main file (more than one means a parse was retried)
+    read_attempts: u32,
+    /// Last error reading the main file, if it never loaded
+    main_file_error: Option<String>,
+    /// Backup loaded instead of an unreadable main file
+    backup_used: Option<String>,
+    /// A parseable `.tmp` newer than the main file (an interrupted or in-flight save)
+    newer_tmp: Option<String>,
+    relocated_key: Option<String>,
+    /// WAL replay outcome (empty until a deferred recovery runs)
+    replay: Replayed,
+}
+
+/// Shape of query results over a collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Emit {
+    /// Matching documents (keys discarded)
+    Values,
+    /// `[{key, value}]`
+    Entries,
+    /// Object of matching documents under their original keys
+    Object,
+    /// `[{index, value}]` with numeric indexes (array collections only)
+    Indexed,
+}
+
+impl Emit {
+    fn parse(emit: Option<&str>) -> std::result::Result<Self, DbError> {
+        match emit.unwrap_or("values") {
+            "values" => Ok(Emit::Values),
+            "entries" => Ok(Emit::Entries),
+            "object" => Ok(Emit::Object),
+            "indexed" => Ok(Emit::Indexed),
+            other => Err(DbError::InvalidArgument(format!("Unknown emit mode '{}'", other))),
+        }
+    }
+}
+
+/// Stored definition of a materialized view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ViewDefinition {
+    path: String,
+    #[serde(default)]
+    filters: Vec<QueryFilter>,
+    /// count / sum / avg / min / max over the (filtered) collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aggregate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    #[serde(default, alias = "auto_refresh", skip_serializing_if = "Option::is_none")]
+    auto_refresh: Option<AutoRefresh>,
+    /// Result shape for query views: "values" (default), "entries" or "object"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    emit: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoRefresh {
+    #[serde(alias = "on_write_under")]
+    on_write_under: String,
+}
+
+/// Callback registered with `on_delete`
+struct DeleteListener {
+    tsfn: ThreadsafeFunction<Value, ErrorStrategy::Fatal>,
+    include_values: bool,
+}
+
+/// Removal causes reported to delete listeners; "explicit" is not stored in the WAL
+const DELETE_REASONS: &[&str] = &["explicit", "ttl", "quota", "cascade"];
+
+/// Write path watched by a view with `autoRefresh`
+struct ViewWatch {
+    path: String,
+    stale: AtomicBool,
+}
+
+/// Open point-in-time snapshots, keyed by handle
+struct SnapshotTable {
+    next_handle: u32,
+    max_open: usize,
+    /// handle -> (frozen root, approximate size in bytes)
+    open: HashMap<u32, (Arc<Value>, usize)>,
+}
+
+impl SnapshotTable {
+    fn new() -> Self {
+        SnapshotTable {
+            next_handle: 1,
+            max_open: 16,
+            open: HashMap::new(),
+        }
+    }
+}
+
+/// Cap on the serialized size of a write context
+const MAX_WRITE_CONTEXT_BYTES: usize = 1024;
+
+/// Paths of each kind of difference listed by `verify_index`
+const VERIFY_SAMPLES: usize = 20;
+
+/// Default cap on results converted to JS values (512MB)
+const DEFAULT_MAX_RESULT_BYTES: usize = 512 * 1024 * 1024;
+
+/// Rough in-memory footprint of a JSON value
+fn approx_size(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => 16,
+        Value::String(s) => 24 + s.len(),
+        Value::Array(arr) => 24 + arr.iter().map(approx_size).sum::<usize>(),
+        Value::Object(map) => 48 + map.iter().map(|(k, v)| 24 + k.len() + approx_size(v)).sum::<usize>(),
+    }
+}
+
 struct PreparedFilter {
-    field: String,
+    field: Vec<String>,
     op: String,
     value: Value,
     regex: Option<regex::Regex>,
+    /// Pre-split path of a sibling field when `value` is `{"$field": "path"}`
+    field_ref: Option<Vec<String>>,
+    missing: MissingPolicy,
+    equality: Equality,
+}
+
+/// A parsed string query's filter tree, ready to evaluate
+enum PreparedExpr {
+    Filter(PreparedFilter),
+    And(Vec<PreparedExpr>),
+    Or(Vec<PreparedExpr>),
+    Not(Box<PreparedExpr>),
+}
+
+impl PreparedExpr {
+    fn from_expr(expr: &query_parse::Expr, equality: Equality) -> Self {
+        use query_parse::Expr;
+        match expr {
+            Expr::Filter(f) => PreparedExpr::Filter(PreparedFilter::from_query_filter(f, equality)),
+            Expr::And(items) => PreparedExpr::And(items.iter().map(|e| Self::from_expr(e, equality)).collect()),
+            Expr::Or(items) => PreparedExpr::Or(items.iter().map(|e| Self::from_expr(e, equality)).collect()),
+            Expr::Not(inner) => PreparedExpr::Not(Box::new(Self::from_expr(inner, equality))),
+        }
+    }
+
+    fn matches(&self, item: &Value) -> bool {
+        match self {
+            PreparedExpr::Filter(f) => NativeDB::matches_filter(item, f),
+            PreparedExpr::And(items) => items.iter().all(|e| e.matches(item)),
+            PreparedExpr::Or(items) => items.iter().any(|e| e.matches(item)),
+            PreparedExpr::Not(inner) => !inner.matches(item),
+        }
+    }
+}
+
+/// Sort order for ORDER BY: numbers, then strings, then booleans, then other
+/// values; null and missing sort last ascending (first descending)
+fn compare_sort_values(a: Option<&Value>, b: Option<&Value>) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    fn rank(v: Option<&Value>) -> u8 {
+        match v {
+            Some(Value::Number(_)) => 0,
+            Some(Value::String(_)) => 1,
+            Some(Value::Bool(_)) => 2,
+            Some(Value::Array(_)) | Some(Value::Object(_)) => 3,
+            Some(Value::Null) | None => 4,
+        }
+    }
+    match (a, b) {
+        (Some(Value::Number(x)), Some(Value::Number(y))) => x.as_f64().partial_cmp(&y.as_f64()).unwrap_or(Ordering::Equal),
+        (Some(Value::String(x)), Some(Value::String(y))) => x.cmp(y),
+        (Some(Value::Bool(x)), Some(Value::Bool(y))) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// What a filter does when the field (or a referenced sibling field) is absent
+#[derive(Clone, Copy, PartialEq)]
+enum MissingPolicy {
+    Exclude,
+    Match,
+    AsNull,
+}
+
+impl MissingPolicy {
+    fn parse(policy: Option<&str>) -> Self {
+        match policy {
+            Some("match") => MissingPolicy::Match,
+            Some("as_null") => MissingPolicy::AsNull,
+            _ => MissingPolicy::Exclude,
+        }
+    }
+}
+
+static NULL: Value = Value::Null;
+
+/// Operators a `QueryFilter` accepts
+const FILTER_OPS: &[&str] = &[
+    "eq", "ne", "gt", "gte", "lt", "lte", "contains", "startswith", "endswith",
+    "in", "notin", "regex", "containsAll", "containsAny",
+];
+
+/// Why `qf` cannot be evaluated as written, if it cannot: an unknown operator
+/// would otherwise match everything, and a malformed value nothing
+fn check_filter(qf: &QueryFilter) -> std::result::Result<(), String> {
+    if !FILTER_OPS.contains(&qf.op.as_str()) {
+        return Err(format!("unknown operator '{}'; use one of {}", qf.op, FILTER_OPS.join(", ")));
+    }
+    if qf.field.is_empty() {
+        return Err(format!("'{}' needs a field", qf.op));
+    }
+    if let Some(policy) = qf.missing.as_deref() {
+        if !matches!(policy, "exclude" | "match" | "as_null") {
+            return Err(format!("unknown missing policy '{}'; use exclude, match or as_null", policy));
+        }
+    }
+    check_equality(qf)?;
+    let field_ref = matches!(&qf.value, Value::Object(map) if map.len() == 1 && map.get("$field").is_some_and(Value::is_string));
+    match (qf.op.as_str(), &qf.value) {
+        ("regex", Value::String(pattern)) => regex::Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("'{}' is not a valid regex: {}", pattern, e)),
+        ("regex", _) => Err("'regex' needs a string pattern".to_string()),
+        ("in" | "notin" | "containsAll" | "containsAny", Value::Array(_)) => Ok(()),
+        ("in" | "notin" | "containsAll" | "containsAny", _) if !field_ref => Err(format!("'{}' needs an array of values", qf.op)),
+        _ => Ok(()),
+    }
+}
+
+/// Why the filter's `equality` is not one the engine knows, if it is not
+fn check_equality(qf: &QueryFilter) -> std::result::Result<(), String> {
+    match qf.equality.as_deref() {
+        Some(mode) if Equality::parse(mode).is_none() => Err(format!("unknown equality '{}'; use strict or canonical", mode)),
+        _ => Ok(()),
+    }
 }
 
 impl PreparedFilter {
-    fn from_query_filter(qf: &QueryFilter) -> Self {
+    /// `default` applies when the filter names no equality
+    fn from_query_filter(qf: &QueryFilter, default: Equality) -> Self {
         let regex = if qf.op == "regex" {
             qf.value.as_str().and_then(|p| regex::Regex::new(p).ok())
         } else {
             None
         };
+        let field_ref = match &qf.value {
+            Value::Object(map) if map.len() == 1 => map.get("$field").and_then(Value::as_str).map(split_field),
+            _ => None,
+        };
         
         PreparedFilter {
-            field: qf.field.clone(),
+            field: split_field(&qf.field),
             op: qf.op.clone(),
             value: qf.value.clone(),
             regex,
+            field_ref,
+            missing: MissingPolicy::parse(qf.missing.as_deref()),
+            equality: qf.equality.as_deref().and_then(Equality::parse).unwrap_or(default),
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        matches!(self.op.as_str(), "ne" | "notin")
+    }
+
+    /// Stand-in for an absent field, or the filter's verdict when there is none
+    fn absent(&self) -> std::result::Result<Cow<'static, Value>, bool> {
+        match self.missing {
+            MissingPolicy::Exclude => Err(false),
+            MissingPolicy::Match => Err(self.is_negative()),
+            MissingPolicy::AsNull => Ok(Cow::Borrowed(&NULL)),
         }
     }
 }
 
+fn split_field(path: &str) -> Vec<String> {
+    path.split('.').map(String::from).collect()
+}
+
+/// Follow pre-split dot-notation segments through objects and arrays,
+/// decompressing any compressed value on the way
+fn resolve_field<'a>(item: &'a Value, parts: &[String]) -> Option<Cow<'a, Value>> {
+    compress::step_all(Cow::Borrowed(item), parts)
+}
+
+use error::DbError;
+use archive::Archive;
+use reader::{ReaderHandle, ReaderSource};
 use fs_lock::{ProcessLock, LockMode};
-use wal::{GroupCommitWAL, WalConfig, WalOp, WalOpType, DurabilityMode, recover_from_wal};
+use undo::{Restore, UndoLimits, UndoLog};
+use coalesce::Coalescer;
+use counter::Counter;
+use upkeep::Upkeep;
+use padding::Padding;
+use trace::{Span, Tracer};
+use bulk::BulkLoad;
+use maintenance::Scheduler;
+use ttl::TtlIndexes;
+use value_limit::{Limit, LimitMode, ValueLimits};
+use freeze::Freezes;
+use canonical::Equality;
+use changes::ChangeLog;
+use quota::{Quota, QuotaPolicy};
+use constraint::Constraint;
+use recovery::{Recovery, Replayed};
+use persistence::{Persistence, SaveReport};
+use path_rules::PathRules;
+use throttle::{ThrottlePolicy, WriteLimiter};
+use lazy::{BaseView, Dirty, LazyCollections, LazyStore};
+use wal::{GroupCommitWAL, OverflowPolicy, WalConfig, WalOp, WalOpType, WalRecord, DurabilityMode, ReplayProgress, recover_from_wal, read_wal_records, compact_wal};
 
 // ============================================
 // THREAD POOL CONFIGURATION
@@ -106,6 +505,114 @@ impl ThreadPoolConfig {
     }
 }
 
+/// `NativeDB::validate_value` without a database instance
+#[napi]
+pub fn validate_value(value: Value, schema_json: String) -> Result<Value> {
+    let schema = schema::compile(&schema_json).map_err(DbError::InvalidArgument)?;
+    Ok(serde_json::to_value(schema::validate_all(&value, &schema)).unwrap_or_default())
+}
+
+/// A value the schema `schema_json` accepts, see sample.rs
+#[napi]
+pub fn generate_sample(schema_json: String) -> Result<Value> {
+    let schema = schema::compile(&schema_json).map_err(DbError::InvalidArgument)?;
+    Ok(sample::generate(&schema))
+}
+
+/// Deepest registered schema at or above `path`, with the path it is registered at
+fn governing_schema<'s>(schemas: &'s HashMap<String, CompiledSchema>, path: &str) -> Option<(&'s String, &'s CompiledSchema)> {
+    if schemas.is_empty() || path.is_empty() {
+        return None;
+    }
+    let parts: Vec<&str> = path.split('.').collect();
+    (1..=parts.len()).rev().find_map(|len| schemas.get_key_value(&parts[..len].join(".")))
+}
+
+/// Milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Whether `path` is `prefix` or lies under it; the root prefix "" covers every path
+fn path_within(path: &str, prefix: &str) -> bool {
+    prefix.is_empty() || path == prefix || (path.starts_with(prefix) && path.as_bytes().get(prefix.len()) == Some(&b'.'))
+}
+
+/// An array operation on something else; the root is always an object
+fn not_an_array(path: &str) -> DbError {
+    if path.is_empty() {
+        return DbError::NotAnArray("The root is an object, not an array".to_string());
+    }
+    DbError::NotAnArray(format!("Target is not an array: {}", path))
+}
+
+/// Whether one dot-notation path is equal to, an ancestor of, or a descendant of the other
+fn paths_overlap(a: &str, b: &str) -> bool {
+    if a.is_empty() || b.is_empty() || a == b {
+        return true;
+    }
+    let (short, long) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    long.starts_with(short) && long.as_bytes()[short.len()] == b'.'
+}
+
+/// An index range bound from an optional key; null leaves that end open
+fn index_bound(value: Option<&Value>, exclusive: Option<bool>) -> std::ops::Bound<&Value> {
+    use std::ops::Bound;
+    match value {
+        None | Some(Value::Null) => Bound::Unbounded,
+        Some(v) if exclusive.unwrap_or(false) => Bound::Excluded(v),
+        Some(v) => Bound::Included(v),
+    }
+}
+
+thread_local! {
+    /// Set while an `atomic_update` updater runs on this thread. The updater
+    /// runs with the write lock held, so a call back into the database from it
+    /// would deadlock; such calls fail instead.
+    static IN_UPDATER: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+thread_local! {
+    /// Transaction named by the write running on this thread, see `TxnScope`
+    static WRITE_TXN: std::cell::Cell<Option<u32>> = const { std::cell::Cell::new(None) };
+}
+
+/// Records the writes made on this thread in transaction `txn` until dropped,
+/// so undo entries recorded deep inside a write find the transaction its
+/// options named
+struct TxnScope(Option<u32>);
+
+impl TxnScope {
+    fn enter(txn: Option<u32>) -> Self {
+        TxnScope(WRITE_TXN.with(|current| current.replace(txn.or(current.get()))))
+    }
+}
+
+impl Drop for TxnScope {
+    fn drop(&mut self) {
+        WRITE_TXN.with(|current| current.set(self.0));
+    }
+}
+
+/// Marks the current thread as running an updater until dropped
+struct UpdaterScope;
+
+impl UpdaterScope {
+    fn enter() -> Self {
+        IN_UPDATER.with(|flag| flag.set(true));
+        UpdaterScope
+    }
+}
+
+impl Drop for UpdaterScope {
+    fn drop(&mut self) {
+        IN_UPDATER.with(|flag| flag.set(false));
+    }
+}
+
 // Global thread pool config (initialized once)
 static THREAD_CONFIG: once_cell::sync::Lazy<ThreadPoolConfig> = 
     once_cell::sync::Lazy::new(ThreadPoolConfig::new);
@@ -129,6 +636,15 @@ pub struct QueryFilter {
     pub field: String,
     pub op: String,   // "eq", "ne", "gt", "gte", "lt", "lte", "contains", "startswith", "endswith"
     pub value: Value,
+    /// Handling of documents without the field: "exclude" (default) never matches,
+    /// "match" matches for negative operators (`ne`, `notin`), "as_null" compares null
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing: Option<String>,
+    /// "strict" compares values as stored, "canonical" treats `1` and `1.0` as
+    /// equal (`eq`, `ne`, `in`, `notin`, `containsAll`, `containsAny`).
+    /// Default: the database's `set_equality` mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equality: Option<String>,
 }
 
 /// Batch query request
@@ -146,6 +662,20 @@ pub struct ParallelResult {
     pub success: bool,
     pub count: u32,
     pub error: Option<String>,
+    /// Values that failed schema validation: `[{index, path, error}]`
+    pub invalid: Option<Vec<Value>>,
+}
+
+/// Options for `batch_set_parallel`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct BatchSetOptions {
+    /// Validate values against registered schemas before writing (default: true)
+    pub validate: Option<bool>,
+    /// "reject" (default) fails the whole batch on any invalid value, "skip" writes the rest
+    pub on_invalid: Option<String>,
+    /// Transaction to record the writes in (see `WriteOptions::txn`)
+    pub txn: Option<u32>,
 }
 
 /// System resource info
@@ -157,84 +687,843 @@ pub struct SystemInfo {
     pub recommended_batch_size: u32,
 }
 
-/// Database options for v4.5
+/// Options accepted by write operations
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct WriteOptions {
+    /// Permit writes into the reserved `__jsondb__` namespace
+    pub allow_internal: Option<bool>,
+    /// Lease owner performing the write (checked when lease enforcement is on)
+    pub owner: Option<String>,
+    /// Why a delete happens: "explicit" (default), "ttl", "quota" or "cascade".
+    /// Reported to delete listeners and by `changes_since`.
+    pub reason: Option<String>,
+    /// Array padding for this write, overriding the database's:
+    /// "pad_null", "error" or "append"
+    pub array_padding: Option<String>,
+    /// Permit replacing or clearing the whole tree with the root path ""
+    pub allow_root_overwrite: Option<bool>,
+    /// Transaction the write belongs to, from `begin_transaction`
+    pub txn: Option<u32>,
+}
+
+/// A check evaluated by `atomic` against the value at `path`: any filter
+/// operator, or "exists" / "notexists" (which ignore `value`)
 #[derive(Debug, Clone)]
-pub struct DBOptions {
-    pub lock_mode: LockMode,
-    pub durability: DurabilityMode,
-    pub wal_batch_size: usize,
-    pub wal_flush_ms: u64,
+#[napi(object)]
+pub struct Condition {
+    pub path: String,
+    pub op: String,
+    pub value: Option<Value>,
+    /// "strict" or "canonical" (default: the database's `set_equality` mode)
+    pub equality: Option<String>,
 }
 
-impl Default for DBOptions {
-    fn default() -> Self {
-        DBOptions {
-            lock_mode: LockMode::Exclusive,
-            durability: DurabilityMode::Batched,
-            wal_batch_size: 1000,
-            wal_flush_ms: 10,
-        }
-    }
+/// One write applied by `atomic`: "set", "delete", "push" (skipped if the array
+/// already holds the value, like `push`), "add" or "subtract" (missing counts as 0)
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct BatchOp {
+    #[napi(js_name = "type")]
+    pub op_type: String,
+    pub path: String,
+    pub value: Option<Value>,
 }
 
-#[napi]
-pub struct NativeDB {
-    path: String,
-    wal_path: String,
-    data: Arc<PLRwLock<Value>>,
-    
-    // v4.5: Process-level file locking
-    #[allow(dead_code)]
-    process_lock: Option<ProcessLock>,
-    
-    // v4.5: Group commit WAL (replaces old WAL)
-    wal: Option<Arc<GroupCommitWAL>>,
-    
-    // v5.1 Persistent Indexes
-    indexes: Arc<PLRwLock<HashMap<String, BTreeIndex>>>,
+/// Options for `atomic`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct AtomicOptions {
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+}
 
-    // v5.1 Schema validation
-    schemas: Arc<PLRwLock<HashMap<String, Schema>>>,
+/// Options for `atomic_update` and `atomic_update_many`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct AtomicUpdateOptions {
+    /// Longest the updater may run; past it nothing is written and the call
+    /// fails with ERR_LIMIT (default: 1000)
+    pub max_callback_ms: Option<u32>,
+    /// Lease owner performing the write (checked when lease enforcement is on)
+    pub owner: Option<String>,
+}
 
-    // v5.1 Transactions
-    transaction_state: Arc<Mutex<Option<TransactionState>>>,
+/// Options for `set_write_rate_limit`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct WriteRateLimitOptions {
+    /// "block" (default) waits for tokens; "error" fails with ERR_THROTTLED
+    pub policy: Option<String>,
+    /// Longest a blocked write waits before failing with ERR_THROTTLED (default: 1000)
+    pub max_wait_ms: Option<u32>,
+}
 
-    // Options (kept for future use)
-    #[allow(dead_code)]
-    options: DBOptions,
+/// Options for `set_max_value_bytes`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct ValueLimitOptions {
+    /// Limit only values written under this path prefix (default: every path)
+    pub path_prefix: Option<String>,
+    /// "reject" (default) fails with ERR_LIMIT; "warn" writes the value and reports it to the trace callback
+    pub mode: Option<String>,
 }
 
-#[napi]
-impl NativeDB {
-    /// Legacy constructor for backwards compatibility
-    #[napi(constructor)]
-    pub fn new(path: String, wal: bool) -> Result<Self> {
-        let options = DBOptions {
-            lock_mode: LockMode::None,  // Legacy: no locking
-            durability: if wal { DurabilityMode::Batched } else { DurabilityMode::None },
-            wal_batch_size: 1000,
-            wal_flush_ms: 10,
-        };
-        
-        Self::new_with_options_internal(path, options)
+/// Options for `analyze`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct AnalyzeOptions {
+    /// Writes under the collection after which its stats count as stale
+    /// (default: a fifth of its entries, at least 1)
+    pub stale_after: Option<u32>,
+}
+
+/// Options for `freeze_path`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct FreezeOptions {
+    /// Keep the freeze in the reserved namespace so it survives a restart (default: false)
+    pub persist: Option<bool>,
+}
+
+/// Options for `export_archive`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct ExportOptions {
+    /// Replace an existing file at the output path (default: false)
+    pub overwrite: Option<bool>,
+    /// zstd-compress the entries (default: false)
+    pub compress: Option<bool>,
+}
+
+/// Thresholds for `advisor_report`; unset fields keep their defaults
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct AdvisorOptions {
+    /// How far back save history is considered (default: one hour)
+    pub window_ms: Option<i64>,
+    /// Size below which byte-based findings are not reported (default: 1MB)
+    pub min_bytes: Option<i64>,
+    /// Bytes rewritten by saves per byte of logged changes (default: 100)
+    pub save_amplification: Option<f64>,
+    /// WAL bytes per byte of data file growth (default: 50)
+    pub wal_growth: Option<f64>,
+    /// Share of the data file that is whitespace (default: 0.25)
+    pub pretty_overhead: Option<f64>,
+    /// Index size per byte of the indexed collection (default: 1)
+    pub index_ratio: Option<f64>,
+}
+
+/// Options for `on_delete`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct DeleteListenerOptions {
+    /// Include each removed value in its event (default: false; values can be large)
+    pub include_values: Option<bool>,
+}
+
+/// Options for `set_trace_callback`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct TraceOptions {
+    /// Size (approximate, in bytes) a `get` result must reach to be traced
+    /// (default 65536)
+    pub get_threshold_bytes: Option<i64>,
+}
+
+/// Options for `begin_transaction`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct TransactionOptions {
+    /// "read_uncommitted" (default): every read sees the transaction's writes
+    /// as they land. "exclusive": the transaction must be the only one open,
+    /// writes outside it fail with ERR_TRANSACTION_ACTIVE until it ends, and
+    /// `get`, `get_serialized` and `has` outside it (reader handles included)
+    /// see the data as it was when it began.
+    pub isolation: Option<String>,
+}
+
+/// Options for `begin_bulk_load`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct BulkLoadOptions {
+    /// Keep validating writes against registered schemas (default false)
+    pub keep_schemas: Option<bool>,
+}
+
+/// Options accepted by read operations
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct ReadOptions {
+    /// Include the reserved `__jsondb__` namespace in results
+    pub include_internal: Option<bool>,
+    /// Transaction the read is part of: it sees the writes of an exclusive
+    /// transaction that other reads do not
+    pub txn: Option<u32>,
+}
+
+/// Contiguous block of sequence values handed out by `reserve_sequence` (inclusive)
+#[derive(Debug)]
+#[napi(object)]
+pub struct SequenceRange {
+    pub start: i64,
+    pub end: i64,
+}
+
+/// Options for `delete_prefix`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct DeletePrefixOptions {
+    /// Only count what would be removed
+    pub dry_run: Option<bool>,
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+    /// Transaction to record the removal in (see `WriteOptions::txn`)
+    pub txn: Option<u32>,
+}
+
+/// Result of `transaction_info`
+#[derive(Debug)]
+#[napi(object)]
+pub struct TransactionInfo {
+    pub active: bool,
+    /// Id of the transaction described
+    pub id: Option<u32>,
+    /// "read_uncommitted" or "exclusive"
+    pub isolation: Option<String>,
+    /// Transactions open in this database
+    pub open: u32,
+    /// Undo entries recorded (at most one per path per savepoint)
+    pub entries: u32,
+    /// Approximate bytes of old values held in memory
+    pub undo_bytes: i64,
+    /// Bytes of old values spilled to the temp file
+    pub spilled_bytes: i64,
+    pub savepoints: u32,
+    pub max_undo_bytes: Option<i64>,
+}
+
+/// Options for `merge_three_way`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct MergeOptions {
+    /// Conflict policy: "report" (default) leaves conflicts untouched, "ours" keeps
+    /// the current value, "theirs" takes the incoming value
+    pub policy: Option<String>,
+}
+
+/// Outcome of a three-way merge walk
+#[derive(Default)]
+struct MergeOutcome {
+    applied: u32,
+    resolved: u32,
+    conflicts: Vec<Value>,
+}
+
+/// Three-way merge of `theirs` into `ours`, both derived from `base`. Objects are
+/// merged key by key; everything else (including arrays) is merged as a whole value.
+/// `None` means the key is absent.
+fn merge_values(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &str,
+    policy: &str,
+    out: &mut MergeOutcome,
+) -> Option<Value> {
+    if theirs == base || ours == theirs {
+        return ours.cloned();
     }
-    
-    /// Internal constructor with full options
-    fn new_with_options_internal(path: String, options: DBOptions) -> Result<Self> {
-        // 1. Acquire process lock if requested
-        let process_lock = match options.lock_mode {
-            LockMode::Exclusive => {
-                match ProcessLock::acquire(&path) {
-                    Ok(lock) => Some(lock),
-                    Err(e) => return Err(Error::from_reason(format!("Failed to acquire lock: {}", e))),
-                }
+    if ours == base {
+        out.applied += 1;
+        return theirs.cloned();
+    }
+
+    if let (Some(Value::Object(b)), Some(Value::Object(o)), Some(Value::Object(t))) = (base, ours, theirs) {
+        let mut merged = o.clone();
+        let keys: Vec<&String> = b.keys().chain(t.keys().filter(|k| !b.contains_key(*k))).collect();
+        for key in keys {
+            let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            match merge_values(b.get(key), o.get(key), t.get(key), &child, policy, out) {
+                Some(v) => { merged.insert(key.clone(), v); }
+                None => { merged.shift_remove(key); }
             }
-            LockMode::Shared => {
-                // Check if locked, but don't acquire
-                match ProcessLock::is_locked(&path) {
-                    Ok(true) => return Err(Error::from_reason("Database is locked by another process".to_string())),
-                    Ok(false) => None,
-                    Err(_) => None, // If we can't check, proceed anyway
+        }
+        return Some(Value::Object(merged));
+    }
+
+    match policy {
+        "theirs" => {
+            out.resolved += 1;
+            theirs.cloned()
+        }
+        "ours" => {
+            out.resolved += 1;
+            ours.cloned()
+        }
+        _ => {
+            out.conflicts.push(json!({
+                "path": path,
+                "base": base.cloned().unwrap_or(Value::Null),
+                "ours": ours.cloned().unwrap_or(Value::Null),
+                "theirs": theirs.cloned().unwrap_or(Value::Null),
+            }));
+            ours.cloned()
+        }
+    }
+}
+
+/// Options for `rename_field`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct RenameFieldOptions {
+    /// Overwrite the target field when it already exists (default: skip the document)
+    pub overwrite: Option<bool>,
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+    /// Transaction to record the renames in (see `WriteOptions::txn`)
+    pub txn: Option<u32>,
+}
+
+/// Result of a `rename_field` migration
+#[derive(Debug)]
+#[napi(object)]
+pub struct RenameFieldResult {
+    pub changed: u32,
+    pub skipped: u32,
+    /// Registered indexes on the old field name that need re-registering/reindexing
+    pub affected_indexes: Vec<String>,
+}
+
+/// Where `insert_before` / `insert_after` / `move_key` placed a key
+#[derive(Debug)]
+#[napi(object)]
+pub struct KeyPlacement {
+    pub key: String,
+    /// Index of the key in its collection's key order
+    pub position: u32,
+    /// The reference key was missing, so the key went last
+    pub appended: bool,
+}
+
+/// Options for `map_collection`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct MapCollectionOptions {
+    /// Return the would-be changes instead of applying them
+    pub dry_run: Option<bool>,
+    /// Documents handed to the mapper per read/write lock cycle (default: 500)
+    pub batch_size: Option<u32>,
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+}
+
+/// Options for `for_each`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct ForEachOptions {
+    /// Documents cloned per read-lock hold (default: 500)
+    pub batch_size: Option<u32>,
+    /// "sync" (default) calls back on the calling thread and returns the result;
+    /// "threadsafe" reads on a worker thread and returns a Promise of it
+    pub mode: Option<String>,
+}
+
+/// Options for `wal_inspect`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct WalInspectOptions {
+    /// Only records with a greater LSN
+    pub from_lsn: Option<i64>,
+    /// At most this many records (oldest first)
+    pub limit: Option<u32>,
+    /// Include each record's value (default: false)
+    pub include_values: Option<bool>,
+}
+
+/// Options for `parallel_query` / `snapshot_query`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct QueryOptions {
+    /// "values" (default) returns matching documents, "entries" returns `[{key, value}]`,
+    /// "object" returns matches under their original keys. Array keys are indices.
+    /// "indexed" returns `[{index, value}]` with numeric indexes and needs an array collection.
+    pub emit: Option<String>,
+}
+
+/// Options for `parallel_query_multi`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct MultiQueryOptions {
+    /// Add the path of its collection to each matching object as `_source`
+    pub tag_source: Option<bool>,
+    /// Field (dot notation) to sort all matches by; collection order otherwise
+    pub sort_by: Option<String>,
+    pub descending: Option<bool>,
+    /// Matches to skip after the sort
+    pub offset: Option<u32>,
+    /// Most matches to return after the sort
+    pub limit: Option<u32>,
+}
+
+/// Options for `get_page`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct PageOptions {
+    /// "key" (default) walks documents by key; anything else names a field to
+    /// walk by, with the key breaking ties
+    pub stable_by: Option<String>,
+    /// The `next` cursor of the previous page
+    pub after: Option<String>,
+    /// Documents per page (default 100)
+    pub limit: Option<u32>,
+    /// Only documents matching every filter
+    pub filters: Option<Vec<QueryFilter>>,
+}
+
+/// Options for `update_many`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct UpdateManyOptions {
+    /// "merge" (default) shallow-merges the patch into object documents, "replace" swaps them
+    pub mode: Option<String>,
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+    /// Transaction to record the writes in (see `WriteOptions::txn`)
+    pub txn: Option<u32>,
+}
+
+/// Options for `delete_many`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct DeleteManyOptions {
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+    /// Transaction to record the removals in (see `WriteOptions::txn`)
+    pub txn: Option<u32>,
+}
+
+/// Options for `upsert_many`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct UpsertOptions {
+    /// "replace" (default) swaps the whole document, "merge" shallow-merges into it
+    pub mode: Option<String>,
+    /// Validate documents against registered schemas (default: true)
+    pub validate: Option<bool>,
+    /// Documents written per write-lock hold (default: 1000)
+    pub batch_size: Option<u32>,
+    /// Lease owner, checked when lease enforcement is on
+    pub owner: Option<String>,
+}
+
+/// Grouping for `index_aggregate`
+enum IndexBucket {
+    Prefix(usize),
+    Interval(f64),
+}
+
+impl IndexBucket {
+    fn parse(spec: &str) -> Result<Self> {
+        let invalid = || DbError::InvalidArgument(format!("Invalid bucket '{}': use 'prefix:N' or 'interval:N'", spec));
+        let (kind, size) = spec.split_once(':').ok_or_else(invalid)?;
+        match kind {
+            "prefix" => size.parse::<usize>().ok().filter(|n| *n > 0).map(IndexBucket::Prefix).ok_or_else(invalid),
+            "interval" => size.parse::<f64>().ok().filter(|n| *n > 0.0).map(IndexBucket::Interval).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }.map_err(Into::into)
+    }
+
+    /// Bucket a key falls into; numeric buckets skip keys that aren't numbers
+    fn label(&self, key: &str) -> Option<Value> {
+        match self {
+            IndexBucket::Prefix(n) => Some(Value::String(key.chars().take(*n).collect())),
+            IndexBucket::Interval(size) => {
+                let floor = (key.parse::<f64>().ok()? / size).floor() * size;
+                Some(serde_json::Number::from_f64(floor).map(Value::Number).unwrap_or(Value::Null))
+            }
+        }
+    }
+}
+
+/// Options for `index_keys`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct IndexKeysOptions {
+    /// Lowest key returned (null for no lower bound); compared as in `find_index_range`
+    pub start: Option<Value>,
+    /// Highest key returned (null for no upper bound)
+    pub end: Option<Value>,
+    /// Leave out a key equal to `start`
+    pub start_exclusive: Option<bool>,
+    /// Leave out a key equal to `end`
+    pub end_exclusive: Option<bool>,
+    /// Only keys starting with this string
+    pub prefix: Option<String>,
+    /// Keys to skip (after `prefix` and ordering are applied)
+    pub offset: Option<u32>,
+    /// Maximum number of keys returned
+    pub limit: Option<u32>,
+    /// Descending order; with `limit: 1` this is the last (largest) key
+    pub reverse: Option<bool>,
+}
+
+/// Options for `register_index`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct IndexOptions {
+    /// Leave out documents whose field is null, as well as those without it
+    pub sparse: Option<bool>,
+}
+
+/// Options for `register_schema`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct SchemaOptions {
+    /// Fill in the schema's `default` values for missing properties on `set` (default: false)
+    pub apply_defaults: Option<bool>,
+}
+
+/// Options for `find_index_range`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct IndexRangeOptions {
+    /// Leave out keys equal to `start`
+    pub start_exclusive: Option<bool>,
+    /// Leave out keys equal to `end`
+    pub end_exclusive: Option<bool>,
+    /// Maximum number of paths returned
+    pub limit: Option<u32>,
+}
+
+/// Result of `build_index`
+#[derive(Debug)]
+#[napi(object)]
+pub struct BuildIndexResult {
+    /// Documents indexed
+    pub indexed: u32,
+    /// Documents without the indexed field, left out
+    pub skipped: u32,
+    /// Entries the index held before the build
+    pub cleared: u32,
+}
+
+/// Options for `scan_anomalies`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct ScanAnomaliesOptions {
+    /// Findings listed per kind; all are counted (default: 100)
+    pub limit: Option<u32>,
+    /// Levels walked below the scanned path before reporting deep nesting
+    /// (default: the path depth limit)
+    pub max_depth: Option<u32>,
+    /// Remove the trailing nulls of every array that has them, as logged writes
+    pub fix_trailing_nulls: Option<bool>,
+}
+
+/// Options for `register_custom_index`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct CustomIndexOptions {
+    /// What an extractor throw does: "skip" (default) leaves the document out of
+    /// the index; "reject" also vets each write beforehand and fails it
+    pub on_error: Option<String>,
+    /// Rebuild even when entries were loaded from the index file
+    pub rebuild: Option<bool>,
+}
+
+/// Options for `import_file`
+#[derive(Debug, Clone, Default)]
+#[napi(object)]
+pub struct ImportOptions {
+    /// Existing target keys: "error" (default, nothing is imported), "skip" or "overwrite"
+    pub on_duplicate: Option<String>,
+    /// Skip items that fail a registered schema (default: true)
+    pub validate: Option<bool>,
+    /// Build the report without writing anything
+    pub dry_run: Option<bool>,
+}
+
+/// One `import_file` mapping rule
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportRule {
+    from: String,
+    to: String,
+    #[serde(alias = "key_field")]
+    key_field: Option<String>,
+}
+
+/// Database options for v4.5
+#[derive(Debug, Clone)]
+pub struct DBOptions {
+    pub lock_mode: LockMode,
+    pub durability: DurabilityMode,
+    pub wal_batch_size: usize,
+    pub wal_flush_ms: u64,
+    /// Cap on old values held by a transaction's undo log (None = unlimited)
+    pub max_undo_bytes: Option<usize>,
+    /// Spill undo entries past `max_undo_bytes` to a temp file instead of failing
+    pub undo_spill: bool,
+    /// Extra attempts to read a main file that fails to parse (e.g. mid-save by another process)
+    pub open_retries: u32,
+    /// Load only the base file; the WAL is replayed by an explicit `recover`
+    pub defer_recovery: bool,
+    /// Writes queued for the WAL commit thread before the overflow policy applies
+    pub wal_queue_capacity: usize,
+    pub wal_overflow_policy: OverflowPolicy,
+    /// Depth, segment length and character limits on written paths
+    pub path_rules: PathRules,
+    /// Apply WAL replay in parallel across top-level keys (None: only large replays)
+    pub parallel_recovery: Option<bool>,
+    /// Keep truncated WAL and a base checkpoint per save for `value_at`
+    pub wal_archive: bool,
+    /// Top-level collections stored outside the base file and loaded per entry
+    pub lazy_collections: Vec<String>,
+    /// Entries of lazy collections kept in memory before the least recently used are dropped
+    pub lazy_max_resident: usize,
+    /// Validate `set`, `set_pointers` and `atomic` writes against registered
+    /// schemas, and batches even when told not to (`push` always validates)
+    pub enforce_schemas: bool,
+}
+
+impl Default for DBOptions {
+    fn default() -> Self {
+        DBOptions {
+            lock_mode: LockMode::Exclusive,
+            durability: DurabilityMode::Batched,
+            wal_batch_size: 1000,
+            wal_flush_ms: 10,
+            max_undo_bytes: None,
+            undo_spill: false,
+            open_retries: 3,
+            defer_recovery: false,
+            wal_queue_capacity: 100000,
+            wal_overflow_policy: OverflowPolicy::Block,
+            path_rules: PathRules::default(),
+            parallel_recovery: None,
+            wal_archive: false,
+            lazy_collections: Vec::new(),
+            lazy_max_resident: 10000,
+            enforce_schemas: false,
+        }
+    }
+}
+
+/// A database handle. The maintenance and TTL threads hold handles of their
+/// own on the same state; only the primary one stops them when dropped.
+#[napi]
+pub struct NativeDB {
+    state: Arc<DbState>,
+    /// False for the handles background threads hold
+    primary: bool,
+}
+
+impl std::ops::Deref for NativeDB {
+    type Target = DbState;
+
+    fn deref(&self) -> &DbState {
+        &self.state
+    }
+}
+
+/// Everything a database handle reads and writes
+pub struct DbState {
+    path: String,
+    wal_path: String,
+    data: Arc<PLRwLock<Value>>,
+    
+    // v4.5: Process-level file locking
+    process_lock: Mutex<Option<ProcessLock>>,
+    
+    // v4.5: Group commit WAL (replaces old WAL)
+    wal: Option<Arc<GroupCommitWAL>>,
+    
+    // v5.1 Persistent Indexes
+    indexes: Arc<PLRwLock<HashMap<String, BTreeIndex>>>,
+
+    // v5.1 Schema validation
+    schemas: Arc<PLRwLock<HashMap<String, CompiledSchema>>>,
+    /// Schema paths registered with `apply_defaults`
+    schema_defaults: PLRwLock<std::collections::HashSet<String>>,
+
+    // v5.1 Transactions
+    transactions: Arc<Mutex<Transactions>>,
+
+    // Point-in-time read-only snapshots
+    snapshots: Mutex<SnapshotTable>,
+
+    // Per-path durability overrides (prefix -> mode)
+    path_durability: PLRwLock<HashMap<String, DurabilityMode>>,
+
+    // Reject writes under paths leased by another owner
+    enforce_leases: AtomicBool,
+
+    // Evaluate malformed filters as before validation (unknown operators match everything)
+    permissive_filters: AtomicBool,
+    // How filters, push dedupe and atomic conditions compare values by default
+    canonical_equality: AtomicBool,
+
+    // Largest result (by approx_size) converted to a JS value; 0 = unlimited
+    max_result_bytes: AtomicUsize,
+
+    // Views marked stale by writes under their watched path
+    view_watches: PLRwLock<HashMap<String, ViewWatch>>,
+
+    // Latest-value staging for writes under coalesced prefixes
+    coalescer: Mutex<Option<Coalescer>>,
+
+    // Strings at least this long are stored compressed by `set`; 0 = off
+    compress_threshold: AtomicUsize,
+
+    // What writes to an array index past the end do (a `Padding`)
+    array_padding: AtomicU8,
+
+    // Deletes removing at least this many bytes compact the parent; 0 = off
+    reclaim_threshold: AtomicUsize,
+
+    // Maintained filtered counts, by name
+    counters: Mutex<HashMap<String, Counter>>,
+
+    // Entry/size limits, by collection path
+    quotas: Mutex<HashMap<String, Quota>>,
+
+    // Uniqueness constraints, by name
+    constraints: Mutex<HashMap<String, Constraint>>,
+
+    // Writes under each analyzed collection since its stats were taken
+    stats_writes: Mutex<HashMap<String, u64>>,
+
+    // Indexes keyed by a JS extractor, by index name
+    custom_indexes: Mutex<HashMap<String, custom_index::CustomIndex>>,
+
+    // Writes not yet applied to the indexes registered with a collection, by index name
+    index_upkeep: Arc<Mutex<HashMap<String, Upkeep>>>,
+
+    // What the constructor (and a deferred recovery) observed while loading
+    load_report: Mutex<LoadReport>,
+
+    // State of a deferred WAL replay
+    recovery: Arc<Recovery>,
+
+    // Attached to every WAL record until cleared
+    write_context: PLRwLock<Option<Value>>,
+
+    // Receives an event for every removal (see on_delete)
+    delete_listener: PLRwLock<Option<DeleteListener>>,
+
+    // Receives a span per significant operation (see set_trace_callback);
+    // `tracing` mirrors whether one is registered, for a lock-free check
+    tracer: PLRwLock<Option<Arc<Tracer>>>,
+    tracing: AtomicBool,
+
+    // The bulk load in progress (see begin_bulk_load); `bulk_loading` mirrors
+    // whether there is one, for a lock-free check on every write
+    bulk_load: PLRwLock<Option<Arc<BulkLoad>>>,
+    bulk_loading: AtomicBool,
+
+    // Save history for persistence_status
+    persistence: Mutex<Persistence>,
+
+    // Registered by reader_token, unregistered on close
+    reader_token: Mutex<Option<u32>>,
+
+    // Set by close; every call but a few status ones then fails with ERR_CLOSED
+    closed: AtomicBool,
+
+    // Writes logged so far, and how many of them the last save started after
+    writes: AtomicU64,
+    saved_writes: AtomicU64,
+
+    // Background thread running scheduled maintenance tasks, if any
+    maintenance: Mutex<Option<Scheduler>>,
+
+    // Token bucket checked by every mutating method; off until configured
+    write_limiter: WriteLimiter,
+
+    // Largest serialized value a write may store, per path prefix; off until configured
+    value_limits: ValueLimits,
+
+    // Most WAL records a save keeps for consumers that have not acked them
+    change_retention: AtomicUsize,
+
+    // Path prefixes refusing writes, e.g. for a migration window
+    freezes: Freezes,
+
+    // Collections expiring documents by a timestamp field, and the thread sweeping them
+    ttl: TtlIndexes,
+    ttl_sweeper: Mutex<Option<Scheduler>>,
+
+    // Collections loaded per entry on demand (shared with readers)
+    lazy: Option<Arc<LazyCollections>>,
+
+    options: DBOptions,
+}
+
+impl Drop for NativeDB {
+    /// A primary collected without close() must not keep its reader token alive,
+    /// nor a maintenance thread running against it
+    fn drop(&mut self) {
+        if !self.primary {
+            return;
+        }
+        self.maintenance.lock().take();
+        self.ttl_sweeper.lock().take();
+        if let Some(token) = self.reader_token.lock().take() {
+            reader::unregister(token);
+        }
+    }
+}
+
+impl From<DbState> for NativeDB {
+    fn from(state: DbState) -> Self {
+        NativeDB { state: Arc::new(state), primary: true }
+    }
+}
+
+#[napi]
+impl NativeDB {
+    /// Legacy constructor for backwards compatibility
+    #[napi(constructor)]
+    pub fn new(path: String, wal: bool) -> Result<Self> {
+        let options = DBOptions {
+            lock_mode: LockMode::None,  // Legacy: no locking
+            durability: if wal { DurabilityMode::Batched } else { DurabilityMode::None },
+            wal_batch_size: 1000,
+            wal_flush_ms: 10,
+            max_undo_bytes: None,
+            undo_spill: false,
+            open_retries: 3,
+            defer_recovery: false,
+            wal_queue_capacity: 100000,
+            wal_overflow_policy: OverflowPolicy::Block,
+            path_rules: PathRules::default(),
+            parallel_recovery: None,
+            wal_archive: false,
+            lazy_collections: Vec::new(),
+            lazy_max_resident: 10000,
+            enforce_schemas: false,
+        };
+        
+        Self::new_with_options_internal(path, options)
+    }
+    
+    /// Internal constructor with full options
+    fn new_with_options_internal(path: String, options: DBOptions) -> Result<Self> {
+        // 1. Acquire process lock if requested
+        let process_lock = match options.lock_mode {
+            LockMode::Exclusive => {
+                match ProcessLock::acquire(&path) {
+                    Ok(lock) => Some(lock),
+                    Err(e) => return Err(DbError::from(e).into()),
+                }
+            }
+            LockMode::Shared => {
+                // Check if locked, but don't acquire
+                match ProcessLock::is_locked(&path) {
+                    Ok(true) => return Err(DbError::LockContended("Database is locked by another process".to_string()).into()),
+                    Ok(false) => None,
+                    Err(_) => None, // If we can't check, proceed anyway
                 }
             }
             LockMode::None => None,
@@ -247,10 +1536,16 @@ impl NativeDB {
                 batch_size: options.wal_batch_size,
                 flush_interval_ms: options.wal_flush_ms,
                 fsync: config.fsync,
+                queue_capacity: options.wal_queue_capacity,
+                overflow_policy: options.wal_overflow_policy,
             };
             match GroupCommitWAL::new(&wal_path, wal_config) {
-                Ok(w) => Some(Arc::new(w)),
-                Err(e) => return Err(Error::from_reason(format!("Failed to create WAL: {}", e))),
+                Ok(w) => {
+                    // Continue numbering after every LSN a consumer may have seen
+                    w.resume_after(ChangeLog::new(&path).last_lsn().max(wal::last_lsn(&wal_path)));
+                    Some(Arc::new(w))
+                }
+                Err(e) => return Err(DbError::WalFailure(format!("Failed to create WAL: {}", e)).into()),
             }
         } else {
             None
@@ -258,58 +1553,311 @@ impl NativeDB {
         
         // 3. Load existing data or start fresh
         let mut data = json!({});
+        let mut load_report = LoadReport::default();
         
         let p = PathBuf::from(&path);
         if p.exists() {
-            // Load main DB
-            let contents = fs::read_to_string(&p).map_err(|e| {
-                Error::from_reason(format!("Failed to read database: {}", e))
-            })?;
-            
-            data = serde_json::from_str(&contents).map_err(|e| {
-                Error::from_reason(format!("Failed to parse database: {}", e))
-            })?;
+            load_report.file_exists = true;
+
+            // Another process may be mid-save: retry with backoff before giving up
+            let mut attempt = 0;
+            let loaded = loop {
+                attempt += 1;
+                match Self::read_data_file(&p) {
+                    Ok(loaded) => break Ok(loaded),
+                    Err(e) if attempt > options.open_retries => break Err(e),
+                    Err(_) => std::thread::sleep(std::time::Duration::from_millis(25 << (attempt - 1).min(6))),
+                }
+            };
+            load_report.read_attempts = attempt;
+
+            let (loaded, size) = match loaded {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    // Main file stayed unreadable: fall back to the newest backup
+                    let backup = Self::newest_backup(&path)
+                        .and_then(|b| Self::read_data_file(Path::new(&b)).ok().map(|loaded| (b, loaded)));
+                    let Some((backup, loaded)) = backup else { return Err(err.into()) };
+                    eprintln!("Database file unreadable ({}), loaded backup {}", err, backup);
+                    load_report.main_file_error = Some(err.to_string());
+                    load_report.backup_used = Some(backup);
+                    loaded
+                }
+            };
+            data = loaded;
+            load_report.file_size = size;
+
+            // A user key squatting on the reserved namespace is moved aside, never merged
+            if let Some(relocated) = meta::migrate_collision(&mut data) {
+                eprintln!("Relocated user key '{}' to '{}'", meta::META_KEY, relocated);
+                load_report.relocated_key = Some(relocated);
+            }
         }
         
-        // 4. Recover from WAL
-        if wal.is_some() {
-            let _ = recover_from_wal(&wal_path, &mut data);
+        // 4. Lazy collections: only their indexes are read now
+        let lazy = if options.lazy_collections.is_empty() {
+            None
         } else {
-            // Legacy WAL recovery
-            let legacy_wal = format!("{}.wal", path);
-            let wal_p = PathBuf::from(&legacy_wal);
-            if wal_p.exists() {
-                let _ = Self::recover_legacy_wal(&legacy_wal, &mut data);
-            }
+            let mut dirty = Dirty::default();
+            let store = LazyStore::open(&path, &options.lazy_collections, options.lazy_max_resident, &mut data, &mut dirty)?;
+            Some(Arc::new(LazyCollections { names: options.lazy_collections.clone(), store: Mutex::new(store), dirty: Mutex::new(dirty) }))
+        };
+
+        // 5. Recover from WAL
+        if !options.defer_recovery {
+            load_report.replay = Self::replay(&wal_path, wal.is_some(), &mut data, lazy.as_deref(), &options, &mut |_| {});
         }
         
-        Ok(NativeDB {
+        load_report.newer_tmp = Self::newer_tmp_file(&path);
+        let view_watches = Self::load_view_watches(&data);
+        // Replayed writes are not reflected in the persisted counter state
+        let counters = Self::load_counters(&data, load_report.replay.changed());
+        let quotas = Self::load_quotas(&data);
+        let stats_writes = Self::load_stats_writes(&data);
+        let constraints = Self::load_constraints(&data);
+        let freezes = Freezes::default();
+        freezes.load(&Self::load_frozen(&data));
+        let resumed_lsn = wal.as_ref().map_or(0, |w| w.committed_lsn());
+
+        Ok(NativeDB::from(DbState {
             path,
             wal_path,
             data: Arc::new(PLRwLock::new(data)),
-            process_lock,
+            process_lock: Mutex::new(process_lock),
             wal,
             indexes: Arc::new(PLRwLock::new(HashMap::new())),
             schemas: Arc::new(PLRwLock::new(HashMap::new())),
-            transaction_state: Arc::new(Mutex::new(None)),
+            schema_defaults: PLRwLock::default(),
+            transactions: Arc::new(Mutex::new(Transactions::default())),
+            snapshots: Mutex::new(SnapshotTable::new()),
+            path_durability: PLRwLock::new(HashMap::new()),
+            enforce_leases: AtomicBool::new(false),
+            permissive_filters: AtomicBool::new(false),
+            canonical_equality: AtomicBool::new(false),
+            max_result_bytes: AtomicUsize::new(DEFAULT_MAX_RESULT_BYTES),
+            view_watches: PLRwLock::new(view_watches),
+            coalescer: Mutex::new(None),
+            compress_threshold: AtomicUsize::new(0),
+            array_padding: AtomicU8::new(Padding::PadNull.to_u8()),
+            reclaim_threshold: AtomicUsize::new(0),
+            counters: Mutex::new(counters),
+            quotas: Mutex::new(quotas),
+            stats_writes: Mutex::new(stats_writes),
+            constraints: Mutex::new(constraints),
+            custom_indexes: Mutex::new(HashMap::new()),
+            index_upkeep: Arc::new(Mutex::new(HashMap::new())),
+            load_report: Mutex::new(load_report),
+            recovery: Arc::new(Recovery::new(options.defer_recovery)),
+            write_context: PLRwLock::new(None),
+            delete_listener: PLRwLock::new(None),
+            tracer: PLRwLock::new(None),
+            tracing: AtomicBool::new(false),
+            bulk_load: PLRwLock::new(None),
+            bulk_loading: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            writes: AtomicU64::new(0),
+            saved_writes: AtomicU64::new(0),
+            maintenance: Mutex::new(None),
+            persistence: Mutex::new(Persistence {
+                truncated_lsn: resumed_lsn,
+                ..Default::default()
+            }),
+            reader_token: Mutex::new(None),
+            write_limiter: WriteLimiter::default(),
+            value_limits: ValueLimits::default(),
+            change_retention: AtomicUsize::new(changes::DEFAULT_MAX_RECORDS),
+            freezes,
+            ttl: TtlIndexes::default(),
+            ttl_sweeper: Mutex::new(None),
+            lazy,
             options,
+        }))
+    }
+
+    /// Read-only handle state sharing a primary's data and indexes: no WAL, no
+    /// lock, its own (empty) transaction, snapshot and staging state
+    fn reader(source: &ReaderSource) -> NativeDB {
+        let data = source.data.read();
+        let view_watches = Self::load_view_watches(&data);
+        drop(data);
+        NativeDB::from(DbState {
+            path: source.path.clone(),
+            wal_path: format!("{}.wal", source.path),
+            data: Arc::clone(&source.data),
+            process_lock: Mutex::new(None),
+            wal: None,
+            indexes: Arc::clone(&source.indexes),
+            schemas: Arc::clone(&source.schemas),
+            schema_defaults: PLRwLock::default(),
+            transactions: Arc::clone(&source.transactions),
+            snapshots: Mutex::new(SnapshotTable::new()),
+            path_durability: PLRwLock::new(HashMap::new()),
+            enforce_leases: AtomicBool::new(false),
+            permissive_filters: AtomicBool::new(false),
+            canonical_equality: AtomicBool::new(source.equality == Equality::Canonical),
+            max_result_bytes: AtomicUsize::new(source.max_result_bytes),
+            view_watches: PLRwLock::new(view_watches),
+            coalescer: Mutex::new(None),
+            compress_threshold: AtomicUsize::new(0),
+            array_padding: AtomicU8::new(Padding::PadNull.to_u8()),
+            reclaim_threshold: AtomicUsize::new(0),
+            counters: Mutex::new(HashMap::new()),
+            quotas: Mutex::new(HashMap::new()),
+            stats_writes: Mutex::new(HashMap::new()),
+            constraints: Mutex::new(HashMap::new()),
+            custom_indexes: Mutex::new(HashMap::new()),
+            index_upkeep: Arc::clone(&source.index_upkeep),
+            load_report: Mutex::new(LoadReport::default()),
+            recovery: Arc::new(Recovery::new(false)),
+            write_context: PLRwLock::new(None),
+            delete_listener: PLRwLock::new(None),
+            tracer: PLRwLock::new(None),
+            tracing: AtomicBool::new(false),
+            bulk_load: PLRwLock::new(None),
+            bulk_loading: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            writes: AtomicU64::new(0),
+            saved_writes: AtomicU64::new(0),
+            maintenance: Mutex::new(None),
+            persistence: Mutex::new(Persistence::default()),
+            reader_token: Mutex::new(None),
+            write_limiter: WriteLimiter::default(),
+            value_limits: ValueLimits::default(),
+            change_retention: AtomicUsize::new(changes::DEFAULT_MAX_RECORDS),
+            freezes: Freezes::default(),
+            ttl: TtlIndexes::default(),
+            ttl_sweeper: Mutex::new(None),
+            lazy: source.lazy.clone(),
+            options: source.options.clone(),
+        })
+    }
+
+    /// Another handle on this database, for a background thread: dropping it
+    /// stops nothing
+    fn handle(&self) -> NativeDB {
+        NativeDB { state: Arc::clone(&self.state), primary: false }
+    }
+
+    fn reader_source(&self) -> Result<ReaderSource> {
+        // A reader must not be the one to install a deferred replay
+        self.check_recovered()?;
+        Ok(ReaderSource {
+            path: self.path.clone(),
+            data: Arc::clone(&self.data),
+            indexes: Arc::clone(&self.indexes),
+            index_upkeep: Arc::clone(&self.index_upkeep),
+            schemas: Arc::clone(&self.schemas),
+            options: self.options.clone(),
+            max_result_bytes: self.max_result_bytes.load(Ordering::Relaxed),
+            equality: self.equality(),
+            lazy: self.lazy.clone(),
+            transactions: Arc::clone(&self.transactions),
         })
     }
     
+    /// Read and parse a database file, returning the data and its size
+    fn read_data_file(path: &Path) -> std::result::Result<(Value, u64), DbError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| DbError::Io(format!("Failed to read database: {}", e)))?;
+        let data = serde_json::from_str(&contents)
+            .map_err(|e| DbError::Corrupt(format!("Failed to parse database: {}", e)))?;
+        Ok((data, contents.len() as u64))
+    }
+
+    /// Most recent `<db>.bak` or `<db>.<name>.<ts>.bak` snapshot next to the database
+    fn newest_backup(path: &str) -> Option<String> {
+        Self::backup_files(path, true).into_iter()
+            .max_by_key(|(modified, _)| *modified)
+            .map(|(_, p)| p.to_string_lossy().into_owned())
+    }
+
+    /// `<db>.<name>.<ts>.bak` snapshots next to the database with their
+    /// modification times, and `<db>.bak` too if `plain`
+    fn backup_files(path: &str, plain: bool) -> Vec<(std::time::SystemTime, PathBuf)> {
+        let db_path = Path::new(path);
+        let Some(file_name) = db_path.file_name().and_then(|n| n.to_str()) else { return Vec::new() };
+        let dir = match db_path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d,
+            _ => Path::new("."),
+        };
+        let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_str().is_some_and(|n| {
+                n.starts_with(file_name) && n.ends_with(".bak")
+                    && if n.len() == file_name.len() + 4 { plain } else { n[file_name.len()..].starts_with('.') }
+            }))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect()
+    }
+
+    /// `<db>.tmp` if it parses and is newer than the main file (or the main file is missing)
+    fn newer_tmp_file(path: &str) -> Option<String> {
+        let tmp = format!("{}.tmp", path);
+        let tmp_modified = fs::metadata(&tmp).ok()?.modified().ok()?;
+        let newer = match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(main_modified) => tmp_modified > main_modified,
+            Err(_) => true,
+        };
+        if !newer {
+            return None;
+        }
+        let file = File::open(&tmp).ok()?;
+        serde_json::from_reader::<_, serde::de::IgnoredAny>(BufReader::new(file)).ok()?;
+        Some(tmp)
+    }
+
     /// v4.5: Create database with options from JS
     #[napi(js_name = "newWithOptions")]
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_options_js(
         path: String,
         lock_mode: String,
         durability: String,
         wal_batch_size: Option<u32>,
         wal_flush_ms: Option<u32>,
+        max_undo_bytes: Option<i64>,
+        undo_spill: Option<bool>,
+        open_retries: Option<u32>,
+        defer_recovery: Option<bool>,
+        wal_queue_capacity: Option<u32>,
+        wal_overflow_policy: Option<String>,
+        max_path_depth: Option<u32>,
+        max_key_length: Option<u32>,
+        forbidden_path_chars: Option<String>,
+        parallel_recovery: Option<bool>,
+        wal_archive: Option<bool>,
+        lazy_collections: Option<Vec<String>>,
+        lazy_max_resident: Option<u32>,
+        enforce_schemas: Option<bool>,
     ) -> Result<Self> {
+        let wal_overflow_policy = match wal_overflow_policy.as_deref() {
+            None => OverflowPolicy::Block,
+            Some(policy) => OverflowPolicy::parse(policy).ok_or_else(|| DbError::InvalidArgument(format!(
+                "Unknown WAL overflow policy '{}': use 'block', 'error' or 'drop_oldest'", policy
+            )))?,
+        };
         let options = DBOptions {
             lock_mode: LockMode::from_str(&lock_mode),
             durability: DurabilityMode::from_str(&durability),
             wal_batch_size: wal_batch_size.unwrap_or(1000) as usize,
             wal_flush_ms: wal_flush_ms.unwrap_or(10) as u64,
+            max_undo_bytes: max_undo_bytes.filter(|&n| n >= 0).map(|n| n as usize),
+            undo_spill: undo_spill.unwrap_or(false),
+            open_retries: open_retries.unwrap_or(3),
+            defer_recovery: defer_recovery.unwrap_or(false),
+            wal_queue_capacity: wal_queue_capacity.map_or(100000, |n| n.max(1) as usize),
+            wal_overflow_policy,
+            path_rules: PathRules {
+                max_depth: max_path_depth.map_or(64, |n| n.max(1) as usize),
+                max_segment_len: max_key_length.map_or(1024, |n| n.max(1) as usize),
+                forbidden: forbidden_path_chars.map(|chars| chars.chars().collect()).unwrap_or_default(),
+            },
+            parallel_recovery,
+            wal_archive: wal_archive.unwrap_or(false),
+            lazy_collections: lazy_collections.unwrap_or_default(),
+            lazy_max_resident: lazy_max_resident.map_or(10000, |n| n.max(1) as usize),
+            enforce_schemas: enforce_schemas.unwrap_or(false),
         };
         
         Self::new_with_options_internal(path, options)
@@ -328,9 +1876,11 @@ impl NativeDB {
     /// v4.5: Explicit sync for durability
     #[napi]
     pub fn sync(&self) -> Result<()> {
+        self.check_open()?;
+        self.flush_coalesced()?;
         if let Some(ref wal) = self.wal {
             wal.sync().map_err(|e| {
-                Error::from_reason(format!("Sync failed: {}", e))
+                DbError::WalFailure(format!("Sync failed: {}", e))
             })?;
         }
         Ok(())
@@ -339,6 +1889,7 @@ impl NativeDB {
     /// v4.5: Get current WAL status
     #[napi]
     pub fn wal_status(&self) -> Result<Value> {
+        self.check_open()?;
         if let Some(ref wal) = self.wal {
             Ok(json!({
                 "enabled": true,
@@ -351,923 +1902,8094 @@ impl NativeDB {
         }
     }
 
-    /// v4.5: Explicitly release resources (locks, WAL handles)
+    /// Commit queue depth and overflow counters: `{enabled, queueDepth,
+    /// queueCapacity, utilization, policy, blockedAppends, blockedMs, rejected, dropped}`.
+    /// Shed load when `utilization` climbs, before appends start to block or fail.
     #[napi]
-    pub fn close(&mut self) -> Result<()> {
-        self.process_lock.take();
-        if let Some(wal) = self.wal.take() {
-            let _ = wal.sync();
+    pub fn wal_pressure(&self) -> Result<Value> {
+        self.check_open()?;
+        let Some(ref wal) = self.wal else {
+            return Ok(json!({ "enabled": false }));
+        };
+        let pressure = wal.pressure();
+        Ok(json!({
+            "enabled": true,
+            "queueDepth": pressure.queue_depth,
+            "queueCapacity": pressure.queue_capacity,
+            "utilization": pressure.queue_depth as f64 / pressure.queue_capacity.max(1) as f64,
+            "policy": pressure.policy.as_str(),
+            "blockedAppends": pressure.blocked_appends,
+            "blockedMs": pressure.blocked_ns as f64 / 1e6,
+            "rejected": pressure.rejected,
+            "dropped": pressure.dropped,
+        }))
+    }
+
+    /// Limit writes to `ops_per_sec` operations per second with bursts of up to
+    /// `burst` (a token bucket; batch methods spend one token per item). When
+    /// the bucket is empty a write waits for tokens, or fails with ERR_THROTTLED
+    /// under the "error" policy or when the wait would exceed `maxWaitMs`.
+    /// Reads and rollbacks are never throttled. Null removes the limit.
+    #[napi]
+    pub fn set_write_rate_limit(&self, ops_per_sec: Option<u32>, burst: u32, options: Option<WriteRateLimitOptions>) -> Result<()> {
+        self.check_open()?;
+        let options = options.unwrap_or_default();
+        let policy = match options.policy.as_deref() {
+            None => ThrottlePolicy::Block,
+            Some(name) => ThrottlePolicy::parse(name).ok_or_else(|| {
+                DbError::InvalidArgument(format!("Unknown throttle policy '{}': use 'block' or 'error'", name))
+            })?,
+        };
+        if ops_per_sec == Some(0) {
+            return Err(DbError::InvalidArgument("opsPerSec must be positive; pass null to remove the limit".to_string()).into());
         }
+        self.write_limiter.configure(ops_per_sec, burst, policy, options.max_wait_ms.unwrap_or(1000));
         Ok(())
     }
 
-    /// Legacy load (maintained for compatibility)
+    /// Write limiter state: `{enabled, opsPerSec, burst, tokens, policy,
+    /// maxWaitMs, throttled, waits, waitedMs}`
     #[napi]
-    pub fn load(&self) -> Result<()> {
-        // Data is already loaded in constructor
-        Ok(())
+    pub fn write_throttle_status(&self) -> Result<Value> {
+        self.check_open()?;
+        Ok(self.write_limiter.stats())
     }
 
+    /// Attach `context` (who or what is writing, e.g. `{user, requestId}`) to every
+    /// following WAL record until cleared. Null clears it. At most 1KB serialized.
     #[napi]
-    pub fn save(&self) -> Result<()> {
-        // Flush WAL first if enabled
-        if let Some(ref wal) = self.wal {
-            wal.sync().map_err(|e| {
-                Error::from_reason(format!("Failed to flush WAL: {}", e))
-            })?;
+    pub fn set_write_context(&self, context: Value) -> Result<()> {
+        self.check_open()?;
+        if context.is_null() {
+            self.clear_write_context()?;
+            return Ok(());
         }
-        
-        let data_guard = self.data.read();
-        let json_str = serde_json::to_string_pretty(&*data_guard).map_err(|e| Error::from_reason(e.to_string()))?;
-        
-        // Atomic write
-        let tmp_path = format!("{}.tmp", self.path);
-        let mut file = File::create(&tmp_path)?;
-        file.write_all(json_str.as_bytes())?;
-        file.sync_all()?;
-        fs::rename(tmp_path, &self.path)?;
-        
-        // Clear WAL after successful save
-        if self.wal.is_some() {
-            // Truncate WAL file
-            File::create(&self.wal_path)?;
+        let size = serde_json::to_vec(&context).map_err(|e| DbError::InvalidArgument(e.to_string()))?.len();
+        if size > MAX_WRITE_CONTEXT_BYTES {
+            return Err(DbError::LimitExceeded(format!(
+                "Write context is {} bytes serialized (limit {})", size, MAX_WRITE_CONTEXT_BYTES
+            )).into());
         }
-        
-        // Save indexes
-        let mut indexes = self.indexes.write();
-        for idx in indexes.values_mut() {
-            idx.save().map_err(|e| Error::from_reason(format!("Failed to save index: {:?}", e)))?;
-        }
-        
+        *self.write_context.write() = Some(context);
         Ok(())
     }
-    
-    /// Legacy WAL append (for internal use)
-    fn append_wal(&self, op_type: WalOpType, path: &str, value: Option<Value>) -> Result<()> {
-        if let Some(ref wal) = self.wal {
-            let op = WalOp {
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64,
-                op_type,
-                path: path.to_string(),
-                value,
-            };
-            
-            wal.append(op).map_err(|e| {
-                Error::from_reason(format!("WAL append failed: {}", e))
-            })?;
-        }
+
+    #[napi]
+    pub fn clear_write_context(&self) -> Result<()> {
+        self.check_open()?;
+        self.write_context.write().take();
         Ok(())
     }
-    
-    /// Recover from legacy WAL format
-    fn recover_legacy_wal(wal_path: &str, data: &mut Value) -> Result<()> {
-        let file = File::open(wal_path)?;
-        let reader = BufReader::new(file);
-        
-        for line in reader.lines() {
-            if let Ok(l) = line {
-                if l.trim().is_empty() { continue; }
-                if let Ok(entry) = serde_json::from_str::<WalEntry>(&l) {
-                    match entry.op.as_str() {
-                        "set" => {
-                            if let Some(val) = entry.value {
-                                let _ = Self::set_value_at_path(data, &entry.path, val);
-                            }
-                        }
-                        "delete" => {
-                            let _ = Self::delete_value_at_path(data, &entry.path);
-                        }
-                        "push" => {
-                            if let Some(val) = entry.value {
-                                let _ = Self::push_value_at_path(data, &entry.path, val);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+
+    /// Records in the WAL (everything since the last save), oldest first:
+    /// `{records: [{lsn, timestamp, op, path, context, value?}], stopReason}`
+    #[napi]
+    pub fn wal_inspect(&self, options: Option<WalInspectOptions>) -> Result<Value> {
+        self.check_open()?;
+        let options = options.unwrap_or_default();
+        let from_lsn = options.from_lsn.unwrap_or(0).max(0) as u64;
+        let limit = options.limit.map_or(usize::MAX, |n| n as usize);
+        let include_values = options.include_values.unwrap_or(false);
+        let (records, stopped_early) = self.read_wal()?;
+        let records: Vec<Value> = records.into_iter()
+            .filter(|(lsn, _)| *lsn > from_lsn)
+            .take(limit)
+            .map(|(lsn, op)| Self::wal_record_json(lsn, op, include_values))
+            .collect();
+        Ok(json!({ "records": records, "stopReason": stopped_early }))
+    }
+
+    /// Register `callback` for an event per removed path: `{path, reason, lsn, value?}`,
+    /// covering delete, delete_many, delete_prefix and quota evictions. `reason` is
+    /// "explicit", "ttl", "quota" or "cascade"; `lsn` is the WAL record that removed
+    /// the path (null when it was not logged on its own). Events are queued onto the
+    /// event loop in WAL order, never called synchronously; the listener does not
+    /// keep the process alive. Passing no callback removes the listener.
+    #[napi(ts_args_type = "callback?: (event: DeleteEvent) => void, options?: DeleteListenerOptions")]
+    pub fn on_delete(&self, env: Env, callback: Option<JsFunction>, options: Option<DeleteListenerOptions>) -> Result<()> {
+        self.check_open()?;
+        let listener = match callback {
+            Some(callback) => {
+                let mut tsfn: ThreadsafeFunction<Value, ErrorStrategy::Fatal> = Self::guard_callback(&env, callback)?
+                    .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<Value>| Ok(vec![ctx.env.to_js_value(&ctx.value)?]))?;
+                tsfn.unref(&env)?;
+                Some(DeleteListener { tsfn, include_values: options.and_then(|o| o.include_values).unwrap_or(false) })
             }
-        }
-        
+            None => None,
+        };
+        *self.delete_listener.write() = listener;
         Ok(())
     }
 
-    // --- Logic Helpers ---
+    /// Register `callback` for a span per significant operation: `{op, path,
+    /// durationUs, itemsScanned, itemsReturned, parallel, error}`. Traced ops are
+    /// "get" (results of at least `getThresholdBytes`), "query", "queryString",
+    /// "aggregate", "indexAggregate", "lookup", "save" and "recovery". Spans are
+    /// queued once the operation has released its locks and delivered on the
+    /// event loop, never synchronously; the callback does not keep the process
+    /// alive and a throw from it is ignored. Passing no callback removes it.
+    #[napi(ts_args_type = "callback?: (span: TraceSpan) => void, options?: TraceOptions")]
+    pub fn set_trace_callback(&self, env: Env, callback: Option<JsFunction>, options: Option<TraceOptions>) -> Result<()> {
+        self.check_open()?;
+        let tracer = match callback {
+            Some(callback) => {
+                let threshold = options.and_then(|o| o.get_threshold_bytes)
+                    .map_or(trace::DEFAULT_GET_THRESHOLD_BYTES, |bytes| bytes.max(0) as usize);
+                Some(Arc::new(Tracer::new(&env, callback, threshold)?))
+            }
+            None => None,
+        };
+        let mut current = self.tracer.write();
+        self.tracing.store(tracer.is_some(), Ordering::Relaxed);
+        *current = tracer;
+        Ok(())
+    }
 
-    fn set_value_at_path(root: &mut Value, path_str: &str, value: Value) -> Result<()> {
-        if path_str.is_empty() {
-            *root = value;
-            return Ok(())
+    fn tracer(&self) -> Option<Arc<Tracer>> {
+        if !self.tracing.load(Ordering::Relaxed) {
+            return None;
         }
-        
-        let parts: Vec<&str> = path_str.split('.').collect();
-        if parts.is_empty() { return Ok(()) }
-        
-        let last_part = parts.last().unwrap();
-        let parent_parts = &parts[..parts.len()-1];
-        
-        let mut current = root;
-        
-        for (i, part) in parent_parts.iter().enumerate() {
-            if current.is_null() {
-                 *current = Value::Object(serde_json::Map::new());
-            }
-            let is_array_idx = parts[i+1].parse::<usize>().is_ok(); 
-            if let Value::Object(map) = current {
-                if !map.contains_key(*part) {
-                    map.insert(part.to_string(), if is_array_idx { json!([]) } else { json!({}) });
-                }
-                current = map.get_mut(*part).unwrap();
-            } else if let Value::Array(arr) = current {
-                 if let Ok(idx) = part.parse::<usize>() {
-                     while arr.len() <= idx {
-                         arr.push(Value::Null);
-                     }
-                     if arr[idx].is_null() {
-                          let is_next_array = parts.get(i+1).map(|p| p.parse::<usize>().is_ok()).unwrap_or(false);
-                          arr[idx] = if is_next_array { json!([]) } else { json!({}) };
-                     }
-                     current = &mut arr[idx];
-                 } else {
-                     return Err(Error::from_reason("Cannot index array with string".to_string()));
-                 }
-            } else {
-                 return Err(Error::from_reason(format!("Path segment '{}' blocked by primitive", part)));
-            }
+        self.tracer.read().clone()
+    }
+
+    /// Run `op`, reporting a span for it once it returns (and so once its locks
+    /// are released) if a trace callback is registered
+    fn traced<T>(&self, op: &str, path: &str, run: impl FnOnce(&mut Span) -> Result<T>) -> Result<T> {
+        let mut span = Span::default();
+        let Some(tracer) = self.tracer() else { return run(&mut span) };
+        let started = std::time::Instant::now();
+        let result = run(&mut span);
+        tracer.emit(op, path, started, &span, result.as_ref().err().map(|e| e.reason.as_str()));
+        result
+    }
+
+    /// Record the collection at `path` as what `span` scans; `filtered` when the
+    /// operation evaluates each item, which large collections do on the pool
+    fn trace_scan(span: &mut Span, data: &Value, path: &str, filtered: bool) {
+        let ptr = Self::to_pointer(path);
+        let items = trace::items(if ptr.is_empty() { Some(data) } else { data.pointer(&ptr) });
+        span.scanned = Some(items);
+        span.parallel = filtered && THREAD_CONFIG.should_parallelize(items);
+    }
+
+    /// Writes logged after `lsn`, with their values and contexts (and, for deletes,
+    /// their reason). Only covers the WAL since the last save, which truncates it.
+    #[napi]
+    pub fn changes_since(&self, lsn: i64, limit: Option<u32>) -> Result<Value> {
+        self.check_open()?;
+        let inspected = self.wal_inspect(Some(WalInspectOptions {
+            from_lsn: Some(lsn),
+            limit,
+            include_values: Some(true),
+        }))?;
+        Ok(inspected["records"].clone())
+    }
+
+    // Consumers
+    //
+    // Named cursors over the change feed, stored in the reserved namespace as
+    // `{position, ackedAt}`. Saves keep the records some consumer has not acked
+    // in `<db>.changes`, up to `set_change_retention` records; a consumer behind
+    // what was dropped gets `resyncRequired` instead of changes.
+
+    /// Record that `consumer` has processed every change up to `seq`, creating
+    /// the consumer if new. `seq` is an LSN, normally the `position` returned by
+    /// `changes_for`, or the current head to start from now.
+    #[napi]
+    pub fn ack_changes(&self, consumer: String, seq: i64) -> Result<()> {
+        self.check_recovered()?;
+        if consumer.is_empty() {
+            return Err(DbError::InvalidArgument("Consumer name must not be empty".to_string()).into());
         }
+        let head = self.head_lsn()?;
+        if seq < 0 || seq as u64 > head {
+            return Err(DbError::InvalidArgument(format!(
+                "Cannot ack {} for '{}': the log is at {}", seq, consumer, head
+            )).into());
+        }
+        let mut data = self.data.write();
+        let mut consumers = Self::get_internal(&data, "consumers").unwrap_or_else(|| json!({}));
+        consumers[consumer.as_str()] = json!({ "position": seq, "ackedAt": now_ms() });
+        self.set_internal(&mut data, "consumers", consumers)
+    }
 
-        if let Value::Object(map) = current {
-            map.insert(last_part.to_string(), value);
-        } else if let Value::Array(arr) = current {
-            if let Ok(idx) = last_part.parse::<usize>() {
-                while arr.len() <= idx {
-                    arr.push(Value::Null);
+    /// Last position acked by `consumer`, or null for an unknown consumer
+    #[napi]
+    pub fn consumer_position(&self, consumer: String) -> Result<Value> {
+        self.check_open()?;
+        let data = self.data.read();
+        Ok(meta::get(&data, "consumers")
+            .and_then(|c| c.get(&consumer))
+            .map_or(Value::Null, |c| c["position"].clone()))
+    }
+
+    /// Changes after `consumer`'s acked position, oldest first, as
+    /// `{changes, position, resyncRequired, headLsn}`. `position` is what to ack
+    /// once they are processed; with `resyncRequired` some changes were dropped
+    /// before the consumer saw them and `changes` is empty.
+    #[napi]
+    pub fn changes_for(&self, consumer: String, limit: Option<u32>) -> Result<Value> {
+        self.check_open()?;
+        let position = {
+            let data = self.data.read();
+            meta::get(&data, "consumers").and_then(|c| c.get(&consumer)).map(|c| c["position"].as_u64().unwrap_or(0))
+        };
+        let Some(mut position) = position else {
+            return Err(DbError::NotFound(format!("Unknown consumer '{}'", consumer)).into());
+        };
+        // A save moves records from the WAL to the change log: read both in between
+        let persistence = self.persistence.lock();
+        let head = self.head_lsn()?;
+        let log = ChangeLog::new(&self.path);
+        if position < log.discarded_through(persistence.saved_through()) {
+            return Ok(json!({ "changes": [], "position": position, "resyncRequired": true, "headLsn": head }));
+        }
+        let mut records = log.read_after(position).map_err(DbError::from)?;
+        let newest = records.last().map_or(position, |(lsn, _)| *lsn);
+        records.extend(self.read_wal()?.0.into_iter().filter(|(lsn, _)| *lsn > newest));
+
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+        let mut changes = Vec::new();
+        for (lsn, op) in records {
+            if !meta::is_internal_path(&op.path) && !op.op_type.is_marker() {
+                if changes.len() == limit {
+                    break;
                 }
-                arr[idx] = value;
-            } else {
-                 return Err(Error::from_reason("Cannot set non-numeric key on array".to_string()));
+                changes.push(Self::wal_record_json(lsn, op, true));
             }
-        } else {
-             if current.is_null() {
-                 let is_array = last_part.parse::<usize>().is_ok();
-                 if is_array {
-                     let idx = last_part.parse::<usize>().unwrap();
-                     let mut arr = vec![Value::Null; idx + 1];
-                     arr[idx] = value;
-                     *current = Value::Array(arr);
-                 } else {
-                     let mut map = serde_json::Map::new();
-                     map.insert(last_part.to_string(), value);
-                     *current = Value::Object(map);
-                 }
-             } else {
-                  return Err(Error::from_reason(format!("Parent of '{}' is not an object/array", last_part)));
-             }
+            position = lsn;
+        }
+        Ok(json!({ "changes": changes, "position": position, "resyncRequired": false, "headLsn": head }))
+    }
+
+    /// Every consumer as `{name, position, ackedAt, resyncRequired}`
+    #[napi]
+    pub fn list_consumers(&self) -> Result<Value> {
+        self.check_open()?;
+        let discarded = ChangeLog::new(&self.path).discarded_through(self.persistence.lock().saved_through());
+        let data = self.data.read();
+        let Some(Value::Object(consumers)) = meta::get(&data, "consumers") else { return Ok(json!([])) };
+        Ok(Value::Array(consumers.iter().map(|(name, c)| json!({
+            "name": name,
+            "position": c["position"],
+            "ackedAt": c["ackedAt"],
+            "resyncRequired": c["position"].as_u64().unwrap_or(0) < discarded,
+        })).collect()))
+    }
+
+    /// Forget `consumer`, letting saves drop the changes only it still needed.
+    /// Returns false if there was no such consumer.
+    #[napi]
+    pub fn drop_consumer(&self, consumer: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut consumers = Self::get_internal(&data, "consumers").unwrap_or_else(|| json!({}));
+        let Some(map) = consumers.as_object_mut() else { return Ok(false) };
+        if map.shift_remove(&consumer).is_none() {
+            return Ok(false);
+        }
+        self.set_internal(&mut data, "consumers", consumers)?;
+        Ok(true)
+    }
+
+    /// Most records a save keeps for consumers that have not acked them; older
+    /// ones are dropped and their consumers told to resync. Defaults to 100000.
+    #[napi]
+    pub fn set_change_retention(&self, max_records: u32) -> Result<()> {
+        self.check_open()?;
+        if max_records == 0 {
+            return Err(DbError::InvalidArgument("Change retention must be at least 1 record".to_string()).into());
         }
+        self.change_retention.store(max_records as usize, Ordering::Relaxed);
         Ok(())
     }
 
-    fn delete_value_at_path(root: &mut Value, path_str: &str) -> Result<()> {
-        if path_str.is_empty() {
-            *root = json!({});
-            return Ok(())
+    /// LSN of the latest write, once flushed
+    fn head_lsn(&self) -> Result<u64> {
+        let Some(ref wal) = self.wal else {
+            return Err(DbError::InvalidArgument("WAL is not enabled".to_string()).into());
+        };
+        wal.sync().map_err(|e| DbError::WalFailure(format!("Failed to flush WAL: {}", e)))?;
+        Ok(wal.committed_lsn())
+    }
+
+    /// Flush the WAL and read back every intact record
+    fn read_wal(&self) -> Result<(Vec<WalRecord>, Option<String>)> {
+        let Some(ref wal) = self.wal else {
+            return Err(DbError::InvalidArgument("WAL is not enabled".to_string()).into());
+        };
+        wal.sync().map_err(|e| DbError::WalFailure(format!("Failed to flush WAL: {}", e)))?;
+        if !Path::new(&self.wal_path).exists() {
+            return Ok((Vec::new(), None));
         }
-        let parts: Vec<&str> = path_str.split('.').collect();
-        if parts.is_empty() { return Ok(()) }
-        
-        let parent_path = parts[..parts.len()-1].join(".");
-        let target_key = parts.last().unwrap();
-        
-        let ptr = if parent_path.is_empty() { "".to_string() } else { format!("/{}", parent_path.replace(".", "/")) };
-        
-        let parent = if ptr.is_empty() { Some(root) } else { root.pointer_mut(&ptr) };
+        read_wal_records(&self.wal_path).map_err(|e| DbError::from(e).into())
+    }
 
-        if let Some(p) = parent {
-            if let Value::Object(map) = p {
-                map.remove(*target_key);
-            } else if let Value::Array(arr) = p {
-                if let Ok(idx) = target_key.parse::<usize>() {
-                    if idx < arr.len() {
-                        arr.remove(idx);
-                    }
-                }
+    fn wal_record_json(lsn: u64, op: WalOp, include_value: bool) -> Value {
+        let mut record = json!({
+            "lsn": lsn,
+            "timestamp": op.timestamp,
+            "op": match op.op_type {
+                WalOpType::Set => "set",
+                WalOpType::Delete => "delete",
+                WalOpType::Begin => "begin",
+                WalOpType::Commit => "commit",
+                WalOpType::Abort => "abort",
+            },
+            "path": op.path,
+            "context": op.context,
+        });
+        if let Some(txn) = op.txn {
+            record["txn"] = json!(txn);
+        }
+        if matches!(op.op_type, WalOpType::Delete) {
+            record["reason"] = json!(op.reason.as_deref().unwrap_or("explicit"));
+        }
+        if include_value {
+            record["value"] = op.value.unwrap_or(Value::Null);
+        }
+        record
+    }
+
+    /// Read-only handle on this database's live data for the current thread.
+    /// Shares data and indexes (nothing is copied) and sees every write as it
+    /// lands; it has no WAL or lock and exposes only reads.
+    #[napi]
+    pub fn create_reader(&self) -> Result<ReaderHandle> {
+        self.check_open()?;
+        Ok(ReaderHandle::new(&self.reader_source()?))
+    }
+
+    /// Token for `ReaderHandle.open(token)` in a worker thread of this process.
+    /// Valid until this database is closed; readers opened with it stay usable
+    /// (and keep the data in memory) after that.
+    #[napi]
+    pub fn reader_token(&self) -> Result<u32> {
+        self.check_open()?;
+        let mut token = self.reader_token.lock();
+        if let Some(token) = *token {
+            return Ok(token);
+        }
+        let registered = reader::register(self.reader_source()?);
+        *token = Some(registered);
+        Ok(registered)
+    }
+
+    /// v4.5: Explicitly release resources (locks, WAL handles). First applies
+    /// coalesced writes, rolls back an open transaction and saves dirty indexes.
+    /// The data itself is not saved: as after `rollback_transaction`, the
+    /// transaction's writes stay in the WAL until the next save, so roll back
+    /// and save first to drop them for good. Resources are released even when
+    /// one of those steps fails, and its error is returned. Afterwards every
+    /// call except `close`, `isClosed`, `recoveryStatus`, `persistenceStatus`
+    /// and `getSystemInfo` fails with ERR_CLOSED. Closing again does nothing.
+    #[napi]
+    pub fn close(&mut self) -> Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        // Waits for a running task or sweep; none can start afterwards
+        self.maintenance.lock().take();
+        self.ttl_sweeper.lock().take();
+        if let Some(token) = self.reader_token.lock().take() {
+            reader::unregister(token);
+        }
+        // An unrecovered database has nothing of its own to flush
+        let flushed = match self.check_recovered() {
+            Ok(()) => self.flush_before_close(),
+            Err(_) => Ok(()),
+        };
+        self.closed.store(true, Ordering::Release);
+        self.process_lock.lock().take();
+        // Flushed, then the commit thread exits and closes the file
+        if let Some(wal) = &self.wal {
+            let _ = wal.sync();
+            let _ = wal.shutdown();
+        }
+        flushed
+    }
+
+    #[napi]
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    fn flush_before_close(&self) -> Result<()> {
+        self.flush_coalesced()?;
+        // Uncommitted writes must not reach the file
+        let open: Vec<u32> = self.transactions.lock().open.keys().rev().copied().collect();
+        for txn in open {
+            self.rollback_transaction(Some(txn))?;
+        }
+        // Indexes maintained across a bulk load are stale until it ends
+        if !self.bulk_loading.load(Ordering::Acquire) {
+            self.refresh_custom_indexes()?;
+            self.upkeep_indexes()?;
+            for idx in self.indexes.write().values_mut() {
+                idx.save().map_err(|e| DbError::Index(format!("Failed to save index: {}", e)))?;
             }
         }
         Ok(())
     }
 
-    fn push_value_at_path(root: &mut Value, path_str: &str, value: Value) -> Result<()> {
-        let ptr = if path_str.starts_with('/') { path_str.to_string() } else { format!("/{}", path_str.replace(".", "/")) };
-        
-        if let Some(target) = root.pointer_mut(&ptr) {
-            if let Value::Array(arr) = target {
-                // Dedupe: check if value exists
-                if !arr.contains(&value) {
-                     arr.push(value);
+    /// Reject every call on a closed database
+    fn check_open(&self) -> Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(DbError::Closed.into());
+        }
+        Ok(())
+    }
+
+    /// Legacy load (maintained for compatibility)
+    #[napi]
+    pub fn load(&self) -> Result<()> {
+        self.check_open()?;
+        // Data is already loaded in constructor
+        Ok(())
+    }
+
+    /// Replay the WAL of a database opened with `deferRecovery`, calling
+    /// `progress({opsReplayed, bytesRead, totalBytes})` periodically. Returns the
+    /// replay report (as in `integrityReport().wal`); once recovery has run this
+    /// just returns the report again. A throwing callback is not called again
+    /// and its error is rethrown after the replay completes.
+    #[napi]
+    pub fn recover(&self, env: Env, progress: Option<JsFunction>) -> Result<Value> {
+        self.check_open()?;
+        if !self.recovery.start()? {
+            self.check_recovered()?;
+            return Ok(self.load_report.lock().replay.to_json(self.wal.is_some()));
+        }
+        let tracer = self.tracer();
+        let started = std::time::Instant::now();
+        let mut failure = None;
+        let replayed = Self::replay(&self.wal_path, self.wal.is_some(), &mut self.data.write(), self.lazy.as_deref(), &self.options, &mut |p| {
+            self.recovery.report(p);
+            if let (Some(callback), None) = (&progress, &failure) {
+                if let Err(e) = env.to_js_value(&Recovery::progress_json(p)).and_then(|arg| callback.call(None, &[arg])) {
+                    failure = Some(e);
                 }
-            } else {
-                return Err(Error::from_reason("Target is not an array".to_string()));
             }
-        } else {
-             return Err(Error::from_reason("Path does not exist".to_string()));
+        });
+        let report = replayed.to_json(self.wal.is_some());
+        if let Some(tracer) = tracer {
+            tracer.emit("recovery", "", started, &replayed.span(), replayed.error.as_deref());
+        }
+        self.recovery.finish(replayed);
+        self.check_recovered()?;
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(report),
         }
-        Ok(())
     }
 
-    // ============================================
-    // PARALLEL OPERATIONS
-    // ============================================
+    /// `recover` on a background thread, resolving with the replay report.
+    /// Progress callbacks are queued onto the event loop without waiting for
+    /// them; calls into the database are rejected with ERR_RECOVERY_PENDING
+    /// until the promise resolves.
+    #[napi(ts_return_type = "Promise<any>")]
+    pub fn recover_async(&self, env: Env, progress: Option<JsFunction>) -> Result<JsUnknown> {
+        self.check_open()?;
+        let tsfn: Option<ThreadsafeFunction<Value, ErrorStrategy::Fatal>> = match progress {
+            Some(callback) => Some(Self::guard_callback(&env, callback)?.create_threadsafe_function(
+                0,
+                |ctx: ThreadSafeCallContext<Value>| Ok(vec![ctx.env.to_js_value(&ctx.value)?]),
+            )?),
+            None => None,
+        };
+        // Both outcomes resolve through one closure type
+        fn resolve_with(report: Value) -> impl FnOnce(Env) -> Result<JsUnknown> {
+            move |env| env.to_js_value(&report)
+        }
+        let (deferred, promise) = env.create_deferred()?;
+        if !self.recovery.start()? {
+            self.check_recovered()?;
+            let report = self.load_report.lock().replay.to_json(self.wal.is_some());
+            deferred.resolve(resolve_with(report));
+            return Ok(promise.into_unknown());
+        }
+
+        let data = Arc::clone(&self.data);
+        let recovery = Arc::clone(&self.recovery);
+        let wal_path = self.wal_path.clone();
+        let wal_enabled = self.wal.is_some();
+        let options = self.options.clone();
+        let lazy = self.lazy.clone();
+        let tracer = self.tracer();
+        std::thread::spawn(move || {
+            let started = std::time::Instant::now();
+            let replayed = Self::replay(&wal_path, wal_enabled, &mut data.write(), lazy.as_deref(), &options, &mut |p| {
+                recovery.report(p);
+                if let Some(tsfn) = &tsfn {
+                    tsfn.call(Recovery::progress_json(p), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            });
+            drop(tsfn);
+            if let Some(tracer) = tracer {
+                tracer.emit("recovery", "", started, &replayed.span(), replayed.error.as_deref());
+            }
+            let report = replayed.to_json(wal_enabled);
+            recovery.finish(replayed);
+            deferred.resolve(resolve_with(report));
+        });
+        Ok(promise.into_unknown())
+    }
 
-    /// Execute batch set operations in parallel when beneficial
+    /// `{state: 'pending' | 'running' | 'done', opsReplayed, bytesRead, totalBytes}`
     #[napi]
-    pub fn batch_set_parallel(&self, operations: Vec<(String, Value)>) -> Result<ParallelResult> {
-        let count = operations.len();
-        
-        if THREAD_CONFIG.should_parallelize(count) {
-            // Pre-validate paths in parallel
-            let validation_results: Vec<bool> = operations
-                .par_iter()
-                .map(|(path, _)| !path.is_empty())
-                .collect();
+    pub fn recovery_status(&self) -> Value {
+        let _ = self.check_recovered();
+        self.recovery.status()
+    }
+
+    #[napi]
+    pub fn save(&self) -> Result<()> {
+        self.save_with_report().map(|_| ())
+    }
+
+    /// `save` returning what it did: `{at, bytes, previousBytes, serializeMs, fsyncMs,
+    /// totalMs, indexesSaved, indexesSkipped, walOpsTruncated, walBytesTruncated, backupRotated}`.
+    /// The latest report is also kept for `persistence_status`. Fails with
+    /// ERR_TRANSACTION_ACTIVE while a transaction is open.
+    #[napi]
+    pub fn save_with_report(&self) -> Result<Value> {
+        self.traced("save", "", |_| {
+            self.check_recovered()?;
+            let start = std::time::Instant::now();
+            let writes = self.writes.load(Ordering::Acquire);
+            self.flush_coalesced()?;
+            if self.bulk_loading.load(Ordering::Acquire) {
+                // Indexes and counters saved mid-load must not miss its writes
+                self.invalidate_derived();
+            }
+            self.refresh_custom_indexes()?;
+            self.upkeep_indexes()?;
+            // Flush WAL first if enabled
+            if let Some(ref wal) = self.wal {
+                wal.sync().map_err(|e| {
+                    DbError::WalFailure(format!("Failed to flush WAL: {}", e))
+                })?;
+            }
             
-            if validation_results.iter().any(|&v| !v) {
-                return Ok(ParallelResult {
-                    success: false,
-                    count: 0,
-                    error: Some("Invalid path in batch".to_string()),
-                });
+            self.persist_counters();
+            self.persist_quotas();
+            // Lazy entries are written first: the base file then leaves them out
+            let mut lazy_store = self.lazy.as_ref().map(|lazy| lazy.store.lock());
+            let data_guard = self.data.read();
+            // Uncommitted writes must not reach the file while the WAL that
+            // could roll them back is truncated; held off by the read lock
+            if self.transactions.lock().is_active() {
+                return Err(DbError::TransactionActive.into());
             }
+            let lazy_stored = match (&self.lazy, lazy_store.as_mut()) {
+                (Some(lazy), Some(store)) => store.persist(&data_guard, &mut lazy.dirty.lock())
+                    .map_err(|e| DbError::Io(format!("Failed to save lazy collections: {}", e)))?,
+                _ => Default::default(),
+            };
+            let serialize_start = std::time::Instant::now();
+            let json_str = serde_json::to_string_pretty(&BaseView { root: &data_guard, skip: &lazy_stored })
+                .map_err(|e| DbError::Corrupt(e.to_string()))?;
+            let serialize_ms = serialize_start.elapsed().as_secs_f64() * 1000.0;
+            let consumer_floor = meta::get(&data_guard, "consumers")
+                .and_then(|consumers| consumers.as_object())
+                .and_then(|consumers| consumers.values().filter_map(|c| c["position"].as_u64()).min());
+            drop(data_guard);
             
-            // Apply all operations (requires sequential write lock)
-            let mut data = self.data.write();
-            let mut success_count = 0u32;
+            // Atomic write
+            let tmp_path = format!("{}.tmp", self.path);
+            let mut file = File::create(&tmp_path).map_err(DbError::from)?;
+            file.write_all(json_str.as_bytes()).map_err(DbError::from)?;
+            let fsync_start = std::time::Instant::now();
+            file.sync_all().map_err(DbError::from)?;
+            let fsync_ms = fsync_start.elapsed().as_secs_f64() * 1000.0;
+            let previous_bytes = fs::metadata(&self.path).ok().map(|m| m.len());
+            fs::rename(tmp_path, &self.path).map_err(DbError::from)?;
             
-            for (path, value) in operations {
-                let _ = self.append_wal(WalOpType::Set, &path, Some(value.clone()));
-                if Self::set_value_at_path(&mut data, &path, value).is_ok() {
-                    success_count += 1;
+            // Clear WAL after successful save. Records left from before this process
+            // opened the database are only in the file until the first truncation.
+            let mut persistence = self.persistence.lock();
+            let mut wal_ops_truncated = 0;
+            let mut wal_bytes_truncated = 0;
+            let mut truncated_lsn = persistence.truncated_lsn;
+            if let Some(ref wal) = self.wal {
+                truncated_lsn = wal.committed_lsn();
+                wal_ops_truncated = truncated_lsn.saturating_sub(persistence.truncated_lsn);
+                wal_bytes_truncated = fs::metadata(&self.wal_path).map_or(0, |m| m.len());
+                if persistence.saves == 0 {
+                    wal_ops_truncated += self.load_report.lock().replay.stats.as_ref().map_or(0, |s| s.ops_read);
                 }
+                if self.options.wal_archive {
+                    Archive::new(&self.path).checkpoint(&self.path, Some(&self.wal_path), now_ms(), truncated_lsn)
+                        .map_err(|e| DbError::Io(format!("Failed to archive WAL: {}", e)))?;
+                }
+                ChangeLog::new(&self.path)
+                    .checkpoint(&self.wal_path, truncated_lsn, consumer_floor, self.change_retention.load(Ordering::Relaxed))
+                    .map_err(|e| DbError::Io(format!("Failed to retain changes: {}", e)))?;
+                // Truncate WAL file
+                File::create(&self.wal_path).map_err(DbError::from)?;
             }
             
-            Ok(ParallelResult {
-                success: true,
-                count: success_count,
-                error: None,
-            })
-        } else {
-            // Sequential fallback
-            let mut data = self.data.write();
-            let mut success_count = 0u32;
-            
-            for (path, value) in operations {
-                let _ = self.append_wal(WalOpType::Set, &path, Some(value.clone()));
-                if Self::set_value_at_path(&mut data, &path, value).is_ok() {
-                    success_count += 1;
+            // Save indexes
+            let (mut indexes_saved, mut indexes_skipped) = (0, 0);
+            let mut indexes = self.indexes.write();
+            for idx in indexes.values_mut() {
+                match idx.save().map_err(|e| DbError::Index(format!("Failed to save index: {}", e)))? {
+                    true => indexes_saved += 1,
+                    false => indexes_skipped += 1,
                 }
             }
             
-            Ok(ParallelResult {
-                success: true,
-                count: success_count,
-                error: None,
-            })
+            let report = SaveReport {
+                at: now_ms(),
+                bytes: json_str.len() as u64,
+                previous_bytes,
+                serialize_ms,
+                fsync_ms,
+                total_ms: start.elapsed().as_secs_f64() * 1000.0,
+                indexes_saved,
+                indexes_skipped,
+                wal_ops_truncated,
+                wal_bytes_truncated,
+                backup_rotated: false,
+            };
+            let json = report.to_json();
+            persistence.record(report, truncated_lsn);
+            self.saved_writes.store(writes, Ordering::Release);
+            if let (Some(lazy), Some(store)) = (&self.lazy, lazy_store.as_mut()) {
+                store.trim_after_save(&mut self.data.write(), &lazy.dirty.lock());
+            }
+            Ok(json)
+        })
+    }
+
+    /// The value at `path` as of `timestamp_ms`, or null if it did not exist then.
+    /// Needs the `walArchive` option: replays archived and live WAL records that
+    /// touch the path on top of the latest checkpoint at or before the timestamp.
+    /// ERR_NOT_FOUND when that history was never archived or has been pruned.
+    #[napi]
+    pub fn value_at(&self, path: String, timestamp_ms: i64) -> Result<Value> {
+        self.check_recovered()?;
+        if !self.options.wal_archive {
+            return Err(DbError::InvalidArgument("value_at needs the walArchive option".to_string()).into());
         }
+        if meta::is_internal_path(&path) {
+            return Ok(Value::Null);
+        }
+        if let Some(ref wal) = self.wal {
+            wal.sync().map_err(|e| DbError::WalFailure(format!("Failed to flush WAL: {}", e)))?;
+        }
+        let path = Self::join_path("", &path);
+        let mut value = Archive::new(&self.path).value_at(&path, timestamp_ms.max(0) as u64, &self.wal_path)?;
+        if path.is_empty() {
+            value = meta::strip_internal(&value);
+        }
+        Ok(value)
     }
 
-    /// Parallel filter/query on a collection
+    /// Drop archived history before `before_ms`, keeping the latest checkpoint
+    /// at or before it: `{checkpointsRemoved, segmentsRemoved, oldest}`
     #[napi]
-    pub fn parallel_query(&self, path: String, filters: Vec<QueryFilter>) -> Result<Value> {
-        let data = self.data.read();
-        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
-        
-        let collection = if ptr == "/" || ptr.is_empty() {
-            Some(&*data)
-        } else {
-            data.pointer(&ptr)
-        };
-        
-        match collection {
-            Some(Value::Object(map)) => {
-                let items: Vec<&Value> = map.values().collect();
-                let prepared: Vec<PreparedFilter> = filters.iter().map(PreparedFilter::from_query_filter).collect();
-                let filtered = self.filter_items_parallel(&items, &prepared);
-                Ok(Value::Array(filtered))
+    pub fn prune_archive(&self, before_ms: i64) -> Result<Value> {
+        self.check_open()?;
+        Ok(Archive::new(&self.path).prune(before_ms.max(0) as u64).map_err(DbError::from)?)
+    }
+
+    /// Write the whole engine state to one archive file: the in-memory data
+    /// (including the reserved namespace and every lazy entry), all indexes and
+    /// registered schemas, with a manifest of format versions and checksums.
+    /// Returns the manifest. The supported way to back up or move a database;
+    /// `import_archive` restores it. Fails with ERR_TRANSACTION_ACTIVE while
+    /// an exclusive transaction is open.
+    #[napi]
+    pub fn export_archive(&self, out_path: String, options: Option<ExportOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        let options = options.unwrap_or_default();
+        let out = Path::new(&out_path);
+        if out.exists() && !options.overwrite.unwrap_or(false) {
+            return Err(DbError::InvalidArgument(format!("{} already exists; pass overwrite to replace it", out_path)).into());
+        }
+        self.flush_coalesced()?;
+        self.fault_in("")?;
+        self.refresh_custom_indexes()?;
+        self.upkeep_indexes()?;
+        self.persist_counters();
+        self.persist_quotas();
+
+        // Data, indexes and schemas from one moment
+        let (entries, schemas) = {
+            let data = self.data.read();
+            // Indexes hold the exclusive transaction's writes, the committed data would not
+            if self.transactions.lock().exclusive.is_some() {
+                return Err(DbError::TransactionActive.into());
             }
-            Some(Value::Array(arr)) => {
-                let items: Vec<&Value> = arr.iter().collect();
-                let prepared: Vec<PreparedFilter> = filters.iter().map(PreparedFilter::from_query_filter).collect();
-                let filtered = self.filter_items_parallel(&items, &prepared);
-                Ok(Value::Array(filtered))
+            let indexes = self.indexes.read();
+            let schemas = self.schemas.read();
+            let mut entries = vec![backup::Entry {
+                name: "data".to_string(),
+                kind: backup::EntryKind::Data,
+                field: None,
+                collection: None,
+                sparse: None,
+                content: serde_json::to_vec_pretty(&*data).map_err(|e| DbError::Corrupt(e.to_string()))?,
+            }];
+            let mut names: Vec<&String> = indexes.keys().collect();
+            names.sort();
+            for name in names {
+                let idx = &indexes[name];
+                entries.push(backup::Entry {
+                    name: name.clone(),
+                    kind: backup::EntryKind::Index,
+                    field: Some(idx.field().to_string()),
+                    collection: idx.collection(),
+                    sparse: idx.sparse().then_some(true),
+                    content: idx.encode(),
+                });
             }
-            _ => Ok(Value::Array(vec![])),
-        }
+            let schemas: serde_json::Map<String, Value> = schemas.iter()
+                .map(|(path, schema)| (path.clone(), schema.to_json()))
+                .collect();
+            (entries, schemas)
+        };
+        let manifest = backup::write(out, &self.path, now_ms(), schemas, entries, options.compress.unwrap_or(false))?;
+        Ok(manifest.to_json())
     }
-    
-    /// Internal parallel filter implementation
-    fn filter_items_parallel(&self, items: &[&Value], filters: &[PreparedFilter]) -> Vec<Value> {
-        let count = items.len();
-        
-        if THREAD_CONFIG.should_parallelize(count) && !filters.is_empty() {
-            items
-                .par_iter()
-                .filter(|item| self.matches_filters(item, filters))
-                .map(|v| (*v).clone())
-                .collect()
-        } else {
-            items
-                .iter()
-                .filter(|item| self.matches_filters(item, filters))
-                .map(|v| (*v).clone())
-                .collect()
+
+    /// Restore an `export_archive` file as a new database at `target_path` and
+    /// open it with default options, its indexes and schemas registered. Every
+    /// entry is checksummed before anything is written at the target, so a
+    /// damaged archive fails with ERR_CORRUPT and leaves no files behind. The
+    /// target must not exist.
+    #[napi]
+    pub fn import_archive(archive_path: String, target_path: String) -> Result<NativeDB> {
+        let archive = Path::new(&archive_path);
+        let manifest = backup::read_manifest(archive)?;
+        if let Some(existing) = backup::target_files(&manifest, &target_path).into_iter().find(|f| f.exists()) {
+            return Err(DbError::InvalidArgument(format!(
+                "{} already exists; import into a new path", existing.display()
+            )).into());
         }
-    }
-    
-    /// Check if an item matches all filters
-    fn matches_filters(&self, item: &Value, filters: &[PreparedFilter]) -> bool {
-        for filter in filters {
-            if !self.matches_filter(item, filter) {
-                return false;
+        let staging = PathBuf::from(format!("{}.import", target_path));
+        if staging.exists() {
+            fs::remove_dir_all(&staging).map_err(DbError::from)?;
+        }
+        let manifest = backup::unpack(archive, &staging)?;
+        backup::install(&manifest, &staging, &target_path)?;
+
+        let db = Self::new_with_options_internal(target_path, DBOptions::default())?;
+        for entry in manifest.entries.iter().filter(|e| e.kind == backup::EntryKind::Index) {
+            let options = IndexOptions { sparse: entry.sparse };
+            db.register_index(entry.name.clone(), entry.field.clone().unwrap_or_default(), entry.collection.clone(), Some(options))?;
+        }
+        {
+            let mut schemas = db.schemas.write();
+            for (path, schema) in manifest.schemas {
+                let schema: CompiledSchema = serde_json::from_value(schema)
+                    .map_err(|e| DbError::Corrupt(format!("Archived schema for '{}' is invalid: {}", path, e)))?;
+                schemas.insert(path, schema);
             }
         }
-        true
+        Ok(db)
     }
-    
-    /// Check if an item matches a single filter
-    fn matches_filter(&self, item: &Value, filter: &PreparedFilter) -> bool {
-        let parts: Vec<&str> = filter.field.split('.').collect();
-        let mut current = item;
-        
-        for part in &parts {
-            match current {
-                Value::Object(map) => {
-                    if let Some(v) = map.get(*part) {
-                        current = v;
-                    } else {
-                        return false;
-                    }
+
+    /// The manifest of an archive written by `export_archive`: `{format,
+    /// dataFormat, indexFormat, engineVersion, createdAt, source, compressed,
+    /// entries: [{name, kind, field?, collection?, sparse?, bytes, stored, crc32}], schemas}`.
+    /// Only the manifest is read; entries are verified by `import_archive`.
+    #[napi]
+    pub fn archive_manifest(archive_path: String) -> Result<Value> {
+        Ok(backup::read_manifest(Path::new(&archive_path))?.to_json())
+    }
+
+    /// Save history of this process: `{saves, bytesWritten, lastSave}`, where
+    /// `lastSave` is the latest `save_with_report` report (null before the first save)
+    #[napi]
+    pub fn persistence_status(&self) -> Value {
+        self.persistence.lock().status()
+    }
+
+    /// Run maintenance tasks on one background thread, replacing any schedule
+    /// already running (an empty list just stops it). Each entry is `{task,
+    /// intervalMs, args}`: "checkpoint" saves, "save_if_dirty" saves only after
+    /// writes, "purge_expired" drops expired leases, "prune_backups" deletes
+    /// backup snapshots past the newest `args.keep` (default 5) or older than
+    /// `args.maxAgeMs`, which also prunes archived WAL history, and
+    /// "refresh_view" refreshes the view `args.name`. Saves are skipped while a
+    /// transaction or bulk load is open. Every run is traced as "maintenance";
+    /// `close` stops the thread after any running task.
+    #[napi]
+    pub fn schedule_maintenance(&self, tasks: Value) -> Result<()> {
+        self.check_open()?;
+        let Value::Array(entries) = tasks else {
+            return Err(DbError::InvalidArgument("Maintenance tasks must be an array".to_string()).into());
+        };
+        let jobs = entries.iter().map(maintenance::Job::parse).collect::<std::result::Result<Vec<_>, _>>()?;
+        let mut slot = self.maintenance.lock();
+        slot.take();
+        if jobs.is_empty() {
+            return Ok(());
+        }
+        let db = self.handle();
+        *slot = Some(Scheduler::start("maintenance", jobs, move |task| db.run_maintenance(task))?);
+        Ok(())
+    }
+
+    /// What the scheduled maintenance tasks last did: `{running, tasks: [{task,
+    /// intervalMs, args, running, runs, failures, lastRunAt, lastDurationMs,
+    /// lastError, lastResult, nextRunAt}]}`
+    #[napi]
+    pub fn maintenance_status(&self) -> Result<Value> {
+        self.check_open()?;
+        Ok(match &*self.maintenance.lock() {
+            Some(scheduler) => scheduler.status(),
+            None => json!({ "running": false, "tasks": [] }),
+        })
+    }
+
+    /// One run of a maintenance task, on the maintenance thread
+    fn run_maintenance(&self, task: &maintenance::Task) -> std::result::Result<Value, String> {
+        use maintenance::Task;
+        self.traced("maintenance", task.name(), |_| match task {
+            Task::Checkpoint | Task::SaveIfDirty => {
+                if self.transactions.lock().is_active() {
+                    return Ok(json!({ "skipped": "a transaction is active" }));
                 }
-                Value::Array(arr) => {
-                    if let Ok(idx) = part.parse::<usize>() {
-                        if let Some(v) = arr.get(idx) {
-                            current = v;
-                        } else {
-                            return false;
-                        }
-                    } else {
-                        return false;
-                    }
+                if self.bulk_loading.load(Ordering::Acquire) {
+                    return Ok(json!({ "skipped": "a bulk load is running" }));
                 }
-                _ => return false,
+                if matches!(task, Task::SaveIfDirty) && !self.has_unsaved_writes() {
+                    return Ok(json!({ "skipped": "nothing to save" }));
+                }
+                self.save_with_report()
             }
+            Task::PurgeExpired => self.purge_expired(),
+            Task::PruneBackups { keep, max_age_ms } => self.prune_backups(*keep, *max_age_ms),
+            Task::RefreshView(name) => self.refresh_view(name.clone()),
+            Task::SweepTtl(name) => self.sweep_ttl(name),
+        }).map_err(|e| e.reason)
+    }
+
+    /// Whether anything was written, or replayed from the WAL, since the last save
+    fn has_unsaved_writes(&self) -> bool {
+        self.writes.load(Ordering::Acquire) != self.saved_writes.load(Ordering::Acquire)
+            || (self.persistence.lock().saves == 0 && self.load_report.lock().replay.changed())
+    }
+
+    /// Drop expired leases, which are otherwise only pruned by the next acquire
+    fn purge_expired(&self) -> Result<Value> {
+        self.check_recovered()?;
+        let now = now_ms();
+        let mut data = self.data.write();
+        let Some(Value::Object(leases)) = Self::get_internal(&data, "leases") else {
+            return Ok(json!({ "leases": 0 }));
+        };
+        let before = leases.len();
+        let live: serde_json::Map<String, Value> = leases.into_iter()
+            .filter(|(_, l)| l["expiresAt"].as_u64().unwrap_or(0) > now)
+            .collect();
+        let purged = before - live.len();
+        if purged > 0 {
+            self.set_internal(&mut data, "leases", Value::Object(live))?;
         }
-        
-        match filter.op.as_str() {
-            "eq" => current == &filter.value,
-            "ne" => current != &filter.value,
-            "gt" => {
-                if let (Some(a), Some(b)) = (current.as_f64(), filter.value.as_f64()) {
-                    a > b
-                } else {
-                    false
-                }
+        Ok(json!({ "leases": purged }))
+    }
+
+    /// Delete `<db>.<name>.<ts>.bak` snapshots past the newest `keep`, and those
+    /// older than `max_age_ms`, which also prunes archived WAL history that old
+    fn prune_backups(&self, keep: usize, max_age_ms: Option<u64>) -> Result<Value> {
+        self.check_open()?;
+        let mut backups = Self::backup_files(&self.path, false);
+        backups.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        let cutoff = max_age_ms.and_then(|age| std::time::SystemTime::now().checked_sub(std::time::Duration::from_millis(age)));
+        let mut removed = Vec::new();
+        for (i, (modified, path)) in backups.iter().enumerate() {
+            if i < keep && cutoff.is_none_or(|cutoff| *modified >= cutoff) {
+                continue;
             }
-            "gte" => {
-                if let (Some(a), Some(b)) = (current.as_f64(), filter.value.as_f64()) {
-                    a >= b
-                } else {
-                    false
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(DbError::Io(format!("Failed to delete backup {}: {}", path.display(), e)).into());
                 }
+                _ => removed.push(path.to_string_lossy().into_owned()),
             }
-            "lt" => {
-                if let (Some(a), Some(b)) = (current.as_f64(), filter.value.as_f64()) {
-                    a < b
-                } else {
-                    false
-                }
+        }
+        let archive = match max_age_ms {
+            Some(age) if self.options.wal_archive => Archive::new(&self.path).prune(now_ms().saturating_sub(age)).map_err(DbError::from)?,
+            _ => Value::Null,
+        };
+        Ok(json!({ "removed": removed, "kept": backups.len() - removed.len(), "archive": archive }))
+    }
+    
+    /// Legacy WAL append (for internal use)
+    ///
+    /// Honors per-path durability: paths configured "none" are not logged,
+    /// paths configured "sync" wait until their record is fsynced.
+    fn append_wal(&self, op_type: WalOpType, path: &str, value: Option<Value>) -> Result<()> {
+        self.append_wal_record(op_type, path, value, None).map(|_| ())
+    }
+
+    /// `append_wal` with a delete reason, returning the record's LSN (None when not logged)
+    fn append_wal_record(&self, op_type: WalOpType, path: &str, value: Option<Value>, reason: Option<&str>) -> Result<Option<u64>> {
+        // Every write is logged here first, so this is where path rules apply
+        if matches!(op_type, WalOpType::Set) {
+            self.options.path_rules.check(path)?;
+        }
+        // Callers check freezes before changing anything; this catches any that don't
+        if !meta::is_internal_path(path) {
+            self.transactions.lock().check_exclusive()?;
+            self.freezes.check(path)?;
+        }
+        self.note_write(path);
+        if let Some(bulk) = self.bulk_load() {
+            // Made durable by the save ending the load
+            bulk.note_write();
+            return Ok(None);
+        }
+        // Tagged with the transaction whose undo log records the write; engine
+        // metadata (sequences, leases, counters) is not rolled back with it
+        let txn = match meta::is_internal_path(path) {
+            true => None,
+            false => self.transactions.lock().recording(WRITE_TXN.with(|current| current.get())).map(|(id, _)| id),
+        };
+        self.log_wal_op(op_type, path, value, reason, txn)
+    }
+
+    /// Log a write already checked by `append_wal_record`, returning its LSN
+    /// (None when its path's durability skips the WAL)
+    fn log_wal_op(&self, op_type: WalOpType, path: &str, value: Option<Value>, reason: Option<&str>, txn: Option<u32>) -> Result<Option<u64>> {
+        if let Some(ref wal) = self.wal {
+            let durability = self.durability_for(path);
+            if durability == Some(DurabilityMode::None) {
+                return Ok(None);
             }
-            "lte" => {
-                if let (Some(a), Some(b)) = (current.as_f64(), filter.value.as_f64()) {
-                    a <= b
-                } else {
-                    false
-                }
+
+            let op = WalOp {
+                timestamp: now_ms(),
+                op_type,
+                path: path.to_string(),
+                value,
+                context: self.write_context.read().clone(),
+                reason: reason.map(str::to_string),
+                txn,
+            };
+            
+            let lsn = Self::wal_append(wal, op)?;
+
+            if durability == Some(DurabilityMode::Sync) {
+                wal.wait_for_lsn(lsn).map_err(|e| {
+                    DbError::WalFailure(format!("WAL sync failed: {}", e))
+                })?;
             }
-            "contains" => {
-                if let (Some(haystack), Some(needle)) = (current.as_str(), filter.value.as_str()) {
-                    haystack.contains(needle)
-                } else {
-                    false
-                }
+            return Ok(Some(lsn));
+        }
+        Ok(None)
+    }
+
+    fn wal_append(wal: &GroupCommitWAL, op: WalOp) -> Result<u64> {
+        wal.append(op).map_err(|e| match e.kind() {
+            std::io::ErrorKind::WouldBlock => DbError::WalBusy(format!("WAL append rejected: {}", e)),
+            _ => DbError::WalFailure(format!("WAL append failed: {}", e)),
+        }.into())
+    }
+
+    /// Log the Begin, Commit or Abort marker of transaction `txn`. A Commit
+    /// waits for the fsync if the transaction wrote a path configured "sync".
+    fn append_txn_marker(&self, op_type: WalOpType, txn: u32, paths: &[&str]) -> Result<()> {
+        let Some(ref wal) = self.wal else { return Ok(()) };
+        let lsn = Self::wal_append(wal, WalOp {
+            timestamp: now_ms(),
+            op_type,
+            path: String::new(),
+            value: None,
+            context: None,
+            reason: None,
+            txn: Some(txn),
+        })?;
+        if paths.iter().any(|path| self.durability_for(path) == Some(DurabilityMode::Sync)) {
+            wal.wait_for_lsn(lsn).map_err(|e| DbError::WalFailure(format!("WAL sync failed: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    fn wants_removed_values(&self) -> bool {
+        !self.bulk_loading.load(Ordering::Acquire)
+            && self.delete_listener.read().as_ref().is_some_and(|l| l.include_values)
+    }
+
+    /// Copy of a value about to be removed, if the delete listener wants values
+    fn removed_value(&self, data: &Value, path: &str) -> Option<Value> {
+        if !self.wants_removed_values() {
+            return None;
+        }
+        let ptr = Self::to_pointer(path);
+        let mut value = if ptr.is_empty() { data.clone() } else { data.pointer(&ptr)?.clone() };
+        compress::expand(&mut value);
+        Some(value)
+    }
+
+    /// Queue a removal event for the delete listener. Called with the data write
+    /// lock held, so events reach the event loop in WAL order.
+    fn notify_delete(&self, path: &str, reason: Option<&str>, lsn: Option<u64>, value: Option<Value>) {
+        if self.bulk_loading.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(listener) = self.delete_listener.read().as_ref() {
+            let mut event = json!({ "path": path, "reason": reason.unwrap_or("explicit"), "lsn": lsn });
+            if listener.include_values {
+                event["value"] = value.unwrap_or(Value::Null);
             }
-            "startswith" => {
-                if let (Some(haystack), Some(needle)) = (current.as_str(), filter.value.as_str()) {
-                    haystack.starts_with(needle)
-                } else {
-                    false
-                }
+            listener.tsfn.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    /// Validate a delete reason; "explicit" is the default and is not stored
+    fn delete_reason(reason: Option<&str>) -> Result<Option<&str>> {
+        match reason {
+            None | Some("explicit") => Ok(None),
+            Some(r) if DELETE_REASONS.contains(&r) => Ok(Some(r)),
+            Some(r) => Err(DbError::InvalidArgument(format!(
+                "Unknown delete reason '{}': use one of {}", r, DELETE_REASONS.join(", ")
+            )).into()),
+        }
+    }
+
+    /// Longest configured durability prefix covering `path`, if any. The reserved
+    /// namespace always uses the global mode.
+    fn durability_for(&self, path: &str) -> Option<DurabilityMode> {
+        if meta::is_internal_path(path) {
+            return None;
+        }
+        let config = self.path_durability.read();
+        config.iter()
+            .filter(|(prefix, _)| path_within(path, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, mode)| *mode)
+    }
+
+    /// Override durability for writes under `path_prefix` (longest prefix wins):
+    /// - "sync": wait for the write's WAL record to be fsynced before returning
+    /// - "none": skip the WAL entirely; such writes are lost on crash until the next save()
+    /// - "lazy" / "batched": use the WAL without a per-write barrier
+    /// - "default": remove the override
+    ///
+    /// Only applies when the database was opened with a WAL.
+    #[napi]
+    pub fn set_path_durability(&self, path_prefix: String, mode: String) -> Result<()> {
+        self.check_open()?;
+        if meta::is_internal_path(&path_prefix) {
+            return Err(DbError::ReadOnly(format!("Cannot configure durability for '{}'", path_prefix)).into());
+        }
+        let mut config = self.path_durability.write();
+        if mode == "default" {
+            config.remove(&path_prefix);
+            return Ok(());
+        }
+        if !matches!(mode.as_str(), "none" | "lazy" | "batched" | "sync") {
+            return Err(DbError::InvalidArgument(format!("Unknown durability mode '{}'", mode)).into());
+        }
+        config.insert(path_prefix, DurabilityMode::from_str(&mode));
+        Ok(())
+    }
+
+    /// Configured per-path durability overrides as `{ prefix: mode }`
+    #[napi]
+    pub fn durability_config(&self) -> Result<Value> {
+        self.check_open()?;
+        let config = self.path_durability.read();
+        Ok(Value::Object(
+            config.iter()
+                .map(|(prefix, mode)| (prefix.clone(), json!(mode.as_str())))
+                .collect(),
+        ))
+    }
+    
+    /// Stage `set` writes under `path_prefixes` and apply only the latest value
+    /// per path once every `window_ms`, on `flushCoalesced()`, before `save()`,
+    /// `close()` or a transaction, and before any read or other write touching
+    /// a staged path; a read of a staged path itself returns its staged value.
+    /// Staged writes are not in the WAL until applied, so a crash loses up to
+    /// one window of them.
+    #[napi]
+    pub fn enable_write_coalescing(&self, window_ms: u32, path_prefixes: Vec<String>) -> Result<()> {
+        self.check_open()?;
+        if path_prefixes.is_empty() {
+            return Err(DbError::InvalidArgument("At least one path prefix is required".to_string()).into());
+        }
+        for prefix in &path_prefixes {
+            if prefix.is_empty() || meta::is_internal_path(prefix) {
+                return Err(DbError::InvalidArgument(format!("Cannot coalesce writes under '{}'", prefix)).into());
             }
-            "endswith" => {
-                if let (Some(haystack), Some(needle)) = (current.as_str(), filter.value.as_str()) {
-                    haystack.ends_with(needle)
-                } else {
-                    false
-                }
+        }
+        let mut coalescer = self.coalescer.lock();
+        if let Some(current) = coalescer.as_mut() {
+            self.apply_staged(current)?;
+        }
+        *coalescer = Some(Coalescer::new(window_ms, path_prefixes));
+        Ok(())
+    }
+
+    /// Apply pending staged writes and stop coalescing
+    #[napi]
+    pub fn disable_write_coalescing(&self) -> Result<u32> {
+        self.check_open()?;
+        let mut coalescer = self.coalescer.lock();
+        let applied = match coalescer.as_mut() {
+            Some(current) => self.apply_staged(current)?,
+            None => 0,
+        };
+        *coalescer = None;
+        Ok(applied)
+    }
+
+    /// Apply all staged writes now; returns how many paths were written
+    #[napi]
+    pub fn flush_coalesced(&self) -> Result<u32> {
+        self.check_open()?;
+        match self.coalescer.lock().as_mut() {
+            Some(coalescer) => self.apply_staged(coalescer),
+            None => Ok(0),
+        }
+    }
+
+    /// Stage a `set` if its path is coalesced. Returns the value back when the
+    /// write must be applied directly.
+    fn stage_write(&self, path: &str, value: Value) -> Result<Option<Value>> {
+        let mut guard = self.coalescer.lock();
+        let Some(coalescer) = guard.as_mut() else { return Ok(Some(value)) };
+        if !coalescer.covers(path) || self.transactions.lock().is_active() {
+            return Ok(Some(value));
+        }
+        // A staged parent or child must land in its original order
+        if coalescer.conflicts(path) {
+            self.apply_staged(coalescer)?;
+        }
+        coalescer.stage(path.to_string(), value);
+        if coalescer.window_elapsed() {
+            self.apply_staged(coalescer)?;
+        }
+        Ok(None)
+    }
+
+    /// Apply staged writes first if any of them overlaps `path`
+    fn settle_staged(&self, path: &str) -> Result<()> {
+        self.fault_in(path)?;
+        let mut guard = self.coalescer.lock();
+        match guard.as_mut() {
+            Some(coalescer) if coalescer.window_elapsed() || coalescer.overlaps(path) => {
+                self.apply_staged(coalescer)?;
             }
-            "in" => {
-                if let Value::Array(arr) = &filter.value {
-                    arr.contains(current)
-                } else {
-                    false
-                }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The staged value of `path` itself while its window is open
+    fn staged_value(&self, path: &str) -> Option<Value> {
+        let guard = self.coalescer.lock();
+        let coalescer = guard.as_ref().filter(|c| !c.window_elapsed())?;
+        coalescer.staged(path).cloned()
+    }
+
+    /// Load the lazy collection entries `path` reads or writes. A path at or
+    /// above a lazy collection loads all of it, with a warning.
+    fn fault_in(&self, path: &str) -> Result<()> {
+        let Some(lazy) = self.lazy.as_deref() else { return Ok(()) };
+        if !lazy.touches(path) {
+            return Ok(());
+        }
+        let mut store = lazy.store.lock();
+        if !store.needs_load(path) {
+            return Ok(());
+        }
+        let mut data = self.data.write();
+        for (name, count) in store.fault_in(path, &mut data, &lazy.dirty.lock())? {
+            eprintln!("Loaded lazy collection '{}' in full ({} entries) for '{}'", name, count, path);
+        }
+        Ok(())
+    }
+
+    /// WAL-log and apply every staged write. The coalescer lock is held by the
+    /// caller so readers wait until the writes are visible.
+    fn apply_staged(&self, coalescer: &mut Coalescer) -> Result<u32> {
+        if coalescer.is_empty() {
+            return Ok(0);
+        }
+        let staged = coalescer.take();
+        let count = staged.len() as u32;
+        for (path, value) in staged {
+            self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+            let mut data = self.data.write();
+            Self::set_value_at_path(&mut data, &path, value)?;
+        }
+        Ok(count)
+    }
+
+    /// Replay the WAL into `data`, or a legacy line-based `.wal` when the
+    /// group-commit WAL is disabled
+    fn replay(wal_path: &str, wal_enabled: bool, data: &mut Value, lazy: Option<&LazyCollections>, options: &DBOptions, progress: &mut dyn FnMut(ReplayProgress)) -> Replayed {
+        let mut replayed = Replayed::default();
+        let rules = &options.path_rules;
+        // Replayed writes need the lazy entries they modify. Nothing else can
+        // reach the data before recovery completes, so locking the store
+        // after the data lock is safe here.
+        if let Some(lazy) = lazy {
+            if let Err(e) = lazy.store.lock().prime(wal_path, wal_enabled, data, &mut lazy.dirty.lock()) {
+                replayed.error = Some(e.to_string());
+                return replayed;
             }
-            "notin" => {
-                if let Value::Array(arr) = &filter.value {
-                    !arr.contains(current)
-                } else {
-                    false
-                }
+        }
+        if wal_enabled {
+            match recover_from_wal(wal_path, data, rules, options.parallel_recovery, progress) {
+                Ok(stats) => replayed.stats = Some(stats),
+                Err(e) => replayed.error = Some(e.to_string()),
             }
-            "regex" => {
-                if let (Some(s), Some(re)) = (current.as_str(), &filter.regex) {
-                    re.is_match(s)
-                } else {
-                    false
-                }
+        } else if PathBuf::from(wal_path).exists() {
+            match Self::recover_legacy_wal(wal_path, data, rules) {
+                Ok(()) => replayed.legacy = true,
+                Err(e) => replayed.error = Some(e.reason.clone()),
             }
-            "containsAll" => {
-                 if let (Value::Array(curr_arr), Value::Array(req_arr)) = (current, &filter.value) {
-                     req_arr.iter().all(|req| curr_arr.contains(req))
-                 } else {
-                     false
+        }
+        replayed
+    }
+
+    /// Reject calls that need the data until a deferred recovery has finished
+    /// (and all of them once the database is closed)
+    fn check_recovered(&self) -> Result<()> {
+        self.check_open()?;
+        if IN_UPDATER.with(|flag| flag.get()) {
+            return Err(DbError::Conflict(
+                "The database cannot be used from inside an atomicUpdate updater".to_string()
+            ).into());
+        }
+        if self.recovery.complete(|replayed| self.install_recovery(replayed)) {
+            return Ok(());
+        }
+        Err(self.recovery.pending_error().into())
+    }
+
+    /// Reload what the constructor derives from the data, now that the WAL is in it
+    fn install_recovery(&self, replayed: Replayed) {
+        let data = self.data.read();
+        *self.view_watches.write() = Self::load_view_watches(&data);
+        *self.counters.lock() = Self::load_counters(&data, replayed.changed());
+        *self.quotas.lock() = Self::load_quotas(&data);
+        *self.stats_writes.lock() = Self::load_stats_writes(&data);
+        *self.constraints.lock() = Self::load_constraints(&data);
+        self.freezes.load(&Self::load_frozen(&data));
+        if replayed.changed() {
+            self.index_upkeep.lock().values_mut().for_each(Upkeep::invalidate);
+        }
+        self.load_report.lock().replay = replayed;
+    }
+
+    /// Recover from legacy WAL format
+    fn recover_legacy_wal(wal_path: &str, data: &mut Value, rules: &PathRules) -> Result<()> {
+        let file = File::open(wal_path).map_err(DbError::from)?;
+        let reader = BufReader::new(file);
+        
+        for l in reader.lines().map_while(std::result::Result::ok) {
+            if l.trim().is_empty() { continue; }
+            if let Ok(entry) = serde_json::from_str::<WalEntry>(&l) {
+                if entry.op != "delete" && rules.violation(&entry.path).is_some() {
+                    continue;
+                }
+                match entry.op.as_str() {
+                    "set" => {
+                        let _ = Self::set_value_at_path(data, &entry.path, entry.value.unwrap_or(Value::Null));
+                    }
+                    "delete" => {
+                        let _ = Self::delete_value_at_path(data, &entry.path);
+                    }
+                    "push" => {
+                        if let Some(val) = entry.value {
+                            let _ = Self::push_value_at_path(data, &entry.path, val);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
+    // --- Logic Helpers ---
+
+    fn set_value_at_path(root: &mut Value, path_str: &str, value: Value) -> Result<()> {
+        if path_str.is_empty() {
+            *root = value;
+            return Ok(())
+        }
+        
+        let parts: Vec<&str> = path_str.split('.').collect();
+        if parts.is_empty() { return Ok(()) }
+        
+        let last_part = parts.last().unwrap();
+        let parent_parts = &parts[..parts.len()-1];
+        
+        let mut current = root;
+        
+        for (i, part) in parent_parts.iter().enumerate() {
+            if current.is_null() {
+                 *current = Value::Object(serde_json::Map::new());
+            }
+            let is_array_idx = parts[i+1].parse::<usize>().is_ok(); 
+            if let Value::Object(map) = current {
+                if !map.contains_key(*part) {
+                    map.insert(part.to_string(), if is_array_idx { json!([]) } else { json!({}) });
+                }
+                current = map.get_mut(*part).unwrap();
+            } else if let Value::Array(arr) = current {
+                 if let Ok(idx) = part.parse::<usize>() {
+                     while arr.len() <= idx {
+                         arr.push(Value::Null);
+                     }
+                     if arr[idx].is_null() {
+                          let is_next_array = parts.get(i+1).map(|p| p.parse::<usize>().is_ok()).unwrap_or(false);
+                          arr[idx] = if is_next_array { json!([]) } else { json!({}) };
+                     }
+                     current = &mut arr[idx];
+                 } else {
+                     return Err(DbError::PathBlocked("Cannot index array with string".to_string()).into());
                  }
+            } else {
+                 return Err(DbError::PathBlocked(format!("Path segment '{}' blocked by primitive", part)).into());
             }
-            "containsAny" => {
-                 if let (Value::Array(curr_arr), Value::Array(req_arr)) = (current, &filter.value) {
-                     req_arr.iter().any(|req| curr_arr.contains(req))
+        }
+
+        if let Value::Object(map) = current {
+            map.insert(last_part.to_string(), value);
+        } else if let Value::Array(arr) = current {
+            if let Ok(idx) = last_part.parse::<usize>() {
+                while arr.len() <= idx {
+                    arr.push(Value::Null);
+                }
+                arr[idx] = value;
+            } else {
+                 return Err(DbError::PathBlocked("Cannot set non-numeric key on array".to_string()).into());
+            }
+        } else {
+             if current.is_null() {
+                 let is_array = last_part.parse::<usize>().is_ok();
+                 if is_array {
+                     let idx = last_part.parse::<usize>().unwrap();
+                     let mut arr = vec![Value::Null; idx + 1];
+                     arr[idx] = value;
+                     *current = Value::Array(arr);
                  } else {
-                     false
+                     let mut map = serde_json::Map::new();
+                     map.insert(last_part.to_string(), value);
+                     *current = Value::Object(map);
                  }
+             } else {
+                  return Err(DbError::PathBlocked(format!("Parent of '{}' is not an object/array", last_part)).into());
+             }
+        }
+        Ok(())
+    }
+
+    fn delete_value_at_path(root: &mut Value, path_str: &str) -> Result<()> {
+        if path_str.is_empty() {
+            *root = json!({});
+            return Ok(())
+        }
+        let parts: Vec<&str> = path_str.split('.').collect();
+        if parts.is_empty() { return Ok(()) }
+        
+        let parent_path = parts[..parts.len()-1].join(".");
+        let target_key = parts.last().unwrap();
+        
+        let ptr = if parent_path.is_empty() { "".to_string() } else { format!("/{}", parent_path.replace(".", "/")) };
+        
+        let parent = if ptr.is_empty() { Some(root) } else { root.pointer_mut(&ptr) };
+
+        if let Some(p) = parent {
+            if let Value::Object(map) = p {
+                map.shift_remove(*target_key);
+            } else if let Value::Array(arr) = p {
+                if let Ok(idx) = target_key.parse::<usize>() {
+                    if idx < arr.len() {
+                        arr.remove(idx);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push_value_at_path(root: &mut Value, path_str: &str, value: Value) -> Result<()> {
+        let ptr = if path_str.starts_with('/') { path_str.to_string() } else { format!("/{}", path_str.replace(".", "/")) };
+        
+        if let Some(target) = root.pointer_mut(&ptr) {
+            if let Value::Array(arr) = target {
+                // Dedupe: check if value exists
+                if !arr.contains(&value) {
+                     arr.push(value);
+                }
+            } else {
+                return Err(not_an_array(path_str).into());
+            }
+        } else {
+             return Err(DbError::PathNotFound(format!("Path does not exist: {}", path_str)).into());
+        }
+        Ok(())
+    }
+
+    /// Convert a dot-notation path (or JSON pointer) into a JSON pointer
+    fn to_pointer(path: &str) -> String {
+        if path.is_empty() || path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path.replace(".", "/"))
+        }
+    }
+
+    /// `ptr` extended by the key or index `key` of a child, escaped as a JSON
+    /// pointer token (`~` as `~0`, `/` as `~1`), so it finds the same child
+    /// its dot-notation path does
+    fn child_pointer(ptr: &str, key: &str) -> String {
+        format!("{}/{}", ptr, key.replace('~', "~0").replace('/', "~1"))
+    }
+
+    /// `path` in dot notation, as reads take it: a leading slash dropped and
+    /// the rest of the slashes turned into dots
+    fn dotted(path: &str) -> Cow<'_, str> {
+        if path.contains('/') {
+            Cow::Owned(path.trim_start_matches('/').replace('/', "."))
+        } else {
+            Cow::Borrowed(path)
+        }
+    }
+
+    /// `path` as writes address it: a slash-notation path (leading slash) in
+    /// dot notation, any other as is, since writes take other slashes as part
+    /// of a key
+    fn written(path: &str) -> Cow<'_, str> {
+        match path.starts_with('/') {
+            true => Self::dotted(path),
+            false => Cow::Borrowed(path),
+        }
+    }
+
+    /// Join a base path and a relative path (dot or slash notation) into a dot path
+    fn join_path(base: &str, relative: &str) -> String {
+        let relative = relative.strip_prefix('/').map(|r| r.replace('/', ".")).unwrap_or_else(|| relative.to_string());
+        match (base.is_empty(), relative.is_empty()) {
+            (_, true) => base.to_string(),
+            (true, false) => relative,
+            (false, false) => format!("{}.{}", base, relative),
+        }
+    }
+
+    /// Values of an object collection, hiding the reserved namespace at the root
+    fn collection_values<'a>(map: &'a serde_json::Map<String, Value>, ptr: &str) -> Vec<&'a Value> {
+        let is_root = ptr == "/" || ptr.is_empty();
+        map.iter()
+            .filter(|(k, _)| !(is_root && k.as_str() == meta::META_KEY))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    /// Spend `ops` write-rate tokens (free while no limit is set)
+    fn throttle(&self, ops: usize) -> Result<()> {
+        self.write_limiter.acquire(ops.min(u32::MAX as usize) as u32).map_err(Into::into)
+    }
+
+    /// Reject user writes into the reserved namespace, and writes touching a frozen path
+    fn check_writable(&self, path: &str, allow_internal: bool) -> Result<()> {
+        if meta::is_internal_path(path) {
+            if !allow_internal {
+                return Err(DbError::ReadOnly(format!(
+                    "Path '{}' is inside the reserved '{}' namespace", path, meta::META_KEY
+                )).into());
+            }
+            return Ok(());
+        }
+        self.transactions.lock().check_exclusive()?;
+        self.freezes.check(path).map_err(Into::into)
+    }
+
+    /// Read a section of the reserved namespace
+    fn get_internal(data: &Value, section: &str) -> Option<Value> {
+        meta::get(data, section).cloned()
+    }
+
+    /// Write a section of the reserved namespace (WAL-logged). Must be called
+    /// with the write lock held.
+    fn set_internal(&self, data: &mut Value, section: &str, value: Value) -> Result<()> {
+        if data.get(meta::META_KEY).is_none() {
+            let mut ns = meta::new_namespace();
+            ns[section] = value.clone();
+            self.append_wal(WalOpType::Set, meta::META_KEY, Some(ns))?;
+        } else {
+            self.append_wal(WalOpType::Set, &meta::internal_path(section), Some(value.clone()))?;
+        }
+        meta::set(data, section, value);
+        Ok(())
+    }
+
+    // ============================================
+    // PARALLEL OPERATIONS
+    // ============================================
+
+    /// Execute batch set operations in parallel when beneficial. Values are
+    /// validated against registered schemas on the rayon pool before the write
+    /// lock is taken.
+    #[napi]
+    pub fn batch_set_parallel(&self, operations: Vec<(String, Value)>, options: Option<BatchSetOptions>) -> Result<ParallelResult> {
+        self.check_recovered()?;
+        self.flush_coalesced()?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        let skip_invalid = match options.on_invalid.as_deref() {
+            None | Some("reject") => false,
+            Some("skip") => true,
+            Some(other) => return Err(DbError::InvalidArgument(format!("Unknown onInvalid '{}': use 'reject' or 'skip'", other)).into()),
+        };
+        for (path, value) in &operations {
+            if path.is_empty() {
+                return Err(DbError::InvalidArgument("batchSetParallel cannot target the root; use set".to_string()).into());
+            }
+            // Internal paths are skipped when applying
+            self.check_writable(path, true)?;
+            self.check_value_size(path, value)?;
+        }
+        self.throttle(operations.len())?;
+        for (path, _) in &operations {
+            self.fault_in(path)?;
+        }
+
+        let mut invalid = Vec::new();
+        if options.validate.unwrap_or(true) || self.options.enforce_schemas {
+            invalid = self.validate_batch(&operations);
+            if !invalid.is_empty() && !skip_invalid {
+                return Ok(ParallelResult {
+                    success: false,
+                    count: 0,
+                    error: Some(format!("{} of {} values failed schema validation", invalid.len(), operations.len())),
+                    invalid: Some(invalid),
+                });
+            }
+        }
+        let failed: std::collections::HashSet<usize> = invalid.iter()
+            .filter_map(|issue| issue["index"].as_u64())
+            .map(|i| i as usize)
+            .collect();
+        let operations: Vec<(usize, String, Value)> = operations.into_iter()
+            .enumerate()
+            .filter(|(i, _)| !failed.contains(i))
+            .map(|(i, (path, value))| (i, path, value))
+            .collect();
+        let count = operations.len();
+        
+        if THREAD_CONFIG.should_parallelize(count) {
+            // Pre-validate paths in parallel
+            let validation_results: Vec<bool> = operations
+                .par_iter()
+                .map(|(_, path, _)| !path.is_empty() && !meta::is_internal_path(path))
+                .collect();
+            
+            if validation_results.iter().any(|&v| !v) {
+                return Ok(ParallelResult {
+                    success: false,
+                    count: 0,
+                    error: Some("Invalid path in batch".to_string()),
+                    invalid: (!invalid.is_empty()).then_some(invalid),
+                });
+            }
+        }
+
+        // Apply all operations (requires sequential write lock)
+        let success_count = self.apply_batch(operations, &mut invalid)?;
+        Ok(ParallelResult {
+            success: true,
+            count: success_count,
+            error: None,
+            invalid: (!invalid.is_empty()).then_some(invalid),
+        })
+    }
+
+    /// Apply indexed batch writes under one write lock, returning how many were
+    /// applied. Writes refused by a collection quota or constraint, or by the
+    /// array padding policy, are added to `invalid`.
+    fn apply_batch(&self, operations: Vec<(usize, String, Value)>, invalid: &mut Vec<Value>) -> Result<u32> {
+        let guards_active = !self.quotas.lock().is_empty() || !self.constraints.lock().is_empty()
+            || !self.custom_indexes.lock().is_empty();
+        let padding = self.padding();
+        // Record the old values of an open transaction in one pass under the
+        // read lock. Writes a guard or the padding policy may redirect are
+        // recorded once resolved, as `set` does.
+        let locked = |path: &str| (guards_active && self.guarded(path)) || padding::applies(path, padding);
+        let recording = self.transactions.lock().is_active();
+        if recording {
+            let data = self.data.read();
+            for (_, path, _) in operations.iter().filter(|(_, path, _)| !meta::is_internal_path(path) && !locked(path)) {
+                self.record_undo_in(&data, path)?;
+            }
+        }
+        let mut data = self.data.write();
+        let mut success_count = 0u32;
+        
+        for (index, path, mut value) in operations {
+            if path.is_empty() || meta::is_internal_path(&path) {
+                continue;
+            }
+            let record = recording && locked(&path);
+            let path = match padding::resolve(&data, &path, padding) {
+                Ok(resolved) => resolved.into_owned(),
+                Err(e) => {
+                    invalid.push(json!({ "index": index, "path": path, "error": Error::from(e).reason }));
+                    continue;
+                }
+            };
+            if guards_active && self.guarded(&path) {
+                if let Err(e) = self.guard_write(&mut data, &path, &mut value, &[]) {
+                    invalid.push(json!({ "index": index, "path": path, "error": e.reason }));
+                    continue;
+                }
+            }
+            if record {
+                if let Err(e) = self.record_undo_in(&data, &path) {
+                    invalid.push(json!({ "index": index, "path": path, "error": e.reason }));
+                    continue;
+                }
+            }
+            let _ = self.append_wal(WalOpType::Set, &path, Some(value.clone()));
+            if Self::set_value_at_path(&mut data, &path, value).is_ok() {
+                success_count += 1;
+            }
+        }
+        invalid.sort_by_key(|issue| issue["index"].as_u64());
+        Ok(success_count)
+    }
+
+    /// Schema issues for a batch of writes as `[{index, path, error}]`. Each
+    /// path's registered schema is looked up once per parent path; the values
+    /// are then checked in parallel.
+    fn validate_batch(&self, operations: &[(String, Value)]) -> Vec<Value> {
+        let schemas = self.schemas.read();
+        if schemas.is_empty() || self.schemas_suspended() {
+            return Vec::new();
+        }
+        let mut by_parent: HashMap<&str, Option<(&String, &CompiledSchema)>> = HashMap::new();
+        let resolved: Vec<std::result::Result<Option<&Schema>, schema::ValidationError>> = operations.iter()
+            .map(|(path, _)| {
+                let found = match schemas.get_key_value(path.as_str()) {
+                    Some(found) => Some(found),
+                    None => {
+                        let parent = path.rsplit_once('.').map(|(parent, _)| parent).unwrap_or("");
+                        *by_parent.entry(parent).or_insert_with(|| governing_schema(&schemas, parent))
+                    }
+                };
+                let Some((prefix, schema)) = found else { return Ok(None) };
+                let parts: Vec<&str> = path.split('.').collect();
+                schema::resolve(schema, &parts[prefix.split('.').count()..])
+            })
+            .collect();
+
+        type Resolved<'s> = std::result::Result<Option<&'s Schema>, schema::ValidationError>;
+        let check = |(index, ((path, value), schema)): (usize, (&(String, Value), &Resolved))| {
+            let error = match schema {
+                Ok(schema) => validate(value, (*schema)?).err()?,
+                // The key itself is not allowed
+                Err(e) => return Some(json!({ "index": index, "path": path, "error": e.to_string() })),
+            };
+            Some(json!({ "index": index, "path": path, "error": error.to_string() }))
+        };
+        if THREAD_CONFIG.should_parallelize(operations.len()) {
+            operations.par_iter().zip(resolved.par_iter()).enumerate().filter_map(check).collect()
+        } else {
+            operations.iter().zip(resolved.iter()).enumerate().filter_map(check).collect()
+        }
+    }
+
+    /// Parallel filter/query on a collection. Answered from an index when the
+    /// first filter allows it (see planner.rs); `explain_query` tells which.
+    #[napi]
+    pub fn parallel_query(&self, path: String, filters: Vec<QueryFilter>, options: Option<QueryOptions>) -> Result<Value> {
+        self.traced("query", &path, |span| {
+            self.check_recovered()?;
+            self.check_filters(&filters)?;
+            self.settle_staged(&path)?;
+            let emit = Emit::parse(options.and_then(|o| o.emit).as_deref())?;
+            let live = self.data.read();
+            let data = self.committed_root(&live, &[&path])?;
+            let planned = match data {
+                Cow::Borrowed(data) => self.plan_query(data, &path, &filters, emit),
+                Cow::Owned(_) => Err("an exclusive transaction has written to it".to_string()),
+            };
+            let result = match planned {
+                Ok(plan) => {
+                    span.scanned = Some(plan.candidates.len());
+                    span.parallel = THREAD_CONFIG.should_parallelize(plan.candidates.len());
+                    self.query_planned(&data, &path, plan, &filters, emit)
+                }
+                Err(_) => {
+                    Self::trace_scan(span, &data, &path, !filters.is_empty());
+                    self.query_in(&data, path.clone(), filters, emit)?
+                }
+            };
+            let result = self.hide_expired_results(&path, emit, result);
+            span.returned(&result);
+            self.check_result_size(&result, "parallelQuery")?;
+            Ok(result)
+        })
+    }
+
+    /// Leave the documents of a TTL collection whose expiry has passed out of
+    /// query results, as emitted by `emit`
+    fn hide_expired_results(&self, collection: &str, emit: Emit, mut result: Value) -> Value {
+        let fields = self.ttl.fields_of(collection);
+        if fields.is_empty() {
+            return result;
+        }
+        let now = now_ms();
+        let before = trace::items(Some(&result));
+        match (&mut result, emit) {
+            (Value::Array(docs), Emit::Values) => docs.retain(|doc| !ttl::expired(doc, &fields, now)),
+            (Value::Array(rows), Emit::Entries | Emit::Indexed) => rows.retain(|row| !ttl::expired(&row["value"], &fields, now)),
+            (Value::Object(docs), Emit::Object) => docs.retain(|_, doc| !ttl::expired(doc, &fields, now)),
+            _ => {}
+        }
+        self.ttl.note_hidden(before - trace::items(Some(&result)));
+        result
+    }
+
+    /// How `parallel_query` would run: `{strategy: "index" | "scan", index,
+    /// field, op, candidates, estimatedCandidates, collectionSize, stats,
+    /// reason}`. `index`, `field`, `op` and `candidates` are null for a scan,
+    /// and `reason` says why there is no index plan. `stats` is what
+    /// `collection_stats` reports, with just the first filter's field, and
+    /// `estimatedCandidates` what the planner expected from them.
+    #[napi]
+    pub fn explain_query(&self, path: String, filters: Vec<QueryFilter>, options: Option<QueryOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.check_filters(&filters)?;
+        self.settle_staged(&path)?;
+        let emit = Emit::parse(options.and_then(|o| o.emit).as_deref())?;
+        let data = self.data.read();
+        let ptr = Self::to_pointer(&path);
+        let size = trace::items(if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) });
+        // The stats the planner reads: those of the first filter's field
+        let mut stats = self.stats_report(&data, &path.trim_start_matches('/').replace('/', "."));
+        if let (Value::Object(record), Some(first)) = (&mut stats, filters.first()) {
+            let field = record.shift_remove("fields").and_then(|mut fields| fields.get_mut(&first.field).map(Value::take));
+            record.insert("field".to_string(), field.unwrap_or(Value::Null));
+        }
+        Ok(match self.plan_query(&data, &path, &filters, emit) {
+            Ok(plan) => json!({
+                "strategy": "index",
+                "index": plan.index,
+                "field": filters[0].field,
+                "op": filters[0].op,
+                "candidates": plan.candidates.len(),
+                "estimatedCandidates": plan.estimate.map(|share| (share * size as f64).round() as u64),
+                "collectionSize": size,
+                "stats": stats,
+                "reason": null,
+            }),
+            Err(reason) => json!({
+                "strategy": "scan",
+                "index": null,
+                "field": null,
+                "op": null,
+                "candidates": null,
+                "estimatedCandidates": null,
+                "collectionSize": size,
+                "stats": stats,
+                "reason": reason,
+            }),
+        })
+    }
+
+    /// Scan the collection at `collection_path` and store planner statistics
+    /// for it: its entry count and, per field with a tracked index, the number
+    /// of indexed documents, distinct keys, lowest and highest key and the most
+    /// common keys. Returns the stored record, as `collection_stats` would.
+    #[napi]
+    pub fn analyze(&self, collection_path: String, options: Option<AnalyzeOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        let mut names: Vec<String> = self.index_upkeep.lock().iter()
+            .filter(|(_, upkeep)| upkeep.collection == collection_path)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+
+        let record = {
+            let data = self.data.read();
+            let ptr = Self::to_pointer(&collection_path);
+            let docs = match data.pointer(&ptr) {
+                Some(Value::Object(map)) if !collection_path.is_empty() => Self::collection_values(map, &ptr),
+                Some(Value::Array(items)) => items.iter().collect(),
+                Some(_) => return Err(DbError::InvalidArgument(format!("'{}' is not a collection", collection_path)).into()),
+                None => return Err(DbError::PathNotFound(format!("Path does not exist: {}", collection_path)).into()),
+            };
+            let parallel = THREAD_CONFIG.should_parallelize(docs.len());
+            let indexes = self.indexes.read();
+            let mut fields = serde_json::Map::new();
+            for name in &names {
+                let Some(idx) = indexes.get(name) else { continue };
+                if !fields.contains_key(idx.field()) {
+                    fields.insert(idx.field().to_string(), stats::field_stats(name, idx, &docs, parallel));
+                }
+            }
+            json!({
+                "entries": docs.len(),
+                "analyzedAt": now_ms(),
+                "staleAfter": options.and_then(|o| o.stale_after).map_or(stats::default_stale_after(docs.len()), u64::from),
+                "fields": fields,
+            })
+        };
+
+        let mut data = self.data.write();
+        let mut all = Self::get_internal(&data, "stats").unwrap_or_else(|| json!({}));
+        all[collection_path.as_str()] = record;
+        self.set_internal(&mut data, "stats", all)?;
+        self.stats_writes.lock().insert(collection_path.clone(), 0);
+        Ok(self.stats_report(&data, &collection_path))
+    }
+
+    /// Planner statistics of the collection at `path`, or null if it was never
+    /// analyzed: the stored `{entries, analyzedAt, staleAfter, fields}` plus
+    /// `currentEntries`, `writes` since the analyze and `stale`
+    #[napi]
+    pub fn collection_stats(&self, path: String) -> Result<Value> {
+        self.check_recovered()?;
+        let data = self.data.read();
+        Ok(self.stats_report(&data, &path))
+    }
+
+    fn stats_report(&self, data: &Value, collection: &str) -> Value {
+        let Some(mut record) = meta::get(data, "stats").and_then(|stats| stats.get(collection)).cloned() else {
+            return Value::Null;
+        };
+        let writes = self.stats_writes.lock().get(collection).copied().unwrap_or(0);
+        record["currentEntries"] = json!(trace::items(data.pointer(&Self::to_pointer(collection))));
+        record["writes"] = json!(writes);
+        record["stale"] = json!(writes >= record["staleAfter"].as_u64().unwrap_or(0));
+        record
+    }
+
+    /// Writes since each analyze, as far as the data tells: how far the
+    /// collection's entry count has drifted
+    fn load_stats_writes(data: &Value) -> HashMap<String, u64> {
+        let Some(Value::Object(stats)) = meta::get(data, "stats") else { return HashMap::new() };
+        stats.iter()
+            .map(|(collection, record)| {
+                let current = trace::items(data.pointer(&Self::to_pointer(collection))) as u64;
+                (collection.clone(), current.abs_diff(record["entries"].as_u64().unwrap_or(0)))
+            })
+            .collect()
+    }
+
+    /// `parallel_query` over several collections at once. The same filters run
+    /// over each (collections in parallel, large ones in parallel within), the
+    /// matches are concatenated in `paths` order, then sorted, offset and limited
+    /// as one list. Returns `{results, missing}`: a collection that does not
+    /// exist is listed in `missing` rather than failing the query. With
+    /// `tagSource`, matching objects carry their collection's path as `_source`;
+    /// other documents are returned as they are.
+    #[napi]
+    pub fn parallel_query_multi(&self, paths: Vec<String>, filters: Vec<QueryFilter>, options: Option<MultiQueryOptions>) -> Result<Value> {
+        self.traced("queryMulti", &paths.join(","), |span| {
+            self.check_recovered()?;
+            self.check_filters(&filters)?;
+            for path in &paths {
+                self.settle_staged(path)?;
+            }
+            let options = options.unwrap_or_default();
+            let prepared = self.prepare_filters(&filters);
+            let live = self.data.read();
+            let data = self.committed_root(&live, &paths.iter().map(String::as_str).collect::<Vec<_>>())?;
+            let collections: Vec<Option<Vec<&Value>>> = paths.iter().map(|path| {
+                let ptr = Self::to_pointer(path);
+                match if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) } {
+                    None => None,
+                    Some(Value::Object(map)) => Some(Self::collection_values(map, &ptr)),
+                    Some(Value::Array(arr)) => Some(arr.iter().collect()),
+                    Some(_) => Some(Vec::new()),
+                }
+            }).collect();
+            let scanned = collections.iter().flatten().map(Vec::len).sum();
+            span.scanned = Some(scanned);
+            span.parallel = !prepared.is_empty() && (THREAD_CONFIG.should_parallelize(scanned) || THREAD_CONFIG.use_parallel && paths.len() > 1);
+
+            let per_collection: Vec<Vec<&Value>> = if THREAD_CONFIG.use_parallel && collections.len() > 1 {
+                collections.par_iter().map(|items| Self::matching_items(items.as_deref().unwrap_or_default(), &prepared)).collect()
+            } else {
+                collections.iter().map(|items| Self::matching_items(items.as_deref().unwrap_or_default(), &prepared)).collect()
+            };
+            let mut matched: Vec<(usize, &Value)> = per_collection.into_iter()
+                .enumerate()
+                .flat_map(|(source, docs)| docs.into_iter().map(move |doc| (source, doc)))
+                .collect();
+            if let Some(field) = &options.sort_by {
+                let field = split_field(field);
+                let descending = options.descending.unwrap_or(false);
+                // Stable: ties keep collection order
+                matched.sort_by(|a, b| {
+                    let ordering = compare_sort_values(resolve_field(a.1, &field).as_deref(), resolve_field(b.1, &field).as_deref());
+                    if descending { ordering.reverse() } else { ordering }
+                });
+            }
+
+            let tag = options.tag_source.unwrap_or(false);
+            let results: Vec<Value> = matched.into_iter()
+                .skip(options.offset.unwrap_or(0) as usize)
+                .take(options.limit.map_or(usize::MAX, |n| n as usize))
+                .map(|(source, doc)| {
+                    let mut doc = doc.clone();
+                    compress::expand(&mut doc);
+                    if let (true, Value::Object(map)) = (tag, &mut doc) {
+                        map.insert("_source".to_string(), json!(paths[source]));
+                    }
+                    doc
+                })
+                .collect();
+            let missing: Vec<&String> = paths.iter().zip(&collections)
+                .filter(|(_, items)| items.is_none())
+                .map(|(path, _)| path)
+                .collect();
+            let result = json!({ "results": results, "missing": missing });
+            span.returned(&result["results"]);
+            self.check_result_size(&result, "parallelQueryMulti")?;
+            Ok(result)
+        })
+    }
+
+    /// Items matching every filter, in order; large collections are filtered on the pool
+    fn matching_items<'a>(items: &[&'a Value], filters: &[PreparedFilter]) -> Vec<&'a Value> {
+        if THREAD_CONFIG.should_parallelize(items.len()) && !filters.is_empty() {
+            items.par_iter().filter(|item| Self::matches_filters(item, filters)).copied().collect()
+        } else {
+            items.iter().filter(|item| Self::matches_filters(item, filters)).copied().collect()
+        }
+    }
+
+    /// The index plan for a live query, or why it has to scan. Brings the
+    /// chosen index up to date first.
+    fn plan_query(&self, data: &Value, path: &str, filters: &[QueryFilter], emit: Emit) -> std::result::Result<planner::Plan, String> {
+        let Some(first) = filters.first() else { return Err("there are no filters".to_string()) };
+        let collection = path.trim_start_matches('/').replace('/', ".");
+        if collection.is_empty() {
+            return Err("the root is never indexed".to_string());
+        }
+        match data.pointer(&Self::to_pointer(&collection)) {
+            Some(Value::Array(_)) => {}
+            Some(Value::Object(_)) if emit != Emit::Indexed => {}
+            _ => return Err(format!("'{}' is not a collection the query can read", collection)),
+        }
+        let probe = planner::Probe::of(&PreparedFilter::from_query_filter(first, self.equality()))?;
+        let mut names: Vec<String> = self.index_upkeep.lock().iter()
+            .filter(|(_, upkeep)| upkeep.collection == collection)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        let name = names.into_iter()
+            .find(|name| self.indexes.read().get(name).is_some_and(|idx| idx.field() == first.field && probe.fits(idx)))
+            .ok_or_else(|| format!("no index on '{}' of '{}'", first.field, collection))?;
+        self.upkeep_index_in(data, &name);
+        let indexes = self.indexes.read();
+        let idx = indexes.get(&name).ok_or_else(|| format!("index '{}' was dropped", name))?;
+        let analyzed = meta::get(data, "stats").and_then(|stats| stats.get(&collection));
+        let estimate = analyzed.and_then(|record| {
+            probe.estimate(idx, record["fields"].get(&first.field)?, record["entries"].as_u64()?)
+        });
+        if let Some(share) = estimate.filter(|share| *share > stats::MAX_INDEX_SHARE) {
+            return Err(format!(
+                "the stats estimate the index selects {:.0}% of '{}', so a scan is cheaper", share * 100.0, collection
+            ));
+        }
+        Ok(planner::Plan { candidates: probe.candidates(idx), index: name, estimate })
+    }
+
+    /// Run a query over the candidates of `plan`, checking every filter, with
+    /// the results in collection order as a scan would return them
+    fn query_planned(&self, data: &Value, path: &str, plan: planner::Plan, filters: &[QueryFilter], emit: Emit) -> Value {
+        let prepared = self.prepare_filters(filters);
+        let prefix = format!("{}.", path.trim_start_matches('/').replace('/', "."));
+        let keys = plan.candidates.iter().filter_map(|doc| doc.strip_prefix(prefix.as_str()));
+        let mut entries: Vec<(usize, String, &Value)> = match data.pointer(&Self::to_pointer(path)) {
+            Some(Value::Array(arr)) => keys
+                .filter_map(|key| key.parse::<usize>().ok())
+                .filter_map(|i| arr.get(i).map(|doc| (i, i.to_string(), doc)))
+                .collect(),
+            // Entries of an object sit in one allocation in insertion order, so
+            // their addresses give their positions
+            Some(Value::Object(map)) => keys
+                .filter_map(|key| map.get_key_value(key))
+                .map(|(key, doc)| (doc as *const Value as usize, key.clone(), doc))
+                .collect(),
+            _ => Vec::new(),
+        };
+        entries.sort_unstable_by_key(|(position, _, _)| *position);
+        entries.dedup_by_key(|(position, _, _)| *position);
+        let matches = |(_, _, doc): &&(usize, String, &Value)| Self::matches_filters(doc, &prepared);
+        let matched: Vec<&(usize, String, &Value)> = if THREAD_CONFIG.should_parallelize(entries.len()) {
+            entries.par_iter().filter(matches).collect()
+        } else {
+            entries.iter().filter(matches).collect()
+        };
+        let mut result = match emit {
+            Emit::Values => Value::Array(matched.into_iter().map(|(_, _, doc)| (*doc).clone()).collect()),
+            Emit::Entries => Value::Array(matched.into_iter().map(|(_, key, doc)| json!({ "key": key, "value": doc })).collect()),
+            Emit::Object => Value::Object(matched.into_iter().map(|(_, key, doc)| (key.clone(), (*doc).clone())).collect()),
+            Emit::Indexed => Value::Array(matched.into_iter().map(|(i, _, doc)| json!({ "index": i, "value": doc })).collect()),
+        };
+        compress::expand(&mut result);
+        result
+    }
+
+    /// Run a string query (see `parse_query` for the grammar) against the
+    /// collection at `path`: matching documents, sorted and paged
+    #[napi]
+    pub fn query_string(&self, path: String, query: String) -> Result<Value> {
+        self.traced("queryString", &path, |span| {
+            self.check_recovered()?;
+            let parsed = query_parse::parse(&query)?;
+            self.settle_staged(&path)?;
+            let filter = parsed.filter.as_ref().map(|expr| PreparedExpr::from_expr(expr, self.equality()));
+            let live = self.data.read();
+            let data = self.committed_root(&live, &[&path])?;
+            let ptr = Self::to_pointer(&path);
+            let items: Vec<&Value> = match if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) } {
+                Some(Value::Object(map)) => Self::collection_values(map, &ptr),
+                Some(Value::Array(arr)) => arr.iter().collect(),
+                _ => Vec::new(),
+            };
+            span.scanned = Some(items.len());
+            span.parallel = filter.is_some() && THREAD_CONFIG.should_parallelize(items.len());
+            let keep = |item: &&&Value| filter.as_ref().is_none_or(|f| f.matches(item));
+            let mut matched: Vec<&Value> = if THREAD_CONFIG.should_parallelize(items.len()) && filter.is_some() {
+                items.par_iter().filter(keep).copied().collect()
+            } else {
+                items.iter().filter(keep).copied().collect()
+            };
+
+            if !parsed.order_by.is_empty() {
+                let keys: Vec<(Vec<String>, bool)> = parsed.order_by.iter().map(|k| (split_field(&k.field), k.descending)).collect();
+                matched.sort_by(|a, b| {
+                    for (field, descending) in &keys {
+                        let ordering = compare_sort_values(resolve_field(a, field).as_deref(), resolve_field(b, field).as_deref());
+                        if ordering != std::cmp::Ordering::Equal {
+                            return if *descending { ordering.reverse() } else { ordering };
+                        }
+                    }
+                    std::cmp::Ordering::Equal
+                });
+            }
+
+            let page = matched.into_iter()
+                .skip(parsed.offset.unwrap_or(0))
+                .take(parsed.limit.unwrap_or(usize::MAX))
+                .cloned();
+            let mut result = Value::Array(page.collect());
+            compress::expand(&mut result);
+            span.returned(&result);
+            self.check_result_size(&result, "queryString")?;
+            Ok(result)
+        })
+    }
+
+    /// Parse a string query without running it: `{where, orderBy, limit, offset}`,
+    /// where `where` nests `{and: [...]}`, `{or: [...]}`, `{not: ...}` and
+    /// `{field, op, value}` filters. Errors give the character position and what was expected.
+    #[napi]
+    pub fn parse_query(query: String) -> Result<Value> {
+        Ok(query_parse::parse(&query)?.to_json())
+    }
+
+    /// One page of a stable walk over the object collection at `path`:
+    /// `{items: [{key, value}], next}`, where `next` is the cursor to pass as
+    /// `after` for the following page (null after the last one). Writes between
+    /// pages never repeat or skip documents; paging.rs has the exact contract.
+    #[napi]
+    pub fn get_page(&self, path: String, options: Option<PageOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        self.check_filters(options.filters.as_deref().unwrap_or_default())?;
+        let by = options.stable_by.unwrap_or_else(|| "key".to_string());
+        let field = (by != "key").then(|| split_field(&by));
+        let limit = match options.limit {
+            Some(0) => return Err(DbError::InvalidArgument("Page limit must be at least 1".to_string()).into()),
+            Some(n) => n as usize,
+            None => 100,
+        };
+        let after = options.after.as_deref().map(paging::Cursor::decode).transpose()?;
+        if let Some(after) = &after {
+            after.check(&path, &by)?;
+        }
+        let filters = self.prepare_filters(&options.filters.unwrap_or_default());
+
+        let live = self.data.read();
+        let data = self.committed_root(&live, &[&path])?;
+        let ptr = Self::to_pointer(&path);
+        let map = match if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) } {
+            Some(Value::Object(map)) => map,
+            Some(Value::Array(_)) => return Err(DbError::InvalidArgument(format!(
+                "Cannot page '{}' stably: array positions shift when elements are removed", path
+            )).into()),
+            Some(_) => return Err(DbError::PathBlocked(format!("Not an object: {}", path)).into()),
+            None => return Ok(json!({ "items": [], "next": null })),
+        };
+        let mut rows: Vec<(Option<Cow<Value>>, &String, &Value)> = map.iter()
+            .filter(|(key, _)| !(ptr.is_empty() && key.as_str() == meta::META_KEY))
+            .map(|(key, doc)| (field.as_ref().and_then(|f| resolve_field(doc, f)), key, doc))
+            .filter(|(value, key, _)| after.as_ref().is_none_or(|a| {
+                paging::compare((value.as_deref(), key), (a.value.as_ref(), &a.key)).is_gt()
+            }))
+            .filter(|(_, _, doc)| Self::matches_filters(doc, &filters))
+            .collect();
+        let order = |a: &(Option<Cow<Value>>, &String, &Value), b: &(Option<Cow<Value>>, &String, &Value)| {
+            paging::compare((a.0.as_deref(), a.1), (b.0.as_deref(), b.1))
+        };
+        let more = rows.len() > limit;
+        if more {
+            rows.select_nth_unstable_by(limit, order);
+            rows.truncate(limit);
+        }
+        rows.sort_unstable_by(order);
+
+        let next = match rows.last() {
+            Some((value, key, _)) if more => json!(paging::Cursor {
+                path: path.clone(),
+                by,
+                value: value.as_deref().cloned(),
+                key: (*key).clone(),
+            }.encode()),
+            _ => Value::Null,
+        };
+        let mut items = Value::Array(rows.into_iter()
+            .map(|(_, key, doc)| json!({ "key": key, "value": doc }))
+            .collect());
+        drop(data);
+        compress::expand(&mut items);
+        self.check_result_size(&items, "getPage")?;
+        Ok(json!({ "items": items, "next": next }))
+    }
+
+    /// Query implementation shared by live data and snapshots
+    fn query_in(&self, data: &Value, path: String, filters: Vec<QueryFilter>, emit: Emit) -> Result<Value> {
+        let mut result = self.query_matches(data, path, filters, emit)?;
+        compress::expand(&mut result);
+        Ok(result)
+    }
+
+    fn query_matches(&self, data: &Value, path: String, filters: Vec<QueryFilter>, emit: Emit) -> Result<Value> {
+        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
+        
+        let collection = if ptr == "/" || ptr.is_empty() {
+            Some(data)
+        } else {
+            data.pointer(&ptr)
+        };
+
+        if emit == Emit::Indexed {
+            let Some(Value::Array(arr)) = collection else {
+                return match collection {
+                    None => Ok(Value::Array(vec![])),
+                    Some(_) => Err(DbError::NotAnArray(format!("emit 'indexed' needs an array collection: {}", ptr.trim_start_matches('/').replace('/', "."))).into()),
+                };
+            };
+            let prepared = self.prepare_filters(&filters);
+            return Ok(Value::Array(Self::matching_indices(arr, &prepared)
+                .into_iter()
+                .map(|index| json!({ "index": index, "value": arr[index] }))
+                .collect()));
+        }
+
+        if emit != Emit::Values {
+            let entries: Vec<(String, &Value)> = match collection {
+                Some(Value::Object(map)) => {
+                    let is_root = ptr == "/" || ptr.is_empty();
+                    map.iter()
+                        .filter(|(k, _)| !(is_root && k.as_str() == meta::META_KEY))
+                        .map(|(k, v)| (k.clone(), v))
+                        .collect()
+                }
+                Some(Value::Array(arr)) => arr.iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+                _ => Vec::new(),
+            };
+            let prepared = self.prepare_filters(&filters);
+            let matches = |(_, item): &&(String, &Value)| Self::matches_filters(item, &prepared);
+            let matched: Vec<(String, Value)> = if THREAD_CONFIG.should_parallelize(entries.len()) && !prepared.is_empty() {
+                entries.par_iter().filter(matches).map(|(k, v)| (k.clone(), (*v).clone())).collect()
+            } else {
+                entries.iter().filter(matches).map(|(k, v)| (k.clone(), (*v).clone())).collect()
+            };
+            return Ok(match emit {
+                Emit::Object => Value::Object(matched.into_iter().collect()),
+                _ => Value::Array(matched.into_iter().map(|(key, value)| json!({ "key": key, "value": value })).collect()),
+            });
+        }
+        
+        match collection {
+            Some(Value::Object(map)) => {
+                let items: Vec<&Value> = Self::collection_values(map, &ptr);
+                let prepared = self.prepare_filters(&filters);
+                let filtered = self.filter_items_parallel(&items, &prepared);
+                Ok(Value::Array(filtered))
+            }
+            Some(Value::Array(arr)) => {
+                let items: Vec<&Value> = arr.iter().collect();
+                let prepared = self.prepare_filters(&filters);
+                let filtered = self.filter_items_parallel(&items, &prepared);
+                Ok(Value::Array(filtered))
+            }
+            _ => Ok(Value::Array(vec![])),
+        }
+    }
+    
+    /// Indexes of the array elements matching every filter, ascending
+    fn matching_indices(arr: &[Value], filters: &[PreparedFilter]) -> Vec<usize> {
+        if THREAD_CONFIG.should_parallelize(arr.len()) && !filters.is_empty() {
+            arr.par_iter().enumerate().filter(|(_, item)| Self::matches_filters(item, filters)).map(|(i, _)| i).collect()
+        } else {
+            arr.iter().enumerate().filter(|(_, item)| Self::matches_filters(item, filters)).map(|(i, _)| i).collect()
+        }
+    }
+
+    /// Internal parallel filter implementation
+    fn filter_items_parallel(&self, items: &[&Value], filters: &[PreparedFilter]) -> Vec<Value> {
+        let count = items.len();
+        
+        if THREAD_CONFIG.should_parallelize(count) && !filters.is_empty() {
+            items
+                .par_iter()
+                .filter(|item| Self::matches_filters(item, filters))
+                .map(|v| (*v).clone())
+                .collect()
+        } else {
+            items
+                .iter()
+                .filter(|item| Self::matches_filters(item, filters))
+                .map(|v| (*v).clone())
+                .collect()
+        }
+    }
+    
+    /// Check if an item matches all filters
+    fn matches_filters(item: &Value, filters: &[PreparedFilter]) -> bool {
+        for filter in filters {
+            if !Self::matches_filter(item, filter) {
+                return false;
+            }
+        }
+        true
+    }
+    
+    /// Check if an item matches a single filter
+    fn matches_filter(item: &Value, filter: &PreparedFilter) -> bool {
+        let current = match resolve_field(item, &filter.field) {
+            Some(v) => v,
+            None => match filter.absent() {
+                Ok(stand_in) => stand_in,
+                Err(verdict) => return verdict,
+            },
+        };
+        let current: &Value = &current;
+        // Field-to-field comparison: a missing sibling follows the same policy
+        let referenced = match &filter.field_ref {
+            Some(parts) => match resolve_field(item, parts) {
+                Some(v) => Some(v),
+                None => match filter.absent() {
+                    Ok(stand_in) => Some(stand_in),
+                    Err(verdict) => return verdict,
+                },
+            },
+            None => None,
+        };
+        let target: &Value = referenced.as_deref().unwrap_or(&filter.value);
+        let equality = filter.equality;
+        
+        match filter.op.as_str() {
+            "eq" => equality.same(current, target),
+            "ne" => !equality.same(current, target),
+            "gt" => {
+                if let (Some(a), Some(b)) = (current.as_f64(), target.as_f64()) {
+                    a > b
+                } else {
+                    false
+                }
+            }
+            "gte" => {
+                if let (Some(a), Some(b)) = (current.as_f64(), target.as_f64()) {
+                    a >= b
+                } else {
+                    false
+                }
+            }
+            "lt" => {
+                if let (Some(a), Some(b)) = (current.as_f64(), target.as_f64()) {
+                    a < b
+                } else {
+                    false
+                }
+            }
+            "lte" => {
+                if let (Some(a), Some(b)) = (current.as_f64(), target.as_f64()) {
+                    a <= b
+                } else {
+                    false
+                }
+            }
+            "contains" => {
+                if let (Some(haystack), Some(needle)) = (current.as_str(), target.as_str()) {
+                    haystack.contains(needle)
+                } else {
+                    false
+                }
+            }
+            "startswith" => {
+                if let (Some(haystack), Some(needle)) = (current.as_str(), target.as_str()) {
+                    haystack.starts_with(needle)
+                } else {
+                    false
+                }
+            }
+            "endswith" => {
+                if let (Some(haystack), Some(needle)) = (current.as_str(), target.as_str()) {
+                    haystack.ends_with(needle)
+                } else {
+                    false
+                }
+            }
+            "in" => {
+                if let Value::Array(arr) = target {
+                    equality.contains(arr, current)
+                } else {
+                    false
+                }
+            }
+            "notin" => {
+                if let Value::Array(arr) = target {
+                    !equality.contains(arr, current)
+                } else {
+                    false
+                }
+            }
+            "regex" => {
+                if let (Some(s), Some(re)) = (current.as_str(), &filter.regex) {
+                    re.is_match(s)
+                } else {
+                    false
+                }
+            }
+            "containsAll" => {
+                 if let (Value::Array(curr_arr), Value::Array(req_arr)) = (current, target) {
+                     req_arr.iter().all(|req| equality.contains(curr_arr, req))
+                 } else {
+                     false
+                 }
+            }
+            "containsAny" => {
+                 if let (Value::Array(curr_arr), Value::Array(req_arr)) = (current, target) {
+                     req_arr.iter().any(|req| equality.contains(curr_arr, req))
+                 } else {
+                     false
+                 }
+            }
+            // Only reached with permissive filters
+            _ => true,
+        }
+    }
+
+    /// Parallel aggregation operations
+    #[napi]
+    pub fn parallel_aggregate(&self, path: String, operation: String, field: Option<String>) -> Result<Value> {
+        self.traced("aggregate", &path, |span| {
+            self.check_recovered()?;
+            self.settle_staged(&path)?;
+            let live = self.data.read();
+            let data = self.committed_root(&live, &[&path])?;
+            Self::trace_scan(span, &data, &path, operation != "count");
+            span.returned = Some(1);
+            self.aggregate_in(&data, path.clone(), operation, field)
+        })
+    }
+
+    /// Aggregation implementation shared by live data and snapshots
+    fn aggregate_in(&self, data: &Value, path: String, operation: String, field: Option<String>) -> Result<Value> {
+        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
+        
+        let collection = if ptr == "/" || ptr.is_empty() {
+            Some(data)
+        } else {
+            data.pointer(&ptr)
+        };
+        
+        let items: Vec<&Value> = match collection {
+            Some(Value::Object(map)) => Self::collection_values(map, &ptr),
+            Some(Value::Array(arr)) => arr.iter().collect(),
+            _ => return Ok(Value::Null),
+        };
+        
+        let count = items.len();
+        
+        match operation.as_str() {
+            "count" => Ok(json!(count)),
+            "sum" => {
+                let field_name = field.unwrap_or_default();
+                let sum: f64 = if THREAD_CONFIG.should_parallelize(count) {
+                    items.par_iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .sum()
+                } else {
+                    items.iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .sum()
+                };
+                Ok(json!(sum))
+            }
+            "avg" => {
+                let field_name = field.unwrap_or_default();
+                let values: Vec<f64> = if THREAD_CONFIG.should_parallelize(count) {
+                    items.par_iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .collect()
+                } else {
+                    items.iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .collect()
+                };
+                if values.is_empty() {
+                    Ok(json!(0.0))
+                } else {
+                    let sum: f64 = values.iter().sum();
+                    Ok(json!(sum / values.len() as f64))
+                }
+            }
+            "min" => {
+                let field_name = field.unwrap_or_default();
+                let min: Option<f64> = if THREAD_CONFIG.should_parallelize(count) {
+                    items.par_iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .reduce(|| f64::INFINITY, |a, b| a.min(b))
+                        .into()
+                } else {
+                    items.iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .reduce(f64::min)
+                };
+                match min {
+                    Some(v) if v != f64::INFINITY => Ok(json!(v)),
+                    _ => Ok(Value::Null),
+                }
+            }
+            "max" => {
+                let field_name = field.unwrap_or_default();
+                let max: Option<f64> = if THREAD_CONFIG.should_parallelize(count) {
+                    items.par_iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .reduce(|| f64::NEG_INFINITY, |a, b| a.max(b))
+                        .into()
+                } else {
+                    items.iter()
+                        .filter_map(|item| self.get_numeric_field(item, &field_name))
+                        .reduce(f64::max)
+                };
+                match max {
+                    Some(v) if v != f64::NEG_INFINITY => Ok(json!(v)),
+                    _ => Ok(Value::Null),
+                }
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// Perform a parallel left outer join between two collections (lookup)
+    #[napi]
+    pub fn parallel_lookup(
+        &self,
+        left_path: String,
+        right_path: String,
+        left_field: String,
+        right_field: String,
+        as_field: String,
+    ) -> Result<Value> {
+        self.traced("lookup", &left_path, |span| {
+            self.check_recovered()?;
+            self.settle_staged(&left_path)?;
+            self.settle_staged(&right_path)?;
+            let live = self.data.read();
+            let data = self.committed_root(&live, &[&left_path, &right_path])?;
+
+            // Helper to get collection items
+            let get_items = |path: &str| -> Option<Vec<&Value>> {
+                let ptr = if path.starts_with('/') { path.to_string() } else { format!("/{}", path.replace(".", "/")) };
+                let collection = if ptr == "/" || ptr.is_empty() {
+                    Some(&*data)
+                } else {
+                    data.pointer(&ptr)
+                };
+                
+                match collection {
+                    Some(Value::Object(map)) => Some(Self::collection_values(map, &ptr)),
+                    Some(Value::Array(arr)) => Some(arr.iter().collect()),
+                    _ => None,
+                }
+            };
+
+            let left_items = get_items(&left_path).ok_or_else(|| DbError::PathNotFound(format!("Left collection not found: {}", left_path)))?;
+            let right_items = get_items(&right_path).ok_or_else(|| DbError::PathNotFound(format!("Right collection not found: {}", right_path)))?;
+
+            span.scanned = Some(left_items.len() + right_items.len());
+            span.parallel = THREAD_CONFIG.should_parallelize(left_items.len());
+
+            // Build hash table on right collection
+            use std::collections::HashMap;
+            let mut hash_table: HashMap<String, Vec<&Value>> = HashMap::new();
+            
+            for item in &right_items {
+                 if let Some(val) = self.get_value_at_field(item, &right_field) {
+                     let key = match val {
+                         Value::String(s) => s.clone(),
+                         _ => val.to_string(),
+                     };
+                     hash_table.entry(key).or_default().push(item);
+                 }
+            }
+
+            // Probe with left collection
+            let results: Vec<Value> = if THREAD_CONFIG.should_parallelize(left_items.len()) {
+                left_items.par_iter().map(|left_item| {
+                    let mut joined = (*left_item).clone();
+                    if let Value::Object(ref mut map) = joined {
+                        let mut matches_curr = Vec::new();
+                        if let Some(val) = self.get_value_at_field(left_item, &left_field) {
+                            let key = match val {
+                                Value::String(s) => s.clone(),
+                                _ => val.to_string(),
+                            };
+                            
+                            if let Some(matches) = hash_table.get(&key) {
+                                for m in matches {
+                                    matches_curr.push((*m).clone());
+                                }
+                            }
+                        }
+                        map.insert(as_field.clone(), Value::Array(matches_curr));
+                    }
+                    joined
+                }).collect()
+            } else {
+                 left_items.iter().map(|left_item| {
+                    let mut joined = (*left_item).clone();
+                    if let Value::Object(ref mut map) = joined {
+                        let mut matches_curr = Vec::new();
+                        if let Some(val) = self.get_value_at_field(left_item, &left_field) {
+                            let key = match val {
+                                Value::String(s) => s.clone(),
+                                _ => val.to_string(),
+                            };
+                            
+                            if let Some(matches) = hash_table.get(&key) {
+                                for m in matches {
+                                    matches_curr.push((*m).clone());
+                                }
+                            }
+                        }
+                        map.insert(as_field.clone(), Value::Array(matches_curr));
+                    }
+                    joined
+                }).collect()
+            };
+
+            let result = Value::Array(results);
+            span.returned(&result);
+            self.check_result_size(&result, "parallelLookup")?;
+            Ok(result)
+        })
+    }
+
+    /// Helper to get arbitrary field value (supports dot notation)
+    fn get_value_at_field<'a>(&self, item: &'a Value, path: &str) -> Option<&'a Value> {
+        let parts: Vec<&str> = path.split('.').collect();
+        let mut current = item;
+        
+        for part in parts {
+            match current {
+                Value::Object(map) => {
+                    if let Some(v) = map.get(part) {
+                        current = v;
+                    } else {
+                        return None;
+                    }
+                }
+                Value::Array(arr) => {
+                    if let Ok(idx) = part.parse::<usize>() {
+                         if let Some(v) = arr.get(idx) {
+                            current = v;
+                         } else {
+                             return None;
+                         }
+                    } else {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(current)
+    }
+    
+    /// Helper to get numeric field value
+    fn get_numeric_field(&self, item: &Value, field: &str) -> Option<f64> {
+        if field.is_empty() {
+            return item.as_f64();
+        }
+        
+        let parts: Vec<&str> = field.split('.').collect();
+        let mut current = item;
+        
+        for part in parts {
+            match current {
+                Value::Object(map) => {
+                    current = map.get(part)?;
+                }
+                Value::Array(arr) => {
+                    let idx: usize = part.parse().ok()?;
+                    current = arr.get(idx)?;
+                }
+                _ => return None,
+            }
+        }
+        
+        current.as_f64()
+    }
+
+    // --- Exposed API ---
+
+    #[napi]
+    pub fn get(&self, path: String, options: Option<ReadOptions>) -> Result<Value> {
+        let Some(tracer) = self.tracer() else { return self.read_path(path, options) };
+        let started = std::time::Instant::now();
+        let traced_path = path.clone();
+        let result = self.read_path(path, options);
+        // Small reads are too frequent to be worth a span each
+        if result.as_ref().map_or(true, |value| approx_size(value) >= tracer.get_threshold) {
+            let mut span = Span::default();
+            if let Ok(value) = &result {
+                span.returned(value);
+            }
+            tracer.emit("get", &traced_path, started, &span, result.as_ref().err().map(|e| e.reason.as_str()));
+        }
+        result
+    }
+
+    fn read_path(&self, path: String, options: Option<ReadOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        let dotted = Self::dotted(&path).into_owned();
+        let options = options.unwrap_or_default();
+        // Reading back a staged path (as set does for its change event) must not
+        // flush the window, or nothing written through set would be coalesced
+        if let Some(mut v) = self.staged_value(&dotted) {
+            compress::expand(&mut v);
+            self.check_result_size(&v, "get")?;
+            if !self.ttl.hide(&self.data.read(), &dotted, &mut v) {
+                return Ok(Value::Null);
+            }
+            return Ok(v);
+        }
+        self.settle_staged(&dotted)?;
+        let include_internal = options.include_internal.unwrap_or(false);
+        if path.is_empty() && include_internal {
+            // A full export is what encrypted databases save: bring counters up to date
+            self.persist_counters();
+            self.persist_quotas();
+        }
+        let data = self.data.read();
+        if let Some(committed) = self.committed_view(&data, &dotted, options.txn)? {
+            let Some(mut v) = committed else { return Ok(Value::Null) };
+            if dotted.is_empty() && !include_internal {
+                v = meta::strip_internal(&v);
+            } else if !include_internal && meta::is_internal_path(&dotted) {
+                return Ok(Value::Null);
+            }
+            compress::expand(&mut v);
+            self.check_result_size(&v, "get")?;
+            if !self.ttl.hide(&data, &dotted, &mut v) {
+                return Ok(Value::Null);
+            }
+            return Ok(v);
+        }
+        if path.is_empty() {
+            self.check_result_size(&data, "get")?;
+            let mut root = if include_internal { data.clone() } else { meta::strip_internal(&data) };
+            if compress::expand(&mut root) {
+                self.check_result_size(&root, "get")?;
+            }
+            self.ttl.hide(&data, "", &mut root);
+            return Ok(root);
+        }
+        if !include_internal && meta::is_internal_path(&path) {
+            return Ok(Value::Null);
+        }
+        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
+        match compress::pointer(&data, &ptr) {
+            Some(v) => {
+                self.check_result_size(&v, "get")?;
+                let mut v = v.into_owned();
+                if compress::expand(&mut v) {
+                    self.check_result_size(&v, "get")?;
+                }
+                if !self.ttl.hide(&data, &dotted, &mut v) {
+                    return Ok(Value::Null);
+                }
+                Ok(v)
+            }
+            None => Ok(Value::Null), 
+        }
+    }
+
+    /// The value at `path` as committed, when an exclusive transaction the read
+    /// (`txn`) is not part of has written at, above or below it; None when the
+    /// live value is what the read should see
+    fn committed_view(&self, data: &Value, path: &str, txn: Option<u32>) -> Result<Option<Option<Value>>> {
+        let entries = self.transactions.lock().uncommitted(path, txn)?;
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let ptr = Self::to_pointer(path);
+        let live = if ptr.is_empty() { Some(data.clone()) } else { compress::pointer(data, &ptr).map(Cow::into_owned) };
+        Self::undo_view(path, live, entries).map(Some)
+    }
+
+    /// The root a read of the collections at `paths` outside the exclusive
+    /// transaction should run against: `data` itself, or when that transaction
+    /// has written at, above or below one of them, a copy holding just their
+    /// committed values and the namespace. Indexes describe the live data, so
+    /// reads of a copy scan.
+    fn committed_root<'a>(&self, data: &'a Value, paths: &[&str]) -> Result<Cow<'a, Value>> {
+        let mut views = Vec::new();
+        for path in paths {
+            let path = Self::dotted(path).into_owned();
+            let committed = self.committed_view(data, &path, None)?;
+            views.push((path, committed));
+        }
+        if views.iter().all(|(_, committed)| committed.is_none()) {
+            return Ok(Cow::Borrowed(data));
+        }
+        // The paths it has not written are copied as they are
+        let mut views: Vec<(String, Option<Value>)> = views.into_iter()
+            .map(|(path, committed)| {
+                let view = committed.unwrap_or_else(|| data.pointer(&Self::to_pointer(&path)).cloned());
+                (path, view)
+            })
+            .collect();
+        if let Some((_, committed)) = views.iter_mut().find(|(path, _)| path.is_empty()) {
+            return Ok(Cow::Owned(committed.take().unwrap_or_else(|| json!({}))));
+        }
+        let mut root = json!({});
+        if let Some(namespace) = data.get(meta::META_KEY) {
+            root[meta::META_KEY] = namespace.clone();
+        }
+        'views: for (path, committed) in views {
+            let Some(value) = committed else { continue };
+            let mut slot = &mut root;
+            for part in path.split('.') {
+                slot = match slot {
+                    Value::Object(map) => map.entry(part.to_string()).or_insert_with(|| json!({})),
+                    // Inside another path's view, which already holds this one
+                    _ => continue 'views,
+                };
+            }
+            *slot = value;
+        }
+        Ok(Cow::Owned(root))
+    }
+
+    /// `get` returning a JSON string. Not subject to the result size cap, and much
+    /// cheaper than a JS object graph for large values.
+    #[napi]
+    pub fn get_serialized(&self, path: String, options: Option<ReadOptions>) -> Result<String> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        let include_internal = options.include_internal.unwrap_or(false);
+        let data = self.data.read();
+        let committed = self.committed_view(&data, &path, options.txn)?;
+        let mut value = if !include_internal && !path.is_empty() && meta::is_internal_path(&path) {
+            Value::Null
+        } else if let Some(committed) = committed {
+            let value = committed.unwrap_or(Value::Null);
+            if path.is_empty() && !include_internal { meta::strip_internal(&value) } else { value }
+        } else if path.is_empty() {
+            if include_internal { data.clone() } else { meta::strip_internal(&data) }
+        } else {
+            compress::pointer(&data, &Self::to_pointer(&path)).map(Cow::into_owned).unwrap_or(Value::Null)
+        };
+        compress::expand(&mut value);
+        if !self.ttl.hide(&data, &path, &mut value) {
+            value = Value::Null;
+        }
+        let serialized = serde_json::to_string(&value);
+        Ok(serialized.map_err(|e| DbError::Io(format!("Serialization failed: {}", e)))?)
+    }
+
+    /// Approximate size in bytes of the value at `path` (0 if missing), as used by the
+    /// result size cap
+    #[napi]
+    pub fn estimate_size(&self, path: String) -> Result<i64> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        if meta::is_internal_path(&path) {
+            return Ok(0);
+        }
+        let data = self.data.read();
+        let ptr = Self::to_pointer(&path);
+        let size = if ptr.is_empty() {
+            approx_size(&meta::strip_internal(&data))
+        } else {
+            data.pointer(&ptr).map(approx_size).unwrap_or(0)
+        };
+        Ok(size as i64)
+    }
+
+    /// Cap the approximate size of results converted to JS values (0 = unlimited,
+    /// default 512MB). Larger results fail with ERR_LIMIT.
+    #[napi]
+    pub fn set_max_result_bytes(&self, max_bytes: i64) -> Result<()> {
+        self.check_open()?;
+        self.max_result_bytes.store(max_bytes.max(0) as usize, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Cap the serialized size of values written under `pathPrefix` (every
+    /// path by default; the longest matching prefix applies). Writes of larger
+    /// values fail with ERR_LIMIT before taking any lock, or in "warn" mode go
+    /// ahead and are reported to the trace callback as `oversizedValue`. The
+    /// size is an estimate of the compact JSON. Null removes the limit.
+    #[napi]
+    pub fn set_max_value_bytes(&self, max_bytes: Option<i64>, options: Option<ValueLimitOptions>) -> Result<()> {
+        self.check_open()?;
+        let options = options.unwrap_or_default();
+        let mode = match options.mode.as_deref() {
+            None => LimitMode::Reject,
+            Some(name) => LimitMode::parse(name).ok_or_else(|| {
+                DbError::InvalidArgument(format!("Unknown value limit mode '{}': use 'reject' or 'warn'", name))
+            })?,
+        };
+        let limit = match max_bytes {
+            None => None,
+            Some(max_bytes) if max_bytes > 0 => Some(Limit { max_bytes: max_bytes as usize, mode }),
+            Some(_) => return Err(DbError::InvalidArgument("maxBytes must be positive; pass null to remove the limit".to_string()).into()),
+        };
+        self.value_limits.set(options.path_prefix.unwrap_or_default(), limit);
+        Ok(())
+    }
+
+    /// Apply the value size limit for `path` to `value`
+    fn check_value_size(&self, path: &str, value: &Value) -> Result<()> {
+        if let Some(warning) = self.value_limits.check(path, value)? {
+            if let Some(tracer) = self.tracer() {
+                tracer.emit("oversizedValue", path, std::time::Instant::now(), &Span::default(), Some(&warning));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_result_size(&self, value: &Value, operation: &str) -> Result<()> {
+        let max = self.max_result_bytes.load(Ordering::Relaxed);
+        if max == 0 {
+            return Ok(());
+        }
+        let size = approx_size(value);
+        if size > max {
+            return Err(DbError::LimitExceeded(format!(
+                "{} result is ~{} bytes, over the {} byte limit; use getSerialized, narrow the query, or raise setMaxResultBytes",
+                operation, size, max
+            )).into());
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn set(&self, path: String, value: Value, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        let allow_internal = options.allow_internal.unwrap_or(false);
+        self.check_writable(&path, allow_internal)?;
+        // Checked again when logged; staged writes are logged only when applied
+        self.options.path_rules.check(&path)?;
+        self.check_lease(&path, options.owner.as_deref())?;
+        let padding = self.padding_for(options.array_padding.as_deref())?;
+        self.check_value_size(&path, &value)?;
+        Self::check_root_overwrite(&path, &options)?;
+        // set_compressed fills in defaults and validates before compressing
+        let mut value = value;
+        if !compress::is_envelope(&value) {
+            value = self.with_schema_defaults(&path, value);
+            if self.options.enforce_schemas {
+                self.check_schema(&path, &value)?;
+            }
+        }
+        self.throttle(1)?;
+
+        if path.is_empty() {
+            Self::keep_namespace(&self.data.read(), &mut value, allow_internal)?;
+        }
+
+        let threshold = self.compress_threshold.load(Ordering::Relaxed);
+        if threshold > 0 && value.as_str().is_some_and(|text| text.len() >= threshold) {
+            value = compress::compress(&value)?;
+        }
+
+        // Coalesced prefixes: keep only the latest value until the window closes.
+        // Paths under a quota or constraint are never staged so every write is
+        // checked, nor are indexes the padding policy has to resolve.
+        let guarded = self.guarded(&path);
+        let locked = guarded || padding::applies(&path, padding);
+        // Nothing is logged during a bulk load, so there is nothing to coalesce
+        let value = if locked || self.bulk_loading.load(Ordering::Acquire) { value } else {
+            let Some(value) = self.stage_write(&path, value)? else { return Ok(()) };
+            value
+        };
+        self.settle_staged(&path)?;
+
+        if locked {
+            let mut value = value;
+            let mut data = self.data.write();
+            let path = padding::resolve(&data, &path, padding)?.into_owned();
+            if guarded {
+                self.guard_write(&mut data, &path, &mut value, &[])?;
+            }
+            self.record_undo_in(&data, &path)?;
+            self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+            return Self::set_value_at_path(&mut data, &path, value);
+        }
+
+        // v5.1 Transaction support
+        self.record_undo(&path)?;
+
+        // Append to WAL first (durability)
+        self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+        
+        // Update memory
+        let mut data = self.data.write();
+        Self::set_value_at_path(&mut data, &path, value)?;
+        Ok(())
+    }
+    
+    /// Store `value` compressed (zstd) at `path`. Reads return the original value.
+    #[napi]
+    pub fn set_compressed(&self, path: String, value: Value, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        let value = self.with_schema_defaults(&path, value);
+        if self.options.enforce_schemas {
+            self.check_schema(&path, &value)?;
+        }
+        self.set(path, compress::compress(&value)?, options)
+    }
+
+    /// Make `set` compress string values of at least `bytes` bytes (0 = off)
+    #[napi]
+    pub fn set_compress_threshold(&self, bytes: i64) -> Result<()> {
+        self.check_open()?;
+        self.compress_threshold.store(bytes.max(0) as usize, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// What writes to an array index past the end do: "pad_null" (the default)
+    /// fills the gap with nulls, "error" rejects the write, "append" writes at
+    /// the end instead. Applies to every write path except WAL replay, which
+    /// repeats the indexes logged.
+    #[napi]
+    pub fn set_array_padding(&self, policy: String) -> Result<()> {
+        self.check_open()?;
+        self.array_padding.store(Padding::parse(&policy)?.to_u8(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The current array padding policy
+    #[napi]
+    pub fn array_padding(&self) -> Result<String> {
+        self.check_open()?;
+        Ok(self.padding().as_str().to_string())
+    }
+
+    fn padding(&self) -> Padding {
+        Padding::from_u8(self.array_padding.load(Ordering::Relaxed))
+    }
+
+    /// The policy for one write: its own override, or the database's
+    fn padding_for(&self, per_call: Option<&str>) -> Result<Padding> {
+        Ok(match per_call {
+            Some(policy) => Padding::parse(policy)?,
+            None => self.padding(),
+        })
+    }
+
+    /// Rewrite the value at `path` in canonical form (sorted keys, integer-valued
+    /// floats as integers, no -0), as one logged write. Compressed values are
+    /// stored expanded.
+    #[napi]
+    pub fn canonicalize(&self, path: String) -> Result<()> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        self.check_writable(&path, false)?;
+        let mut value = {
+            let data = self.data.read();
+            match compress::pointer(&data, &Self::to_pointer(&path)) {
+                Some(value) => value.into_owned(),
+                None => return Err(DbError::PathNotFound(format!("Path does not exist: {}", path)).into()),
+            }
+        };
+        compress::expand(&mut value);
+        if let (true, Value::Object(map)) = (path.is_empty(), &mut value) {
+            // Replacing the root keeps the engine metadata as it is
+            map.shift_remove(meta::META_KEY);
+        }
+        // Rewrites the tree without changing what it holds
+        let options = WriteOptions { allow_root_overwrite: Some(true), ..Default::default() };
+        self.set(path, canonical::canonicalize(&value)?, Some(options))
+    }
+
+    /// Canonical serialization of `value`: keys sorted at every depth, numbers
+    /// normalized, no whitespace. Equal data always yields the same string.
+    #[napi]
+    pub fn canonical_string(value: Value) -> Result<String> {
+        Ok(canonical::to_string(&value)?)
+    }
+
+    /// Resolve several relative pointers below `base_path` under one read lock.
+    /// Returns an object keyed by pointer, with null for misses.
+    #[napi]
+    pub fn get_pointers(&self, base_path: String, pointers: Vec<String>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&base_path)?;
+        let live = self.data.read();
+        let data = self.committed_root(&live, &[&base_path])?;
+        let base_ptr = Self::to_pointer(&base_path);
+        let base = if base_ptr.is_empty() { Some(&*data) } else { data.pointer(&base_ptr) };
+
+        let mut result = serde_json::Map::with_capacity(pointers.len());
+        for pointer in pointers {
+            let full = Self::join_path(&base_path, &pointer);
+            let value = if meta::is_internal_path(&full) {
+                None
+            } else {
+                base.and_then(|b| compress::pointer(b, &Self::to_pointer(&Self::join_path("", &pointer))))
+            };
+            result.insert(pointer, value.map(Cow::into_owned).unwrap_or(Value::Null));
+        }
+        let mut result = Value::Object(result);
+        compress::expand(&mut result);
+        self.check_result_size(&result, "getPointers")?;
+        Ok(result)
+    }
+
+    /// The root path "" replaces or clears the whole tree, so a write to it
+    /// must opt in with `allow_root_overwrite`
+    fn check_root_overwrite(path: &str, options: &WriteOptions) -> Result<()> {
+        if path.is_empty() && !options.allow_root_overwrite.unwrap_or(false) {
+            return Err(DbError::ReadOnly(
+                "Replacing the whole tree needs the allowRootOverwrite option".to_string()
+            ).into());
+        }
+        Ok(())
+    }
+
+    /// Ready `value` to replace the root: it must be an object, and keeps the
+    /// engine metadata found in `data`
+    fn keep_namespace(data: &Value, value: &mut Value, allow_internal: bool) -> Result<()> {
+        let Value::Object(map) = value else {
+            return Err(DbError::InvalidArgument("The root must be an object".to_string()).into());
+        };
+        if !allow_internal && map.contains_key(meta::META_KEY) {
+            return Err(DbError::ReadOnly(format!("Root value must not contain reserved key '{}'", meta::META_KEY)).into());
+        }
+        if let Some(ns) = data.get(meta::META_KEY) {
+            map.entry(meta::META_KEY.to_string()).or_insert_with(|| ns.clone());
+        }
+        Ok(())
+    }
+
+    /// Apply several small writes relative to `base_path` under one write lock
+    #[napi]
+    pub fn set_pointers(&self, base_path: String, entries: Vec<(String, Value)>, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        self.settle_staged(&base_path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        let allow_internal = options.allow_internal.unwrap_or(false);
+        let paths: Vec<String> = entries.iter().map(|(p, _)| Self::join_path(&base_path, p)).collect();
+        for (path, (_, value)) in paths.iter().zip(&entries) {
+            self.check_writable(path, allow_internal)?;
+            self.check_lease(path, options.owner.as_deref())?;
+            self.check_value_size(path, value)?;
+            Self::check_root_overwrite(path, &options)?;
+            if self.options.enforce_schemas {
+                self.check_schema(path, value)?;
+            }
+        }
+        let padding = self.padding_for(options.array_padding.as_deref())?;
+        self.throttle(paths.len())?;
+
+        // Writes applied before a failure stay applied (and undoable)
+        let mut data = self.data.write();
+        paths.into_iter().zip(entries).try_for_each(|(path, (_, mut value))| {
+            if path.is_empty() {
+                Self::keep_namespace(&data, &mut value, allow_internal)?;
+            }
+            let path = padding::resolve(&data, &path, padding)?.into_owned();
+            self.record_undo_in(&data, &path)?;
+            self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+            Self::set_value_at_path(&mut data, &path, value)
+        })
+    }
+
+    /// Check every condition and, only if all pass, apply every op, all under
+    /// one hold of the write lock. Returns whether the ops were applied. Needs
+    /// no transaction, so concurrent callers never contend for one. If an op
+    /// fails (a quota, constraint or type error), the ops before it are undone.
+    #[napi]
+    pub fn atomic(&self, conditions: Vec<Condition>, ops: Vec<BatchOp>, options: Option<AtomicOptions>) -> Result<bool> {
+        self.check_recovered()?;
+        let owner = options.and_then(|o| o.owner);
+        for path in conditions.iter().map(|c| &c.path).chain(ops.iter().map(|o| &o.path)) {
+            self.settle_staged(path)?;
+        }
+        let checks: Vec<(String, Option<PreparedFilter>)> = conditions.iter().map(|c| {
+            let op = c.op.clone();
+            match op.as_str() {
+                "exists" | "notexists" => Ok((op, None)),
+                "eq" | "ne" | "gt" | "gte" | "lt" | "lte" | "contains" | "startswith" | "endswith" | "in" | "notin"
+                | "regex" | "containsAll" | "containsAny" => {
+                    let filter = QueryFilter {
+                        field: String::new(),
+                        op: op.clone(),
+                        value: c.value.clone().unwrap_or(Value::Null),
+                        missing: None,
+                        equality: c.equality.clone(),
+                    };
+                    check_equality(&filter).map_err(|e| DbError::InvalidArgument(format!("Condition on '{}': {}", c.path, e)))?;
+                    let mut prepared = PreparedFilter::from_query_filter(&filter, self.equality());
+                    prepared.field = if c.path.is_empty() { Vec::new() } else { split_field(&c.path) };
+                    Ok((op, Some(prepared)))
+                }
+                other => Err(DbError::InvalidArgument(format!("Unknown condition operator '{}' for '{}'", other, c.path))),
+            }
+        }).collect::<std::result::Result<_, _>>()?;
+        for op in &ops {
+            if !matches!(op.op_type.as_str(), "set" | "delete" | "push" | "add" | "subtract") {
+                return Err(DbError::InvalidArgument(format!(
+                    "Unknown op type '{}': use 'set', 'delete', 'push', 'add' or 'subtract'", op.op_type
+                )).into());
+            }
+            if op.path.is_empty() {
+                return Err(DbError::InvalidArgument("atomic ops cannot target the root; use set or delete".to_string()).into());
+            }
+            if op.op_type != "delete" && op.value.is_none() {
+                return Err(DbError::InvalidArgument(format!("'{}' of '{}' needs a value", op.op_type, op.path)).into());
+            }
+            self.check_writable(&op.path, false)?;
+            self.check_lease(&op.path, owner.as_deref())?;
+            if op.op_type != "delete" {
+                self.options.path_rules.check(&op.path)?;
+            }
+            if let Some(value) = &op.value {
+                self.check_value_size(&op.path, value)?;
+            }
+            // Other ops are checked once their result is known
+            if let (true, "set", Some(value)) = (self.options.enforce_schemas, op.op_type.as_str(), &op.value) {
+                self.check_schema(&op.path, value)?;
+            }
+        }
+        self.throttle(ops.len())?;
+
+        let mut data = self.data.write();
+        let passed = checks.iter().zip(&conditions).all(|((op, filter), condition)| match filter {
+            Some(filter) => Self::matches_filter(&data, filter),
+            None => {
+                let exists = compress::pointer(&data, &Self::to_pointer(&condition.path)).is_some();
+                exists == (op == "exists")
+            }
+        });
+        if !passed {
+            return Ok(false);
+        }
+
+        // Old values of the ops applied so far, to put back if a later op fails
+        let mut applied: Vec<(String, Option<Value>)> = Vec::with_capacity(ops.len());
+        for op in ops {
+            if let Err(e) = self.apply_atomic_op(&mut data, &op, &mut applied) {
+                self.revert_applied(&mut data, applied);
+                return Err(e);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read-modify-write of one path with a synchronous JS updater
+    /// `(current) => next`, called with the write lock held so no other write
+    /// can land between the read and the write. `current` is undefined when the
+    /// path is missing; returning undefined leaves the value as it is. The new
+    /// value is checked against its schema, then logged and undoable like a
+    /// `set`. Returns the value now stored (null if still missing).
+    ///
+    /// Every other writer waits while the updater runs, so it must be short and
+    /// must not call back into the database (such calls fail). An updater that
+    /// runs longer than `maxCallbackMs` has its result discarded and the call
+    /// fails with ERR_LIMIT; Node cannot interrupt it, so one that never
+    /// returns still hangs.
+    #[napi]
+    pub fn atomic_update(&self, env: Env, path: String, updater: JsFunction, options: Option<AtomicUpdateOptions>) -> Result<Value> {
+        self.check_open()?;
+        let stored = self.run_updater(std::slice::from_ref(&path), options, |current| {
+            let arg = match current.into_iter().next().flatten() {
+                Some(value) => env.to_js_value(&value)?,
+                None => env.get_undefined()?.into_unknown(),
+            };
+            let result = updater.call(None, &[arg])?;
+            Ok(vec![match result.get_type()? {
+                ValueType::Undefined => None,
+                _ => Some(env.from_js_value(result)?),
+            }])
+        })?;
+        Ok(stored.into_iter().next().unwrap_or(Value::Null))
+    }
+
+    /// `atomic_update` over several paths at once, for invariants spanning
+    /// keys (moving a balance between two accounts, say). The updater gets the
+    /// current values as an array in `paths` order (undefined where missing) and
+    /// returns an array of the same length, undefined entries leaving their path
+    /// as it is, or undefined to change nothing. Either every path is written or,
+    /// when one write fails, none is. Returns the values now stored.
+    #[napi]
+    pub fn atomic_update_many(
+        &self,
+        env: Env,
+        paths: Vec<String>,
+        updater: JsFunction,
+        options: Option<AtomicUpdateOptions>,
+    ) -> Result<Vec<Value>> {
+        let count = paths.len();
+        self.run_updater(&paths, options, |current| {
+            let mut values = env.create_array_with_length(count)?;
+            for (i, value) in current.into_iter().enumerate() {
+                match value {
+                    Some(value) => values.set_element(i as u32, env.to_js_value(&value)?)?,
+                    None => values.set_element(i as u32, env.get_undefined()?)?,
+                }
+            }
+            let result = updater.call(None, &[values])?;
+            match result.get_type()? {
+                ValueType::Undefined => return Ok(vec![None; count]),
+                ValueType::Object if result.is_array()? => {}
+                _ => return Err(DbError::InvalidArgument(
+                    "atomicUpdateMany updater must return an array or undefined".to_string()
+                ).into()),
+            }
+            let result: napi::JsObject = unsafe { result.cast() };
+            if result.get_array_length()? as usize != count {
+                return Err(DbError::InvalidArgument(format!(
+                    "atomicUpdateMany updater must return {} values, one per path", count
+                )).into());
+            }
+            (0..count)
+                .map(|i| {
+                    let next: JsUnknown = result.get_element(i as u32)?;
+                    Ok(match next.get_type()? {
+                        ValueType::Undefined => None,
+                        _ => Some(env.from_js_value(next)?),
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Shared body of `atomic_update(_many)`: check the paths, take the write
+    /// lock, hand the current values to `call` and write back what it returns
+    fn run_updater(
+        &self,
+        paths: &[String],
+        options: Option<AtomicUpdateOptions>,
+        call: impl FnOnce(Vec<Option<Value>>) -> Result<Vec<Option<Value>>>,
+    ) -> Result<Vec<Value>> {
+        self.check_recovered()?;
+        let options = options.unwrap_or_default();
+        let max_callback = std::time::Duration::from_millis(options.max_callback_ms.unwrap_or(1000) as u64);
+        if paths.is_empty() {
+            return Err(DbError::InvalidArgument("atomicUpdateMany needs at least one path".to_string()).into());
+        }
+        for (i, path) in paths.iter().enumerate() {
+            if path.is_empty() {
+                return Err(DbError::InvalidArgument("atomicUpdate cannot target the root".to_string()).into());
+            }
+            if let Some(other) = paths[..i].iter().find(|other| paths_overlap(other, path)) {
+                return Err(DbError::InvalidArgument(format!(
+                    "atomicUpdateMany paths '{}' and '{}' overlap", other, path
+                )).into());
+            }
+            self.check_writable(path, false)?;
+            self.options.path_rules.check(path)?;
+            self.check_lease(path, options.owner.as_deref())?;
+            self.settle_staged(path)?;
+        }
+        self.throttle(paths.len())?;
+
+        let mut data = self.data.write();
+        let current: Vec<Option<Value>> = paths.iter()
+            .map(|path| compress::pointer(&data, &Self::to_pointer(path)).map(|v| {
+                let mut v = v.into_owned();
+                compress::expand(&mut v);
+                v
+            }))
+            .collect();
+
+        let started = std::time::Instant::now();
+        let next = {
+            let _scope = UpdaterScope::enter();
+            call(current.clone())
+        }?;
+        let elapsed = started.elapsed();
+        if elapsed > max_callback {
+            return Err(DbError::LimitExceeded(format!(
+                "atomicUpdate updater ran for {} ms, over the {} ms limit; nothing was written",
+                elapsed.as_millis(), max_callback.as_millis()
+            )).into());
+        }
+
+        // Nothing is written unless every new value passes its schema
+        for (path, after) in paths.iter().zip(&next) {
+            if let Some(after) = after {
+                self.check_schema(path, after)?;
+            }
+        }
+
+        // Old values of the writes applied so far, to put back if a later one fails
+        let mut applied: Vec<(String, Option<Value>)> = Vec::with_capacity(paths.len());
+        let mut stored = Vec::with_capacity(paths.len());
+        for ((path, before), after) in paths.iter().zip(current).zip(next) {
+            let Some(after) = after else {
+                stored.push(before.unwrap_or(Value::Null));
+                continue;
+            };
+            let op = BatchOp { op_type: "set".to_string(), path: path.clone(), value: Some(after.clone()) };
+            if let Err(e) = self.apply_atomic_op(&mut data, &op, &mut applied) {
+                self.revert_applied(&mut data, applied);
+                return Err(e);
+            }
+            stored.push(after);
+        }
+        Ok(stored)
+    }
+
+    /// Put back the old values of writes made so far by `apply_atomic_op`
+    fn revert_applied(&self, data: &mut Value, applied: Vec<(String, Option<Value>)>) {
+        for (path, old) in applied.into_iter().rev() {
+            let _ = match old {
+                Some(old) => self.append_wal(WalOpType::Set, &path, Some(old.clone()))
+                    .and_then(|_| Self::set_value_at_path(data, &path, old)),
+                None => self.append_wal(WalOpType::Delete, &path, None)
+                    .and_then(|_| Self::delete_value_at_path(data, &path)),
+            };
+        }
+    }
+
+    fn apply_atomic_op(&self, data: &mut Value, op: &BatchOp, applied: &mut Vec<(String, Option<Value>)>) -> Result<()> {
+        let path = if op.op_type == "delete" { op.path.clone() } else {
+            padding::resolve(data, &op.path, self.padding())?.into_owned()
+        };
+        let current = data.pointer(&Self::to_pointer(&path)).cloned();
+        let operand = op.value.clone().unwrap_or(Value::Null);
+        let mut value = match op.op_type.as_str() {
+            "delete" => {
+                let Some(old) = current else { return Ok(()) };
+                let removed = self.removed_value(data, &path);
+                self.record_undo_in(data, &path)?;
+                let lsn = self.append_wal_record(WalOpType::Delete, &path, None, None)?;
+                Self::delete_value_at_path(data, &path)?;
+                self.notify_delete(&path, None, lsn, removed);
+                applied.push((path.clone(), Some(old)));
+                return Ok(());
+            }
+            "push" => match current.clone().map(|mut v| { compress::expand(&mut v); v }) {
+                Some(Value::Array(arr)) if self.equality().contains(&arr, &operand) => return Ok(()),
+                Some(Value::Array(mut arr)) => {
+                    arr.push(operand);
+                    Value::Array(arr)
+                }
+                Some(_) => return Err(not_an_array(&path).into()),
+                None => return Err(DbError::PathNotFound(format!("Path does not exist: {}", path)).into()),
+            },
+            "add" | "subtract" => {
+                let base = match &current {
+                    None | Some(Value::Null) => 0.0,
+                    Some(v) => v.as_f64().ok_or_else(|| DbError::InvalidArgument(format!("'{}' is not a number", path)))?,
+                };
+                let delta = operand.as_f64().ok_or_else(|| DbError::InvalidArgument(format!("'{}' of '{}' needs a number", op.op_type, path)))?;
+                let result = if op.op_type == "add" { base + delta } else { base - delta };
+                // Integers stay integers, as they would after `set` from JS
+                match (result.fract() == 0.0 && result.abs() < 9_007_199_254_740_992.0, serde_json::Number::from_f64(result)) {
+                    (true, _) => json!(result as i64),
+                    (false, Some(n)) => Value::Number(n),
+                    (false, None) => return Err(DbError::InvalidArgument(format!("'{}' of '{}' is not finite", op.op_type, path)).into()),
+                }
+            }
+            _ => {
+                let threshold = self.compress_threshold.load(Ordering::Relaxed);
+                if threshold > 0 && operand.as_str().is_some_and(|text| text.len() >= threshold) {
+                    compress::compress(&operand)?
+                } else {
+                    operand
+                }
+            }
+        };
+        if self.options.enforce_schemas && op.op_type != "set" {
+            self.check_schema(&path, &value)?;
+        }
+        if self.guarded(&path) {
+            self.guard_write(data, &path, &mut value, &[])?;
+        }
+        self.record_undo_in(data, &path)?;
+        self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+        Self::set_value_at_path(data, &path, value)?;
+        applied.push((path.clone(), current));
+        Ok(())
+    }
+
+    #[napi]
+    pub fn has(&self, path: String, options: Option<ReadOptions>) -> Result<bool> {
+        self.check_recovered()?;
+        let dotted = path.trim_start_matches('/').replace('/', ".");
+        self.settle_staged(&dotted)?;
+        if meta::is_internal_path(&path) {
+            return Ok(false);
+        }
+        let data = self.data.read();
+        if let Some(committed) = self.committed_view(&data, &dotted, options.and_then(|o| o.txn))? {
+            return Ok(dotted.is_empty() || committed.is_some());
+        }
+        let ptr = Self::to_pointer(&path);
+        // The root always exists
+        Ok(ptr.is_empty() || data.pointer(&ptr).is_some())
+    }
+    
+    /// Delete a path. Returns what cascade rules did as `{rule: {deleted, nullified}}`.
+    #[napi]
+    pub fn delete(&self, path: String, options: Option<WriteOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        if options.reason.as_deref() == Some("ttl") && self.freezes.blocking(&path).is_some() {
+            // Expiry waits until the path is unfrozen
+            self.freezes.skip_purge();
+            return Ok(json!({}));
+        }
+        self.check_writable(&path, options.allow_internal.unwrap_or(false))?;
+        self.check_lease(&path, options.owner.as_deref())?;
+        Self::check_root_overwrite(&path, &options)?;
+
+        if path.is_empty() {
+            // Clearing the root keeps the engine metadata
+            let ns = self.data.read().get(meta::META_KEY).cloned();
+            if let Some(ns) = ns {
+                let mut root = serde_json::Map::new();
+                root.insert(meta::META_KEY.to_string(), ns);
+                self.set(path, Value::Object(root), Some(WriteOptions { allow_internal: Some(true), ..options }))?;
+                return Ok(json!({}));
+            }
+        }
+
+        let reason = Self::delete_reason(options.reason.as_deref())?;
+        self.throttle(1)?;
+
+        // v5.1 Transaction support
+        self.record_undo(&path)?;
+
+        let threshold = self.reclaim_threshold.load(Ordering::Relaxed);
+        let removed = if threshold > 0 {
+            self.data.read().pointer(&Self::to_pointer(&path)).map(approx_size).unwrap_or(0)
+        } else {
+            0
+        };
+
+        let mut data = self.data.write();
+        let existed = data.pointer(&Self::to_pointer(&path)).is_some();
+        let plan = match path.rsplit_once('.') {
+            Some((collection, key)) if existed => self.plan_cascade(&data, collection, &[key.to_string()])?,
+            _ => cascade::Plan::default(),
+        };
+        let value = self.removed_value(&data, &path);
+        let lsn = self.append_wal_record(WalOpType::Delete, &path, None, reason)?;
+        
+        Self::delete_value_at_path(&mut data, &path)?;
+        if existed {
+            self.notify_delete(&path, reason, lsn, value);
+        }
+        if !plan.is_empty() {
+            self.apply_cascade(&mut data, &plan)?;
+        }
+        drop(data);
+
+        if threshold > 0 && removed >= threshold {
+            let parent = path.rsplit_once('.').map(|(parent, _)| parent).unwrap_or("");
+            self.compact_memory(Some(parent.to_string()))?;
+        }
+        Ok(plan.counts())
+    }
+
+    /// Bulk delete. `cache.tenant_42` (or `cache.tenant_42.*`) clears every child of that
+    /// object or array; `cache.tenant_*` removes the children of `cache` whose key starts
+    /// with `tenant_`. Returns `{deleted}`, the number of children removed (or that would be,
+    /// on a dry run), with `cascaded` counts per rule when cascade rules applied.
+    #[napi]
+    pub fn delete_prefix(&self, path_prefix: String, options: Option<DeletePrefixOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(path_prefix.trim_end_matches('*').trim_end_matches('.'))?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        let trimmed = path_prefix.strip_suffix(".*").or_else(|| path_prefix.strip_suffix('*').filter(|p| p.is_empty()));
+        let (container_path, key_prefix) = match trimmed {
+            Some(container) => (container.to_string(), None),
+            None => match path_prefix.strip_suffix('*') {
+                Some(pattern) => match pattern.rsplit_once('.') {
+                    Some((parent, prefix)) => (parent.to_string(), Some(prefix.to_string())),
+                    None => (String::new(), Some(pattern.to_string())),
+                },
+                None => (path_prefix.clone(), None),
+            },
+        };
+        if container_path.contains('*') {
+            return Err(DbError::InvalidArgument(format!(
+                "Only the last segment of '{}' may contain a wildcard", path_prefix
+            )).into());
+        }
+        self.check_writable(&container_path, false)?;
+        self.check_lease(&container_path, options.owner.as_deref())?;
+        self.throttle(1)?;
+
+        let mut data = self.data.write();
+        let ptr = Self::to_pointer(&container_path);
+        let at_root = ptr.is_empty();
+        let container = if at_root { Some(&mut *data) } else { data.pointer_mut(&ptr) };
+        let Some(container) = container else { return Ok(json!({ "deleted": 0 })) };
+
+        let doomed: Vec<String> = match (&*container, &key_prefix) {
+            (Value::Object(map), _) => map.keys()
+                .filter(|k| !(at_root && k.as_str() == meta::META_KEY))
+                .filter(|k| key_prefix.as_deref().is_none_or(|p| k.starts_with(p)))
+                .cloned()
+                .collect(),
+            (Value::Array(arr), None) => (0..arr.len()).map(|i| i.to_string()).collect(),
+            (Value::Array(_), Some(_)) => {
+                return Err(DbError::InvalidArgument(format!(
+                    "Key prefix patterns are not supported on arrays: {}", path_prefix
+                )).into());
+            }
+            _ => return Err(DbError::PathBlocked(format!("Not an object or array: {}", container_path)).into()),
+        };
+        if options.dry_run.unwrap_or(false) || doomed.is_empty() {
+            return Ok(json!({ "deleted": doomed.len() }));
+        }
+        let plan = self.plan_cascade(&data, &container_path, &doomed)?;
+        let Some(container) = (if at_root { Some(&mut *data) } else { data.pointer_mut(&ptr) }) else {
+            return Ok(json!({ "deleted": 0 }));
+        };
+
+        // One undo snapshot of the container covers every removed child
+        self.record_undo_with(&container_path, || Some(container.clone()))?;
+
+        let clears_all = match &*container {
+            Value::Object(map) => doomed.len() == map.len() - usize::from(at_root && map.contains_key(meta::META_KEY)),
+            _ => true,
+        };
+        if clears_all {
+            // Whole subtree in a single WAL record
+            let emptied = match &*container {
+                Value::Array(_) => json!([]),
+                Value::Object(map) => {
+                    let mut kept = serde_json::Map::new();
+                    if let Some(ns) = map.get(meta::META_KEY).filter(|_| at_root) {
+                        kept.insert(meta::META_KEY.to_string(), ns.clone());
+                    }
+                    Value::Object(kept)
+                }
+                _ => unreachable!(),
+            };
+            let lsn = self.append_wal_record(WalOpType::Set, &container_path, Some(emptied.clone()), None)?;
+            let old = std::mem::replace(container, emptied);
+            for key in &doomed {
+                let path = Self::join_path(&container_path, key);
+                let value = self.removed_value(&old, key);
+                self.notify_delete(&path, None, lsn, value);
+            }
+        } else if let Value::Object(map) = container {
+            for key in &doomed {
+                let path = Self::join_path(&container_path, key);
+                let lsn = self.append_wal_record(WalOpType::Delete, &path, None, None)?;
+                let mut value = map.shift_remove(key).filter(|_| self.wants_removed_values());
+                if let Some(v) = value.as_mut() {
+                    compress::expand(v);
+                }
+                self.notify_delete(&path, None, lsn, value);
+            }
+        }
+        if !plan.is_empty() {
+            self.apply_cascade(&mut data, &plan)?;
+        }
+        drop(data);
+
+        // Drop index entries for documents at or below the removed children
+        let doomed: std::collections::HashSet<String> = doomed.into_iter().collect();
+        let child_key = |doc_path: &str| -> Option<String> {
+            let rest = if at_root { Some(doc_path) } else { doc_path.strip_prefix(container_path.as_str())?.strip_prefix('.') };
+            rest.map(|r| r.split('.').next().unwrap_or("").to_string())
+        };
+        for idx in self.indexes.write().values_mut() {
+            idx.remove_where(|doc_path| child_key(doc_path).is_some_and(|k| doomed.contains(&k)));
+        }
+
+        let mut result = json!({ "deleted": doomed.len() });
+        if !plan.is_empty() {
+            result["cascaded"] = plan.counts();
+        }
+        Ok(result)
+    }
+
+    // Inserts
+    //
+    // New documents get a key from the collection's ID strategy, kept in the
+    // reserved namespace under "idStrategies".
+
+    /// The ID strategy of a collection: the configured one, or uuid4
+    fn id_strategy_for(&self, collection_path: &str) -> ids::IdStrategy {
+        let data = self.data.read();
+        meta::get(&data, "idStrategies")
+            .and_then(|strategies| strategies.get(collection_path))
+            .and_then(|spec| ids::IdStrategy::parse(spec).ok())
+            .unwrap_or(ids::IdStrategy::Uuid4)
+    }
+
+    /// Keys for new documents of a collection, checked against the path rules.
+    /// Sequence keys are reserved in one step before anything is written.
+    fn generate_keys(&self, collection_path: &str, strategy: &ids::IdStrategy, docs: &[Value]) -> Result<Vec<String>> {
+        let keys: Vec<String> = match strategy {
+            ids::IdStrategy::Sequence { name } => {
+                if docs.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let end = self.advance_sequence(name, docs.len() as i64)?;
+                (end - docs.len() as i64 + 1..=end).map(|n| n.to_string()).collect()
+            }
+            ids::IdStrategy::Field { field } => docs.iter().enumerate()
+                .map(|(index, doc)| match self.get_value_at_field(doc, field) {
+                    Some(Value::String(s)) => Ok(s.clone()),
+                    Some(Value::Number(n)) => Ok(n.to_string()),
+                    _ => Err(DbError::InvalidArgument(format!("Document {} has no usable '{}' for its key", index, field))),
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            generated => docs.iter().filter_map(|_| generated.generate()).collect(),
+        };
+        for key in &keys {
+            if key.is_empty() || key.contains('.') {
+                return Err(DbError::InvalidPath(format!("Key '{}' cannot be used as a path segment", key)).into());
+            }
+            self.options.path_rules.check(&Self::join_path(collection_path, key))?;
+        }
+        Ok(keys)
+    }
+
+    /// Choose how `insert` keys new documents of a collection: "uuid4" (the
+    /// default), "ulid", "nanoid" (`{type: "nanoid", length}`), a sequence
+    /// (`{type: "sequence", name}`) or a field of the document (`{type: "field",
+    /// field}`). Persisted with the data; null restores the default.
+    #[napi]
+    pub fn set_id_strategy(&self, collection_path: String, strategy: Value) -> Result<()> {
+        self.check_recovered()?;
+        self.check_writable(&collection_path, false)?;
+        let stored = match &strategy {
+            Value::Null => None,
+            spec => Some(ids::IdStrategy::parse(spec)?.to_json()),
+        };
+        let mut data = self.data.write();
+        let mut strategies = Self::get_internal(&data, "idStrategies").unwrap_or_else(|| json!({}));
+        match stored {
+            Some(stored) => strategies[collection_path.as_str()] = stored,
+            None => {
+                if let Some(map) = strategies.as_object_mut() {
+                    map.shift_remove(&collection_path);
+                }
+            }
+        }
+        self.set_internal(&mut data, "idStrategies", strategies)
+    }
+
+    /// The effective ID strategy of a collection, e.g. `{type: "nanoid", length: 21,
+    /// configured: true}`; unconfigured collections report `{type: "uuid4", configured: false}`
+    #[napi]
+    pub fn id_strategy(&self, collection_path: String) -> Result<Value> {
+        self.check_recovered()?;
+        let configured = meta::get(&self.data.read(), "idStrategies")
+            .is_some_and(|strategies| strategies.get(&collection_path).is_some());
+        let mut effective = self.id_strategy_for(&collection_path).to_json();
+        effective["configured"] = json!(configured);
+        Ok(effective)
+    }
+
+    /// Add a document to an object-map collection under a key from the
+    /// collection's ID strategy, returning the key
+    #[napi]
+    pub fn insert(&self, collection_path: String, doc: Value, options: Option<WriteOptions>) -> Result<String> {
+        self.check_recovered()?;
+        Ok(self.insert_many(collection_path, vec![doc], options)?.pop().unwrap_or_default())
+    }
+
+    /// `insert` for several documents, returning their keys in order. Keys and
+    /// schemas are checked before anything is written; a key that already
+    /// exists fails the call with ERR_CONFLICT. Quota and constraint failures
+    /// stop it after the documents before the failing one.
+    #[napi]
+    pub fn insert_many(&self, collection_path: String, docs: Vec<Value>, options: Option<WriteOptions>) -> Result<Vec<String>> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&collection_path, false)?;
+        self.check_lease(&collection_path, options.owner.as_deref())?;
+        for doc in &docs {
+            self.check_value_size(&collection_path, doc)?;
+        }
+        self.throttle(docs.len())?;
+
+        let strategy = self.id_strategy_for(&collection_path);
+        let keys = self.generate_keys(&collection_path, &strategy, &docs)?;
+        let mut seen = std::collections::HashSet::new();
+        if let Some(dup) = keys.iter().find(|key| !seen.insert(key.as_str())) {
+            return Err(DbError::Conflict(format!("Key '{}' appears twice in one insert", dup)).into());
+        }
+        for (key, doc) in keys.iter().zip(&docs) {
+            let path = Self::join_path(&collection_path, key);
+            if let Some(schema) = self.schema_for_path(&path) {
+                validate(doc, &schema).map_err(|e| DbError::ValidationFailed { path: path.clone(), details: e.to_string() })?;
+            }
+        }
+
+        let ptr = Self::to_pointer(&collection_path);
+        let guarded = self.guarded(&collection_path);
+        let mut data = self.data.write();
+        match if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) } {
+            Some(Value::Object(map)) => {
+                if let Some(key) = keys.iter().find(|key| map.contains_key(key.as_str())) {
+                    return Err(DbError::Conflict(format!("Document already exists: {}", Self::join_path(&collection_path, key))).into());
+                }
+            }
+            None => {
+                // An object, so numeric keys don't make it an array
+                self.record_undo_with(&collection_path, || None)?;
+                self.append_wal(WalOpType::Set, &collection_path, Some(json!({})))?;
+                Self::set_value_at_path(&mut data, &collection_path, json!({}))?;
+            }
+            Some(_) => return Err(DbError::PathBlocked(format!("Collection is not an object: {}", collection_path)).into()),
+        }
+        for (key, mut doc) in keys.iter().zip(docs) {
+            let path = Self::join_path(&collection_path, key);
+            if guarded {
+                self.guard_write(&mut data, &path, &mut doc, &[])?;
+            }
+            self.record_undo_with(&path, || None)?;
+            self.append_wal(WalOpType::Set, &path, Some(doc.clone()))?;
+            Self::set_value_at_path(&mut data, &path, doc)?;
+        }
+        Ok(keys)
+    }
+
+    /// Insert or update documents of an object-map collection keyed by `key_field`.
+    /// Documents without a usable key, or failing validation, are reported in
+    /// `failed` as `{index, error}`; the rest are written. When the collection
+    /// has an ID strategy and `key_field` is top-level, documents without the
+    /// field get a generated key, stored in the field.
+    #[napi]
+    pub fn upsert_many(&self, collection_path: String, docs: Vec<Value>, key_field: String, options: Option<UpsertOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        let options = options.unwrap_or_default();
+        let merge = match options.mode.as_deref().unwrap_or("replace") {
+            "replace" => false,
+            "merge" => true,
+            other => return Err(DbError::InvalidArgument(format!("Unknown upsert mode '{}'", other)).into()),
+        };
+        let validate_docs = options.validate.unwrap_or(true);
+        let batch_size = options.batch_size.unwrap_or(1000).max(1) as usize;
+        self.check_writable(&collection_path, false)?;
+        for doc in &docs {
+            self.check_value_size(&collection_path, doc)?;
+        }
+        self.throttle(docs.len())?;
+
+        let mut docs = docs;
+        let configured = meta::get(&self.data.read(), "idStrategies")
+            .is_some_and(|strategies| strategies.get(&collection_path).is_some());
+        if configured && !key_field.contains('.') {
+            let unkeyed: Vec<usize> = (0..docs.len())
+                .filter(|&i| docs[i].is_object() && self.get_value_at_field(&docs[i], &key_field).is_none())
+                .collect();
+            if !unkeyed.is_empty() {
+                let strategy = self.id_strategy_for(&collection_path);
+                let sample: Vec<Value> = unkeyed.iter().map(|&i| docs[i].clone()).collect();
+                let keys = self.generate_keys(&collection_path, &strategy, &sample)?;
+                for (i, key) in unkeyed.into_iter().zip(keys) {
+                    if let Value::Object(map) = &mut docs[i] {
+                        map.insert(key_field.clone(), Value::String(key));
+                    }
+                }
+            }
+        }
+
+        let mut failed = Vec::new();
+        let mut keyed = Vec::with_capacity(docs.len());
+        for (index, doc) in docs.into_iter().enumerate() {
+            let key = match self.get_value_at_field(&doc, &key_field) {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Number(n)) => n.to_string(),
+                _ => {
+                    failed.push(json!({ "index": index, "error": format!("Missing key field '{}'", key_field) }));
+                    continue;
+                }
+            };
+            if key.is_empty() || key.contains('.') {
+                failed.push(json!({ "index": index, "error": format!("Key '{}' cannot be used as a path segment", key) }));
+                continue;
+            }
+            keyed.push((index, key, doc));
+        }
+        {
+            let data = self.data.read();
+            for (_, key, _) in &keyed {
+                self.check_lease_in(&data, &Self::join_path(&collection_path, key), options.owner.as_deref())?;
+            }
+        }
+
+        let ptr = Self::to_pointer(&collection_path);
+        let guarded = self.guarded(&collection_path);
+        let mut inserted = 0u32;
+        let mut updated = 0u32;
+        let mut pending = keyed.into_iter().peekable();
+        while pending.peek().is_some() {
+            let mut data = self.data.write();
+
+            // Create the collection as an object so numeric keys don't make it an array
+            match if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) } {
+                Some(Value::Object(_)) => {}
+                None => {
+                    self.record_undo_with(&collection_path, || None)?;
+                    self.append_wal(WalOpType::Set, &collection_path, Some(json!({})))?;
+                    Self::set_value_at_path(&mut data, &collection_path, json!({}))?;
+                }
+                Some(_) => return Err(DbError::PathBlocked(format!("Collection is not an object: {}", collection_path)).into()),
+            }
+
+            for (index, key, doc) in pending.by_ref().take(batch_size) {
+                let path = Self::join_path(&collection_path, &key);
+                let doc_ptr = Self::child_pointer(&ptr, &key);
+                let existing = data.pointer(&doc_ptr);
+                let is_update = existing.is_some();
+                let mut new_doc = match (existing, doc) {
+                    (Some(Value::Object(current)), Value::Object(fields)) if merge => {
+                        let mut merged = current.clone();
+                        merged.extend(fields);
+                        Value::Object(merged)
+                    }
+                    (_, doc) => doc,
+                };
+
+                if validate_docs {
+                    if let Some(schema) = self.schema_for_path(&path) {
+                        if let Err(e) = validate(&new_doc, &schema) {
+                            failed.push(json!({ "index": index, "error": e.to_string() }));
+                            continue;
+                        }
+                    }
+                }
+
+                if guarded {
+                    if let Err(e) = self.guard_write(&mut data, &path, &mut new_doc, &[]) {
+                        failed.push(json!({ "index": index, "error": e.reason }));
+                        continue;
+                    }
+                }
+
+                self.record_undo_with(&path, || data.pointer(&doc_ptr).cloned())?;
+                self.append_wal(WalOpType::Set, &path, Some(new_doc.clone()))?;
+                match data.pointer_mut(&doc_ptr) {
+                    Some(slot) => *slot = new_doc,
+                    None => Self::set_value_at_path(&mut data, &path, new_doc)?,
+                }
+                if is_update { updated += 1 } else { inserted += 1 }
+            }
+        }
+
+        failed.sort_by_key(|f| f["index"].as_u64());
+        Ok(json!({ "inserted": inserted, "updated": updated, "failed": failed }))
+    }
+
+    /// Update every document of a collection matching the filters. Object documents
+    /// are shallow-merged with `patch` (anything else is replaced by it). Matches
+    /// of an array collection are updated in place, by index, as one array write;
+    /// in an object collection each document is its own write, and those applied
+    /// before a failure stay applied (and undoable).
+    #[napi]
+    pub fn update_many(&self, collection_path: String, filters: Vec<QueryFilter>, patch: Value, options: Option<UpdateManyOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.check_filters(&filters)?;
+        self.settle_staged(&collection_path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        let merge = match options.mode.as_deref().unwrap_or("merge") {
+            "merge" => true,
+            "replace" => false,
+            other => return Err(DbError::InvalidArgument(format!("Unknown update mode '{}'", other)).into()),
+        };
+        let owner = options.owner.as_deref();
+        self.check_writable(&collection_path, false)?;
+        self.check_value_size(&collection_path, &patch)?;
+        self.throttle(1)?;
+        let prepared = self.prepare_filters(&filters);
+        let updated = |doc: &Value| match (doc, &patch) {
+            (Value::Object(current), Value::Object(fields)) if merge => {
+                let mut merged = current.clone();
+                merged.extend(fields.clone());
+                Value::Object(merged)
+            }
+            _ => patch.clone(),
+        };
+
+        if self.is_array_at(&collection_path) {
+            // The array is written as a whole
+            self.check_lease(&collection_path, owner)?;
+            return self.modify_array(&collection_path, |arr| {
+                let indices = Self::matching_indices(arr, &prepared);
+                for &i in &indices {
+                    arr[i] = updated(&arr[i]);
+                }
+                (json!({ "updated": indices.len(), "indices": indices }), indices)
+            });
+        }
+
+        let guarded = self.guarded(&collection_path);
+        let mut data = self.data.write();
+        let keys = Self::matching_keys(&data, &collection_path, &prepared)?;
+        let mut writes = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let path = Self::join_path(&collection_path, key);
+            let Some(current) = data.pointer(&Self::to_pointer(&path)) else { continue };
+            self.check_lease_in(&data, &path, owner)?;
+            let new_doc = updated(current);
+            if let Some(schema) = self.schema_for_path(&path) {
+                validate(&new_doc, &schema).map_err(|e| DbError::ValidationFailed { path: path.clone(), details: e.to_string() })?;
+            }
+            writes.push((path, new_doc));
+        }
+        for (path, mut new_doc) in writes {
+            if guarded {
+                self.guard_write(&mut data, &path, &mut new_doc, &[])?;
+            }
+            self.record_undo_in(&data, &path)?;
+            self.append_wal(WalOpType::Set, &path, Some(new_doc.clone()))?;
+            Self::set_value_at_path(&mut data, &path, new_doc)?;
+        }
+        Ok(json!({ "updated": keys.len(), "keys": keys }))
+    }
+
+    /// Delete every document of a collection matching the filters. Matches are
+    /// collected first; array elements are then removed in descending index order
+    /// as one array write, so no removal shifts an element still to be removed.
+    /// Deleted documents of an object collection trigger its cascade rules, whose
+    /// counts are reported as `cascaded`.
+    #[napi]
+    pub fn delete_many(&self, collection_path: String, filters: Vec<QueryFilter>, options: Option<DeleteManyOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.check_filters(&filters)?;
+        self.settle_staged(&collection_path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        let owner = options.owner.as_deref();
+        self.check_writable(&collection_path, false)?;
+        self.throttle(1)?;
+        let prepared = self.prepare_filters(&filters);
+
+        if self.is_array_at(&collection_path) {
+            // The array is written as a whole
+            self.check_lease(&collection_path, owner)?;
+            let wants_values = self.wants_removed_values();
+            let (result, removed) = self.modify_array(&collection_path, |arr| {
+                let indices = Self::matching_indices(arr, &prepared);
+                let mut removed = Vec::with_capacity(indices.len());
+                for &i in indices.iter().rev() {
+                    let value = arr.remove(i);
+                    removed.push((i, Some(value).filter(|_| wants_values)));
+                }
+                removed.reverse();
+                ((json!({ "deleted": indices.len(), "indices": indices }), removed), Vec::new())
+            })?;
+            // Events use indexes from before the removal; the array was logged as one write
+            for (i, mut value) in removed {
+                if let Some(v) = value.as_mut() {
+                    compress::expand(v);
+                }
+                self.notify_delete(&Self::join_path(&collection_path, &i.to_string()), None, None, value);
+            }
+            return Ok(result);
+        }
+
+        let mut data = self.data.write();
+        let keys = Self::matching_keys(&data, &collection_path, &prepared)?;
+        for key in &keys {
+            self.check_lease_in(&data, &Self::join_path(&collection_path, key), owner)?;
+        }
+        let plan = self.plan_cascade(&data, &collection_path, &keys)?;
+        for key in &keys {
+            let path = Self::join_path(&collection_path, key);
+            self.record_undo_in(&data, &path)?;
+            let value = self.removed_value(&data, &path);
+            let lsn = self.append_wal_record(WalOpType::Delete, &path, None, None)?;
+            Self::delete_value_at_path(&mut data, &path)?;
+            self.notify_delete(&path, None, lsn, value);
+        }
+        let mut result = json!({ "deleted": keys.len(), "keys": keys });
+        if !plan.is_empty() {
+            self.apply_cascade(&mut data, &plan)?;
+            result["cascaded"] = plan.counts();
+        }
+        Ok(result)
+    }
+
+    fn is_array_at(&self, path: &str) -> bool {
+        let data = self.data.read();
+        let ptr = Self::to_pointer(path);
+        matches!(if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) }, Some(Value::Array(_)))
+    }
+
+    /// Keys of the documents of the object collection at `collection_path` matching
+    /// every filter, in collection order (none if the collection doesn't exist)
+    fn matching_keys(data: &Value, collection_path: &str, filters: &[PreparedFilter]) -> Result<Vec<String>> {
+        let ptr = Self::to_pointer(collection_path);
+        match if ptr.is_empty() { Some(data) } else { data.pointer(&ptr) } {
+            Some(Value::Object(map)) => Ok(map.iter()
+                .filter(|(key, doc)| !(ptr.is_empty() && key.as_str() == meta::META_KEY) && Self::matches_filters(doc, filters))
+                .map(|(key, _)| key.clone())
+                .collect()),
+            Some(_) => Err(DbError::PathBlocked(format!("Not an object or array: {}", collection_path)).into()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Append a value to an array unless it is already present
+    #[napi]
+    pub fn push(&self, path: String, value: Value, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        self.push_many(path, vec![value], options)
+    }
+
+    // Array Operations
+
+    /// Append several values to an array, skipping ones already present
+    #[napi]
+    pub fn push_many(&self, path: String, values: Vec<Value>, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&path, options.allow_internal.unwrap_or(false))?;
+        self.check_lease(&path, options.owner.as_deref())?;
+        for value in &values {
+            self.check_value_size(&path, value)?;
+        }
+        self.throttle(values.len())?;
+
+        let equality = self.equality();
+        self.append_array(&path, |arr| {
+            let mut picked: Vec<Value> = Vec::new();
+            for value in values {
+                // Dedupe: check if value exists
+                if !equality.contains(arr, &value) && !equality.contains(&picked, &value) {
+                    picked.push(value);
+                }
+            }
+            Some(picked)
+        })?;
+        Ok(())
+    }
+
+    /// Remove `delete_count` elements at `start` and insert `items` in their place.
+    /// Returns the removed elements.
+    #[napi]
+    pub fn splice(
+        &self,
+        path: String,
+        start: u32,
+        delete_count: u32,
+        items: Option<Vec<Value>>,
+        options: Option<WriteOptions>,
+    ) -> Result<Vec<Value>> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&path, options.allow_internal.unwrap_or(false))?;
+        self.check_lease(&path, options.owner.as_deref())?;
+        for item in items.iter().flatten() {
+            self.check_value_size(&path, item)?;
+        }
+        self.throttle(1)?;
+
+        let mut items = items.unwrap_or_default();
+        if delete_count == 0 {
+            // Inserting at the end is an append
+            let mut pending = Some(items);
+            self.append_array(&path, |arr| pending.take_if(|_| start as usize >= arr.len()))?;
+            match pending {
+                Some(rest) => items = rest,
+                None => return Ok(Vec::new()),
+            }
+        }
+        self.modify_array(&path, |arr| {
+            let start = (start as usize).min(arr.len());
+            let end = start.saturating_add(delete_count as usize).min(arr.len());
+            let inserted = (start..start + items.len()).collect();
+            let removed = arr.splice(start..end, items).collect();
+            (removed, inserted)
+        })
+    }
+
+    /// Append the elements `pick` chooses, given the current ones, to the array
+    /// at `path` in place under one write lock. Nothing moves, so only the new
+    /// elements are validated and WAL-logged (one record each), and undo keeps
+    /// the old length. Returns false, changing nothing, when `pick` returns None.
+    /// Paths under constraints, quotas or custom indexes go through `modify_array`.
+    fn append_array(&self, path: &str, pick: impl FnOnce(&[Value]) -> Option<Vec<Value>>) -> Result<bool> {
+        if self.guarded(path) {
+            return self.modify_array(path, |arr| match pick(arr) {
+                Some(items) => {
+                    let inserted = (arr.len()..arr.len() + items.len()).collect();
+                    arr.extend(items);
+                    (true, inserted)
+                }
+                None => (false, Vec::new()),
+            });
+        }
+        let schema = self.schema_for_path(path);
+
+        let mut data = self.data.write();
+        let ptr = Self::to_pointer(path);
+        let target = if ptr.is_empty() { Some(&mut *data) } else { data.pointer_mut(&ptr) };
+        let arr = match target {
+            Some(Value::Array(arr)) => arr,
+            Some(_) => return Err(not_an_array(path).into()),
+            None => return Err(DbError::PathNotFound(format!("Path does not exist: {}", path)).into()),
+        };
+        let Some(items) = pick(arr) else { return Ok(false) };
+        if items.is_empty() {
+            return Ok(true);
+        }
+        if let Some(schema) = &schema {
+            validate_array_append(schema, arr, &items)
+                .map_err(|e| DbError::ValidationFailed { path: path.to_string(), details: e.to_string() })?;
+        }
+
+        self.record_append_undo(path, arr.len())?;
+        let dotted = Self::dotted(path);
+        for item in items {
+            // Elements logged before a failure stay, as a replay would have them
+            self.append_wal(WalOpType::Set, &format!("{}.{}", dotted, arr.len()), Some(item.clone()))?;
+            arr.push(item);
+        }
+        Ok(true)
+    }
+
+    /// Apply `op` to a copy of the array at `path` under one write lock. The result
+    /// is validated against the best-matching schema (`op` reports the indexes it
+    /// inserted), WAL-logged, and only then swapped in.
+    fn modify_array<R>(&self, path: &str, op: impl FnOnce(&mut Vec<Value>) -> (R, Vec<usize>)) -> Result<R> {
+        let schema = self.schema_for_path(path);
+        let guarded = self.guarded(path);
+
+        let mut data = self.data.write();
+        let ptr = Self::to_pointer(path);
+        let target = if ptr.is_empty() { Some(&mut *data) } else { data.pointer_mut(&ptr) };
+        let arr = match target {
+            Some(Value::Array(arr)) => arr,
+            Some(_) => return Err(not_an_array(path).into()),
+            None => return Err(DbError::PathNotFound(format!("Path does not exist: {}", path)).into()),
+        };
+
+        let mut updated = arr.clone();
+        let (result, inserted) = op(&mut updated);
+        if updated == *arr {
+            return Ok(result);
+        }
+
+        if let Some(schema) = &schema {
+            let inserted: Vec<(usize, &Value)> = inserted.iter().map(|&i| (i, &updated[i])).collect();
+            validate_array_write(schema, &updated, &inserted)
+                .map_err(|e| DbError::ValidationFailed { path: path.to_string(), details: e.to_string() })?;
+        }
+
+        let mut updated = Value::Array(updated);
+        if guarded {
+            self.guard_write(&mut data, path, &mut updated, &inserted)?;
+        }
+        let Some(arr) = (if ptr.is_empty() { Some(&mut *data) } else { data.pointer_mut(&ptr) }) else {
+            return Err(DbError::PathNotFound(format!("Path does not exist: {}", path)).into());
+        };
+
+        // Unguarded appends never get here: `append_array` records them by length
+        self.record_undo_with(path, || Some(arr.clone()))?;
+        self.append_wal(WalOpType::Set, path, Some(updated.clone()))?;
+        *arr = updated;
+        Ok(result)
+    }
+
+    /// Move one array element from `from_index` to `to_index` under a single write lock.
+    /// Targets past the end are clamped; sources past the end are an error.
+    #[napi]
+    pub fn reorder(&self, path: String, from_index: u32, to_index: u32, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&path, options.allow_internal.unwrap_or(false))?;
+        self.check_lease(&path, options.owner.as_deref())?;
+        self.throttle(1)?;
+
+        // Nothing new is inserted; a `prefixItems` schema still checks every position
+        self.modify_array(&path, |arr| {
+            let from = from_index as usize;
+            if from >= arr.len() {
+                let err = DbError::InvalidArgument(format!(
+                    "Source index {} out of range for array of length {}", from, arr.len()
+                ));
+                return (Err(err.into()), Vec::new());
+            }
+            let item = arr.remove(from);
+            let to = (to_index as usize).min(arr.len());
+            arr.insert(to, item);
+            (Ok(()), Vec::new())
+        })?
+    }
+
+    /// Rearrange a whole array in one step. `new_order[i]` is the current index
+    /// of the element that should end up at position `i`.
+    #[napi]
+    pub fn reorder_all(&self, path: String, new_order: Vec<u32>, options: Option<WriteOptions>) -> Result<()> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&path, options.allow_internal.unwrap_or(false))?;
+        self.check_lease(&path, options.owner.as_deref())?;
+        self.throttle(1)?;
+
+        self.modify_array(&path, |arr| {
+            if new_order.len() != arr.len() {
+                let err = DbError::InvalidArgument(format!(
+                    "Permutation length {} does not match array length {}", new_order.len(), arr.len()
+                ));
+                return (Err(err.into()), Vec::new());
+            }
+            let mut seen = vec![false; arr.len()];
+            for &idx in &new_order {
+                let idx = idx as usize;
+                if idx >= arr.len() || seen[idx] {
+                    let err = DbError::InvalidArgument("new_order is not a valid permutation".to_string());
+                    return (Err(err.into()), Vec::new());
+                }
+                seen[idx] = true;
+            }
+            let mut taken: Vec<Option<Value>> = arr.drain(..).map(Some).collect();
+            arr.extend(new_order.iter().filter_map(|&i| taken[i as usize].take()));
+            (Ok(()), Vec::new())
+        })?
+    }
+
+    // Merging
+
+    /// Insert `new_key` into an object collection directly before `reference_key`.
+    /// The collection is rewritten (and WAL-logged) as a whole under one write
+    /// lock. A missing reference key appends instead and sets `appended`.
+    #[napi]
+    pub fn insert_before(&self, collection_path: String, new_key: String, value: Value, reference_key: String, options: Option<WriteOptions>) -> Result<KeyPlacement> {
+        self.check_recovered()?;
+        self.insert_at(&collection_path, new_key, value, &reference_key, false, options.unwrap_or_default())
+    }
+
+    /// `insert_before`, placing the key directly after `reference_key`
+    #[napi]
+    pub fn insert_after(&self, collection_path: String, new_key: String, value: Value, reference_key: String, options: Option<WriteOptions>) -> Result<KeyPlacement> {
+        self.check_recovered()?;
+        self.insert_at(&collection_path, new_key, value, &reference_key, true, options.unwrap_or_default())
+    }
+
+    /// Move an existing key of an object collection directly before `before_key`,
+    /// or last when `before_key` is omitted or missing
+    #[napi]
+    pub fn move_key(&self, collection_path: String, key: String, before_key: Option<String>, options: Option<WriteOptions>) -> Result<KeyPlacement> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&collection_path, options.allow_internal.unwrap_or(false))?;
+        self.check_lease(&Self::join_path(&collection_path, &key), options.owner.as_deref())?;
+        self.throttle(1)?;
+        let mut data = self.data.write();
+        let map = Self::object_collection(&mut data, &collection_path)?;
+        let Some(value) = map.get(&key).cloned() else {
+            return Err(DbError::PathNotFound(format!("Key '{}' does not exist in {}", key, collection_path)).into());
+        };
+
+        let mut reordered = map.clone();
+        reordered.shift_remove(&key);
+        let target = before_key.as_deref().and_then(|before| reordered.keys().position(|k| k == before));
+        let position = target.unwrap_or(reordered.len());
+        reordered.shift_insert(position, key.clone(), value);
+
+        self.record_undo_with(&collection_path, || Some(Value::Object(map.clone())))?;
+        self.append_wal(WalOpType::Set, &collection_path, Some(Value::Object(reordered.clone())))?;
+        *map = reordered;
+        Ok(KeyPlacement { key, position: position as u32, appended: target.is_none() && before_key.is_some() })
+    }
+
+    fn insert_at(&self, collection_path: &str, new_key: String, mut value: Value, reference_key: &str, after: bool, options: WriteOptions) -> Result<KeyPlacement> {
+        self.settle_staged(collection_path)?;
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(collection_path, options.allow_internal.unwrap_or(false))?;
+        if new_key.is_empty() || new_key.contains('.') {
+            return Err(DbError::InvalidArgument(format!("Key '{}' cannot be used as a path segment", new_key)).into());
+        }
+        let doc_path = Self::join_path(collection_path, &new_key);
+        self.check_lease(&doc_path, options.owner.as_deref())?;
+        self.check_value_size(&doc_path, &value)?;
+        self.throttle(1)?;
+
+        let mut data = self.data.write();
+        if Self::object_collection(&mut data, collection_path)?.contains_key(&new_key) {
+            return Err(DbError::InvalidArgument(format!(
+                "Key '{}' already exists in {}; use moveKey to reposition it", new_key, collection_path
+            )).into());
+        }
+        if self.guarded(&doc_path) {
+            self.guard_write(&mut data, &doc_path, &mut value, &[])?;
+        }
+        // Quota evictions may have changed the collection
+        let map = Self::object_collection(&mut data, collection_path)?;
+        let target = map.keys().position(|k| k == reference_key);
+        let position = match target {
+            Some(i) if after => i + 1,
+            Some(i) => i,
+            None => map.len(),
+        };
+        let mut updated = map.clone();
+        updated.shift_insert(position, new_key.clone(), value);
+
+        self.record_undo_with(collection_path, || Some(Value::Object(map.clone())))?;
+        self.append_wal(WalOpType::Set, collection_path, Some(Value::Object(updated.clone())))?;
+        *map = updated;
+        Ok(KeyPlacement { key: new_key, position: position as u32, appended: target.is_none() })
+    }
+
+    /// The object at a collection path, for key-order edits
+    fn object_collection<'a>(data: &'a mut Value, collection_path: &str) -> Result<&'a mut serde_json::Map<String, Value>> {
+        let ptr = Self::to_pointer(collection_path);
+        match if ptr.is_empty() { Some(data) } else { data.pointer_mut(&ptr) } {
+            Some(Value::Object(map)) => Ok(map),
+            Some(_) => Err(DbError::PathBlocked(format!("Collection is not an object: {}", collection_path)).into()),
+            None => Err(DbError::PathNotFound(format!("Path does not exist: {}", collection_path)).into()),
+        }
+    }
+
+    /// Merge an offline-edited subtree: changes from `base` to `theirs` are applied where
+    /// the current value hasn't diverged from `base`; the rest are resolved per policy or
+    /// reported as `{path, base, ours, theirs}` conflicts.
+    #[napi]
+    pub fn merge_three_way(&self, path: String, base: Value, theirs: Value, options: Option<MergeOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&path)?;
+        self.check_writable(&path, false)?;
+        self.check_value_size(&path, &theirs)?;
+        self.throttle(1)?;
+        let policy = options.and_then(|o| o.policy).unwrap_or_else(|| "report".to_string());
+        if !matches!(policy.as_str(), "report" | "ours" | "theirs") {
+            return Err(DbError::InvalidArgument(format!("Unknown merge policy '{}'", policy)).into());
+        }
+
+        let mut out = MergeOutcome::default();
+        let mut data = self.data.write();
+        let ptr = Self::to_pointer(&path);
+        let current = if ptr.is_empty() { Some(&*data) } else { data.pointer(&ptr) }.cloned();
+
+        let merged = merge_values(Some(&base), current.as_ref(), Some(&theirs), &path, &policy, &mut out);
+        if merged != current {
+            self.record_undo_with(&path, || current.clone())?;
+            match merged {
+                Some(value) => {
+                    self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+                    Self::set_value_at_path(&mut data, &path, value)?;
+                }
+                None => {
+                    self.append_wal(WalOpType::Delete, &path, None)?;
+                    Self::delete_value_at_path(&mut data, &path)?;
+                }
+            }
+        }
+
+        Ok(json!({
+            "applied": out.applied,
+            "resolved": out.resolved,
+            "conflicts": out.conflicts,
+        }))
+    }
+
+    // Migrations
+
+    /// Rename a (dot-notation) field in every document of a collection
+    #[napi]
+    pub fn rename_field(
+        &self,
+        collection_path: String,
+        from_field: String,
+        to_field: String,
+        options: Option<RenameFieldOptions>,
+    ) -> Result<RenameFieldResult> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        if from_field.is_empty() || to_field.is_empty() {
+            return Err(DbError::InvalidArgument("Field names must not be empty".to_string()).into());
+        }
+        let options = options.unwrap_or_default();
+        let _txn = self.enter_txn(options.txn)?;
+        self.check_writable(&collection_path, false)?;
+        self.throttle(1)?;
+        let overwrite = options.overwrite.unwrap_or(false);
+        let doc_path = |key: &str| if collection_path.is_empty() { key.to_string() } else { format!("{}.{}", collection_path, key) };
+        let ptr = Self::to_pointer(&collection_path);
+
+        let mut data = self.data.write();
+
+        // Discovery: find documents that carry the source field
+        let candidates: Vec<(String, bool)> = {
+            let entries: Vec<(String, &Value)> = match data.pointer(&ptr) {
+                Some(Value::Object(map)) => map.iter()
+                    .filter(|(k, _)| !(collection_path.is_empty() && k.as_str() == meta::META_KEY))
+                    .map(|(k, v)| (k.clone(), v))
+                    .collect(),
+                Some(Value::Array(arr)) => arr.iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+                _ => return Err(DbError::PathNotFound(format!("Collection not found: {}", collection_path)).into()),
+            };
+            let probe = |(key, doc): &(String, &Value)| {
+                self.get_value_at_field(doc, &from_field)
+                    .map(|_| (key.clone(), self.get_value_at_field(doc, &to_field).is_some()))
+            };
+            if THREAD_CONFIG.should_parallelize(entries.len()) {
+                entries.par_iter().filter_map(probe).collect()
+            } else {
+                entries.iter().filter_map(probe).collect()
+            }
+        };
+
+        for (key, target_exists) in &candidates {
+            if overwrite || !target_exists {
+                self.check_lease_in(&data, &doc_path(key), options.owner.as_deref())?;
+            }
+        }
+
+        // Apply sequentially under the same write lock
+        let mut changed = 0u32;
+        let mut skipped = 0u32;
+        for (key, target_exists) in candidates {
+            if target_exists && !overwrite {
+                skipped += 1;
+                continue;
+            }
+            let doc_ptr = Self::child_pointer(&ptr, &key);
+            let Some(doc) = data.pointer_mut(&doc_ptr) else { continue };
+
+            let old_doc = doc.clone();
+            let mut new_doc = old_doc.clone();
+            let Some(moved) = self.get_value_at_field(&new_doc, &from_field).cloned() else { continue };
+            Self::delete_value_at_path(&mut new_doc, &from_field)?;
+            if Self::set_value_at_path(&mut new_doc, &to_field, moved).is_err() {
+                skipped += 1;
+                continue;
+            }
+
+            let doc_path = doc_path(&key);
+            self.record_undo_with(&doc_path, || Some(old_doc))?;
+            self.append_wal(WalOpType::Set, &doc_path, Some(new_doc.clone()))?;
+            *doc = new_doc;
+            changed += 1;
+        }
+        drop(data);
+
+        let affected_indexes = self.indexes.read()
+            .iter()
+            .filter(|(_, idx)| idx.field() == from_field)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        Ok(RenameFieldResult { changed, skipped, affected_indexes })
+    }
+
+    /// Transform documents of a collection with a synchronous JS mapper `(doc, key) => newDoc`.
+    /// Returning `undefined` leaves the document untouched. Documents are processed in
+    /// batches so the mapper runs without holding any lock; documents modified concurrently
+    /// between the read and the write are reported as conflicts and left as they are.
+    #[napi]
+    pub fn map_collection(
+        &self,
+        env: Env,
+        collection_path: String,
+        filters: Option<Vec<QueryFilter>>,
+        mapper: JsFunction,
+        options: Option<MapCollectionOptions>,
+    ) -> Result<Value> {
+        self.check_recovered()?;
+        self.check_filters(filters.as_deref().unwrap_or_default())?;
+        self.settle_staged(&collection_path)?;
+        self.check_writable(&collection_path, false)?;
+        let options = options.unwrap_or_default();
+        let dry_run = options.dry_run.unwrap_or(false);
+        if !dry_run {
+            self.throttle(1)?;
+        }
+        let batch_size = options.batch_size.unwrap_or(500).max(1) as usize;
+        let prepared = self.prepare_filters(&filters.unwrap_or_default());
+        let ptr = Self::to_pointer(&collection_path);
+        let keys = Self::collection_keys(&self.data.read(), &collection_path)?;
+
+        let mut updated = 0u32;
+        let mut unchanged = 0u32;
+        let mut conflicts = Vec::new();
+        let mut changes = Vec::new();
+
+        for chunk in keys.chunks(batch_size) {
+            // 1. Snapshot the matching documents of this batch
+            let (batch, _) = Self::read_batch(&self.data.read(), &ptr, chunk, &prepared);
+
+            // 2. Run the mapper without holding any lock
+            let mut pending = Vec::with_capacity(batch.len());
+            for (key, doc) in batch {
+                let args = [env.to_js_value(&doc)?, env.create_string(&key)?.into_unknown()];
+                let result = mapper.call(None, &args)?;
+                if result.get_type()? == ValueType::Undefined {
+                    unchanged += 1;
+                    continue;
+                }
+                let new_doc: Value = env.from_js_value(result)?;
+                pending.push((key, doc, new_doc));
+            }
+
+            let doc_path = |key: &str| {
+                if collection_path.is_empty() { key.to_string() } else { format!("{}.{}", collection_path, key) }
+            };
+
+            if dry_run {
+                for (key, before, after) in pending {
+                    changes.push(json!({ "key": key, "path": doc_path(&key), "before": before, "after": after }));
+                }
+                continue;
+            }
+
+            // 3. Apply under a short write lock, skipping documents that moved on
+            {
+                let mut data = self.data.write();
+                for (key, _, _) in &pending {
+                    self.check_lease_in(&data, &doc_path(key), options.owner.as_deref())?;
+                }
+                for (key, before, after) in pending {
+                    let Some(current) = data.pointer_mut(&Self::child_pointer(&ptr, &key)) else {
+                        conflicts.push(key);
+                        continue;
+                    };
+                    if *current != before {
+                        conflicts.push(key);
+                        continue;
+                    }
+                    let path = doc_path(&key);
+                    self.record_undo_with(&path, || Some(before))?;
+                    self.append_wal(WalOpType::Set, &path, Some(after.clone()))?;
+                    *current = after;
+                    updated += 1;
+                }
+            }
+        }
+
+        if dry_run {
+            Ok(json!({ "dryRun": true, "changes": changes, "unchanged": unchanged }))
+        } else {
+            Ok(json!({ "updated": updated, "unchanged": unchanged, "conflicts": conflicts }))
+        }
+    }
+
+    /// Keys of the collection at `collection_path` (indexes for arrays), without
+    /// the reserved namespace at the root
+    fn collection_keys(data: &Value, collection_path: &str) -> Result<Vec<String>> {
+        match data.pointer(&Self::to_pointer(collection_path)) {
+            Some(Value::Object(map)) => Ok(map.keys()
+                .filter(|k| !(collection_path.is_empty() && k.as_str() == meta::META_KEY))
+                .cloned()
+                .collect()),
+            Some(Value::Array(arr)) => Ok((0..arr.len()).map(|i| i.to_string()).collect()),
+            _ => Err(DbError::PathNotFound(format!("Collection not found: {}", collection_path)).into()),
+        }
+    }
+
+    /// Clone the documents of `keys` that still exist and match `filters`.
+    /// Returns them with the number of keys that no longer exist.
+    fn read_batch(data: &Value, ptr: &str, keys: &[String], filters: &[PreparedFilter]) -> (Vec<(String, Value)>, u32) {
+        let mut missing = 0;
+        let batch = keys
+            .iter()
+            .filter_map(|key| {
+                let Some(doc) = data.pointer(&Self::child_pointer(ptr, key)) else {
+                    missing += 1;
+                    return None;
+                };
+                Self::matches_filters(doc, filters).then(|| (key.clone(), doc.clone()))
+            })
+            .collect();
+        (batch, missing)
+    }
+
+    /// Run a JS callback `(doc, key)` over every matching document of a collection
+    /// without holding a lock across the iteration: the key list is taken up front,
+    /// then each batch is cloned under a short read lock. Keys deleted in between
+    /// are skipped and counted. Returns `{processed, skipped, errors: [{key, error}]}`.
+    ///
+    /// In "threadsafe" mode the batches are read on a worker thread and each call is
+    /// queued onto the event loop (at most one batch in flight); the result comes
+    /// back as a Promise. Promises returned by the callback are not awaited.
+    #[napi]
+    pub fn for_each(
+        &self,
+        env: Env,
+        collection_path: String,
+        filters: Option<Vec<QueryFilter>>,
+        callback: JsFunction,
+        options: Option<ForEachOptions>,
+    ) -> Result<JsUnknown> {
+        self.check_recovered()?;
+        self.check_filters(filters.as_deref().unwrap_or_default())?;
+        self.settle_staged(&collection_path)?;
+        let options = options.unwrap_or_default();
+        let batch_size = options.batch_size.unwrap_or(500).max(1) as usize;
+        let threadsafe = match options.mode.as_deref() {
+            None | Some("sync") => false,
+            Some("threadsafe") => true,
+            Some(other) => return Err(DbError::InvalidArgument(format!("Unknown forEach mode '{}': use 'sync' or 'threadsafe'", other)).into()),
+        };
+        let prepared = self.prepare_filters(&filters.unwrap_or_default());
+        let ptr = Self::to_pointer(&collection_path);
+        // Outside an exclusive transaction's writes: walk what it found
+        let data = match self.committed_root(&self.data.read(), &[&collection_path])? {
+            Cow::Owned(root) => Arc::new(PLRwLock::new(root)),
+            Cow::Borrowed(_) => Arc::clone(&self.data),
+        };
+        let keys = Self::collection_keys(&data.read(), &collection_path)?;
+
+        if !threadsafe {
+            let (mut processed, mut skipped, mut errors) = (0u32, 0u32, Vec::new());
+            for chunk in keys.chunks(batch_size) {
+                let (batch, missing) = Self::read_batch(&data.read(), &ptr, chunk, &prepared);
+                skipped += missing;
+                for (key, doc) in batch {
+                    let args = [env.to_js_value(&doc)?, env.create_string(&key)?.into_unknown()];
+                    match callback.call(None, &args) {
+                        Ok(_) => processed += 1,
+                        Err(e) => errors.push(json!({ "key": key, "error": e.reason })),
+                    }
+                }
+            }
+            return env.to_js_value(&json!({ "processed": processed, "skipped": skipped, "errors": errors }));
+        }
+
+        let tsfn: ThreadsafeFunction<(String, Value), ErrorStrategy::Fatal> = Self::guard_callback(&env, callback)?.create_threadsafe_function(
+            batch_size,
+            |ctx: ThreadSafeCallContext<(String, Value)>| {
+                let (key, doc) = ctx.value;
+                Ok(vec![ctx.env.to_js_value(&doc)?, ctx.env.create_string(&key)?.into_unknown()])
+            },
+        )?;
+        let (deferred, promise) = env.create_deferred()?;
+
+        std::thread::spawn(move || {
+            let (mut processed, mut skipped, mut errors) = (0u32, 0u32, Vec::new());
+            let (done_tx, done_rx) = std::sync::mpsc::channel::<(String, Option<String>)>();
+            for chunk in keys.chunks(batch_size) {
+                let (batch, missing) = Self::read_batch(&data.read(), &ptr, chunk, &prepared);
+                skipped += missing;
+                let queued = batch.len();
+                for (key, doc) in batch {
+                    let done = done_tx.clone();
+                    let name = key.clone();
+                    let status = tsfn.call_with_return_value((key, doc), ThreadsafeFunctionCallMode::Blocking, move |ret: JsUnknown| {
+                        let error = match ret.is_error() {
+                            Ok(true) => Some(ret.coerce_to_string()
+                                .and_then(|s| s.into_utf8())
+                                .and_then(|s| s.into_owned())
+                                .unwrap_or_default()),
+                            _ => None,
+                        };
+                        let _ = done.send((name, error));
+                        Ok(())
+                    });
+                    if status != Status::Ok {
+                        let _ = done_tx.send((String::new(), Some(format!("Callback not queued: {}", status))));
+                    }
+                }
+                // Wait for this batch before reading the next
+                for (key, error) in done_rx.iter().take(queued) {
+                    match error {
+                        None => processed += 1,
+                        Some(error) => errors.push(json!({ "key": key, "error": error })),
+                    }
+                }
+            }
+            drop(tsfn);
+            deferred.resolve(move |env| env.to_js_value(&json!({ "processed": processed, "skipped": skipped, "errors": errors })));
+        });
+        Ok(promise.into_unknown())
+    }
+
+    /// Wrap a callback for use as a threadsafe function. A throw inside a
+    /// threadsafe call is fatal, so the wrapper hands errors back as return values.
+    fn guard_callback(env: &Env, callback: JsFunction) -> Result<JsFunction> {
+        let guard: JsFunction = env.run_script(
+            "(fn) => (...args) => { try { fn(...args); } catch (e) { return e instanceof Error ? e : new Error(String(e)); } }",
+        )?;
+        guard.call(None, &[callback])?.try_into()
+    }
+
+    /// One-time migration from a lowdb / plain JSON file. Each mapping rule
+    /// `{from, to, keyField?}` copies the source subtree at `from` under `to`: object
+    /// entries keep their keys, arrays are re-keyed by `keyField` into an object map,
+    /// anything else is written as a single value at `to`.
+    #[napi]
+    pub fn import_file(&self, source_path: String, mapping: Value, options: Option<ImportOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        self.flush_coalesced()?;
+        let options = options.unwrap_or_default();
+        let policy = options.on_duplicate.unwrap_or_else(|| "error".to_string());
+        if !matches!(policy.as_str(), "error" | "skip" | "overwrite") {
+            return Err(DbError::InvalidArgument(format!("Unknown duplicate policy '{}'", policy)).into());
+        }
+        let validate_items = options.validate.unwrap_or(true);
+        let rules: Vec<ImportRule> = serde_json::from_value(mapping)
+            .map_err(|e| DbError::InvalidArgument(format!("Invalid import mapping: {}", e)))?;
+        for rule in &rules {
+            self.check_writable(&rule.to, false)?;
+            self.fault_in(&rule.to)?;
+        }
+        self.throttle(1)?;
+
+        let file = File::open(&source_path)
+            .map_err(|e| DbError::Io(format!("Cannot open import source {}: {}", source_path, e)))?;
+        let source: Value = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| DbError::Corrupt(format!("Cannot parse import source {}: {}", source_path, e)))?;
+
+        let mut data = self.data.write();
+
+        // Plan every write first so the "error" policy leaves the database untouched
+        let mut writes: Vec<(String, Value)> = Vec::new();
+        let mut planned = std::collections::HashSet::new();
+        let mut invalid = Vec::new();
+        let mut containers = Vec::new();
+        let mut rule_reports = Vec::with_capacity(rules.len());
+        let (mut total_imported, mut total_skipped, mut total_duplicates) = (0u32, 0u32, 0u32);
+
+        for rule in &rules {
+            let (mut imported, mut skipped, mut duplicates) = (0u32, 0u32, 0u32);
+            let from_ptr = Self::to_pointer(&rule.from);
+            let Some(subtree) = (if from_ptr.is_empty() { Some(&source) } else { source.pointer(&from_ptr) }) else {
+                rule_reports.push(json!({ "from": rule.from, "to": rule.to, "missing": true }));
+                continue;
+            };
+
+            let (keyed, items): (bool, Vec<(Option<String>, &Value)>) = match (subtree, &rule.key_field) {
+                (Value::Array(arr), Some(field)) => (true, arr.iter()
+                    .map(|item| {
+                        let key = match self.get_value_at_field(item, field) {
+                            Some(Value::String(s)) => Some(s.clone()),
+                            Some(Value::Number(n)) => Some(n.to_string()),
+                            _ => None,
+                        };
+                        (key, item)
+                    })
+                    .collect()),
+                (Value::Object(map), _) => (true, map.iter().map(|(k, v)| (Some(k.clone()), v)).collect()),
+                _ => (false, vec![(None, subtree)]),
+            };
+            if keyed {
+                // Keyed items land in an object map, created up front so numeric keys
+                // don't turn it into an array
+                match data.pointer(&Self::to_pointer(&rule.to)) {
+                    Some(Value::Object(_)) => {}
+                    None => containers.push(rule.to.clone()),
+                    Some(_) => return Err(DbError::PathBlocked(format!("Import target is not an object: {}", rule.to)).into()),
+                }
+            }
+
+            for (key, value) in items {
+                let path = match (keyed, key) {
+                    (false, _) => rule.to.clone(),
+                    (true, Some(key)) if !key.is_empty() && !key.contains('.') => Self::join_path(&rule.to, &key),
+                    // Missing key field, or a key that can't be addressed with dot paths
+                    (true, _) => {
+                        skipped += 1;
+                        continue;
+                    }
+                };
+                if meta::is_internal_path(&path) {
+                    skipped += 1;
+                    continue;
+                }
+                if validate_items {
+                    if let Some(schema) = self.schema_for_path(&path) {
+                        if let Err(e) = validate(value, &schema) {
+                            invalid.push(json!({ "path": path, "error": e.to_string() }));
+                            skipped += 1;
+                            continue;
+                        }
+                    }
+                }
+                if planned.contains(&path) || data.pointer(&Self::to_pointer(&path)).is_some() {
+                    duplicates += 1;
+                    match policy.as_str() {
+                        "error" => return Err(DbError::Conflict(format!("Duplicate key at {}", path)).into()),
+                        "skip" => continue,
+                        _ => {}
+                    }
+                }
+                planned.insert(path.clone());
+                writes.push((path, value.clone()));
+                imported += 1;
+            }
+
+            rule_reports.push(json!({
+                "from": rule.from,
+                "to": rule.to,
+                "imported": imported,
+                "skipped": skipped,
+                "duplicates": duplicates,
+            }));
+            total_imported += imported;
+            total_skipped += skipped;
+            total_duplicates += duplicates;
+        }
+
+        let dry_run = options.dry_run.unwrap_or(false);
+        if !dry_run {
+            for path in containers {
+                if data.pointer(&Self::to_pointer(&path)).is_none() {
+                    self.record_undo_with(&path, || None)?;
+                    self.append_wal(WalOpType::Set, &path, Some(json!({})))?;
+                    Self::set_value_at_path(&mut data, &path, json!({}))?;
+                }
+            }
+            for (path, value) in writes {
+                self.record_undo_in(&data, &path)?;
+                self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
+                Self::set_value_at_path(&mut data, &path, value)?;
+            }
+        }
+
+        Ok(json!({
+            "dryRun": dry_run,
+            "imported": total_imported,
+            "skipped": total_skipped,
+            "duplicates": total_duplicates,
+            "invalid": invalid,
+            "rules": rule_reports,
+        }))
+    }
+
+    // Sequences
+    //
+    // Counters live in the reserved namespace and are never rolled back by
+    // transactions, so a value handed out once is never handed out again.
+
+    /// Atomically advance a named sequence by `count`, returning the new value.
+    /// The WAL is synced before returning so a crash can't reuse a value.
+    fn advance_sequence(&self, name: &str, count: i64) -> Result<i64> {
+        if count < 1 {
+            return Err(DbError::InvalidArgument("Sequence increment must be positive".to_string()).into());
+        }
+        let next = {
+            let mut data = self.data.write();
+            let mut sequences = Self::get_internal(&data, "sequences").unwrap_or_else(|| json!({}));
+            let current = sequences.get(name).and_then(|v| v.as_i64()).unwrap_or(0);
+            let next = current.checked_add(count)
+                .ok_or_else(|| DbError::LimitExceeded(format!("Sequence '{}' overflowed", name)))?;
+            sequences[name] = json!(next);
+            self.set_internal(&mut data, "sequences", sequences)?;
+            next
+        };
+        self.sync()?;
+        Ok(next)
+    }
+
+    /// Increment a named sequence and return the new value
+    #[napi]
+    pub fn next_sequence(&self, name: String) -> Result<i64> {
+        self.check_recovered()?;
+        self.advance_sequence(&name, 1)
+    }
+
+    /// Reserve `count` consecutive values of a sequence in one step
+    #[napi]
+    pub fn reserve_sequence(&self, name: String, count: u32) -> Result<SequenceRange> {
+        self.check_recovered()?;
+        let end = self.advance_sequence(&name, count as i64)?;
+        Ok(SequenceRange { start: end - count as i64 + 1, end })
+    }
+
+    /// Current value of a sequence (0 if it has never been used)
+    #[napi]
+    pub fn current_sequence(&self, name: String) -> Result<i64> {
+        self.check_recovered()?;
+        let data = self.data.read();
+        Ok(meta::get(&data, "sequences")
+            .and_then(|s| s.get(&name))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0))
+    }
+
+    /// Reset a sequence to an explicit value
+    #[napi]
+    pub fn set_sequence(&self, name: String, value: i64) -> Result<()> {
+        self.check_recovered()?;
+        {
+            let mut data = self.data.write();
+            let mut sequences = Self::get_internal(&data, "sequences").unwrap_or_else(|| json!({}));
+            sequences[name.as_str()] = json!(value);
+            self.set_internal(&mut data, "sequences", sequences)?;
+        }
+        self.sync()
+    }
+
+    // Leases
+    //
+    // Advisory check-out leases stored in the reserved namespace. Expired
+    // leases are ignored everywhere and pruned on the next acquire.
+
+    /// Try to lease `path` for `owner`. Returns false if another owner holds an
+    /// unexpired lease on the path, an ancestor, or a descendant.
+    #[napi]
+    pub fn acquire_lease(&self, path: String, owner: String, ttl_ms: u32) -> Result<bool> {
+        self.check_recovered()?;
+        let now = now_ms();
+        let mut data = self.data.write();
+        let leases = Self::get_internal(&data, "leases").unwrap_or_else(|| json!({}));
+        let mut live: serde_json::Map<String, Value> = leases
+            .as_object()
+            .map(|m| {
+                m.iter()
+                    .filter(|(_, l)| l["expiresAt"].as_u64().unwrap_or(0) > now)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let conflict = live.iter().any(|(leased, l)| {
+            paths_overlap(leased, &path) && l["owner"].as_str() != Some(owner.as_str())
+        });
+        if conflict {
+            return Ok(false);
+        }
+
+        live.insert(path, json!({ "owner": owner, "expiresAt": now + ttl_ms as u64 }));
+        self.set_internal(&mut data, "leases", Value::Object(live))?;
+        Ok(true)
+    }
+
+    /// Release a lease held by `owner`. Returns false if `owner` didn't hold it.
+    #[napi]
+    pub fn release_lease(&self, path: String, owner: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut leases = Self::get_internal(&data, "leases").unwrap_or_else(|| json!({}));
+        let held = leases.get(&path).and_then(|l| l["owner"].as_str()) == Some(owner.as_str());
+        if !held {
+            return Ok(false);
+        }
+        if let Some(map) = leases.as_object_mut() {
+            map.shift_remove(&path);
+        }
+        self.set_internal(&mut data, "leases", leases)?;
+        Ok(true)
+    }
+
+    /// Active lease on exactly `path` as `{ owner, expiresAt }`, or null
+    #[napi]
+    pub fn lease_info(&self, path: String) -> Result<Value> {
+        self.check_recovered()?;
+        let data = self.data.read();
+        Ok(meta::get(&data, "leases")
+            .and_then(|l| l.get(&path))
+            .filter(|l| l["expiresAt"].as_u64().unwrap_or(0) > now_ms())
+            .cloned()
+            .unwrap_or(Value::Null))
+    }
+
+    /// Turn strict lease mode on or off. When on, writes overlapping a path
+    /// leased by someone else must pass the lease owner in their options.
+    #[napi]
+    pub fn set_lease_enforcement(&self, enabled: bool) -> Result<()> {
+        self.check_open()?;
+        self.enforce_leases.store(enabled, Ordering::Release);
+        Ok(())
+    }
+
+    /// Accept filters written for the original, unchecked evaluation: unknown
+    /// operators match every document, a regex that does not compile and a
+    /// non-array `in` match none. Off by default, when such filters fail the
+    /// query with ERR_INVALID_ARGUMENT.
+    #[napi]
+    pub fn set_permissive_filters(&self, enabled: bool) -> Result<()> {
+        self.check_open()?;
+        self.permissive_filters.store(enabled, Ordering::Release);
+        Ok(())
+    }
+
+    /// Reject the first malformed filter unless filters are permissive
+    /// Compare values in filters, `push` dedupe and `atomic` conditions as
+    /// stored ("strict", the default: `1` and `1.0` differ) or by canonical
+    /// form ("canonical": integer-valued floats equal integers). Object key
+    /// order never matters. A filter's own `equality` overrides this; views
+    /// and counters keep the mode current when they were defined.
+    #[napi]
+    pub fn set_equality(&self, mode: String) -> Result<()> {
+        self.check_open()?;
+        let equality = Equality::parse(&mode).ok_or_else(|| {
+            DbError::InvalidArgument(format!("Unknown equality '{}': use 'strict' or 'canonical'", mode))
+        })?;
+        self.canonical_equality.store(equality == Equality::Canonical, Ordering::Release);
+        Ok(())
+    }
+
+    fn equality(&self) -> Equality {
+        if self.canonical_equality.load(Ordering::Acquire) { Equality::Canonical } else { Equality::Strict }
+    }
+
+    fn prepare_filters(&self, filters: &[QueryFilter]) -> Vec<PreparedFilter> {
+        let equality = self.equality();
+        filters.iter().map(|f| PreparedFilter::from_query_filter(f, equality)).collect()
+    }
+
+    /// Write the current default equality into filters naming none, so a
+    /// stored definition keeps comparing the same way
+    fn pin_equality(&self, filters: &mut [QueryFilter]) {
+        let equality = self.equality();
+        for filter in filters.iter_mut().filter(|f| f.equality.is_none()) {
+            filter.equality = Some(equality.as_str().to_string());
+        }
+    }
+
+    fn check_filters(&self, filters: &[QueryFilter]) -> Result<()> {
+        if self.permissive_filters.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        for (i, filter) in filters.iter().enumerate() {
+            check_filter(filter).map_err(|e| DbError::InvalidArgument(format!("Filter {} on '{}': {}", i, filter.field, e)))?;
+        }
+        Ok(())
+    }
+
+    // Frozen paths
+    //
+    // Prefixes refusing writes with ERR_FROZEN, checked wherever writes are.
+    // Persisted ones are listed in the reserved namespace as well.
+
+    /// Refuse writes touching `path_prefix` (the prefix, anything under it, and
+    /// its ancestors) until `unfreeze_path`. With `persist` the freeze survives a
+    /// restart. Returns false if the prefix was already frozen that way.
+    #[napi]
+    pub fn freeze_path(&self, path_prefix: String, options: Option<FreezeOptions>) -> Result<bool> {
+        self.check_recovered()?;
+        if meta::is_internal_path(&path_prefix) {
+            return Err(DbError::InvalidArgument(format!(
+                "Cannot freeze '{}' inside the reserved '{}' namespace", path_prefix, meta::META_KEY
+            )).into());
+        }
+        let mut data = self.data.write();
+        let changed = self.freezes.freeze(&path_prefix, options.and_then(|o| o.persist).unwrap_or(false));
+        if changed {
+            self.persist_frozen(&mut data)?;
+        }
+        Ok(changed)
+    }
+
+    /// Lift the freeze on exactly `path_prefix`. Returns false if it wasn't frozen.
+    #[napi]
+    pub fn unfreeze_path(&self, path_prefix: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        match self.freezes.unfreeze(&path_prefix) {
+            Some(true) => self.persist_frozen(&mut data).map(|_| true),
+            Some(false) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Frozen prefixes as `[{path, persisted}]`
+    #[napi]
+    pub fn frozen_paths(&self) -> Result<Value> {
+        self.check_open()?;
+        Ok(self.freezes.list())
+    }
+
+    /// Whether a write at `path` would fail with ERR_FROZEN
+    #[napi]
+    pub fn is_frozen(&self, path: String) -> Result<bool> {
+        self.check_open()?;
+        Ok(!meta::is_internal_path(&path) && self.freezes.blocking(&path).is_some())
+    }
+
+    /// Store the persisted freezes in the reserved namespace, if they changed
+    fn persist_frozen(&self, data: &mut Value) -> Result<()> {
+        let persisted = self.freezes.persisted();
+        if Self::load_frozen(data) != persisted {
+            self.set_internal(data, "frozen", json!(persisted))?;
+        }
+        Ok(())
+    }
+
+    fn load_frozen(data: &Value) -> Vec<String> {
+        meta::get(data, "frozen")
+            .and_then(Value::as_array)
+            .map(|paths| paths.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    }
+
+    fn check_lease(&self, path: &str, owner: Option<&str>) -> Result<()> {
+        if !self.enforce_leases.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        self.check_lease_in(&self.data.read(), path, owner)
+    }
+
+    /// `check_lease` against `data`, for callers already holding the data lock
+    fn check_lease_in(&self, data: &Value, path: &str, owner: Option<&str>) -> Result<()> {
+        if !self.enforce_leases.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let Some(Value::Object(leases)) = meta::get(data, "leases") else { return Ok(()) };
+        let now = now_ms();
+        for (leased, lease) in leases {
+            if lease["expiresAt"].as_u64().unwrap_or(0) <= now || !paths_overlap(leased, path) {
+                continue;
+            }
+            if lease["owner"].as_str() != owner {
+                return Err(DbError::Conflict(format!(
+                    "Path '{}' is leased by '{}'", leased, lease["owner"].as_str().unwrap_or("")
+                )).into());
+            }
+        }
+        Ok(())
+    }
+
+    // Views
+    //
+    // Materialized query/aggregate results stored in the reserved namespace.
+    // Staleness is tracked in memory; after a restart every auto-refresh view
+    // reports stale until it is refreshed, since writes made before the restart
+    // were not tracked.
+
+    /// Define (or redefine) a view: `{path, filters?, aggregate?, field?, autoRefresh?: {onWriteUnder}}`.
+    /// The result is computed by `refresh_view`.
+    #[napi]
+    pub fn create_view(&self, name: String, definition: Value) -> Result<()> {
+        self.check_recovered()?;
+        let mut definition: ViewDefinition = serde_json::from_value(definition)
+            .map_err(|e| DbError::InvalidArgument(format!("Invalid view definition: {}", e)))?;
+        self.check_filters(&definition.filters)?;
+        self.pin_equality(&mut definition.filters);
+        if let Some(op) = &definition.aggregate {
+            if !matches!(op.as_str(), "count" | "sum" | "avg" | "min" | "max") {
+                return Err(DbError::InvalidArgument(format!("Unknown aggregate '{}'", op)).into());
+            }
+        }
+        Emit::parse(definition.emit.as_deref())?;
+
+        let mut data = self.data.write();
+        let mut views = Self::get_internal(&data, "views").unwrap_or_else(|| json!({}));
+        views[&name] = json!({
+            "definition": definition,
+            "result": null,
+            "refreshedAt": null,
+            "stale": true,
+        });
+        self.set_internal(&mut data, "views", views)?;
+
+        let mut watches = self.view_watches.write();
+        match definition.auto_refresh {
+            Some(auto) => {
+                watches.insert(name, ViewWatch { path: auto.on_write_under, stale: AtomicBool::new(true) });
+            }
+            None => {
+                watches.remove(&name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Recompute a view, store the result and return it
+    #[napi]
+    pub fn refresh_view(&self, name: String) -> Result<Value> {
+        self.check_recovered()?;
+        self.flush_coalesced()?;
+        let source = Self::get_internal(&self.data.read(), "views")
+            .and_then(|views| views.get(&name)?.get("definition")?.get("path")?.as_str().map(str::to_string));
+        if let Some(source) = source {
+            self.fault_in(&source)?;
+        }
+        let mut data = self.data.write();
+        let mut views = Self::get_internal(&data, "views").unwrap_or_else(|| json!({}));
+        let Some(view) = views.get_mut(&name) else {
+            return Err(DbError::NotFound(format!("View '{}' not found", name)).into());
+        };
+        let definition: ViewDefinition = serde_json::from_value(view["definition"].clone())
+            .map_err(|e| DbError::Corrupt(format!("Stored view '{}' is invalid: {}", name, e)))?;
+
+        let result = match &definition.aggregate {
+            None => {
+                let emit = Emit::parse(definition.emit.as_deref())?;
+                self.query_in(&data, definition.path.clone(), definition.filters.clone(), emit)?
+            }
+            Some(op) if definition.filters.is_empty() => {
+                self.aggregate_in(&data, definition.path.clone(), op.clone(), definition.field.clone())?
+            }
+            Some(op) => {
+                let matched = self.query_in(&data, definition.path.clone(), definition.filters.clone(), Emit::Values)?;
+                self.aggregate_in(&matched, String::new(), op.clone(), definition.field.clone())?
+            }
+        };
+        view["result"] = result.clone();
+        view["refreshedAt"] = json!(now_ms());
+        view["stale"] = json!(false);
+        self.set_internal(&mut data, "views", views)?;
+
+        if let Some(watch) = self.view_watches.read().get(&name) {
+            watch.stale.store(false, Ordering::Release);
+        }
+        Ok(result)
+    }
+
+    /// Stored result of a view as `{result, refreshedAt, stale}`
+    #[napi]
+    pub fn get_view(&self, name: String) -> Result<Value> {
+        self.check_recovered()?;
+        let data = self.data.read();
+        let Some(view) = meta::get(&data, "views").and_then(|v| v.get(&name)) else {
+            return Err(DbError::NotFound(format!("View '{}' not found", name)).into());
+        };
+        Ok(json!({
+            "result": view["result"],
+            "refreshedAt": view["refreshedAt"],
+            "stale": self.view_is_stale(&name, view),
+        }))
+    }
+
+    /// All views as `[{name, definition, refreshedAt, stale}]`
+    #[napi]
+    pub fn list_views(&self) -> Result<Value> {
+        self.check_recovered()?;
+        let data = self.data.read();
+        let Some(Value::Object(views)) = meta::get(&data, "views") else { return Ok(json!([])) };
+        Ok(Value::Array(views.iter().map(|(name, view)| json!({
+            "name": name,
+            "definition": view["definition"],
+            "refreshedAt": view["refreshedAt"],
+            "stale": self.view_is_stale(name, view),
+        })).collect()))
+    }
+
+    /// Remove a view. Returns false if it didn't exist.
+    #[napi]
+    pub fn drop_view(&self, name: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut views = Self::get_internal(&data, "views").unwrap_or_else(|| json!({}));
+        let removed = views.as_object_mut().and_then(|m| m.shift_remove(&name)).is_some();
+        if removed {
+            self.set_internal(&mut data, "views", views)?;
+            self.view_watches.write().remove(&name);
+        }
+        Ok(removed)
+    }
+
+    fn view_is_stale(&self, name: &str, view: &Value) -> bool {
+        view["stale"].as_bool().unwrap_or(false)
+            || self.view_watches.read().get(name).is_some_and(|w| w.stale.load(Ordering::Acquire))
+    }
+
+    /// Tell derived state about a write at `path`: flag every auto-refresh view
+    /// watching an overlapping path and mark the document dirty in counters.
+    /// During a bulk load only quotas, constraints and lazy collections are told.
+    fn note_write(&self, path: &str) {
+        self.writes.fetch_add(1, Ordering::AcqRel);
+        if meta::is_internal_path(path) {
+            return;
+        }
+        if let Some(lazy) = &self.lazy {
+            lazy.note_write(path);
+        }
+        for quota in self.quotas.lock().values_mut() {
+            quota.note_write(path);
+        }
+        for constraint in self.constraints.lock().values_mut() {
+            constraint.note_write(path);
+        }
+        for (collection, writes) in self.stats_writes.lock().iter_mut() {
+            if paths_overlap(collection, path) {
+                *writes += 1;
+            }
+        }
+        // A bulk load rebuilds the rest when it ends
+        if self.bulk_loading.load(Ordering::Acquire) {
+            return;
+        }
+        let watches = self.view_watches.read();
+        for watch in watches.values() {
+            if paths_overlap(&watch.path, path) {
+                watch.stale.store(true, Ordering::Release);
+            }
+        }
+        drop(watches);
+        for counter in self.counters.lock().values_mut() {
+            counter.note_write(path);
+        }
+        for custom in self.custom_indexes.lock().values_mut() {
+            custom.note_write(path);
+        }
+        for upkeep in self.index_upkeep.lock().values_mut() {
+            upkeep.note_write(path);
+        }
+    }
+
+    fn load_view_watches(data: &Value) -> HashMap<String, ViewWatch> {
+        let Some(Value::Object(views)) = meta::get(data, "views") else { return HashMap::new() };
+        views.iter()
+            .filter_map(|(name, view)| {
+                let path = view["definition"]["autoRefresh"]["onWriteUnder"].as_str()?;
+                Some((name.clone(), ViewWatch { path: path.to_string(), stale: AtomicBool::new(true) }))
+            })
+            .collect()
+    }
+
+    // Counters
+
+    /// Maintain a count of documents in `collection_path` matching `filters`.
+    /// Writes under the collection only mark documents dirty; `counterValue`
+    /// re-evaluates those, so polling never rescans. Returns the initial count.
+    #[napi]
+    pub fn register_counter(&self, name: String, collection_path: String, filters: Vec<QueryFilter>) -> Result<i64> {
+        self.check_recovered()?;
+        self.check_filters(&filters)?;
+        let mut filters = filters;
+        self.pin_equality(&mut filters);
+        if collection_path.is_empty() || meta::is_internal_path(&collection_path) {
+            return Err(DbError::InvalidArgument(format!("Cannot count documents under '{}'", collection_path)).into());
+        }
+        self.settle_staged(&collection_path)?;
+        let mut data = self.data.write();
+        let mut counter = Counter::new(collection_path, filters);
+        Self::refresh_counter(&data, &mut counter);
+        let count = counter.count() as i64;
+
+        let mut stored = Self::get_internal(&data, "counters").unwrap_or_else(|| json!({}));
+        stored[&name] = counter.to_stored();
+        self.set_internal(&mut data, "counters", stored)?;
+        self.counters.lock().insert(name, counter);
+        Ok(count)
+    }
+
+    /// Current count of a registered counter
+    #[napi]
+    pub fn counter_value(&self, name: String) -> Result<i64> {
+        self.check_recovered()?;
+        let collection = match self.counters.lock().get(&name) {
+            Some(counter) => counter.collection.clone(),
+            None => return Err(DbError::NotFound(format!("Counter '{}' not found", name)).into()),
+        };
+        self.settle_staged(&collection)?;
+        let data = self.data.read();
+        let mut counters = self.counters.lock();
+        let counter = counters.get_mut(&name)
+            .ok_or_else(|| DbError::NotFound(format!("Counter '{}' not found", name)))?;
+        Self::refresh_counter(&data, counter);
+        Ok(counter.count() as i64)
+    }
+
+    /// Recompute a counter with a full scan of its collection
+    #[napi]
+    pub fn rebuild_counter(&self, name: String) -> Result<i64> {
+        self.check_recovered()?;
+        let data = self.data.read();
+        let mut counters = self.counters.lock();
+        let counter = counters.get_mut(&name)
+            .ok_or_else(|| DbError::NotFound(format!("Counter '{}' not found", name)))?;
+        counter.invalidate();
+        Self::refresh_counter(&data, counter);
+        Ok(counter.count() as i64)
+    }
+
+    /// Compare a counter's maintained value with a fresh scan without changing it
+    #[napi]
+    pub fn verify_counter(&self, name: String) -> Result<Value> {
+        self.check_recovered()?;
+        let current = self.counter_value(name.clone())?;
+        let data = self.data.read();
+        let counters = self.counters.lock();
+        let counter = counters.get(&name)
+            .ok_or_else(|| DbError::NotFound(format!("Counter '{}' not found", name)))?;
+        let mut scan = Counter::new(counter.collection.clone(), counter.filters.clone());
+        Self::refresh_counter(&data, &mut scan);
+        let actual = scan.count() as i64;
+        Ok(json!({ "name": name, "count": current, "actual": actual, "consistent": current == actual }))
+    }
+
+    /// Registered counters as `{ name: { collection, filters, count } }`
+    #[napi]
+    pub fn list_counters(&self) -> Result<Value> {
+        self.check_open()?;
+        let counters = self.counters.lock();
+        Ok(Value::Object(counters.iter()
+            .map(|(name, counter)| (name.clone(), json!({
+                "collection": counter.collection,
+                "filters": counter.filters,
+                "count": counter.count(),
+                "dirty": !counter.is_clean(),
+            })))
+            .collect()))
+    }
+
+    #[napi]
+    pub fn drop_counter(&self, name: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut stored = Self::get_internal(&data, "counters").unwrap_or_else(|| json!({}));
+        let removed = stored.as_object_mut().and_then(|m| m.shift_remove(&name)).is_some();
+        if removed {
+            self.set_internal(&mut data, "counters", stored)?;
+        }
+        Ok(self.counters.lock().remove(&name).is_some() || removed)
+    }
+
+    fn refresh_counter(data: &Value, counter: &mut Counter) {
+        if counter.is_clean() {
+            return;
+        }
+        // Registration records the equality in each filter
+        let prepared: Vec<PreparedFilter> = counter.filters.iter().map(|f| PreparedFilter::from_query_filter(f, Equality::Strict)).collect();
+        let collection = data.pointer(&Self::to_pointer(&counter.collection));
+        counter.refresh(collection, |doc| Self::matches_filters(doc, &prepared));
+    }
+
+    /// Store every counter's matched keys in the namespace so the next open
+    /// needs no rescan. Not WAL-logged: it is only trusted when no WAL
+    /// records were replayed on top of the saved file.
+    fn persist_counters(&self) {
+        let mut data = self.data.write();
+        let mut counters = self.counters.lock();
+        if counters.is_empty() {
+            return;
+        }
+        let mut stored = serde_json::Map::new();
+        for (name, counter) in counters.iter_mut() {
+            Self::refresh_counter(&data, counter);
+            stored.insert(name.clone(), counter.to_stored());
+        }
+        meta::set(&mut data, "counters", Value::Object(stored));
+    }
+
+    fn load_counters(data: &Value, replayed: bool) -> HashMap<String, Counter> {
+        let Some(Value::Object(stored)) = meta::get(data, "counters") else { return HashMap::new() };
+        stored.iter()
+            .filter_map(|(name, stored)| {
+                let mut counter = Counter::from_stored(stored)?;
+                if replayed {
+                    counter.invalidate();
+                }
+                Some((name.clone(), counter))
+            })
+            .collect()
+    }
+
+    // Quotas
+
+    /// Cap the entry count and/or approximate byte size of a collection.
+    /// "reject" makes writes that would exceed it fail with ERR_QUOTA;
+    /// "evict_oldest" deletes the oldest entries first, by insertion order or
+    /// by `order_field` (a number or ISO date; entries without it go first).
+    /// Enforced by set, push/splice, batchSetParallel and upsertMany. Replaces
+    /// any quota already on the collection and returns its status.
+    #[napi]
+    pub fn set_quota(
+        &self,
+        collection_path: String,
+        max_entries: Option<u32>,
+        max_bytes: Option<i64>,
+        policy: String,
+        order_field: Option<String>,
+    ) -> Result<Value> {
+        self.check_recovered()?;
+        if collection_path.is_empty() || meta::is_internal_path(&collection_path) {
+            return Err(DbError::InvalidArgument(format!("Cannot set a quota on '{}'", collection_path)).into());
+        }
+        let Some(policy) = QuotaPolicy::parse(&policy) else {
+            return Err(DbError::InvalidArgument(format!("Unknown quota policy '{}': use 'reject' or 'evict_oldest'", policy)).into());
+        };
+        if max_entries.is_none() && max_bytes.is_none() {
+            return Err(DbError::InvalidArgument("A quota needs maxEntries or maxBytes".to_string()).into());
+        }
+        self.settle_staged(&collection_path)?;
+
+        let mut data = self.data.write();
+        let mut quota = Quota::new(
+            collection_path.clone(),
+            max_entries.map(|n| n as usize),
+            max_bytes.map(|n| n.max(0) as usize),
+            policy,
+            order_field,
+        );
+        // Keep the insertion order already learned for this collection
+        if let Some(previous) = self.quotas.lock().get(&collection_path) {
+            if quota.order_field.is_none() && previous.order_field.is_none() {
+                quota = Quota::from_stored(&collection_path, &json!({
+                    "policy": policy.as_str(),
+                    "maxEntries": quota.max_entries,
+                    "maxBytes": quota.max_bytes,
+                    "order": previous.to_stored()["order"],
+                })).unwrap_or(quota);
+            }
+        }
+        let mut quotas = self.quotas.lock();
+        quotas.insert(collection_path.clone(), quota);
+        let stored: serde_json::Map<String, Value> = quotas.iter()
+            .map(|(collection, quota)| (collection.clone(), quota.config()))
+            .collect();
+        drop(quotas);
+        self.set_internal(&mut data, "quotas", Value::Object(stored))?;
+        drop(data);
+        self.quota_status(collection_path)
+    }
+
+    /// Remove the quota on a collection
+    #[napi]
+    pub fn remove_quota(&self, collection_path: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut stored = Self::get_internal(&data, "quotas").unwrap_or_else(|| json!({}));
+        let removed = stored.as_object_mut().and_then(|m| m.shift_remove(&collection_path)).is_some();
+        if removed {
+            self.set_internal(&mut data, "quotas", stored)?;
+        }
+        Ok(self.quotas.lock().remove(&collection_path).is_some() || removed)
+    }
+
+    /// Current usage of a collection against its quota:
+    /// `{ collection, entries, bytes, maxEntries, maxBytes, policy, orderField, exceeded, rejected, evicted }`
+    #[napi]
+    pub fn quota_status(&self, collection_path: String) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        let data = self.data.read();
+        let mut quotas = self.quotas.lock();
+        let quota = quotas.get_mut(&collection_path)
+            .ok_or_else(|| DbError::NotFound(format!("No quota on '{}'", collection_path)))?;
+        let (entries, bytes) = Self::quota_usage(&data, quota);
+        Ok(quota.status(entries, bytes))
+    }
+
+    /// Usage of a quota's collection: maintained for objects, measured for arrays
+    fn quota_usage(data: &Value, quota: &mut Quota) -> (usize, usize) {
+        let collection = data.pointer(&Self::to_pointer(&quota.collection));
+        quota.refresh(collection);
+        match collection {
+            Some(Value::Object(_)) => quota.usage(),
+            Some(other) => Quota::measure(other),
+            None => (0, 0),
+        }
+    }
+
+    /// Whether any quota's collection overlaps `path`
+    fn quota_applies(&self, path: &str) -> bool {
+        let quotas = self.quotas.lock();
+        !quotas.is_empty() && quotas.keys().any(|collection| paths_overlap(collection, path))
+    }
+
+    /// Whether writes at `path` must be checked against a quota, constraint or
+    /// rejecting custom index
+    fn guarded(&self, path: &str) -> bool {
+        self.quota_applies(path) || self.constraint_applies(path) || self.custom_index_vets(path)
+    }
+
+    /// Check a pending write against constraints and custom index extractors, then
+    /// quotas (which may evict), with the data write lock held
+    fn guard_write(&self, data: &mut Value, path: &str, value: &mut Value, keep: &[usize]) -> Result<()> {
+        self.check_constraint_write(data, path, value)?;
+        self.check_custom_index_write(data, path, value)?;
+        self.enforce_quotas(data, path, value, keep)
+    }
+
+    /// Where `collection` lies inside a write at `path` that replaces it (or an
+    /// ancestor), relative to the written value; None for writes inside it
+    fn relative_below(collection: &str, path: &str) -> Option<String> {
+        collection.strip_prefix(path)
+            .map(|rest| rest.strip_prefix('.').unwrap_or(rest).to_string())
+            .filter(|_| path.is_empty() || collection.len() == path.len() || collection.as_bytes()[path.len()] == b'.')
+    }
+
+    /// Check a pending write of `value` at `path` against every overlapping quota,
+    /// with the data write lock held. Under evict_oldest, a write replacing the
+    /// collection (or an ancestor) is trimmed to fit, never dropping the array
+    /// indexes in `keep`; a write inside it evicts the collection's oldest other
+    /// entries first (object collections only).
+    fn enforce_quotas(&self, data: &mut Value, path: &str, value: &mut Value, keep: &[usize]) -> Result<()> {
+        let mut evictions: Vec<String> = Vec::new();
+        let mut quotas = self.quotas.lock();
+        for quota in quotas.values_mut().filter(|q| paths_overlap(&q.collection, path)) {
+            let exceeded = |quota: &mut Quota, entries: usize, bytes: usize| -> napi::Error {
+                quota.rejected += 1;
+                DbError::QuotaExceeded(format!(
+                    "Write to '{}' would put '{}' at {} entries / {} bytes (limits: {} entries / {} bytes)",
+                    path,
+                    quota.collection,
+                    entries,
+                    bytes,
+                    quota.max_entries.map_or("-".to_string(), |n| n.to_string()),
+                    quota.max_bytes.map_or("-".to_string(), |n| n.to_string()),
+                )).into()
+            };
+
+            if let Some(relative) = Self::relative_below(&quota.collection, path) {
+                // Replacing the collection or an ancestor of it
+                let Some(incoming) = (if relative.is_empty() { Some(&mut *value) } else { value.pointer_mut(&Self::to_pointer(&relative)) }) else {
+                    continue;
+                };
+                quota.refresh(data.pointer(&Self::to_pointer(&quota.collection)));
+                let (entries, bytes) = Quota::measure(incoming);
+                if !quota.exceeds(entries, bytes) {
+                    continue;
+                }
+                let trimmed = match quota.policy {
+                    QuotaPolicy::Reject => None,
+                    QuotaPolicy::EvictOldest => quota.trim(incoming, if relative.is_empty() { keep } else { &[] }),
+                };
+                match trimmed {
+                    Some(n) => quota.evicted += n as u64,
+                    None => return Err(exceeded(quota, entries, bytes)),
+                }
+                continue;
+            }
+
+            // Writing inside one entry of the collection
+            let rest = &path[quota.collection.len() + 1..];
+            let (key, inner) = rest.split_once('.').unwrap_or((rest, ""));
+            let collection = data.pointer(&Self::to_pointer(&quota.collection));
+            let (entries, bytes, old_size) = match collection {
+                Some(Value::Object(_)) => {
+                    quota.refresh(collection);
+                    let (entries, bytes) = quota.usage();
+                    (entries, bytes, quota.size_of(key))
+                }
+                Some(other) => {
+                    let (entries, bytes) = Quota::measure(other);
+                    let old = key.parse::<usize>().ok().and_then(|i| other.as_array()?.get(i)).map(approx_size);
+                    (entries, bytes, old)
+                }
+                None => (0, 0, None),
+            };
+            let new_size = match (old_size, inner.is_empty()) {
+                (_, true) => Quota::entry_size(key, value),
+                (Some(old), false) => match data.pointer(&Self::to_pointer(path)) {
+                    Some(replaced) => (old + approx_size(value)).saturating_sub(approx_size(replaced)),
+                    None => old + 24 + inner.len() + approx_size(value),
+                },
+                (None, false) => Quota::entry_size(key, &Value::Null) + inner.len() + approx_size(value),
+            };
+            let entries = entries + usize::from(old_size.is_none());
+            let bytes = (bytes + new_size).saturating_sub(old_size.unwrap_or(0));
+            if !quota.exceeds(entries, bytes) {
+                continue;
+            }
+            let victims = match (quota.policy, collection) {
+                (QuotaPolicy::EvictOldest, Some(Value::Object(_))) => quota.victims(entries, bytes, key),
+                _ => None,
+            };
+            match victims {
+                Some(victims) => {
+                    quota.evicted += victims.len() as u64;
+                    evictions.extend(victims.iter().map(|k| Self::join_path(&quota.collection, k)));
+                }
+                None => return Err(exceeded(quota, entries, bytes)),
+            }
+        }
+        // Released before the deletes: logging them notes the writes on every quota
+        drop(quotas);
+
+        for victim in evictions {
+            let ptr = Self::to_pointer(&victim);
+            self.record_undo_with(&victim, || data.pointer(&ptr).cloned())?;
+            let value = self.removed_value(data, &victim);
+            let lsn = self.append_wal_record(WalOpType::Delete, &victim, None, Some("quota"))?;
+            Self::delete_value_at_path(data, &victim)?;
+            self.notify_delete(&victim, Some("quota"), lsn, value);
+        }
+        Ok(())
+    }
+
+    /// Store each quota's insertion order in the namespace so eviction order
+    /// survives a restart. Not WAL-logged; keys written after the last save
+    /// rank after the stored ones.
+    fn persist_quotas(&self) {
+        let mut data = self.data.write();
+        let mut quotas = self.quotas.lock();
+        if quotas.is_empty() {
+            return;
+        }
+        let mut stored = serde_json::Map::new();
+        for (collection, quota) in quotas.iter_mut() {
+            quota.refresh(data.pointer(&Self::to_pointer(collection)));
+            stored.insert(collection.clone(), quota.to_stored());
+        }
+        meta::set(&mut data, "quotas", Value::Object(stored));
+    }
+
+    fn load_quotas(data: &Value) -> HashMap<String, Quota> {
+        let Some(Value::Object(stored)) = meta::get(data, "quotas") else { return HashMap::new() };
+        stored.iter()
+            .filter_map(|(collection, stored)| Some((collection.clone(), Quota::from_stored(collection, stored)?)))
+            .collect()
+    }
+
+    // Constraints
+
+    /// Forbid two documents of `collection_path` from sharing the same values of
+    /// `fields` (dot paths). Checked on set, upsertMany, batch writes and array
+    /// edits; documents missing one of the fields are not constrained. Meant for
+    /// small collections: no index is kept on disk. Returns the violations already
+    /// in the data as `[{values, keys}]`; they are reported, not removed.
+    #[napi]
+    pub fn register_constraint(&self, name: String, collection_path: String, fields: Vec<String>) -> Result<Value> {
+        self.check_recovered()?;
+        if collection_path.is_empty() || meta::is_internal_path(&collection_path) {
+            return Err(DbError::InvalidArgument(format!("Cannot constrain documents under '{}'", collection_path)).into());
+        }
+        if fields.is_empty() || fields.iter().any(|f| f.is_empty()) {
+            return Err(DbError::InvalidArgument("A constraint needs at least one non-empty field".to_string()).into());
+        }
+        self.settle_staged(&collection_path)?;
+
+        let mut data = self.data.write();
+        let constraint = Constraint::new(collection_path.clone(), fields);
+        let violations = constraint.duplicates(data.pointer(&Self::to_pointer(&collection_path)));
+        let mut constraints = self.constraints.lock();
+        constraints.insert(name, constraint);
+        let stored: serde_json::Map<String, Value> = constraints.iter()
+            .map(|(name, constraint)| (name.clone(), constraint.to_stored()))
+            .collect();
+        drop(constraints);
+        self.set_internal(&mut data, "constraints", Value::Object(stored))?;
+        Ok(Value::Array(violations))
+    }
+
+    #[napi]
+    pub fn drop_constraint(&self, name: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut stored = Self::get_internal(&data, "constraints").unwrap_or_else(|| json!({}));
+        let removed = stored.as_object_mut().and_then(|m| m.shift_remove(&name)).is_some();
+        if removed {
+            self.set_internal(&mut data, "constraints", stored)?;
+        }
+        Ok(self.constraints.lock().remove(&name).is_some() || removed)
+    }
+
+    /// Audit a collection against each of its constraints:
+    /// `[{name, fields, rejected, violations: [{values, keys}]}]`
+    #[napi]
+    pub fn check_constraints(&self, collection_path: String) -> Result<Value> {
+        self.check_recovered()?;
+        self.settle_staged(&collection_path)?;
+        let data = self.data.read();
+        let collection = data.pointer(&Self::to_pointer(&collection_path));
+        let constraints = self.constraints.lock();
+        let mut report: Vec<(&String, Value)> = constraints.iter()
+            .filter(|(_, constraint)| constraint.collection == collection_path)
+            .map(|(name, constraint)| (name, json!({
+                "name": name,
+                "fields": constraint.fields,
+                "rejected": constraint.rejected,
+                "violations": constraint.duplicates(collection),
+            })))
+            .collect();
+        report.sort_by(|a, b| a.0.cmp(b.0));
+        Ok(Value::Array(report.into_iter().map(|(_, entry)| entry).collect()))
+    }
+
+    /// Whether any constraint's collection overlaps `path`
+    fn constraint_applies(&self, path: &str) -> bool {
+        let constraints = self.constraints.lock();
+        !constraints.is_empty() && constraints.values().any(|c| paths_overlap(&c.collection, path))
+    }
+
+    /// Reject a pending write of `value` at `path` that would give a document the
+    /// same constrained values as another one, with the data write lock held. A
+    /// write replacing the collection (or an ancestor) must be free of duplicates.
+    fn check_constraint_write(&self, data: &Value, path: &str, value: &Value) -> Result<()> {
+        let mut constraints = self.constraints.lock();
+        for (name, constraint) in constraints.iter_mut().filter(|(_, c)| paths_overlap(&c.collection, path)) {
+            let fields = constraint.fields.join(", ");
+            let violation = match Self::relative_below(&constraint.collection, path) {
+                Some(relative) => {
+                    let incoming = if relative.is_empty() { Some(value) } else { value.pointer(&Self::to_pointer(&relative)) };
+                    constraint.duplicates(incoming).into_iter().next().map(|dup| format!(
+                        "keys {} would share ({}) = {}", dup["keys"], fields, dup["values"]
+                    ))
+                }
+                None => {
+                    let rest = &path[constraint.collection.len() + 1..];
+                    let (key, inner) = rest.split_once('.').unwrap_or((rest, ""));
+                    if !constraint.touches(inner) {
+                        continue;
+                    }
+                    let new_doc = if inner.is_empty() {
+                        Cow::Borrowed(value)
+                    } else {
+                        let current = data.pointer(&Self::to_pointer(&Self::join_path(&constraint.collection, key)));
+                        let mut doc = current.cloned().unwrap_or_else(|| json!({}));
+                        if Self::set_value_at_path(&mut doc, inner, value.clone()).is_err() {
+                            continue;
+                        }
+                        Cow::Owned(doc)
+                    };
+                    let Some(tuple) = constraint.tuple_of(&new_doc) else { continue };
+                    constraint.refresh(data.pointer(&Self::to_pointer(&constraint.collection)));
+                    constraint.holder(&tuple, key).map(|holder| format!(
+                        "'{}' would have ({}) = {}, already held by '{}'", key, fields, tuple, holder
+                    ))
+                }
+            };
+            if let Some(violation) = violation {
+                constraint.rejected += 1;
+                return Err(DbError::ConstraintViolation(format!(
+                    "Constraint '{}' on '{}' violated: {}", name, constraint.collection, violation
+                )).into());
+            }
+        }
+        Ok(())
+    }
+
+    fn load_constraints(data: &Value) -> HashMap<String, Constraint> {
+        let Some(Value::Object(stored)) = meta::get(data, "constraints") else { return HashMap::new() };
+        stored.iter()
+            .filter_map(|(name, stored)| Some((name.clone(), Constraint::from_stored(stored)?)))
+            .collect()
+    }
+
+    // Cascades
+
+    /// Act on the documents of `child_collection` whose `child_field` holds the key
+    /// of a deleted document of `parent_collection`: "delete" them (cascading
+    /// further), "nullify" the field, or "restrict" (refuse the parent delete
+    /// while any exist). Applies to delete, deleteMany and deletePrefix of direct
+    /// children of the parent collection. Rejects a rule that would close a cycle.
+    #[napi]
+    pub fn register_cascade(&self, name: String, parent_collection: String, child_collection: String, child_field: String, action: String) -> Result<()> {
+        self.check_recovered()?;
+        let action = cascade::Action::parse(&action)?;
+        for collection in [&parent_collection, &child_collection] {
+            if collection.is_empty() || meta::is_internal_path(collection) {
+                return Err(DbError::InvalidArgument(format!("Cannot cascade between documents under '{}'", collection)).into());
+            }
+        }
+        if name.is_empty() || child_field.is_empty() {
+            return Err(DbError::InvalidArgument("A cascade needs a name and a child field".to_string()).into());
+        }
+
+        let mut data = self.data.write();
+        let mut rules = cascade::load(meta::get(&data, "cascades"));
+        rules.retain(|(existing, _)| *existing != name);
+        rules.push((name.clone(), cascade::Rule::new(parent_collection, child_collection, child_field, action)));
+        if let Some(cycle) = cascade::find_cycle(rules.iter().map(|(_, rule)| rule)) {
+            return Err(DbError::InvalidArgument(format!(
+                "Cascade '{}' would form a cycle: {}", name, cycle.join(" -> ")
+            )).into());
+        }
+        let mut stored = Self::get_internal(&data, "cascades").unwrap_or_else(|| json!({}));
+        if let Some(map) = stored.as_object_mut() {
+            map.insert(name, rules.last().map(|(_, rule)| rule.to_stored()).unwrap_or(Value::Null));
+        }
+        self.set_internal(&mut data, "cascades", stored)
+    }
+
+    #[napi]
+    pub fn drop_cascade(&self, name: String) -> Result<bool> {
+        self.check_recovered()?;
+        let mut data = self.data.write();
+        let mut stored = Self::get_internal(&data, "cascades").unwrap_or_else(|| json!({}));
+        let removed = stored.as_object_mut().and_then(|m| m.shift_remove(&name)).is_some();
+        if removed {
+            self.set_internal(&mut data, "cascades", stored)?;
+        }
+        Ok(removed)
+    }
+
+    /// Registered cascades: `{name: {parent, child, field, action}}`
+    #[napi]
+    pub fn cascades(&self) -> Result<Value> {
+        self.check_recovered()?;
+        Ok(Self::get_internal(&self.data.read(), "cascades").unwrap_or_else(|| json!({})))
+    }
+
+    /// Keys of the documents of the rule's child collection referencing `key`,
+    /// through an index on the child field when there is one
+    fn cascade_children(&self, data: &Value, rule: &cascade::Rule, key: &str) -> Vec<String> {
+        let Some(Value::Object(children)) = data.pointer(&Self::to_pointer(&rule.child)) else { return Vec::new() };
+        let covering = |idx: &BTreeIndex| idx.field() == rule.field && idx.collection().as_deref() == Some(rule.child.as_str());
+        let name = self.indexes.read().iter().find(|(_, idx)| covering(idx)).map(|(name, _)| name.clone());
+        if let Some(name) = &name {
+            self.upkeep_index_in(data, name);
+        }
+        let indexes = self.indexes.read();
+        match name.as_ref().and_then(|name| indexes.get(name)) {
+            // Index entries are checked against the data, in case the index is stale
+            Some(idx) => idx.find(&Value::String(key.to_string()))
+                .map(|paths| paths.iter()
+                    .filter_map(|p| p.strip_prefix(rule.child.as_str())?.strip_prefix('.'))
+                    .filter(|k| children.get(*k).is_some_and(|doc| rule.references(doc, key)))
+                    .map(String::from)
+                    .collect())
+                .unwrap_or_default(),
+            None => children.iter()
+                .filter(|(_, doc)| rule.references(doc, key))
+                .map(|(k, _)| k.clone())
+                .collect(),
+        }
+    }
+
+    /// What deleting `keys` of `collection` cascades into, with the data write lock
+    /// held. Fails, before anything is written, if a restrict rule has children
+    /// that would survive the delete.
+    fn plan_cascade(&self, data: &Value, collection: &str, keys: &[String]) -> Result<cascade::Plan> {
+        let mut plan = cascade::Plan::default();
+        let rules = cascade::load(meta::get(data, "cascades"));
+        if !rules.iter().any(|(_, rule)| rule.parent == collection) {
+            return Ok(plan);
+        }
+
+        let mut doomed: std::collections::HashSet<String> = keys.iter().map(|k| Self::join_path(collection, k)).collect();
+        let mut pending: std::collections::VecDeque<(String, String)> = keys.iter()
+            .map(|k| (collection.to_string(), k.clone()))
+            .collect();
+        let mut restricted: Vec<(&str, String, String)> = Vec::new();
+        while let Some((parent, key)) = pending.pop_front() {
+            for (name, rule) in rules.iter().filter(|(_, rule)| rule.parent == parent) {
+                for child_key in self.cascade_children(data, rule, &key) {
+                    let child_path = Self::join_path(&rule.child, &child_key);
+                    match rule.action {
+                        cascade::Action::Delete => {
+                            if doomed.insert(child_path.clone()) {
+                                plan.deletes.push((name.clone(), child_path));
+                                pending.push_back((rule.child.clone(), child_key));
+                            }
+                        }
+                        cascade::Action::Nullify => plan.nullifies.push((name.clone(), child_path, rule.field.clone())),
+                        cascade::Action::Restrict => restricted.push((name, Self::join_path(&parent, &key), child_path)),
+                    }
+                }
+            }
+        }
+
+        if let Some((name, parent, child)) = restricted.iter().find(|(_, _, child)| !doomed.contains(child)) {
+            return Err(DbError::ConstraintViolation(format!(
+                "Cascade '{}' restricts deleting '{}': '{}' references it", name, parent, child
+            )).into());
+        }
+        // Children deleted by another rule need no nullifying
+        let mut seen = std::collections::HashSet::new();
+        plan.nullifies.retain(|(_, doc, field)| !doomed.contains(doc) && seen.insert(Self::join_path(doc, field)));
+        for (_, path) in &plan.deletes {
+            self.freezes.check(path)?;
+        }
+        for (_, doc, field) in &plan.nullifies {
+            self.freezes.check(&Self::join_path(doc, field))?;
+        }
+        Ok(plan)
+    }
+
+    /// Apply a cascade under the write-lock hold of the delete that triggered it.
+    /// Each write is undo-recorded and WAL-logged, so a rollback reverses it too.
+    fn apply_cascade(&self, data: &mut Value, plan: &cascade::Plan) -> Result<()> {
+        for (_, doc, field) in &plan.nullifies {
+            let path = Self::join_path(doc, field);
+            self.record_undo_in(data, &path)?;
+            self.append_wal(WalOpType::Set, &path, Some(Value::Null))?;
+            Self::set_value_at_path(data, &path, Value::Null)?;
+        }
+        for (_, path) in &plan.deletes {
+            self.record_undo_in(data, path)?;
+            let value = self.removed_value(data, path);
+            let lsn = self.append_wal_record(WalOpType::Delete, path, None, Some("cascade"))?;
+            Self::delete_value_at_path(data, path)?;
+            self.notify_delete(path, Some("cascade"), lsn, value);
+        }
+
+        let deleted: std::collections::HashSet<&str> = plan.deletes.iter().map(|(_, path)| path.as_str()).collect();
+        let mut indexes = self.indexes.write();
+        for idx in indexes.values_mut() {
+            if !deleted.is_empty() {
+                idx.remove_where(|doc_path| deleted.contains(doc_path));
+            }
+            for (_, doc, field) in &plan.nullifies {
+                if idx.field() == field && idx.collection().as_deref() == doc.rsplit_once('.').map(|(c, _)| c) {
+                    idx.insert(&Value::Null, doc.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Snapshots
+
+    /// Freeze the current state and return a handle for consistent reads. An
+    /// open exclusive transaction's writes are left out.
+    #[napi]
+    pub fn snapshot_open(&self) -> Result<u32> {
+        self.check_recovered()?;
+        self.flush_coalesced()?;
+        // A snapshot cannot load entries later
+        self.fault_in("")?;
+        let mut table = self.snapshots.lock();
+        if table.open.len() >= table.max_open {
+            return Err(DbError::LimitExceeded(format!(
+                "Too many open snapshots (max {})", table.max_open
+            )).into());
+        }
+        let root = self.committed_root(&self.data.read(), &[""])?.into_owned();
+        let size = approx_size(&root);
+        let handle = table.next_handle;
+        table.next_handle = table.next_handle.wrapping_add(1).max(1);
+        table.open.insert(handle, (Arc::new(root), size));
+        Ok(handle)
+    }
+
+    /// Release a snapshot. Returns false for unknown handles.
+    #[napi]
+    pub fn snapshot_close(&self, handle: u32) -> Result<bool> {
+        self.check_open()?;
+        Ok(self.snapshots.lock().open.remove(&handle).is_some())
+    }
+
+    /// Limit how many snapshots may be open at once (default: 16)
+    #[napi]
+    pub fn set_max_snapshots(&self, max_open: u32) -> Result<()> {
+        self.check_open()?;
+        self.snapshots.lock().max_open = max_open as usize;
+        Ok(())
+    }
+
+    fn snapshot_root(&self, handle: u32) -> Result<Arc<Value>> {
+        self.snapshots.lock().open.get(&handle)
+            .map(|(root, _)| root.clone())
+            .ok_or_else(|| DbError::NotFound(format!("Snapshot {} is not open", handle)).into())
+    }
+
+    #[napi]
+    pub fn snapshot_get(&self, handle: u32, path: String) -> Result<Value> {
+        self.check_open()?;
+        let root = self.snapshot_root(handle)?;
+        if path.is_empty() {
+            self.check_result_size(&root, "snapshotGet")?;
+            return Ok(meta::strip_internal(&root));
+        }
+        if meta::is_internal_path(&path) {
+            return Ok(Value::Null);
+        }
+        let mut value = compress::pointer(&root, &Self::to_pointer(&path)).map(Cow::into_owned).unwrap_or(Value::Null);
+        compress::expand(&mut value);
+        self.check_result_size(&value, "snapshotGet")?;
+        Ok(value)
+    }
+
+    #[napi]
+    pub fn snapshot_query(&self, handle: u32, path: String, filters: Vec<QueryFilter>, options: Option<QueryOptions>) -> Result<Value> {
+        self.check_open()?;
+        self.traced("snapshotQuery", &path, |span| {
+            self.check_filters(&filters)?;
+            let emit = Emit::parse(options.and_then(|o| o.emit).as_deref())?;
+            let root = self.snapshot_root(handle)?;
+            Self::trace_scan(span, &root, &path, !filters.is_empty());
+            let result = self.query_in(&root, path.clone(), filters, emit)?;
+            span.returned(&result);
+            self.check_result_size(&result, "snapshotQuery")?;
+            Ok(result)
+        })
+    }
+
+    #[napi]
+    pub fn snapshot_aggregate(&self, handle: u32, path: String, operation: String, field: Option<String>) -> Result<Value> {
+        self.check_open()?;
+        self.traced("snapshotAggregate", &path, |span| {
+            let root = self.snapshot_root(handle)?;
+            Self::trace_scan(span, &root, &path, operation != "count");
+            span.returned = Some(1);
+            self.aggregate_in(&root, path.clone(), operation, field)
+        })
+    }
+
+    /// Rebuild the subtree at `path` (default: the whole tree) with exactly-sized
+    /// allocations so memory left behind by deletes can be returned. Runs under
+    /// the write lock one child at a time, so the pause per step is bounded by
+    /// the largest child. Sizes are estimates.
+    #[napi]
+    pub fn compact_memory(&self, path: Option<String>) -> Result<Value> {
+        self.check_recovered()?;
+        let start = std::time::Instant::now();
+        let path = path.unwrap_or_default();
+        let ptr = Self::to_pointer(&path);
+
+        let (keys, before) = {
+            let data = self.data.read();
+            let Some(target) = data.pointer(&ptr) else {
+                return Err(DbError::PathNotFound(path).into());
+            };
+            let keys: Vec<String> = match target {
+                Value::Object(map) => map.keys().cloned().collect(),
+                Value::Array(arr) => (0..arr.len()).map(|i| i.to_string()).collect(),
+                _ => Vec::new(),
+            };
+            (keys, reclaim::allocated_size(target))
+        };
+
+        for key in &keys {
+            let mut data = self.data.write();
+            let child = match data.pointer_mut(&ptr) {
+                Some(Value::Object(map)) => map.get_mut(key),
+                Some(Value::Array(arr)) => key.parse::<usize>().ok().and_then(|i| arr.get_mut(i)),
+                _ => None,
+            };
+            if let Some(child) = child {
+                *child = reclaim::compact(std::mem::take(child));
+            }
+        }
+
+        // The container itself: children are already compact, so this only moves them
+        let after = {
+            let mut data = self.data.write();
+            let Some(target) = data.pointer_mut(&ptr) else {
+                return Err(DbError::PathNotFound(path).into());
+            };
+            *target = match std::mem::take(target) {
+                Value::Object(map) => Value::Object(reclaim::shrink_map(map, |v| v)),
+                Value::Array(mut arr) => {
+                    arr.shrink_to_fit();
+                    Value::Array(arr)
+                }
+                other => reclaim::compact(other),
+            };
+            reclaim::allocated_size(target)
+        };
+
+        Ok(json!({
+            "path": path,
+            "chunks": keys.len(),
+            "beforeBytes": before,
+            "afterBytes": after,
+            "reclaimedBytes": before.saturating_sub(after),
+            "ms": start.elapsed().as_millis() as u64,
+        }))
+    }
+
+    /// Make `delete` run `compact_memory` on the parent when the removed subtree
+    /// is at least `bytes` (approximate) in size (0 = off)
+    #[napi]
+    pub fn set_reclaim_threshold(&self, bytes: i64) -> Result<()> {
+        self.check_open()?;
+        self.reclaim_threshold.store(bytes.max(0) as usize, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Lazy collections
+
+    fn lazy(&self) -> Result<&LazyCollections> {
+        self.lazy.as_deref().ok_or_else(|| DbError::InvalidArgument("No lazy collections are configured".to_string()).into())
+    }
+
+    /// Drop a lazy entry (`events.<key>`), or every entry of a lazy collection,
+    /// from memory; the next access reads it from disk again. Pinned entries and
+    /// entries written since the last save stay. Returns how many were dropped.
+    #[napi]
+    pub fn evict(&self, path: String) -> Result<u32> {
+        self.check_recovered()?;
+        let lazy = self.lazy()?;
+        let mut store = lazy.store.lock();
+        let mut data = self.data.write();
+        Ok(store.evict(&path, &mut data, &lazy.dirty.lock())?)
+    }
+
+    /// Load a lazy entry and keep it in memory until `unpin`. Returns whether it exists.
+    #[napi]
+    pub fn pin(&self, path: String) -> Result<bool> {
+        self.check_recovered()?;
+        let lazy = self.lazy()?;
+        let mut store = lazy.store.lock();
+        let mut data = self.data.write();
+        Ok(store.pin(&path, true, &mut data, &lazy.dirty.lock())?)
+    }
+
+    /// Let a pinned entry be evicted again. Returns whether it was pinned.
+    #[napi]
+    pub fn unpin(&self, path: String) -> Result<bool> {
+        self.check_recovered()?;
+        let lazy = self.lazy()?;
+        let mut store = lazy.store.lock();
+        let mut data = self.data.write();
+        Ok(store.pin(&path, false, &mut data, &lazy.dirty.lock())?)
+    }
+
+    /// `{enabled, maxResident, resident, loads, evictions, fullLoads, collections:
+    /// {name: {stored, resident, pinned, dirty, replaced, complete, dataBytes}}}`
+    #[napi]
+    pub fn lazy_status(&self) -> Result<Value> {
+        self.check_open()?;
+        let Some(lazy) = self.lazy.as_deref() else {
+            return Ok(json!({ "enabled": false }));
+        };
+        let mut status = lazy.store.lock().status(&lazy.dirty.lock());
+        status["enabled"] = json!(true);
+        Ok(status)
+    }
+
+    /// Engine statistics
+    #[napi]
+    pub fn db_stats(&self) -> Result<Value> {
+        self.check_recovered()?;
+        let coalescing = match self.coalescer.lock().as_ref() {
+            Some(coalescer) => coalescer.stats(),
+            None => json!({ "enabled": false }),
+        };
+        let (values, stored, raw) = compress::stats(&self.data.read());
+        let table = self.snapshots.lock();
+        Ok(json!({
+            "snapshots": {
+                "open": table.open.len(),
+                "max": table.max_open,
+                "approxBytes": table.open.values().map(|(_, size)| size).sum::<usize>(),
+            },
+            "coalescing": coalescing,
+            "throttle": self.write_limiter.stats(),
+            "valueLimits": self.value_limits.stats(),
+            "frozen": self.freezes.stats(),
+            "ttl": self.ttl.stats(),
+            "compression": {
+                "values": values,
+                "storedBytes": stored,
+                "originalBytes": raw,
+                "savedBytes": raw.saturating_sub(stored),
+                "thresholdBytes": self.compress_threshold.load(Ordering::Relaxed),
+            },
+        }))
+    }
+
+    /// Look under `path` (default: everything) for likely bad data: unsafe
+    /// integers, replacement and control characters, empty or unaddressable
+    /// keys, arrays ending in nulls and excessive nesting (anomaly.rs lists the
+    /// kinds). Returns `{anomalies: [{path, kind, detail}], counts, truncated,
+    /// fixed}`, findings grouped by kind. Top-level keys are scanned in parallel.
+    /// With `fixTrailingNulls` the padding is trimmed and `fixed` lists the
+    /// arrays trimmed; nothing else is ever changed.
+    #[napi]
+    pub fn scan_anomalies(&self, path: Option<String>, options: Option<ScanAnomaliesOptions>) -> Result<Value> {
+        self.check_recovered()?;
+        let path = path.unwrap_or_default();
+        let options = options.unwrap_or_default();
+        let limits = anomaly::Limits {
+            per_kind: options.limit.unwrap_or(100) as usize,
+            max_depth: options.max_depth.map_or(self.options.path_rules.max_depth, |d| d as usize),
+        };
+        if !path.is_empty() {
+            if meta::is_internal_path(&path) {
+                return Err(DbError::InvalidArgument(format!("Cannot scan '{}'", path)).into());
+            }
+            self.settle_staged(&path)?;
+        } else {
+            self.flush_coalesced()?;
+        }
+
+        let scan = {
+            let data = self.data.read();
+            match compress::pointer(&data, &Self::to_pointer(&path)) {
+                Some(value) => {
+                    let skip = path.is_empty().then_some(meta::META_KEY);
+                    anomaly::scan_tree(&value, &path, skip, limits, THREAD_CONFIG.use_parallel)
+                }
+                None => return Err(DbError::PathNotFound(format!("Path does not exist: {}", path)).into()),
+            }
+        };
+        let mut report = scan.to_json();
+
+        let mut fixed = Vec::new();
+        if options.fix_trailing_nulls.unwrap_or(false) && !scan.trailing_nulls.is_empty() {
+            self.throttle(scan.trailing_nulls.len())?;
+            let mut data = self.data.write();
+            for array_path in scan.trailing_nulls {
+                // Compressed arrays and arrays changed since the scan are left alone
+                let Some(Value::Array(items)) = data.pointer(&Self::to_pointer(&array_path)) else { continue };
+                let keep = items.len() - items.iter().rev().take_while(|v| v.is_null()).count();
+                if keep == items.len() {
+                    continue;
+                }
+                let mut trimmed = Value::Array(items[..keep].to_vec());
+                if self.guarded(&array_path) {
+                    self.guard_write(&mut data, &array_path, &mut trimmed, &[])?;
+                }
+                self.record_undo_in(&data, &array_path)?;
+                self.append_wal(WalOpType::Set, &array_path, Some(trimmed.clone()))?;
+                Self::set_value_at_path(&mut data, &array_path, trimmed)?;
+                fixed.push(array_path);
+            }
+        }
+        report["fixed"] = json!(fixed);
+        Ok(report)
+    }
+
+    /// Findings about the usage pattern, from save history, WAL size and
+    /// pressure, the data file and index sizes: `{generatedAt, windowMs,
+    /// findings: [{code, severity, message, metrics}], inputs}`. Codes are
+    /// stable: SAVE_AMPLIFICATION, PRETTY_FILE_OVERHEAD, WAL_OUTGROWS_DATA,
+    /// WAL_BACKLOG, WAL_BACKPRESSURE, INDEX_LARGER_THAN_COLLECTION. Serializes
+    /// the data once to size it, so call it from monitoring, not per request.
+    #[napi]
+    pub fn advisor_report(&self, options: Option<AdvisorOptions>) -> Result<Value> {
+        self.check_open()?;
+        let stats = self.db_stats()?;
+        let options = options.unwrap_or_default();
+        let defaults = advisor::Thresholds::default();
+        let thresholds = advisor::Thresholds {
+            window_ms: options.window_ms.map_or(defaults.window_ms, |ms| ms.max(0) as u64),
+            min_bytes: options.min_bytes.map_or(defaults.min_bytes, |b| b.max(0) as u64),
+            save_amplification: options.save_amplification.unwrap_or(defaults.save_amplification),
+            wal_growth: options.wal_growth.unwrap_or(defaults.wal_growth),
+            pretty_overhead: options.pretty_overhead.unwrap_or(defaults.pretty_overhead),
+            index_ratio: options.index_ratio.unwrap_or(defaults.index_ratio),
+        };
+        let now = now_ms();
+        let saves = self.persistence.lock().since(now.saturating_sub(thresholds.window_ms));
+
+        let (compact_bytes, indexes) = {
+            let data = self.data.read();
+            let skip: std::collections::HashSet<String> = self.lazy.as_ref()
+                .map(|lazy| lazy.names.iter().cloned().collect())
+                .unwrap_or_default();
+            let compact_bytes = advisor::serialized_bytes(&BaseView { root: &data, skip: &skip });
+            let indexes = self.indexes.read().iter()
+                .map(|(name, idx)| {
+                    let collection = idx.collection();
+                    let collection_bytes = collection.as_deref()
+                        .and_then(|c| data.pointer(&Self::to_pointer(c)))
+                        .map_or(0, advisor::serialized_bytes);
+                    advisor::IndexSize {
+                        name: name.clone(),
+                        collection,
+                        entries: idx.len(),
+                        bytes: advisor::serialized_bytes(idx),
+                        collection_bytes,
+                    }
+                })
+                .collect::<Vec<_>>();
+            (compact_bytes, indexes)
+        };
+        let inputs = advisor::Inputs {
+            saves: &saves,
+            file_bytes: fs::metadata(&self.path).ok().map(|m| m.len()),
+            compact_bytes,
+            wal_bytes: self.wal.as_ref().map(|_| fs::metadata(&self.wal_path).map_or(0, |m| m.len())),
+            wal_pressure: self.wal.as_ref().map(|wal| wal.pressure()),
+            indexes,
+        };
+        let findings = advisor::findings(&inputs, &thresholds);
+
+        Ok(json!({
+            "generatedAt": now,
+            "windowMs": thresholds.window_ms,
+            "findings": findings,
+            "inputs": {
+                "saves": {
+                    "count": saves.len(),
+                    "bytesRewritten": saves.iter().map(|s| s.bytes).sum::<u64>(),
+                    "walBytes": saves.iter().map(|s| s.wal_bytes_truncated).sum::<u64>(),
+                    "totalMs": saves.iter().map(|s| s.total_ms).sum::<f64>(),
+                },
+                "file": { "bytes": inputs.file_bytes, "compactBytes": inputs.compact_bytes },
+                "wal": {
+                    "enabled": self.wal.is_some(),
+                    "bytes": inputs.wal_bytes,
+                    "pressure": self.wal_pressure()?,
+                },
+                "indexes": inputs.indexes.iter().map(|idx| json!({
+                    "name": idx.name,
+                    "collection": idx.collection,
+                    "entries": idx.entries,
+                    "bytes": idx.bytes,
+                    "collectionBytes": idx.collection_bytes,
+                })).collect::<Vec<_>>(),
+                "stats": stats,
+            },
+        }))
+    }
+
+    // Integrity
+
+    /// Rewrite a WAL file without records superseded by later writes,
+    /// preserving the surviving LSNs. For offline use only: no process may
+    /// have the database open.
+    #[napi]
+    pub fn compact_wal(wal_path: String) -> Result<Value> {
+        if !Path::new(&wal_path).exists() {
+            return Err(DbError::PathNotFound(format!("WAL file not found: {}", wal_path)).into());
+        }
+        let stats = compact_wal(&wal_path).map_err(DbError::from)?;
+        Ok(json!({
+            "before": stats.before,
+            "after": stats.after,
+            "removed": stats.before - stats.after,
+            "stopReason": stats.stopped_early,
+        }))
+    }
+
+    /// State of the world after open: main file, WAL replay, and indexes.
+    /// With `deep`, every index entry is checked against the data.
+    #[napi]
+    pub fn integrity_report(&self, deep: Option<bool>) -> Result<Value> {
+        // Report a finished deferred replay, if any
+        let _ = self.check_recovered();
+        // Custom indexes are checked by their extractor, before any lock is taken
+        let custom: Vec<String> = self.custom_indexes.lock().keys().cloned().collect();
+        let mut custom_verified: HashMap<String, Value> = HashMap::new();
+        for name in custom.iter().filter(|_| deep.unwrap_or(false)) {
+            let verified = self.verify_custom_index(name.clone()).unwrap_or_else(|e| json!({ "error": e.reason }));
+            custom_verified.insert(name.clone(), verified);
+        }
+        // Writes still pending for tracked indexes are not drift
+        if deep.unwrap_or(false) {
+            let _ = self.upkeep_indexes();
+        }
+        let report = self.load_report.lock();
+        let mut wal = report.replay.to_json(self.wal.is_some());
+        wal["recovery"] = self.recovery.status();
+
+        let data = self.data.read();
+        let indexes = self.indexes.read();
+        let index_reports: serde_json::Map<String, Value> = indexes.iter()
+            .map(|(name, idx)| {
+                let mut entry = json!({
+                    "field": idx.field(),
+                    "loadedFromDisk": idx.loaded_from_disk(),
+                });
+                if let Some(collection) = idx.tracked() {
+                    entry["collection"] = json!(collection);
+                }
+                if custom.contains(name) {
+                    entry["custom"] = json!(true);
+                    if let Some(verified) = custom_verified.remove(name) {
+                        entry["verify"] = verified;
+                    }
+                } else if deep.unwrap_or(false) {
+                    entry["verify"] = idx.verify(&data);
+                }
+                (name.clone(), entry)
+            })
+            .collect();
+
+        Ok(json!({
+            "file": {
+                "exists": report.file_exists,
+                "parsed": report.main_file_error.is_none(),
+                "size": report.file_size,
+                "readAttempts": report.read_attempts,
+                "error": report.main_file_error,
+                "newerTmp": report.newer_tmp,
+                "relocatedReservedKey": report.relocated_key,
+            },
+            "wal": wal,
+            "indexes": index_reports,
+            "backupUsed": report.backup_used.is_some(),
+            "backupPath": report.backup_used,
+        }))
+    }
+
+    // Indexing API
+    
+    /// Register an index on `field`. With `collection_path` the engine keeps it up
+    /// to date: every write under the collection reaches the index before it is
+    /// next read or saved, so `update_index` is never needed. An index file that
+    /// predates the registration, or a WAL replayed over it, is re-derived from
+    /// the data. A sparse index leaves out documents whose field is null too,
+    /// so looking up null finds nothing; registering an index again with the
+    /// other `sparse` setting re-derives a tracked one.
+    #[napi]
+    pub fn register_index(&self, name: String, field: String, collection_path: Option<String>, options: Option<IndexOptions>) -> Result<()> {
+        self.check_open()?;
+        if let Some(collection) = &collection_path {
+            if collection.is_empty() || meta::is_internal_path(collection) {
+                return Err(DbError::InvalidArgument(format!("Cannot index documents under '{}'", collection)).into());
+            }
+        }
+        if self.custom_indexes.lock().contains_key(&name) {
+            return Err(DbError::InvalidArgument(format!("Index '{}' is a custom index", name)).into());
+        }
+        let sparse = options.and_then(|o| o.sparse).unwrap_or(false);
+        let (trusted, resparsed) = {
+            let mut indexes = self.indexes.write();
+            if let std::collections::hash_map::Entry::Vacant(entry) = indexes.entry(name.clone()) {
+                 let idx = BTreeIndex::load_or_create(name.clone(), field, &self.path)
+                     .map_err(|e| DbError::Index(format!("Failed to load index {}: {}", name, e)))?;
+                 entry.insert(idx);
+            }
+            let idx = indexes.get_mut(&name).expect("index was just registered");
+            let resparsed = idx.set_sparse(sparse);
+            let Some(collection) = &collection_path else { return Ok(()) };
+            let trusted = idx.loaded_from_disk() && idx.tracked() == Some(collection.as_str()) && !resparsed;
+            idx.track(collection.clone());
+            (trusted, resparsed)
+        };
+        let Some(collection) = collection_path else { return Ok(()) };
+        let replayed = self.load_report.lock().replay.changed();
+        let mut upkeep = self.index_upkeep.lock();
+        match upkeep.get_mut(&name) {
+            Some(existing) if existing.collection == collection => {
+                if resparsed {
+                    existing.invalidate();
+                }
+            }
+            _ => {
+                upkeep.insert(name, Upkeep::new(collection, !trusted || replayed));
+            }
+        }
+        Ok(())
+    }
+    
+    #[napi]
+    pub fn update_index(&self, name: String, key: Value, path: String, is_delete: bool) -> Result<()> {
+        self.check_open()?;
+        let mut indexes = self.indexes.write();
+        if let Some(idx) = indexes.get_mut(&name) {
+            if is_delete {
+                idx.remove(&key, &path);
+            } else {
+                idx.insert(&key, path);
+            }
+        }
+        Ok(())
+    }
+    
+    #[napi]
+    pub fn find_index_paths(&self, name: String, key: Value) -> Result<Vec<String>> {
+        self.check_open()?;
+        self.upkeep_index(&name)?;
+        self.refresh_custom_index(&name)?;
+        let indexes = self.indexes.read();
+        if let Some(idx) = indexes.get(&name) {
+            if let Some(paths) = idx.find(&key) {
+                return Ok(paths.clone());
+            }
+        }
+        Ok(vec![])
+    }
+    
+    /// Paths of the documents with an index key between `start` and `end` (either may
+    /// be null for an open end), in key order. Bounds are inclusive unless excluded
+    /// in `options`. Numeric keys come first in numeric order, then the others as
+    /// strings; numeric bounds only match numeric keys.
+    #[napi]
+    pub fn find_index_range(&self, name: String, start: Option<Value>, end: Option<Value>, options: Option<IndexRangeOptions>) -> Result<Vec<String>> {
+        self.check_open()?;
+        self.upkeep_index(&name)?;
+        self.refresh_custom_index(&name)?;
+        let options = options.unwrap_or_default();
+        let indexes = self.indexes.read();
+        let Some(idx) = indexes.get(&name) else {
+            return Err(DbError::NotFound(format!("Index '{}' not found", name)).into());
+        };
+        Ok(idx.range(
+            index_bound(start.as_ref(), options.start_exclusive),
+            index_bound(end.as_ref(), options.end_exclusive),
+            options.limit.map(|l| l as usize).unwrap_or(usize::MAX),
+        ))
+    }
+
+    /// Distinct keys of an index in sorted order as `[{key, count}]`, read from the
+    /// index alone without touching documents
+    #[napi]
+    pub fn index_keys(&self, name: String, options: Option<IndexKeysOptions>) -> Result<Value> {
+        self.index_walk(name, options, |key, paths| json!({ "key": key, "count": paths.len() }))
+    }
+
+    /// Distinct keys of an index in sorted order with the paths indexed under
+    /// them, as `[[key, [paths]]]`, read from the index alone. Takes the options
+    /// of `index_keys`; `limit` and `offset` count keys, not paths.
+    #[napi]
+    pub fn index_entries(&self, name: String, options: Option<IndexKeysOptions>) -> Result<Value> {
+        self.index_walk(name, options, |key, paths| json!([key, paths]))
+    }
+
+    fn index_walk(&self, name: String, options: Option<IndexKeysOptions>, row: impl Fn(&str, &Vec<String>) -> Value) -> Result<Value> {
+        self.check_open()?;
+        self.upkeep_index(&name)?;
+        self.refresh_custom_index(&name)?;
+        let options = options.unwrap_or_default();
+        let indexes = self.indexes.read();
+        let Some(idx) = indexes.get(&name) else {
+            return Err(DbError::NotFound(format!("Index '{}' not found", name)).into());
+        };
+        let rows: Vec<Value> = idx
+            .entries_between(
+                index_bound(options.start.as_ref(), options.start_exclusive),
+                index_bound(options.end.as_ref(), options.end_exclusive),
+                options.prefix.as_deref(),
+                options.reverse.unwrap_or(false),
+            )
+            .skip(options.offset.unwrap_or(0) as usize)
+            .take(options.limit.map(|l| l as usize).unwrap_or(usize::MAX))
+            .map(|(key, paths)| row(key, paths))
+            .collect();
+        Ok(Value::Array(rows))
+    }
+    
+    /// Aggregate over an index key range without touching documents:
+    /// - "count": indexed documents with a key in `[start, end]`
+    /// - "distinct": distinct keys in `[start, end]`
+    ///
+    /// Numeric bounds compare keys numerically. `bucket` groups the result as
+    /// `[{bucket, count}]`: "prefix:N" by the first N characters of the key
+    /// (e.g. "prefix:10" for days of ISO dates), "interval:N" by numeric key
+    /// rounded down to a multiple of N.
+    #[napi]
+    pub fn index_aggregate(
+        &self,
+        name: String,
+        operation: String,
+        start: Option<Value>,
+        end: Option<Value>,
+        bucket: Option<String>,
+    ) -> Result<Value> {
+        self.check_open()?;
+        self.traced("indexAggregate", &name, |span| {
+            let distinct = match operation.as_str() {
+                "count" => false,
+                "distinct" => true,
+                other => return Err(DbError::InvalidArgument(format!("Unsupported index aggregate '{}'", other)).into()),
+            };
+            let bucket = bucket.as_deref().map(IndexBucket::parse).transpose()?;
+            self.upkeep_index(&name)?;
+            self.refresh_custom_index(&name)?;
+
+            let indexes = self.indexes.read();
+            let Some(idx) = indexes.get(&name) else {
+                return Err(DbError::NotFound(format!("Index '{}' not found", name)).into());
+            };
+            let keys = idx.key_counts_between(start.as_ref(), end.as_ref());
+            span.scanned = Some(keys.len());
+            let weight = |docs: usize| if distinct { 1 } else { docs as u64 };
+
+            let Some(bucket) = bucket else {
+                span.returned = Some(1);
+                return Ok(json!(keys.iter().map(|(_, docs)| weight(*docs)).sum::<u64>()));
+            };
+            let mut buckets: Vec<(Value, u64)> = Vec::new();
+            for (key, docs) in keys {
+                let Some(label) = bucket.label(key) else { continue };
+                match buckets.iter_mut().find(|(existing, _)| *existing == label) {
+                    Some((_, total)) => *total += weight(docs),
+                    None => buckets.push((label, weight(docs))),
+                }
+            }
+            if matches!(bucket, IndexBucket::Interval(_)) {
+                buckets.sort_by(|a, b| a.0.as_f64().partial_cmp(&b.0.as_f64()).unwrap_or(std::cmp::Ordering::Equal));
+            }
+            span.returned = Some(buckets.len());
+            Ok(Value::Array(buckets.into_iter()
+                .map(|(label, count)| json!({ "bucket": label, "count": count }))
+                .collect()))
+        })
+    }
+
+    #[napi]
+    pub fn clear_index(&self, name: String) -> Result<()> {
+        self.check_open()?;
+         let mut indexes = self.indexes.write();
+         if let Some(idx) = indexes.get_mut(&name) {
+             idx.clear();
+         }
+         drop(indexes);
+         // A tracked index fills itself again from the data
+         if let Some(upkeep) = self.index_upkeep.lock().get_mut(&name) {
+             upkeep.invalidate();
+         }
+         Ok(())
+    }
+
+    /// Registered indexes, custom ones included, in name order: `[{name, field,
+    /// collection, custom, sparse, entries, keys, fileBytes}]`. Tracked and custom
+    /// indexes are brought up to date first. `collection` is null for an index
+    /// fed by `update_index`, and `fileBytes` until the index is first saved.
+    #[napi]
+    pub fn list_indexes(&self) -> Result<Value> {
+        self.check_recovered()?;
+        self.upkeep_indexes()?;
+        self.refresh_custom_indexes()?;
+        let customs: HashMap<String, String> = self.custom_indexes.lock().iter()
+            .map(|(name, custom)| (name.clone(), custom.collection.clone()))
+            .collect();
+        let indexes = self.indexes.read();
+        let mut listed: Vec<(&String, &BTreeIndex)> = indexes.iter().collect();
+        listed.sort_by(|a, b| a.0.cmp(b.0));
+        Ok(Value::Array(listed.into_iter().map(|(name, idx)| json!({
+            "name": name,
+            "field": idx.field(),
+            "collection": customs.get(name).map(String::as_str).or(idx.tracked()),
+            "custom": customs.contains_key(name),
+            "sparse": idx.sparse(),
+            "entries": idx.len(),
+            "keys": idx.key_count(),
+            "fileBytes": fs::metadata(idx.file()).ok().map(|m| m.len()),
+        })).collect()))
+    }
+
+    /// Unregister an index (custom, TTL or not) and delete its file. A query
+    /// reading the index finishes first; later ones find no index, and a TTL
+    /// index is no longer swept. Returns false if there was no such index.
+    #[napi]
+    pub fn drop_index(&self, name: String) -> Result<bool> {
+        self.check_recovered()?;
+        self.index_upkeep.lock().remove(&name);
+        self.custom_indexes.lock().remove(&name);
+        if self.ttl.remove(&name) {
+            if let Some(sweeper) = &*self.ttl_sweeper.lock() {
+                sweeper.remove(&maintenance::Task::SweepTtl(name.clone()));
             }
-            _ => true,
+        }
+        let Some(idx) = self.indexes.write().remove(&name) else {
+            return Ok(false);
+        };
+        match fs::remove_file(idx.file()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(DbError::Io(
+                format!("Failed to delete index file {}: {}", idx.file(), e)
+            ).into()),
+            _ => Ok(true),
         }
     }
 
-    /// Parallel aggregation operations
+    /// Fill a registered index from the documents of `collection_path` in one
+    /// pass, dropping whatever it held, then save it. Field values are extracted
+    /// in parallel for large collections. The index then tracks the collection,
+    /// as if registered with it, so later writes keep it in step.
     #[napi]
-    pub fn parallel_aggregate(&self, path: String, operation: String, field: Option<String>) -> Result<Value> {
+    pub fn build_index(&self, name: String, collection_path: String) -> Result<BuildIndexResult> {
+        self.check_recovered()?;
+        if collection_path.is_empty() || meta::is_internal_path(&collection_path) {
+            return Err(DbError::InvalidArgument(format!("Cannot index documents under '{}'", collection_path)).into());
+        }
+        if self.custom_indexes.lock().contains_key(&name) {
+            return Err(DbError::InvalidArgument(format!(
+                "Index '{}' is a custom index; use rebuildCustomIndex", name
+            )).into());
+        }
+        let field = match self.indexes.read().get(&name) {
+            Some(idx) => idx.field().to_string(),
+            None => return Err(DbError::NotFound(format!("Unknown index '{}'", name)).into()),
+        };
+        self.settle_staged(&collection_path)?;
+
+        // The read lock stays held until the upkeep is reset, so no write falls in between
         let data = self.data.read();
-        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
-        
-        let collection = if ptr == "/" || ptr.is_empty() {
-            Some(&*data)
-        } else {
-            data.pointer(&ptr)
+        let collection = compress::pointer(&data, &Self::to_pointer(&collection_path));
+        let docs: Vec<(String, &Value)> = match collection.as_deref() {
+            None => Vec::new(),
+            Some(Value::Object(map)) => map.iter().map(|(key, doc)| (key.clone(), doc)).collect(),
+            Some(Value::Array(items)) => items.iter().enumerate().map(|(i, doc)| (i.to_string(), doc)).collect(),
+            Some(_) => return Err(DbError::PathBlocked(format!("'{}' is not a collection", collection_path)).into()),
         };
-        
-        let items: Vec<&Value> = match collection {
-            Some(Value::Object(map)) => map.values().collect(),
-            Some(Value::Array(arr)) => arr.iter().collect(),
-            _ => return Ok(Value::Null),
+        let parts = split_field(&field);
+        let extract = |(key, doc): &(String, &Value)| {
+            resolve_field(doc, &parts).map(|value| (value.into_owned(), format!("{}.{}", collection_path, key)))
         };
-        
-        let count = items.len();
-        
-        match operation.as_str() {
-            "count" => Ok(json!(count)),
-            "sum" => {
-                let field_name = field.unwrap_or_default();
-                let sum: f64 = if THREAD_CONFIG.should_parallelize(count) {
-                    items.par_iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .sum()
-                } else {
-                    items.iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .sum()
-                };
-                Ok(json!(sum))
-            }
-            "avg" => {
-                let field_name = field.unwrap_or_default();
-                let values: Vec<f64> = if THREAD_CONFIG.should_parallelize(count) {
-                    items.par_iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .collect()
-                } else {
-                    items.iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .collect()
-                };
-                if values.is_empty() {
-                    Ok(json!(0.0))
-                } else {
-                    let sum: f64 = values.iter().sum();
-                    Ok(json!(sum / values.len() as f64))
-                }
+        let entries: Vec<(Value, String)> = if THREAD_CONFIG.should_parallelize(docs.len()) {
+            docs.par_iter().filter_map(extract).collect()
+        } else {
+            docs.iter().filter_map(extract).collect()
+        };
+
+        let total = docs.len() as u32;
+        drop(docs);
+        drop(collection);
+        let mut upkeep = self.index_upkeep.lock();
+        let mut indexes = self.indexes.write();
+        let idx = indexes.get_mut(&name).ok_or_else(|| DbError::NotFound(format!("Unknown index '{}'", name)))?;
+        let cleared = idx.len() as u32;
+        idx.clear();
+        let indexed = entries.len() as u32;
+        for (value, path) in entries {
+            idx.insert(&value, path);
+        }
+        idx.track(collection_path.clone());
+        upkeep.insert(name, Upkeep::new(collection_path, false));
+        drop(upkeep);
+        drop(data);
+        idx.save().map_err(|e| DbError::Index(format!("Failed to save index: {}", e)))?;
+
+        Ok(BuildIndexResult { indexed, skipped: total - indexed, cleared })
+    }
+
+    /// Compare a field index with what `collection_path` holds now: `{name,
+    /// collection, documents, entries, missing, stale, wrongKeys, samples, ok,
+    /// repaired}`. `missing` counts documents with the field but no entry,
+    /// `stale` entries whose document is gone (or outside the collection) or no
+    /// longer has the field, `wrongKeys` entries filed under another key than
+    /// the field's value; `samples` lists the first paths of each. Writes not
+    /// yet applied to a tracked index are applied first, so any difference is
+    /// drift. With `repair` the differences are fixed in place and the index
+    /// saved.
+    #[napi]
+    pub fn verify_index(&self, name: String, collection_path: String, repair: Option<bool>) -> Result<Value> {
+        self.check_recovered()?;
+        if collection_path.is_empty() || meta::is_internal_path(&collection_path) {
+            return Err(DbError::InvalidArgument(format!("Cannot index documents under '{}'", collection_path)).into());
+        }
+        if self.custom_indexes.lock().contains_key(&name) {
+            return Err(DbError::InvalidArgument(format!(
+                "Index '{}' is a custom index; use verifyCustomIndex", name
+            )).into());
+        }
+        match self.indexes.read().get(&name).map(|idx| idx.tracked().map(String::from)) {
+            None => return Err(DbError::NotFound(format!("Unknown index '{}'", name)).into()),
+            Some(Some(tracked)) if tracked != collection_path => {
+                return Err(DbError::InvalidArgument(format!(
+                    "Index '{}' covers '{}', not '{}'", name, tracked, collection_path
+                )).into());
             }
-            "min" => {
-                let field_name = field.unwrap_or_default();
-                let min: Option<f64> = if THREAD_CONFIG.should_parallelize(count) {
-                    items.par_iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .reduce(|| f64::INFINITY, |a, b| a.min(b))
-                        .into()
-                } else {
-                    items.iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .reduce(f64::min)
-                };
-                match min {
-                    Some(v) if v != f64::INFINITY => Ok(json!(v)),
-                    _ => Ok(Value::Null),
+            Some(_) => {}
+        }
+        self.settle_staged(&collection_path)?;
+
+        // The read lock stays held through any repair, so no write falls in between
+        let data = self.data.read();
+        self.upkeep_index_in(&data, &name);
+        let collection = compress::pointer(&data, &Self::to_pointer(&collection_path));
+        if matches!(collection.as_deref(), Some(v) if !v.is_object() && !v.is_array()) {
+            return Err(DbError::PathBlocked(format!("'{}' is not a collection", collection_path)).into());
+        }
+        let docs = Self::documents_of(collection.as_deref());
+
+        let indexes = self.indexes.read();
+        let idx = indexes.get(&name).ok_or_else(|| DbError::NotFound(format!("Unknown index '{}'", name)))?;
+        let parts = split_field(idx.field());
+        // doc path -> (key, field value) for every document the index should hold
+        let mut expected: HashMap<String, (String, Value)> = docs.iter()
+            .filter_map(|(key, doc)| {
+                let value = resolve_field(doc, &parts)?;
+                let index_key = idx.key_of(&value)?;
+                Some((Self::join_path(&collection_path, key), (index_key, value.into_owned())))
+            })
+            .collect();
+        let mut stale = Vec::new();
+        let mut wrong_keys = Vec::new();
+        for (doc_path, keys) in idx.entries() {
+            match expected.remove(doc_path) {
+                None => stale.push(doc_path.to_string()),
+                Some((key, value)) if keys != [key.as_str()] => {
+                    wrong_keys.push((doc_path.to_string(), keys.join(","), key, value));
                 }
+                Some(_) => {}
             }
-            "max" => {
-                let field_name = field.unwrap_or_default();
-                let max: Option<f64> = if THREAD_CONFIG.should_parallelize(count) {
-                    items.par_iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .reduce(|| f64::NEG_INFINITY, |a, b| a.max(b))
-                        .into()
-                } else {
-                    items.iter()
-                        .filter_map(|item| self.get_numeric_field(item, &field_name))
-                        .reduce(f64::max)
-                };
-                match max {
-                    Some(v) if v != f64::NEG_INFINITY => Ok(json!(v)),
-                    _ => Ok(Value::Null),
-                }
+        }
+        let mut missing: Vec<(String, (String, Value))> = expected.into_iter().collect();
+        missing.sort_by(|a, b| a.0.cmp(&b.0));
+        stale.sort();
+        wrong_keys.sort_by(|a, b| a.0.cmp(&b.0));
+        let entries = idx.len();
+        drop(indexes);
+
+        let ok = missing.is_empty() && stale.is_empty() && wrong_keys.is_empty();
+        let report = json!({
+            "name": name,
+            "collection": collection_path,
+            "documents": docs.len(),
+            "entries": entries,
+            "missing": missing.len(),
+            "stale": stale.len(),
+            "wrongKeys": wrong_keys.len(),
+            "samples": {
+                "missing": missing.iter().take(VERIFY_SAMPLES).map(|(path, _)| path).collect::<Vec<_>>(),
+                "stale": stale.iter().take(VERIFY_SAMPLES).collect::<Vec<_>>(),
+                "wrongKeys": wrong_keys.iter().take(VERIFY_SAMPLES)
+                    .map(|(path, indexed, expected, _)| json!({ "path": path, "indexed": indexed, "expected": expected }))
+                    .collect::<Vec<_>>(),
+            },
+            "ok": ok,
+            "repaired": repair.unwrap_or(false) && !ok,
+        });
+        if !repair.unwrap_or(false) {
+            return Ok(report);
+        }
+        let mut indexes = self.indexes.write();
+        let idx = indexes.get_mut(&name).ok_or_else(|| DbError::NotFound(format!("Unknown index '{}'", name)))?;
+        for doc_path in &stale {
+            idx.remove(&Value::Null, doc_path);
+        }
+        for (doc_path, _, _, value) in wrong_keys {
+            idx.insert(&value, doc_path);
+        }
+        for (doc_path, (_, value)) in missing {
+            idx.insert(&value, doc_path);
+        }
+        drop(data);
+        idx.save().map_err(|e| DbError::Index(format!("Failed to save index: {}", e)))?;
+        Ok(report)
+    }
+
+    /// Apply the writes since the last refresh to a tracked index
+    fn upkeep_index(&self, name: &str) -> Result<()> {
+        let collection = match self.index_upkeep.lock().get(name) {
+            Some(upkeep) if !upkeep.is_clean() => upkeep.collection.clone(),
+            _ => return Ok(()),
+        };
+        self.settle_staged(&collection)?;
+        self.upkeep_index_in(&self.data.read(), name);
+        Ok(())
+    }
+
+    /// `upkeep_index` with the data already locked
+    fn upkeep_index_in(&self, data: &Value, name: &str) {
+        let mut upkeeps = self.index_upkeep.lock();
+        let Some(upkeep) = upkeeps.get_mut(name).filter(|u| !u.is_clean()) else { return };
+        if let Some(idx) = self.indexes.write().get_mut(name) {
+            upkeep.refresh(idx, data.pointer(&Self::to_pointer(&upkeep.collection)));
+        }
+    }
+
+    fn upkeep_indexes(&self) -> Result<()> {
+        let names: Vec<String> = self.index_upkeep.lock().keys().cloned().collect();
+        for name in names {
+            self.upkeep_index(&name)?;
+        }
+        Ok(())
+    }
+
+    // TTL indexes
+    //
+    // A tracked index on the expiry field, swept by its own thread; ttl.rs
+    // describes what sweeps and reads do with expired documents.
+
+    /// Expire the documents of the object collection `collection_path` by the
+    /// epoch-millisecond timestamp in `field`. A sparse index on the field,
+    /// named `ttl.<collection>.<field>`, is kept up to date like any tracked
+    /// index and swept every `sweep_interval_ms` (default 1000) on a
+    /// background thread shared by all TTL indexes: documents whose timestamp
+    /// has passed are deleted with reason "ttl". Until they are, `get` and
+    /// `parallel_query` leave them out. Registering again changes the
+    /// interval; `drop_index` stops the sweeps and `close` the thread.
+    /// Returns the index name.
+    #[napi]
+    pub fn register_ttl_index(&self, collection_path: String, field: String, sweep_interval_ms: Option<u32>) -> Result<String> {
+        self.check_recovered()?;
+        let interval_ms = sweep_interval_ms.map_or(ttl::DEFAULT_SWEEP_INTERVAL_MS, u64::from);
+        if interval_ms < maintenance::MIN_INTERVAL_MS {
+            return Err(DbError::InvalidArgument(format!(
+                "TTL sweeps need an interval of at least {}ms", maintenance::MIN_INTERVAL_MS
+            )).into());
+        }
+        if field.is_empty() {
+            return Err(DbError::InvalidArgument("A TTL index needs a field".to_string()).into());
+        }
+        if self.is_array_at(&collection_path) {
+            return Err(DbError::InvalidArgument(format!(
+                "TTL indexes need an object collection, and '{}' is an array", collection_path
+            )).into());
+        }
+        let name = ttl::index_name(&collection_path, &field);
+        self.register_index(name.clone(), field.clone(), Some(collection_path.clone()), Some(IndexOptions { sparse: Some(true) }))?;
+        self.ttl.register(&name, &collection_path, &field, interval_ms);
+
+        let job = maintenance::Job::new(maintenance::Task::SweepTtl(name.clone()), interval_ms, json!({ "index": name }));
+        let mut sweeper = self.ttl_sweeper.lock();
+        match &*sweeper {
+            Some(running) => running.insert(job),
+            None => {
+                let db = self.handle();
+                *sweeper = Some(Scheduler::start("ttl-sweeper", vec![job], move |task| db.run_maintenance(task))?);
             }
-            _ => Ok(Value::Null),
         }
+        Ok(name)
     }
 
-    /// Perform a parallel left outer join between two collections (lookup)
+    /// Registered TTL indexes and what their sweeps did: `[{index, collection,
+    /// field, sweepIntervalMs, sweeps, expired, skipped, lastSweepAt, lastError}]`.
+    /// `skipped` counts expired documents left for a later sweep because they
+    /// were frozen or leased.
     #[napi]
-    pub fn parallel_lookup(
-        &self,
-        left_path: String,
-        right_path: String,
-        left_field: String,
-        right_field: String,
-        as_field: String,
-    ) -> Result<Value> {
-        let data = self.data.read();
+    pub fn ttl_indexes(&self) -> Result<Value> {
+        self.check_open()?;
+        Ok(self.ttl.list())
+    }
+
+    /// One sweep of the TTL index `name`, on the sweeper thread
+    fn sweep_ttl(&self, name: &str) -> Result<Value> {
+        if self.transactions.lock().is_active() {
+            return Ok(json!({ "skipped": "a transaction is active" }));
+        }
+        if self.bulk_loading.load(Ordering::Acquire) {
+            return Ok(json!({ "skipped": "a bulk load is running" }));
+        }
+        let swept = self.sweep_expired(name);
+        self.ttl.swept(name, &swept.as_ref().map(|counts| *counts).map_err(|e| e.reason.clone()));
+        let (expired, skipped) = swept?;
+        Ok(json!({ "expired": expired, "skipped": skipped }))
+    }
 
-        // Helper to get collection items
-        let get_items = |path: &str| -> Option<Vec<&Value>> {
-            let ptr = if path.starts_with('/') { path.to_string() } else { format!("/{}", path.replace(".", "/")) };
-            let collection = if ptr == "/" || ptr.is_empty() {
-                Some(&*data)
+    /// Delete up to `MAX_SWEEP_DOCS` expired documents of a TTL index's
+    /// collection under one write lock. Returns how many were deleted and
+    /// how many were skipped as frozen or leased.
+    fn sweep_expired(&self, name: &str) -> Result<(usize, usize)> {
+        self.check_recovered()?;
+        let Some((collection, parts)) = self.ttl.get(name) else { return Ok((0, 0)) };
+        self.upkeep_index(name)?;
+        let now = now_ms();
+        let due = match self.indexes.read().get(name) {
+            Some(idx) => idx.range(std::ops::Bound::Unbounded, std::ops::Bound::Excluded(&json!(now)), ttl::MAX_SWEEP_DOCS),
+            None => return Ok((0, 0)),
+        };
+        let mut skipped = 0;
+        let mut keys = Vec::with_capacity(due.len());
+        for path in &due {
+            let Some(key) = path.strip_prefix(collection.as_str()).and_then(|rest| rest.strip_prefix('.')) else { continue };
+            if self.freezes.blocking(path).is_some() {
+                self.freezes.skip_purge();
+                skipped += 1;
+            } else if self.check_lease(path, None).is_err() {
+                skipped += 1;
             } else {
-                data.pointer(&ptr)
-            };
-            
-            match collection {
-                Some(Value::Object(map)) => Some(map.values().collect()),
-                Some(Value::Array(arr)) => Some(arr.iter().collect()),
-                _ => None,
+                keys.push(key.to_string());
             }
-        };
+        }
+        if keys.is_empty() {
+            return Ok((0, skipped));
+        }
+
+        let fields = [parts];
+        let mut data = self.data.write();
+        if !matches!(data.pointer(&Self::to_pointer(&collection)), Some(Value::Object(_))) {
+            return Ok((0, skipped));
+        }
+        // A write since the index was read may have moved an expiry
+        keys.retain(|key| {
+            compress::pointer(&data, &Self::to_pointer(&Self::join_path(&collection, key)))
+                .is_some_and(|doc| ttl::expired(&doc, &fields, now))
+        });
+        let plan = self.plan_cascade(&data, &collection, &keys)?;
+        for key in &keys {
+            let path = Self::join_path(&collection, key);
+            self.record_undo_in(&data, &path)?;
+            let value = self.removed_value(&data, &path);
+            let lsn = self.append_wal_record(WalOpType::Delete, &path, None, Some("ttl"))?;
+            Self::delete_value_at_path(&mut data, &path)?;
+            self.notify_delete(&path, Some("ttl"), lsn, value);
+        }
+        if !plan.is_empty() {
+            self.apply_cascade(&mut data, &plan)?;
+        }
+        Ok((keys.len(), skipped))
+    }
 
-        let left_items = get_items(&left_path).ok_or_else(|| Error::from_reason(format!("Left collection not found: {}", left_path)))?;
-        let right_items = get_items(&right_path).ok_or_else(|| Error::from_reason(format!("Right collection not found: {}", right_path)))?;
+    // Custom indexes
+    //
+    // Keys come from a JS extractor; custom_index.rs has its contract. Entries
+    // are kept in a BTreeIndex like any other index, with an empty field.
 
-        // Build hash table on right collection
-        use std::collections::HashMap;
-        let mut hash_table: HashMap<String, Vec<&Value>> = HashMap::new();
-        
-        for item in &right_items {
-             if let Some(val) = self.get_value_at_field(item, &right_field) {
-                 let key = match val {
-                     Value::String(s) => s.clone(),
-                     _ => val.to_string(),
-                 };
-                 hash_table.entry(key).or_default().push(item);
-             }
+    /// Index the documents of `collection_path` under the keys a synchronous JS
+    /// extractor `(doc, key) => string[]` returns. The index is queried like any
+    /// other (findIndexPaths, findIndexRange, indexKeys, indexAggregate) and saved
+    /// with them; writes mark documents dirty and the extractor catches up when the
+    /// index is read. Built now unless loaded from its file. Returns its status.
+    #[napi]
+    pub fn register_custom_index(
+        &self,
+        env: Env,
+        name: String,
+        collection_path: String,
+        extract: JsFunction,
+        options: Option<CustomIndexOptions>,
+    ) -> Result<Value> {
+        self.check_recovered()?;
+        let options = options.unwrap_or_default();
+        if collection_path.is_empty() || meta::is_internal_path(&collection_path) {
+            return Err(DbError::InvalidArgument(format!("Cannot index documents under '{}'", collection_path)).into());
         }
+        let on_error = custom_index::OnError::parse(options.on_error.as_deref())?;
+        let extractor = custom_index::Extractor::new(&env, extract)?;
 
-        // Probe with left collection
-        let results: Vec<Value> = if THREAD_CONFIG.should_parallelize(left_items.len()) {
-            left_items.par_iter().map(|left_item| {
-                let mut joined = (*left_item).clone();
-                if let Value::Object(ref mut map) = joined {
-                    let mut matches_curr = Vec::new();
-                    if let Some(val) = self.get_value_at_field(left_item, &left_field) {
-                        let key = match val {
-                            Value::String(s) => s.clone(),
-                            _ => val.to_string(),
-                        };
-                        
-                        if let Some(matches) = hash_table.get(&key) {
-                            for m in matches {
-                                matches_curr.push((*m).clone());
-                            }
-                        }
-                    }
-                    map.insert(as_field.clone(), Value::Array(matches_curr));
+        let replacing = self.custom_indexes.lock().contains_key(&name);
+        let mut rescan = replacing || options.rebuild.unwrap_or(false);
+        {
+            let mut indexes = self.indexes.write();
+            if !replacing {
+                if indexes.contains_key(&name) {
+                    return Err(DbError::InvalidArgument(format!("Index '{}' already exists on field '{}'", name, indexes[&name].field())).into());
                 }
-                joined
-            }).collect()
-        } else {
-             left_items.iter().map(|left_item| {
-                let mut joined = (*left_item).clone();
-                if let Value::Object(ref mut map) = joined {
-                    let mut matches_curr = Vec::new();
-                    if let Some(val) = self.get_value_at_field(left_item, &left_field) {
-                        let key = match val {
-                            Value::String(s) => s.clone(),
-                            _ => val.to_string(),
-                        };
-                        
-                        if let Some(matches) = hash_table.get(&key) {
-                            for m in matches {
-                                matches_curr.push((*m).clone());
-                            }
-                        }
-                    }
-                    map.insert(as_field.clone(), Value::Array(matches_curr));
+                let mut idx = BTreeIndex::load_or_create(name.clone(), String::new(), &self.path)
+                    .map_err(|e| DbError::Index(format!("Failed to load index {}: {}", name, e)))?;
+                // A file left by a field index of the same name is no use here
+                if !idx.loaded_from_disk() || !idx.field().is_empty() {
+                    idx = BTreeIndex::new(name.clone(), String::new(), &self.path);
+                    rescan = true;
                 }
-                joined
-            }).collect()
+                indexes.insert(name.clone(), idx);
+            }
+        }
+        let mut custom = custom_index::CustomIndex::new(collection_path, on_error, extractor);
+        custom.rescan = rescan;
+        self.custom_indexes.lock().insert(name.clone(), custom);
+        self.refresh_custom_index(&name)?;
+        self.custom_index_status(name)
+    }
+
+    /// `{collection, onError, entries, keys, pending, errors, lastError, rejected}`;
+    /// `pending` counts documents written since the extractor last ran
+    #[napi]
+    pub fn custom_index_status(&self, name: String) -> Result<Value> {
+        self.check_open()?;
+        let customs = self.custom_indexes.lock();
+        let Some(custom) = customs.get(&name) else {
+            return Err(DbError::NotFound(format!("Custom index '{}' not found", name)).into());
         };
+        let indexes = self.indexes.read();
+        let (entries, keys) = indexes.get(&name).map_or((0, 0), |idx| (idx.len(), idx.key_count()));
+        Ok(custom.status(entries, keys))
+    }
 
-        Ok(Value::Array(results))
+    /// Run the extractor over the whole collection again, replacing every entry
+    #[napi]
+    pub fn rebuild_custom_index(&self, name: String) -> Result<Value> {
+        self.check_recovered()?;
+        self.custom_extractor(&name)?;
+        if let Some(custom) = self.custom_indexes.lock().get_mut(&name) {
+            custom.rescan = true;
+        }
+        self.refresh_custom_index(&name)?;
+        self.custom_index_status(name)
     }
 
-    /// Helper to get arbitrary field value (supports dot notation)
-    fn get_value_at_field<'a>(&self, item: &'a Value, path: &str) -> Option<&'a Value> {
-        let parts: Vec<&str> = path.split('.').collect();
-        let mut current = item;
-        
-        for part in parts {
-            match current {
-                Value::Object(map) => {
-                    if let Some(v) = map.get(part) {
-                        current = v;
-                    } else {
-                        return None;
+    /// Compare the index with what the extractor yields for the collection now:
+    /// `{entries, keys, missingDocs, mismatched, extractorErrors, ok}`. Pending
+    /// writes are applied first, so a mismatch means the index drifted.
+    #[napi]
+    pub fn verify_custom_index(&self, name: String) -> Result<Value> {
+        self.check_recovered()?;
+        let (collection, extractor) = self.custom_extractor(&name)?;
+        self.refresh_custom_index(&name)?;
+        self.settle_staged(&collection)?;
+        let docs: Vec<(String, Value)> = {
+            let data = self.data.read();
+            Self::documents_of(data.pointer(&Self::to_pointer(&collection)))
+                .into_iter()
+                .map(|(key, doc)| (key, doc.clone()))
+                .collect()
+        };
+
+        let present: std::collections::HashSet<String> = docs.iter().map(|(key, _)| Self::join_path(&collection, key)).collect();
+        let mut expected: HashMap<String, Vec<String>> = HashMap::new();
+        let mut failed = std::collections::HashSet::new();
+        for (key, mut doc) in docs {
+            compress::expand(&mut doc);
+            let path = Self::join_path(&collection, &key);
+            match extractor.extract(&doc, &key) {
+                Some(Ok(mut keys)) => {
+                    keys.sort_unstable();
+                    keys.dedup();
+                    if !keys.is_empty() {
+                        expected.insert(path, keys);
                     }
                 }
-                Value::Array(arr) => {
-                    if let Ok(idx) = part.parse::<usize>() {
-                         if let Some(v) = arr.get(idx) {
-                            current = v;
-                         } else {
-                             return None;
-                         }
-                    } else {
-                        return None;
-                    }
+                _ => {
+                    failed.insert(path);
                 }
-                _ => return None,
             }
         }
-        Some(current)
+
+        let indexes = self.indexes.read();
+        let Some(idx) = indexes.get(&name) else {
+            return Err(DbError::NotFound(format!("Index '{}' not found", name)).into());
+        };
+        let (mut missing_docs, mut mismatched) = (0u64, 0u64);
+        for (doc_path, keys) in idx.entries() {
+            if !present.contains(doc_path) {
+                missing_docs += 1;
+            } else if !failed.contains(doc_path) && expected.remove(doc_path).is_none_or(|want| want != keys) {
+                mismatched += 1;
+            }
+        }
+        // Documents that should be indexed but are not
+        mismatched += expected.keys().filter(|path| !failed.contains(*path)).count() as u64;
+        Ok(json!({
+            "entries": idx.len(),
+            "keys": idx.key_count(),
+            "missingDocs": missing_docs,
+            "mismatched": mismatched,
+            "extractorErrors": failed.len(),
+            "ok": missing_docs == 0 && mismatched == 0,
+        }))
     }
-    
-    /// Helper to get numeric field value
-    fn get_numeric_field(&self, item: &Value, field: &str) -> Option<f64> {
-        if field.is_empty() {
-            return item.as_f64();
+
+    /// A custom index's collection and extractor, if the extractor can run here
+    fn custom_extractor(&self, name: &str) -> Result<(String, Arc<custom_index::Extractor>)> {
+        let customs = self.custom_indexes.lock();
+        let Some(custom) = customs.get(name) else {
+            return Err(DbError::NotFound(format!("Custom index '{}' not found", name)).into());
+        };
+        if !custom.extractor.callable() {
+            return Err(DbError::InvalidArgument(format!(
+                "Custom index '{}' can only be maintained from the thread that registered it", name
+            )).into());
         }
-        
-        let parts: Vec<&str> = field.split('.').collect();
-        let mut current = item;
-        
-        for part in parts {
-            match current {
-                Value::Object(map) => {
-                    current = map.get(part)?;
+        Ok((custom.collection.clone(), Arc::clone(&custom.extractor)))
+    }
+
+    /// The documents of an object or array collection, keyed as in their paths
+    fn documents_of(collection: Option<&Value>) -> Vec<(String, &Value)> {
+        match collection {
+            Some(Value::Object(map)) => map.iter().map(|(key, doc)| (key.clone(), doc)).collect(),
+            Some(Value::Array(items)) => items.iter().enumerate().map(|(i, doc)| (i.to_string(), doc)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Run the extractor over the documents written since the last refresh of a
+    /// custom index (all of them after a rescan). A no-op for field indexes and
+    /// off the registering thread. No lock is held while the extractor runs.
+    fn refresh_custom_index(&self, name: &str) -> Result<()> {
+        let collection = match self.custom_indexes.lock().get(name) {
+            Some(custom) if custom.extractor.callable() && (custom.rescan || !custom.dirty.is_empty()) => custom.collection.clone(),
+            _ => return Ok(()),
+        };
+        self.settle_staged(&collection)?;
+        let (extractor, dirty) = {
+            let mut customs = self.custom_indexes.lock();
+            let Some(custom) = customs.get_mut(name) else { return Ok(()) };
+            let dirty = std::mem::take(&mut custom.dirty);
+            (Arc::clone(&custom.extractor), (!std::mem::take(&mut custom.rescan)).then_some(dirty))
+        };
+
+        let rescan = dirty.is_none();
+        let docs: Vec<(String, Option<Value>)> = {
+            let data = self.data.read();
+            let ptr = Self::to_pointer(&collection);
+            match dirty {
+                Some(keys) => keys.into_iter()
+                    .map(|key| {
+                        let doc = data.pointer(&Self::child_pointer(&ptr, &key)).cloned();
+                        (key, doc)
+                    })
+                    .collect(),
+                None => Self::documents_of(data.pointer(&ptr))
+                    .into_iter()
+                    .map(|(key, doc)| (key, Some(doc.clone())))
+                    .collect(),
+            }
+        };
+
+        let mut errors = Vec::new();
+        let entries: Vec<(String, Vec<String>)> = docs.into_iter()
+            .map(|(key, doc)| {
+                let keys = match doc {
+                    None => Vec::new(),
+                    Some(mut doc) => {
+                        compress::expand(&mut doc);
+                        match extractor.extract(&doc, &key) {
+                            Some(Ok(keys)) => keys,
+                            Some(Err(e)) => {
+                                errors.push(format!("'{}': {}", key, e));
+                                Vec::new()
+                            }
+                            None => Vec::new(),
+                        }
+                    }
+                };
+                (Self::join_path(&collection, &key), keys)
+            })
+            .collect();
+
+        if let Some(idx) = self.indexes.write().get_mut(name) {
+            if rescan {
+                idx.clear();
+            }
+            for (doc_path, keys) in entries {
+                idx.set_keys(&doc_path, keys);
+            }
+        }
+        if let (Some(custom), Some(last)) = (self.custom_indexes.lock().get_mut(name), errors.last()) {
+            custom.errors += errors.len() as u64;
+            custom.last_error = Some(last.clone());
+        }
+        Ok(())
+    }
+
+    fn refresh_custom_indexes(&self) -> Result<()> {
+        let names: Vec<String> = self.custom_indexes.lock().keys().cloned().collect();
+        for name in names {
+            self.refresh_custom_index(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a rejecting custom index covers writes at `path`
+    fn custom_index_vets(&self, path: &str) -> bool {
+        let customs = self.custom_indexes.lock();
+        !customs.is_empty() && customs.values()
+            .any(|c| c.on_error == custom_index::OnError::Reject && paths_overlap(&c.collection, path))
+    }
+
+    /// Run the extractors of rejecting custom indexes on the documents a pending
+    /// write of `value` at `path` produces, with the data write lock held. A throw
+    /// rejects the write.
+    fn check_custom_index_write(&self, data: &Value, path: &str, value: &Value) -> Result<()> {
+        let vetting: Vec<(String, String, Arc<custom_index::Extractor>)> = self.custom_indexes.lock().iter()
+            .filter(|(_, c)| c.on_error == custom_index::OnError::Reject && c.extractor.callable() && paths_overlap(&c.collection, path))
+            .map(|(name, c)| (name.clone(), c.collection.clone(), Arc::clone(&c.extractor)))
+            .collect();
+        for (name, collection, extractor) in vetting {
+            let docs: Vec<(String, Value)> = match Self::relative_below(&collection, path) {
+                Some(relative) => {
+                    let incoming = if relative.is_empty() { Some(value) } else { value.pointer(&Self::to_pointer(&relative)) };
+                    Self::documents_of(incoming).into_iter().map(|(key, doc)| (key, doc.clone())).collect()
                 }
-                Value::Array(arr) => {
-                    let idx: usize = part.parse().ok()?;
-                    current = arr.get(idx)?;
+                None => {
+                    let rest = &path[collection.len() + 1..];
+                    let (key, inner) = rest.split_once('.').unwrap_or((rest, ""));
+                    let doc = if inner.is_empty() {
+                        value.clone()
+                    } else {
+                        let current = data.pointer(&Self::to_pointer(&Self::join_path(&collection, key)));
+                        let mut doc = current.cloned().unwrap_or_else(|| json!({}));
+                        if Self::set_value_at_path(&mut doc, inner, value.clone()).is_err() {
+                            continue;
+                        }
+                        doc
+                    };
+                    vec![(key.to_string(), doc)]
+                }
+            };
+            for (key, mut doc) in docs {
+                compress::expand(&mut doc);
+                if let Some(Err(e)) = extractor.extract(&doc, &key) {
+                    if let Some(custom) = self.custom_indexes.lock().get_mut(&name) {
+                        custom.rejected += 1;
+                    }
+                    return Err(DbError::Index(format!(
+                        "Custom index '{}' rejected the write to '{}': {}", name, path, e
+                    )).into());
                 }
-                _ => return None,
             }
         }
-        
-        current.as_f64()
+        Ok(())
     }
 
-    // --- Exposed API ---
+    // Schema API
 
     #[napi]
-    pub fn get(&self, path: String) -> Result<Value> {
-        let data = self.data.read();
-        if path.is_empty() {
-            return Ok(data.clone());
-        }
-        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
-        match data.pointer(&ptr) {
-            Some(v) => Ok(v.clone()),
-            None => Ok(Value::Null), 
+    pub fn register_schema(&self, path: String, schema_json: String, options: Option<SchemaOptions>) -> Result<()> {
+        self.check_open()?;
+        let schema = schema::parse(&schema_json, &self.schemas.read()).map_err(DbError::InvalidArgument)?;
+        let mut schemas = self.schemas.write();
+        let mut defaults = self.schema_defaults.write();
+        if options.and_then(|o| o.apply_defaults).unwrap_or(false) {
+            defaults.insert(path.clone());
+        } else {
+            defaults.remove(&path);
         }
+        schemas.insert(path, schema);
+        Ok(())
     }
 
+    /// Paths with a registered schema, sorted
     #[napi]
-    pub fn set(&self, path: String, value: Value) -> Result<()> {
-        // v5.1 Transaction support
-        self.record_undo(&path);
-
-        // Append to WAL first (durability)
-        self.append_wal(WalOpType::Set, &path, Some(value.clone()))?;
-        
-        // Update memory
-        let mut data = self.data.write();
-        Self::set_value_at_path(&mut data, &path, value)?;
-        Ok(())
+    pub fn list_schemas(&self) -> Result<Vec<String>> {
+        self.check_open()?;
+        let mut paths: Vec<String> = self.schemas.read().keys().cloned().collect();
+        paths.sort();
+        Ok(paths)
     }
-    
+
+    /// The schema registered at exactly `path`, with its `$ref`s resolved as
+    /// when it was registered; null if there is none
     #[napi]
-    pub fn has(&self, path: String) -> Result<bool> {
-        let data = self.data.read();
-        let ptr = if path.starts_with('/') { path } else { format!("/{}", path.replace(".", "/")) };
-        Ok(data.pointer(&ptr).is_some())
+    pub fn get_schema(&self, path: String) -> Result<Value> {
+        self.check_open()?;
+        Ok(self.schemas.read().get(&path).map_or(Value::Null, CompiledSchema::to_json))
     }
-    
+
+    /// The schema a write at `path` is checked against: `{path, exact,
+    /// applyDefaults, schema, resolved}`, where `path` is where the deepest
+    /// schema at or above it is registered and `resolved` the part of that
+    /// schema describing the write (null if it describes nothing there, or
+    /// does not allow a key on the way). Null without such a schema.
     #[napi]
-    pub fn delete(&self, path: String) -> Result<()> {
-        // v5.1 Transaction support
-        self.record_undo(&path);
+    pub fn resolve_schema_for(&self, path: String) -> Result<Value> {
+        self.check_open()?;
+        let schemas = self.schemas.read();
+        let Some((prefix, schema)) = governing_schema(&schemas, &path) else { return Ok(Value::Null) };
+        let parts: Vec<&str> = path.split('.').collect();
+        let resolved = schema::resolve(schema, &parts[prefix.split('.').count()..]).ok().flatten();
+        Ok(json!({
+            "path": prefix,
+            "exact": *prefix == path,
+            "applyDefaults": self.schema_defaults.read().contains(prefix),
+            "schema": schema.to_json(),
+            "resolved": resolved.map(schema::to_json),
+        }))
+    }
 
-        self.append_wal(WalOpType::Delete, &path, None)?;
-        
-        let mut data = self.data.write();
-        Self::delete_value_at_path(&mut data, &path)?;
-        Ok(())
+    /// A value the schema governing `path` accepts there, see sample.rs.
+    /// Throws ERR_NOT_FOUND if no registered schema describes `path`.
+    #[napi]
+    pub fn generate_from_schema(&self, path: String) -> Result<Value> {
+        self.check_open()?;
+        let schemas = self.schemas.read();
+        let not_found = || DbError::NotFound(format!("No registered schema describes '{}'", path));
+        let Some((prefix, schema)) = governing_schema(&schemas, &path) else { return Err(not_found().into()) };
+        let parts: Vec<&str> = path.split('.').collect();
+        match schema::resolve(schema, &parts[prefix.split('.').count()..]) {
+            Ok(Some(schema)) => Ok(sample::generate(schema)),
+            Ok(None) => Err(not_found().into()),
+            Err(e) => Err(DbError::ValidationFailed { path: path.clone(), details: e.to_string() }.into()),
+        }
     }
 
+    /// Remove the schema registered at exactly `path`. Schemas that refer to
+    /// it keep the copy they took when registered. False if there was none.
     #[napi]
-    pub fn push(&self, path: String, value: Value) -> Result<()> {
-        // v5.1 Transaction support
-        self.record_undo(&path);
+    pub fn unregister_schema(&self, path: String) -> Result<bool> {
+        self.check_open()?;
+        let mut schemas = self.schemas.write();
+        self.schema_defaults.write().remove(&path);
+        Ok(schemas.remove(&path).is_some())
+    }
 
-        let mut data = self.data.write();
-        Self::push_value_at_path(&mut data, &path, value)?;
-        Ok(())
+    /// `value` with the defaults of its schema filled in, if the schema
+    /// governing `path` was registered with `apply_defaults`
+    fn with_schema_defaults(&self, path: &str, value: Value) -> Value {
+        if self.schemas_suspended() || self.schema_defaults.read().is_empty() {
+            return value;
+        }
+        let schemas = self.schemas.read();
+        let Some((prefix, schema)) = governing_schema(&schemas, path) else { return value };
+        if !self.schema_defaults.read().contains(prefix) {
+            return value;
+        }
+        let parts: Vec<&str> = path.split('.').collect();
+        match schema::resolve(schema, &parts[prefix.split('.').count()..]) {
+            Ok(Some(schema)) => schema::apply_defaults(value, schema),
+            _ => value,
+        }
     }
 
-    // Indexing API
-    
-    #[napi]
-    pub fn register_index(&self, name: String, field: String) -> Result<()> {
-        let mut indexes = self.indexes.write();
-        if !indexes.contains_key(&name) {
-             let idx = BTreeIndex::load_or_create(name.clone(), field.clone(), &self.path)
-                 .map_err(|e| Error::from_reason(format!("Failed to load index {}: {:?}", name, e)))?;
-             indexes.insert(name, idx);
+    /// Best-matching schema for a path: the nearest registered schema at the path or
+    /// an ancestor, resolved down to the sub-schema describing the path itself
+    /// (None below a key the schema does not allow; `check_schema` reports that)
+    fn schema_for_path(&self, path: &str) -> Option<Schema> {
+        if self.schemas_suspended() {
+            return None;
         }
-        Ok(())
+        let schemas = self.schemas.read();
+        let (prefix, schema) = governing_schema(&schemas, path)?;
+        let parts: Vec<&str> = path.split('.').collect();
+        schema::resolve(schema, &parts[prefix.split('.').count()..]).ok().flatten().cloned()
     }
-    
+
+    /// Check a path against this database's path rules without writing:
+    /// `{valid, depth}`, plus `{segment, reason}` naming the first rule broken
     #[napi]
-    pub fn update_index(&self, name: String, key: Value, path: String, is_delete: bool) -> Result<()> {
-        let mut indexes = self.indexes.write();
-        if let Some(idx) = indexes.get_mut(&name) {
-            if is_delete {
-                idx.remove(&key, &path);
-            } else {
-                idx.insert(&key, path);
-            }
-        }
-        Ok(())
+    pub fn validate_path_string(&self, path: String) -> Result<Value> {
+        self.check_open()?;
+        Ok(self.options.path_rules.report(&path))
     }
-    
+
+    /// Validate any value against an ad-hoc schema, returning every issue
+    /// (empty when valid) instead of throwing. An unparseable schema throws.
     #[napi]
-    pub fn find_index_paths(&self, name: String, key: Value) -> Result<Vec<String>> {
-        let indexes = self.indexes.read();
-        if let Some(idx) = indexes.get(&name) {
-            if let Some(paths) = idx.find(&key) {
-                return Ok(paths.clone());
-            }
-        }
-        Ok(vec![])
+    pub fn validate_value(&self, value: Value, schema_json: String) -> Result<Value> {
+        self.check_open()?;
+        validate_value(value, schema_json)
     }
-    
+
+    /// Validate `value` as `set` would store it at `path`, defaults filled in
     #[napi]
-    pub fn clear_index(&self, name: String) -> Result<()> {
-         let mut indexes = self.indexes.write();
-         if let Some(idx) = indexes.get_mut(&name) {
-             idx.clear();
-         }
-         Ok(())
+    pub fn validate_path(&self, path: String, value: Value) -> Result<()> {
+        self.check_recovered()?;
+        let value = self.with_schema_defaults(&path, value);
+        self.check_schema(&path, &value)
     }
 
-    // Schema API
+    /// Validate `value` against the part of the nearest registered schema that
+    /// describes `path` itself; a path the schema says nothing about passes
+    fn check_schema(&self, path: &str, value: &Value) -> Result<()> {
+        if self.schemas_suspended() {
+            return Ok(());
+        }
+        let schemas = self.schemas.read();
+        let Some((prefix, schema)) = governing_schema(&schemas, path) else { return Ok(()) };
+        let parts: Vec<&str> = path.split('.').collect();
+        let details = match schema::resolve(schema, &parts[prefix.split('.').count()..]) {
+            Ok(Some(schema)) => match validate(value, schema) {
+                Ok(()) => return Ok(()),
+                Err(e) => e.to_string(),
+            },
+            Ok(None) => return Ok(()),
+            // A key on the way is not allowed
+            Err(e) => e.to_string(),
+        };
+        Err(DbError::ValidationFailed { path: path.to_string(), details }.into())
+    }
 
+    // Bulk load
+    //
+    // bulk.rs describes what the mode skips and what still applies.
+
+    /// Start a bulk load: until `end_bulk_load`, writes skip the WAL (a crash
+    /// loses them all), coalescing, delete notifications and the upkeep of
+    /// counters, indexes and views, and schemas are not enforced unless
+    /// `keepSchemas` is set. Fails with ERR_TRANSACTION_ACTIVE inside a
+    /// transaction, and with ERR_CONFLICT if a bulk load is already running.
     #[napi]
-    pub fn register_schema(&self, path: String, schema_json: String) -> Result<()> {
-        let schema: Schema = serde_json::from_str(&schema_json)
-            .map_err(|e| Error::from_reason(format!("Invalid schema JSON: {}", e)))?;
-        let mut schemas = self.schemas.write();
-        schemas.insert(path, schema);
+    pub fn begin_bulk_load(&self, options: Option<BulkLoadOptions>) -> Result<()> {
+        self.check_recovered()?;
+        self.flush_coalesced()?;
+        // Held so no transaction can start in between
+        let transactions = self.transactions.lock();
+        if transactions.is_active() {
+            return Err(DbError::TransactionActive.into());
+        }
+        let mut bulk = self.bulk_load.write();
+        if bulk.is_some() {
+            return Err(DbError::Conflict("A bulk load is already in progress".to_string()).into());
+        }
+        let keep_schemas = options.and_then(|o| o.keep_schemas).unwrap_or(false);
+        *bulk = Some(Arc::new(BulkLoad::new(keep_schemas)));
+        self.bulk_loading.store(true, Ordering::Release);
         Ok(())
     }
 
+    /// End the bulk load: rebuild every tracked index and counter from the data
+    /// (in parallel), mark custom indexes and auto-refresh views for a full
+    /// refresh, then save, which makes the load durable. Returns `{writes,
+    /// loadMs, rebuildMs, saveMs, totalMs, indexesRebuilt, countersRebuilt}`.
     #[napi]
-    pub fn validate_path(&self, path: String, value: Value) -> Result<()> {
-        let schemas = self.schemas.read();
-        // Find best matching schema (exact or parent)
-        let mut parts: Vec<&str> = path.split('.').collect();
-        while !parts.is_empty() {
-            let current_path = parts.join(".");
-            if let Some(schema) = schemas.get(&current_path) {
-                validate(&value, schema).map_err(|e| Error::from_reason(format!("Validation failed at {}: {}", current_path, e)))?;
-                break;
-            }
-            parts.pop();
+    pub fn end_bulk_load(&self) -> Result<Value> {
+        self.check_recovered()?;
+        let bulk = {
+            // Under the write lock so no write falls between the mode ending
+            // and the derived state being marked stale
+            let _data = self.data.write();
+            let Some(bulk) = self.bulk_load.write().take() else {
+                return Err(DbError::InvalidArgument("No bulk load is in progress".to_string()).into());
+            };
+            self.bulk_loading.store(false, Ordering::Release);
+            self.invalidate_derived();
+            bulk
+        };
+        let load_ms = bulk.started.elapsed().as_secs_f64() * 1000.0;
+
+        let rebuild_started = std::time::Instant::now();
+        let (indexes_rebuilt, counters_rebuilt) = {
+            let data = self.data.read();
+            (self.rebuild_tracked_indexes(&data), self.rebuild_counters(&data))
+        };
+        let rebuild_ms = rebuild_started.elapsed().as_secs_f64() * 1000.0;
+        // Custom indexes run their extractors here, on this thread
+        let save = self.save_with_report()?;
+        Ok(json!({
+            "writes": bulk.writes(),
+            "loadMs": load_ms,
+            "rebuildMs": rebuild_ms,
+            "saveMs": save["totalMs"],
+            "totalMs": bulk.started.elapsed().as_secs_f64() * 1000.0,
+            "indexesRebuilt": indexes_rebuilt,
+            "countersRebuilt": counters_rebuilt,
+        }))
+    }
+
+    /// Whether a bulk load is in progress
+    #[napi]
+    pub fn is_bulk_loading(&self) -> Result<bool> {
+        self.check_open()?;
+        Ok(self.bulk_loading.load(Ordering::Acquire))
+    }
+
+    fn bulk_load(&self) -> Option<Arc<BulkLoad>> {
+        if !self.bulk_loading.load(Ordering::Acquire) {
+            return None;
         }
-        Ok(())
+        self.bulk_load.read().clone()
+    }
+
+    /// Mark everything `note_write` skips during a bulk load for a full refresh
+    fn invalidate_derived(&self) {
+        for watch in self.view_watches.read().values() {
+            watch.stale.store(true, Ordering::Release);
+        }
+        for counter in self.counters.lock().values_mut() {
+            counter.invalidate();
+        }
+        for custom in self.custom_indexes.lock().values_mut() {
+            custom.rescan = true;
+        }
+        for upkeep in self.index_upkeep.lock().values_mut() {
+            upkeep.invalidate();
+        }
+    }
+
+    /// Whether a bulk load has schema enforcement off
+    fn schemas_suspended(&self) -> bool {
+        self.bulk_load().is_some_and(|bulk| !bulk.keep_schemas)
+    }
+
+    /// Bring every stale tracked index up to date, one index per thread; returns
+    /// how many were refreshed
+    fn rebuild_tracked_indexes(&self, data: &Value) -> usize {
+        let mut upkeeps = self.index_upkeep.lock();
+        let mut indexes = self.indexes.write();
+        let mut stale: HashMap<&String, &mut Upkeep> = upkeeps.iter_mut().filter(|(_, u)| !u.is_clean()).collect();
+        let mut work: Vec<(&mut Upkeep, &mut BTreeIndex)> = indexes.iter_mut()
+            .filter_map(|(name, idx)| Some((stale.remove(name)?, idx)))
+            .collect();
+        work.par_iter_mut().for_each(|(upkeep, idx)| {
+            upkeep.refresh(idx, data.pointer(&Self::to_pointer(&upkeep.collection)));
+        });
+        work.len()
+    }
+
+    /// Bring every stale counter up to date, one counter per thread; returns how
+    /// many were refreshed
+    fn rebuild_counters(&self, data: &Value) -> usize {
+        let mut counters = self.counters.lock();
+        let mut work: Vec<&mut Counter> = counters.values_mut().filter(|c| !c.is_clean()).collect();
+        work.par_iter_mut().for_each(|counter| Self::refresh_counter(data, counter));
+        work.len()
     }
 
     // Advanced Transactions
-    
+
+    /// Open a transaction and return its id. Several may be open at once, each
+    /// with its own undo log and savepoints: writes pass the id as their `txn`
+    /// option, and a write without one joins none. A write overlapping a path
+    /// another open transaction wrote fails with ERR_CONFLICT, one without a
+    /// `txn` too, so rolling one back never undoes another's writes.
+    ///
+    /// With `isolation: "exclusive"` no other transaction may be open, and
+    /// none can begin until it ends (both fail with ERR_TRANSACTION_ACTIVE).
     #[napi]
-    pub fn begin_transaction(&self) -> Result<()> {
-        let mut state = self.transaction_state.lock();
-        if state.is_some() {
-            return Err(Error::from_reason("Transaction already active".to_string()));
+    pub fn begin_transaction(&self, options: Option<TransactionOptions>) -> Result<u32> {
+        self.check_recovered()?;
+        let exclusive = match options.and_then(|o| o.isolation).as_deref() {
+            None | Some("read_uncommitted") => false,
+            Some("exclusive") => true,
+            Some(other) => return Err(DbError::InvalidArgument(format!(
+                "Unknown isolation '{}': expected 'read_uncommitted' or 'exclusive'", other
+            )).into()),
+        };
+        self.flush_coalesced()?;
+        let mut transactions = self.transactions.lock();
+        if self.bulk_loading.load(Ordering::Acquire) {
+            return Err(DbError::Conflict("A bulk load is in progress; end it before starting a transaction".to_string()).into());
         }
-        *state = Some(TransactionState {
-            undo_log: Vec::new(),
+        if transactions.exclusive.is_some() || (exclusive && transactions.is_active()) {
+            return Err(DbError::TransactionActive.into());
+        }
+        let id = transactions.next_id + 1;
+        self.append_txn_marker(WalOpType::Begin, id, &[])?;
+        transactions.next_id = id;
+        if exclusive {
+            transactions.exclusive = Some(id);
+        }
+        transactions.open.insert(id, TransactionState {
+            undo: UndoLog::new(format!("{}.undo.{}.tmp", self.path, id)),
             savepoints: HashMap::new(),
         });
+        Ok(id)
+    }
+
+    /// Commit `txn`, or the only open transaction. Its writes survive a crash
+    /// once the Commit record is in the WAL; if logging it fails the
+    /// transaction stays open.
+    #[napi]
+    pub fn commit_transaction(&self, txn: Option<u32>) -> Result<()> {
+        self.check_open()?;
+        let mut transactions = self.transactions.lock();
+        let (id, state) = transactions.target(txn)?;
+        self.append_txn_marker(WalOpType::Commit, id, &state.undo.paths())?;
+        transactions.close(id);
         Ok(())
     }
-    
+
+    /// Undo every write of `txn`, or of the only open transaction
     #[napi]
-    pub fn commit_transaction(&self) -> Result<()> {
-        let mut state = self.transaction_state.lock();
-        if state.is_none() {
-            return Err(Error::from_reason("No active transaction".to_string()));
+    pub fn rollback_transaction(&self, txn: Option<u32>) -> Result<()> {
+        self.check_open()?;
+        // Take the log out first: writers lock data before the transaction state
+        let mut state = {
+            let mut transactions = self.transactions.lock();
+            let (id, _) = transactions.target(txn)?;
+            // Recovery drops a transaction without a Commit whether or not
+            // the Abort made it, so a failed append must not stop the rollback
+            let _ = self.append_txn_marker(WalOpType::Abort, id, &[]);
+            transactions.close(id).expect("target is open")
+        };
+        let mut data = self.data.write();
+        while let Some((path, restore)) = state.undo.pop()? {
+            self.note_write(&path);
+            Self::apply_undo_entry(&mut data, &path, restore);
         }
-        *state = None;
         Ok(())
     }
-    
+
     #[napi]
-    pub fn rollback_transaction(&self) -> Result<()> {
-        let mut state_lock = self.transaction_state.lock();
-        if let Some(state) = state_lock.take() {
-            let mut data = self.data.write();
-            self.apply_undo_log(&mut data, state.undo_log)?;
-        } else {
-            return Err(Error::from_reason("No active transaction".to_string()));
+    pub fn create_savepoint(&self, name: String, txn: Option<u32>) -> Result<()> {
+        self.check_open()?;
+        let mut transactions = self.transactions.lock();
+        let (_, state) = transactions.target(txn)?;
+        state.savepoints.insert(name, state.undo.len());
+        state.undo.mark_boundary();
+        Ok(())
+    }
+
+    #[napi]
+    pub fn rollback_to_savepoint(&self, name: String, txn: Option<u32>) -> Result<()> {
+        self.check_open()?;
+        let (id, to_rollback) = {
+            let mut transactions = self.transactions.lock();
+            let (id, state) = transactions.target(txn)?;
+            let Some(&index) = state.savepoints.get(&name) else {
+                return Err(DbError::NotFound(format!("Savepoint '{}' not found", name)).into());
+            };
+            let mut entries = Vec::with_capacity(state.undo.len().saturating_sub(index));
+            while state.undo.len() > index {
+                if let Some(entry) = state.undo.pop()? {
+                    entries.push(entry);
+                }
+            }
+            state.savepoints.retain(|_, &mut i| i <= index);
+            state.undo.mark_boundary();
+            (id, entries)
+        };
+
+        // Entries come out newest first. The restores are logged in the
+        // transaction, so a commit does not replay the writes they undo.
+        let mut data = self.data.write();
+        for (mut path, restore) in to_rollback {
+            self.note_write(&path);
+            let reinserted = matches!(restore, Restore::Key(..)) && data.pointer(&Self::to_pointer(&path)).is_none();
+            Self::apply_undo_entry(&mut data, &path, restore);
+            if let (true, Some((parent, _))) = (reinserted, path.rsplit_once('.')) {
+                // A replayed Set would put a restored key last: log its parent
+                path = parent.to_string();
+            }
+            match data.pointer(&Self::to_pointer(&path)) {
+                Some(value) => self.log_wal_op(WalOpType::Set, &path, Some(value.clone()), None, Some(id))?,
+                None => self.log_wal_op(WalOpType::Delete, &path, None, None, Some(id))?,
+            };
         }
         Ok(())
     }
+
+    /// State and undo log size of `txn`, or of the only open transaction
+    #[napi]
+    pub fn transaction_info(&self, txn: Option<u32>) -> Result<TransactionInfo> {
+        self.check_open()?;
+        let mut transactions = self.transactions.lock();
+        let open = transactions.open.len() as u32;
+        let exclusive = transactions.exclusive;
+        let max_undo_bytes = self.options.max_undo_bytes.map(|n| n as i64);
+        Ok(match transactions.target(txn) {
+            Ok((id, s)) => TransactionInfo {
+                active: true,
+                id: Some(id),
+                isolation: Some(if exclusive == Some(id) { "exclusive" } else { "read_uncommitted" }.to_string()),
+                open,
+                entries: s.undo.len() as u32,
+                undo_bytes: s.undo.inline_bytes() as i64,
+                spilled_bytes: s.undo.spilled_bytes() as i64,
+                savepoints: s.savepoints.len() as u32,
+                max_undo_bytes,
+            },
+            Err(DbError::TransactionInactive) => TransactionInfo {
+                active: false,
+                id: None,
+                isolation: None,
+                open,
+                entries: 0,
+                undo_bytes: 0,
+                spilled_bytes: 0,
+                savepoints: 0,
+                max_undo_bytes,
+            },
+            Err(e) => return Err(e.into()),
+        })
+    }
     
+    /// Value of `path` as of the start of `txn` (or of the only open
+    /// transaction): live data with every overlapping undo entry applied newest
+    /// to oldest, the way a rollback would. Outside a transaction this is the
+    /// same as `get`.
     #[napi]
-    pub fn create_savepoint(&self, name: String) -> Result<()> {
-        let mut state = self.transaction_state.lock();
-        if let Some(s) = state.as_mut() {
-            s.savepoints.insert(name, s.undo_log.len());
-            Ok(())
-        } else {
-            Err(Error::from_reason("No active transaction".to_string()))
+    pub fn get_original(&self, path: String, txn: Option<u32>) -> Result<Value> {
+        self.check_open()?;
+        self.settle_staged(&path)?;
+        if meta::is_internal_path(&path) {
+            return Ok(Value::Null);
         }
+        let path = Self::join_path("", &path);
+        let data = self.data.read();
+        let ptr = Self::to_pointer(&path);
+        let original = if ptr.is_empty() { Some(data.clone()) } else { data.pointer(&ptr).cloned() };
+
+        let entries = match self.transactions.lock().target(txn) {
+            Ok((_, state)) => state.undo.overlapping(&path)?,
+            Err(DbError::TransactionInactive) if txn.is_none() => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        drop(data);
+
+        let original = Self::undo_view(&path, original, entries)?;
+
+        let original = match original {
+            Some(v) if path.is_empty() => meta::strip_internal(&v),
+            Some(v) => v,
+            None => Value::Null,
+        };
+        self.check_result_size(&original, "getOriginal")?;
+        Ok(original)
     }
-    
+
+    /// Paths written by `txn` (or the only open transaction), in first-touch
+    /// order (empty outside one)
     #[napi]
-    pub fn rollback_to_savepoint(&self, name: String) -> Result<()> {
-        let mut state_lock = self.transaction_state.lock();
-        if let Some(state) = state_lock.as_mut() {
-            if let Some(&index) = state.savepoints.get(&name) {
-                let to_rollback = state.undo_log.split_off(index);
-                let mut data = self.data.write();
-                self.apply_undo_log(&mut data, to_rollback)?;
-                Ok(())
+    pub fn touched_paths(&self, txn: Option<u32>) -> Result<Vec<String>> {
+        self.check_open()?;
+        Ok(match self.transactions.lock().target(txn) {
+            Ok((_, state)) => state.undo.paths().into_iter().map(String::from).collect(),
+            Err(DbError::TransactionInactive) if txn.is_none() => Vec::new(),
+            Err(e) => return Err(e.into()),
+        })
+    }
+
+    /// `value`, read at `path`, with the undo entries `entries` (newest first,
+    /// each at, above or below `path`) applied the way a rollback would
+    fn undo_view(path: &str, value: Option<Value>, entries: Vec<(String, Restore)>) -> Result<Option<Value>> {
+        let mut original = value;
+        for (entry_path, restore) in entries {
+            if entry_path.len() <= path.len() {
+                // Same path or an ancestor: its old value contains ours
+                let rel = if entry_path.is_empty() { path } else { path[entry_path.len()..].trim_start_matches('.') };
+                match restore {
+                    Restore::Key(old_value, _) => {
+                        let rel_ptr = Self::to_pointer(rel);
+                        original = if rel_ptr.is_empty() { Some(old_value) } else { old_value.pointer(&rel_ptr).cloned() };
+                    }
+                    Restore::Value(old_value) => {
+                        let rel_ptr = Self::to_pointer(rel);
+                        original = old_value.and_then(|v| if rel_ptr.is_empty() { Some(v) } else { v.pointer(&rel_ptr).cloned() });
+                    }
+                    Restore::Truncate(len) if rel.is_empty() => Self::truncate_at(&mut original, "", len),
+                    Restore::Truncate(len) => {
+                        // Gone if it is inside an appended element
+                        let index = rel.split('.').next().and_then(|i| i.parse::<usize>().ok());
+                        if index.is_some_and(|i| i >= len) {
+                            original = None;
+                        }
+                    }
+                }
             } else {
-                Err(Error::from_reason(format!("Savepoint '{}' not found", name)))
+                // A descendant: patch it into our value
+                let rel = if path.is_empty() { entry_path.as_str() } else { &entry_path[path.len() + 1..] };
+                let old_value = match restore {
+                    Restore::Value(old_value) => old_value,
+                    Restore::Key(old_value, position) => {
+                        let mut root = original.take().unwrap_or(Value::Null);
+                        Self::restore_key_at(&mut root, rel, old_value, position)?;
+                        original = Some(root);
+                        continue;
+                    }
+                    Restore::Truncate(len) => {
+                        Self::truncate_at(&mut original, rel, len);
+                        continue;
+                    }
+                };
+                match old_value {
+                    Some(v) => {
+                        let mut root = original.take().unwrap_or(Value::Null);
+                        Self::set_value_at_path(&mut root, rel, v)?;
+                        original = Some(root);
+                    }
+                    None => {
+                        if let Some(root) = original.as_mut() {
+                            Self::delete_value_at_path(root, rel)?;
+                        }
+                    }
+                }
             }
-        } else {
-            Err(Error::from_reason("No active transaction".to_string()))
         }
+        Ok(original)
     }
-    
-    fn apply_undo_log(&self, data: &mut Value, undo_log: Vec<(String, Option<Value>)>) -> Result<()> {
-        // Apply in reverse order
-        for (path, old_value) in undo_log.into_iter().rev() {
-            if let Some(val) = old_value {
-                let _ = Self::set_value_at_path(data, &path, val);
-            } else {
-                let _ = Self::delete_value_at_path(data, &path);
+
+    fn apply_undo_entry(data: &mut Value, path: &str, restore: Restore) {
+        match restore {
+            Restore::Value(Some(val)) => {
+                let _ = Self::set_value_at_path(data, path, val);
+            }
+            Restore::Value(None) => {
+                let _ = Self::delete_value_at_path(data, path);
+            }
+            Restore::Key(val, position) => {
+                let _ = Self::restore_key_at(data, path, val, position);
+            }
+            Restore::Truncate(len) => {
+                if let Some(Value::Array(arr)) = data.pointer_mut(&Self::to_pointer(path)) {
+                    arr.truncate(len);
+                }
+            }
+        }
+    }
+
+    /// Set `value` at `path`, putting a key missing from its parent object
+    /// back at `position` among its siblings rather than last
+    fn restore_key_at(data: &mut Value, path: &str, value: Value, position: usize) -> Result<()> {
+        let (parent, key) = path.rsplit_once('.').unwrap_or(("", path));
+        let parent_ptr = Self::to_pointer(parent);
+        let target = if parent_ptr.is_empty() { Some(&mut *data) } else { data.pointer_mut(&parent_ptr) };
+        match target {
+            Some(Value::Object(map)) if !map.contains_key(key) => {
+                map.shift_insert(position.min(map.len()), key.to_string(), value);
+                Ok(())
+            }
+            _ => Self::set_value_at_path(data, path, value),
+        }
+    }
+
+    /// Truncate the array at `rel` inside `value` to `len` elements
+    fn truncate_at(value: &mut Option<Value>, rel: &str, len: usize) {
+        if let Some(Value::Array(arr)) = value.as_mut().and_then(|v| v.pointer_mut(&Self::to_pointer(rel))) {
+            arr.truncate(len);
+        }
+    }
+
+    /// Record this thread's writes in `txn` until the scope drops;
+    /// ERR_NO_TRANSACTION if `txn` is not open
+    fn enter_txn(&self, txn: Option<u32>) -> Result<TxnScope> {
+        if txn.is_some_and(|id| !self.transactions.lock().open.contains_key(&id)) {
+            return Err(DbError::TransactionInactive.into());
+        }
+        Ok(TxnScope::enter(txn))
+    }
+
+    fn undo_limits(&self) -> UndoLimits {
+        UndoLimits {
+            max_bytes: self.options.max_undo_bytes,
+            spill: self.options.undo_spill,
+        }
+    }
+
+    /// Record the old value of `path` in the transaction the running write
+    /// belongs to, if any. `old_value` is only evaluated when the path has no
+    /// entry since the last savepoint. Fails with ERR_CONFLICT if another open
+    /// transaction wrote an overlapping path. Callers holding the data lock may
+    /// call this (lock order is data, then transaction state).
+    fn record_undo_with(&self, path: &str, old_value: impl FnOnce() -> Option<Value>) -> Result<()> {
+        self.record_undo_at(path, || (old_value(), None))
+    }
+
+    /// `record_undo_with`, where `old` also gives the position of the key in
+    /// its parent object, so a rollback restores a removed key in place
+    fn record_undo_at(&self, path: &str, old: impl FnOnce() -> (Option<Value>, Option<usize>)) -> Result<()> {
+        let path = Self::written(path);
+        self.with_recording(&path, |undo| {
+            if undo.has_entry_for(&path) {
+                return Ok(());
             }
+            let (old_value, position) = old();
+            undo.push(&path, old_value, position, self.undo_limits())
+        })
+    }
+
+    /// Record that the array at `path` had `len` elements before values were
+    /// appended, so rollback truncates it instead of restoring a copy. Called
+    /// before the append, which never copies the array.
+    fn record_append_undo(&self, path: &str, len: usize) -> Result<()> {
+        let path = Self::written(path);
+        self.with_recording(&path, |undo| {
+            undo.push_truncate(&path, len);
+            Ok(())
+        })
+    }
+
+    /// Run `record` on the undo log of the transaction the running write
+    /// belongs to, if any, after checking no other open transaction wrote an
+    /// overlapping path. A write naming no transaction is checked the same
+    /// way: a rollback could overwrite it without a trace in the WAL.
+    fn with_recording(&self, path: &str, record: impl FnOnce(&mut UndoLog) -> std::result::Result<(), DbError>) -> Result<()> {
+        let mut transactions = self.transactions.lock();
+        let txn = WRITE_TXN.with(|current| current.get()).filter(|id| transactions.open.contains_key(id));
+        if let Some(other) = transactions.open.iter().find(|(other, state)| Some(**other) != txn && state.undo.touches(path)) {
+            return Err(DbError::Conflict(format!("'{}' overlaps a path written by transaction {}", path, other.0)).into());
         }
+        let Some((_, state)) = transactions.recording(txn) else { return Ok(()) };
+        record(&mut state.undo)?;
         Ok(())
     }
-    
-    fn record_undo(&self, path: &str) {
-        let mut state_lock = self.transaction_state.lock();
-        if let Some(state) = state_lock.as_mut() {
-            let data = self.data.read();
-            let old_value = data.pointer(&format!("/{}", path.replace(".", "/"))).cloned();
-            state.undo_log.push((path.to_string(), old_value));
+
+    fn record_undo(&self, path: &str) -> Result<()> {
+        if !self.transactions.lock().is_active() {
+            return Ok(());
+        }
+        let data = self.data.read();
+        self.record_undo_in(&data, path)
+    }
+
+    /// Record the old value of `path` as found in `data`. A write to an array
+    /// element snapshots the whole array instead: removing an element shifts the
+    /// ones after it, so an entry recorded by index could later restore into the
+    /// wrong slot (or be skipped as a repeat of a write to a different element).
+    /// A write creating missing parents records the highest one, so a rollback
+    /// removes them too.
+    fn record_undo_in(&self, data: &Value, path: &str) -> Result<()> {
+        let path = Self::written(path);
+        let mut path = path.as_ref();
+        if data.pointer(&Self::to_pointer(path)).is_none() {
+            if let Some((end, _)) = path.match_indices('.').find(|(end, _)| data.pointer(&Self::to_pointer(&path[..*end])).is_none()) {
+                path = &path[..end];
+            }
         }
+        let target = match path.rsplit_once('.') {
+            Some((parent, index)) if index.parse::<usize>().is_ok()
+                && matches!(data.pointer(&Self::to_pointer(parent)), Some(Value::Array(_))) => parent,
+            _ => path,
+        };
+        let ptr = Self::to_pointer(target);
+        self.record_undo_at(target, || {
+            if ptr.is_empty() {
+                return (Some(data.clone()), None);
+            }
+            let (parent, key) = target.rsplit_once('.').unwrap_or(("", target));
+            let position = match data.pointer(&Self::to_pointer(parent)) {
+                Some(Value::Object(map)) => map.keys().position(|k| k == key),
+                _ => None,
+            };
+            (data.pointer(&ptr).cloned(), position)
+        })
     }
 }
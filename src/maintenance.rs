@@ -0,0 +1,271 @@
+//! Scheduled maintenance
+//!
+//! `schedule_maintenance` hands recurring upkeep (checkpoints, saving unsaved
+//! writes, purging expired leases, pruning backups, refreshing views) to one
+//! background thread instead of timers in every service. Tasks run one at a
+//! time, each through the same code a caller would use, so a task holds its
+//! locks for as long as that call does and no longer. A task is next due its
+//! interval after its previous run finished, so a slow run delays it rather
+//! than piling up, and it never overlaps itself. A task that fails (or
+//! panics) records its error and runs again on schedule; the thread only
+//! stops when the schedule is replaced or the database closes.
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::DbError;
+use crate::now_ms;
+
+/// Shortest interval a task may be scheduled at
+pub const MIN_INTERVAL_MS: u64 = 10;
+
+/// Backup snapshots `prune_backups` keeps by default
+const DEFAULT_KEEP_BACKUPS: usize = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Task {
+    /// Save the database, truncating the WAL
+    Checkpoint,
+    /// Save only if anything was written since the last save
+    SaveIfDirty,
+    /// Drop expired leases from the reserved namespace
+    PurgeExpired,
+    /// Delete backup snapshots past the newest `keep`, or older than `max_age_ms`
+    PruneBackups { keep: usize, max_age_ms: Option<u64> },
+    RefreshView(String),
+    /// Delete the expired documents of a TTL index; only run by the TTL sweeper
+    SweepTtl(String),
+}
+
+impl Task {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Task::Checkpoint => "checkpoint",
+            Task::SaveIfDirty => "save_if_dirty",
+            Task::PurgeExpired => "purge_expired",
+            Task::PruneBackups { .. } => "prune_backups",
+            Task::RefreshView(_) => "refresh_view",
+            Task::SweepTtl(_) => "sweep_ttl",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Entry {
+    task: String,
+    #[serde(alias = "interval_ms")]
+    interval_ms: u64,
+    #[serde(default)]
+    args: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Args {
+    keep: Option<usize>,
+    #[serde(alias = "max_age_ms")]
+    max_age_ms: Option<u64>,
+    name: Option<String>,
+}
+
+/// A scheduled task and what its runs so far did
+pub struct Job {
+    task: Task,
+    interval: Duration,
+    args: Value,
+    due: Instant,
+    running: bool,
+    runs: u64,
+    failures: u64,
+    last_run_at: Option<u64>,
+    last_duration_ms: Option<f64>,
+    last_error: Option<String>,
+    last_result: Option<Value>,
+}
+
+impl Job {
+    /// Parse one `{task, intervalMs, args}` entry; the first run is due one interval from now
+    pub fn parse(entry: &Value) -> Result<Self, DbError> {
+        let entry: Entry = serde_json::from_value(entry.clone())
+            .map_err(|e| DbError::InvalidArgument(format!("Invalid maintenance task: {}", e)))?;
+        if entry.interval_ms < MIN_INTERVAL_MS {
+            return Err(DbError::InvalidArgument(format!(
+                "Maintenance task '{}' needs an interval of at least {}ms", entry.task, MIN_INTERVAL_MS
+            )));
+        }
+        let args: Args = match &entry.args {
+            Value::Null => Args::default(),
+            args => serde_json::from_value(args.clone())
+                .map_err(|e| DbError::InvalidArgument(format!("Invalid args for maintenance task '{}': {}", entry.task, e)))?,
+        };
+        let task = match entry.task.as_str() {
+            "checkpoint" => Task::Checkpoint,
+            "save_if_dirty" => Task::SaveIfDirty,
+            "purge_expired" => Task::PurgeExpired,
+            "prune_backups" => Task::PruneBackups { keep: args.keep.unwrap_or(DEFAULT_KEEP_BACKUPS), max_age_ms: args.max_age_ms },
+            "refresh_view" => match args.name {
+                Some(name) => Task::RefreshView(name),
+                None => return Err(DbError::InvalidArgument("Maintenance task 'refresh_view' needs args.name".to_string())),
+            },
+            other => return Err(DbError::InvalidArgument(format!(
+                "Unknown maintenance task '{}': use checkpoint, save_if_dirty, purge_expired, prune_backups or refresh_view", other
+            ))),
+        };
+        Ok(Job::new(task, entry.interval_ms, entry.args))
+    }
+
+    /// A job first due one interval from now
+    pub fn new(task: Task, interval_ms: u64, args: Value) -> Self {
+        let interval = Duration::from_millis(interval_ms);
+        Job {
+            task,
+            interval,
+            args,
+            due: Instant::now() + interval,
+            running: false,
+            runs: 0,
+            failures: 0,
+            last_run_at: None,
+            last_duration_ms: None,
+            last_error: None,
+            last_result: None,
+        }
+    }
+
+    fn status(&self) -> Value {
+        let next_run_at = (!self.running).then(|| now_ms() + self.due.saturating_duration_since(Instant::now()).as_millis() as u64);
+        json!({
+            "task": self.task.name(),
+            "intervalMs": self.interval.as_millis() as u64,
+            "args": self.args,
+            "running": self.running,
+            "runs": self.runs,
+            "failures": self.failures,
+            "lastRunAt": self.last_run_at,
+            "lastDurationMs": self.last_duration_ms,
+            "lastError": self.last_error,
+            "lastResult": self.last_result,
+            "nextRunAt": next_run_at,
+        })
+    }
+}
+
+struct State {
+    jobs: Vec<Job>,
+    stopping: bool,
+}
+
+type Shared = Arc<(Mutex<State>, Condvar)>;
+
+/// A thread running jobs (the maintenance thread, or the TTL sweeper); dropping
+/// it stops the thread once any running task returns
+pub struct Scheduler {
+    shared: Shared,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    pub fn start(name: &str, jobs: Vec<Job>, run: impl Fn(&Task) -> Result<Value, String> + Send + 'static) -> Result<Self, DbError> {
+        let shared: Shared = Arc::new((Mutex::new(State { jobs, stopping: false }), Condvar::new()));
+        let worker = Arc::clone(&shared);
+        let thread = std::thread::Builder::new()
+            .name(format!("jsondb-{}", name))
+            .spawn(move || Self::work(&worker, run))
+            .map_err(|e| DbError::Io(format!("Failed to start the {} thread: {}", name, e)))?;
+        Ok(Scheduler { shared, thread: Some(thread) })
+    }
+
+    /// Add a job to the running thread, replacing one with the same task
+    pub fn insert(&self, job: Job) {
+        let mut state = self.shared.0.lock();
+        match state.jobs.iter_mut().find(|j| j.task == job.task) {
+            Some(existing) => {
+                existing.interval = job.interval;
+                existing.args = job.args;
+                existing.due = job.due;
+            }
+            None => state.jobs.push(job),
+        }
+        self.shared.1.notify_all();
+    }
+
+    /// Remove the job running `task`; a run in progress still finishes
+    pub fn remove(&self, task: &Task) -> bool {
+        let mut state = self.shared.0.lock();
+        let before = state.jobs.len();
+        state.jobs.retain(|j| j.task != *task);
+        before != state.jobs.len()
+    }
+
+    fn work(shared: &Shared, run: impl Fn(&Task) -> Result<Value, String>) {
+        let (state, wake) = &**shared;
+        let mut state = state.lock();
+        while !state.stopping {
+            let Some(next) = (0..state.jobs.len()).min_by_key(|&i| state.jobs[i].due) else {
+                // Idle until a job is inserted
+                wake.wait(&mut state);
+                continue;
+            };
+            let due = state.jobs[next].due;
+            if due > Instant::now() {
+                wake.wait_until(&mut state, due);
+                continue;
+            }
+            let job = &mut state.jobs[next];
+            job.running = true;
+            let task = job.task.clone();
+            let started = Instant::now();
+            let outcome = MutexGuard::unlocked(&mut state, || {
+                catch_unwind(AssertUnwindSafe(|| run(&task))).unwrap_or_else(|panic| {
+                    let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown panic".to_string());
+                    Err(format!("task panicked: {}", message))
+                })
+            });
+            // Jobs may have been inserted or removed while it ran
+            let Some(job) = state.jobs.iter_mut().find(|j| j.task == task) else { continue };
+            job.running = false;
+            job.runs += 1;
+            job.last_run_at = Some(now_ms());
+            job.last_duration_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+            match outcome {
+                Ok(result) => {
+                    job.last_result = Some(result);
+                    job.last_error = None;
+                }
+                Err(error) => {
+                    job.failures += 1;
+                    job.last_error = Some(error);
+                }
+            }
+            job.due = Instant::now() + job.interval;
+        }
+    }
+
+    /// `{running, tasks: [{task, intervalMs, args, running, runs, failures,
+    /// lastRunAt, lastDurationMs, lastError, lastResult, nextRunAt}]}`
+    pub fn status(&self) -> Value {
+        let state = self.shared.0.lock();
+        json!({
+            "running": !state.stopping,
+            "tasks": state.jobs.iter().map(Job::status).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shared.0.lock().stopping = true;
+        self.shared.1.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Reserved metadata namespace
+//!
+//! Engine features (sequences, leases, TTL tables, ...) persist their state in a
+//! top-level `__jsondb__` object inside the data file. User reads and writes
+//! never see it unless they opt in explicitly.
+
+use serde_json::{Map, Value};
+
+/// Top-level key holding engine metadata
+pub const META_KEY: &str = "__jsondb__";
+
+/// Marker written into the namespace so a user object that happens to use the
+/// reserved key is never mistaken for engine metadata
+const META_MARKER: &str = "$jsondb";
+const META_VERSION: u64 = 1;
+
+/// Whether a dot-notation path or JSON pointer addresses the reserved namespace
+pub fn is_internal_path(path: &str) -> bool {
+    let trimmed = path.strip_prefix('/').unwrap_or(path);
+    let first = trimmed.split(['.', '/']).next().unwrap_or("");
+    first == META_KEY
+}
+
+/// Build a dot-notation path inside the namespace
+pub fn internal_path(section: &str) -> String {
+    format!("{}.{}", META_KEY, section)
+}
+
+/// A fresh namespace object
+pub fn new_namespace() -> Value {
+    let mut map = Map::new();
+    map.insert(META_MARKER.to_string(), Value::from(META_VERSION));
+    Value::Object(map)
+}
+
+fn is_namespace(value: &Value) -> bool {
+    value.get(META_MARKER).is_some()
+}
+
+/// Move a pre-existing user key that collides with the namespace out of the way.
+/// Returns the key it was relocated to, if any.
+pub fn migrate_collision(root: &mut Value) -> Option<String> {
+    let map = root.as_object_mut()?;
+    match map.get(META_KEY) {
+        Some(existing) if !is_namespace(existing) => {}
+        _ => return None,
+    }
+
+    let mut target = format!("{}user", META_KEY);
+    let mut n = 1;
+    while map.contains_key(&target) {
+        n += 1;
+        target = format!("{}user{}", META_KEY, n);
+    }
+    // The renamed key keeps its place among the user's keys
+    let position = map.keys().position(|k| k == META_KEY)?;
+    let user_value = map.shift_remove(META_KEY)?;
+    map.shift_insert(position, target.clone(), user_value);
+    Some(target)
+}
+
+/// Clone of `value` without the namespace (only the root object can hold it)
+pub fn strip_internal(value: &Value) -> Value {
+    match value {
+        Value::Object(map) if map.contains_key(META_KEY) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| k.as_str() != META_KEY)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Read a section of the namespace
+pub fn get<'a>(root: &'a Value, section: &str) -> Option<&'a Value> {
+    root.get(META_KEY)?.get(section)
+}
+
+/// Write a section of the namespace, creating the namespace on first use
+pub fn set(root: &mut Value, section: &str, value: Value) {
+    if !root.is_object() {
+        return;
+    }
+    let map = root.as_object_mut().unwrap();
+    let ns = map.entry(META_KEY.to_string()).or_insert_with(new_namespace);
+    if let Value::Object(ns_map) = ns {
+        ns_map.insert(section.to_string(), value);
+    }
+}
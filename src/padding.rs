@@ -0,0 +1,136 @@
+//! Array padding policy
+//!
+//! What a write to an array index past the end does (`arr.10` on a 3-element
+//! array, or `list.2` where `list` does not exist yet):
+//!
+//! - `pad_null` (the default): fills the gap with nulls
+//! - `error`: fails with ERR_INVALID_ARGUMENT and writes nothing
+//! - `append`: writes at the end instead, as a push would
+//!
+//! The policy rewrites the path before the write is logged, so the WAL holds
+//! the index actually written and replay never depends on the policy.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+use crate::error::DbError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    PadNull,
+    Error,
+    Append,
+}
+
+impl Padding {
+    pub fn parse(policy: &str) -> Result<Self, DbError> {
+        match policy {
+            "pad_null" => Ok(Padding::PadNull),
+            "error" => Ok(Padding::Error),
+            "append" => Ok(Padding::Append),
+            other => Err(DbError::InvalidArgument(format!(
+                "Unknown array padding '{}': use 'pad_null', 'error' or 'append'", other
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Padding::PadNull => "pad_null",
+            Padding::Error => "error",
+            Padding::Append => "append",
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_u8(n: u8) -> Self {
+        match n {
+            1 => Padding::Error,
+            2 => Padding::Append,
+            _ => Padding::PadNull,
+        }
+    }
+}
+
+/// What a path segment lands in, following the container creation rules of
+/// the engine's path writes
+enum Node<'a> {
+    Existing(&'a Value),
+    NewArray,
+    NewObject,
+}
+
+fn is_index(part: &str) -> bool {
+    part.parse::<usize>().is_ok()
+}
+
+/// Whether `resolve` could change or reject a write to `path`
+pub fn applies(path: &str, policy: Padding) -> bool {
+    policy != Padding::PadNull && path.split('.').any(is_index)
+}
+
+/// `path` with every array index past the end resolved by `policy` against
+/// `root`: unchanged for `PadNull`, an error for `Error`, the end of the array
+/// for `Append`
+pub fn resolve<'p>(root: &Value, path: &'p str, policy: Padding) -> Result<Cow<'p, str>, DbError> {
+    if !applies(path, policy) {
+        return Ok(Cow::Borrowed(path));
+    }
+    let parts: Vec<&str> = path.split('.').collect();
+    let mut resolved: Vec<Cow<str>> = Vec::with_capacity(parts.len());
+    let mut node = Node::Existing(root);
+    for (i, part) in parts.iter().enumerate() {
+        let last = i + 1 == parts.len();
+        // A missing parent is created as an array when the next segment is an index
+        let created = || if !last && is_index(parts[i + 1]) { Node::NewArray } else { Node::NewObject };
+        let array_len = match &node {
+            Node::Existing(Value::Array(items)) => Some(items.len()),
+            Node::NewArray => Some(0),
+            // A null parent of the last segment becomes an array for an index
+            Node::Existing(Value::Null) if last && is_index(part) => Some(0),
+            _ => None,
+        };
+        let Some(len) = array_len else {
+            node = match node {
+                Node::Existing(Value::Object(map)) => match map.get(*part) {
+                    Some(child) => Node::Existing(child),
+                    None => created(),
+                },
+                // Primitives fail in the write itself; a null parent becomes an object
+                _ => created(),
+            };
+            resolved.push(Cow::Borrowed(part));
+            continue;
+        };
+        let Ok(index) = part.parse::<usize>() else {
+            // Not an index: the write itself reports the blocked path
+            resolved.push(Cow::Borrowed(part));
+            node = created();
+            continue;
+        };
+        let index = if index > len {
+            if policy == Padding::Error {
+                let at = parts[..i].join(".");
+                return Err(DbError::InvalidArgument(format!(
+                    "Index {} is past the end of '{}' ({} elements) and array padding is 'error'", index, at, len
+                )));
+            }
+            resolved.push(Cow::Owned(len.to_string()));
+            len
+        } else {
+            resolved.push(Cow::Borrowed(part));
+            index
+        };
+        node = match &node {
+            Node::Existing(Value::Array(items)) => match items.get(index) {
+                Some(Value::Null) | None => created(),
+                Some(child) => Node::Existing(child),
+            },
+            _ => created(),
+        };
+    }
+    Ok(Cow::Owned(resolved.join(".")))
+}
@@ -0,0 +1,73 @@
+//! Stable pagination
+//!
+//! `get_page` walks an object collection in a fixed order, by document key or
+//! by a field with the key breaking ties, and resumes from a cursor holding the
+//! position of the last document returned rather than a numeric offset. The
+//! next page starts strictly after that position, so writes between pages
+//! never repeat a document or skip one that was already there:
+//!
+//! - a document present for the whole walk is returned exactly once, unless
+//!   its `stable_by` field changes during the walk (it may then appear twice
+//!   or not at all)
+//! - a document inserted ahead of the cursor is returned, one inserted behind
+//!   it is not
+//! - a document deleted before the walk reaches it is not returned
+//!
+//! Cursors are opaque to callers: `p1.` and the URL-safe base64 of a small
+//! JSON object naming the collection, the order and the last position.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::compare_sort_values;
+use crate::error::DbError;
+
+const PREFIX: &str = "p1.";
+
+/// The position of one document in a walk
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cursor {
+    /// Collection path
+    #[serde(rename = "p")]
+    pub path: String,
+    /// "key", or the field ordering the walk
+    #[serde(rename = "b")]
+    pub by: String,
+    /// The document's field value (None when missing, or when ordering by key)
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    #[serde(rename = "k")]
+    pub key: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(json))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, DbError> {
+        let invalid = || DbError::InvalidArgument(format!("Invalid page cursor '{}'", cursor));
+        let body = cursor.strip_prefix(PREFIX).ok_or_else(invalid)?;
+        let json = URL_SAFE_NO_PAD.decode(body).map_err(|_| invalid())?;
+        serde_json::from_slice(&json).map_err(|_| invalid())
+    }
+
+    /// Check the cursor came from a walk of `path` in the same order
+    pub fn check(&self, path: &str, by: &str) -> Result<(), DbError> {
+        if self.path != path || self.by != by {
+            return Err(DbError::InvalidArgument(format!(
+                "Page cursor belongs to a walk of '{}' by '{}', not of '{}' by '{}'",
+                self.path, self.by, path, by
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Walk order: the field value (when ordering by a field), then the key
+pub fn compare(a: (Option<&Value>, &str), b: (Option<&Value>, &str)) -> Ordering {
+    compare_sort_values(a.0, b.0).then_with(|| a.1.cmp(b.1))
+}
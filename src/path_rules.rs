@@ -0,0 +1,93 @@
+//! Path hygiene
+//!
+//! Paths often carry user input as segments, so writes are held to a maximum
+//! depth, a maximum segment length and a set of rejected characters (control
+//! characters always, plus any configured ones). The same rules gate WAL
+//! replay, so recovery cannot recreate a path a live write would reject.
+//! Deletes are never checked, so a bad path can always be removed.
+
+use serde_json::{json, Value};
+
+use crate::error::DbError;
+
+#[derive(Debug, Clone)]
+pub struct PathRules {
+    pub max_depth: usize,
+    /// Maximum characters per segment
+    pub max_segment_len: usize,
+    /// Rejected in addition to control characters
+    pub forbidden: Vec<char>,
+}
+
+impl Default for PathRules {
+    fn default() -> Self {
+        PathRules { max_depth: 64, max_segment_len: 1024, forbidden: Vec::new() }
+    }
+}
+
+/// Why a path was rejected
+pub struct PathViolation {
+    pub segment: Option<String>,
+    pub reason: String,
+}
+
+impl PathRules {
+    /// The first rule `path` breaks, if any (the empty path is the root)
+    pub fn violation(&self, path: &str) -> Option<PathViolation> {
+        if path.is_empty() {
+            return None;
+        }
+        let depth = path.split('.').count();
+        if depth > self.max_depth {
+            return Some(PathViolation {
+                segment: None,
+                reason: format!("path is {} segments deep (max {})", depth, self.max_depth),
+            });
+        }
+        for segment in path.split('.') {
+            let len = segment.chars().count();
+            if len > self.max_segment_len {
+                return Some(PathViolation {
+                    segment: Some(segment.to_string()),
+                    reason: format!("segment is {} characters long (max {})", len, self.max_segment_len),
+                });
+            }
+            if let Some(c) = segment.chars().find(|c| c.is_control() || self.forbidden.contains(c)) {
+                return Some(PathViolation {
+                    segment: Some(segment.to_string()),
+                    reason: format!("segment contains forbidden character {:?}", c),
+                });
+            }
+        }
+        None
+    }
+
+    pub fn check(&self, path: &str) -> Result<(), DbError> {
+        match self.violation(path) {
+            None => Ok(()),
+            Some(PathViolation { segment: Some(segment), reason }) => Err(DbError::InvalidPath(format!(
+                "Path segment {:?} of {:?} rejected: {}", shorten(&segment), shorten(path), reason
+            ))),
+            Some(PathViolation { segment: None, reason }) => Err(DbError::InvalidPath(format!(
+                "Path {:?} rejected: {}", shorten(path), reason
+            ))),
+        }
+    }
+
+    /// `{valid, depth}` plus `{segment, reason}` when invalid
+    pub fn report(&self, path: &str) -> Value {
+        let depth = if path.is_empty() { 0 } else { path.split('.').count() };
+        match self.violation(path) {
+            None => json!({ "valid": true, "depth": depth }),
+            Some(v) => json!({ "valid": false, "depth": depth, "segment": v.segment, "reason": v.reason }),
+        }
+    }
+}
+
+/// At most 64 characters of `text` for an error message
+fn shorten(text: &str) -> String {
+    match text.char_indices().nth(64) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
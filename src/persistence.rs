@@ -0,0 +1,93 @@
+//! Save reports
+//!
+//! Every save measures what it wrote: the size of the file (and of the one it
+//! replaced), where the time went, which indexes were rewritten and how much
+//! WAL the save made redundant. The latest report is kept for
+//! `persistence_status`, so a database file that quietly doubles shows up as
+//! a number rather than as a surprise on disk.
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+
+/// Reports kept for `recent`: enough for an hour of saves every few seconds
+const RECENT_SAVES: usize = 1024;
+
+#[derive(Debug, Default, Clone)]
+pub struct SaveReport {
+    pub at: u64,
+    pub bytes: u64,
+    /// Size of the file the save replaced, if there was one
+    pub previous_bytes: Option<u64>,
+    pub serialize_ms: f64,
+    pub fsync_ms: f64,
+    pub total_ms: f64,
+    pub indexes_saved: u32,
+    /// Indexes with no changes since their last save
+    pub indexes_skipped: u32,
+    pub wal_ops_truncated: u64,
+    /// Size of the WAL the save made redundant: the changes it persisted
+    pub wal_bytes_truncated: u64,
+    /// Saves keep no backup generations (snapshots are explicit), so this stays false
+    pub backup_rotated: bool,
+}
+
+impl SaveReport {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "at": self.at,
+            "bytes": self.bytes,
+            "previousBytes": self.previous_bytes,
+            "serializeMs": self.serialize_ms,
+            "fsyncMs": self.fsync_ms,
+            "totalMs": self.total_ms,
+            "indexesSaved": self.indexes_saved,
+            "indexesSkipped": self.indexes_skipped,
+            "walOpsTruncated": self.wal_ops_truncated,
+            "walBytesTruncated": self.wal_bytes_truncated,
+            "backupRotated": self.backup_rotated,
+        })
+    }
+}
+
+/// Save history of one open database
+#[derive(Debug, Default)]
+pub struct Persistence {
+    pub saves: u64,
+    pub bytes_written: u64,
+    pub last: Option<SaveReport>,
+    /// Latest reports, oldest first, for the storage advisor
+    pub recent: VecDeque<SaveReport>,
+    /// Committed WAL LSN at the last truncation (LSNs continue across restarts)
+    pub truncated_lsn: u64,
+}
+
+impl Persistence {
+    /// Highest LSN a save of this process truncated from the WAL
+    pub fn saved_through(&self) -> u64 {
+        if self.saves > 0 { self.truncated_lsn } else { 0 }
+    }
+
+    pub fn record(&mut self, report: SaveReport, truncated_lsn: u64) {
+        self.saves += 1;
+        self.bytes_written += report.bytes;
+        self.truncated_lsn = truncated_lsn;
+        if self.recent.len() == RECENT_SAVES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(report.clone());
+        self.last = Some(report);
+    }
+
+    pub fn status(&self) -> Value {
+        json!({
+            "saves": self.saves,
+            "bytesWritten": self.bytes_written,
+            "lastSave": self.last.as_ref().map(SaveReport::to_json),
+        })
+    }
+
+    /// Reports of saves at or after `since_ms`, oldest first
+    pub fn since(&self, since_ms: u64) -> Vec<SaveReport> {
+        self.recent.iter().filter(|r| r.at >= since_ms).cloned().collect()
+    }
+}
@@ -0,0 +1,107 @@
+//! Index-aware query planning
+//!
+//! `parallel_query` looks at its first filter. When that is an `eq` on a
+//! scalar or a `gt` / `gte` / `lt` / `lte` on a number, and a tracked index
+//! covers the filtered field of the queried collection, the index supplies
+//! the candidate documents and every filter (the first included) is then
+//! checked against just those. Otherwise the whole collection is scanned.
+//!
+//! Candidates are a superset of the matches, never a subset: index keys are
+//! text, so `5` and `"5"` share a key, and range bounds are widened slightly
+//! so every spelling of an equal number (`5`, `5.0`, `-0.0`) falls inside,
+//! whatever the rounding when a key is read back as a number. Checking the
+//! filters again makes the result exactly the scan's, in the same collection
+//! order. A sparse index leaves out null fields, so it never answers `eq null`.
+//! A canonical `eq` on a number reads the same narrow range, so `1` finds the
+//! documents holding `1.0` as well.
+//!
+//! With stats from `analyze` (see stats.rs) the probe's share of the
+//! collection is estimated first, and a probe expected to select more than
+//! half of it scans instead.
+
+use serde_json::{Number, Value};
+use std::ops::Bound;
+
+use crate::btree::BTreeIndex;
+use crate::canonical::Equality;
+use crate::stats;
+use crate::PreparedFilter;
+
+/// How the first filter reads the index
+pub enum Probe {
+    Eq(Value),
+    Range(Bound<Value>, Bound<Value>),
+}
+
+impl Probe {
+    /// The probe for `filter`, or why it cannot use an index
+    pub fn of(filter: &PreparedFilter) -> Result<Self, String> {
+        if filter.field_ref.is_some() {
+            return Err("the first filter compares two fields".to_string());
+        }
+        // Indexes leave out documents without the field
+        if !matches!(filter.absent(), Err(false)) {
+            return Err("the first filter can match documents without the field".to_string());
+        }
+        let op = filter.op.as_str();
+        match (op, &filter.value) {
+            ("eq", Value::Object(_) | Value::Array(_)) => Err("the first filter compares with an object or array".to_string()),
+            ("eq", Value::Number(n)) if filter.equality == Equality::Canonical => Self::around(n, op),
+            ("eq", value) => Ok(Probe::Eq(value.clone())),
+            ("gt" | "gte" | "lt" | "lte", Value::Number(n)) => Self::around(n, op),
+            ("gt" | "gte" | "lt" | "lte", _) => Err(format!("'{}' only matches numbers, and the value is not one", op)),
+            (other, _) => Err(format!("the first filter's '{}' cannot use an index", other)),
+        }
+    }
+
+    /// The range `op` reads around `n`: from just below it, up to just above
+    /// it, or both for a canonical `eq`
+    fn around(n: &Number, op: &str) -> Result<Self, String> {
+        let Some(n) = n.as_f64() else { return Err(format!("'{}' is not a finite number", n)) };
+        // Keys are parsed back from text, not always to the exact float
+        let margin = n.abs() * 1e-9 + f64::MIN_POSITIVE;
+        let widened = |f: f64| Number::from_f64(f).map(Value::Number);
+        let (Some(below), Some(above)) = (widened(n - margin), widened(n + margin)) else {
+            return Err(format!("'{}' is at the end of the number range", n));
+        };
+        Ok(match op {
+            "eq" => Probe::Range(Bound::Included(below), Bound::Included(above)),
+            "gt" | "gte" => Probe::Range(Bound::Included(below), Bound::Unbounded),
+            _ => Probe::Range(Bound::Unbounded, Bound::Included(above)),
+        })
+    }
+
+    /// Whether `idx` holds every document the probe can match
+    pub fn fits(&self, idx: &BTreeIndex) -> bool {
+        !(idx.sparse() && matches!(self, Probe::Eq(Value::Null)))
+    }
+
+    /// Expected share of the analyzed collection the probe selects, from
+    /// `field`'s stats over `entries` documents
+    pub fn estimate(&self, idx: &BTreeIndex, field: &Value, entries: u64) -> Option<f64> {
+        let number = |bound: &Bound<Value>| match bound {
+            Bound::Included(v) | Bound::Excluded(v) => v.as_f64(),
+            Bound::Unbounded => None,
+        };
+        match self {
+            Probe::Eq(value) => stats::eq_share(field, entries, &idx.key_of(value)?),
+            Probe::Range(start, end) => stats::range_share(field, entries, number(start), number(end)),
+        }
+    }
+
+    /// Document paths the probe selects
+    pub fn candidates(&self, idx: &BTreeIndex) -> Vec<String> {
+        match self {
+            Probe::Eq(value) => idx.find(value).cloned().unwrap_or_default(),
+            Probe::Range(start, end) => idx.range(start.as_ref(), end.as_ref(), usize::MAX),
+        }
+    }
+}
+
+/// A query answered from an index
+pub struct Plan {
+    pub index: String,
+    pub candidates: Vec<String>,
+    /// Expected share of the collection, when it was analyzed
+    pub estimate: Option<f64>,
+}
@@ -0,0 +1,425 @@
+//! String queries
+//!
+//! A deliberately small grammar for typing queries in a console:
+//!
+//! ```text
+//! query      := [expr] [ORDER BY key {, key}] [LIMIT n] [OFFSET n]
+//! key        := field [ASC | DESC]
+//! expr       := and {OR and}
+//! and        := unary {AND unary}
+//! unary      := NOT unary | ( expr ) | comparison
+//! comparison := field (= | != | <> | > | >= | < | <=) value
+//!             | field [NOT] IN list
+//!             | field CONTAINS [ANY | ALL] value
+//! value      := "string" | 'string' | number | true | false | null | list
+//! list       := [ [value {, value}] ]
+//! ```
+//!
+//! Keywords are case-insensitive; fields are dotted paths. Comparisons become
+//! the ordinary `QueryFilter`s, so a string query matches exactly what the
+//! equivalent `parallel_query` filters would.
+
+use serde_json::{json, Number, Value};
+
+use crate::error::DbError;
+use crate::QueryFilter;
+
+/// Boolean combination of filters
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Filter(QueryFilter),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    pub fn to_json(&self) -> Value {
+        match self {
+            Expr::Filter(f) => json!({ "field": f.field, "op": f.op, "value": f.value }),
+            Expr::And(items) => json!({ "and": items.iter().map(Expr::to_json).collect::<Vec<_>>() }),
+            Expr::Or(items) => json!({ "or": items.iter().map(Expr::to_json).collect::<Vec<_>>() }),
+            Expr::Not(inner) => json!({ "not": inner.to_json() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParsedQuery {
+    /// None matches every document
+    pub filter: Option<Expr>,
+    pub order_by: Vec<SortKey>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl ParsedQuery {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "where": self.filter.as_ref().map(Expr::to_json),
+            "orderBy": self.order_by.iter()
+                .map(|k| json!({ "field": k.field, "direction": if k.descending { "desc" } else { "asc" } }))
+                .collect::<Vec<_>>(),
+            "limit": self.limit,
+            "offset": self.offset,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Num(Number),
+    Op(&'static str),
+    Open,
+    Close,
+    OpenList,
+    CloseList,
+    Comma,
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => format!("'{}'", w),
+            Token::Str(s) => format!("string {:?}", s),
+            Token::Num(n) => format!("number {}", n),
+            Token::Op(op) => format!("'{}'", op),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+            Token::OpenList => "'['".to_string(),
+            Token::CloseList => "']'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::End => "end of query".to_string(),
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "AND", "OR", "NOT", "IN", "CONTAINS", "ANY", "ALL", "ORDER", "BY", "ASC", "DESC", "LIMIT", "OFFSET",
+];
+
+fn error(pos: usize, message: String) -> DbError {
+    DbError::InvalidArgument(format!("Query parse error at position {}: {}", pos, message))
+}
+
+/// Tokens with the character position each starts at
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, DbError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let token = match c {
+            '(' => { i += 1; Token::Open }
+            ')' => { i += 1; Token::Close }
+            '[' => { i += 1; Token::OpenList }
+            ']' => { i += 1; Token::CloseList }
+            ',' => { i += 1; Token::Comma }
+            '"' | '\'' => {
+                let (text, end) = read_string(&chars, i)?;
+                i = end;
+                Token::Str(text)
+            }
+            '=' => {
+                i += if chars.get(i + 1) == Some(&'=') { 2 } else { 1 };
+                Token::Op("=")
+            }
+            '!' if chars.get(i + 1) == Some(&'=') => { i += 2; Token::Op("!=") }
+            '<' if chars.get(i + 1) == Some(&'>') => { i += 2; Token::Op("!=") }
+            '<' | '>' => {
+                let with_eq = chars.get(i + 1) == Some(&'=');
+                i += if with_eq { 2 } else { 1 };
+                Token::Op(match (c, with_eq) {
+                    ('<', false) => "<",
+                    ('<', true) => "<=",
+                    ('>', false) => ">",
+                    _ => ">=",
+                })
+            }
+            '-' | '0'..='9' => {
+                let (number, end) = read_number(&chars, i)?;
+                i = end;
+                Token::Num(number)
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$' | '.')) {
+                    i += 1;
+                }
+                Token::Word(chars[start..i].iter().collect())
+            }
+            other => return Err(error(start, format!("unexpected character {:?}", other))),
+        };
+        tokens.push((token, start));
+    }
+    tokens.push((Token::End, chars.len()));
+    Ok(tokens)
+}
+
+/// A quoted string starting at `start`; the same quote is escaped with a backslash
+fn read_string(chars: &[char], start: usize) -> Result<(String, usize), DbError> {
+    let quote = chars[start];
+    let mut text = String::new();
+    let mut i = start + 1;
+    loop {
+        match chars.get(i) {
+            None => return Err(error(start, format!("unterminated string: expected closing {}", quote))),
+            Some(&c) if c == quote => return Ok((text, i + 1)),
+            Some('\\') => {
+                let escaped = match chars.get(i + 1) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('u') => {
+                        let hex: String = chars.iter().skip(i + 2).take(4).collect();
+                        let code = u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4).and_then(char::from_u32);
+                        let Some(c) = code else {
+                            return Err(error(i, "expected 4 hex digits after \\u".to_string()));
+                        };
+                        text.push(c);
+                        i += 6;
+                        continue;
+                    }
+                    Some(&c @ ('"' | '\'' | '\\' | '/')) => c,
+                    Some(c) => return Err(error(i, format!("unknown escape \\{}: expected one of \\\" \\' \\\\ \\/ \\n \\t \\r \\u", c))),
+                    None => return Err(error(start, format!("unterminated string: expected closing {}", quote))),
+                };
+                text.push(escaped);
+                i += 2;
+            }
+            Some(&c) => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn read_number(chars: &[char], start: usize) -> Result<(Number, usize), DbError> {
+    let mut i = start;
+    if chars[i] == '-' {
+        i += 1;
+    }
+    while i < chars.len() && (chars[i].is_ascii_digit() || matches!(chars[i], '.' | 'e' | 'E')
+        || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+    {
+        i += 1;
+    }
+    let text: String = chars[start..i].iter().collect();
+    match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Number(n)) => Ok((n, i)),
+        _ => Err(error(start, format!("malformed number {:?}", text))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.at].0
+    }
+
+    fn pos(&self) -> usize {
+        self.tokens[self.at].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.at].0.clone();
+        if token != Token::End {
+            self.at += 1;
+        }
+        token
+    }
+
+    fn expected(&self, what: &str) -> DbError {
+        error(self.pos(), format!("expected {}, found {}", what, self.peek().describe()))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), DbError> {
+        if self.eat_keyword(keyword) { Ok(()) } else { Err(self.expected(keyword)) }
+    }
+
+    fn query(&mut self) -> Result<ParsedQuery, DbError> {
+        let mut parsed = ParsedQuery::default();
+        if !matches!(self.peek(), Token::End) && !self.is_keyword("ORDER") && !self.is_keyword("LIMIT") && !self.is_keyword("OFFSET") {
+            parsed.filter = Some(self.or()?);
+        }
+        if self.eat_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let field = self.field()?;
+                let descending = if self.eat_keyword("DESC") { true } else { self.eat_keyword("ASC"); false };
+                parsed.order_by.push(SortKey { field, descending });
+                if *self.peek() != Token::Comma {
+                    break;
+                }
+                self.next();
+            }
+        }
+        if self.eat_keyword("LIMIT") {
+            parsed.limit = Some(self.count("LIMIT")?);
+        }
+        if self.eat_keyword("OFFSET") {
+            parsed.offset = Some(self.count("OFFSET")?);
+        }
+        if *self.peek() != Token::End {
+            let hint = if parsed.filter.is_some() { "AND, OR, ORDER BY, LIMIT, OFFSET or end of query" } else { "a condition, ORDER BY, LIMIT, OFFSET or end of query" };
+            return Err(self.expected(hint));
+        }
+        Ok(parsed)
+    }
+
+    fn or(&mut self) -> Result<Expr, DbError> {
+        let mut items = vec![self.and()?];
+        while self.eat_keyword("OR") {
+            items.push(self.and()?);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Expr::Or(items) })
+    }
+
+    fn and(&mut self) -> Result<Expr, DbError> {
+        let mut items = vec![self.unary()?];
+        while self.eat_keyword("AND") {
+            items.push(self.unary()?);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Expr::And(items) })
+    }
+
+    fn unary(&mut self) -> Result<Expr, DbError> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if *self.peek() == Token::Open {
+            self.next();
+            let inner = self.or()?;
+            if *self.peek() != Token::Close {
+                return Err(self.expected("')' or AND/OR"));
+            }
+            self.next();
+            return Ok(inner);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, DbError> {
+        let field = self.field()?;
+        let filter = |op: &str, value: Value| Expr::Filter(QueryFilter { field: field.clone(), op: op.to_string(), value, missing: None, equality: None });
+        if let Token::Op(op) = *self.peek() {
+            self.next();
+            let value = self.value()?;
+            return Ok(filter(match op {
+                "=" => "eq",
+                "!=" => "ne",
+                ">" => "gt",
+                ">=" => "gte",
+                "<" => "lt",
+                _ => "lte",
+            }, value));
+        }
+        if self.eat_keyword("NOT") {
+            self.expect_keyword("IN")?;
+            return Ok(filter("notin", self.list()?));
+        }
+        if self.eat_keyword("IN") {
+            return Ok(filter("in", self.list()?));
+        }
+        if self.eat_keyword("CONTAINS") {
+            if self.eat_keyword("ANY") {
+                return Ok(filter("containsAny", self.list()?));
+            }
+            if self.eat_keyword("ALL") {
+                return Ok(filter("containsAll", self.list()?));
+            }
+            return Ok(filter("contains", self.value()?));
+        }
+        Err(self.expected("a comparison operator (=, !=, <>, >, >=, <, <=), IN, NOT IN or CONTAINS"))
+    }
+
+    fn field(&mut self) -> Result<String, DbError> {
+        match self.peek() {
+            Token::Word(w) if !KEYWORDS.iter().any(|k| w.eq_ignore_ascii_case(k))
+                && !w.starts_with('.') && !w.ends_with('.') && !w.contains("..") =>
+            {
+                let field = w.clone();
+                self.next();
+                Ok(field)
+            }
+            _ => Err(self.expected("a field name")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, DbError> {
+        match self.peek().clone() {
+            Token::Str(s) => { self.next(); Ok(Value::String(s)) }
+            Token::Num(n) => { self.next(); Ok(Value::Number(n)) }
+            Token::OpenList => self.list(),
+            Token::Word(w) if w.eq_ignore_ascii_case("true") => { self.next(); Ok(Value::Bool(true)) }
+            Token::Word(w) if w.eq_ignore_ascii_case("false") => { self.next(); Ok(Value::Bool(false)) }
+            Token::Word(w) if w.eq_ignore_ascii_case("null") => { self.next(); Ok(Value::Null) }
+            _ => Err(self.expected("a value (string, number, true, false, null or [list])")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Value, DbError> {
+        if *self.peek() != Token::OpenList {
+            return Err(self.expected("'[' starting a list"));
+        }
+        self.next();
+        let mut items = Vec::new();
+        if *self.peek() == Token::CloseList {
+            self.next();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Token::Comma => { self.next(); }
+                Token::CloseList => { self.next(); return Ok(Value::Array(items)); }
+                _ => return Err(self.expected("',' or ']'")),
+            }
+        }
+    }
+
+    fn count(&mut self, clause: &str) -> Result<usize, DbError> {
+        match self.peek() {
+            Token::Num(n) if n.as_u64().is_some() => {
+                let n = n.as_u64().unwrap_or(0) as usize;
+                self.next();
+                Ok(n)
+            }
+            _ => Err(self.expected(&format!("a non-negative integer after {}", clause))),
+        }
+    }
+}
+
+pub fn parse(query: &str) -> Result<ParsedQuery, DbError> {
+    Parser { tokens: tokenize(query)?, at: 0 }.query()
+}
@@ -0,0 +1,367 @@
+//! Collection quotas
+//!
+//! A quota caps the entry count and/or approximate byte size of one collection.
+//! Usage of an object collection is maintained per document the way a counter
+//! is: writes mark the touched keys dirty and the next check re-measures only
+//! those. Array collections are measured directly, since every array write
+//! already copies the array.
+
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{approx_size, paths_overlap, resolve_field, split_field};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum QuotaPolicy {
+    /// Writes that would exceed the quota fail
+    Reject,
+    /// Writes evict the oldest entries until the collection fits
+    EvictOldest,
+}
+
+impl QuotaPolicy {
+    pub fn parse(policy: &str) -> Option<Self> {
+        match policy {
+            "reject" => Some(QuotaPolicy::Reject),
+            "evict_oldest" => Some(QuotaPolicy::EvictOldest),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaPolicy::Reject => "reject",
+            QuotaPolicy::EvictOldest => "evict_oldest",
+        }
+    }
+}
+
+/// Eviction order of an entry, oldest first. Entries without a usable order
+/// field value come first, then numbers, then strings (ISO dates sort as text).
+#[derive(Clone, Debug)]
+pub enum Rank {
+    Missing,
+    Number(f64),
+    Text(String),
+    /// Insertion sequence, when the quota has no order field
+    Seq(u64),
+}
+
+impl Rank {
+    fn class(&self) -> u8 {
+        match self {
+            Rank::Missing => 0,
+            Rank::Number(_) => 1,
+            Rank::Text(_) => 2,
+            Rank::Seq(_) => 3,
+        }
+    }
+
+    fn seq(&self) -> Option<u64> {
+        match self {
+            Rank::Seq(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl Ord for Rank {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Rank::Number(a), Rank::Number(b)) => a.total_cmp(b),
+            (Rank::Text(a), Rank::Text(b)) => a.cmp(b),
+            (Rank::Seq(a), Rank::Seq(b)) => a.cmp(b),
+            _ => self.class().cmp(&other.class()),
+        }
+    }
+}
+
+impl PartialOrd for Rank {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Rank {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Rank {}
+
+struct Entry {
+    size: usize,
+    rank: Rank,
+}
+
+pub struct Quota {
+    pub collection: String,
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<usize>,
+    pub policy: QuotaPolicy,
+    pub order_field: Option<String>,
+    order_parts: Option<Vec<String>>,
+    /// Tracked documents of an object collection
+    entries: HashMap<String, Entry>,
+    ranked: BTreeSet<(Rank, String)>,
+    bytes: usize,
+    next_seq: u64,
+    /// Keys written since the last refresh
+    dirty: HashSet<String>,
+    /// The collection itself (or an ancestor) was replaced
+    rescan: bool,
+    pub rejected: u64,
+    pub evicted: u64,
+}
+
+impl Quota {
+    pub fn new(
+        collection: String,
+        max_entries: Option<usize>,
+        max_bytes: Option<usize>,
+        policy: QuotaPolicy,
+        order_field: Option<String>,
+    ) -> Self {
+        Quota {
+            collection,
+            max_entries,
+            max_bytes,
+            policy,
+            order_parts: order_field.as_deref().map(split_field),
+            order_field,
+            entries: HashMap::new(),
+            ranked: BTreeSet::new(),
+            bytes: 0,
+            next_seq: 0,
+            dirty: HashSet::new(),
+            rescan: true,
+            rejected: 0,
+            evicted: 0,
+        }
+    }
+
+    /// Restore a quota persisted by `to_stored`, seeding the insertion order
+    pub fn from_stored(collection: &str, stored: &Value) -> Option<Self> {
+        let limit = |key: &str| stored.get(key).and_then(Value::as_u64).map(|n| n as usize);
+        let policy = QuotaPolicy::parse(stored.get("policy")?.as_str()?)?;
+        let order_field = stored.get("orderField").and_then(Value::as_str).map(String::from);
+        let mut quota = Quota::new(collection.to_string(), limit("maxEntries"), limit("maxBytes"), policy, order_field);
+        if let Some(order) = stored.get("order").and_then(Value::as_array) {
+            for key in order.iter().filter_map(Value::as_str) {
+                let rank = Rank::Seq(quota.next_seq);
+                quota.next_seq += 1;
+                quota.entries.insert(key.to_string(), Entry { size: 0, rank });
+            }
+        }
+        Some(quota)
+    }
+
+    pub fn config(&self) -> Value {
+        json!({
+            "maxEntries": self.max_entries,
+            "maxBytes": self.max_bytes,
+            "policy": self.policy.as_str(),
+            "orderField": self.order_field,
+        })
+    }
+
+    /// Config plus the insertion order, so eviction order survives a restart
+    pub fn to_stored(&self) -> Value {
+        let mut stored = self.config();
+        if self.order_parts.is_none() && !self.entries.is_empty() {
+            let order: Vec<&str> = self.ranked.iter().map(|(_, key)| key.as_str()).collect();
+            stored["order"] = json!(order);
+        }
+        stored
+    }
+
+    /// Record a write at `path`
+    pub fn note_write(&mut self, path: &str) {
+        if !paths_overlap(&self.collection, path) {
+            return;
+        }
+        match path.strip_prefix(self.collection.as_str()).and_then(|rest| rest.strip_prefix('.')) {
+            Some(rest) => {
+                let key = rest.split('.').next().unwrap_or(rest);
+                self.dirty.insert(key.to_string());
+            }
+            None => self.rescan = true,
+        }
+    }
+
+    /// Bring the tracked usage up to date with `collection` (the current value
+    /// at the collection path). Only object collections are tracked.
+    pub fn refresh(&mut self, collection: Option<&Value>) {
+        if !self.rescan && self.dirty.is_empty() {
+            return;
+        }
+        match collection {
+            Some(Value::Object(map)) if !self.rescan => {
+                for key in std::mem::take(&mut self.dirty) {
+                    let seq = self.untrack(&key);
+                    if let Some(doc) = map.get(&key) {
+                        self.track(key, doc, seq);
+                    }
+                }
+            }
+            Some(Value::Object(map)) => {
+                let previous = std::mem::take(&mut self.entries);
+                self.ranked.clear();
+                self.bytes = 0;
+                // Keys already seen keep their place in the insertion order
+                let (mut known, new): (Vec<_>, Vec<_>) = map.iter()
+                    .partition(|(key, _)| previous.get(*key).is_some_and(|e| e.rank.seq().is_some()));
+                known.sort_by_key(|(key, _)| previous[*key].rank.seq());
+                self.next_seq = 0;
+                for (key, doc) in known.into_iter().chain(new) {
+                    self.track(key.clone(), doc, None);
+                }
+            }
+            _ => {
+                self.entries.clear();
+                self.ranked.clear();
+                self.bytes = 0;
+            }
+        }
+        self.dirty.clear();
+        self.rescan = false;
+    }
+
+    fn track(&mut self, key: String, doc: &Value, seq: Option<u64>) {
+        let rank = match &self.order_parts {
+            Some(parts) => Self::rank_of(resolve_field(doc, parts).as_deref()),
+            None => Rank::Seq(seq.unwrap_or_else(|| {
+                self.next_seq += 1;
+                self.next_seq - 1
+            })),
+        };
+        let size = Self::entry_size(&key, doc);
+        self.bytes += size;
+        self.ranked.insert((rank.clone(), key.clone()));
+        self.entries.insert(key, Entry { size, rank });
+    }
+
+    /// Stop tracking `key`, returning its insertion sequence
+    fn untrack(&mut self, key: &str) -> Option<u64> {
+        let entry = self.entries.remove(key)?;
+        self.bytes -= entry.size;
+        self.ranked.remove(&(entry.rank.clone(), key.to_string()));
+        entry.rank.seq()
+    }
+
+    /// Tracked (entries, bytes) of an object collection
+    pub fn usage(&self) -> (usize, usize) {
+        (self.entries.len(), self.bytes)
+    }
+
+    pub fn size_of(&self, key: &str) -> Option<usize> {
+        self.entries.get(key).map(|e| e.size)
+    }
+
+    /// Approximate bytes one entry adds to its collection
+    pub fn entry_size(key: &str, doc: &Value) -> usize {
+        24 + key.len() + approx_size(doc)
+    }
+
+    pub fn exceeds(&self, entries: usize, bytes: usize) -> bool {
+        self.max_entries.is_some_and(|max| entries > max) || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+
+    fn rank_of(value: Option<&Value>) -> Rank {
+        match value {
+            Some(Value::Number(n)) => n.as_f64().map(Rank::Number).unwrap_or(Rank::Missing),
+            Some(Value::String(s)) => Rank::Text(s.clone()),
+            _ => Rank::Missing,
+        }
+    }
+
+    /// Oldest tracked keys (other than `keep`) whose removal brings a collection
+    /// of `entries` / `bytes` within the limits; None if that is not possible
+    pub fn victims(&self, mut entries: usize, mut bytes: usize, keep: &str) -> Option<Vec<String>> {
+        let mut victims = Vec::new();
+        let mut oldest = self.ranked.iter().filter(|(_, key)| key != keep);
+        while self.exceeds(entries, bytes) {
+            let (_, key) = oldest.next()?;
+            entries -= 1;
+            bytes = bytes.saturating_sub(self.entries[key].size);
+            victims.push(key.clone());
+        }
+        Some(victims)
+    }
+
+    /// Remove the oldest entries of an incoming collection value until it fits,
+    /// never removing the array indexes in `keep`. Returns how many were removed,
+    /// or None if it cannot fit.
+    pub fn trim(&self, collection: &mut Value, keep: &[usize]) -> Option<usize> {
+        let mut candidates: Vec<(Rank, String, usize)> = match &*collection {
+            Value::Object(map) => map.iter()
+                .map(|(key, doc)| {
+                    let rank = match &self.order_parts {
+                        Some(parts) => Self::rank_of(resolve_field(doc, parts).as_deref()),
+                        // Keys not seen before are newer than every tracked one
+                        None => self.entries.get(key).map(|e| e.rank.clone()).unwrap_or(Rank::Seq(u64::MAX)),
+                    };
+                    (rank, key.clone(), Self::entry_size(key, doc))
+                })
+                .collect(),
+            Value::Array(arr) => arr.iter()
+                .enumerate()
+                .filter(|(i, _)| !keep.contains(i))
+                .map(|(i, doc)| {
+                    let rank = match &self.order_parts {
+                        Some(parts) => Self::rank_of(resolve_field(doc, parts).as_deref()),
+                        None => Rank::Seq(i as u64),
+                    };
+                    (rank, i.to_string(), approx_size(doc))
+                })
+                .collect(),
+            _ => return Some(0),
+        };
+        candidates.sort();
+
+        let (mut entries, mut bytes) = Self::measure(collection);
+        let mut doomed = Vec::new();
+        let mut oldest = candidates.into_iter();
+        while self.exceeds(entries, bytes) {
+            let (_, key, size) = oldest.next()?;
+            entries -= 1;
+            bytes = bytes.saturating_sub(size);
+            doomed.push(key);
+        }
+        match collection {
+            Value::Object(map) => doomed.iter().for_each(|key| { map.shift_remove(key); }),
+            Value::Array(arr) => {
+                let doomed: HashSet<usize> = doomed.iter().filter_map(|i| i.parse().ok()).collect();
+                let mut index = 0;
+                arr.retain(|_| {
+                    index += 1;
+                    !doomed.contains(&(index - 1))
+                });
+            }
+            _ => {}
+        }
+        Some(doomed.len())
+    }
+
+    /// (entries, bytes) of a collection value, measured directly
+    pub fn measure(collection: &Value) -> (usize, usize) {
+        match collection {
+            Value::Object(map) => (map.len(), map.iter().map(|(k, doc)| Self::entry_size(k, doc)).sum()),
+            Value::Array(arr) => (arr.len(), arr.iter().map(approx_size).sum()),
+            _ => (0, 0),
+        }
+    }
+
+    pub fn status(&self, entries: usize, bytes: usize) -> Value {
+        let mut status = self.config();
+        status["collection"] = json!(self.collection);
+        status["entries"] = json!(entries);
+        status["bytes"] = json!(bytes);
+        status["exceeded"] = json!(self.exceeds(entries, bytes));
+        status["rejected"] = json!(self.rejected);
+        status["evicted"] = json!(self.evicted);
+        status
+    }
+}
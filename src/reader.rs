@@ -0,0 +1,163 @@
+//! Read replicas
+//!
+//! A `ReaderHandle` shares the primary's data and indexes instead of copying
+//! them: queries take the same read locks as the primary's own reads and see
+//! its writes as they land. A handle has no WAL and no process lock and
+//! exposes only reads, so it cannot write. Worker threads cannot receive a
+//! napi object, so the primary registers its shared state under a token that
+//! a worker passes to `ReaderHandle.open`. Open handles keep the data alive
+//! after the primary closes. While the primary has an exclusive transaction
+//! open, `get`, `has` and queries see the data as it was before it.
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock as PLRwLock};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use crate::btree::BTreeIndex;
+use crate::canonical::Equality;
+use crate::error::DbError;
+use crate::lazy::LazyCollections;
+use crate::schema::CompiledSchema;
+use crate::upkeep::Upkeep;
+use crate::{DBOptions, IndexKeysOptions, IndexRangeOptions, NativeDB, PageOptions, QueryFilter, QueryOptions, ReadOptions};
+
+/// What a reader shares with its primary
+#[derive(Clone)]
+pub struct ReaderSource {
+    pub path: String,
+    pub data: Arc<PLRwLock<Value>>,
+    pub indexes: Arc<PLRwLock<HashMap<String, BTreeIndex>>>,
+    /// Readers bring tracked indexes up to date like the primary
+    pub index_upkeep: Arc<Mutex<HashMap<String, Upkeep>>>,
+    pub schemas: Arc<PLRwLock<HashMap<String, CompiledSchema>>>,
+    pub options: DBOptions,
+    pub max_result_bytes: usize,
+    pub equality: Equality,
+    /// Readers load lazy entries into the shared tree like the primary
+    pub lazy: Option<Arc<LazyCollections>>,
+    /// Readers see the data as committed while an exclusive transaction is open
+    pub transactions: Arc<Mutex<crate::Transactions>>,
+}
+
+/// Sources registered by `reader_token`, until their primary closes
+static SOURCES: Lazy<Mutex<HashMap<u32, ReaderSource>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_TOKEN: AtomicU32 = AtomicU32::new(1);
+
+pub fn register(source: ReaderSource) -> u32 {
+    let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    SOURCES.lock().insert(token, source);
+    token
+}
+
+pub fn unregister(token: u32) {
+    SOURCES.lock().remove(&token);
+}
+
+/// Read-only view of a database opened in this process
+#[napi]
+pub struct ReaderHandle {
+    db: NativeDB,
+}
+
+impl ReaderHandle {
+    pub fn new(source: &ReaderSource) -> Self {
+        ReaderHandle { db: NativeDB::reader(source) }
+    }
+}
+
+#[napi]
+impl ReaderHandle {
+    /// Open a reader from a token returned by the primary's `reader_token`,
+    /// typically in a worker thread
+    #[napi(factory)]
+    pub fn open(token: u32) -> Result<Self> {
+        let source = SOURCES.lock().get(&token).cloned().ok_or_else(|| {
+            DbError::NotFound(format!("Unknown reader token {}: the primary may have closed", token))
+        })?;
+        Ok(Self::new(&source))
+    }
+
+    #[napi]
+    pub fn get(&self, path: String, options: Option<ReadOptions>) -> Result<Value> {
+        self.db.get(path, options)
+    }
+
+    #[napi]
+    pub fn get_serialized(&self, path: String, options: Option<ReadOptions>) -> Result<String> {
+        self.db.get_serialized(path, options)
+    }
+
+    #[napi]
+    pub fn has(&self, path: String) -> Result<bool> {
+        self.db.has(path, None)
+    }
+
+    #[napi]
+    pub fn parallel_query(&self, path: String, filters: Vec<QueryFilter>, options: Option<QueryOptions>) -> Result<Value> {
+        self.db.parallel_query(path, filters, options)
+    }
+
+    #[napi]
+    pub fn query_string(&self, path: String, query: String) -> Result<Value> {
+        self.db.query_string(path, query)
+    }
+
+    #[napi]
+    pub fn get_page(&self, path: String, options: Option<PageOptions>) -> Result<Value> {
+        self.db.get_page(path, options)
+    }
+
+    #[napi]
+    pub fn parallel_aggregate(&self, path: String, operation: String, field: Option<String>) -> Result<Value> {
+        self.db.parallel_aggregate(path, operation, field)
+    }
+
+    #[napi]
+    pub fn parallel_lookup(
+        &self,
+        left_path: String,
+        right_path: String,
+        left_field: String,
+        right_field: String,
+        as_field: String,
+    ) -> Result<Value> {
+        self.db.parallel_lookup(left_path, right_path, left_field, right_field, as_field)
+    }
+
+    #[napi]
+    pub fn find_index_paths(&self, name: String, key: Value) -> Result<Vec<String>> {
+        self.db.find_index_paths(name, key)
+    }
+
+    #[napi]
+    pub fn find_index_range(&self, name: String, start: Option<Value>, end: Option<Value>, options: Option<IndexRangeOptions>) -> Result<Vec<String>> {
+        self.db.find_index_range(name, start, end, options)
+    }
+
+    #[napi]
+    pub fn index_keys(&self, name: String, options: Option<IndexKeysOptions>) -> Result<Value> {
+        self.db.index_keys(name, options)
+    }
+
+    #[napi]
+    pub fn index_entries(&self, name: String, options: Option<IndexKeysOptions>) -> Result<Value> {
+        self.db.index_entries(name, options)
+    }
+
+    #[napi]
+    pub fn index_aggregate(
+        &self,
+        name: String,
+        operation: String,
+        start: Option<Value>,
+        end: Option<Value>,
+        bucket: Option<String>,
+    ) -> Result<Value> {
+        self.db.index_aggregate(name, operation, start, end, bucket)
+    }
+}
@@ -0,0 +1,49 @@
+//! Memory reclamation
+//!
+//! serde_json keeps the capacity of strings and arrays after they shrink, and
+//! maps emptied by deletes leave sparse B-tree nodes behind. Rebuilding a
+//! subtree moves every value into exactly-sized allocations so the freed
+//! memory can go back to the allocator.
+
+use serde_json::{Map, Value};
+use std::mem::size_of;
+
+/// Rebuild `value` with exact capacities
+pub fn compact(value: Value) -> Value {
+    match value {
+        Value::String(mut s) => {
+            s.shrink_to_fit();
+            Value::String(s)
+        }
+        Value::Array(arr) => {
+            // Collecting from `into_iter` reuses the old buffer, so shrink it too
+            let mut arr: Vec<Value> = arr.into_iter().map(compact).collect();
+            arr.shrink_to_fit();
+            Value::Array(arr)
+        }
+        Value::Object(map) => Value::Object(shrink_map(map, compact)),
+        other => other,
+    }
+}
+
+/// Rebuild a map's nodes (and shrink its keys), mapping each value through `f`
+pub fn shrink_map(map: Map<String, Value>, f: impl Fn(Value) -> Value) -> Map<String, Value> {
+    map.into_iter()
+        .map(|(mut k, v)| {
+            k.shrink_to_fit();
+            (k, f(v))
+        })
+        .collect()
+}
+
+/// Estimated heap bytes held by `value`, counting spare capacity
+pub fn allocated_size(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.capacity(),
+        Value::Array(arr) => arr.capacity() * size_of::<Value>() + arr.iter().map(allocated_size).sum::<usize>(),
+        Value::Object(map) => map.iter()
+            .map(|(k, v)| size_of::<String>() + size_of::<Value>() + k.capacity() + allocated_size(v))
+            .sum(),
+        _ => 0,
+    }
+}
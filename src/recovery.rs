@@ -0,0 +1,170 @@
+//! Deferred WAL recovery
+//!
+//! A database opened with `defer_recovery` holds only its base file until the
+//! WAL is replayed explicitly, possibly on another thread. The replay records
+//! its progress here and leaves its outcome to be installed by the next call
+//! into the database, since installing it (reloading counters, quotas and view
+//! watches from the recovered data) needs the database itself.
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::error::DbError;
+use crate::trace::Span;
+use crate::wal::{RecoveryStats, ReplayProgress};
+
+/// What replaying the WAL (or a legacy line-based `.wal`) produced
+#[derive(Debug, Default)]
+pub struct Replayed {
+    pub stats: Option<RecoveryStats>,
+    pub error: Option<String>,
+    pub legacy: bool,
+}
+
+impl Replayed {
+    /// Whether any write was replayed on top of the base file
+    pub fn changed(&self) -> bool {
+        self.legacy || self.stats.as_ref().is_some_and(|stats| stats.ops_applied > 0)
+    }
+
+    /// Records read as scanned, operations applied as returned
+    pub fn span(&self) -> Span {
+        Span {
+            scanned: self.stats.as_ref().map(|stats| stats.ops_read as usize),
+            returned: self.stats.as_ref().map(|stats| stats.ops_applied as usize),
+            parallel: self.stats.as_ref().is_some_and(|stats| stats.parallel),
+        }
+    }
+
+    pub fn to_json(&self, wal_enabled: bool) -> Value {
+        match (&self.stats, &self.error) {
+            (Some(stats), _) => json!({
+                "enabled": true,
+                "walRecords": stats.ops_read,
+                "replayedOps": stats.ops_applied,
+                "compactedOps": stats.ops_read - stats.ops_applied - stats.rejected_paths - stats.uncommitted_ops - stats.txn_markers,
+                "firstLsn": stats.first_lsn,
+                "lastLsn": stats.last_lsn,
+                "skippedRecords": stats.stopped_early.is_some(),
+                "stopReason": stats.stopped_early,
+                "rejectedPaths": stats.rejected_paths,
+                "uncommittedOps": stats.uncommitted_ops,
+                "transactionMarkers": stats.txn_markers,
+                "apply": {
+                    "parallel": stats.parallel,
+                    "groups": stats.groups,
+                    "barriers": stats.barriers,
+                    "readMs": stats.read_ms,
+                    "applyMs": stats.apply_ms,
+                    // Apply time a sequential replay would have needed, over the time taken
+                    "speedup": if stats.apply_ms > 0.0 { stats.group_ms / stats.apply_ms } else { 1.0 },
+                },
+            }),
+            (None, Some(err)) => json!({ "enabled": wal_enabled, "error": err }),
+            (None, None) => json!({ "enabled": false, "legacyReplayed": self.legacy }),
+        }
+    }
+}
+
+enum Phase {
+    Pending,
+    Running,
+    /// Finished but not yet installed
+    Replayed(Replayed),
+    Done,
+}
+
+pub struct Recovery {
+    ready: AtomicBool,
+    phase: Mutex<Phase>,
+    ops_replayed: AtomicU64,
+    bytes_read: AtomicU64,
+    total_bytes: AtomicU64,
+}
+
+impl Recovery {
+    pub fn new(deferred: bool) -> Self {
+        Recovery {
+            ready: AtomicBool::new(!deferred),
+            phase: Mutex::new(if deferred { Phase::Pending } else { Phase::Done }),
+            ops_replayed: AtomicU64::new(0),
+            bytes_read: AtomicU64::new(0),
+            total_bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Claim the replay. Ok(false) if it has already run.
+    pub fn start(&self) -> Result<bool, DbError> {
+        let mut phase = self.phase.lock();
+        match *phase {
+            Phase::Pending => {
+                *phase = Phase::Running;
+                Ok(true)
+            }
+            Phase::Running => Err(DbError::Conflict("WAL recovery is already running".to_string())),
+            _ => Ok(false),
+        }
+    }
+
+    pub fn report(&self, progress: ReplayProgress) {
+        self.ops_replayed.store(progress.ops_replayed, Ordering::Relaxed);
+        self.bytes_read.store(progress.bytes_read, Ordering::Relaxed);
+        self.total_bytes.store(progress.total_bytes, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self, replayed: Replayed) {
+        *self.phase.lock() = Phase::Replayed(replayed);
+    }
+
+    /// True once recovery is complete, installing a finished replay with
+    /// `install` first if there is one
+    pub fn complete(&self, install: impl FnOnce(Replayed)) -> bool {
+        if self.ready.load(Ordering::Acquire) {
+            return true;
+        }
+        let mut phase = self.phase.lock();
+        match std::mem::replace(&mut *phase, Phase::Done) {
+            Phase::Replayed(replayed) => install(replayed),
+            Phase::Done => {}
+            other => {
+                *phase = other;
+                return false;
+            }
+        }
+        self.ready.store(true, Ordering::Release);
+        true
+    }
+
+    /// Callers are rejected with this until `complete` returns true
+    pub fn pending_error(&self) -> DbError {
+        let reason = match *self.phase.lock() {
+            Phase::Running => "WAL recovery is still running",
+            _ => "WAL recovery was deferred; call recover() or recoverAsync() first",
+        };
+        DbError::RecoveryPending(reason.to_string())
+    }
+
+    pub fn progress_json(progress: ReplayProgress) -> Value {
+        json!({
+            "opsReplayed": progress.ops_replayed,
+            "bytesRead": progress.bytes_read,
+            "totalBytes": progress.total_bytes,
+        })
+    }
+
+    pub fn status(&self) -> Value {
+        let state = match *self.phase.lock() {
+            Phase::Pending => "pending",
+            Phase::Running => "running",
+            Phase::Replayed(_) | Phase::Done => "done",
+        };
+        let mut status = Self::progress_json(ReplayProgress {
+            ops_replayed: self.ops_replayed.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+        });
+        status["state"] = json!(state);
+        status
+    }
+}
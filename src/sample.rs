@@ -0,0 +1,237 @@
+//! Sample values generated from a schema
+//!
+//! `generate` builds a value a schema accepts: the `default` where one is
+//! declared, else the first `enum` member, else a value of the schema's type
+//! (inferred from its keywords when `type` is absent) at the low end of its
+//! constraints: the smallest allowed number, `minLength` characters or an
+//! example of a known `format`, `minItems` generated items, and every
+//! required property. `allOf` branches are merged in; when that is not
+//! enough, each `oneOf`/`anyOf` branch and then `if`+`then` or `else` is
+//! merged in turn until a candidate passes the whole schema.
+//!
+//! Strings are not built to match a `pattern`; give such a property a
+//! `default` or `enum`. If no candidate passes, the last one is returned as is.
+
+use serde_json::{json, Map, Value};
+
+use crate::schema::{validate, Schema, SchemaType};
+
+/// A value `schema` accepts, as far as one can be found
+pub fn generate(schema: &Schema) -> Value {
+    let passes = |value: &Value| validate(value, schema).is_ok();
+    if let Some(default) = schema.default.as_ref().filter(|value| passes(value)) {
+        return default.clone();
+    }
+    if let Some(members) = &schema.r#enum {
+        if let Some(member) = members.iter().find(|value| passes(value)).or(members.first()) {
+            return member.clone();
+        }
+    }
+
+    let base = without_branches(schema);
+    let plain = build(&base);
+    if passes(&plain) {
+        return plain;
+    }
+    let unions: Vec<Option<&Schema>> = match (&schema.one_of, &schema.any_of) {
+        (None, None) => vec![None],
+        (one, any) => one.iter().chain(any).flatten().map(Some).collect(),
+    };
+    let mut conditions = vec![None];
+    if let Some(condition) = &schema.r#if {
+        conditions.push(Some(match &schema.then {
+            Some(then) => condition.merge(then),
+            None => (**condition).clone(),
+        }));
+        conditions.extend(schema.r#else.as_deref().cloned().map(Some));
+    }
+    let mut last = plain;
+    for union in &unions {
+        for condition in &conditions {
+            if union.is_none() && condition.is_none() {
+                continue;
+            }
+            let mut candidate = base.clone();
+            if let Some(union) = union {
+                candidate = candidate.merge(union);
+            }
+            if let Some(condition) = condition {
+                candidate = candidate.merge(condition);
+            }
+            last = generate(&candidate);
+            if passes(&last) {
+                return last;
+            }
+        }
+    }
+    last
+}
+
+/// `schema` without its composition and conditional keywords, `allOf`
+/// branches merged in
+fn without_branches(schema: &Schema) -> Schema {
+    let mut base = schema.clone();
+    base.one_of = None;
+    base.any_of = None;
+    base.all_of = None;
+    base.r#if = None;
+    base.then = None;
+    base.r#else = None;
+    for branch in schema.all_of.iter().flatten() {
+        base = base.merge(&without_branches(branch));
+    }
+    base
+}
+
+/// The type a value of `schema` gets: `type`, else the one its keywords
+/// constrain, else null
+fn value_type(schema: &Schema) -> SchemaType {
+    if let Some(schema_type) = &schema.schema_type {
+        return schema_type.clone();
+    }
+    if schema.properties.is_some() || schema.required.is_some() || schema.dependent_required.is_some()
+        || schema.pattern_properties.is_some() || schema.additional_properties.is_some() || schema.property_names.is_some() {
+        SchemaType::Object
+    } else if schema.prefix_items.is_some() || schema.items.is_some() || schema.min_items.is_some()
+        || schema.max_items.is_some() || schema.unique_items.is_some() {
+        SchemaType::Array
+    } else if schema.min_length.is_some() || schema.max_length.is_some() || schema.pattern.is_some() || schema.format.is_some() {
+        SchemaType::String
+    } else if schema.minimum.is_some() || schema.maximum.is_some()
+        || schema.exclusive_minimum.is_some() || schema.exclusive_maximum.is_some() {
+        SchemaType::Number
+    } else {
+        SchemaType::Null
+    }
+}
+
+fn build(schema: &Schema) -> Value {
+    match value_type(schema) {
+        SchemaType::Null => Value::Null,
+        SchemaType::Boolean => Value::Bool(false),
+        SchemaType::Number => number(schema),
+        SchemaType::String => string(schema),
+        SchemaType::Array => array(schema),
+        SchemaType::Object => object(schema),
+    }
+}
+
+/// The lowest whole number in bounds, else the middle of the bounds; 0 when
+/// unbounded below unless the upper bound is lower
+fn number(schema: &Schema) -> Value {
+    let below = [schema.minimum, schema.exclusive_minimum].into_iter().flatten().reduce(f64::max);
+    let above = [schema.maximum, schema.exclusive_maximum].into_iter().flatten().reduce(f64::min);
+    let low = [schema.minimum.map(f64::ceil), schema.exclusive_minimum.map(|n| n.floor() + 1.0)].into_iter().flatten().reduce(f64::max);
+    let high = [schema.maximum.map(f64::floor), schema.exclusive_maximum.map(|n| n.ceil() - 1.0)].into_iter().flatten().reduce(f64::min);
+    let n = match (low, high) {
+        (Some(low), Some(high)) if high < low => {
+            // No whole number fits
+            (below.unwrap_or(0.0) + above.unwrap_or(0.0)) / 2.0
+        }
+        (Some(low), _) => low,
+        (None, Some(high)) if high < 0.0 => high,
+        _ => 0.0,
+    };
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        json!(n as i64)
+    } else {
+        json!(n)
+    }
+}
+
+/// Example strings for the formats format.rs knows
+fn format_example(format: &str) -> Option<&'static str> {
+    Some(match format {
+        "date" => "2000-01-01",
+        "date-time" => "2000-01-01T00:00:00Z",
+        "email" => "user@example.com",
+        "ipv4" => "127.0.0.1",
+        "ipv6" => "::1",
+        "uri" => "https://example.com",
+        "uuid" => "00000000-0000-0000-0000-000000000000",
+        _ => return None,
+    })
+}
+
+fn string(schema: &Schema) -> Value {
+    let min = schema.min_length.unwrap_or(0);
+    let text = match schema.format.as_deref().and_then(format_example) {
+        Some(example) => format!("{}{}", example, "a".repeat(min.saturating_sub(example.chars().count()))),
+        None => "a".repeat(min),
+    };
+    Value::String(text)
+}
+
+/// `minItems` items, each generated from its item schema; with `uniqueItems`
+/// repeats are made distinct where the item type allows
+fn array(schema: &Schema) -> Value {
+    let mut items: Vec<Value> = Vec::new();
+    let count = schema.min_items.unwrap_or(0);
+    for index in 0..count {
+        let mut item = match schema.item_schema(index) {
+            Ok(Some(item_schema)) => generate(item_schema),
+            _ => Value::Null,
+        };
+        if schema.unique_items == Some(true) && items.contains(&item) {
+            item = distinct(item, index);
+        }
+        items.push(item);
+    }
+    Value::Array(items)
+}
+
+fn distinct(item: Value, index: usize) -> Value {
+    match item {
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => json!(n + index as i64),
+            None => json!(n.as_f64().unwrap_or(0.0) + index as f64),
+        },
+        Value::String(s) => Value::String(format!("{}{}", s, index)),
+        Value::Bool(b) => Value::Bool(!b),
+        Value::Array(mut items) => {
+            items.push(json!(index));
+            Value::Array(items)
+        }
+        Value::Object(mut map) => {
+            map.insert(format!("_{}", index), json!(index));
+            Value::Object(map)
+        }
+        Value::Null => json!(index),
+    }
+}
+
+/// The required properties, the properties they or their presence require
+/// (`dependentRequired`), and the properties that declare a default
+fn object(schema: &Schema) -> Value {
+    let mut keys: Vec<String> = schema.required.clone().unwrap_or_default();
+    if let Some(properties) = &schema.properties {
+        let mut defaulted: Vec<&String> = properties.iter()
+            .filter(|(key, property)| property.default.is_some() && !keys.contains(key))
+            .map(|(key, _)| key)
+            .collect();
+        defaulted.sort();
+        keys.extend(defaulted.into_iter().cloned());
+    }
+    if let Some(dependents) = &schema.dependent_required {
+        let mut next = 0;
+        while next < keys.len() {
+            for dependent in dependents.get(&keys[next]).into_iter().flatten() {
+                if !keys.contains(dependent) {
+                    keys.push(dependent.clone());
+                }
+            }
+            next += 1;
+        }
+    }
+
+    let mut map = Map::new();
+    for key in keys {
+        let named = schema.properties.as_ref().and_then(|properties| properties.get(&key));
+        let value = match named.or_else(|| schema.key_schemas(&key).next()) {
+            Some(property) => generate(property),
+            None => Value::Null,
+        };
+        map.insert(key, value);
+    }
+    Value::Object(map)
+}
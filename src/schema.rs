@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+
+use crate::format;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -17,15 +24,33 @@ pub enum SchemaType {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Schema {
-    #[serde(rename = "type")]
-    pub schema_type: SchemaType,
+    /// Any type when absent, as for a schema that only composes others
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub schema_type: Option<SchemaType>,
     pub properties: Option<HashMap<String, Schema>>,
     pub required: Option<Vec<String>>,
+    /// Properties that, when present, require others: `{card: ['cvc']}`
+    pub dependent_required: Option<HashMap<String, Vec<String>>>,
+    /// Schemas for the properties whose names match a regex
+    pub pattern_properties: Option<HashMap<String, Schema>>,
+    /// Properties neither listed in `properties` nor matched by a pattern:
+    /// `false` forbids them, a schema validates them
+    pub additional_properties: Option<Additional>,
+    /// Schema every property name must pass, as a string
+    pub property_names: Option<Box<Schema>>,
+    /// `pattern` and `pattern_properties` compiled; filled by `CompiledSchema`,
+    /// else built on first use
+    #[serde(skip)]
+    regexes: OnceCell<Regexes>,
     
     // String constraints
     pub min_length: Option<usize>,
     pub max_length: Option<usize>,
     pub pattern: Option<String>,
+    /// Named string format, see format.rs; unknown names are ignored
+    pub format: Option<String>,
+    /// Refuse the schema if it, or any schema below it, names an unknown format
+    pub strict_formats: Option<bool>,
     
     // Number constraints
     pub minimum: Option<f64>,
@@ -34,13 +59,79 @@ pub struct Schema {
     pub exclusive_maximum: Option<f64>,
     
     // Array constraints
-    pub items: Option<Box<Schema>>,
+    /// Schemas for the first items, by position
+    pub prefix_items: Option<Vec<Schema>>,
+    /// Items after `prefix_items` (all items without it): `false` forbids
+    /// them, a schema validates them
+    pub items: Option<Additional>,
     pub min_items: Option<usize>,
     pub max_items: Option<usize>,
     pub unique_items: Option<bool>,
     
     // Enum
     pub r#enum: Option<Vec<Value>>,
+
+    // Composition: the value must pass exactly one / at least one / every branch
+    pub one_of: Option<Vec<Schema>>,
+    pub any_of: Option<Vec<Schema>>,
+    pub all_of: Option<Vec<Schema>>,
+
+    // Conditional: a value that passes `if` must pass `then`, any other `else`
+    pub r#if: Option<Box<Schema>>,
+    pub then: Option<Box<Schema>>,
+    pub r#else: Option<Box<Schema>>,
+
+    // Reuse: `$ref` names a definition ("#/definitions/name") or another
+    // registered schema ("path" or "path#/definitions/name"). `parse` replaces
+    // every reference with what it names, so validation never follows one.
+    #[serde(alias = "$defs")]
+    pub definitions: Option<HashMap<String, Schema>>,
+    #[serde(rename = "$ref")]
+    pub r#ref: Option<String>,
+
+    /// Stored in place of a missing property when its schema was registered
+    /// with `applyDefaults`; `default: null` is kept as a null default
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+}
+
+/// A keyword that is present, even as null
+fn present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+/// The regexes of one schema node
+#[derive(Debug, Clone, Default)]
+struct Regexes {
+    pattern: Option<Regex>,
+    /// With their `pattern_properties` key, in key order
+    properties: Vec<(Regex, String)>,
+}
+
+impl Regexes {
+    fn build(schema: &Schema) -> Result<Regexes, String> {
+        let pattern = match &schema.pattern {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?),
+            None => None,
+        };
+        let mut patterns: Vec<&String> = schema.pattern_properties.iter().flat_map(|p| p.keys()).collect();
+        patterns.sort();
+        let properties = patterns.into_iter()
+            .map(|p| Regex::new(p)
+                .map(|re| (re, p.clone()))
+                .map_err(|e| format!("Invalid patternProperties regex '{}': {}", p, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Regexes { pattern, properties })
+    }
+}
+
+/// Schema for the properties or items a schema does not name, or a boolean:
+/// `false` allows none, `true` any
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Additional {
+    Allowed(bool),
+    Schema(Box<Schema>),
 }
 
 #[derive(Debug)]
@@ -50,6 +141,8 @@ pub enum ValidationError {
     MinLength(usize),
     MaxLength(usize),
     PatternMismatch(String),
+    /// The format, and the string that is not in it
+    FormatMismatch(String, String),
     Minimum(f64),
     Maximum(f64),
     MinItems(usize),
@@ -58,6 +151,32 @@ pub enum ValidationError {
     EnumMismatch,
     PropertyError(String, Box<ValidationError>),
     ItemError(usize, Box<ValidationError>),
+    /// A property `additionalProperties: false` leaves out
+    AdditionalProperty(String),
+    /// An array longer than its `prefixItems` under `items: false`: their count
+    ExtraItems(usize),
+    /// A property name that fails `propertyNames`
+    PropertyName(String, Box<ValidationError>),
+    /// No anyOf branch passed; why each one failed
+    AnyOf(Vec<ValidationError>),
+    /// No oneOf branch passed; why each one failed
+    OneOfNone(Vec<ValidationError>),
+    /// More than one oneOf branch passed: their indexes
+    OneOfMany(Vec<usize>),
+    /// An allOf branch failed
+    AllOf(usize, Box<ValidationError>),
+    /// A present property, and the property it requires that is missing
+    DependentRequired(String, String),
+    /// The conditional branch taken, "then" or "else", failed
+    Branch(&'static str, Box<ValidationError>),
+}
+
+/// "branch 0: ...; branch 1: ..."
+fn branch_errors(errors: &[ValidationError]) -> String {
+    errors.iter().enumerate()
+        .map(|(i, err)| format!("branch {}: {}", i, err))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 impl std::fmt::Display for ValidationError {
@@ -68,6 +187,7 @@ impl std::fmt::Display for ValidationError {
             ValidationError::MinLength(len) => write!(f, "String too short: min length {}", len),
             ValidationError::MaxLength(len) => write!(f, "String too long: max length {}", len),
             ValidationError::PatternMismatch(p) => write!(f, "String does not match pattern: {}", p),
+            ValidationError::FormatMismatch(format, value) => write!(f, "String is not a valid {}: {:?}", format, value),
             ValidationError::Minimum(val) => write!(f, "Value too small: min {}", val),
             ValidationError::Maximum(val) => write!(f, "Value too large: max {}", val),
             ValidationError::MinItems(len) => write!(f, "Array too short: min items {}", len),
@@ -76,6 +196,43 @@ impl std::fmt::Display for ValidationError {
             ValidationError::EnumMismatch => write!(f, "Value not in allowed enum"),
             ValidationError::PropertyError(prop, err) => write!(f, "In property '{}': {}", prop, err),
             ValidationError::ItemError(idx, err) => write!(f, "In item {}: {}", idx, err),
+            ValidationError::AdditionalProperty(prop) => write!(f, "Property not allowed: {}", prop),
+            ValidationError::ExtraItems(len) => write!(f, "Array allows no items after the first {}", len),
+            ValidationError::PropertyName(prop, err) => write!(f, "Invalid property name '{}': {}", prop, err),
+            ValidationError::AnyOf(errors) => write!(f, "Value matches no anyOf branch ({})", branch_errors(errors)),
+            ValidationError::OneOfNone(errors) => write!(f, "Value matches no oneOf branch ({})", branch_errors(errors)),
+            ValidationError::OneOfMany(matched) => write!(f, "Value matches more than one oneOf branch: {:?}", matched),
+            ValidationError::AllOf(idx, err) => write!(f, "In allOf branch {}: {}", idx, err),
+            ValidationError::DependentRequired(prop, dep) => write!(f, "Property '{}' requires property: {}", prop, dep),
+            ValidationError::Branch(name, err) => write!(f, "In {} branch: {}", name, err),
+        }
+    }
+}
+
+impl ValidationError {
+    /// Schema keyword that rejected the value
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ValidationError::TypeMismatch { .. } => "type",
+            ValidationError::MissingRequired(_) => "required",
+            ValidationError::MinLength(_) => "minLength",
+            ValidationError::MaxLength(_) => "maxLength",
+            ValidationError::PatternMismatch(_) => "pattern",
+            ValidationError::FormatMismatch(..) => "format",
+            ValidationError::Minimum(_) => "minimum",
+            ValidationError::Maximum(_) => "maximum",
+            ValidationError::MinItems(_) => "minItems",
+            ValidationError::MaxItems(_) => "maxItems",
+            ValidationError::UniqueItems => "uniqueItems",
+            ValidationError::EnumMismatch => "enum",
+            ValidationError::AdditionalProperty(_) => "additionalProperties",
+            ValidationError::ExtraItems(_) => "items",
+            ValidationError::PropertyName(..) => "propertyNames",
+            ValidationError::AnyOf(_) => "anyOf",
+            ValidationError::OneOfNone(_) | ValidationError::OneOfMany(_) => "oneOf",
+            ValidationError::DependentRequired(..) => "dependentRequired",
+            ValidationError::PropertyError(_, err) | ValidationError::ItemError(_, err) | ValidationError::AllOf(_, err)
+            | ValidationError::Branch(_, err) => err.keyword(),
         }
     }
 }
@@ -83,13 +240,14 @@ impl std::fmt::Display for ValidationError {
 pub fn validate(value: &Value, schema: &Schema) -> Result<(), ValidationError> {
     // 1. Check type
     match (&schema.schema_type, value) {
-        (SchemaType::Object, Value::Object(_)) => {}
-        (SchemaType::Array, Value::Array(_)) => {}
-        (SchemaType::String, Value::String(_)) => {}
-        (SchemaType::Number, Value::Number(_)) => {}
-        (SchemaType::Boolean, Value::Bool(_)) => {}
-        (SchemaType::Null, Value::Null) => {}
-        (expected, found) => {
+        (None, _) => {}
+        (Some(SchemaType::Object), Value::Object(_)) => {}
+        (Some(SchemaType::Array), Value::Array(_)) => {}
+        (Some(SchemaType::String), Value::String(_)) => {}
+        (Some(SchemaType::Number), Value::Number(_)) => {}
+        (Some(SchemaType::Boolean), Value::Bool(_)) => {}
+        (Some(SchemaType::Null), Value::Null) => {}
+        (Some(expected), found) => {
             let found_str = match found {
                 Value::Null => "null",
                 Value::Bool(_) => "boolean",
@@ -118,10 +276,10 @@ pub fn validate(value: &Value, schema: &Schema) -> Result<(), ValidationError> {
             if let Some(max) = schema.max_length {
                 if s.len() > max { return Err(ValidationError::MaxLength(max)); }
             }
-            if let Some(pattern_str) = &schema.pattern {
-                let re = Regex::new(pattern_str).map_err(|_| ValidationError::PatternMismatch(pattern_str.clone()))?;
-                if !re.is_match(s) {
-                    return Err(ValidationError::PatternMismatch(pattern_str.clone()));
+            check_pattern(schema, s)?;
+            if let Some(format) = &schema.format {
+                if format::check(format, s) == Some(false) {
+                    return Err(ValidationError::FormatMismatch(format.clone(), s.clone()));
                 }
             }
         }
@@ -149,16 +307,12 @@ pub fn validate(value: &Value, schema: &Schema) -> Result<(), ValidationError> {
                 if arr.len() > max { return Err(ValidationError::MaxItems(max)); }
             }
             if let Some(true) = schema.unique_items {
-                let mut unique = arr.clone();
-                unique.sort_by(|a, b| a.to_string().cmp(&b.to_string())); // Simple unique check
-                let original_len = arr.len();
-                unique.dedup();
-                if unique.len() < original_len {
+                if !all_unique(arr) {
                     return Err(ValidationError::UniqueItems);
                 }
             }
-            if let Some(item_schema) = &schema.items {
-                for (i, item) in arr.iter().enumerate() {
+            for (i, item) in arr.iter().enumerate() {
+                if let Some(item_schema) = schema.item_schema(i)? {
                     validate(item, item_schema).map_err(|e| ValidationError::ItemError(i, Box::new(e)))?;
                 }
             }
@@ -171,6 +325,9 @@ pub fn validate(value: &Value, schema: &Schema) -> Result<(), ValidationError> {
                     }
                 }
             }
+            if let Some(err) = missing_dependents(obj, schema).next() {
+                return Err(err);
+            }
             if let Some(props) = &schema.properties {
                 for (key, prop_schema) in props {
                     if let Some(val) = obj.get(key) {
@@ -178,9 +335,843 @@ pub fn validate(value: &Value, schema: &Schema) -> Result<(), ValidationError> {
                     }
                 }
             }
+            if schema.checks_keys() {
+                for (key, val) in obj {
+                    check_key(schema, key)?;
+                    for sub in schema.key_schemas(key) {
+                        validate(val, sub).map_err(|e| ValidationError::PropertyError(key.clone(), Box::new(e)))?;
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // 4. Composition
+    validate_composition(value, schema)
+}
+
+fn validate_composition(value: &Value, schema: &Schema) -> Result<(), ValidationError> {
+    for (i, branch) in schema.all_of.iter().flatten().enumerate() {
+        validate(value, branch).map_err(|e| ValidationError::AllOf(i, Box::new(e)))?;
+    }
+    if let Some(branches) = &schema.any_of {
+        check_any_of(value, branches)?;
+    }
+    if let Some(branches) = &schema.one_of {
+        check_one_of(value, branches)?;
+    }
+    match conditional_branch(value, schema) {
+        Some((name, branch)) => validate(value, branch).map_err(|e| ValidationError::Branch(name, Box::new(e))),
+        None => Ok(()),
+    }
+}
+
+/// The `then` or `else` schema that applies to `value`, by name
+fn conditional_branch<'a>(value: &Value, schema: &'a Schema) -> Option<(&'static str, &'a Schema)> {
+    if validate(value, schema.r#if.as_deref()?).is_ok() {
+        Some(("then", schema.then.as_deref()?))
+    } else {
+        Some(("else", schema.r#else.as_deref()?))
+    }
+}
+
+/// `dependentRequired` failures of `obj`, in property order
+fn missing_dependents<'a>(obj: &'a serde_json::Map<String, Value>, schema: &'a Schema) -> impl Iterator<Item = ValidationError> + 'a {
+    let dependents = schema.dependent_required.as_ref();
+    obj.keys()
+        .filter_map(move |key| Some((key, dependents?.get(key)?)))
+        .flat_map(move |(key, required)| required.iter()
+            .filter(|dep| !obj.contains_key(*dep))
+            .map(move |dep| ValidationError::DependentRequired(key.clone(), dep.clone())))
+}
+
+fn check_any_of(value: &Value, branches: &[Schema]) -> Result<(), ValidationError> {
+    let mut errors = Vec::with_capacity(branches.len());
+    if branches.iter().any(|branch| validate(value, branch).map_err(|e| errors.push(e)).is_ok()) {
+        Ok(())
+    } else {
+        Err(ValidationError::AnyOf(errors))
+    }
+}
+
+/// Every branch is tried, so overlapping branches are caught
+fn check_one_of(value: &Value, branches: &[Schema]) -> Result<(), ValidationError> {
+    let mut matched = Vec::new();
+    let mut errors = Vec::new();
+    for (i, branch) in branches.iter().enumerate() {
+        match validate(value, branch) {
+            Ok(()) => matched.push(i),
+            Err(e) => errors.push(e),
+        }
+    }
+    match matched.len() {
+        1 => Ok(()),
+        0 => Err(ValidationError::OneOfNone(errors)),
+        _ => Err(ValidationError::OneOfMany(matched)),
+    }
+}
+
+/// Uniqueness check over canonical string forms (object keys serialize sorted)
+fn all_unique<'a>(items: impl IntoIterator<Item = &'a Value>) -> bool {
+    let mut seen = HashSet::new();
+    items.into_iter().all(|item| seen.insert(item.to_string()))
+}
+
+/// Descend from a registered schema to the sub-schema describing `segments`
+/// below it (None if it describes nothing there). Array indexes step into
+/// `items`; object keys into `properties`, then a matching pattern property,
+/// then an `additionalProperties` schema, and fail if the key itself is not
+/// allowed. A schema registered on a collection describes each document, so
+/// the first segment may also be a document key, unless the schema describes
+/// keys itself (`patternProperties`, `propertyNames`, `additionalProperties`).
+pub fn resolve<'a>(schema: &'a Schema, segments: &[&str]) -> Result<Option<&'a Schema>, ValidationError> {
+    match resolve_exact(schema, segments) {
+        Ok(None) => match segments.split_first() {
+            Some((_, rest)) if matches!(schema.schema_type, Some(SchemaType::Object)) && !schema.checks_keys() => {
+                resolve_exact(schema, rest)
+            }
+            _ => Ok(None),
+        },
+        exact => exact,
+    }
+}
+
+fn resolve_exact<'a>(schema: &'a Schema, segments: &[&str]) -> Result<Option<&'a Schema>, ValidationError> {
+    let Some((first, rest)) = segments.split_first() else { return Ok(Some(schema)) };
+    let child = match schema.schema_type {
+        Some(SchemaType::Array) | None if first.parse::<usize>().is_ok() && schema.describes_items() => {
+            schema.item_schema(first.parse().unwrap_or_default())?
+        }
+        Some(SchemaType::Object) | None => {
+            check_key(schema, first)?;
+            schema.properties.as_ref().and_then(|p| p.get(*first)).or_else(|| schema.key_schemas(first).next())
+        }
+        _ => None,
+    };
+    match child {
+        Some(child) => resolve_exact(child, rest).map_err(|e| ValidationError::PropertyError(first.to_string(), Box::new(e))),
+        None => Ok(None),
+    }
+}
+
+/// Whether `s` matches the `pattern` of `schema`, if it has one
+fn check_pattern(schema: &Schema, s: &str) -> Result<(), ValidationError> {
+    match &schema.pattern {
+        Some(pattern) if !schema.regexes().pattern.as_ref().is_some_and(|re| re.is_match(s)) => {
+            Err(ValidationError::PatternMismatch(pattern.clone()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether the object `schema` allows a property named `key`
+fn check_key(schema: &Schema, key: &str) -> Result<(), ValidationError> {
+    if let Some(names) = &schema.property_names {
+        validate(&Value::String(key.to_string()), names).map_err(|e| ValidationError::PropertyName(key.to_string(), Box::new(e)))?;
+    }
+    if matches!(schema.additional_properties, Some(Additional::Allowed(false))) && schema.is_additional(key) {
+        return Err(ValidationError::AdditionalProperty(key.to_string()));
+    }
+    Ok(())
+}
+
+/// Fill in the defaults `schema` declares: a property missing from an object
+/// gets its schema's default (with the defaults below it filled in too), and
+/// present properties and array items are filled in below. Values given
+/// explicitly, null included, are kept.
+pub fn apply_defaults(mut value: Value, schema: &Schema) -> Value {
+    fill_defaults(&mut value, schema);
+    value
+}
+
+fn fill_defaults(value: &mut Value, schema: &Schema) {
+    // Every allOf branch applies; which anyOf/oneOf branch would is not known yet
+    for branch in schema.all_of.iter().flatten() {
+        fill_defaults(value, branch);
+    }
+    match value {
+        Value::Object(obj) => {
+            if let Some(props) = &schema.properties {
+                // Defaults are appended in key order, so the stored document is the same every time
+                let mut keys: Vec<&String> = props.keys().collect();
+                keys.sort();
+                for key in keys {
+                    let prop_schema = &props[key];
+                    match obj.get_mut(key) {
+                        Some(val) => fill_defaults(val, prop_schema),
+                        None => {
+                            if let Some(default) = &prop_schema.default {
+                                obj.insert(key.clone(), apply_defaults(default.clone(), prop_schema));
+                            }
+                        }
+                    }
+                }
+            }
+            if schema.checks_keys() {
+                for (key, val) in obj.iter_mut() {
+                    for sub in schema.key_schemas(key) {
+                        fill_defaults(val, sub);
+                    }
+                }
+            }
+        }
+        Value::Array(arr) => {
+            for (i, item) in arr.iter_mut().enumerate() {
+                if let Ok(Some(item_schema)) = schema.item_schema(i) {
+                    fill_defaults(item, item_schema);
+                }
+            }
         }
         _ => {}
     }
+}
+
+/// Validate an array write: every inserted element against `items`, and the
+/// length/uniqueness constraints against the post-operation array. Under
+/// `prefixItems` an insert can move items to other positions, so every item
+/// of the result is checked.
+pub fn validate_array_write(schema: &Schema, result: &[Value], inserted: &[(usize, &Value)]) -> Result<(), ValidationError> {
+    let checked: Vec<(usize, &Value)> = match schema.prefix_items {
+        Some(_) => result.iter().enumerate().collect(),
+        None => inserted.to_vec(),
+    };
+    for (i, item) in checked {
+        if let Some(item_schema) = schema.item_schema(i)? {
+            validate(item, item_schema).map_err(|e| ValidationError::ItemError(i, Box::new(e)))?;
+        }
+    }
+    check_array_result(schema, result.len(), result)
+}
+
+/// Validate appending `appended` to `existing`: nothing moves, so only the new
+/// elements are checked against `items`/`prefixItems` for their positions,
+/// and the length/uniqueness constraints against the result.
+pub fn validate_array_append(schema: &Schema, existing: &[Value], appended: &[Value]) -> Result<(), ValidationError> {
+    for (offset, item) in appended.iter().enumerate() {
+        let i = existing.len() + offset;
+        if let Some(item_schema) = schema.item_schema(i)? {
+            validate(item, item_schema).map_err(|e| ValidationError::ItemError(i, Box::new(e)))?;
+        }
+    }
+    check_array_result(schema, existing.len() + appended.len(), existing.iter().chain(appended))
+}
 
+/// The length and uniqueness constraints of an array write, against its result
+fn check_array_result<'a>(schema: &Schema, len: usize, result: impl IntoIterator<Item = &'a Value>) -> Result<(), ValidationError> {
+    if let Some(min) = schema.min_items {
+        if len < min { return Err(ValidationError::MinItems(min)); }
+    }
+    if let Some(max) = schema.max_items {
+        if len > max { return Err(ValidationError::MaxItems(max)); }
+    }
+    if let Some(true) = schema.unique_items {
+        if !all_unique(result) {
+            return Err(ValidationError::UniqueItems);
+        }
+    }
     Ok(())
 }
+
+/// One failure reported by `validate_all`
+#[derive(Debug, Serialize)]
+pub struct SchemaIssue {
+    /// Dotted path below the validated value ("" for the value itself)
+    pub path: String,
+    pub keyword: &'static str,
+    pub message: String,
+}
+
+/// Validate without stopping at the first failure, reporting every issue
+/// with the path it was found at. An empty list means the value is valid.
+pub fn validate_all(value: &Value, schema: &Schema) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    collect_issues(value, schema, "", &mut issues);
+    issues
+}
+
+fn collect_issues(value: &Value, schema: &Schema, path: &str, issues: &mut Vec<SchemaIssue>) {
+    let issue = |err: ValidationError| SchemaIssue {
+        path: path.to_string(),
+        keyword: err.keyword(),
+        message: err.to_string(),
+    };
+    let child_path = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+
+    // A value of the wrong type has no meaningful nested constraints
+    let bare = Schema { schema_type: schema.schema_type.clone(), ..Schema::empty() };
+    if let Err(err) = validate(value, &bare) {
+        issues.push(issue(err));
+        return;
+    }
+    if let Some(allowed) = &schema.r#enum {
+        if !allowed.contains(value) {
+            issues.push(issue(ValidationError::EnumMismatch));
+        }
+    }
+
+    match value {
+        Value::String(_) | Value::Number(_) => {
+            // Scalar constraints are independent; check each on its own
+            let only = |only: Schema| validate(value, &Schema { schema_type: schema.schema_type.clone(), ..only }).err();
+            let errors = [
+                only(Schema { min_length: schema.min_length, ..Schema::empty() }),
+                only(Schema { max_length: schema.max_length, ..Schema::empty() }),
+                // Against the schema itself, which holds the compiled regex
+                value.as_str().and_then(|s| check_pattern(schema, s).err()),
+                only(Schema { format: schema.format.clone(), ..Schema::empty() }),
+                only(Schema { minimum: schema.minimum, exclusive_minimum: schema.exclusive_minimum, ..Schema::empty() }),
+                only(Schema { maximum: schema.maximum, exclusive_maximum: schema.exclusive_maximum, ..Schema::empty() }),
+            ];
+            issues.extend(errors.into_iter().flatten().map(issue));
+        }
+        Value::Array(arr) => {
+            if let Some(min) = schema.min_items {
+                if arr.len() < min { issues.push(issue(ValidationError::MinItems(min))); }
+            }
+            if let Some(max) = schema.max_items {
+                if arr.len() > max { issues.push(issue(ValidationError::MaxItems(max))); }
+            }
+            if schema.unique_items == Some(true) && !all_unique(arr) {
+                issues.push(issue(ValidationError::UniqueItems));
+            }
+            for (i, item) in arr.iter().enumerate() {
+                match schema.item_schema(i) {
+                    Ok(Some(item_schema)) => collect_issues(item, item_schema, &child_path(&i.to_string()), issues),
+                    Ok(None) => {}
+                    Err(err) => {
+                        issues.push(issue(err));
+                        break;
+                    }
+                }
+            }
+        }
+        Value::Object(obj) => {
+            for req in schema.required.iter().flatten() {
+                if !obj.contains_key(req) {
+                    issues.push(issue(ValidationError::MissingRequired(req.clone())));
+                }
+            }
+            issues.extend(missing_dependents(obj, schema).map(issue));
+            if let Some(props) = &schema.properties {
+                let mut keys: Vec<&String> = props.keys().collect();
+                keys.sort();
+                for key in keys {
+                    if let Some(val) = obj.get(key) {
+                        collect_issues(val, &props[key], &child_path(key), issues);
+                    }
+                }
+            }
+            if schema.checks_keys() {
+                for (key, val) in obj {
+                    if let Err(err) = check_key(schema, key) {
+                        issues.push(issue(err));
+                    }
+                    for sub in schema.key_schemas(key) {
+                        collect_issues(val, sub, &child_path(key), issues);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // Every allOf branch reports its own issues; anyOf and oneOf only pass or fail as a whole
+    for branch in schema.all_of.iter().flatten() {
+        collect_issues(value, branch, path, issues);
+    }
+    if let Some(Err(err)) = schema.any_of.as_deref().map(|branches| check_any_of(value, branches)) {
+        issues.push(issue(err));
+    }
+    if let Some(Err(err)) = schema.one_of.as_deref().map(|branches| check_one_of(value, branches)) {
+        issues.push(issue(err));
+    }
+    // Each issue of the branch taken, saying which branch it was
+    if let Some((name, branch)) = conditional_branch(value, schema) {
+        let mut branch_issues = Vec::new();
+        collect_issues(value, branch, path, &mut branch_issues);
+        issues.extend(branch_issues.into_iter().map(|found| SchemaIssue {
+            message: format!("In {} branch: {}", name, found.message),
+            ..found
+        }));
+    }
+}
+
+impl Schema {
+    /// Whether validating an object has to look at every key, not just `properties`
+    fn checks_keys(&self) -> bool {
+        self.pattern_properties.is_some() || self.property_names.is_some() || self.additional_properties.is_some()
+    }
+
+    /// A schema that was not compiled builds its regexes here, once; one that
+    /// does not compile matches nothing
+    fn regexes(&self) -> &Regexes {
+        self.regexes.get_or_init(|| Regexes::build(self).unwrap_or_default())
+    }
+
+    /// Whether `key` is neither a listed property nor matched by a pattern
+    fn is_additional(&self, key: &str) -> bool {
+        !self.properties.as_ref().is_some_and(|p| p.contains_key(key))
+            && !self.regexes().properties.iter().any(|(re, _)| re.is_match(key))
+    }
+
+    fn describes_items(&self) -> bool {
+        self.prefix_items.is_some() || self.items.is_some()
+    }
+
+    /// The schema for the array item at `index`: its `prefix_items` entry,
+    /// else `items`. Fails past the prefix under `items: false`.
+    pub(crate) fn item_schema(&self, index: usize) -> Result<Option<&Schema>, ValidationError> {
+        if let Some(schema) = self.prefix_items.as_ref().and_then(|prefix| prefix.get(index)) {
+            return Ok(Some(schema));
+        }
+        match &self.items {
+            Some(Additional::Schema(schema)) => Ok(Some(schema)),
+            Some(Additional::Allowed(false)) => Err(ValidationError::ExtraItems(self.prefix_items.as_ref().map_or(0, Vec::len))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Schemas besides `properties` that the property `key` must pass: every
+    /// matching pattern property, or else the `additionalProperties` schema
+    pub(crate) fn key_schemas<'a: 'k, 'k>(&'a self, key: &'k str) -> impl Iterator<Item = &'a Schema> + 'k {
+        let patterns = self.pattern_properties.as_ref();
+        let matched = self.regexes().properties.iter()
+            .filter(move |(re, _)| re.is_match(key))
+            .filter_map(move |(_, pattern)| patterns?.get(pattern));
+        let additional = match &self.additional_properties {
+            Some(Additional::Schema(schema)) if self.is_additional(key) => Some(&**schema),
+            _ => None,
+        };
+        matched.chain(additional)
+    }
+
+    /// The referenced schema `base` with the keywords written next to the
+    /// `$ref` (this schema) taking precedence
+    fn over(self, base: Schema) -> Schema {
+        Schema {
+            schema_type: self.schema_type.or(base.schema_type),
+            properties: self.properties.or(base.properties),
+            required: self.required.or(base.required),
+            dependent_required: self.dependent_required.or(base.dependent_required),
+            pattern_properties: self.pattern_properties.or(base.pattern_properties),
+            additional_properties: self.additional_properties.or(base.additional_properties),
+            property_names: self.property_names.or(base.property_names),
+            regexes: OnceCell::new(),
+            min_length: self.min_length.or(base.min_length),
+            max_length: self.max_length.or(base.max_length),
+            pattern: self.pattern.or(base.pattern),
+            format: self.format.or(base.format),
+            strict_formats: self.strict_formats.or(base.strict_formats),
+            minimum: self.minimum.or(base.minimum),
+            maximum: self.maximum.or(base.maximum),
+            exclusive_minimum: self.exclusive_minimum.or(base.exclusive_minimum),
+            exclusive_maximum: self.exclusive_maximum.or(base.exclusive_maximum),
+            prefix_items: self.prefix_items.or(base.prefix_items),
+            items: self.items.or(base.items),
+            min_items: self.min_items.or(base.min_items),
+            max_items: self.max_items.or(base.max_items),
+            unique_items: self.unique_items.or(base.unique_items),
+            r#enum: self.r#enum.or(base.r#enum),
+            default: self.default.or(base.default),
+            one_of: self.one_of.or(base.one_of),
+            any_of: self.any_of.or(base.any_of),
+            all_of: self.all_of.or(base.all_of),
+            r#if: self.r#if.or(base.r#if),
+            then: self.then.or(base.then),
+            r#else: self.r#else.or(base.r#else),
+            definitions: self.definitions.or(base.definitions),
+            r#ref: None,
+        }
+    }
+
+    /// A schema that, as far as keywords can be combined, only values passing
+    /// both `self` and `other` pass: properties and required lists are
+    /// joined, bounds tightened and enums intersected. Other keywords are
+    /// taken from `self` if set there, so a value built from the result still
+    /// has to be checked against both.
+    pub fn merge(&self, other: &Schema) -> Schema {
+        fn pick<T: Clone>(a: &Option<T>, b: &Option<T>) -> Option<T> {
+            a.clone().or_else(|| b.clone())
+        }
+        fn tighter<T: Copy>(a: Option<T>, b: Option<T>, pick: fn(T, T) -> T) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(pick(a, b)),
+                _ => a.or(b),
+            }
+        }
+        let properties = match (&self.properties, &other.properties) {
+            (Some(mine), Some(theirs)) => {
+                let mut joined = mine.clone();
+                for (key, schema) in theirs {
+                    let merged = match mine.get(key) {
+                        Some(own) => own.merge(schema),
+                        None => schema.clone(),
+                    };
+                    joined.insert(key.clone(), merged);
+                }
+                Some(joined)
+            }
+            (mine, theirs) => pick(mine, theirs),
+        };
+        let required = match (&self.required, &other.required) {
+            (Some(mine), Some(theirs)) => {
+                let mut joined = mine.clone();
+                joined.extend(theirs.iter().filter(|key| !mine.contains(key)).cloned());
+                Some(joined)
+            }
+            (mine, theirs) => pick(mine, theirs),
+        };
+        let dependent_required = match (&self.dependent_required, &other.dependent_required) {
+            (Some(mine), Some(theirs)) => {
+                let mut joined = mine.clone();
+                for (key, deps) in theirs {
+                    let entry = joined.entry(key.clone()).or_default();
+                    entry.extend(deps.iter().filter(|dep| !mine.get(key).is_some_and(|own| own.contains(dep))).cloned());
+                }
+                Some(joined)
+            }
+            (mine, theirs) => pick(mine, theirs),
+        };
+        let r#enum = match (&self.r#enum, &other.r#enum) {
+            (Some(mine), Some(theirs)) => Some(mine.iter().filter(|v| theirs.contains(v)).cloned().collect()),
+            (mine, theirs) => pick(mine, theirs),
+        };
+        Schema {
+            schema_type: pick(&self.schema_type, &other.schema_type),
+            properties,
+            required,
+            dependent_required,
+            pattern_properties: pick(&self.pattern_properties, &other.pattern_properties),
+            additional_properties: pick(&self.additional_properties, &other.additional_properties),
+            property_names: pick(&self.property_names, &other.property_names),
+            regexes: OnceCell::new(),
+            min_length: tighter(self.min_length, other.min_length, usize::max),
+            max_length: tighter(self.max_length, other.max_length, usize::min),
+            pattern: pick(&self.pattern, &other.pattern),
+            format: pick(&self.format, &other.format),
+            strict_formats: pick(&self.strict_formats, &other.strict_formats),
+            minimum: tighter(self.minimum, other.minimum, f64::max),
+            maximum: tighter(self.maximum, other.maximum, f64::min),
+            exclusive_minimum: tighter(self.exclusive_minimum, other.exclusive_minimum, f64::max),
+            exclusive_maximum: tighter(self.exclusive_maximum, other.exclusive_maximum, f64::min),
+            prefix_items: pick(&self.prefix_items, &other.prefix_items),
+            items: pick(&self.items, &other.items),
+            min_items: tighter(self.min_items, other.min_items, usize::max),
+            max_items: tighter(self.max_items, other.max_items, usize::min),
+            unique_items: pick(&self.unique_items, &other.unique_items),
+            r#enum,
+            default: pick(&self.default, &other.default),
+            one_of: pick(&self.one_of, &other.one_of),
+            any_of: pick(&self.any_of, &other.any_of),
+            all_of: pick(&self.all_of, &other.all_of),
+            r#if: pick(&self.r#if, &other.r#if),
+            then: pick(&self.then, &other.then),
+            r#else: pick(&self.r#else, &other.r#else),
+            definitions: None,
+            r#ref: None,
+        }
+    }
+
+    /// A schema with no constraints
+    fn empty() -> Self {
+        Schema {
+            schema_type: None,
+            properties: None,
+            required: None,
+            dependent_required: None,
+            pattern_properties: None,
+            additional_properties: None,
+            property_names: None,
+            regexes: OnceCell::new(),
+            min_length: None,
+            max_length: None,
+            pattern: None,
+            format: None,
+            strict_formats: None,
+            minimum: None,
+            maximum: None,
+            exclusive_minimum: None,
+            exclusive_maximum: None,
+            prefix_items: None,
+            items: None,
+            min_items: None,
+            max_items: None,
+            unique_items: None,
+            r#enum: None,
+            default: None,
+            one_of: None,
+            any_of: None,
+            all_of: None,
+            r#if: None,
+            then: None,
+            r#else: None,
+            definitions: None,
+            r#ref: None,
+        }
+    }
+}
+
+/// A schema ready to validate against: `$ref`s resolved, checked, and every
+/// regex in it compiled, so validating never builds one. Serializes as the
+/// schema it was built from; deserializing compiles again.
+#[derive(Debug, Clone)]
+pub struct CompiledSchema(Schema);
+
+impl CompiledSchema {
+    /// Check `schema`, its `$ref`s already resolved, and compile its regexes
+    pub fn new(schema: Schema) -> Result<Self, String> {
+        check(&schema, false)?;
+        Ok(CompiledSchema(schema))
+    }
+}
+
+impl CompiledSchema {
+    /// The schema as JSON, without its unset keywords
+    pub fn to_json(&self) -> Value {
+        to_json(&self.0)
+    }
+}
+
+/// `schema` as JSON, without its unset keywords
+pub fn to_json(schema: &Schema) -> Value {
+    without_nulls(serde_json::to_value(schema).unwrap_or_default())
+}
+
+/// A serialized schema without its null (unset) keywords. `enum` and
+/// `default` are data and kept as they are, `default: null` included.
+fn without_nulls(schema: Value) -> Value {
+    let Value::Object(keywords) = schema else { return schema };
+    Value::Object(keywords.into_iter()
+        .filter(|(keyword, value)| !value.is_null() || keyword == "default")
+        .map(|(keyword, value)| {
+            let value = match (keyword.as_str(), value) {
+                ("enum" | "default", data) => data,
+                // Schemas by name; the names may be keywords themselves
+                ("properties" | "patternProperties" | "definitions", Value::Object(named)) => {
+                    Value::Object(named.into_iter().map(|(name, schema)| (name, without_nulls(schema))).collect())
+                }
+                (_, Value::Array(schemas)) => Value::Array(schemas.into_iter().map(without_nulls).collect()),
+                (_, value) => without_nulls(value),
+            };
+            (keyword, value)
+        })
+        .collect())
+}
+
+impl std::ops::Deref for CompiledSchema {
+    type Target = Schema;
+
+    fn deref(&self) -> &Schema {
+        &self.0
+    }
+}
+
+impl Serialize for CompiledSchema {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CompiledSchema {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        CompiledSchema::new(Schema::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parsed schemas by hash of their JSON text, so repeated ad-hoc validation
+/// with the same schema skips parsing
+type CompiledCache = HashMap<u64, (String, Arc<CompiledSchema>)>;
+static COMPILED: Lazy<Mutex<CompiledCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+const COMPILED_CAPACITY: usize = 256;
+
+/// Parse and check a schema as `parse` does, cached. Errors describe the
+/// schema itself, never a value.
+pub fn compile(schema_json: &str) -> Result<Arc<CompiledSchema>, String> {
+    let mut hasher = DefaultHasher::new();
+    schema_json.hash(&mut hasher);
+    let key = hasher.finish();
+
+    if let Some((text, schema)) = COMPILED.lock().get(&key) {
+        if text == schema_json {
+            return Ok(schema.clone());
+        }
+    }
+
+    let schema = Arc::new(parse(schema_json, &HashMap::new())?);
+
+    let mut compiled = COMPILED.lock();
+    if compiled.len() >= COMPILED_CAPACITY {
+        compiled.clear();
+    }
+    compiled.insert(key, (schema_json.to_string(), schema.clone()));
+    Ok(schema)
+}
+
+/// Parse a schema, resolve its `$ref`s (`registered` answers references to
+/// other schemas), and compile it
+pub fn parse(schema_json: &str, registered: &HashMap<String, CompiledSchema>) -> Result<CompiledSchema, String> {
+    let schema: Schema = serde_json::from_str(schema_json).map_err(|e| format!("Invalid schema JSON: {}", e))?;
+    CompiledSchema::new(Refs::new(&schema, registered).resolve_root(schema)?)
+}
+
+/// Compile the regexes of `schema` and every schema below it, and check
+/// that, under `strictFormats`, every format it names is known
+fn check(schema: &Schema, strict_formats: bool) -> Result<(), String> {
+    let regexes = Regexes::build(schema)?;
+    // Copies of a compiled schema keep its regexes
+    let _ = schema.regexes.set(regexes);
+    let strict_formats = strict_formats || schema.strict_formats == Some(true);
+    if let Some(name) = schema.format.as_deref().filter(|_| strict_formats) {
+        if !format::KNOWN.contains(&name) {
+            return Err(format!("Unknown format '{}' (known: {})", name, format::KNOWN.join(", ")));
+        }
+    }
+    for item in schema.prefix_items.iter().flatten() {
+        check(item, strict_formats)?;
+    }
+    if let Some(Additional::Schema(items)) = &schema.items {
+        check(items, strict_formats)?;
+    }
+    for prop in schema.properties.iter().flat_map(|p| p.values()) {
+        check(prop, strict_formats)?;
+    }
+    for prop in schema.pattern_properties.iter().flat_map(|p| p.values()) {
+        check(prop, strict_formats)?;
+    }
+    if let Some(Additional::Schema(additional)) = &schema.additional_properties {
+        check(additional, strict_formats)?;
+    }
+    if let Some(names) = &schema.property_names {
+        check(names, strict_formats)?;
+    }
+    for branch in [&schema.one_of, &schema.any_of, &schema.all_of].into_iter().flatten().flatten() {
+        check(branch, strict_formats)?;
+    }
+    for branch in [&schema.r#if, &schema.then, &schema.r#else].into_iter().flatten() {
+        check(branch, strict_formats)?;
+    }
+    for definition in schema.definitions.iter().flat_map(|d| d.values()) {
+        check(definition, strict_formats)?;
+    }
+    Ok(())
+}
+
+/// Resolves the `$ref`s of one schema. Each definition is resolved once; the
+/// definitions being resolved are kept on a stack to catch cycles.
+struct Refs<'a> {
+    definitions: HashMap<String, Schema>,
+    registered: &'a HashMap<String, CompiledSchema>,
+    resolved: HashMap<String, Schema>,
+    resolving: Vec<String>,
+}
+
+impl<'a> Refs<'a> {
+    fn new(root: &Schema, registered: &'a HashMap<String, CompiledSchema>) -> Self {
+        Refs {
+            definitions: root.definitions.clone().unwrap_or_default(),
+            registered,
+            resolved: HashMap::new(),
+            resolving: Vec::new(),
+        }
+    }
+
+    fn resolve_root(&mut self, mut root: Schema) -> Result<Schema, String> {
+        let names: Vec<String> = root.definitions.take().map(|d| d.into_keys().collect()).unwrap_or_default();
+        let mut root = self.resolve(root)?;
+        if !names.is_empty() {
+            // Kept, resolved, so other schemas can refer to them
+            let mut definitions = HashMap::with_capacity(names.len());
+            for name in names {
+                let definition = self.definition(&name)?;
+                definitions.insert(name, definition);
+            }
+            root.definitions = Some(definitions);
+        }
+        Ok(root)
+    }
+
+    fn resolve(&mut self, mut schema: Schema) -> Result<Schema, String> {
+        if let Some(props) = schema.properties.take() {
+            let mut resolved = HashMap::with_capacity(props.len());
+            for (key, prop) in props {
+                resolved.insert(key, self.resolve(prop)?);
+            }
+            schema.properties = Some(resolved);
+        }
+        if let Some(props) = schema.pattern_properties.take() {
+            let mut resolved = HashMap::with_capacity(props.len());
+            for (pattern, prop) in props {
+                resolved.insert(pattern, self.resolve(prop)?);
+            }
+            schema.pattern_properties = Some(resolved);
+        }
+        if let Some(Additional::Schema(additional)) = &mut schema.additional_properties {
+            **additional = self.resolve(std::mem::replace(additional, Schema::empty()))?;
+        }
+        if let Some(names) = schema.property_names.take() {
+            schema.property_names = Some(Box::new(self.resolve(*names)?));
+        }
+        if let Some(prefix) = schema.prefix_items.take() {
+            schema.prefix_items = Some(prefix.into_iter().map(|item| self.resolve(item)).collect::<Result<_, _>>()?);
+        }
+        if let Some(Additional::Schema(items)) = &mut schema.items {
+            **items = self.resolve(std::mem::replace(items, Schema::empty()))?;
+        }
+        for branches in [&mut schema.one_of, &mut schema.any_of, &mut schema.all_of] {
+            if let Some(list) = branches.take() {
+                *branches = Some(list.into_iter().map(|b| self.resolve(b)).collect::<Result<_, _>>()?);
+            }
+        }
+        for branch in [&mut schema.r#if, &mut schema.then, &mut schema.r#else] {
+            if let Some(conditional) = branch.take() {
+                *branch = Some(Box::new(self.resolve(*conditional)?));
+            }
+        }
+        match schema.r#ref.take() {
+            Some(reference) => Ok(schema.over(self.target(&reference)?)),
+            None => Ok(schema),
+        }
+    }
+
+    /// What `reference` names, resolved
+    fn target(&mut self, reference: &str) -> Result<Schema, String> {
+        let (path, fragment) = reference.split_once('#').unwrap_or((reference, ""));
+        let name = match fragment {
+            "" => None,
+            _ => Some(fragment.strip_prefix("/definitions/").or_else(|| fragment.strip_prefix("/$defs/"))
+                .ok_or_else(|| format!("Unsupported $ref '{}': use '#/definitions/<name>'", reference))?),
+        };
+        if path.is_empty() {
+            return match name {
+                Some(name) => self.definition(name),
+                None => Err("$ref '#' makes the schema contain itself".to_string()),
+            };
+        }
+        let schema = self.registered.get(path).ok_or_else(|| format!("$ref '{}' names no registered schema", reference))?;
+        match name {
+            None => Ok(schema.0.clone()),
+            Some(name) => schema.definitions.as_ref().and_then(|d| d.get(name)).cloned()
+                .ok_or_else(|| format!("$ref '{}': the schema at '{}' has no definition '{}'", reference, path, name)),
+        }
+    }
+
+    fn definition(&mut self, name: &str) -> Result<Schema, String> {
+        if let Some(done) = self.resolved.get(name) {
+            return Ok(done.clone());
+        }
+        if let Some(at) = self.resolving.iter().position(|n| n == name) {
+            let mut cycle = self.resolving[at..].to_vec();
+            cycle.push(name.to_string());
+            return Err(format!("$ref cycle: {}", cycle.join(" -> ")));
+        }
+        let definition = self.definitions.get(name).cloned()
+            .ok_or_else(|| format!("$ref '#/definitions/{}' names no definition", name))?;
+        self.resolving.push(name.to_string());
+        let resolved = self.resolve(definition);
+        self.resolving.pop();
+        let resolved = resolved?;
+        self.resolved.insert(name.to_string(), resolved.clone());
+        Ok(resolved)
+    }
+}
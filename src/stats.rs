@@ -0,0 +1,125 @@
+//! Planner statistics
+//!
+//! `analyze` scans a collection once, on the pool when it is large, and
+//! records per indexed field how many documents have a key, how many distinct
+//! keys there are, the lowest and highest key in index order and the most
+//! common keys with their document counts. Records are kept in the reserved
+//! namespace, so they survive restarts, and only change on the next analyze.
+//!
+//! Writes under an analyzed collection are counted in memory; once the count
+//! reaches the record's `staleAfter` the stats are reported stale. After a
+//! restart the count starts from how far the entry count has drifted.
+//!
+//! The planner estimates from them the share of the collection an index probe
+//! selects, and scans instead when that is over `MAX_INDEX_SHARE`: reading
+//! most of a collection through an index costs more than reading it in order.
+
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::btree::{BTreeIndex, Key};
+use crate::resolve_field;
+
+/// Largest share of a collection an index probe may select
+pub const MAX_INDEX_SHARE: f64 = 0.5;
+
+/// Keys listed in `mostCommon`
+const MOST_COMMON: usize = 5;
+
+/// Stats of the field `idx` indexes over `docs`:
+/// `{index, indexed, cardinality, min, max, mostCommon: [{key, docs}]}`
+pub fn field_stats(name: &str, idx: &BTreeIndex, docs: &[&Value], parallel: bool) -> Value {
+    let parts = crate::split_field(idx.field());
+    let count = |mut counts: HashMap<String, u64>, doc: &&Value| {
+        if let Some(key) = resolve_field(doc, &parts).and_then(|field| idx.key_of(&field)) {
+            *counts.entry(key).or_default() += 1;
+        }
+        counts
+    };
+    let counts = if parallel {
+        docs.par_iter()
+            .fold(HashMap::new, count)
+            .reduce(HashMap::new, |mut a, b| {
+                for (key, n) in b {
+                    *a.entry(key).or_default() += n;
+                }
+                a
+            })
+    } else {
+        docs.iter().fold(HashMap::new(), count)
+    };
+
+    let mut keys: Vec<Key> = counts.keys().cloned().map(Key::new).collect();
+    keys.sort_unstable();
+    let mut common: Vec<(&String, &u64)> = counts.iter().collect();
+    common.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    json!({
+        "index": name,
+        "indexed": counts.values().sum::<u64>(),
+        "cardinality": counts.len(),
+        "min": keys.first().map(Key::as_str),
+        "max": keys.last().map(Key::as_str),
+        "mostCommon": common.into_iter()
+            .take(MOST_COMMON)
+            .map(|(key, docs)| json!({ "key": key, "docs": docs }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Stats are stale after this many writes unless analyze was told otherwise:
+/// a fifth of the entries, at least one
+pub fn default_stale_after(entries: usize) -> u64 {
+    (entries as u64 / 5).max(1)
+}
+
+/// Documents the most common keys account for, with their count
+fn common(field: &Value) -> (Vec<(&str, u64)>, u64) {
+    let common: Vec<(&str, u64)> = field["mostCommon"].as_array().into_iter().flatten()
+        .filter_map(|c| Some((c["key"].as_str()?, c["docs"].as_u64()?)))
+        .collect();
+    let docs = common.iter().map(|(_, docs)| docs).sum();
+    (common, docs)
+}
+
+/// Expected share of the analyzed collection under the key `key`
+pub fn eq_share(field: &Value, entries: u64, key: &str) -> Option<f64> {
+    if entries == 0 {
+        return None;
+    }
+    let (common, common_docs) = common(field);
+    if let Some((_, docs)) = common.iter().find(|(k, _)| *k == key) {
+        return Some(*docs as f64 / entries as f64);
+    }
+    // Assume the rest spread evenly over the other keys
+    let rest_keys = field["cardinality"].as_u64()?.saturating_sub(common.len() as u64).max(1);
+    let rest_docs = field["indexed"].as_u64()?.saturating_sub(common_docs);
+    Some(rest_docs as f64 / rest_keys as f64 / entries as f64)
+}
+
+/// Expected share of the analyzed collection with a numeric key in
+/// `[low, high]` (None for an open end)
+pub fn range_share(field: &Value, entries: u64, low: Option<f64>, high: Option<f64>) -> Option<f64> {
+    let number = |v: &Value| v.as_str().and_then(|s| s.parse::<f64>().ok());
+    let (Some(min), Some(max)) = (number(&field["min"]), number(&field["max"])) else { return None };
+    if entries == 0 {
+        return None;
+    }
+    let inside = |n: f64| low.is_none_or(|low| n >= low) && high.is_none_or(|high| n <= high);
+    let (common, common_docs) = common(field);
+    let common_inside: u64 = common.iter()
+        .filter(|(key, _)| key.parse::<f64>().is_ok_and(inside))
+        .map(|(_, docs)| docs)
+        .sum();
+    // Assume the other keys spread evenly between the lowest and highest
+    let (low, high) = (low.unwrap_or(min).max(min), high.unwrap_or(max).min(max));
+    let covered = if high < low {
+        0.0
+    } else if max > min {
+        (high - low) / (max - min)
+    } else {
+        1.0
+    };
+    let rest_docs = field["indexed"].as_u64()?.saturating_sub(common_docs);
+    Some((common_inside as f64 + covered * rest_docs as f64) / entries as f64)
+}
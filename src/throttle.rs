@@ -0,0 +1,160 @@
+//! Write throttling
+//!
+//! A token bucket in front of every mutating method: each write spends one
+//! token per operation and tokens refill at the configured rate up to the
+//! burst size. An empty bucket either delays the writer (never longer than
+//! the configured maximum) or rejects it with `ERR_THROTTLED`. Reads and
+//! rollbacks never pass through here. With no limit set the check is a single
+//! relaxed atomic load, so the limiter can stay compiled into every write.
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::DbError;
+
+/// What a write does when the bucket is empty
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThrottlePolicy {
+    /// Sleep until enough tokens have refilled, up to the maximum wait
+    Block,
+    /// Fail immediately with `ERR_THROTTLED`
+    Error,
+}
+
+impl ThrottlePolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "block" => Some(ThrottlePolicy::Block),
+            "error" => Some(ThrottlePolicy::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThrottlePolicy::Block => "block",
+            ThrottlePolicy::Error => "error",
+        }
+    }
+}
+
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    /// Negative while blocked writers hold reservations
+    tokens: f64,
+    last: Instant,
+    policy: ThrottlePolicy,
+    max_wait: Duration,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last = now;
+    }
+}
+
+pub struct WriteLimiter {
+    enabled: AtomicBool,
+    bucket: Mutex<Option<Bucket>>,
+    throttled: AtomicU64,
+    waits: AtomicU64,
+    waited_ns: AtomicU64,
+}
+
+impl Default for WriteLimiter {
+    fn default() -> Self {
+        WriteLimiter {
+            enabled: AtomicBool::new(false),
+            bucket: Mutex::new(None),
+            throttled: AtomicU64::new(0),
+            waits: AtomicU64::new(0),
+            waited_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+impl WriteLimiter {
+    /// Set the limit (None removes it). The bucket starts full.
+    pub fn configure(&self, ops_per_sec: Option<u32>, burst: u32, policy: ThrottlePolicy, max_wait_ms: u32) {
+        let mut bucket = self.bucket.lock();
+        *bucket = ops_per_sec.map(|rate| Bucket {
+            rate: rate as f64,
+            burst: burst.max(1) as f64,
+            tokens: burst.max(1) as f64,
+            last: Instant::now(),
+            policy,
+            max_wait: Duration::from_millis(max_wait_ms as u64),
+        });
+        self.enabled.store(bucket.is_some(), Ordering::Release);
+    }
+
+    /// Spend `ops` tokens, waiting or failing per the policy when short
+    pub fn acquire(&self, ops: u32) -> Result<(), DbError> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let wait = {
+            let mut guard = self.bucket.lock();
+            let Some(bucket) = guard.as_mut() else {
+                return Ok(());
+            };
+            let now = Instant::now();
+            bucket.refill(now);
+            let cost = ops as f64;
+            // A batch larger than the burst only has to wait for a full bucket
+            let needed = cost.min(bucket.burst);
+            if bucket.tokens >= needed {
+                bucket.tokens -= cost;
+                return Ok(());
+            }
+            let wait = Duration::from_secs_f64((needed - bucket.tokens) / bucket.rate.max(f64::MIN_POSITIVE));
+            if bucket.policy == ThrottlePolicy::Error || wait > bucket.max_wait {
+                self.throttled.fetch_add(1, Ordering::Relaxed);
+                return Err(DbError::Throttled(format!(
+                    "Write rate limit of {} ops/sec exceeded ({:.1} of {} tokens available, {} needed)",
+                    bucket.rate, bucket.tokens.max(0.0), bucket.burst, ops
+                )));
+            }
+            // Reserve now so writers queued behind this one wait their turn
+            bucket.tokens -= cost;
+            wait
+        };
+        self.waits.fetch_add(1, Ordering::Relaxed);
+        self.waited_ns.fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+        std::thread::sleep(wait);
+        Ok(())
+    }
+
+    /// `{enabled, opsPerSec, burst, tokens, policy, maxWaitMs, throttled, waits, waitedMs}`
+    pub fn stats(&self) -> Value {
+        let counters = json!({
+            "throttled": self.throttled.load(Ordering::Relaxed),
+            "waits": self.waits.load(Ordering::Relaxed),
+            "waitedMs": self.waited_ns.load(Ordering::Relaxed) as f64 / 1e6,
+        });
+        let mut guard = self.bucket.lock();
+        let mut stats = match guard.as_mut() {
+            Some(bucket) => {
+                bucket.refill(Instant::now());
+                json!({
+                    "enabled": true,
+                    "opsPerSec": bucket.rate,
+                    "burst": bucket.burst,
+                    "tokens": bucket.tokens,
+                    "policy": bucket.policy.as_str(),
+                    "maxWaitMs": bucket.max_wait.as_millis() as u64,
+                })
+            }
+            None => json!({ "enabled": false }),
+        };
+        if let (Some(stats), Value::Object(counters)) = (stats.as_object_mut(), counters) {
+            stats.extend(counters);
+        }
+        stats
+    }
+}
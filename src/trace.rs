@@ -0,0 +1,96 @@
+//! Operation tracing
+//!
+//! With a trace callback registered, significant operations each report one
+//! span once they finish: `{op, path, durationUs, itemsScanned, itemsReturned,
+//! parallel, error}`. Spans are only emitted after the operation has released
+//! its locks. They go into a queue that is drained onto the event loop, one
+//! callback delivery per drain however many spans were queued, so threads
+//! finishing work at the same time never wait on each other or on JS.
+//!
+//! With no callback registered, the only cost is one atomic load per
+//! operation.
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Default size a `get` result must reach to be traced
+pub const DEFAULT_GET_THRESHOLD_BYTES: usize = 64 * 1024;
+
+/// What an operation reports about its work
+#[derive(Default)]
+pub struct Span {
+    pub scanned: Option<usize>,
+    pub returned: Option<usize>,
+    pub parallel: bool,
+}
+
+impl Span {
+    /// Count `result` as what was returned: its entries for an object or
+    /// array, otherwise one item
+    pub fn returned(&mut self, result: &Value) {
+        self.returned = Some(items(Some(result)));
+    }
+}
+
+/// Entries of a collection, 0 for a missing one
+pub fn items(value: Option<&Value>) -> usize {
+    match value {
+        Some(Value::Object(map)) => map.len(),
+        Some(Value::Array(items)) => items.len(),
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub struct Tracer {
+    deliver: ThreadsafeFunction<(), ErrorStrategy::Fatal>,
+    queue: Arc<Mutex<Vec<Value>>>,
+    /// A delivery is queued on the event loop and will drain `queue`
+    scheduled: Arc<AtomicBool>,
+    pub get_threshold: usize,
+}
+
+impl Tracer {
+    pub fn new(env: &Env, callback: JsFunction, get_threshold: usize) -> Result<Self> {
+        // Called once per drained batch; a throwing callback loses its own span only
+        let each: JsFunction = env.run_script(
+            "(fn) => (spans) => { for (const span of spans) { try { fn(span); } catch {} } }",
+        )?;
+        let each: JsFunction = each.call(None, &[callback])?.try_into()?;
+        let queue: Arc<Mutex<Vec<Value>>> = Arc::new(Mutex::new(Vec::new()));
+        let scheduled = Arc::new(AtomicBool::new(false));
+        let (pending, flag) = (Arc::clone(&queue), Arc::clone(&scheduled));
+        let mut deliver: ThreadsafeFunction<(), ErrorStrategy::Fatal> = each.create_threadsafe_function(0, move |ctx: ThreadSafeCallContext<()>| {
+            // Cleared before draining: a span queued after this is either in
+            // the drain or schedules the next delivery
+            flag.store(false, Ordering::Release);
+            let spans = std::mem::take(&mut *pending.lock());
+            Ok(vec![ctx.env.to_js_value(&Value::Array(spans))?])
+        })?;
+        deliver.unref(env)?;
+        Ok(Tracer { deliver, queue, scheduled, get_threshold })
+    }
+
+    /// Queue the span of `op` on `path`, started at `started`
+    pub fn emit(&self, op: &str, path: &str, started: Instant, span: &Span, error: Option<&str>) {
+        let event = json!({
+            "op": op,
+            "path": path,
+            "durationUs": started.elapsed().as_micros() as u64,
+            "itemsScanned": span.scanned,
+            "itemsReturned": span.returned,
+            "parallel": span.parallel,
+            "error": error,
+        });
+        self.queue.lock().push(event);
+        if !self.scheduled.swap(true, Ordering::AcqRel) {
+            self.deliver.call((), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+}
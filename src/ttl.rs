@@ -0,0 +1,194 @@
+//! TTL indexes
+//!
+//! `register_ttl_index` indexes an object collection on an epoch-millisecond
+//! field and sweeps it from a background thread, one job per index: a sweep
+//! range-scans the index for keys below now and deletes those documents with
+//! reason "ttl" under one write lock, WAL-logged and with cascade rules and
+//! other indexes kept up to date as for `delete_many`. Documents whose field
+//! is missing or not a number never expire.
+//!
+//! Between sweeps `get` and `parallel_query` leave expired documents out, so
+//! a reader never sees one whatever the sweep interval. Sweeps skip frozen or
+//! leased documents and do not run while a transaction or bulk load is open;
+//! those documents stay hidden and are deleted by a later sweep.
+
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::compress;
+use crate::{now_ms, resolve_field, split_field};
+
+/// Sweep interval when none is given
+pub const DEFAULT_SWEEP_INTERVAL_MS: u64 = 1000;
+
+/// Documents one sweep deletes at most, bounding how long it holds the write lock
+pub const MAX_SWEEP_DOCS: usize = 10_000;
+
+pub struct TtlIndex {
+    pub collection: String,
+    pub field: String,
+    parts: Vec<String>,
+    pub interval_ms: u64,
+    sweeps: u64,
+    expired: u64,
+    skipped: u64,
+    last_sweep_at: Option<u64>,
+    last_error: Option<String>,
+}
+
+/// Whether `doc` has an expiry before `now` in one of `fields`
+pub fn expired(doc: &Value, fields: &[Vec<String>], now: u64) -> bool {
+    fields.iter().any(|parts| {
+        resolve_field(doc, parts).and_then(|at| at.as_f64()).is_some_and(|at| at < now as f64)
+    })
+}
+
+/// Index name of the TTL index on `field` of `collection`
+pub fn index_name(collection: &str, field: &str) -> String {
+    format!("ttl.{}.{}", collection, field)
+}
+
+#[derive(Default)]
+pub struct TtlIndexes {
+    /// Any TTL index is registered; reads skip the lookup otherwise
+    enabled: AtomicBool,
+    /// By index name
+    indexes: RwLock<BTreeMap<String, TtlIndex>>,
+    hidden: AtomicU64,
+}
+
+impl TtlIndexes {
+    /// Register (or re-register with a new interval) the TTL index `name`
+    pub fn register(&self, name: &str, collection: &str, field: &str, interval_ms: u64) {
+        let mut indexes = self.indexes.write();
+        match indexes.get_mut(name) {
+            Some(existing) => existing.interval_ms = interval_ms,
+            None => {
+                indexes.insert(name.to_string(), TtlIndex {
+                    collection: collection.to_string(),
+                    field: field.to_string(),
+                    parts: split_field(field),
+                    interval_ms,
+                    sweeps: 0,
+                    expired: 0,
+                    skipped: 0,
+                    last_sweep_at: None,
+                    last_error: None,
+                });
+            }
+        }
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        let mut indexes = self.indexes.write();
+        let removed = indexes.remove(name).is_some();
+        self.enabled.store(!indexes.is_empty(), Ordering::Release);
+        removed
+    }
+
+    /// Collection and field of the TTL index `name`
+    pub fn get(&self, name: &str) -> Option<(String, Vec<String>)> {
+        self.indexes.read().get(name).map(|ttl| (ttl.collection.clone(), ttl.parts.clone()))
+    }
+
+    /// Expiry fields of the TTL indexes on `collection`
+    pub fn fields_of(&self, collection: &str) -> Vec<Vec<String>> {
+        if !self.enabled.load(Ordering::Acquire) {
+            return Vec::new();
+        }
+        self.indexes.read().values()
+            .filter(|ttl| ttl.collection == collection)
+            .map(|ttl| ttl.parts.clone())
+            .collect()
+    }
+
+    /// Record what a sweep of `name` did
+    pub fn swept(&self, name: &str, result: &Result<(usize, usize), String>) {
+        let mut indexes = self.indexes.write();
+        let Some(ttl) = indexes.get_mut(name) else { return };
+        ttl.sweeps += 1;
+        ttl.last_sweep_at = Some(now_ms());
+        match result {
+            Ok((expired, skipped)) => {
+                ttl.expired += *expired as u64;
+                ttl.skipped += *skipped as u64;
+                ttl.last_error = None;
+            }
+            Err(error) => ttl.last_error = Some(error.clone()),
+        }
+    }
+
+    /// Leave the expired documents out of `value`, read at `path`. Returns
+    /// false if `value` is, or is inside, an expired document.
+    pub fn hide(&self, data: &Value, path: &str, value: &mut Value) -> bool {
+        if !self.enabled.load(Ordering::Acquire) {
+            return true;
+        }
+        let now = now_ms();
+        let indexes = self.indexes.read();
+        for ttl in indexes.values() {
+            let fields = std::slice::from_ref(&ttl.parts);
+            if path == ttl.collection {
+                self.retain_live(value, fields, now);
+            } else if let Some(rest) = path.strip_prefix(ttl.collection.as_str()).and_then(|r| r.strip_prefix('.')) {
+                let doc = match rest.split_once('.') {
+                    None => Some(Cow::Borrowed(&*value)),
+                    Some((key, _)) => compress::pointer(data, &format!("/{}/{}", ttl.collection.replace('.', "/"), key)),
+                };
+                if doc.is_some_and(|doc| expired(&doc, fields, now)) {
+                    self.hidden.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+            } else if path.is_empty() || ttl.collection.strip_prefix(path).is_some_and(|r| r.starts_with('.')) {
+                let rel = if path.is_empty() { &ttl.collection[..] } else { &ttl.collection[path.len() + 1..] };
+                if let Some(collection) = value.pointer_mut(&format!("/{}", rel.replace('.', "/"))) {
+                    self.retain_live(collection, fields, now);
+                }
+            }
+        }
+        true
+    }
+
+    fn retain_live(&self, collection: &mut Value, fields: &[Vec<String>], now: u64) {
+        if let Value::Object(docs) = collection {
+            let before = docs.len();
+            docs.retain(|_, doc| !expired(doc, fields, now));
+            self.hidden.fetch_add((before - docs.len()) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Count documents a query left out as expired
+    pub fn note_hidden(&self, docs: usize) {
+        self.hidden.fetch_add(docs as u64, Ordering::Relaxed);
+    }
+
+    /// `[{index, collection, field, sweepIntervalMs, sweeps, expired, skipped,
+    /// lastSweepAt, lastError}]` in index name order
+    pub fn list(&self) -> Value {
+        Value::Array(self.indexes.read().iter().map(|(name, ttl)| json!({
+            "index": name,
+            "collection": ttl.collection,
+            "field": ttl.field,
+            "sweepIntervalMs": ttl.interval_ms,
+            "sweeps": ttl.sweeps,
+            "expired": ttl.expired,
+            "skipped": ttl.skipped,
+            "lastSweepAt": ttl.last_sweep_at,
+            "lastError": ttl.last_error,
+        })).collect())
+    }
+
+    /// `{indexes, expired, hidden}`
+    pub fn stats(&self) -> Value {
+        let indexes = self.indexes.read();
+        json!({
+            "indexes": indexes.len(),
+            "expired": indexes.values().map(|ttl| ttl.expired).sum::<u64>(),
+            "hidden": self.hidden.load(Ordering::Relaxed),
+        })
+    }
+}
@@ -0,0 +1,263 @@
+//! Transaction undo log
+//!
+//! Keeps the first old value of every path written since the last savepoint
+//! boundary, tracks the approximate memory it holds, and either rejects or
+//! spills entries to a temp file once `max_undo_bytes` would be exceeded.
+//! Appending to an array records its old length instead of a copy, so
+//! pushes onto a large array cost the same to undo as onto a small one.
+//! An object key also records its position, so a rollback puts a removed
+//! key back where it was instead of last.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::{approx_size, paths_overlap};
+use crate::error::DbError;
+
+/// Undo memory limits
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UndoLimits {
+    /// Maximum bytes of old values held in memory (None = unlimited)
+    pub max_bytes: Option<usize>,
+    /// Spill to disk instead of failing once `max_bytes` is reached
+    pub spill: bool,
+}
+
+/// What rolling back an entry does at its path
+#[derive(Debug, Clone, PartialEq)]
+pub enum Restore {
+    /// Put the old value back (None: delete the path)
+    Value(Option<Value>),
+    /// Put the old value of an object key back, at this position among its
+    /// siblings if the key has been removed since
+    Key(Value, usize),
+    /// Truncate the array there to this length, dropping what was appended
+    Truncate(usize),
+}
+
+enum UndoValue {
+    Inline(Option<Value>),
+    Spilled { offset: u64, len: u64 },
+    Truncate(usize),
+}
+
+struct UndoEntry {
+    path: String,
+    value: UndoValue,
+    /// Position of the key in its parent object, when it had one
+    position: Option<usize>,
+    bytes: usize,
+}
+
+fn restore(value: Option<Value>, position: Option<usize>) -> Restore {
+    match (value, position) {
+        (Some(value), Some(position)) => Restore::Key(value, position),
+        (value, _) => Restore::Value(value),
+    }
+}
+
+/// Append-only temp file holding spilled undo values; removed on drop
+struct SpillFile {
+    file: File,
+    path: String,
+    end: u64,
+}
+
+impl SpillFile {
+    fn create(path: String) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        Ok(SpillFile { file, path, end: 0 })
+    }
+
+    fn write(&mut self, value: &Option<Value>) -> io::Result<(u64, u64)> {
+        let bytes = serde_json::to_vec(value)?;
+        let offset = self.end;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&bytes)?;
+        self.end += bytes.len() as u64;
+        Ok((offset, bytes.len() as u64))
+    }
+
+    fn read(&mut self, offset: u64, len: u64) -> io::Result<Option<Value>> {
+        let mut buf = vec![0u8; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(serde_json::from_slice(&buf)?)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub struct UndoLog {
+    entries: Vec<UndoEntry>,
+    inline_bytes: usize,
+    spilled_bytes: u64,
+    spill: Option<SpillFile>,
+    spill_path: String,
+    /// path -> index of its old value entry, used to skip repeat writes since `boundary`
+    first_index: HashMap<String, usize>,
+    /// path -> index of its Truncate entry, used to skip repeat appends
+    first_truncate: HashMap<String, usize>,
+    boundary: usize,
+}
+
+impl UndoLog {
+    pub fn new(spill_path: String) -> Self {
+        UndoLog {
+            entries: Vec::new(),
+            inline_bytes: 0,
+            spilled_bytes: 0,
+            spill: None,
+            spill_path,
+            first_index: HashMap::new(),
+            first_truncate: HashMap::new(),
+            boundary: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn inline_bytes(&self) -> usize {
+        self.inline_bytes
+    }
+
+    pub fn spilled_bytes(&self) -> u64 {
+        self.spilled_bytes
+    }
+
+    /// Start a new dedupe window (called when a savepoint is created)
+    pub fn mark_boundary(&mut self) {
+        self.boundary = self.entries.len();
+    }
+
+    fn in_window(&self, index: Option<&usize>) -> bool {
+        matches!(index, Some(&i) if i >= self.boundary && i < self.entries.len())
+    }
+
+    /// Whether `path` already has an old value entry in the current dedupe window
+    pub fn has_entry_for(&self, path: &str) -> bool {
+        self.in_window(self.first_index.get(path))
+    }
+
+    /// Record that the array at `path` had `len` elements before an append.
+    /// An earlier entry for `path` in the window already undoes it. A later
+    /// write that changes existing elements still records the old value,
+    /// which rollback restores before truncating.
+    pub fn push_truncate(&mut self, path: &str, len: usize) {
+        if self.has_entry_for(path) || self.in_window(self.first_truncate.get(path)) {
+            return;
+        }
+        let bytes = path.len() + std::mem::size_of::<usize>();
+        self.inline_bytes += bytes;
+        self.first_truncate.insert(path.to_string(), self.entries.len());
+        self.entries.push(UndoEntry { path: path.to_string(), value: UndoValue::Truncate(len), position: None, bytes });
+    }
+
+    /// Record the old value of `path`, and its position if it is an object key
+    pub fn push(&mut self, path: &str, old_value: Option<Value>, position: Option<usize>, limits: UndoLimits) -> Result<(), DbError> {
+        if self.has_entry_for(path) {
+            return Ok(());
+        }
+
+        let bytes = old_value.as_ref().map(approx_size).unwrap_or(0) + path.len();
+        let over_limit = limits.max_bytes.is_some_and(|max| self.inline_bytes + bytes > max);
+
+        let value = if over_limit {
+            if !limits.spill {
+                return Err(DbError::LimitExceeded(format!(
+                    "Transaction too large: undo log would exceed {} bytes",
+                    limits.max_bytes.unwrap_or(0)
+                )));
+            }
+            if self.spill.is_none() {
+                self.spill = Some(SpillFile::create(self.spill_path.clone())?);
+            }
+            let (offset, len) = self.spill.as_mut().unwrap().write(&old_value)?;
+            self.spilled_bytes += len;
+            UndoValue::Spilled { offset, len }
+        } else {
+            self.inline_bytes += bytes;
+            UndoValue::Inline(old_value)
+        };
+
+        self.first_index.insert(path.to_string(), self.entries.len());
+        self.entries.push(UndoEntry { path: path.to_string(), value, position, bytes });
+        Ok(())
+    }
+
+    /// Distinct recorded paths in first-touch order
+    pub fn paths(&self) -> Vec<&str> {
+        let mut seen = std::collections::HashSet::new();
+        self.entries.iter()
+            .map(|e| e.path.as_str())
+            .filter(|p| seen.insert(*p))
+            .collect()
+    }
+
+    /// Whether any entry is at, above or below `path`
+    pub fn touches(&self, path: &str) -> bool {
+        self.entries.iter().any(|e| paths_overlap(&e.path, path))
+    }
+
+    /// Entries at, above or below `path`, newest first
+    pub fn overlapping(&mut self, path: &str) -> Result<Vec<(String, Restore)>, DbError> {
+        let mut out = Vec::new();
+        for entry in self.entries.iter().rev().filter(|e| paths_overlap(&e.path, path)) {
+            let restore = match &entry.value {
+                UndoValue::Inline(v) => restore(v.clone(), entry.position),
+                UndoValue::Spilled { offset, len } => restore(match self.spill.as_mut() {
+                    Some(spill) => spill.read(*offset, *len)?,
+                    None => None,
+                }, entry.position),
+                UndoValue::Truncate(len) => Restore::Truncate(*len),
+            };
+            out.push((entry.path.clone(), restore));
+        }
+        Ok(out)
+    }
+
+    /// Remove and return the newest entry, reading it back from disk if spilled
+    pub fn pop(&mut self) -> Result<Option<(String, Restore)>, DbError> {
+        let Some(entry) = self.entries.pop() else { return Ok(None) };
+        let index = self.entries.len();
+        for first in [&mut self.first_index, &mut self.first_truncate] {
+            if first.get(&entry.path) == Some(&index) {
+                first.remove(&entry.path);
+            }
+        }
+        let value = match entry.value {
+            UndoValue::Inline(v) => {
+                self.inline_bytes -= entry.bytes;
+                restore(v, entry.position)
+            }
+            UndoValue::Spilled { offset, len } => {
+                self.spilled_bytes -= len;
+                restore(match self.spill.as_mut() {
+                    Some(spill) => spill.read(offset, len)?,
+                    None => None,
+                }, entry.position)
+            }
+            UndoValue::Truncate(len) => {
+                self.inline_bytes -= entry.bytes;
+                Restore::Truncate(len)
+            }
+        };
+        if self.boundary > self.entries.len() {
+            self.boundary = self.entries.len();
+        }
+        Ok(Some((entry.path, value)))
+    }
+}
@@ -0,0 +1,97 @@
+//! Index upkeep
+//!
+//! An index registered with a collection keeps itself in step with the data.
+//! Writes only mark the touched document keys dirty, as for counters; before
+//! the index is next read or saved, the indexed field of just those documents
+//! is extracted again and the entry inserted, moved or removed. Replacing or
+//! removing the collection itself (or an ancestor) re-indexes all of it, and
+//! so does every write to an array collection, since a removal shifts every
+//! later position.
+
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::btree::BTreeIndex;
+use crate::{paths_overlap, resolve_field, split_field};
+
+pub struct Upkeep {
+    pub collection: String,
+    /// Keys of documents written since the last refresh
+    dirty: HashSet<String>,
+    /// The collection itself (or an ancestor) was replaced
+    rescan: bool,
+}
+
+impl Upkeep {
+    /// `rescan` when the index's current entries can't be trusted
+    pub fn new(collection: String, rescan: bool) -> Self {
+        Upkeep { collection, dirty: HashSet::new(), rescan }
+    }
+
+    /// Record a write at `path`
+    pub fn note_write(&mut self, path: &str) {
+        if !paths_overlap(&self.collection, path) {
+            return;
+        }
+        match path.strip_prefix(self.collection.as_str()).and_then(|rest| rest.strip_prefix('.')) {
+            Some(rest) => {
+                let key = rest.split('.').next().unwrap_or(rest);
+                self.dirty.insert(key.to_string());
+            }
+            None => self.rescan = true,
+        }
+    }
+
+    /// Force a full re-index on the next refresh
+    pub fn invalidate(&mut self) {
+        self.rescan = true;
+    }
+
+    pub fn is_clean(&self) -> bool {
+        !self.rescan && self.dirty.is_empty()
+    }
+
+    fn doc_path(&self, key: &str) -> String {
+        format!("{}.{}", self.collection, key)
+    }
+
+    /// Bring `idx` up to date with `collection` (the current value at the
+    /// collection path). Documents without the field are left out; a null
+    /// field is indexed as null, unless the index is sparse.
+    pub fn refresh(&mut self, idx: &mut BTreeIndex, collection: Option<&Value>) {
+        if self.is_clean() {
+            return;
+        }
+        let parts = split_field(idx.field());
+        let dirty = std::mem::take(&mut self.dirty);
+        match collection {
+            Some(Value::Object(map)) if !self.rescan => {
+                for key in dirty {
+                    let path = self.doc_path(&key);
+                    match map.get(&key).and_then(|doc| resolve_field(doc, &parts)) {
+                        Some(field) => idx.insert(&field, path),
+                        None => idx.remove(&Value::Null, &path),
+                    }
+                }
+            }
+            Some(Value::Object(map)) => {
+                idx.clear();
+                for (key, doc) in map {
+                    if let Some(field) = resolve_field(doc, &parts) {
+                        idx.insert(&field, self.doc_path(key));
+                    }
+                }
+            }
+            Some(Value::Array(items)) => {
+                idx.clear();
+                for (i, doc) in items.iter().enumerate() {
+                    if let Some(field) = resolve_field(doc, &parts) {
+                        idx.insert(&field, self.doc_path(&i.to_string()));
+                    }
+                }
+            }
+            _ => idx.clear(),
+        }
+        self.rescan = false;
+    }
+}
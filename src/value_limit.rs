@@ -0,0 +1,154 @@
+//! Value size limits
+//!
+//! A write whose value would serialize to more than the configured number of
+//! bytes is refused with `ERR_LIMIT` before any lock is taken, or in "warn"
+//! mode let through and reported to the trace callback, for rolling a limit
+//! out gradually. A limit applies under its path prefix; the longest
+//! configured prefix wins, the empty prefix being the global limit.
+//!
+//! The size is that of the compact JSON, except that string escapes are not
+//! counted, and the count stops as soon as it passes the limit: refusing a
+//! 400MB value costs no more than refusing one just over.
+
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::error::DbError;
+use crate::path_within;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitMode {
+    Reject,
+    Warn,
+}
+
+impl LimitMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "reject" => Some(LimitMode::Reject),
+            "warn" => Some(LimitMode::Warn),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LimitMode::Reject => "reject",
+            LimitMode::Warn => "warn",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Limit {
+    pub max_bytes: usize,
+    pub mode: LimitMode,
+}
+
+#[derive(Default)]
+pub struct ValueLimits {
+    /// Any limit is configured; writes skip the lookup otherwise
+    enabled: AtomicBool,
+    limits: RwLock<HashMap<String, Limit>>,
+    rejected: AtomicU64,
+    warned: AtomicU64,
+}
+
+impl ValueLimits {
+    /// Set (or with None remove) the limit under `prefix`
+    pub fn set(&self, prefix: String, limit: Option<Limit>) {
+        let mut limits = self.limits.write();
+        match limit {
+            Some(limit) => limits.insert(prefix, limit),
+            None => limits.remove(&prefix),
+        };
+        self.enabled.store(!limits.is_empty(), Ordering::Release);
+    }
+
+    fn limit_for(&self, path: &str) -> Option<Limit> {
+        let limits = self.limits.read();
+        limits.iter()
+            .filter(|(prefix, _)| path_within(path, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, limit)| *limit)
+    }
+
+    /// Check `value` written at `path`. Over a "warn" limit this returns the
+    /// warning to report instead of failing.
+    pub fn check(&self, path: &str, value: &Value) -> Result<Option<String>, DbError> {
+        if !self.enabled.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let Some(limit) = self.limit_for(path) else { return Ok(None) };
+        let Some(estimate) = estimate_over(value, limit.max_bytes) else { return Ok(None) };
+        let message = format!(
+            "Value for '{}' is at least {} bytes, over the {} byte limit", path, estimate, limit.max_bytes
+        );
+        match limit.mode {
+            LimitMode::Reject => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(DbError::LimitExceeded(message))
+            }
+            LimitMode::Warn => {
+                self.warned.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(message))
+            }
+        }
+    }
+
+    /// `{limits: {prefix: {maxBytes, mode}}, rejected, warned}`, "" being the global limit
+    pub fn stats(&self) -> Value {
+        let limits = self.limits.read();
+        json!({
+            "limits": limits.iter()
+                .map(|(prefix, limit)| (prefix.clone(), json!({ "maxBytes": limit.max_bytes, "mode": limit.mode.as_str() })))
+                .collect::<serde_json::Map<_, _>>(),
+            "rejected": self.rejected.load(Ordering::Relaxed),
+            "warned": self.warned.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// The estimated size of `value` once it passes `max_bytes`, or None if it never does
+pub fn estimate_over(value: &Value, max_bytes: usize) -> Option<usize> {
+    let mut total = 0;
+    (!add_size(value, &mut total, max_bytes)).then_some(total)
+}
+
+/// Add the compact JSON size of `value` to `total`; false once past `max`
+fn add_size(value: &Value, total: &mut usize, max: usize) -> bool {
+    match value {
+        Value::Null => *total += 4,
+        Value::Bool(b) => *total += if *b { 4 } else { 5 },
+        Value::Number(n) => *total += match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => digits(u),
+            (None, Some(i)) => 1 + digits(i.unsigned_abs()),
+            _ => n.to_string().len(),
+        },
+        Value::String(s) => *total += s.len() + 2,
+        Value::Array(items) => {
+            *total += 2 + items.len().saturating_sub(1);
+            for item in items {
+                if !add_size(item, total, max) {
+                    return false;
+                }
+            }
+        }
+        Value::Object(map) => {
+            *total += 2 + map.len().saturating_sub(1);
+            for (key, item) in map {
+                *total += key.len() + 3;
+                if !add_size(item, total, max) {
+                    return false;
+                }
+            }
+        }
+    }
+    *total <= max
+}
+
+fn digits(n: u64) -> usize {
+    n.checked_ilog10().map_or(1, |d| d as usize + 1)
+}
@@ -8,22 +8,39 @@
 //! - LENGTH: Length of DATA
 //! - DATA: JSON-encoded operation
 
-use crossbeam::channel::{bounded, Sender, Receiver, RecvTimeoutError};
+use crossbeam::channel::{bounded, Sender, Receiver, RecvTimeoutError, TrySendError};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, Map};
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write, Read};
+use std::io::{BufWriter, Write, Read, Seek, SeekFrom};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::io;
 
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+use crate::path_rules::PathRules;
+
 /// WAL operation types
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum WalOpType {
     Set,
     Delete,
+    /// Transaction markers: no path or value, `txn` names the transaction.
+    /// Recovery applies a transaction's operations only if its Commit was
+    /// logged.
+    Begin,
+    Commit,
+    Abort,
+}
+
+impl WalOpType {
+    pub fn is_marker(&self) -> bool {
+        matches!(self, WalOpType::Begin | WalOpType::Commit | WalOpType::Abort)
+    }
 }
 
 /// Single WAL operation
@@ -33,18 +50,57 @@ pub struct WalOp {
     pub op_type: WalOpType,
     pub path: String,
     pub value: Option<Value>,
+    /// Caller-supplied origin of the write (user, request id); ignored by recovery
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Value>,
+    /// Why a delete happened when not requested directly ("ttl", "quota", "cascade")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Transaction the operation belongs to; None for writes outside one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub txn: Option<u32>,
 }
 
 /// WAL command types for channel
 pub enum WalCmd {
-    Write { lsn: u64, op: WalOp },
+    Write { lsn: u64, op: Box<WalOp> },
     Sync { tx: std::sync::mpsc::Sender<()> },
     #[allow(dead_code)]
     Flush,
-    #[allow(dead_code)]
     Shutdown,
 }
 
+/// What `append` does when the commit thread's queue is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for room (time spent waiting is counted)
+    Block,
+    /// Fail with `ErrorKind::WouldBlock`; the caller may retry
+    Error,
+    /// Discard the oldest queued write to make room. Those writes are lost on
+    /// a crash, so this only suits Lazy durability.
+    DropOldest,
+}
+
+impl OverflowPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "block" => Some(OverflowPolicy::Block),
+            "error" => Some(OverflowPolicy::Error),
+            "drop_oldest" => Some(OverflowPolicy::DropOldest),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverflowPolicy::Block => "block",
+            OverflowPolicy::Error => "error",
+            OverflowPolicy::DropOldest => "drop_oldest",
+        }
+    }
+}
+
 /// WAL configuration
 #[derive(Clone, Copy)]
 pub struct WalConfig {
@@ -54,6 +110,9 @@ pub struct WalConfig {
     pub flush_interval_ms: u64,
     /// Whether to fsync (false = group write, true = group commit)
     pub fsync: bool,
+    /// Commands queued for the commit thread before `overflow_policy` applies
+    pub queue_capacity: usize,
+    pub overflow_policy: OverflowPolicy,
 }
 
 impl Default for WalConfig {
@@ -62,54 +121,135 @@ impl Default for WalConfig {
             batch_size: 1000,
             flush_interval_ms: 10,
             fsync: true,
+            queue_capacity: 100000,
+            overflow_policy: OverflowPolicy::Block,
         }
     }
 }
 
+/// Queue depth and overflow counters, for shedding load before the queue fills
+#[derive(Debug, Clone, Copy)]
+pub struct WalPressure {
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    pub policy: OverflowPolicy,
+    /// Appends that had to wait for room, and the total time they waited
+    pub blocked_appends: u64,
+    pub blocked_ns: u64,
+    /// Appends failed under the "error" policy
+    pub rejected: u64,
+    /// Queued writes discarded under the "drop_oldest" policy
+    pub dropped: u64,
+}
+
 /// Group Commit WAL implementation
 pub struct GroupCommitWAL {
     cmd_tx: Sender<WalCmd>,
+    /// Lets `append` discard the oldest queued write under DropOldest
+    cmd_rx: Receiver<WalCmd>,
     committed_lsn: Arc<AtomicU64>,
     _next_lsn: Arc<AtomicU64>,
+    policy: OverflowPolicy,
+    blocked_appends: AtomicU64,
+    blocked_ns: AtomicU64,
+    rejected: AtomicU64,
+    dropped: AtomicU64,
 }
 
 impl GroupCommitWAL {
     /// Create new WAL with background commit thread
     pub fn new(wal_path: &str, config: WalConfig) -> io::Result<Self> {
-        let (cmd_tx, cmd_rx) = bounded(100000);
+        let (cmd_tx, cmd_rx) = bounded(config.queue_capacity.max(1));
         let committed_lsn = Arc::new(AtomicU64::new(0));
         let next_lsn = Arc::new(AtomicU64::new(1));
         
         let committed_lsn_clone = committed_lsn.clone();
         let _next_lsn_clone = next_lsn.clone();
         let path = wal_path.to_string();
+        let thread_rx = cmd_rx.clone();
         
         std::thread::spawn(move || {
-            Self::commit_thread(path, cmd_rx, committed_lsn_clone, _next_lsn_clone, config);
+            Self::commit_thread(path, thread_rx, committed_lsn_clone, _next_lsn_clone, config);
         });
         
         Ok(GroupCommitWAL {
             cmd_tx,
+            cmd_rx,
             committed_lsn,
             _next_lsn: next_lsn,
+            policy: config.overflow_policy,
+            blocked_appends: AtomicU64::new(0),
+            blocked_ns: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
         })
     }
     
-    /// Append operation to WAL (non-blocking)
+    /// Continue numbering after `lsn`, handed out by an earlier process or
+    /// before a save. Must be called before the first append.
+    pub fn resume_after(&self, lsn: u64) {
+        self._next_lsn.fetch_max(lsn + 1, Ordering::SeqCst);
+        self.committed_lsn.fetch_max(lsn, Ordering::Release);
+    }
+
+    /// Append operation to WAL. Non-blocking until the queue is full; then the
+    /// overflow policy decides.
     pub fn append(&self, op: WalOp) -> io::Result<u64> {
         let lsn = self._next_lsn.fetch_add(1, Ordering::SeqCst);
+        let stopped = || io::Error::other("WAL thread stopped");
         
-        self.cmd_tx.send(WalCmd::Write { lsn, op })
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "WAL thread stopped"))?;
-            
+        let mut cmd = match self.cmd_tx.try_send(WalCmd::Write { lsn, op: Box::new(op) }) {
+            Ok(()) => return Ok(lsn),
+            Err(TrySendError::Disconnected(_)) => return Err(stopped()),
+            Err(TrySendError::Full(cmd)) => cmd,
+        };
+        match self.policy {
+            OverflowPolicy::Block => {
+                let started = Instant::now();
+                self.cmd_tx.send(cmd).map_err(|_| stopped())?;
+                self.blocked_appends.fetch_add(1, Ordering::Relaxed);
+                self.blocked_ns.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            }
+            OverflowPolicy::Error => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "WAL queue is full"));
+            }
+            OverflowPolicy::DropOldest => loop {
+                match self.cmd_rx.try_recv() {
+                    Ok(WalCmd::Write { .. }) => {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    // Control commands are never dropped; they move behind the newer writes
+                    Ok(control) => self.cmd_tx.send(control).map_err(|_| stopped())?,
+                    Err(_) => {}
+                }
+                cmd = match self.cmd_tx.try_send(cmd) {
+                    Ok(()) => break,
+                    Err(TrySendError::Disconnected(_)) => return Err(stopped()),
+                    Err(TrySendError::Full(cmd)) => cmd,
+                };
+            },
+        }
         Ok(lsn)
     }
+
+    pub fn pressure(&self) -> WalPressure {
+        WalPressure {
+            queue_depth: self.cmd_tx.len(),
+            queue_capacity: self.cmd_tx.capacity().unwrap_or(usize::MAX),
+            policy: self.policy,
+            blocked_appends: self.blocked_appends.load(Ordering::Relaxed),
+            blocked_ns: self.blocked_ns.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
     
     /// Wait for all operations up to current point to be committed
     pub fn sync(&self) -> io::Result<()> {
         let (tx, rx) = std::sync::mpsc::channel();
         self.cmd_tx.send(WalCmd::Sync { tx })
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "WAL thread stopped"))?;
+            .map_err(|_| io::Error::other("WAL thread stopped"))?;
         
         rx.recv_timeout(Duration::from_secs(5))
             .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "WAL sync timeout"))?;
@@ -117,12 +257,20 @@ impl GroupCommitWAL {
         Ok(())
     }
     
+    /// Wait until the operation with the given LSN is committed
+    pub fn wait_for_lsn(&self, lsn: u64) -> io::Result<()> {
+        if self.committed_lsn() >= lsn {
+            return Ok(());
+        }
+        self.sync()
+    }
+    
     /// Force immediate flush
     #[allow(dead_code)]
     /// Force immediate flush
     pub fn flush(&self) -> io::Result<()> {
         self.cmd_tx.send(WalCmd::Flush)
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "WAL thread stopped"))?;
+            .map_err(|_| io::Error::other("WAL thread stopped"))?;
         Ok(())
     }
     
@@ -131,7 +279,6 @@ impl GroupCommitWAL {
         self.committed_lsn.load(Ordering::Acquire)
     }
     
-    #[allow(dead_code)]
     /// Shutdown WAL thread
     pub fn shutdown(&self) -> io::Result<()> {
         let _ = self.cmd_tx.send(WalCmd::Shutdown);
@@ -171,7 +318,7 @@ impl GroupCommitWAL {
             while batch.len() < config.batch_size {
                 match rx.recv_timeout(timeout) {
                     Ok(WalCmd::Write { lsn, op }) => {
-                        batch.push((lsn, op));
+                        batch.push((lsn, *op));
                     }
                     Ok(WalCmd::Sync { tx }) => {
                         // Flush immediately and signal completion
@@ -247,8 +394,9 @@ impl GroupCommitWAL {
             max_lsn = *lsn;
         }
         
-        // Single write syscall
-        if let Err(e) = writer.write_all(&buf) {
+        // Single write syscall; push it past the BufWriter so a crash
+        // after the LSN is committed cannot lose it
+        if let Err(e) = writer.write_all(&buf).and_then(|_| writer.flush()) {
             eprintln!("WAL write error: {}", e);
             return;
         }
@@ -266,20 +414,99 @@ impl GroupCommitWAL {
     }
 }
 
-/// Recover database state from WAL
-pub fn recover_from_wal(wal_path: &str, data: &mut Value) -> io::Result<u64> {
-    if !Path::new(wal_path).exists() {
-        return Ok(0);
-    }
-    
+/// What a WAL replay did
+#[derive(Debug, Default, Clone)]
+pub struct RecoveryStats {
+    /// Intact records read from the log
+    pub ops_read: u64,
+    /// Operations applied after compaction
+    pub ops_applied: u64,
+    /// First and last LSN read (0 if none)
+    pub first_lsn: u64,
+    pub last_lsn: u64,
+    /// Why replay stopped before the end of the file (truncation, CRC, decode)
+    pub stopped_early: Option<String>,
+    /// Set records skipped because their path breaks the path rules
+    pub rejected_paths: u64,
+    /// Operations of transactions that were aborted or never committed
+    pub uncommitted_ops: u64,
+    /// Begin, Commit and Abort records
+    pub txn_markers: u64,
+    /// Operations were applied in parallel across top-level keys
+    pub parallel: bool,
+    /// Top-level key groups applied (parallel only)
+    pub groups: u64,
+    /// Root writes applied sequentially between parallel phases
+    pub barriers: u64,
+    pub read_ms: f64,
+    pub apply_ms: f64,
+    /// Time spent applying groups, summed across threads
+    pub group_ms: f64,
+}
+
+/// Where a running WAL replay is. Records are read first (`bytes_read` grows),
+/// then the surviving operations are applied (`ops_replayed` grows).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReplayProgress {
+    pub ops_replayed: u64,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+}
+
+/// Records read or applied between progress reports
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// Replays with fewer operations are applied sequentially unless asked otherwise
+const PARALLEL_MIN_OPS: usize = 10_000;
+
+/// Before/after record counts of an offline `compact_wal`
+#[derive(Debug, Default, Clone)]
+pub struct CompactionStats {
+    pub before: u64,
+    pub after: u64,
+    /// Why reading stopped before the end of the file; later bytes were discarded
+    pub stopped_early: Option<String>,
+}
+
+/// An LSN and the operation logged under it
+pub type WalRecord = (u64, WalOp);
+
+/// Read every intact record, stopping at the first truncated, corrupt or
+/// undecodable one.
+pub fn read_wal_records(wal_path: &str) -> io::Result<(Vec<WalRecord>, Option<String>)> {
+    read_wal_records_with(wal_path, &mut |_, _| {})
+}
+
+/// `read_wal_records`, calling `on_record(records, bytes)` after each intact record
+fn read_wal_records_with(
+    wal_path: &str,
+    on_record: &mut dyn FnMut(u64, u64),
+) -> io::Result<(Vec<WalRecord>, Option<String>)> {
+    let mut bytes_read = 0u64;
+    let mut records = Vec::new();
+    let mut stopped_early = None;
+    let mut last_lsn = 0u64;
+
     let mut file = File::open(wal_path)?;
-    let mut last_valid_lsn = 0u64;
     
     loop {
         // Read header: [LSN:8][CRC:4][LEN:4]
         let mut header = [0u8; 16];
-        if file.read_exact(&mut header).is_err() {
-            break; // EOF or truncated
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                // Clean EOF, or a partially written header
+                let pos = file.stream_position().unwrap_or(0);
+                let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+                if pos < len {
+                    stopped_early = Some(format!("truncated header after LSN {}", last_lsn));
+                }
+                break;
+            }
+            Err(e) => {
+                stopped_early = Some(format!("read error after LSN {}: {}", last_lsn, e));
+                break;
+            }
         }
         
         let lsn = u64::from_le_bytes([
@@ -293,80 +520,384 @@ pub fn recover_from_wal(wal_path: &str, data: &mut Value) -> io::Result<u64> {
         let mut data_buf = vec![0u8; len as usize];
         if file.read_exact(&mut data_buf).is_err() {
             eprintln!("WAL truncated at LSN {}", lsn);
+            stopped_early = Some(format!("truncated record at LSN {}", lsn));
             break;
         }
         
         // Verify CRC
         if crc32fast::hash(&data_buf) != crc {
             eprintln!("WAL corruption at LSN {}, stopping recovery", lsn);
+            stopped_early = Some(format!("CRC mismatch at LSN {}", lsn));
             break;
         }
         
-        // Deserialize and apply
         match serde_json::from_slice::<WalOp>(&data_buf) {
             Ok(op) => {
-                apply_wal_op(data, &op);
-                last_valid_lsn = lsn;
+                records.push((lsn, op));
+                last_lsn = lsn;
+                bytes_read += 16 + len as u64;
+                on_record(records.len() as u64, bytes_read);
             }
             Err(e) => {
                 eprintln!("WAL deserialization error at LSN {}: {}", lsn, e);
+                stopped_early = Some(format!("undecodable record at LSN {}", lsn));
                 break;
             }
         }
     }
     
-    Ok(last_valid_lsn)
+    Ok((records, stopped_early))
 }
 
-/// Apply a single WAL operation to data
-fn apply_wal_op(data: &mut Value, op: &WalOp) {
-    #[allow(unused_imports)]
-    use serde_json::Map;
-    
-    match op.op_type {
-        WalOpType::Set => {
-            if let Some(ref value) = op.value {
-                set_value_at_path(data, &op.path, value.clone());
+/// Records of committed work: operations outside a transaction, and those of
+/// transactions whose Commit was logged, in their original order. Operations
+/// of aborted transactions, or of ones still open where the log ends, are
+/// dropped along with every marker. Ids restart with each process, so a
+/// Begin starts a new transaction even under an id seen before; operations
+/// with no Begin before them (it was truncated by a save) start one too.
+/// Returns the records kept and how many operations were dropped.
+pub fn committed_records(records: Vec<WalRecord>) -> (Vec<WalRecord>, u64) {
+    let mut open: HashMap<u32, usize> = HashMap::new();
+    let mut committed: Vec<bool> = Vec::new();
+    let owners: Vec<Option<usize>> = records.iter()
+        .map(|(_, op)| {
+            let id = op.txn?;
+            let current = open.get(&id).copied().filter(|_| !matches!(op.op_type, WalOpType::Begin));
+            let owner = current.unwrap_or_else(|| {
+                committed.push(false);
+                open.insert(id, committed.len() - 1);
+                committed.len() - 1
+            });
+            match op.op_type {
+                WalOpType::Commit => {
+                    committed[owner] = true;
+                    open.remove(&id);
+                }
+                WalOpType::Abort => {
+                    open.remove(&id);
+                }
+                _ => {}
+            }
+            Some(owner)
+        })
+        .collect();
+
+    let mut dropped = 0;
+    let kept = records.into_iter()
+        .zip(owners)
+        .filter(|((_, op), owner)| {
+            if op.op_type.is_marker() {
+                return false;
+            }
+            let keep = owner.is_none_or(|owner| committed[owner]);
+            if !keep {
+                dropped += 1;
             }
+            keep
+        })
+        .map(|(record, _)| record)
+        .collect();
+    (kept, dropped)
+}
+
+/// Drop records whose effect is erased by a later record.
+///
+/// Walking backwards, a Set is redundant once a later Set targets the same
+/// path or an ancestor: that Set replaces the whole subtree and creates the
+/// same parents. A Delete is redundant under a later Set or Delete of the
+/// same path or an ancestor. A Delete never supersedes an earlier Set, since
+/// the Set may have created parent objects the Delete leaves behind, so
+/// delete-then-set and set-then-delete sequences replay exactly. Pushes are
+/// logged as a Set of the whole array, so no record is order-dependent
+/// beyond this. Survivors keep their original order and LSNs.
+pub fn compact_records(records: Vec<WalRecord>) -> Vec<WalRecord> {
+    use std::collections::HashSet;
+
+    fn covered(set: &HashSet<String>, path: &str) -> bool {
+        if set.contains("") {
+            return true;
         }
-        WalOpType::Delete => {
-            delete_value_at_path(data, &op.path);
+        path.match_indices('.')
+            .any(|(i, _)| set.contains(&path[..i]))
+            || set.contains(path)
+    }
+
+    let mut set_paths: HashSet<String> = HashSet::new();
+    let mut delete_paths: HashSet<String> = HashSet::new();
+    let mut keep = vec![false; records.len()];
+
+    for (i, (_, op)) in records.iter().enumerate().rev() {
+        match op.op_type {
+            WalOpType::Set => {
+                if !covered(&set_paths, &op.path) {
+                    keep[i] = true;
+                    set_paths.insert(op.path.clone());
+                }
+            }
+            WalOpType::Delete => {
+                if !covered(&set_paths, &op.path) && !covered(&delete_paths, &op.path) {
+                    keep[i] = true;
+                    // Deleting the root is a no-op and supersedes nothing
+                    if !op.path.is_empty() {
+                        delete_paths.insert(op.path.clone());
+                    }
+                }
+            }
+            WalOpType::Begin | WalOpType::Commit | WalOpType::Abort => keep[i] = true,
         }
     }
+
+    records.into_iter()
+        .zip(keep)
+        .filter_map(|(record, keep)| keep.then_some(record))
+        .collect()
 }
 
-/// Set value at path (helper for recovery)
-fn set_value_at_path(root: &mut Value, path: &str, value: Value) {
-    if path.is_empty() {
-        *root = value;
-        return;
+/// Recover database state from WAL, replaying only committed records (see
+/// `committed_records`) that survive compaction. Sets whose path breaks `rules` are dropped before compaction,
+/// so they can't supersede earlier records either. `parallel` forces the apply
+/// phase on or off (None: parallel for large replays on multi-core machines).
+/// Progress is reported every `PROGRESS_INTERVAL` records read and operations
+/// applied (after each phase when parallel), and once more at the end.
+pub fn recover_from_wal(
+    wal_path: &str,
+    data: &mut Value,
+    rules: &PathRules,
+    parallel: Option<bool>,
+    progress: &mut dyn FnMut(ReplayProgress),
+) -> io::Result<RecoveryStats> {
+    let mut stats = RecoveryStats::default();
+    let started = Instant::now();
+    if !Path::new(wal_path).exists() {
+        return Ok(stats);
     }
     
-    let parts: Vec<&str> = path.split('.').collect();
-    let mut current = root;
+    let mut report = ReplayProgress {
+        total_bytes: std::fs::metadata(wal_path)?.len(),
+        ..Default::default()
+    };
+    let (records, stopped_early) = read_wal_records_with(wal_path, &mut |count, bytes| {
+        report.bytes_read = bytes;
+        if count % PROGRESS_INTERVAL == 0 {
+            progress(report);
+        }
+    })?;
+    stats.stopped_early = stopped_early;
+    stats.ops_read = records.len() as u64;
+    if let (Some((first, _)), Some((last, _))) = (records.first(), records.last()) {
+        stats.first_lsn = *first;
+        stats.last_lsn = *last;
+    }
     
-    for (i, part) in parts.iter().enumerate() {
-        if i == parts.len() - 1 {
-            // Last part - set value
-            if let Value::Object(map) = current {
-                map.insert(part.to_string(), value);
+    stats.txn_markers = records.iter().filter(|(_, op)| op.op_type.is_marker()).count() as u64;
+    let (records, uncommitted) = committed_records(records);
+    stats.uncommitted_ops = uncommitted;
+    let committed = records.len() as u64;
+    let records: Vec<WalRecord> = records.into_iter()
+        .filter(|(_, op)| matches!(op.op_type, WalOpType::Delete) || rules.violation(&op.path).is_none())
+        .collect();
+    stats.rejected_paths = committed - records.len() as u64;
+    let ops: Vec<WalOp> = compact_records(records).into_iter().map(|(_, op)| op).collect();
+    stats.read_ms = started.elapsed().as_secs_f64() * 1000.0;
+    
+    let applying = Instant::now();
+    if parallel.unwrap_or(ops.len() >= PARALLEL_MIN_OPS && crate::THREAD_CONFIG.use_parallel) {
+        apply_parallel(data, ops, &mut stats, &mut |applied| {
+            report.ops_replayed = applied;
+            progress(report);
+        });
+    } else {
+        for op in &ops {
+            apply_wal_op(data, op);
+            stats.ops_applied += 1;
+            if stats.ops_applied % PROGRESS_INTERVAL == 0 {
+                report.ops_replayed = stats.ops_applied;
+                progress(report);
             }
-            return;
         }
-        
-        // Navigate/create path
-        match current {
-            Value::Object(map) => {
-                if !map.contains_key(*part) {
-                    map.insert(part.to_string(), Value::Object(Map::new()));
-                }
-                current = map.get_mut(*part).unwrap();
+    }
+    stats.apply_ms = applying.elapsed().as_secs_f64() * 1000.0;
+    if !stats.parallel {
+        stats.group_ms = stats.apply_ms;
+    }
+    report.ops_replayed = stats.ops_applied;
+    progress(report);
+    
+    Ok(stats)
+}
+
+/// Apply operations in parallel across top-level keys. Root writes are
+/// barriers applied on their own; the operations between two of them touch
+/// disjoint top-level subtrees, so each key's operations run in order on
+/// its detached subtree while other keys run on other threads.
+fn apply_parallel(data: &mut Value, ops: Vec<WalOp>, stats: &mut RecoveryStats, progress: &mut dyn FnMut(u64)) {
+    stats.parallel = true;
+    let mut phase = Vec::new();
+    for op in ops {
+        if !op.path.is_empty() {
+            phase.push(op);
+            continue;
+        }
+        apply_phase(data, std::mem::take(&mut phase), stats);
+        apply_wal_op(data, &op);
+        stats.barriers += 1;
+        stats.ops_applied += 1;
+        progress(stats.ops_applied);
+    }
+    apply_phase(data, phase, stats);
+}
+
+/// Apply operations below the root, grouped by top-level key, then reassemble
+/// the root in the key order a sequential replay would have left
+fn apply_phase(data: &mut Value, ops: Vec<WalOp>, stats: &mut RecoveryStats) {
+    if ops.is_empty() {
+        return;
+    }
+    stats.ops_applied += ops.len() as u64;
+    let Value::Object(root) = data else {
+        // Nothing below a non-object root can be set; keep sequential semantics
+        ops.iter().for_each(|op| apply_wal_op(data, op));
+        return;
+    };
+
+    // Sequentially, a set creating a top-level key appends it and a delete of
+    // one removes it; replay just that to get the final key order
+    let mut order: Map<String, Value> = root.keys().map(|key| (key.clone(), Value::Null)).collect();
+    let mut groups: Vec<(String, Vec<&WalOp>)> = Vec::new();
+    let mut group_of: HashMap<&str, usize> = HashMap::new();
+    for op in &ops {
+        let (key, nested) = match op.path.split_once('.') {
+            Some((key, _)) => (key, true),
+            None => (op.path.as_str(), false),
+        };
+        match op.op_type {
+            WalOpType::Set if !order.contains_key(key) => {
+                order.insert(key.to_string(), Value::Null);
             }
-            _ => return,
+            WalOpType::Delete if !nested => {
+                order.shift_remove(key);
+            }
+            _ => {}
+        }
+        let index = *group_of.entry(key).or_insert_with(|| {
+            groups.push((key.to_string(), Vec::new()));
+            groups.len() - 1
+        });
+        groups[index].1.push(op);
+    }
+    stats.groups += groups.len() as u64;
+
+    let detached: Vec<(String, Option<Value>, Vec<&WalOp>)> = groups.into_iter()
+        .map(|(key, ops)| {
+            let value = root.get_mut(&key).map(std::mem::take);
+            (key, value, ops)
+        })
+        .collect();
+    let applied: Vec<(String, Option<Value>, f64)> = detached.into_par_iter()
+        .map(|(key, value, ops)| {
+            let started = Instant::now();
+            let mut subtree = Value::Object(Map::new());
+            if let (Some(value), Value::Object(map)) = (value, &mut subtree) {
+                map.insert(key.clone(), value);
+            }
+            for op in ops {
+                apply_wal_op(&mut subtree, op);
+            }
+            let value = match &mut subtree {
+                Value::Object(map) => map.shift_remove(&key),
+                _ => None,
+            };
+            (key, value, started.elapsed().as_secs_f64() * 1000.0)
+        })
+        .collect();
+
+    let mut touched: HashMap<String, Option<Value>> = HashMap::with_capacity(applied.len());
+    for (key, value, ms) in applied {
+        stats.group_ms += ms;
+        touched.insert(key, value);
+    }
+    let mut untouched = std::mem::take(root);
+    for key in order.keys() {
+        let value = match touched.remove(key) {
+            Some(value) => value,
+            None => untouched.get_mut(key).map(std::mem::take),
+        };
+        if let Some(value) = value {
+            root.insert(key.clone(), value);
         }
     }
 }
 
+/// Rewrite a WAL file with redundant records removed, keeping the LSNs of
+/// the survivors. Anything after a truncated or corrupt record is dropped, as
+/// is everything recovery would not replay: transaction markers and the
+/// operations of transactions that never committed. Only safe while no
+/// process has the database open.
+pub fn compact_wal(wal_path: &str) -> io::Result<CompactionStats> {
+    let (records, stopped_early) = read_wal_records(wal_path)?;
+    let before = records.len() as u64;
+    let survivors = compact_records(committed_records(records).0);
+    let after = survivors.len() as u64;
+
+    write_records(wal_path, &survivors)?;
+
+    Ok(CompactionStats { before, after, stopped_early })
+}
+
+/// Replace the file at `path` with `records` in WAL format, atomically
+pub fn write_records(path: &str, records: &[WalRecord]) -> io::Result<()> {
+    let tmp_path = format!("{}.compact.tmp", path);
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    for (lsn, op) in records {
+        let data = serde_json::to_vec(op)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writer.write_all(&lsn.to_le_bytes())?;
+        writer.write_all(&crc32fast::hash(&data).to_le_bytes())?;
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(&data)?;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+    std::fs::rename(&tmp_path, path)
+}
+
+/// LSN of the last record in the WAL file at `path` (0 if there is none),
+/// reading only record headers
+pub fn last_lsn(path: &str) -> u64 {
+    let Ok(mut file) = File::open(path) else { return 0 };
+    let mut last = 0;
+    let mut header = [0u8; 16];
+    while file.read_exact(&mut header).is_ok() {
+        let lsn = u64::from_le_bytes(header[0..8].try_into().unwrap_or_default());
+        let len = u32::from_le_bytes(header[12..16].try_into().unwrap_or_default());
+        // A record cut short was still handed its LSN
+        last = last.max(lsn);
+        if file.seek(SeekFrom::Current(len as i64)).is_err() {
+            break;
+        }
+    }
+    last
+}
+
+/// Apply a single WAL operation to data
+pub fn apply_wal_op(data: &mut Value, op: &WalOp) {
+    #[allow(unused_imports)]
+    use serde_json::Map;
+    
+    match op.op_type {
+        WalOpType::Set => {
+            // A logged null reads back as no value. Same path write as live
+            // writes, so sets into arrays replay where they landed.
+            let _ = crate::NativeDB::set_value_at_path(data, &op.path, op.value.clone().unwrap_or(Value::Null));
+        }
+        WalOpType::Delete => {
+            delete_value_at_path(data, &op.path);
+        }
+        WalOpType::Begin | WalOpType::Commit | WalOpType::Abort => {}
+    }
+}
+
 /// Delete value at path (helper for recovery)
 fn delete_value_at_path(root: &mut Value, path: &str) {
     if path.is_empty() {
@@ -379,7 +910,7 @@ fn delete_value_at_path(root: &mut Value, path: &str) {
     for (i, part) in parts.iter().enumerate() {
         if i == parts.len() - 1 {
             if let Value::Object(map) = current {
-                map.remove(*part);
+                map.shift_remove(*part);
             }
             return;
         }
@@ -398,7 +929,7 @@ fn delete_value_at_path(root: &mut Value, path: &str) {
 }
 
 /// Durability mode
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DurabilityMode {
     /// No WAL, manual save only
     None,
@@ -420,23 +951,35 @@ impl DurabilityMode {
         }
     }
     
-    pub fn to_config(&self) -> Option<WalConfig> {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DurabilityMode::None => "none",
+            DurabilityMode::Lazy => "lazy",
+            DurabilityMode::Batched => "batched",
+            DurabilityMode::Sync => "sync",
+        }
+    }
+    
+    pub fn to_config(self) -> Option<WalConfig> {
         match self {
             DurabilityMode::None => None,
             DurabilityMode::Lazy => Some(WalConfig {
                 batch_size: 1000,
                 flush_interval_ms: 100,
                 fsync: true,
+                ..WalConfig::default()
             }),
             DurabilityMode::Batched => Some(WalConfig {
                 batch_size: 1000,
                 flush_interval_ms: 10,
                 fsync: true,
+                ..WalConfig::default()
             }),
             DurabilityMode::Sync => Some(WalConfig {
                 batch_size: 1,
                 flush_interval_ms: 0,
                 fsync: true,
+                ..WalConfig::default()
             }),
         }
     }
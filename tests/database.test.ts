@@ -1,5 +1,5 @@
-import { JSONDatabase } from '../index.ts';
-import { unlinkSync, existsSync } from 'fs';
+import { JSONDatabase, type QueryFilter, type BatchOperation, type Schema, type RecoveryProgress } from '../index.ts';
+import { unlinkSync, existsSync, writeFileSync, copyFileSync, readFileSync, statSync, rmSync } from 'fs';
 
 const TEST_DB = 'test_db.json';
 const TEST_WAL = 'test_db.json.wal';
@@ -264,15 +264,15 @@ async function runTests() {
             // Operation 1: Alice gives to Bob
             const alice = await dbWithIndex.get<number>('bank.alice');
             const bob = await dbWithIndex.get<number>('bank.bob');
-            await dbWithIndex.set('bank.alice', alice - 50);
-            await dbWithIndex.set('bank.bob', bob + 50);
+            await tx.set('bank.alice', alice - 50);
+            await tx.set('bank.bob', bob + 50);
             
             // Create savepoint
             await tx.savepoint('sp1');
             
             // Operation 2: Bob gives to Charlie (oops, typo)
-            await dbWithIndex.set('bank.bob', bob + 50 - 20);
-            await dbWithIndex.set('bank.charlie', 20);
+            await tx.set('bank.bob', bob + 50 - 20);
+            await tx.set('bank.charlie', 20);
             
             // Rollback to savepoint
             await tx.rollbackTo('sp1');
@@ -757,6 +757,3880 @@ async function runTests() {
     if (existsSync(TEST_DB + '.schema.wal')) unlinkSync(TEST_DB + '.schema.wal');
     console.log('   ✅ Passed\n');
 
+    // ============================================
+    // TEST 33: Typed Error Codes
+    // ============================================
+    console.log('🏷️ [Test 33] Typed Error Codes');
+    const dbCodes = new JSONDatabase(TEST_DB + '.codes', { wal: false });
+    const nativeCodes = (dbCodes as any).native;
+    const expectCode = (fn: () => void, code: string) => {
+        try {
+            fn();
+        } catch (e: any) {
+            if (!e.message.startsWith(`${code}:`)) throw new Error(`Expected ${code}, got: ${e.message}`);
+            return;
+        }
+        throw new Error(`Expected ${code}, but call succeeded`);
+    };
+    nativeCodes.set('scalar', 1);
+    expectCode(() => nativeCodes.push('missing.list', 1), 'ERR_PATH_NOT_FOUND');
+    expectCode(() => nativeCodes.push('scalar', 1), 'ERR_NOT_AN_ARRAY');
+    expectCode(() => nativeCodes.set('scalar.child', 1), 'ERR_PATH_BLOCKED');
+    expectCode(() => nativeCodes.commitTransaction(), 'ERR_NO_TRANSACTION');
+    expectCode(() => nativeCodes.set('__jsondb__.x', 1), 'ERR_READ_ONLY');
+    nativeCodes.registerSchema('strict', JSON.stringify({ type: 'number' }));
+    expectCode(() => nativeCodes.validatePath('strict', 'nope'), 'ERR_VALIDATION');
+    console.log('   Error codes match');
+    await dbCodes.close();
+    if (existsSync(TEST_DB + '.codes')) unlinkSync(TEST_DB + '.codes');
+    console.log('   ✅ Passed\n');
+
+    // ============================================
+    // TEST 34: Validated Push
+    // ============================================
+    console.log('📥 [Test 34] Validated Push');
+    const dbPush = new JSONDatabase(TEST_DB + '.push', {
+        wal: false,
+        schemas: {
+            'scores': { type: 'array', items: { type: 'number', minimum: 0 }, maxItems: 3 }
+        }
+    });
+    await dbPush.set('scores', [1, 2]);
+    try {
+        await dbPush.push('scores', 'not-a-number');
+        throw new Error('Should have rejected invalid element');
+    } catch (e: any) {
+        if (!e.message.startsWith('ERR_VALIDATION:')) throw e;
+    }
+    if ((await dbPush.get<number[]>('scores')).length !== 2) throw new Error('Rejected push modified array');
+    await dbPush.push('scores', 3);
+    const pushed = await dbPush.get<number[]>('scores');
+    if (pushed.length !== 3 || pushed[2] !== 3) throw new Error('Valid push did not land');
+    try {
+        await dbPush.push('scores', 4);
+        throw new Error('Should have rejected push past maxItems');
+    } catch (e: any) {
+        if (!e.message.startsWith('ERR_VALIDATION:')) throw e;
+    }
+    console.log('   Scores:', pushed);
+    await dbPush.close();
+    if (existsSync(TEST_DB + '.push')) unlinkSync(TEST_DB + '.push');
+
+    // An append checks and logs only the new elements
+    const tupleFiles = [TEST_DB + '.tuple', TEST_DB + '.tuple.wal', TEST_DB + '.tuple.process_lock'];
+    for (const f of tupleFiles) if (existsSync(f)) unlinkSync(f);
+    const tupleOptions = {
+        durability: 'batched' as const,
+        lockMode: 'exclusive' as const,
+        schemas: { 'row': { type: 'array', prefixItems: [{ type: 'string' }, { type: 'number' }], items: { type: 'boolean' } } },
+    };
+    const dbTuple = new JSONDatabase(TEST_DB + '.tuple', tupleOptions);
+    await dbTuple.set('row', ['id']);
+    await dbTuple.push('row', 7);
+    await dbTuple.push('row', true, false);
+    const badTail = await dbTuple.push('row', 'late').then(() => 'pushed', (e: any) => String(e.message));
+    if (!badTail.startsWith('ERR_VALIDATION')) throw new Error(`Appended elements should match prefixItems/items: ${badTail}`);
+    const appendRecords = (dbTuple as any).native.walInspect().records.map((r: any) => r.path).filter((p: string) => p.startsWith('row.'));
+    if (JSON.stringify(appendRecords) !== '["row.1","row.2","row.3"]') {
+        throw new Error(`Each appended element should be logged on its own: ${JSON.stringify(appendRecords)}`);
+    }
+    await dbTuple.close();
+    const dbTupleAgain = new JSONDatabase(TEST_DB + '.tuple', tupleOptions);
+    if (JSON.stringify(await dbTupleAgain.get('row')) !== '["id",7,true,false]') throw new Error('Appended elements should persist');
+    await dbTupleAgain.close();
+    for (const f of tupleFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // ============================================
+    // TEST 35: WAL Compaction on Recovery
+    // ============================================
+    console.log('🗜️ [Test 35] WAL Compaction on Recovery');
+    const COMPACT_DB = TEST_DB + '.compact';
+    const walRecord = (lsn: number, op: 'Set' | 'Delete', path: string, value?: any) => {
+        const data = Buffer.from(JSON.stringify({ timestamp: lsn, op_type: op, path, value: value ?? null }));
+        const header = Buffer.alloc(16);
+        header.writeBigUInt64LE(BigInt(lsn), 0);
+        header.writeUInt32LE(Bun.hash.crc32(data), 8);
+        header.writeUInt32LE(data.length, 12);
+        return Buffer.concat([header, data]);
+    };
+    const ops: Array<['Set' | 'Delete', string, any?]> = [
+        ['Set', 'a', { x: 1 }],       // parent then child: both must survive
+        ['Set', 'a.b', 2],
+        ['Set', 'p.q', 1],            // child then parent: child is redundant
+        ['Set', 'p', { r: 1 }],
+        ['Set', 'd.e', 1],            // set then delete leaves d = {}
+        ['Delete', 'd.e'],
+        ['Delete', 'g.h'],            // delete then set: set wins
+        ['Set', 'g', { k: 1 }],
+    ];
+    for (let i = 0; i < 50; i++) ops.push(['Set', `hot.k${i % 5}`, i]);
+    for (const f of [COMPACT_DB, COMPACT_DB + '.wal', COMPACT_DB + '.copy.wal']) if (existsSync(f)) unlinkSync(f);
+    writeFileSync(COMPACT_DB + '.wal', Buffer.concat(ops.map(([op, path, value], i) => walRecord(i + 1, op, path, value))));
+    copyFileSync(COMPACT_DB + '.wal', COMPACT_DB + '.copy.wal');
+
+    const expected = {
+        a: { x: 1, b: 2 }, p: { r: 1 }, d: {}, g: { k: 1 },
+        hot: { k0: 45, k1: 46, k2: 47, k3: 48, k4: 49 },
+    };
+    const dbCompact = new JSONDatabase(COMPACT_DB, { durability: 'batched' });
+    for (const [key, value] of Object.entries(expected)) {
+        const got = await dbCompact.get(key);
+        if (JSON.stringify(got) !== JSON.stringify(value)) {
+            throw new Error(`Compacted replay diverged at '${key}': ${JSON.stringify(got)}`);
+        }
+    }
+    const walReport = (dbCompact as any).native.integrityReport().wal;
+    console.log('   Replay:', walReport.walRecords, '->', walReport.replayedOps);
+    if (walReport.walRecords !== ops.length || walReport.replayedOps !== 11) {
+        throw new Error('Unexpected compaction counts');
+    }
+    await dbCompact.close();
+
+    const offline = JSONDatabase.compactWal(COMPACT_DB + '.copy.wal');
+    if (offline.before !== ops.length || offline.after !== 11) throw new Error('Offline compaction counts wrong');
+    for (const f of [COMPACT_DB, COMPACT_DB + '.wal', COMPACT_DB + '.copy.wal']) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // ============================================
+    // TEST 36: Value Compression
+    // ============================================
+    console.log('🗜️ [Test 36] Value Compression');
+    const dbZip = new JSONDatabase(TEST_DB + '.zip', { wal: false, compressThresholdBytes: 1024 });
+    const blob = 'lorem ipsum '.repeat(2000);
+    await dbZip.setCompressed('docs.a', { name: 'a', meta: { tag: 'hot' }, blob });
+    await dbZip.set('docs.b', { name: 'b', meta: { tag: 'cold' }, blob: 'tiny' });
+    await dbZip.set('docs.c', { name: 'c', meta: { tag: 'cold' } });
+    await dbZip.set('docs.c.blob', blob); // over the threshold: stored compressed
+    const zipped = await dbZip.get<any>('docs.a');
+    if (zipped.blob !== blob || zipped.meta.tag !== 'hot') throw new Error('Compressed value did not round-trip');
+    if ((await dbZip.get('docs.a.meta.tag')) !== 'hot') throw new Error('Path into compressed value not resolved');
+    // Filters decompress on the fly
+    const hot = await dbZip.parallelQuery('docs', [{ field: 'meta.tag', op: 'eq', value: 'hot' }]);
+    if (hot.length !== 1 || (hot[0] as any).blob !== blob) throw new Error('Filter inside compressed value failed');
+    const long = await dbZip.parallelQuery('docs', [{ field: 'blob', op: 'contains', value: 'ipsum lorem' }]);
+    if (long.map((d: any) => d.name).sort().join() !== 'a,c') throw new Error('Filter on compressed field failed');
+    const zipStats = (dbZip as any).native.dbStats().compression;
+    console.log('   Compression:', zipStats);
+    if (zipStats.values !== 2 || zipStats.savedBytes <= 0) throw new Error('Compression stats wrong');
+    await dbZip.close();
+    if (existsSync(TEST_DB + '.zip')) unlinkSync(TEST_DB + '.zip');
+    console.log('   ✅ Passed\n');
+
+    // TEST 37: Index Aggregates
+    console.log('📝 TEST 37: Index Aggregates');
+    const dbAgg = new JSONDatabase(TEST_DB + '.agg', {
+        wal: false,
+        indices: [
+            { name: 'age', path: 'people', field: 'age' },
+            { name: 'joined', path: 'people', field: 'joined' },
+        ]
+    });
+    const people: Record<string, { age: number; joined: string }> = {};
+    for (let i = 0; i < 60; i++) {
+        people['p' + i] = { age: i % 23, joined: `2024-0${1 + (i % 4)}-1${i % 7}` };
+    }
+    await dbAgg.set('people', people);
+    dbAgg.rebuildIndex();
+    const native = (dbAgg as any).native;
+    const scanAges = Object.values(people).map(p => p.age);
+
+    const rangeCount = native.indexAggregate('age', 'count', 5, 12, null);
+    const scanCount = scanAges.filter(a => a >= 5 && a <= 12).length;
+    console.log('   count age 5..12:', rangeCount, 'scan:', scanCount);
+    if (rangeCount !== scanCount) throw new Error('Index count mismatch');
+
+    const distinct = native.indexAggregate('age', 'distinct', null, null, null);
+    if (distinct !== new Set(scanAges).size) throw new Error('Index distinct mismatch');
+
+    const byTen = native.indexAggregate('age', 'count', null, null, 'interval:10');
+    const scanByTen: Record<number, number> = {};
+    for (const a of scanAges) scanByTen[Math.floor(a / 10) * 10] = (scanByTen[Math.floor(a / 10) * 10] || 0) + 1;
+    console.log('   age buckets:', byTen);
+    for (const { bucket, count } of byTen) {
+        if (scanByTen[bucket] !== count) throw new Error(`Interval bucket ${bucket} mismatch`);
+    }
+
+    const byMonth = native.indexAggregate('joined', 'count', null, null, 'prefix:7');
+    for (const { bucket, count } of byMonth) {
+        const expected = Object.values(people).filter(p => p.joined.startsWith(bucket)).length;
+        if (expected !== count) throw new Error(`Prefix bucket ${bucket} mismatch`);
+    }
+    await dbAgg.close();
+    if (existsSync(TEST_DB + '.agg')) unlinkSync(TEST_DB + '.agg');
+    console.log('   ✅ Passed\n');
+
+    // TEST 38: Missing-Field Policies
+    console.log('📝 TEST 38: Missing-Field Policies');
+    const dbMissing = new JSONDatabase(TEST_DB + '.missing', { wal: false });
+    await dbMissing.set('docs', { absent: { id: 'absent' }, nulled: { id: 'nulled', v: null } });
+    const policies = ['exclude', 'match', 'as_null'] as const;
+    // Whether the document without `v` matches, per policy [exclude, match, as_null]
+    const truthTable: Array<[string, unknown, [boolean, boolean, boolean]]> = [
+        ['eq', 1, [false, false, false]],
+        ['ne', 1, [false, true, true]],
+        ['gt', 1, [false, false, false]],
+        ['gte', 1, [false, false, false]],
+        ['lt', 1, [false, false, false]],
+        ['lte', 1, [false, false, false]],
+        ['contains', 'a', [false, false, false]],
+        ['startswith', 'a', [false, false, false]],
+        ['endswith', 'a', [false, false, false]],
+        ['in', [1, null], [false, false, true]],
+        ['notin', [1], [false, true, true]],
+        ['regex', '.*', [false, false, false]],
+        ['containsAll', [1], [false, false, false]],
+        ['containsAny', [1], [false, false, false]],
+        ['eq', { $field: 'id' }, [false, false, false]],
+        ['ne', { $field: 'id' }, [false, true, true]],
+    ];
+    const nativeMissing = (dbMissing as any).native;
+    for (const [op, value, expected] of truthTable) {
+        policies.forEach((missing, i) => {
+            const ids = nativeMissing.parallelQuery('docs', [{ field: 'v', op, value, missing }], null)
+                .map((d: any) => d.id);
+            if (ids.includes('absent') !== expected[i]) {
+                throw new Error(`${op} ${JSON.stringify(value)} with missing=${missing}: expected ${expected[i]}`);
+            }
+            // as_null must behave exactly like a stored null
+            if (missing === 'as_null' && ids.includes('nulled') !== expected[i]) {
+                throw new Error(`${op} ${JSON.stringify(value)}: as_null differs from a stored null`);
+            }
+        });
+    }
+    console.log(`   ${truthTable.length} operators × ${policies.length} policies checked`);
+    await dbMissing.close();
+    if (existsSync(TEST_DB + '.missing')) unlinkSync(TEST_DB + '.missing');
+    console.log('   ✅ Passed\n');
+
+    // TEST 39: Array Collections - Indexed Matches, Bulk Writes, Rollback
+    console.log('📝 TEST 39: Array Collections - Indexed Matches, Bulk Writes, Rollback');
+    const dbArrays = new JSONDatabase(TEST_DB + '.arrays', { wal: false });
+    const original = [{ n: 0 }, { n: 1, t: 'x' }, { n: 2 }, { n: 3, t: 'x' }, { n: 4 }, { n: 5, t: 'x' }];
+    await dbArrays.set('items', original);
+    const indexed = await dbArrays.parallelQuery<{ index: number }>('items', [{ field: 't', op: 'eq', value: 'x' }], { emit: 'indexed' });
+    if (JSON.stringify(indexed.map(m => m.index)) !== '[1,3,5]') {
+        throw new Error(`Expected indices [1,3,5], got ${JSON.stringify(indexed)}`);
+    }
+    const nativeArrays = (dbArrays as any).native;
+    const arraysTxn: number = nativeArrays.beginTransaction();
+    // Deleting element 3 shifts 4 and 5 down; the set below then lands on the old element 4
+    nativeArrays.delete('items.3', { txn: arraysTxn });
+    nativeArrays.set('items.3', { n: 'replaced' }, { txn: arraysTxn });
+    nativeArrays.set('items.1.t', 'y', { txn: arraysTxn });
+    const deleted = await dbArrays.deleteMany('items', [{ field: 't', op: 'eq', value: 'x' }], { txn: arraysTxn });
+    const updated = await dbArrays.updateMany('items', [{ field: 'n', op: 'gte', value: 2 }], { big: true }, { txn: arraysTxn });
+    if (JSON.stringify(deleted.indices) !== '[4]' || JSON.stringify(updated.indices) !== '[2]') {
+        throw new Error(`Unexpected bulk results: ${JSON.stringify({ deleted, updated })}`);
+    }
+    nativeArrays.rollbackTransaction();
+    if (JSON.stringify(await dbArrays.get('items')) !== JSON.stringify(original)) {
+        throw new Error(`Rollback left ${JSON.stringify(await dbArrays.get('items'))}`);
+    }
+    console.log('   Array restored after mixed deletes and sets');
+    await dbArrays.close();
+    if (existsSync(TEST_DB + '.arrays')) unlinkSync(TEST_DB + '.arrays');
+    console.log('   ✅ Passed\n');
+
+    // TEST 40: Parallel WAL Recovery Matches Sequential
+    console.log('📝 TEST 40: Parallel WAL Recovery Matches Sequential');
+    const replaySource = TEST_DB + '.replay';
+    const replayTargets = [TEST_DB + '.parallel', TEST_DB + '.sequential'];
+    const dbReplay = new JSONDatabase(replaySource, { durability: 'batched' });
+    const nativeReplay = (dbReplay as any).native;
+    let seed = 7;
+    const rnd = (n: number) => (seed = (seed * 1103515245 + 12345) % 2147483648) % n;
+    const randomPath = () => Array.from({ length: 1 + rnd(3) }, (_, i) => (i ? 'f' : 'k') + rnd(i ? 4 : 300)).join('.');
+    for (let i = 0; i < 20000; i++) {
+        const r = rnd(100);
+        try {
+            if (i % 7000 === 3500) nativeReplay.set('', { reset: i }, { allowRootOverwrite: true });
+            else if (r < 70) nativeReplay.set(randomPath(), { v: i, tag: 't' + rnd(9) });
+            else if (r < 95) nativeReplay.delete(randomPath());
+            else nativeReplay.set(randomPath(), i);
+        } catch {
+            // Writes below primitives are rejected; the workload keeps going
+        }
+    }
+    await dbReplay.sync();
+    for (const target of replayTargets) copyFileSync(replaySource + '.wal', target + '.wal');
+    const [dbParallel, dbSequential] = replayTargets.map((target, i) =>
+        new JSONDatabase(target, { durability: 'batched', parallelRecovery: i === 0 }));
+    const parallelTree = JSON.stringify((dbParallel as any).native.get(''));
+    if (parallelTree !== JSON.stringify((dbSequential as any).native.get(''))) {
+        throw new Error('Parallel and sequential replays produced different trees');
+    }
+    const applyStats = (dbParallel as any).native.integrityReport(false).wal.apply;
+    if (!applyStats.parallel || applyStats.groups === 0) {
+        throw new Error(`Expected a parallel replay, got ${JSON.stringify(applyStats)}`);
+    }
+    console.log(`   ${applyStats.groups} groups, ${applyStats.barriers} barrier(s), trees identical`);
+    for (const db of [dbReplay, dbParallel, dbSequential]) await db.close();
+    for (const file of [replaySource, ...replayTargets]) {
+        for (const f of [file, file + '.wal']) if (existsSync(f)) unlinkSync(f);
+    }
+    console.log('   ✅ Passed\n');
+
+    // TEST 41: String Queries
+    console.log('📝 TEST 41: String Queries');
+    const dbDsl = new JSONDatabase(TEST_DB + '.dsl', { wal: false });
+    await dbDsl.set('people', [
+        { name: 'ann', status: 'active', age: 35, role: 'user', createdAt: 3, tags: ['x', 'y'], bio: 'says "hi"' },
+        { name: 'bob', status: 'active', age: 20, role: 'admin', createdAt: 5, tags: ['y'] },
+        { name: 'cy', status: 'off', age: 50, role: 'ops', createdAt: 1, bio: "it's" },
+        { name: 'di', status: 'active', age: 40, role: 'user', createdAt: 9 },
+    ]);
+    const dslCases: [string, string[]][] = [
+        ['status = "active" AND (age > 30 OR role IN ["admin","ops"]) ORDER BY createdAt DESC LIMIT 20', ['di', 'bob', 'ann']],
+        ['NOT status == "active"', ['cy']],
+        ['status = "active" AND NOT age < 30 OR role = "ops"', ['ann', 'cy', 'di']],
+        ['age >= 35 and age <> 40 order by age desc', ['cy', 'ann']],
+        ['role NOT IN ["user"] ORDER BY name DESC', ['cy', 'bob']],
+        ['tags CONTAINS ANY ["x"]', ['ann']],
+        ['tags contains all ["x", "y"]', ['ann']],
+        ['bio CONTAINS "says \\"hi\\""', ['ann']],
+        ["bio CONTAINS 'it\\'s'", ['cy']],
+        ['bio = "\\u0069t\'s"', ['cy']],
+        ['ORDER BY age LIMIT 2 OFFSET 1', ['ann', 'di']],
+        ['ORDER BY bio, name', ['cy', 'ann', 'bob', 'di']],
+        ['', ['ann', 'bob', 'cy', 'di']],
+        ['age > -1 AND age < 2.5e1', ['bob']],
+        ['missing = null', []],
+    ];
+    for (const [query, expected] of dslCases) {
+        const names = (await dbDsl.queryString<any>('people', query)).map(p => p.name);
+        if (JSON.stringify(names) !== JSON.stringify(expected)) {
+            throw new Error(`${query}: expected ${JSON.stringify(expected)}, got ${JSON.stringify(names)}`);
+        }
+    }
+    const dslErrors: [string, number, string][] = [
+        ['status = ', 9, 'expected a value'],
+        ['status = "active" AND', 21, 'expected a field name'],
+        ['(age > 3', 8, "expected ')'"],
+        ['age ~ 3', 4, 'unexpected character'],
+        ['name = "open', 7, 'unterminated string'],
+        ['name IN [1 2]', 11, "expected ',' or ']'"],
+        ['name IN "x"', 8, "expected '['"],
+        ['LIMIT -1', 6, 'non-negative integer'],
+        ['age 3', 4, 'comparison operator'],
+        ['a = 1 b = 2', 6, 'AND, OR'],
+        ['ORDER age', 6, 'expected BY'],
+        ['name = "\\q"', 8, 'unknown escape'],
+    ];
+    for (const [query, position, hint] of dslErrors) {
+        try {
+            JSONDatabase.parseQuery(query);
+            throw new Error(`${query}: expected a parse error`);
+        } catch (e: any) {
+            if (!e.message.includes(`position ${position}:`) || !e.message.includes(hint)) {
+                throw new Error(`${query}: unexpected error ${e.message}`);
+            }
+        }
+    }
+    const parsedDsl = JSONDatabase.parseQuery('a = 1 OR NOT (b < 2 AND c IN []) ORDER BY a, b DESC LIMIT 1 OFFSET 2');
+    if (JSON.stringify(parsedDsl) !== JSON.stringify({
+        where: { or: [
+            { field: 'a', op: 'eq', value: 1 },
+            { not: { and: [{ field: 'b', op: 'lt', value: 2 }, { field: 'c', op: 'in', value: [] }] } },
+        ] },
+        orderBy: [{ field: 'a', direction: 'asc' }, { field: 'b', direction: 'desc' }],
+        limit: 1,
+        offset: 2,
+    })) {
+        throw new Error(`Unexpected parse tree ${JSON.stringify(parsedDsl)}`);
+    }
+    await dbDsl.close();
+    if (existsSync(TEST_DB + '.dsl')) unlinkSync(TEST_DB + '.dsl');
+    console.log(`   ${dslCases.length} queries and ${dslErrors.length} parse errors as expected`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 42: Delete Notifications
+    console.log('📝 TEST 42: Delete Notifications');
+    const dbDel = new JSONDatabase(TEST_DB + '.del', { durability: 'batched', deleteEventValues: true });
+    const deleteEvents: any[] = [];
+    dbDel.on('delete', (event) => deleteEvents.push(event));
+    for (const id of ['s1', 's2', 's3']) await dbDel.setWithTTL(`sessions.${id}`, { id }, 0.05);
+    await dbDel.set('sessions.keep', { id: 'keep' });
+    await new Promise(r => setTimeout(r, 250));
+    const ttlEvents = deleteEvents.filter(e => e.reason === 'ttl');
+    const ttlPaths = ttlEvents.map(e => e.path).sort();
+    if (deleteEvents.length !== 3 || JSON.stringify(ttlPaths) !== JSON.stringify(['sessions.s1', 'sessions.s2', 'sessions.s3'])) {
+        throw new Error(`Expected one ttl event per expired key, got ${JSON.stringify(deleteEvents)}`);
+    }
+    if (ttlEvents.some(e => e.value?.id !== e.path.split('.')[1])) {
+        throw new Error('TTL events should carry the removed values');
+    }
+    const walDeletes = dbDel.changesSince(0).filter(c => c.op === 'delete');
+    for (const event of ttlEvents) {
+        const record = walDeletes.find(c => c.lsn === event.lsn);
+        if (!record || record.path !== event.path || record.reason !== 'ttl') {
+            throw new Error(`No matching changesSince record for ${JSON.stringify(event)}`);
+        }
+    }
+    (dbDel as any).native.setQuota('feed', 2, null, 'evict_oldest');
+    for (let i = 0; i < 4; i++) await dbDel.set(`feed.e${i}`, i);
+    await dbDel.deleteMany('sessions', [{ field: 'id', op: 'eq', value: 'keep' }]);
+    await new Promise(r => setTimeout(r, 20));
+    const later = deleteEvents.slice(3).map(e => `${e.path}:${e.reason}`);
+    if (JSON.stringify(later) !== JSON.stringify(['feed.e0:quota', 'feed.e1:quota', 'sessions.keep:explicit'])) {
+        throw new Error(`Unexpected eviction/deleteMany events ${JSON.stringify(later)}`);
+    }
+    if (deleteEvents.some((e, i) => i > 0 && e.lsn !== null && e.lsn <= deleteEvents[i - 1].lsn)) {
+        throw new Error('Delete events should arrive in WAL order');
+    }
+    await dbDel.close();
+    for (const f of [TEST_DB + '.del', TEST_DB + '.del.wal']) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${ttlEvents.length} ttl events, ${later.length} quota/explicit events`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 43: Read Replicas Across Worker Threads
+    console.log('📝 TEST 43: Read Replicas Across Worker Threads');
+    const { Worker } = require('worker_threads');
+    const dbPrimary = new JSONDatabase(TEST_DB + '.primary', { wal: false });
+    for (let i = 0; i < 1000; i++) await dbPrimary.set(`items.i${i}`, { i, hot: i % 2 === 0 });
+    const sameThread = dbPrimary.createReader();
+    if (sameThread.parallelAggregate('items', 'count') !== 1000 || (sameThread as any).set !== undefined) {
+        throw new Error('Same-thread reader should see the data and expose no writes');
+    }
+    const workerScript = 'test_reader_worker.cjs';
+    writeFileSync(workerScript, `
+        const { parentPort, workerData } = require('worker_threads');
+        const { ReaderHandle } = require('./index.js');
+        const reader = ReaderHandle.open(workerData);
+        const counts = [];
+        for (let k = 0; k < 40; k++) {
+            counts.push(reader.parallelQuery('items', [{ field: 'hot', op: 'eq', value: true }]).length);
+            const until = Date.now() + 2;
+            while (Date.now() < until);
+        }
+        parentPort.postMessage(counts);
+    `);
+    const token = dbPrimary.readerToken();
+    const workerRuns: Promise<number[]>[] = [0, 1, 2].map(() => new Promise((resolve, reject) => {
+        const worker = new Worker(workerScript, { workerData: token });
+        worker.once('message', resolve);
+        worker.once('error', reject);
+    }));
+    let writing = true;
+    let written = 1000;
+    const writer = (async () => {
+        while (writing) {
+            for (let k = 0; k < 50; k++, written++) await dbPrimary.set(`items.i${written}`, { i: written, hot: true });
+            await sleep(1);
+        }
+    })();
+    const workerCounts = await Promise.all(workerRuns);
+    writing = false;
+    await writer;
+    for (const counts of workerCounts) {
+        if (counts.some((c, k) => c < 500 || (k > 0 && c < counts[k - 1]))) {
+            throw new Error(`Reader counts should start at 500 and never shrink: ${counts}`);
+        }
+    }
+    if (!workerCounts.some(counts => counts[counts.length - 1] > counts[0])) {
+        throw new Error('Readers should observe writes made while they query');
+    }
+    await dbPrimary.close();
+    if (sameThread.parallelAggregate('items', 'count') !== written) {
+        throw new Error('A reader should keep working after its primary closes');
+    }
+    unlinkSync(workerScript);
+    if (existsSync(TEST_DB + '.primary')) unlinkSync(TEST_DB + '.primary');
+    console.log(`   3 workers queried during ${written - 1000} primary writes`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 44: Canonical JSON
+    console.log('📝 TEST 44: Canonical JSON');
+    const canonicalCases: Array<[unknown, string]> = [
+        [{ b: 1, a: 2 }, '{"a":2,"b":1}'],
+        [{ outer: { z: [3, 1], a: null } }, '{"outer":{"a":null,"z":[3,1]}}'],
+        [-0, '0'],
+        [[1.5, -0, 2 ** 53], '[1.5,0,9007199254740992]'],
+        ['é\n', '"é\\n"'],
+    ];
+    for (const [value, expected] of canonicalCases) {
+        const actual = JSONDatabase.canonicalString(value);
+        if (actual !== expected) {
+            throw new Error(`canonicalString(${JSON.stringify(value)}) = ${actual}, expected ${expected}`);
+        }
+    }
+    if (JSONDatabase.canonicalString({ x: 1, y: { b: 2, a: 1 } }) !== JSONDatabase.canonicalString({ y: { a: 1, b: 2 }, x: 1.0 })) {
+        throw new Error('Equal data should have one canonical string');
+    }
+    for (const bad of [NaN, Infinity, { nested: [-Infinity] }]) {
+        try {
+            JSONDatabase.canonicalString(bad);
+            throw new Error(`canonicalString should reject ${String(bad)}`);
+        } catch (e: any) {
+            if (!e.message.startsWith('ERR_INVALID_ARGUMENT')) throw e;
+        }
+    }
+    // JS numbers cannot tell 1.0 from 1, so the floats come from a file
+    writeFileSync(TEST_DB + '.canon', '{"docs":{"d1":{"z":1.0,"a":{"y":-0.0,"x":2.50}}}}');
+    const dbCanon = new JSONDatabase(TEST_DB + '.canon');
+    await dbCanon.canonicalize('docs');
+    if (JSON.stringify(await dbCanon.get('docs.d1')) !== '{"a":{"x":2.5,"y":0},"z":1}') {
+        throw new Error(`canonicalize should sort keys in place, got ${JSON.stringify(await dbCanon.get('docs.d1'))}`);
+    }
+    await dbCanon.close();
+    const savedCanon = require('fs').readFileSync(TEST_DB + '.canon', 'utf8').replace(/\s/g, '');
+    if (!savedCanon.includes('"d1":{"a":{"x":2.5,"y":0},"z":1}')) {
+        throw new Error(`Saved file should hold the canonical numbers, got ${savedCanon}`);
+    }
+    for (const file of [TEST_DB + '.canon', TEST_DB + '.canon.wal']) if (existsSync(file)) unlinkSync(file);
+    console.log('   ✅ Passed\n');
+
+    // TEST 45: Storage Advisor
+    console.log('📝 TEST 45: Storage Advisor');
+    const advisorDb = (name: string, options: Record<string, unknown> = {}) => {
+        const file = `${TEST_DB}.adv-${name}`;
+        for (const f of [file, file + '.wal']) if (existsSync(f)) unlinkSync(f);
+        return { file, db: new JSONDatabase(file, { durability: 'batched', autoSaveInterval: 60000, ...options }) };
+    };
+    const codesOf = (db: JSONDatabase) => db.advisorReport({ minBytes: 0 }).findings.map(f => f.code);
+    const expectFinding = (db: JSONDatabase, code: string) => {
+        const report = db.advisorReport({ minBytes: 0 });
+        const finding = report.findings.find(f => f.code === code);
+        if (!finding || !finding.message || Object.keys(finding.metrics).length === 0) {
+            throw new Error(`Expected ${code}, got ${JSON.stringify(report.findings)}`);
+        }
+        return finding;
+    };
+
+    // Tiny changes, each persisted by rewriting a large file
+    const amp = advisorDb('amp');
+    await amp.db.close();
+    writeFileSync(amp.file, JSON.stringify({ big: { blob: 'x'.repeat(200000) } }));
+    const ampDb = new JSONDatabase(amp.file, { durability: 'batched', autoSaveInterval: 60000 });
+    for (let i = 0; i < 5; i++) {
+        (ampDb as any).native.set('counter', i);
+        await ampDb.save();
+    }
+    const ampFinding = expectFinding(ampDb, 'SAVE_AMPLIFICATION');
+    if ((ampFinding.metrics.amplification as number) < 100 || !ampFinding.message.startsWith('save() rewrote')) {
+        throw new Error(`Unexpected amplification finding ${JSON.stringify(ampFinding)}`);
+    }
+    if (ampDb.advisorReport().findings.length !== 0) {
+        throw new Error('A 200KB database is below the default 1MB floor');
+    }
+
+    // Deep nesting: the pretty-printed file is mostly indentation
+    const pretty = advisorDb('pretty');
+    const nested: Record<string, unknown> = {};
+    let level = nested;
+    for (let i = 0; i < 30; i++) level = (level.k = { a: 1, b: 2 }) as Record<string, unknown>;
+    (pretty.db as any).native.set('nested', nested);
+    await pretty.db.save();
+    expectFinding(pretty.db, 'PRETTY_FILE_OVERHEAD');
+
+    // Rewriting the same value: the WAL grows, the data does not
+    const growth = advisorDb('growth');
+    for (let i = 0; i < 60; i++) {
+        (growth.db as any).native.set('value', String(i % 10).repeat(10000));
+        await growth.db.save();
+    }
+    expectFinding(growth.db, 'WAL_OUTGROWS_DATA');
+
+    // Unsaved writes outweigh the file
+    const backlog = advisorDb('backlog');
+    (backlog.db as any).native.set('seed', 1);
+    await backlog.db.save();
+    for (let i = 0; i < 100; i++) (backlog.db as any).native.set(`w${i}`, 'z'.repeat(100));
+    await backlog.db.sync();
+    expectFinding(backlog.db, 'WAL_BACKLOG');
+
+    // A one-slot commit queue that fails instead of waiting
+    const pressure = advisorDb('pressure', { walQueueCapacity: 1, walOverflowPolicy: 'error' });
+    let rejectedWrites = 0;
+    for (let i = 0; i < 20000; i++) {
+        try { (pressure.db as any).native.set(`q${i % 10}`, i); } catch { rejectedWrites++; }
+    }
+    if (rejectedWrites > 0 && expectFinding(pressure.db, 'WAL_BACKPRESSURE').severity !== 'critical') {
+        throw new Error('Rejected WAL appends should be critical');
+    }
+
+    // Long document keys, tiny documents
+    const index = advisorDb('index', { indices: [{ name: 'tag', path: 'docs', field: 'tag' }] });
+    const docs: Record<string, { tag: string }> = {};
+    for (let i = 0; i < 200; i++) docs[`document-with-a-long-key-${i}`] = { tag: 't' };
+    await index.db.set('docs', docs);
+    const indexFinding = expectFinding(index.db, 'INDEX_LARGER_THAN_COLLECTION');
+    if (indexFinding.metrics.collection !== 'docs' || indexFinding.metrics.entries !== 200) {
+        throw new Error(`Unexpected index finding ${JSON.stringify(indexFinding)}`);
+    }
+    if (codesOf(index.db).includes('SAVE_AMPLIFICATION')) {
+        throw new Error('No saves means no save amplification');
+    }
+
+    for (const db of [ampDb, pretty.db, growth.db, backlog.db, pressure.db, index.db]) await db.close();
+    for (const { file } of [amp, pretty, growth, backlog, pressure, index]) {
+        for (const f of [file, file + '.wal', `${file}.tag.idx`]) if (existsSync(f)) unlinkSync(f);
+    }
+    console.log('   ✅ Passed\n');
+
+    // TEST 46: Automatic Index Maintenance
+    console.log('📝 TEST 46: Automatic Index Maintenance');
+    const autoFile = `${TEST_DB}.autoidx`;
+    const autoFiles = [autoFile, `${autoFile}.wal`, `${autoFile}.city.idx`, `${autoFile}.tag.idx`];
+    const autoIndices = [
+        { name: 'city', path: 'users', field: 'address.city' },
+        { name: 'tag', path: 'posts', field: 'tag' },
+    ];
+    const expectPaths = async (db: JSONDatabase, index: string, key: unknown, expected: string[]) => {
+        const paths = [...(db as any).native.findIndexPaths(index, key)].sort();
+        if (JSON.stringify(paths) !== JSON.stringify([...expected].sort())) {
+            throw new Error(`${index}=${JSON.stringify(key)} should find ${JSON.stringify(expected)}, got ${JSON.stringify(paths)}`);
+        }
+    };
+    const dbAuto = new JSONDatabase(autoFile, { wal: true, indices: autoIndices });
+    await dbAuto.set('users', {
+        u1: { address: { city: 'Oslo' } },
+        u2: { address: { city: 'Rome' } },
+    });
+    await expectPaths(dbAuto, 'city', 'Oslo', ['users.u1']);
+
+    // Nested writes under a document move it between keys
+    await dbAuto.set('users.u2.address.city', 'Oslo');
+    await dbAuto.set('users.u1.address', { city: 'Lima' });
+    await expectPaths(dbAuto, 'city', 'Oslo', ['users.u2']);
+    await expectPaths(dbAuto, 'city', 'Lima', ['users.u1']);
+    await expectPaths(dbAuto, 'city', 'Rome', []);
+
+    // Losing the field, or the document, leaves the index
+    await dbAuto.delete('users.u2.address.city');
+    await expectPaths(dbAuto, 'city', 'Oslo', []);
+    await dbAuto.set('users.u3', { address: { city: 'Lima' } });
+    await dbAuto.delete('users.u1');
+    await expectPaths(dbAuto, 'city', 'Lima', ['users.u3']);
+
+    // Batches and nested pushes
+    await dbAuto.batchSetParallel([
+        { path: 'posts.p1', value: { tag: 'a', likes: [] } },
+        { path: 'posts.p2', value: { tag: 'b', likes: [] } },
+        { path: 'posts.p2.tag', value: 'a' },
+    ]);
+    await dbAuto.push('posts.p1.likes', 'u3');
+    await expectPaths(dbAuto, 'tag', 'a', ['posts.p1', 'posts.p2']);
+    await expectPaths(dbAuto, 'tag', 'b', []);
+
+    // Replacing the whole collection re-indexes it
+    await dbAuto.set('posts', { p9: { tag: 'z' } });
+    await expectPaths(dbAuto, 'tag', 'a', []);
+    await expectPaths(dbAuto, 'tag', 'z', ['posts.p9']);
+
+    // Writes only in the WAL reach the saved index on reopen
+    await dbAuto.save();
+    await (dbAuto as any).native.set('users.u4', { address: { city: 'Lima' } });
+    (dbAuto as any).native.close();
+    const dbAutoReopened = new JSONDatabase(autoFile, { wal: true, indices: autoIndices });
+    await expectPaths(dbAutoReopened, 'city', 'Lima', ['users.u3', 'users.u4']);
+    const autoReport = (dbAutoReopened as any).native.integrityReport(true);
+    if (!autoReport.indexes.city.verify.ok || autoReport.indexes.city.collection !== 'users') {
+        throw new Error(`Maintained index should verify clean: ${JSON.stringify(autoReport.indexes.city)}`);
+    }
+    await dbAutoReopened.close();
+    for (const f of autoFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 47: Stable Pagination
+    console.log('📝 TEST 47: Stable Pagination');
+    const pageFile = `${TEST_DB}.pages`;
+    const dbPages = new JSONDatabase(pageFile);
+    const pageDocs: Record<string, { rank: number }> = {};
+    for (let i = 0; i < 60; i++) pageDocs[`m${String(i).padStart(3, '0')}`] = { rank: i % 9 };
+    await dbPages.set('feed', pageDocs);
+    const walk = async (stableBy: string | undefined, between: (round: number) => Promise<void>) => {
+        const seen: string[] = [];
+        let after: string | null = null;
+        let round = 0;
+        do {
+            const page: any = await dbPages.getPage('feed', { stableBy, after, limit: 8 });
+            seen.push(...page.items.map((item: { key: string }) => item.key));
+            after = page.next;
+            await between(round++);
+        } while (after);
+        return seen;
+    };
+    const checkWalk = (label: string, seen: string[], gone: string[]) => {
+        if (new Set(seen).size !== seen.length) {
+            throw new Error(`${label}: page walk repeated documents: ${seen.join(',')}`);
+        }
+        const missed = Object.keys(pageDocs).filter(key => !gone.includes(key) && !seen.includes(key));
+        if (missed.length > 0) throw new Error(`${label}: page walk missed ${missed.join(',')}`);
+        if (seen.some(key => gone.includes(key))) throw new Error(`${label}: returned a document deleted ahead of the cursor`);
+    };
+
+    // Inserts on both sides of the cursor and deletes ahead of it between pages
+    const byKey = await walk(undefined, async round => {
+        await dbPages.set(`feed.a${round}`, { rank: 0 });   // behind the cursor: not returned
+        await dbPages.set(`feed.z${round}`, { rank: 8 });   // ahead: returned
+        if (round === 1) await dbPages.delete('feed.m050');
+    });
+    checkWalk('key', byKey, ['m050']);
+    if (!byKey.includes('z0') || byKey.some(key => key.startsWith('a'))) {
+        throw new Error(`Key walk should see inserts ahead of the cursor only: ${byKey.join(',')}`);
+    }
+    if (byKey.join() !== [...byKey].sort().join()) throw new Error('Key walk should be in key order');
+
+    // By field: ties broken by key, writes to other documents between pages
+    const ranksSeen: number[] = [];
+    const byRank = await walk('rank', async round => {
+        await dbPages.set(`feed.n${round}`, { rank: round % 9 });
+        if (round === 2) await dbPages.delete('feed.m059');
+    });
+    for (const key of byRank) ranksSeen.push(((await dbPages.get(`feed.${key}`)) as any)?.rank ?? -1);
+    checkWalk('rank', byRank, ['m050', 'm059']);
+    const kept = ranksSeen.filter(rank => rank >= 0);
+    if (kept.some((rank, i) => i > 0 && kept[i - 1] > rank)) throw new Error(`Rank walk out of order: ${kept.join(',')}`);
+
+    // Cursors are tied to their walk
+    const first: any = await dbPages.getPage('feed', { limit: 2 });
+    for (const bad of [{ after: first.next, stableBy: 'rank' }, { after: 'not-a-cursor' }]) {
+        try {
+            await dbPages.getPage('feed', bad);
+            throw new Error(`getPage should reject ${JSON.stringify(bad)}`);
+        } catch (e: any) {
+            if (!e.message.startsWith('ERR_INVALID_ARGUMENT')) throw e;
+        }
+    }
+    await dbPages.close();
+    for (const f of [pageFile, `${pageFile}.wal`]) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 48: Numeric Index Key Order
+    console.log('📝 TEST 48: Numeric Index Key Order');
+    const numFile = `${TEST_DB}.numkeys`;
+    const numFiles = [numFile, `${numFile}.wal`, `${numFile}.temp.idx`];
+    const numIndices = [{ name: 'temp', path: 'readings', field: 'temp' }];
+    const temps: Record<string, number | string> = {
+        r1: 9, r2: 10, r3: -5, r4: -12.5, r5: 2.25, r6: 100, r7: 0, r8: -0.5, r9: 9.75, r10: 'n/a',
+    };
+    const readings = Object.fromEntries(Object.entries(temps).map(([key, temp]) => [key, { temp }]));
+    // An index file from before numeric ordering: keys as text, nothing else
+    const oldIndex = { name: 'temp', field: 'temp', map: {} as Record<string, string[]>, reverse_map: {} as Record<string, string> };
+    for (const [key, temp] of Object.entries(temps)) {
+        oldIndex.map[String(temp)] = [`readings.${key}`];
+        oldIndex.reverse_map[`readings.${key}`] = String(temp);
+    }
+    writeFileSync(`${numFile}.temp.idx`, JSON.stringify(oldIndex));
+    const dbNum = new JSONDatabase(numFile, { wal: true, indices: numIndices });
+    await dbNum.set('readings', readings);
+    const nativeNum = (dbNum as any).native;
+    const tempsOf = (paths: string[]) => paths.map(path => temps[path.slice('readings.'.length)]);
+    const expectTemps = (label: string, paths: string[], expected: (number | string)[]) => {
+        if (JSON.stringify(tempsOf(paths)) !== JSON.stringify(expected)) {
+            throw new Error(`${label}: expected ${JSON.stringify(expected)}, got ${JSON.stringify(tempsOf(paths))}`);
+        }
+    };
+    const checkOrder = (db: any) => {
+        expectTemps('9..30', db.findIndexRange('temp', 9, 30), [9, 9.75, 10]);
+        expectTemps('-13..0 exclusive', db.findIndexRange('temp', -13, 0, { endExclusive: true }), [-12.5, -5, -0.5]);
+        expectTemps('-1..', db.findIndexRange('temp', -1, null), [-0.5, 0, 2.25, 9, 9.75, 10, 100]);
+        const keys = db.indexKeys('temp').map((k: { key: string }) => k.key);
+        const expected = ['-12.5', '-5', '-0.5', '0', '2.25', '9', '9.75', '10', '100', 'n/a'];
+        if (JSON.stringify(keys) !== JSON.stringify(expected)) {
+            throw new Error(`Numeric keys should sort by value, then text keys: ${keys.join(' ')}`);
+        }
+    };
+    checkOrder(nativeNum);
+    const warm = await dbNum.findByIndexRange<{ temp: number }>('temp', 2, 10, { endExclusive: true });
+    if (JSON.stringify(warm.map(r => r.temp)) !== JSON.stringify([2.25, 9, 9.75])) {
+        throw new Error(`findByIndexRange should return documents in numeric order: ${JSON.stringify(warm)}`);
+    }
+    if (nativeNum.indexAggregate('temp', 'count', -10, 9.5) !== 5) throw new Error('Numeric aggregate bounds are wrong');
+    if (nativeNum.findIndexRange('temp', 10, 9).length !== 0) throw new Error('An inverted range should be empty');
+
+    // Saved and reloaded, the order holds
+    await dbNum.save();
+    await dbNum.close();
+    const dbNumReopened = new JSONDatabase(numFile, { wal: true, indices: numIndices });
+    checkOrder((dbNumReopened as any).native);
+    await dbNumReopened.close();
+    for (const f of numFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 49: Array Padding Policy
+    console.log('📝 TEST 49: Array Padding Policy');
+    const padFile = `${TEST_DB}.padding`;
+    const padCopy = `${TEST_DB}.padding-replay`;
+    const dbPad = new JSONDatabase(padFile, { durability: 'batched' });
+    const nativePad = (dbPad as any).native;
+    const expectList = async (path: string, expected: unknown) => {
+        const actual = await dbPad.get(path);
+        if (JSON.stringify(actual) !== JSON.stringify(expected)) {
+            throw new Error(`${path}: expected ${JSON.stringify(expected)}, got ${JSON.stringify(actual)}`);
+        }
+    };
+    if (dbPad.getArrayPadding() !== 'pad_null') throw new Error('Array padding should default to pad_null');
+
+    // pad_null: the gap fills with nulls, and a padded slot can later become an object
+    await dbPad.set('padded', ['a']);
+    await dbPad.set('padded.3', 'd');
+    await expectList('padded', ['a', null, null, 'd']);
+    await dbPad.set('padded.2.name', 'c');
+    await expectList('padded', ['a', null, { name: 'c' }, 'd']);
+    await dbPad.set('fresh.2.name', 'x');
+    await expectList('fresh', [null, null, { name: 'x' }]);
+
+    // error: nothing is written, in place or nested
+    dbPad.setArrayPadding('error');
+    if (dbPad.getArrayPadding() !== 'error') throw new Error('getArrayPadding should report the new policy');
+    await dbPad.set('strict', ['a', 'b']);
+    expectCode(() => nativePad.set('strict.5', 'x'), 'ERR_INVALID_ARGUMENT');
+    expectCode(() => nativePad.set('strict.3.name', 'x'), 'ERR_INVALID_ARGUMENT');
+    expectCode(() => nativePad.set('missing.1', 'x'), 'ERR_INVALID_ARGUMENT');
+    await dbPad.set('strict.2', 'c');
+    await dbPad.set('strict.0', 'A');
+    await dbPad.set('nested.0.name', 'first');
+    await expectList('strict', ['A', 'b', 'c']);
+    await expectList('nested', [{ name: 'first' }]);
+    if (await dbPad.has('missing')) throw new Error('A refused write should create nothing');
+    const batch = await dbPad.batchSetParallel([{ path: 'strict.9', value: 'x' }, { path: 'strict.3', value: 'd' }]);
+    if (batch.count !== 1 || batch.invalid?.[0]?.path !== 'strict.9') {
+        throw new Error(`The batch should apply strict.3 and refuse strict.9: ${JSON.stringify(batch)}`);
+    }
+    await expectList('strict', ['A', 'b', 'c', 'd']);
+
+    // append: writes past the end land at the end, nested ones included
+    dbPad.setArrayPadding('append');
+    await dbPad.set('queue', ['a']);
+    await dbPad.set('queue.7', 'b');
+    await dbPad.set('queue.9.name', 'c');
+    await dbPad.set('queue.0', 'A');
+    await expectList('queue', ['A', 'b', { name: 'c' }]);
+    await dbPad.atomic([], [{ type: 'set', path: 'queue.50', value: 'd' }]);
+    await expectList('queue', ['A', 'b', { name: 'c' }, 'd']);
+
+    // A per-call policy overrides the database's
+    await dbPad.set('queue.20', 'padded', { arrayPadding: 'pad_null' });
+    if ((await dbPad.get<unknown[]>('queue'))!.length !== 21) throw new Error('pad_null override should pad to index 20');
+    expectCode(() => nativePad.set('queue.30', 'x', { arrayPadding: 'error' }), 'ERR_INVALID_ARGUMENT');
+    expectCode(() => dbPad.setArrayPadding('sparse' as any), 'ERR_INVALID_ARGUMENT');
+
+    // Replay repeats the indexes written, whatever the policy on reopen
+    await dbPad.sync();
+    copyFileSync(`${padFile}.wal`, `${padCopy}.wal`);
+    const dbPadReplayed = new JSONDatabase(padCopy, { durability: 'batched', arrayPadding: 'error' });
+    for (const path of ['padded', 'fresh', 'strict', 'nested', 'queue']) {
+        const replayed = JSON.stringify(await dbPadReplayed.get(path));
+        if (replayed !== JSON.stringify(await dbPad.get(path))) throw new Error(`WAL replay changed ${path}: ${replayed}`);
+    }
+    if (dbPadReplayed.getArrayPadding() !== 'error') throw new Error('The arrayPadding option should set the policy');
+    await dbPadReplayed.close();
+    await dbPad.close();
+    for (const file of [padFile, padCopy]) {
+        for (const f of [file, `${file}.wal`]) if (existsSync(f)) unlinkSync(f);
+    }
+    console.log('   ✅ Passed\n');
+
+    // TEST 50: Index-Aware Query Planner
+    console.log('📝 TEST 50: Index-Aware Query Planner');
+    const planFile = `${TEST_DB}.planned`;
+    const scanFile = `${TEST_DB}.scanned`;
+    const planFiles = [planFile, `${planFile}.wal`, `${planFile}.age.idx`, `${planFile}.queueAge.idx`, scanFile, `${scanFile}.wal`];
+    for (const f of planFiles) if (existsSync(f)) unlinkSync(f);
+    // Same data in both; only the first has indexes, so it plans and the other scans
+    const dbPlanned = new JSONDatabase(planFile, {
+        wal: true,
+        indices: [{ name: 'age', path: 'people', field: 'age' }, { name: 'queueAge', path: 'queue', field: 'age' }],
+    });
+    const dbScanned = new JSONDatabase(scanFile, { wal: true });
+    let planSeed = 11;
+    const planRnd = (n: number) => (planSeed = (planSeed * 1103515245 + 12345) % 2147483648) % n;
+    // Numbers, fractions, numeric-looking strings, null, booleans, text and missing fields
+    const randomAge = (): unknown => {
+        const r = planRnd(10);
+        if (r < 4) return planRnd(50) - 10;
+        if (r < 5) return (planRnd(100) - 30) / 4;
+        if (r < 6) return String(planRnd(20));
+        if (r < 7) return null;
+        if (r < 8) return planRnd(2) === 0;
+        if (r < 9) return `x${planRnd(5)}`;
+        return undefined;
+    };
+    const randomDoc = (n: number) => {
+        const age = randomAge();
+        return age === undefined ? { n } : { n, age };
+    };
+    const people: Record<string, unknown> = {};
+    const queue: unknown[] = [];
+    for (let i = 0; i < 600; i++) {
+        people[`p${planRnd(100000)}`] = randomDoc(i);
+        queue.push(randomDoc(i));
+    }
+    for (const db of [dbPlanned, dbScanned]) {
+        await db.set('people', people);
+        await db.set('queue', queue);
+    }
+    const ops = ['eq', 'gt', 'gte', 'lt', 'lte'] as const;
+    let indexPlans = 0;
+    for (let round = 0; round < 400; round++) {
+        const collection = planRnd(2) ? 'people' : 'queue';
+        const age = randomAge();
+        const value = age === undefined ? planRnd(30) : age;
+        const filters: QueryFilter[] = [{ field: 'age', op: ops[planRnd(ops.length)], value }];
+        if (planRnd(2)) filters.push({ field: 'n', op: 'lt', value: planRnd(600) });
+        if (planRnd(6) === 0) filters[0].missing = 'as_null';
+        const emits = ['values', 'entries', 'object', 'indexed'] as const;
+        const emit = emits[planRnd(collection === 'queue' ? 4 : 3)];
+        const planned = JSON.stringify(await dbPlanned.parallelQuery(collection, filters, { emit }));
+        const scanned = JSON.stringify(await dbScanned.parallelQuery(collection, filters, { emit }));
+        if (planned !== scanned) {
+            throw new Error(`Planned and scanned results differ for ${collection} ${JSON.stringify(filters)} (${emit})`);
+        }
+        if (dbPlanned.explainQuery(collection, filters, { emit }).strategy === 'index') indexPlans++;
+        // Writes between queries keep the indexes honest
+        if (planRnd(4) === 0) {
+            const key = collection === 'people' ? Object.keys(people)[planRnd(500)] : String(planRnd(600));
+            const age = randomAge();
+            for (const db of [dbPlanned, dbScanned]) {
+                if (age === undefined) await db.delete(`${collection}.${key}.age`);
+                else await db.set(`${collection}.${key}.age`, age);
+            }
+        }
+    }
+    if (indexPlans < 100) throw new Error(`The planner should use the index for most of these queries (${indexPlans} of 400)`);
+    const plan = dbPlanned.explainQuery('people', [{ field: 'age', op: 'gte', value: 30 }]);
+    if (plan.strategy !== 'index' || plan.index !== 'age' || plan.candidates === null || plan.candidates >= plan.collectionSize) {
+        throw new Error(`A numeric range on an indexed field should use the index: ${JSON.stringify(plan)}`);
+    }
+    for (const [filters, why] of [
+        [[{ field: 'age', op: 'contains', value: 'x' }], 'contains'],
+        [[{ field: 'n', op: 'eq', value: 3 }], 'no index'],
+        [[{ field: 'age', op: 'gt', value: 'x1' }], 'only matches numbers'],
+        [[{ field: 'age', op: 'eq', value: null, missing: 'as_null' }], 'without the field'],
+    ] as [QueryFilter[], string][]) {
+        const scan = dbPlanned.explainQuery('people', filters);
+        if (scan.strategy !== 'scan' || !scan.reason?.includes(why)) {
+            throw new Error(`Expected a scan because of '${why}': ${JSON.stringify(scan)}`);
+        }
+    }
+    await dbPlanned.close();
+    await dbScanned.close();
+    for (const f of planFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 51: Binary Index Files
+    console.log('📝 TEST 51: Binary Index Files');
+    const binFile = `${TEST_DB}.binidx`;
+    const binFiles = [binFile, `${binFile}.wal`, `${binFile}.score.idx`];
+    // A large index file in the old JSON format: numeric, fractional and text keys
+    const legacyIndex = { name: 'score', field: 'score', map: {} as Record<string, string[]>, reverse_map: {} as Record<string, string> };
+    for (let i = 0; i < 200000; i++) {
+        const key = i % 7 === 0 ? `tag-${i % 911}` : String(i % 3 === 0 ? (i % 20011) / 4 : (i % 20011) - 5000);
+        (legacyIndex.map[key] ??= []).push(`items.i${i}`);
+        legacyIndex.reverse_map[`items.i${i}`] = key;
+    }
+    writeFileSync(`${binFile}.score.idx`, JSON.stringify(legacyIndex));
+    const jsonIndexBytes = statSync(`${binFile}.score.idx`).size;
+    const openScoreIndex = () => {
+        const db = new JSONDatabase(binFile, { wal: false });
+        const native = (db as any).native;
+        const started = performance.now();
+        native.registerIndex('score', 'score');
+        return { db, native, ms: performance.now() - started };
+    };
+    const indexContents = (native: any) => JSON.stringify([
+        native.indexKeys('score'),
+        native.findIndexRange('score', null, null),
+        native.findIndexRange('score', -100, 250.5, { endExclusive: true }),
+        native.findIndexPaths('score', 'tag-5'),
+        native.findIndexPaths('score', 1234),
+    ]);
+    const fromJson = openScoreIndex();
+    const jsonContents = indexContents(fromJson.native);
+    // The first save rewrites the file in the binary format
+    await fromJson.db.save();
+    await fromJson.db.close();
+    const binaryIndex = readFileSync(`${binFile}.score.idx`);
+    if (binaryIndex.subarray(0, 8).toString('latin1') !== 'JDBIDX\0\x01') {
+        throw new Error(`The index should be rewritten in the binary format, got ${binaryIndex.subarray(0, 8).toString('hex')}`);
+    }
+    if (binaryIndex.length >= jsonIndexBytes) throw new Error('The binary index should be smaller than the JSON one');
+    const fromBinary = openScoreIndex();
+    if (indexContents(fromBinary.native) !== jsonContents) {
+        throw new Error('An index should read the same after moving from JSON to the binary format');
+    }
+    console.log(`   200k entries: JSON ${jsonIndexBytes} bytes, ${fromJson.ms.toFixed(1)}ms; binary ${binaryIndex.length} bytes, ${fromBinary.ms.toFixed(1)}ms`);
+    await fromBinary.db.close();
+    // A damaged file is refused rather than half loaded
+    binaryIndex[binaryIndex.length - 3] ^= 1;
+    writeFileSync(`${binFile}.score.idx`, binaryIndex);
+    const damaged = new JSONDatabase(binFile, { wal: false });
+    let refused = false;
+    try {
+        (damaged as any).native.registerIndex('score', 'score');
+    } catch (e: any) {
+        refused = String(e.message).includes('checksum');
+    }
+    if (!refused) throw new Error('A corrupt index file should fail to load');
+    await damaged.close();
+    for (const f of binFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 52: Bulk Load Mode
+    console.log('📝 TEST 52: Bulk Load Mode');
+    const BULK_DOCS = 20000;
+    const bulkDoc = (i: number) => ({ name: `person${i}`, age: i % 90 });
+    const openBulkDb = (file: string) => {
+        for (const f of [file, `${file}.wal`, `${file}.age.idx`]) if (existsSync(f)) unlinkSync(f);
+        const db = new JSONDatabase(file, {
+            durability: 'batched',
+            autoSaveInterval: 60000,
+            indices: [{ name: 'age', path: 'people', field: 'age' }],
+            schemas: { strict: { type: 'object', properties: { age: { type: 'number' } } } },
+        });
+        (db as any).native.registerCounter('adults', 'people', [{ field: 'age', op: 'gte', value: 18 }]);
+        return db;
+    };
+    const loadPeople = async (db: JSONDatabase) => {
+        const started = performance.now();
+        for (let i = 0; i < BULK_DOCS / 2; i++) await db.set(`people.p${i}`, bulkDoc(i));
+        const ops: BatchOperation[] = [];
+        for (let i = BULK_DOCS / 2; i < BULK_DOCS; i++) ops.push({ type: 'set', path: `people.p${i}`, value: bulkDoc(i) });
+        await db.batch(ops);
+        return performance.now() - started;
+    };
+    const peopleState = async (db: JSONDatabase) => JSON.stringify([
+        await db.get('people'),
+        (db as any).native.findIndexPaths('age', 17),
+        (db as any).native.indexKeys('age'),
+        (db as any).native.counterValue('adults'),
+    ]);
+
+    const normalFile = `${TEST_DB}.bulk-normal`;
+    const dbNormal = openBulkDb(normalFile);
+    const normalMs = await loadPeople(dbNormal);
+    const normalState = await peopleState(dbNormal);
+    await dbNormal.close();
+    for (const f of [normalFile, `${normalFile}.wal`, `${normalFile}.age.idx`]) if (existsSync(f)) unlinkSync(f);
+
+    const bulkFile = `${TEST_DB}.bulk`;
+    const bulkFiles = [bulkFile, `${bulkFile}.wal`, `${bulkFile}.age.idx`];
+    const dbBulk = openBulkDb(bulkFile);
+    let peopleEvents = 0;
+    dbBulk.subscribe('people', () => peopleEvents++);
+    const walBefore = statSync(`${bulkFile}.wal`).size;
+    dbBulk.beginBulkLoad();
+    let refused = '';
+    try {
+        await dbBulk.transaction(async () => {});
+    } catch (e: any) {
+        refused = e.message;
+    }
+    if (!refused.startsWith('ERR_CONFLICT')) throw new Error(`A transaction should be refused during a bulk load: ${refused}`);
+    const bulkMs = await loadPeople(dbBulk);
+    // Schemas are off unless kept
+    await dbBulk.set('strict', { age: 'not a number' });
+    if (statSync(`${bulkFile}.wal`).size !== walBefore) throw new Error('A bulk load should not write the WAL');
+    if (peopleEvents !== 0) throw new Error('Subscribers should not hear bulk writes one by one');
+    const report = await dbBulk.endBulkLoad();
+    if (report.writes !== BULK_DOCS + 1 || report.indexesRebuilt !== 1 || report.countersRebuilt !== 1) {
+        throw new Error(`Unexpected bulk load report: ${JSON.stringify(report)}`);
+    }
+    if (peopleEvents !== 1) throw new Error(`Subscribers should hear once per top-level key, got ${peopleEvents}`);
+    if (await peopleState(dbBulk) !== normalState) throw new Error('A bulk load should end in the same data, index and counter as plain writes');
+    console.log(`   ${BULK_DOCS} docs: ${normalMs.toFixed(0)}ms with every write logged, ${bulkMs.toFixed(0)}ms bulk (+${(report.rebuildMs + report.saveMs).toFixed(0)}ms rebuild and save)`);
+
+    // With keepSchemas, writes are still validated; a bulk load cannot start inside a transaction
+    dbBulk.beginBulkLoad({ keepSchemas: true });
+    let rejected = false;
+    try {
+        await dbBulk.set('strict', { age: 'still not a number' });
+    } catch {
+        rejected = true;
+    }
+    await dbBulk.endBulkLoad();
+    if (!rejected) throw new Error('keepSchemas should keep schema validation on');
+    await dbBulk.transaction(async () => {
+        let inside = '';
+        try {
+            dbBulk.beginBulkLoad();
+        } catch (e: any) {
+            inside = e.message;
+        }
+        if (!inside.startsWith('ERR_TRANSACTION_ACTIVE')) throw new Error(`A bulk load should not start in a transaction: ${inside}`);
+    });
+    await dbBulk.close();
+    // The load was saved when it ended
+    const dbBulkReopened = new JSONDatabase(bulkFile, { durability: 'batched' });
+    if (Object.keys(await dbBulkReopened.get<object>('people')).length !== BULK_DOCS) throw new Error('A bulk load should be durable once ended');
+    await dbBulkReopened.close();
+    for (const f of bulkFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 53: Write Throttling
+    console.log('📝 TEST 53: Write Throttling');
+    const throttleFile = `${TEST_DB}.throttle`;
+    const throttleFiles = [throttleFile, `${throttleFile}.wal`, `${throttleFile}.process_lock`];
+    for (const f of throttleFiles) if (existsSync(f)) unlinkSync(f);
+    const dbThrottled = new JSONDatabase(throttleFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeThrottled = (dbThrottled as any).native;
+    const throttleError = (call: () => unknown) => Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+    if (dbThrottled.writeThrottleStatus().enabled) throw new Error('Writes should not be limited by default');
+    if (!(await throttleError(() => dbThrottled.setWriteRateLimit(0, 5)))?.startsWith('ERR_INVALID_ARGUMENT')) {
+        throw new Error('A zero rate should be rejected');
+    }
+    // 'error' policy: the burst is spent, then writes fail until tokens refill
+    dbThrottled.setWriteRateLimit(1, 3, { policy: 'error' });
+    for (let i = 0; i < 3; i++) await dbThrottled.set(`tokens.t${i}`, i);
+    const throttledMessage = await throttleError(() => dbThrottled.set('tokens.t3', 3));
+    if (!throttledMessage?.startsWith('ERR_THROTTLED')) throw new Error(`An empty bucket should throttle writes: ${throttledMessage}`);
+    if (JSON.stringify(await dbThrottled.get('tokens')) !== '{"t0":0,"t1":1,"t2":2}') {
+        throw new Error(`Throttled writes should not apply: ${JSON.stringify(await dbThrottled.get('tokens'))}`);
+    }
+    // Reads are never throttled
+    if (await dbThrottled.get('tokens.t1') !== 1) throw new Error('Reads should not be throttled');
+    const throttleStatus = dbThrottled.writeThrottleStatus();
+    if (!throttleStatus.enabled || throttleStatus.opsPerSec !== 1 || throttleStatus.burst !== 3 || throttleStatus.throttled !== 1 || throttleStatus.tokens! >= 1) {
+        throw new Error(`Unexpected limiter state: ${JSON.stringify(throttleStatus)}`);
+    }
+    // Batches spend one token per item; one larger than the burst waits for a full bucket
+    dbThrottled.setWriteRateLimit(1, 2, { policy: 'error' });
+    if (!nativeThrottled.batchSetParallel([['bulk.a', 1], ['bulk.b', 2], ['bulk.c', 3]]).success) {
+        throw new Error('A batch should run on a full bucket');
+    }
+    const batchMessage = await throttleError(() => nativeThrottled.batchSetParallel([['bulk.d', 4]]));
+    if (!batchMessage?.startsWith('ERR_THROTTLED')) throw new Error(`A batch should spend a token per item: ${batchMessage}`);
+    // Rollbacks are never throttled
+    const throttledTx = nativeThrottled.beginTransaction();
+    dbThrottled.setWriteRateLimit(1, 2, { policy: 'error' });
+    await dbThrottled.set('tokens.t0', 'changed', { txn: throttledTx });
+    await dbThrottled.set('tokens.t1', 'changed', { txn: throttledTx });
+    nativeThrottled.rollbackTransaction(throttledTx);
+    if (JSON.stringify(await dbThrottled.get('tokens')) !== '{"t0":0,"t1":1,"t2":2}') {
+        throw new Error(`A rollback with an empty bucket should still apply: ${JSON.stringify(await dbThrottled.get('tokens'))}`);
+    }
+    // 'block' policy: writes wait for tokens, up to maxWaitMs
+    dbThrottled.setWriteRateLimit(20, 1, { maxWaitMs: 500 });
+    const blockStart = Date.now();
+    await dbThrottled.set('blocked.a', 1);
+    await dbThrottled.set('blocked.b', 2);
+    if (Date.now() - blockStart < 30) throw new Error('A write with an empty bucket should wait for a token');
+    if (dbThrottled.writeThrottleStatus().waits !== 1) throw new Error(`The wait should be counted: ${JSON.stringify(dbThrottled.writeThrottleStatus())}`);
+    dbThrottled.setWriteRateLimit(1, 1, { maxWaitMs: 10 });
+    await dbThrottled.set('blocked.c', 3);
+    const tooLongMessage = await throttleError(() => dbThrottled.set('blocked.d', 4));
+    if (!tooLongMessage?.startsWith('ERR_THROTTLED')) throw new Error(`A wait beyond maxWaitMs should fail: ${tooLongMessage}`);
+    // Removing the limit keeps the counters
+    dbThrottled.setWriteRateLimit(null, 0);
+    for (let i = 0; i < 20; i++) await dbThrottled.set('free', i);
+    const freeStatus = dbThrottled.writeThrottleStatus();
+    if (freeStatus.enabled || freeStatus.throttled !== 3) throw new Error(`Unexpected limiter state: ${JSON.stringify(freeStatus)}`);
+    await dbThrottled.close();
+    for (const f of throttleFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 54: Close Safety
+    console.log('📝 TEST 54: Close Safety');
+    const closeFile = `${TEST_DB}.close`;
+    const closeFiles = [closeFile, `${closeFile}.wal`, `${closeFile}.process_lock`];
+    for (const f of closeFiles) if (existsSync(f)) unlinkSync(f);
+    const dbClosed = new JSONDatabase(closeFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbClosed.set('users', { u1: { name: 'Ann', age: 30 } });
+    // Concurrent and repeated closes share one close
+    await Promise.all([dbClosed.close(), dbClosed.close()]);
+    await dbClosed.close();
+    if (!dbClosed.isClosed()) throw new Error('isClosed() should report a closed database');
+
+    const adult: QueryFilter[] = [{ field: 'age', op: 'gte', value: 18 }];
+    const afterClose: Array<[string, () => unknown]> = [
+        ['recover', () => dbClosed.recover()],
+        ['recoverAsync', () => dbClosed.recoverAsync()],
+        ['save', () => dbClosed.save()],
+        ['saveWithReport', () => dbClosed.saveWithReport()],
+        ['validatePathString', () => dbClosed.validatePathString('users.u1')],
+        ['valueAt', () => dbClosed.valueAt('users', Date.now())],
+        ['pruneArchive', () => dbClosed.pruneArchive(Date.now())],
+        ['evict', () => dbClosed.evict('users')],
+        ['pin', () => dbClosed.pin('users')],
+        ['unpin', () => dbClosed.unpin('users')],
+        ['lazyStatus', () => dbClosed.lazyStatus()],
+        ['advisorReport', () => dbClosed.advisorReport()],
+        ['scanAnomalies', () => dbClosed.scanAnomalies()],
+        ['sync', () => dbClosed.sync()],
+        ['walStatus', () => dbClosed.walStatus()],
+        ['walPressure', () => dbClosed.walPressure()],
+        ['setWriteRateLimit', () => dbClosed.setWriteRateLimit(100, 100)],
+        ['setMaxValueBytes', () => dbClosed.setMaxValueBytes(1024)],
+        ['writeThrottleStatus', () => dbClosed.writeThrottleStatus()],
+        ['setWriteContext', () => dbClosed.setWriteContext({ user: 'ann' })],
+        ['clearWriteContext', () => dbClosed.clearWriteContext()],
+        ['changesSince', () => dbClosed.changesSince(0)],
+        ['setTraceCallback', () => dbClosed.setTraceCallback(() => {})],
+        ['enableWriteCoalescing', () => dbClosed.enableWriteCoalescing(10, ['users'])],
+        ['disableWriteCoalescing', () => dbClosed.disableWriteCoalescing()],
+        ['flushCoalesced', () => dbClosed.flushCoalesced()],
+        ['setArrayPadding', () => dbClosed.setArrayPadding('pad_null')],
+        ['getArrayPadding', () => dbClosed.getArrayPadding()],
+        ['before', () => dbClosed.before('set', 'users.*', (ctx) => ctx)],
+        ['after', () => dbClosed.after('set', 'users.*', (ctx) => ctx)],
+        ['subscribe', () => dbClosed.subscribe('users', () => {})],
+        ['setWithTTL', () => dbClosed.setWithTTL('session', 1, 60)],
+        ['setTTL', () => dbClosed.setTTL('users.u1', 60)],
+        ['getTTL', () => dbClosed.getTTL('users.u1')],
+        ['clearTTL', () => dbClosed.clearTTL('users.u1')],
+        ['hasTTL', () => dbClosed.hasTTL('users.u1')],
+        ['get', () => dbClosed.get('users')],
+        ['set', () => dbClosed.set('users.u2', { name: 'Bob' })],
+        ['canonicalize', () => dbClosed.canonicalize('users')],
+        ['setCompressed', () => dbClosed.setCompressed('blob', 'x')],
+        ['has', () => dbClosed.has('users')],
+        ['delete', () => dbClosed.delete('users.u1')],
+        ['push', () => dbClosed.push('list', 1)],
+        ['pull', () => dbClosed.pull('list', 1)],
+        ['add', () => dbClosed.add('n', 1)],
+        ['subtract', () => dbClosed.subtract('n', 1)],
+        ['findByIndex', () => dbClosed.findByIndex('age', 30)],
+        ['findByIndexRange', () => dbClosed.findByIndexRange('age', 0, 100)],
+        ['registerCustomIndex', () => dbClosed.registerCustomIndex('names', 'users', (doc) => [doc.name])],
+        ['customIndexStatus', () => dbClosed.customIndexStatus('names')],
+        ['rebuildCustomIndex', () => dbClosed.rebuildCustomIndex('names')],
+        ['verifyCustomIndex', () => dbClosed.verifyCustomIndex('names')],
+        ['rebuildIndex', () => dbClosed.rebuildIndex()],
+        ['buildIndex', () => dbClosed.buildIndex('age', 'users')],
+        ['listIndexes', () => dbClosed.listIndexes()],
+        ['dropIndex', () => dbClosed.dropIndex('age')],
+        ['verifyIndex', () => dbClosed.verifyIndex('age', 'users')],
+        ['scheduleMaintenance', () => dbClosed.scheduleMaintenance([{ task: 'checkpoint', intervalMs: 1000 }])],
+        ['maintenanceStatus', () => dbClosed.maintenanceStatus()],
+        ['query', () => dbClosed.query('users')],
+        ['find', () => dbClosed.find('users', { name: 'Ann' })],
+        ['findAll', () => dbClosed.findAll('users', { name: 'Ann' })],
+        ['paginate', () => dbClosed.paginate('users', 1, 10)],
+        ['getPage', () => dbClosed.getPage('users')],
+        ['batch', () => dbClosed.batch([{ type: 'set', path: 'a', value: 1 }])],
+        ['atomic', () => dbClosed.atomic([], [{ type: 'set', path: 'a', value: 1 }])],
+        ['atomicUpdate', () => dbClosed.atomicUpdate('n', (n: any) => (n ?? 0) + 1)],
+        ['atomicUpdateMany', () => dbClosed.atomicUpdateMany(['n'], (v) => v)],
+        ['insert', () => dbClosed.insert('users', { name: 'Cy' })],
+        ['insertMany', () => dbClosed.insertMany('users', [{ name: 'Cy' }])],
+        ['setIdStrategy', () => dbClosed.setIdStrategy('users', 'uuid4')],
+        ['idStrategy', () => dbClosed.idStrategy('users')],
+        ['registerCascade', () => dbClosed.registerCascade('c', 'users', 'posts', 'userId', 'delete')],
+        ['dropCascade', () => dbClosed.dropCascade('c')],
+        ['cascades', () => dbClosed.cascades()],
+        ['upsertMany', () => dbClosed.upsertMany('users', [{ id: 'u1' }], 'id')],
+        ['updateMany', () => dbClosed.updateMany('users', adult, { adult: true })],
+        ['deleteMany', () => dbClosed.deleteMany('users', adult)],
+        ['beginBulkLoad', () => dbClosed.beginBulkLoad()],
+        ['endBulkLoad', () => dbClosed.endBulkLoad()],
+        ['isBulkLoading', () => dbClosed.isBulkLoading()],
+        ['transaction', () => dbClosed.transaction(async () => {})],
+        ['createSnapshot', () => dbClosed.createSnapshot('after-close')],
+        ['exportArchive', () => dbClosed.exportArchive(`${closeFile}.archive`)],
+        ['restoreSnapshot', () => dbClosed.restoreSnapshot(closeFile)],
+        ['keys', () => dbClosed.keys('users')],
+        ['values', () => dbClosed.values('users')],
+        ['count', () => dbClosed.count('users')],
+        ['clear', () => dbClosed.clear()],
+        ['stats', () => dbClosed.stats()],
+        ['batchSetParallel', () => dbClosed.batchSetParallel([{ path: 'a', value: 1 }])],
+        ['parallelQuery', () => dbClosed.parallelQuery('users', adult)],
+        ['parallelQueryMulti', () => dbClosed.parallelQueryMulti(['users', 'admins'], adult)],
+        ['explainQuery', () => dbClosed.explainQuery('users', adult)],
+        ['queryString', () => dbClosed.queryString('users', 'age >= 18')],
+        ['createReader', () => dbClosed.createReader()],
+        ['readerToken', () => dbClosed.readerToken()],
+        ['parallelAggregate', () => dbClosed.parallelAggregate('users', 'count')],
+        ['parallelLookup', () => dbClosed.parallelLookup('users', 'users', 'name', 'name', 'self')],
+        ['ackChanges', () => dbClosed.ackChanges('feed', 0)],
+        ['consumerPosition', () => dbClosed.consumerPosition('feed')],
+        ['changesFor', () => dbClosed.changesFor('feed')],
+        ['listConsumers', () => dbClosed.listConsumers()],
+        ['dropConsumer', () => dbClosed.dropConsumer('feed')],
+        ['freezePath', () => dbClosed.freezePath('users')],
+        ['unfreezePath', () => dbClosed.unfreezePath('users')],
+        ['frozenPaths', () => dbClosed.frozenPaths()],
+        ['analyze', () => dbClosed.analyze('users')],
+        ['collectionStats', () => dbClosed.collectionStats('users')],
+        ['registerTTLIndex', () => dbClosed.registerTTLIndex('users', 'expiresAt')],
+        ['ttlIndexes', () => dbClosed.ttlIndexes()],
+        ['registerSchema', () => dbClosed.registerSchema('users', { type: 'object' })],
+        ['listSchemas', () => dbClosed.listSchemas()],
+        ['getSchema', () => dbClosed.getSchema('users')],
+        ['resolveSchemaFor', () => dbClosed.resolveSchemaFor('users.u1')],
+        ['generateFromSchema', () => dbClosed.generateFromSchema('users.u1')],
+        ['unregisterSchema', () => dbClosed.unregisterSchema('users')],
+    ];
+    const notRefused: string[] = [];
+    for (const [name, call] of afterClose) {
+        try {
+            await call();
+            notRefused.push(`${name} (succeeded)`);
+        } catch (e: any) {
+            if (!String(e?.message).startsWith('ERR_CLOSED')) notRefused.push(`${name} (${e?.message})`);
+        }
+    }
+    if (notRefused.length) throw new Error(`Calls after close() should fail with ERR_CLOSED: ${notRefused.join(', ')}`);
+    if (dbClosed.recoveryStatus().state !== 'done') throw new Error('recoveryStatus() should still answer after close()');
+
+    // The lock was released; a transaction open at close() is rolled back, not saved
+    const dbReopened = new JSONDatabase(closeFile, { durability: 'batched', lockMode: 'exclusive' });
+    let openGate!: () => void;
+    const gate = new Promise<void>((resolve) => { openGate = resolve; });
+    const pending = dbReopened.transaction(async (tx) => {
+        await tx.set('uncommitted', true);
+        await gate;
+    }).then(() => 'committed', (e: any) => String(e.message));
+    await dbReopened.close();
+    openGate();
+    const outcome = await pending;
+    if (!outcome.startsWith('ERR_CLOSED')) throw new Error(`A transaction cut off by close() should fail with ERR_CLOSED: ${outcome}`);
+    const dbAfterTx = new JSONDatabase(closeFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (await dbAfterTx.has('uncommitted')) throw new Error('close() should roll back an open transaction before saving');
+    if (!(await dbAfterTx.has('users.u1'))) throw new Error('close() should save committed writes');
+    await dbAfterTx.close();
+    for (const f of closeFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${afterClose.length} methods refused with ERR_CLOSED`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 55: Filter Validation
+    console.log('📝 TEST 55: Filter Validation');
+    const filterFile = `${TEST_DB}.filters`;
+    const filterFiles = [filterFile, `${filterFile}.wal`, `${filterFile}.process_lock`];
+    for (const f of filterFiles) if (existsSync(f)) unlinkSync(f);
+    const dbFilters = new JSONDatabase(filterFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbFilters.set('users', { u1: { name: 'Ann', role: 'admin', tags: ['a'] }, u2: { name: 'Bob', role: 'user', tags: ['b'] } });
+    const malformed: Array<[string, any, string]> = [
+        ['unknown operator', { field: 'role', op: 'equals', value: 'admin' }, "unknown operator 'equals'; use one of eq, ne,"],
+        ['empty field', { field: '', op: 'eq', value: 'admin' }, "'eq' needs a field"],
+        ['non-string regex', { field: 'name', op: 'regex', value: 42 }, "'regex' needs a string pattern"],
+        ['invalid regex', { field: 'name', op: 'regex', value: '(' }, "'(' is not a valid regex"],
+        ['non-array in', { field: 'role', op: 'in', value: 'admin' }, "'in' needs an array of values"],
+        ['non-array notin', { field: 'role', op: 'notin', value: 'admin' }, "'notin' needs an array of values"],
+        ['non-array containsAny', { field: 'tags', op: 'containsAny', value: 'a' }, "'containsAny' needs an array of values"],
+        ['unknown missing policy', { field: 'role', op: 'eq', value: 'admin', missing: 'skip' }, "unknown missing policy 'skip'"],
+    ];
+    for (const [label, filter, expected] of malformed) {
+        const entryPoints: Array<[string, () => unknown]> = [
+            ['parallelQuery', () => dbFilters.parallelQuery('users', [filter])],
+            ['explainQuery', () => dbFilters.explainQuery('users', [filter])],
+            ['getPage', () => dbFilters.getPage('users', { filters: [filter] })],
+            ['updateMany', () => dbFilters.updateMany('users', [filter], { hacked: true })],
+            ['deleteMany', () => dbFilters.deleteMany('users', [filter])],
+        ];
+        for (const [name, call] of entryPoints) {
+            let message = '';
+            try {
+                await call();
+            } catch (e: any) {
+                message = String(e?.message);
+            }
+            if (!message.startsWith('ERR_INVALID_ARGUMENT') || !message.includes(expected)) {
+                throw new Error(`${name} with ${label} should fail with ERR_INVALID_ARGUMENT (${expected}): ${message || 'no error'}`);
+            }
+        }
+    }
+    const secondBad = await dbFilters.parallelQuery('users', [
+        { field: 'name', op: 'eq', value: 'Ann' },
+        { field: 'role', op: 'equals' as any, value: 'admin' },
+    ]).then(() => 'no error', (e: any) => String(e.message));
+    if (!secondBad.includes('Filter 1 on \'role\'')) throw new Error(`The error should name the bad filter: ${secondBad}`);
+    if (await dbFilters.has('users.u1.hacked') || !(await dbFilters.has('users.u2'))) {
+        throw new Error('Rejected bulk writes should change nothing');
+    }
+    // A $field reference may stand in for the array
+    await dbFilters.set('users.u1.allowed', ['admin']);
+    const referenced = await dbFilters.parallelQuery('users', [{ field: 'role', op: 'in', value: { $field: 'allowed' } }]);
+    if (referenced.length !== 1) throw new Error(`'in' with a $field reference should be accepted: ${referenced.length}`);
+    await dbFilters.close();
+
+    // permissiveFilters keeps the old behavior: an unknown operator matches everything
+    const dbPermissive = new JSONDatabase(filterFile, { durability: 'batched', lockMode: 'exclusive', permissiveFilters: true });
+    const everything = await dbPermissive.parallelQuery('users', [{ field: 'role', op: 'equals' as any, value: 'admin' }]);
+    if (everything.length !== 2) throw new Error(`permissiveFilters should let an unknown operator match everything: ${everything.length}`);
+    await dbPermissive.close();
+    for (const f of filterFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${malformed.length} malformed filter classes rejected`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 56: Canonical Equality
+    console.log('📝 TEST 56: Canonical Equality');
+    const equalityFile = `${TEST_DB}.equality`;
+    const equalityFiles = [equalityFile, `${equalityFile}.wal`, `${equalityFile}.process_lock`];
+    for (const f of equalityFiles) if (existsSync(f)) unlinkSync(f);
+    // Written as text so the number forms survive: JS would turn 1.0 into 1
+    writeFileSync(equalityFile, `{ "items": {
+        "intOne": { "id": "intOne", "v": 1, "tags": [1, 2] },
+        "floatOne": { "id": "floatOne", "v": 1.0, "tags": [1.0, 2] },
+        "negZero": { "id": "negZero", "v": -0.0 },
+        "half": { "id": "half", "v": 1.5 },
+        "objAB": { "id": "objAB", "v": { "a": 1, "b": 2 } },
+        "objBAFloat": { "id": "objBAFloat", "v": { "b": 2, "a": 1.0 } }
+    } }`);
+    const dbEquality = new JSONDatabase(equalityFile, { durability: 'batched', lockMode: 'exclusive' });
+    const matching = async (filter: QueryFilter) =>
+        (await dbEquality.parallelQuery('items', [filter])).map((doc: any) => doc.id as string);
+    const matrix: Array<[string, QueryFilter, string[], string[]]> = [
+        ['int vs 1', { field: 'v', op: 'eq', value: 1 }, ['intOne'], ['intOne', 'floatOne']],
+        ['zero vs -0.0', { field: 'v', op: 'eq', value: 0 }, [], ['negZero']],
+        ['float vs 1.5', { field: 'v', op: 'eq', value: 1.5 }, ['half'], ['half']],
+        ['key order', { field: 'v', op: 'eq', value: { b: 2, a: 1 } }, ['objAB'], ['objAB', 'objBAFloat']],
+        ['in', { field: 'v', op: 'in', value: [1, 0] }, ['intOne'], ['intOne', 'floatOne', 'negZero']],
+        ['containsAll', { field: 'tags', op: 'containsAll', value: [1] }, ['intOne'], ['intOne', 'floatOne']],
+    ];
+    for (const [label, filter, strict, canonical] of matrix) {
+        for (const [mode, expected] of [['strict', strict], ['canonical', canonical]] as const) {
+            const found = (await matching({ ...filter, equality: mode })).sort();
+            if (JSON.stringify(found) !== JSON.stringify([...expected].sort())) {
+                throw new Error(`${label} (${mode}) should match ${JSON.stringify(expected)}: ${JSON.stringify(found)}`);
+            }
+        }
+    }
+    const badEquality = await dbEquality.parallelQuery('items', [{ field: 'v', op: 'eq', value: 1, equality: 'loose' as any }])
+        .then(() => 'no error', (e: any) => String(e.message));
+    if (!badEquality.startsWith('ERR_INVALID_ARGUMENT')) throw new Error(`An unknown equality should be rejected: ${badEquality}`);
+    await dbEquality.close();
+
+    // The database-wide mode applies to filters, push dedupe and atomic conditions
+    const dbCanonical = new JSONDatabase(equalityFile, { durability: 'batched', lockMode: 'exclusive', equality: 'canonical' });
+    const byDefault = await dbCanonical.parallelQuery('items', [{ field: 'v', op: 'eq', value: 1 }]);
+    if (byDefault.length !== 2) throw new Error(`equality: 'canonical' should be the filter default: ${byDefault.length}`);
+    await dbCanonical.push('items.floatOne.tags', 1, 3);
+    const tags = await dbCanonical.get<number[]>('items.floatOne.tags');
+    if (JSON.stringify(tags) !== '[1,2,3]') throw new Error(`push should dedupe 1 against 1.0: ${JSON.stringify(tags)}`);
+    const swapped = await dbCanonical.atomic([{ path: 'items.negZero.v', op: 'eq', value: 0 }], [{ type: 'set', path: 'items.negZero.seen', value: true }]);
+    const strictSwap = await dbCanonical.atomic([{ path: 'items.negZero.v', op: 'eq', value: 0, equality: 'strict' }], [{ type: 'set', path: 'items.negZero.seen', value: false }]);
+    if (!swapped || strictSwap || !(await dbCanonical.get('items.negZero.seen'))) {
+        throw new Error(`atomic conditions should follow the equality mode: ${swapped} ${strictSwap}`);
+    }
+    await dbCanonical.close();
+    for (const f of equalityFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${matrix.length} comparisons checked in both modes`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 57: Frozen Paths
+    console.log('📝 TEST 57: Frozen Paths');
+    const frozenFile = `${TEST_DB}.frozen`;
+    const frozenFiles = [frozenFile, `${frozenFile}.wal`, `${frozenFile}.process_lock`];
+    for (const f of frozenFiles) if (existsSync(f)) unlinkSync(f);
+    const dbFrozen = new JSONDatabase(frozenFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbFrozen.set('billing', { inv: { i1: { total: 10 }, i2: { total: 20 } } });
+    await dbFrozen.set('billingReports', { r1: 1 });
+    if (!dbFrozen.freezePath('billing.inv') || dbFrozen.freezePath('billing.inv')) {
+        throw new Error('freezePath should report whether it changed anything');
+    }
+    dbFrozen.freezePath('billingReports', { persist: true });
+    const frozenWrites: Array<[string, () => unknown]> = [
+        ['set', () => dbFrozen.set('billing.inv.i1.total', 11)],
+        ['set ancestor', () => dbFrozen.set('billing', {})],
+        ['delete', () => dbFrozen.delete('billing.inv.i2')],
+        ['batchSetParallel', () => dbFrozen.batchSetParallel([{ path: 'other', value: 1 }, { path: 'billing.inv.i3', value: {} }])],
+        ['updateMany', () => dbFrozen.updateMany('billing.inv', [], { paid: true })],
+        ['atomic', () => dbFrozen.atomic([], [{ type: 'set', path: 'billing.inv.i1.paid', value: true }])],
+    ];
+    for (const [name, write] of frozenWrites) {
+        const outcome = await Promise.resolve().then(write).then(() => 'written', (e: any) => String(e.message));
+        if (!outcome.startsWith('ERR_FROZEN')) throw new Error(`${name} under a frozen path should fail with ERR_FROZEN: ${outcome}`);
+    }
+    await dbFrozen.set('billing.notes', 'migrating');
+    if ((await dbFrozen.get('billing.inv.i1.total')) !== 10 || await dbFrozen.has('other')) {
+        throw new Error('Refused writes should change nothing');
+    }
+    await dbFrozen.close();
+    const dbThawed = new JSONDatabase(frozenFile, { durability: 'batched', lockMode: 'exclusive' });
+    const stillFrozen = dbThawed.frozenPaths();
+    if (JSON.stringify(stillFrozen) !== JSON.stringify([{ path: 'billingReports', persisted: true }])) {
+        throw new Error(`Only persisted freezes should survive a restart: ${JSON.stringify(stillFrozen)}`);
+    }
+    if (!dbThawed.unfreezePath('billingReports') || dbThawed.unfreezePath('billingReports')) {
+        throw new Error('unfreezePath should report whether the path was frozen');
+    }
+    await dbThawed.set('billingReports.r2', 2);
+    await dbThawed.close();
+    for (const f of frozenFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${frozenWrites.length} write methods refused under a frozen path`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 58: Planner Statistics
+    console.log('📝 TEST 58: Planner Statistics');
+    const statsFile = `${TEST_DB}.stats`;
+    const statsFiles = [statsFile, `${statsFile}.wal`, `${statsFile}.process_lock`];
+    for (const f of statsFiles) if (existsSync(f)) unlinkSync(f);
+    const dbStats = new JSONDatabase(statsFile, {
+        durability: 'batched',
+        lockMode: 'exclusive',
+        indices: [{ name: 'status', path: 'accounts', field: 'status' }],
+    });
+    const accounts: Record<string, { status: string }> = {};
+    for (let i = 0; i < 1000; i++) accounts[`a${i}`] = { status: i < 900 ? 'active' : 'new' };
+    await dbStats.set('accounts', accounts);
+    if (dbStats.collectionStats('accounts') !== null) throw new Error('collectionStats should be null before analyze');
+    const activeFilter = [{ field: 'status', op: 'eq' as const, value: 'active' }];
+    if (dbStats.explainQuery('accounts', activeFilter).strategy !== 'index') {
+        throw new Error('Without stats the planner should use the index');
+    }
+    const analyzed = dbStats.analyze('accounts', { staleAfter: 10 });
+    if (analyzed.fields.status?.cardinality !== 2 || analyzed.fields.status.mostCommon[0].docs !== 900) {
+        throw new Error(`analyze should count keys per indexed field: ${JSON.stringify(analyzed.fields)}`);
+    }
+    const activePlan = dbStats.explainQuery('accounts', activeFilter);
+    if (activePlan.strategy !== 'scan' || !activePlan.reason?.includes('90%')) {
+        throw new Error(`An index selecting most of the collection should lose to a scan: ${JSON.stringify(activePlan)}`);
+    }
+    const newPlan = dbStats.explainQuery('accounts', [{ field: 'status', op: 'eq', value: 'new' }]);
+    if (newPlan.strategy !== 'index' || newPlan.estimatedCandidates !== 100 || newPlan.stats?.field?.index !== 'status') {
+        throw new Error(`A selective key should keep the index: ${JSON.stringify(newPlan)}`);
+    }
+    for (let i = 0; i < 10; i++) await dbStats.set(`accounts.a${i}.status`, 'new');
+    if (!dbStats.collectionStats('accounts')?.stale) throw new Error('Stats should go stale past staleAfter writes');
+    if ((await dbStats.parallelQuery('accounts', activeFilter) as unknown[]).length !== 890) throw new Error('Scanned query returned wrong rows');
+    await dbStats.close();
+    for (const f of statsFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   'active' planned as ${activePlan.strategy}, 'new' as ${newPlan.strategy}`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 59: TTL Indexes
+    console.log('📝 TEST 59: TTL Indexes');
+    const ttlFile = `${TEST_DB}.ttlindex`;
+    const ttlFiles = [ttlFile, `${ttlFile}.wal`, `${ttlFile}.process_lock`, `${ttlFile}.ttl.sessions.expiresAt.idx`];
+    for (const f of ttlFiles) if (existsSync(f)) unlinkSync(f);
+    const dbTtl = new JSONDatabase(ttlFile, { durability: 'batched', lockMode: 'exclusive' });
+    const ttlNow = Date.now();
+    await dbTtl.set('sessions', {
+        s1: { user: 1, expiresAt: ttlNow + 100 },
+        s2: { user: 2, expiresAt: ttlNow + 100 },
+        s3: { user: 3, expiresAt: ttlNow + 60_000 },
+        s4: { user: 4 },
+    });
+    const ttlIndex = dbTtl.registerTTLIndex('sessions', 'expiresAt', 60_000);
+    await new Promise(resolve => setTimeout(resolve, 150));
+    const hidden = Object.keys(await dbTtl.get<Record<string, unknown>>('sessions')).sort();
+    if (JSON.stringify(hidden) !== '["s3","s4"]' || (await dbTtl.get('sessions.s1.user')) !== null) {
+        throw new Error(`Expired documents should be hidden before a sweep: ${hidden}`);
+    }
+    if ((await dbTtl.parallelQuery('sessions', []) as unknown[]).length !== 2) {
+        throw new Error('parallelQuery should leave out expired documents');
+    }
+    dbTtl.registerTTLIndex('sessions', 'expiresAt', 20);
+    await new Promise(resolve => setTimeout(resolve, 150));
+    const [ttlInfo] = dbTtl.ttlIndexes();
+    if (ttlInfo.index !== ttlIndex || ttlInfo.expired !== 2 || ttlInfo.sweepIntervalMs !== 20) {
+        throw new Error(`The sweeper should delete expired documents: ${JSON.stringify(ttlInfo)}`);
+    }
+    await dbTtl.close();
+    const dbTtlReopened = new JSONDatabase(ttlFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (await dbTtlReopened.has('sessions.s1')) throw new Error('Swept deletes should be replayed from the WAL');
+    await dbTtlReopened.close();
+    for (const f of ttlFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${ttlInfo.expired} documents swept in ${ttlInfo.sweeps} sweeps`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 60: Root Path
+    console.log('📝 TEST 60: Root Path');
+    const rootFile = `${TEST_DB}.root`;
+    const rootFiles = [rootFile, `${rootFile}.wal`, `${rootFile}.process_lock`];
+    for (const f of rootFiles) if (existsSync(f)) unlinkSync(f);
+    const dbRoot = new JSONDatabase(rootFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbRoot.set('a', { n: 1 });
+    await dbRoot.set('b', { n: 2 });
+    (dbRoot as any).native.acquireLease('locks.job', 'worker', 60_000);
+    const outcome = async (call: () => unknown) =>
+        Promise.resolve().then(call).then(v => JSON.stringify(v ?? null), (e: any) => String(e.message).split(':')[0]);
+    const rootContract: Array<[string, () => unknown, string]> = [
+        ['has', () => dbRoot.has(''), 'true'],
+        ['get', () => dbRoot.get(''), '{"a":{"n":1},"b":{"n":2}}'],
+        ['keys', () => dbRoot.keys(''), '["a","b"]'],
+        ['count', () => dbRoot.count(''), '2'],
+        ['values', () => dbRoot.values(''), '[{"n":1},{"n":2}]'],
+        ['parallelQuery', () => dbRoot.parallelQuery('', [{ field: 'n', op: 'gt', value: 1 }]), '[{"n":2}]'],
+        ['parallelAggregate', () => dbRoot.parallelAggregate('', 'sum', 'n'), '3'],
+        ['set', () => dbRoot.set('', { c: 3 }), '"ERR_READ_ONLY"'],
+        ['delete', () => dbRoot.delete(''), '"ERR_READ_ONLY"'],
+        ['set non-object', () => dbRoot.set('', [1], { allowRootOverwrite: true }), '"ERR_INVALID_ARGUMENT"'],
+        ['push', () => dbRoot.push('', 1), '"ERR_NOT_AN_ARRAY"'],
+        ['batchSetParallel', () => dbRoot.batchSetParallel([{ path: '', value: {} }]), '"ERR_INVALID_ARGUMENT"'],
+        ['atomic', () => dbRoot.atomic([], [{ type: 'set', path: '', value: {} }]), '"ERR_INVALID_ARGUMENT"'],
+        ['canonicalize', () => dbRoot.canonicalize(''), 'null'],
+    ];
+    for (const [name, call, expected] of rootContract) {
+        const actual = await outcome(call);
+        if (actual !== expected) throw new Error(`${name}('') should give ${expected}, got ${actual}`);
+    }
+    await dbRoot.transaction(async (tx) => {
+        await tx.set('', { replaced: true }, { allowRootOverwrite: true });
+        throw new Error('roll back');
+    }).catch(() => undefined);
+    if (JSON.stringify(await dbRoot.get('')) !== '{"a":{"n":1},"b":{"n":2}}') {
+        throw new Error('A transaction should roll back a root overwrite');
+    }
+    await dbRoot.set('', { fresh: 1 }, { allowRootOverwrite: true });
+    if (!(dbRoot as any).native.leaseInfo('locks.job')) throw new Error('Replacing the root should keep the reserved namespace');
+    await dbRoot.clear();
+    if (await dbRoot.count('') !== 0 || !(await dbRoot.has(''))) throw new Error('clear() should leave an empty root');
+    await dbRoot.close();
+    for (const f of rootFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${rootContract.length} methods checked against the root path`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 61: Schema Defaults
+    console.log('📝 TEST 61: Schema Defaults');
+    const defaultsFile = `${TEST_DB}.defaults`;
+    const defaultsFiles = [defaultsFile, `${defaultsFile}.wal`, `${defaultsFile}.process_lock`];
+    for (const f of defaultsFiles) if (existsSync(f)) unlinkSync(f);
+    const dbDefaults = new JSONDatabase(defaultsFile, { durability: 'batched', lockMode: 'exclusive' });
+    const accountSchema: Schema = {
+        type: 'object',
+        required: ['name', 'role'],
+        properties: {
+            name: { type: 'string' },
+            role: { type: 'string', default: 'member' },
+            nickname: { type: 'string', default: 'anon' },
+            prefs: {
+                type: 'object',
+                default: {},
+                properties: { theme: { type: 'string', default: 'dark' }, lang: { type: 'string', default: 'en' } },
+            },
+            devices: { type: 'array', items: { type: 'object', properties: { trusted: { type: 'boolean', default: false } } } },
+        },
+    };
+    dbDefaults.registerSchema('accounts', accountSchema, { applyDefaults: true });
+    // role is required, but its default satisfies it
+    await dbDefaults.set('accounts.a', { name: 'Ann', devices: [{}, { trusted: true }] });
+    await dbDefaults.set('accounts.b', { name: 'Bo', role: 'admin', prefs: { lang: 'fr' } });
+    // An explicit null is kept too (the wrapper's own validation would refuse it here)
+    (dbDefaults as any).native.set('accounts.n', { name: 'Nil', role: 'guest', nickname: null });
+    const expectedDefaults = {
+        a: { name: 'Ann', devices: [{ trusted: false }, { trusted: true }], nickname: 'anon', prefs: { lang: 'en', theme: 'dark' }, role: 'member' },
+        b: { name: 'Bo', role: 'admin', prefs: { lang: 'fr', theme: 'dark' }, nickname: 'anon' },
+        n: { name: 'Nil', role: 'guest', nickname: null, prefs: { lang: 'en', theme: 'dark' } },
+    };
+    if (JSON.stringify(await dbDefaults.get('accounts')) !== JSON.stringify(expectedDefaults)) {
+        throw new Error(`Defaults should fill only missing properties: ${JSON.stringify(await dbDefaults.get('accounts'))}`);
+    }
+    await dbDefaults.set('accounts.a.prefs', {});
+    if ((await dbDefaults.get('accounts.a.prefs.theme')) !== 'dark') throw new Error('Defaults should apply to writes below the schema path');
+    dbDefaults.registerSchema('accounts', accountSchema);
+    await dbDefaults.set('accounts.c', { name: 'Cy', role: 'guest' });
+    if (await dbDefaults.has('accounts.c.nickname')) throw new Error('Defaults should only apply with applyDefaults');
+    await dbDefaults.close();
+    const dbDefaultsReopened = new JSONDatabase(defaultsFile, { durability: 'batched', lockMode: 'exclusive' });
+    if ((await dbDefaultsReopened.get('accounts.a.role')) !== 'member') throw new Error('Filled-in defaults should be logged');
+    await dbDefaultsReopened.close();
+    for (const f of defaultsFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 62: Schema Composition
+    console.log('📝 TEST 62: Schema Composition');
+    const cardBranch: Schema = { type: 'object', required: ['card'], properties: { card: { type: 'string', minLength: 4 } } };
+    const bankBranch: Schema = { type: 'object', required: ['iban'], properties: { iban: { type: 'string' } } };
+    const payment: Schema = { type: 'object', oneOf: [cardBranch, bankBranch] };
+    const keywords = (value: unknown, schema: Schema) => JSONDatabase.validateValue(value, schema).map(i => i.keyword).join(',');
+    if (keywords({ card: '4242' }, payment) !== '' || keywords({ iban: 'DE89' }, payment) !== '') {
+        throw new Error('A value matching one oneOf branch should pass');
+    }
+    // Both branches accept a value with a card and an iban, so oneOf must reject it
+    const overlap = JSONDatabase.validateValue({ card: '4242', iban: 'DE89' }, payment);
+    if (overlap.length !== 1 || overlap[0].keyword !== 'oneOf' || !overlap[0].message.includes('[0, 1]')) {
+        throw new Error(`oneOf should reject a value matching two branches: ${JSON.stringify(overlap)}`);
+    }
+    if (keywords({ card: '4242', iban: 'DE89' }, { anyOf: [cardBranch, bankBranch] }) !== '') {
+        throw new Error('anyOf should accept a value matching two branches');
+    }
+    const nothing = JSONDatabase.validateValue(7, { anyOf: [{ type: 'string' }, { type: 'null' }] });
+    if (nothing.length !== 1 || !nothing[0].message.includes('branch 0: Type mismatch') || !nothing[0].message.includes('branch 1: Type mismatch')) {
+        throw new Error(`anyOf should report why every branch failed: ${JSON.stringify(nothing)}`);
+    }
+    if (keywords(-1, { allOf: [{ type: 'number', minimum: 0 }, { maximum: 10 }] }) !== 'minimum'
+        || keywords(11, { allOf: [{ type: 'number', minimum: 0 }, { maximum: 10 }] }) !== 'maximum') {
+        throw new Error('allOf should require every branch');
+    }
+    const compositionFile = `${TEST_DB}.composition`;
+    const compositionFiles = [compositionFile, `${compositionFile}.wal`, `${compositionFile}.process_lock`];
+    for (const f of compositionFiles) if (existsSync(f)) unlinkSync(f);
+    const dbComposition = new JSONDatabase(compositionFile, { durability: 'batched', lockMode: 'exclusive', schemas: { orders: { type: 'object', properties: { payment } } } });
+    await dbComposition.set('orders.1', { payment: { iban: 'DE89' } });
+    const rejected = await dbComposition.set('orders.2', { payment: { card: '4242', iban: 'DE89' } }).then(() => null, (e: Error) => e.message);
+    if (!rejected?.startsWith('ERR_VALIDATION') || await dbComposition.has('orders.2')) {
+        throw new Error(`set should reject an ambiguous oneOf value: ${rejected}`);
+    }
+    await dbComposition.close();
+    for (const f of compositionFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 63: String Formats
+    console.log('📝 TEST 63: String Formats');
+    const formatCases: Array<[string, string[], string[]]> = [
+        ['email', ['a@b.co', 'first.last+tag@mail.example.org'], ['a@b', '@b.co', 'a b@c.de', 'a..b@c.de']],
+        ['uuid', ['123e4567-e89b-12d3-a456-426614174000'], ['123e4567e89b12d3a456426614174000', '123e4567-e89b-12d3-a456-42661417400g']],
+        ['date-time', ['2024-01-02T03:04:05Z', '2024-01-02T03:04:05.123+02:00'], ['2024-01-02', '2024-02-30T10:00:00Z', '2024-01-02T03:04:05']],
+        ['uri', ['https://example.org/a?b=c#d', 'urn:isbn:0451450523'], ['/relative/path', 'http://a b', 'http://a/%zz']],
+        ['ipv4', ['192.168.0.1'], ['256.1.1.1', '01.2.3.4']],
+        ['ipv6', ['::1', '2001:db8::8a2e:370:7334'], ['1.2.3.4', ':::']],
+    ];
+    for (const [format, valid, invalid] of formatCases) {
+        for (const value of valid) {
+            const issues = JSONDatabase.validateValue(value, { type: 'string', format });
+            if (issues.length) throw new Error(`'${value}' should be a valid ${format}: ${JSON.stringify(issues)}`);
+        }
+        for (const value of invalid) {
+            const issues = JSONDatabase.validateValue(value, { type: 'string', format });
+            if (issues.length !== 1 || issues[0].keyword !== 'format') throw new Error(`'${value}' should not be a valid ${format}`);
+        }
+    }
+    if (JSONDatabase.validateValue('anything', { type: 'string', format: 'hostname' }).length) {
+        throw new Error('Unknown formats should be ignored by default');
+    }
+    const strict: Schema = { type: 'object', strictFormats: true, properties: { host: { type: 'string', format: 'hostname' } } };
+    try {
+        JSONDatabase.validateValue({ host: 'x' }, strict);
+        throw new Error('strictFormats should refuse an unknown format');
+    } catch (e: any) {
+        if (!String(e.message).startsWith('ERR_INVALID_ARGUMENT')) throw e;
+    }
+    console.log(`   ${formatCases.length} formats checked`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 64: Enforced Schemas
+    console.log('📝 TEST 64: Enforced Schemas');
+    const enforcedFile = `${TEST_DB}.enforced`;
+    const enforcedFiles = [enforcedFile, `${enforcedFile}.wal`, `${enforcedFile}.process_lock`];
+    for (const f of enforcedFiles) if (existsSync(f)) unlinkSync(f);
+    const memberSchema: Schema = {
+        type: 'object',
+        required: ['name'],
+        properties: { name: { type: 'string' }, age: { type: 'number', minimum: 0 }, tags: { type: 'array', items: { type: 'string' } } },
+    };
+    const enforcedOptions = { durability: 'batched', lockMode: 'exclusive', enforceSchemas: true, schemas: { members: memberSchema } } as const;
+    const dbEnforced = new JSONDatabase(enforcedFile, enforcedOptions);
+    await dbEnforced.set('members.a', { name: 'Ann', age: 30, tags: [] });
+    // A write below the schema path is checked against its own sub-schema
+    await dbEnforced.set('members.a.age', 31);
+    await dbEnforced.set('members.a.note', 'not described by the schema');
+    const enforcedNative = (dbEnforced as any).native;
+    enforcedNative.sync();
+    const enforcedBefore = JSON.stringify(await dbEnforced.get('members'));
+    const enforcedLsn = enforcedNative.walStatus().committed_lsn;
+    const refusals: Array<[string, () => unknown]> = [
+        ['set', () => dbEnforced.set('members.b', { age: 1 })],
+        ['deep set', () => dbEnforced.set('members.a.age', -1)],
+        ['push', () => dbEnforced.push('members.a.tags', 7)],
+        ['batchSetParallel', async () => {
+            const result = await dbEnforced.batchSetParallel([{ path: 'members.c', value: { age: 2 } }], { validate: false });
+            if (!result.success) throw new Error(`ERR_VALIDATION: ${result.error}`);
+        }],
+        ['atomic', () => dbEnforced.atomic([], [{ type: 'subtract', path: 'members.a.age', value: 100 }])],
+        ['transaction', () => dbEnforced.transaction(async (tx) => { await tx.set('members.a.name', 42); })],
+    ];
+    for (const [name, call] of refusals) {
+        const error = await Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+        if (!error?.startsWith('ERR_VALIDATION')) throw new Error(`${name} should fail validation, got ${error}`);
+    }
+    enforcedNative.sync();
+    if (JSON.stringify(await dbEnforced.get('members')) !== enforcedBefore) throw new Error('Rejected writes should leave the tree untouched');
+    if (enforcedNative.walStatus().committed_lsn !== enforcedLsn) throw new Error('Rejected writes should not reach the WAL');
+    await dbEnforced.close();
+    const dbEnforcedReopened = new JSONDatabase(enforcedFile, enforcedOptions);
+    if (JSON.stringify(await dbEnforcedReopened.get('members')) !== enforcedBefore) throw new Error('Replay should match the accepted writes');
+    await dbEnforcedReopened.close();
+    for (const f of enforcedFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${refusals.length} invalid writes refused`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 65: Schema References
+    console.log('📝 TEST 65: Schema References');
+    const addressDef: Schema = { type: 'object', required: ['city'], properties: { city: { type: 'string' }, zip: { type: 'string', pattern: '^[0-9]{5}$' } } };
+    const customer: Schema = {
+        type: 'object',
+        definitions: { address: addressDef },
+        properties: {
+            billing: { $ref: '#/definitions/address' },
+            shipping: { $ref: '#/definitions/address', required: ['city', 'zip'] },
+            previous: { type: 'array', items: { $ref: '#/definitions/address' } },
+        },
+    };
+    const refIssues = JSONDatabase.validateValue({ billing: { city: 'Oslo' }, shipping: { city: 'Bergen' }, previous: [{ city: 'Rome', zip: '1' }] }, customer);
+    if (JSON.stringify(refIssues.map(i => `${i.path}:${i.keyword}`)) !== '["previous.0.zip:pattern","shipping:required"]') {
+        throw new Error(`$ref should validate like the definition it names: ${JSON.stringify(refIssues)}`);
+    }
+    const refError = (schema: Schema) => {
+        try {
+            JSONDatabase.validateValue({}, schema);
+            return null;
+        } catch (e: any) {
+            return String(e.message);
+        }
+    };
+    const cycle = refError({ definitions: { a: { $ref: '#/definitions/b' }, b: { properties: { x: { $ref: '#/definitions/a' } } } }, properties: { q: { $ref: '#/definitions/a' } } });
+    if (cycle !== 'ERR_INVALID_ARGUMENT: $ref cycle: a -> b -> a') throw new Error(`A $ref cycle should be refused: ${cycle}`);
+    if (!refError({ properties: { q: { $ref: '#/definitions/missing' } } })?.startsWith('ERR_INVALID_ARGUMENT')) {
+        throw new Error('A $ref to a missing definition should be refused');
+    }
+    const refsFile = `${TEST_DB}.refs`;
+    const refsFiles = [refsFile, `${refsFile}.wal`, `${refsFile}.process_lock`];
+    for (const f of refsFiles) if (existsSync(f)) unlinkSync(f);
+    const dbRefs = new JSONDatabase(refsFile, { durability: 'batched', lockMode: 'exclusive' });
+    dbRefs.registerSchema('customers', customer);
+    dbRefs.registerSchema('warehouses', { type: 'object', properties: { location: { $ref: 'customers#/definitions/address' } } });
+    const crossRef = await dbRefs.set('warehouses.w1', { location: { zip: '12345' } }).then(() => null, (e: Error) => e.message);
+    if (!crossRef?.includes('Missing required property: city')) throw new Error(`A $ref to another schema should apply it: ${crossRef}`);
+    await dbRefs.close();
+    for (const f of refsFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 66: Dynamic Keys
+    console.log('📝 TEST 66: Dynamic Keys');
+    const settings: Schema = {
+        type: 'object',
+        properties: { theme: { type: 'string' } },
+        patternProperties: { '^flag_': { type: 'boolean' }, '^n_': { type: 'number' } },
+        additionalProperties: false,
+    };
+    const keyIssues = JSONDatabase.validateValue({ theme: 'dark', flag_beta: 'yes', n_max: 3, colour: 'red' }, settings);
+    if (JSON.stringify(keyIssues.map(i => `${i.path}:${i.keyword}`)) !== '["flag_beta:type",":additionalProperties"]') {
+        throw new Error(`patternProperties and additionalProperties should check every key: ${JSON.stringify(keyIssues)}`);
+    }
+    const namesIssues = JSONDatabase.validateValue({ a: 1 }, { type: 'object', propertyNames: { format: 'uuid' } });
+    if (namesIssues.length !== 1 || namesIssues[0].keyword !== 'propertyNames') {
+        throw new Error(`propertyNames should check the keys: ${JSON.stringify(namesIssues)}`);
+    }
+    const keysFile = `${TEST_DB}.keys`;
+    const keysFiles = [keysFile, `${keysFile}.wal`, `${keysFile}.process_lock`];
+    for (const f of keysFiles) if (existsSync(f)) unlinkSync(f);
+    const dbKeys = new JSONDatabase(keysFile, { durability: 'batched', lockMode: 'exclusive', enforceSchemas: true });
+    dbKeys.registerSchema('users', {
+        type: 'object',
+        propertyNames: { format: 'uuid' },
+        additionalProperties: { type: 'object', required: ['name'], properties: { name: { type: 'string' } } },
+    });
+    dbKeys.registerSchema('settings', settings);
+    const userId = '123e4567-e89b-12d3-a456-426614174000';
+    await dbKeys.set(`users.${userId}`, { name: 'Ann' });
+    await dbKeys.set('settings.flag_beta', true);
+    const keyError = (path: string, value: unknown) => dbKeys.set(path, value).then(() => null, (e: Error) => e.message);
+    if (!(await keyError(`users.${userId}`, {}))?.includes('Missing required property: name')) {
+        throw new Error('A keyed document should be validated against additionalProperties');
+    }
+    if (!(await keyError('users.bad.name', 'x'))?.includes("Invalid property name 'bad'")) {
+        throw new Error('A write under a key propertyNames refuses should fail');
+    }
+    if (!(await keyError('settings.colour', 'red'))?.includes('Property not allowed: colour')) {
+        throw new Error('A write under a key additionalProperties: false refuses should fail');
+    }
+    if (JSON.stringify(await dbKeys.get('settings')) !== '{"flag_beta":true}') throw new Error('Rejected writes should not be stored');
+    await dbKeys.close();
+    for (const f of keysFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 67: Precompiled Schemas
+    console.log('📝 TEST 67: Precompiled Schemas');
+    const records = Array.from({ length: 10_000 }, (_, i) => ({ sku: `SKU-${String(i).padStart(6, '0')}`, email: `user${i}@example.com`, qty: i }));
+    const recordSchema = (patterns: boolean): Schema => ({
+        type: 'object',
+        required: ['sku'],
+        properties: {
+            sku: { type: 'string', ...(patterns ? { pattern: '^SKU-[0-9]{6}$' } : {}) },
+            email: { type: 'string', ...(patterns ? { pattern: '^[^@]+@[^@]+$' } : {}) },
+            qty: { type: 'number', minimum: 0 },
+        },
+    });
+    const timeValidation = (schema: Schema) => {
+        JSONDatabase.validateValue([], { type: 'array', items: schema });
+        const start = performance.now();
+        const issues = JSONDatabase.validateValue(records, { type: 'array', items: schema });
+        if (issues.length !== 0) throw new Error(`The records should be valid: ${JSON.stringify(issues.slice(0, 3))}`);
+        return performance.now() - start;
+    };
+    const plainMs = timeValidation(recordSchema(false));
+    const patternMs = timeValidation(recordSchema(true));
+    console.log(`   10k records: ${plainMs.toFixed(1)}ms without patterns, ${patternMs.toFixed(1)}ms with`);
+    // Compiling two regexes per record would cost many times the rest of the validation
+    if (patternMs > plainMs * 4 + 100) throw new Error(`Patterns should be compiled once, not per record (${patternMs.toFixed(1)}ms)`);
+
+    const compiledFile = `${TEST_DB}.compiled`;
+    const compiledFiles = [compiledFile, `${compiledFile}.wal`, `${compiledFile}.process_lock`];
+    for (const f of compiledFiles) if (existsSync(f)) unlinkSync(f);
+    const dbCompiled = new JSONDatabase(compiledFile, { durability: 'batched', lockMode: 'exclusive' });
+    dbCompiled.registerSchema('records', { type: 'object', additionalProperties: recordSchema(true) });
+    const importStart = performance.now();
+    const imported = await dbCompiled.batchSetParallel(records.map((value, i) => ({ path: `records.r${i}`, value })));
+    console.log(`   10k validated writes: ${(performance.now() - importStart).toFixed(1)}ms`);
+    if (!imported.success || imported.count !== 10_000) throw new Error(`The records should all be written: ${imported.error}`);
+    const rejected = await dbCompiled.batchSetParallel([{ path: 'records.bad', value: { sku: 'sku-1' } }]);
+    if (rejected.success || !rejected.invalid?.[0]?.error.includes('pattern')) {
+        throw new Error(`A registered pattern should still be enforced: ${JSON.stringify(rejected)}`);
+    }
+    await dbCompiled.close();
+    for (const f of compiledFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 68: Conditional Rules
+    console.log('📝 TEST 68: Conditional Rules');
+    const payments: Schema = {
+        type: 'object',
+        required: ['method'],
+        properties: { method: { enum: ['card', 'bank'] } },
+        if: { properties: { method: { enum: ['card'] } } },
+        then: { required: ['cardNumber'] },
+        else: { required: ['iban'] },
+        dependentRequired: { cardNumber: ['expiry', 'cvc'] },
+    };
+    const conditionalIssues = (value: unknown) => JSONDatabase.validateValue(value, payments).map(i => `${i.keyword}:${i.message}`);
+    if (JSON.stringify(conditionalIssues({ method: 'bank', iban: 'DE89' })) !== '[]') throw new Error('A value passing else should be valid');
+    if (JSON.stringify(conditionalIssues({ method: 'card' })) !== '["required:In then branch: Missing required property: cardNumber"]') {
+        throw new Error(`A value passing if should be checked against then: ${conditionalIssues({ method: 'card' })}`);
+    }
+    if (JSON.stringify(conditionalIssues({ method: 'bank' })) !== '["required:In else branch: Missing required property: iban"]') {
+        throw new Error(`A value failing if should be checked against else: ${conditionalIssues({ method: 'bank' })}`);
+    }
+    const dependents = conditionalIssues({ method: 'card', cardNumber: '4242', cvc: '123' });
+    if (JSON.stringify(dependents) !== `["dependentRequired:Property 'cardNumber' requires property: expiry"]`) {
+        throw new Error(`dependentRequired should require the listed properties: ${dependents}`);
+    }
+    const conditionalFile = `${TEST_DB}.conditional`;
+    const conditionalFiles = [conditionalFile, `${conditionalFile}.wal`, `${conditionalFile}.process_lock`];
+    for (const f of conditionalFiles) if (existsSync(f)) unlinkSync(f);
+    const dbConditional = new JSONDatabase(conditionalFile, { durability: 'batched', lockMode: 'exclusive', enforceSchemas: true });
+    dbConditional.registerSchema('payments', payments);
+    await dbConditional.set('payments.p1', { method: 'card', cardNumber: '4242', expiry: '12/30', cvc: '123' });
+    const branchError = await dbConditional.set('payments.p2', { method: 'bank' }).then(() => null, (e: Error) => e.message);
+    if (!branchError?.includes('In else branch: Missing required property: iban')) throw new Error(`set should name the branch taken: ${branchError}`);
+    if (await dbConditional.has('payments.p2')) throw new Error('A rejected payment should not be stored');
+    await dbConditional.close();
+    for (const f of conditionalFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 69: Tuples
+    console.log('📝 TEST 69: Tuples');
+    const point: Schema = { type: 'array', prefixItems: [{ type: 'number', minimum: -90, maximum: 90 }, { type: 'number' }], items: false };
+    const tupleIssues = (value: unknown, schema: Schema) => JSON.stringify(JSONDatabase.validateValue(value, schema).map(i => `${i.path}:${i.keyword}`));
+    if (tupleIssues([51.5, -0.1], point) !== '[]') throw new Error('A matching tuple should be valid');
+    if (tupleIssues([100, 'x'], point) !== '["0:maximum","1:type"]') throw new Error(`Each item should be checked against its position: ${tupleIssues([100, 'x'], point)}`);
+    if (tupleIssues([1, 2, 3], point) !== '[":items"]') throw new Error('items: false should refuse items after the prefix');
+    // Items the array does not have are not required on their own
+    if (tupleIssues([51.5], point) !== '[]' || tupleIssues([], point) !== '[]') throw new Error('A short tuple should be valid without minItems');
+    const fixedPoint: Schema = { ...point, minItems: 2 };
+    if (tupleIssues([51.5], fixedPoint) !== '[":minItems"]') throw new Error(`minItems should refuse a short tuple: ${tupleIssues([51.5], fixedPoint)}`);
+    if (tupleIssues(['x'], fixedPoint) !== '[":minItems","0:type"]') throw new Error(`A short tuple should still have its items checked: ${tupleIssues(['x'], fixedPoint)}`);
+    const logEntry: Schema = { type: 'array', prefixItems: [{ type: 'string' }], items: { type: 'number' }, minItems: 2 };
+    if (tupleIssues(['start', 1, 2], logEntry) !== '[]') throw new Error('items should cover the items after the prefix');
+    if (tupleIssues(['start', 1, 'x'], logEntry) !== '["2:type"]') throw new Error(`An extra item should be checked against items: ${tupleIssues(['start', 1, 'x'], logEntry)}`);
+    const tupleFile = `${TEST_DB}.tuples`;
+    const tupleFiles = [tupleFile, `${tupleFile}.wal`, `${tupleFile}.process_lock`];
+    for (const f of tupleFiles) if (existsSync(f)) unlinkSync(f);
+    const dbTuples = new JSONDatabase(tupleFile, { durability: 'batched', lockMode: 'exclusive', enforceSchemas: true });
+    dbTuples.registerSchema('places', { type: 'object', additionalProperties: { type: 'object', properties: { at: fixedPoint, log: logEntry } } });
+    await dbTuples.set('places.home', { at: [51.5, -0.1], log: ['created', 1] });
+    const tupleError = (write: Promise<unknown>) => write.then(() => null, (e: Error) => e.message);
+    if (!(await tupleError(dbTuples.push('places.home.at', 3)))?.includes('Array allows no items after the first 2')) {
+        throw new Error('push should not grow a closed tuple');
+    }
+    if (!(await tupleError(dbTuples.set('places.home.at', [51.5])))?.includes('Array too short: min items 2')) {
+        throw new Error('set should refuse a short tuple under minItems');
+    }
+    if (!(await tupleError(dbTuples.set('places.home.at.1', 'x')))?.includes('Type mismatch')) {
+        throw new Error('A write to one position should be checked against its schema');
+    }
+    await dbTuples.push('places.home.log', 2);
+    if (JSON.stringify(await dbTuples.get('places.home')) !== '{"at":[51.5,-0.1],"log":["created",1,2]}') {
+        throw new Error(`Only the valid writes should be stored: ${JSON.stringify(await dbTuples.get('places.home'))}`);
+    }
+    await dbTuples.close();
+    for (const f of tupleFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 70: Inspecting Schemas
+    console.log('📝 TEST 70: Inspecting Schemas');
+    const inspectFile = `${TEST_DB}.inspect`;
+    const inspectFiles = [inspectFile, `${inspectFile}.wal`, `${inspectFile}.process_lock`];
+    for (const f of inspectFiles) if (existsSync(f)) unlinkSync(f);
+    const dbInspect = new JSONDatabase(inspectFile, { durability: 'batched', lockMode: 'exclusive' });
+    const member: Schema = {
+        type: 'object',
+        definitions: { age: { type: 'number', minimum: 0 } },
+        properties: { age: { $ref: '#/definitions/age' }, role: { type: 'string', default: null } },
+    };
+    dbInspect.registerSchema('users', member, { applyDefaults: true });
+    dbInspect.registerSchema('orders', { type: 'object', additionalProperties: false });
+    if (JSON.stringify(dbInspect.listSchemas()) !== '["orders","users"]') throw new Error(`listSchemas should list the paths: ${dbInspect.listSchemas()}`);
+    const stored = dbInspect.getSchema('users');
+    if (JSON.stringify(stored?.properties?.age) !== '{"type":"number","minimum":0}') {
+        throw new Error(`getSchema should return the schema with $refs resolved and no unset keywords: ${JSON.stringify(stored)}`);
+    }
+    if (!stored?.properties?.role || !('default' in stored.properties.role) || stored.properties.role.default !== null) {
+        throw new Error('getSchema should keep a null default');
+    }
+    if (dbInspect.getSchema('users.1') !== null) throw new Error('getSchema should only return a schema registered at the path itself');
+    const exact = dbInspect.resolveSchemaFor('users');
+    if (!exact?.exact || exact.path !== 'users' || !exact.applyDefaults) throw new Error(`resolveSchemaFor should find the exact schema: ${JSON.stringify(exact)}`);
+    const below = dbInspect.resolveSchemaFor('users.1.age');
+    if (below?.exact !== false || below.path !== 'users' || JSON.stringify(below.resolved) !== '{"type":"number","minimum":0}') {
+        throw new Error(`resolveSchemaFor should find the ancestor schema and the part describing the path: ${JSON.stringify(below)}`);
+    }
+    if (dbInspect.resolveSchemaFor('orders.o1')?.resolved !== null) throw new Error('A key the schema does not allow should resolve to nothing');
+    if (dbInspect.resolveSchemaFor('other.path') !== null) throw new Error('A path without a schema should resolve to null');
+    if (!dbInspect.unregisterSchema('orders') || dbInspect.unregisterSchema('orders')) throw new Error('unregisterSchema should report whether a schema was removed');
+    await dbInspect.set('orders.o1', { anything: true });
+    if (JSON.stringify(dbInspect.listSchemas()) !== '["users"]') throw new Error('An unregistered schema should not be listed');
+    await dbInspect.close();
+    for (const f of inspectFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 71: Sample Values
+    console.log('📝 TEST 71: Sample Values');
+    const account: Schema = {
+        type: 'object',
+        required: ['name', 'age', 'tags', 'address', 'role'],
+        properties: {
+            name: { type: 'string', minLength: 3 },
+            age: { type: 'number', exclusiveMinimum: 17, maximum: 99 },
+            tags: { type: 'array', minItems: 2, uniqueItems: true, items: { type: 'string' } },
+            address: {
+                type: 'object',
+                required: ['zip'],
+                properties: { zip: { type: 'string', pattern: '^[0-9]{4}$', default: '1234' }, country: { type: 'string', default: 'NL' } },
+            },
+            role: { enum: ['admin', 'user'] },
+            email: { type: 'string', format: 'email' },
+        },
+        dependentRequired: { role: ['email'] },
+    };
+    const sample = JSONDatabase.generateSample<any>(account);
+    if (JSONDatabase.validateValue(sample, account).length !== 0) throw new Error(`A generated sample should validate: ${JSON.stringify(sample)}`);
+    if (sample.name !== 'aaa' || sample.age !== 18 || sample.role !== 'admin' || sample.email !== 'user@example.com') {
+        throw new Error(`The sample should use minimums, the first enum member and format examples: ${JSON.stringify(sample)}`);
+    }
+    if (JSON.stringify(sample.address) !== '{"zip":"1234","country":"NL"}') throw new Error(`Nested defaults should be used: ${JSON.stringify(sample.address)}`);
+    const composed: Schema[] = [
+        { oneOf: [{ type: 'string', minLength: 2 }, { type: 'number', minimum: 10 }] },
+        { allOf: [{ type: 'object', required: ['a'], properties: { a: { type: 'number', minimum: 2 } } }, { required: ['b'], properties: { b: { format: 'uuid' } } }] },
+        { type: 'object', required: ['kind'], anyOf: [{ properties: { kind: { enum: ['a'] } }, required: ['a'] }, { required: ['b'] }] },
+        { type: 'object', properties: { t: { type: 'string' } }, required: ['t'], if: { properties: { t: { enum: [''] } } }, then: { required: ['x'] } },
+        { type: 'array', minItems: 2, prefixItems: [{ type: 'number', minimum: 1 }, { type: 'string', format: 'date' }], items: false },
+        { type: 'number', exclusiveMinimum: 0, exclusiveMaximum: 1 },
+    ];
+    for (const schema of composed) {
+        const value = JSONDatabase.generateSample(schema);
+        const issues = JSONDatabase.validateValue(value, schema);
+        if (issues.length !== 0) throw new Error(`${JSON.stringify(value)} generated for ${JSON.stringify(schema)} should validate: ${JSON.stringify(issues)}`);
+    }
+    const sampleFile = `${TEST_DB}.sample`;
+    const sampleFiles = [sampleFile, `${sampleFile}.wal`, `${sampleFile}.process_lock`];
+    for (const f of sampleFiles) if (existsSync(f)) unlinkSync(f);
+    const dbSample = new JSONDatabase(sampleFile, { durability: 'batched', lockMode: 'exclusive' });
+    dbSample.registerSchema('accounts', { type: 'object', additionalProperties: account });
+    const generated = dbSample.generateFromSchema('accounts.a1');
+    await dbSample.set('accounts.a1', generated);
+    if (JSON.stringify(dbSample.generateFromSchema('accounts.a2.address')) !== '{"zip":"1234","country":"NL"}') {
+        throw new Error('generateFromSchema should generate for the part of the schema describing the path');
+    }
+    try {
+        dbSample.generateFromSchema('other');
+        throw new Error('generateFromSchema should throw without a schema');
+    } catch (e: any) {
+        if (!e.message.startsWith('ERR_NOT_FOUND:')) throw e;
+    }
+    await dbSample.close();
+    for (const f of sampleFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 72: Concurrent Transactions
+    console.log('📝 TEST 72: Concurrent Transactions');
+    const txFile = `${TEST_DB}.txns`;
+    const txFiles = [txFile, `${txFile}.wal`, `${txFile}.process_lock`];
+    for (const f of txFiles) if (existsSync(f)) unlinkSync(f);
+    const dbTx = new JSONDatabase(txFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbTx.set('accounts', { a: 100, b: 100 });
+    await dbTx.set('log', []);
+    const nativeTx = (dbTx as any).native;
+    const first: number = nativeTx.beginTransaction();
+    const second: number = nativeTx.beginTransaction();
+    if (first === second) throw new Error('Each transaction should get its own id');
+    await dbTx.set('accounts.a', 50, { txn: first });
+    await dbTx.set('accounts.b', 150, { txn: second });
+    await dbTx.set('accounts.c', 1, { txn: first });
+    nativeTx.createSavepoint('sp', second);
+    const info = nativeTx.transactionInfo(first);
+    if (info.id !== first || info.open !== 2 || info.entries !== 2 || info.savepoints !== 0) {
+        throw new Error(`transactionInfo should describe the named transaction: ${JSON.stringify(info)}`);
+    }
+    try {
+        await dbTx.set('accounts.a', 0, { txn: second });
+        throw new Error('A write overlapping another transaction should fail');
+    } catch (e: any) {
+        if (!e.message.startsWith('ERR_CONFLICT:')) throw e;
+    }
+    try {
+        nativeTx.commitTransaction();
+        throw new Error('Committing without an id should fail while several transactions are open');
+    } catch (e: any) {
+        if (!e.message.startsWith('ERR_INVALID_ARGUMENT:')) throw e;
+    }
+    // A write without a txn joins no transaction, and fails only on a path one has written
+    await dbTx.set('accounts.e', 1);
+    const untagged = await dbTx.set('accounts.a', 0).then(() => 'written', (e: any) => String(e.message));
+    if (!untagged.startsWith('ERR_CONFLICT') || (await dbTx.get('accounts.a')) !== 50) {
+        throw new Error(`A write without a txn should fail on a path a transaction has written: ${untagged}`);
+    }
+    nativeTx.rollbackTransaction(first);
+    if (JSON.stringify(await dbTx.get('accounts')) !== '{"a":100,"b":150,"e":1}') {
+        throw new Error(`Rolling back one transaction should leave the other's writes: ${JSON.stringify(await dbTx.get('accounts'))}`);
+    }
+    if (JSON.stringify(nativeTx.touchedPaths(second)) !== '["accounts.b"]') throw new Error('The other undo log should be untouched');
+    nativeTx.commitTransaction(second);
+    const other: number = nativeTx.beginTransaction();
+    // Nor the only open one: rolling it back leaves the write
+    await dbTx.set('accounts.f', 1);
+    await dbTx.transaction(async (tx) => {
+        await tx.set('accounts.d', 1);
+        await tx.push('log', 'd');
+    });
+    nativeTx.rollbackTransaction(other);
+    if ((await dbTx.get('accounts.d')) !== 1 || JSON.stringify(await dbTx.get('log')) !== '["d"]') {
+        throw new Error('A committed transaction() should survive rolling back another transaction');
+    }
+    if ((await dbTx.get('accounts.f')) !== 1) throw new Error('A write without a txn should survive a rollback');
+    await dbTx.close();
+    for (const f of txFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 73: Transaction Isolation
+    console.log('📝 TEST 73: Transaction Isolation');
+    const isoFile = `${TEST_DB}.isolation`;
+    const isoFiles = [isoFile, `${isoFile}.wal`, `${isoFile}.process_lock`];
+    for (const f of isoFiles) if (existsSync(f)) unlinkSync(f);
+    const dbIso = new JSONDatabase(isoFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbIso.set('bank', { alice: 100, bob: 100 });
+    const isoScript = 'test_isolation_worker.cjs';
+    writeFileSync(isoScript, `
+        const { parentPort, workerData } = require('worker_threads');
+        const { ReaderHandle } = require('./index.js');
+        const reader = ReaderHandle.open(workerData);
+        parentPort.on('message', () => parentPort.postMessage({ bank: reader.get('bank'), carol: reader.has('bank.carol') }));
+    `);
+    const isoWorker = new Worker(isoScript, { workerData: dbIso.readerToken() });
+    const observe = () => new Promise<any>((resolve, reject) => {
+        isoWorker.once('message', resolve);
+        isoWorker.once('error', reject);
+        isoWorker.postMessage('read');
+    });
+    await dbIso.transaction(async (tx) => {
+        await tx.set('bank.alice', 50);
+        await tx.set('bank.bob', 150);
+        await tx.set('bank.carol', 0);
+        const seen = await observe();
+        if (JSON.stringify(seen) !== '{"bank":{"alice":100,"bob":100},"carol":false}') {
+            throw new Error(`A reader thread should see the committed state mid-transaction: ${JSON.stringify(seen)}`);
+        }
+        if (JSON.stringify(await dbIso.get('bank')) !== '{"alice":100,"bob":100}') throw new Error('Reads outside the transaction should see the committed state');
+        if ((await tx.get<number>('bank.alice')) !== 50) throw new Error('The transaction should see its own writes');
+        const nativeIso = (dbIso as any).native;
+        const surface = {
+            keys: await dbIso.keys('bank'),
+            count: await dbIso.count('bank'),
+            query: await dbIso.parallelQuery('bank', []),
+            queryString: await dbIso.queryString('bank', ''),
+            page: (await dbIso.getPage('bank')).items.map((item: any) => item.key),
+            sum: await dbIso.parallelAggregate('bank', 'sum'),
+            pointers: nativeIso.getPointers('bank', ['alice', 'carol']),
+            snapshot: nativeIso.snapshotGet(nativeIso.snapshotOpen(), 'bank'),
+        };
+        const expectedSurface = '{"keys":["alice","bob"],"count":2,"query":[100,100],"queryString":[100,100],'
+            + '"page":["alice","bob"],"sum":200,"pointers":{"alice":100,"carol":null},"snapshot":{"alice":100,"bob":100}}';
+        if (JSON.stringify(surface) !== expectedSurface) {
+            throw new Error(`Queries and iteration outside the transaction should see the committed state: ${JSON.stringify(surface)}`);
+        }
+        const exported = await dbIso.exportArchive(`${isoFile}.archive`).then(() => 'exported', (e: any) => String(e.message));
+        if (!exported.startsWith('ERR_TRANSACTION_ACTIVE')) throw new Error(`An archive would mix committed data and live indexes: ${exported}`);
+        try {
+            await dbIso.set('bank.dave', 1);
+            throw new Error('A write outside an exclusive transaction should fail');
+        } catch (e: any) {
+            if (!e.message.startsWith('ERR_TRANSACTION_ACTIVE:')) throw e;
+        }
+    }, { isolation: 'exclusive' });
+    const committed = await observe();
+    if (JSON.stringify(committed) !== '{"bank":{"alice":50,"bob":150,"carol":0},"carol":true}') {
+        throw new Error(`A reader thread should see the writes once committed: ${JSON.stringify(committed)}`);
+    }
+    const aborted = await dbIso.transaction(async (tx) => {
+        await tx.set('bank.alice', 0);
+        throw new Error('abort');
+    }, { isolation: 'exclusive' }).catch((e: Error) => e.message);
+    if (aborted !== 'abort' || (await observe()).bank.alice !== 50) throw new Error('A rolled back exclusive transaction should never be seen');
+    await dbIso.set('bank.dave', 1);
+    await isoWorker.terminate();
+    unlinkSync(isoScript);
+    await dbIso.close();
+    for (const f of isoFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 74: Crash-Atomic Transactions
+    console.log('📝 TEST 74: Crash-Atomic Transactions');
+    const crashTxFile = `${TEST_DB}.crashtx`;
+    const crashTxCopy = `${TEST_DB}.crashtx.copy`;
+    const crashTxFiles = [crashTxFile, `${crashTxFile}.wal`, `${crashTxFile}.process_lock`, crashTxCopy, `${crashTxCopy}.wal`, `${crashTxCopy}.process_lock`];
+    for (const f of crashTxFiles) if (existsSync(f)) unlinkSync(f);
+    const dbCrashTx = new JSONDatabase(crashTxFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeCrashTx = (dbCrashTx as any).native;
+    await dbCrashTx.set('stock', { apples: 10 });
+    await dbCrashTx.transaction(async (tx) => {
+        await tx.set('stock.apples', 8);
+        await tx.savepoint('before_pears');
+        await tx.set('stock.pears', 5);
+        await tx.rollbackTo('before_pears');
+    });
+    const aborted73 = await dbCrashTx.transaction(async (tx) => {
+        await tx.set('stock.apples', 0);
+        throw new Error('abort');
+    }).catch((e: Error) => e.message);
+    if (aborted73 !== 'abort') throw new Error('The aborted transaction should rethrow');
+    const pending: number = nativeCrashTx.beginTransaction();
+    await dbCrashTx.set('stock.apples', 1, { txn: pending });
+    await dbCrashTx.set('stock.plums', 3, { txn: pending });
+    await dbCrashTx.delete('stock', { txn: pending });
+    // Sequence values are engine metadata: logged outside the transaction
+    const orderId: number = nativeCrashTx.nextSequence('orders');
+    const savedMidTx = await dbCrashTx.save().then(() => 'saved', (e: any) => String(e.message));
+    if (!savedMidTx.startsWith('ERR_TRANSACTION_ACTIVE')) {
+        throw new Error(`A save must not write an open transaction's writes: ${savedMidTx}`);
+    }
+    await dbCrashTx.sync();
+    const markers = nativeCrashTx.walInspect().records.filter((r: any) => r.txn === pending).map((r: any) => r.op);
+    if (JSON.stringify(markers) !== '["begin","set","set","delete"]') {
+        throw new Error(`Transaction writes should be tagged with its id: ${JSON.stringify(markers)}`);
+    }
+    // What a crash right now would leave behind
+    copyFileSync(`${crashTxFile}.wal`, `${crashTxCopy}.wal`);
+    nativeCrashTx.rollbackTransaction(pending);
+    await dbCrashTx.close();
+    const dbRolledBack = new JSONDatabase(crashTxFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbRolledBack.get('stock')) !== '{"apples":8}') {
+        throw new Error(`Rolled-back writes should not reach the file: ${JSON.stringify(await dbRolledBack.get('stock'))}`);
+    }
+    await dbRolledBack.close();
+
+    const dbCrashCopy = new JSONDatabase(crashTxCopy, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbCrashCopy.get('stock')) !== '{"apples":8}') {
+        throw new Error(`Only committed transactions should be recovered: ${JSON.stringify(await dbCrashCopy.get('stock'))}`);
+    }
+    const nextOrderId: number = (dbCrashCopy as any).native.nextSequence('orders');
+    if (nextOrderId <= orderId) throw new Error(`Sequence ids must not be reused after a crash: ${orderId} then ${nextOrderId}`);
+    const crashReport = (dbCrashCopy as any).native.integrityReport().wal;
+    if (crashReport.uncommittedOps !== 4 || crashReport.transactionMarkers !== 5) {
+        throw new Error(`Unexpected recovery counts: ${JSON.stringify(crashReport)}`);
+    }
+    await dbCrashCopy.close();
+    for (const f of crashTxFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 75: Batches in Transactions
+    console.log('📝 TEST 75: Batches in Transactions');
+    const batchTxFile = `${TEST_DB}.batchtx`;
+    const batchTxFiles = [batchTxFile, `${batchTxFile}.wal`, `${batchTxFile}.process_lock`];
+    for (const f of batchTxFiles) if (existsSync(f)) unlinkSync(f);
+    const dbBatchTx = new JSONDatabase(batchTxFile, { durability: 'batched', lockMode: 'exclusive' });
+    const seeded: Record<string, { n: number }> = {};
+    for (let i = 0; i < 5000; i++) seeded[`u${i}`] = { n: i };
+    await dbBatchTx.set('users', seeded);
+    await dbBatchTx.set('list', [1, 2, 3]);
+    const beforeBatch = JSON.stringify(await dbBatchTx.get(''));
+    const batchOps: Array<{ path: string; value: unknown }> = [];
+    for (let i = 0; i < 10000; i++) batchOps.push({ path: `users.u${i}`, value: { n: -i } });
+    batchOps.push({ path: 'list.5', value: 9 }, { path: 'fresh.deep.key', value: 1 });
+    const rolledBack = await dbBatchTx.transaction(async (tx) => {
+        const result = await dbBatchTx.batchSetParallel(batchOps, { txn: tx.id });
+        if (result.count !== batchOps.length) throw new Error(`The batch should write every path: ${result.count}`);
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (rolledBack !== 'undo') throw new Error(`The transaction should rethrow: ${rolledBack}`);
+    if (JSON.stringify(await dbBatchTx.get('')) !== beforeBatch) {
+        throw new Error('Rolling back should restore every path the batch touched');
+    }
+    if (await dbBatchTx.has('users.u5000') || await dbBatchTx.has('fresh')) {
+        throw new Error('Paths the batch created should be deleted on rollback');
+    }
+    const nativeBatchTx = (dbBatchTx as any).native;
+    const exclusiveBatch: number = nativeBatchTx.beginTransaction({ isolation: 'exclusive' });
+    try {
+        await dbBatchTx.batchSetParallel([{ path: 'users.u1', value: 0 }]);
+        throw new Error('A batch outside an exclusive transaction should fail');
+    } catch (e: any) {
+        if (!e.message.startsWith('ERR_TRANSACTION_ACTIVE:')) throw e;
+    }
+    nativeBatchTx.rollbackTransaction(exclusiveBatch);
+    await dbBatchTx.close();
+    for (const f of batchTxFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 76: Push Rollback
+    console.log('📝 TEST 76: Push Rollback');
+    const pushTxFile = `${TEST_DB}.pushtx`;
+    const pushTxFiles = [pushTxFile, `${pushTxFile}.wal`, `${pushTxFile}.process_lock`];
+    for (const f of pushTxFiles) if (existsSync(f)) unlinkSync(f);
+    const dbPushTx = new JSONDatabase(pushTxFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativePushTx = (dbPushTx as any).native;
+    await dbPushTx.set('big', Array.from({ length: 100000 }, (_, i) => i));
+    const pushTxn: number = nativePushTx.beginTransaction();
+    for (let i = 0; i < 100; i++) nativePushTx.pushMany('big', [-1 - i], { txn: pushTxn });
+    const pushInfo = nativePushTx.transactionInfo(pushTxn);
+    if (pushInfo.entries !== 1 || pushInfo.undoBytes > 100) {
+        throw new Error(`Pushes should be undone by length, not a copy of the array: ${JSON.stringify(pushInfo)}`);
+    }
+    if ((nativePushTx.getOriginal('big.100000', pushTxn) ?? null) !== null) throw new Error('A pushed element should not exist originally');
+    nativePushTx.rollbackTransaction(pushTxn);
+    const bigAfter = await dbPushTx.get<number[]>('big');
+    if (bigAfter?.length !== 100000 || bigAfter[99999] !== 99999) throw new Error('Rollback should drop the pushed elements');
+    await dbPushTx.set('list', [1, 2, 3]);
+    const mixed = await dbPushTx.transaction(async (tx) => {
+        await tx.push('/list', 4);
+        nativePushTx.splice('list', 0, 1, [9], { txn: tx.id });
+        await tx.push('list', 5);
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (mixed !== 'undo' || JSON.stringify(await dbPushTx.get('list')) !== '[1,2,3]') {
+        throw new Error(`Rollback should undo pushes and splices in order: ${JSON.stringify(await dbPushTx.get('list'))}`);
+    }
+    if (JSON.stringify(Object.keys(await dbPushTx.get('') as object)) !== '["big","list"]') {
+        throw new Error('A slash path should be rolled back where it was written');
+    }
+    await dbPushTx.close();
+    for (const f of pushTxFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 77: Archive Round Trip
+    console.log('📝 TEST 77: Archive Round Trip');
+    const archiveFile = `${TEST_DB}.archived`;
+    const archiveOut = `${TEST_DB}.jdbarchive`;
+    const archiveTarget = `${TEST_DB}.restored`;
+    const archiveFiles = [
+        archiveFile, `${archiveFile}.wal`, `${archiveFile}.process_lock`, `${archiveFile}.byRole.idx`, archiveOut,
+        archiveTarget, `${archiveTarget}.wal`, `${archiveTarget}.process_lock`, `${archiveTarget}.byRole.idx`,
+    ];
+    for (const f of archiveFiles) if (existsSync(f)) unlinkSync(f);
+    const dbArchived = new JSONDatabase(archiveFile, {
+        durability: 'batched',
+        lockMode: 'exclusive',
+        indices: [{ name: 'byRole', path: 'staff', field: 'role', sparse: true }],
+    });
+    await dbArchived.set('staff', { a: { role: 'admin' }, b: { role: null }, c: { role: 'dev' }, d: {} });
+    const archived = await dbArchived.exportArchive(archiveOut);
+    const indexEntry = archived.entries.find(e => e.kind === 'index');
+    if (indexEntry?.collection !== 'staff' || indexEntry.sparse !== true) {
+        throw new Error(`The manifest should record the index's collection and sparseness: ${JSON.stringify(indexEntry)}`);
+    }
+    await dbArchived.close();
+    const dbRestored = await JSONDatabase.importArchive(archiveOut, archiveTarget);
+    await dbRestored.set('staff.e', { role: 'admin' });
+    const nativeRestored = (dbRestored as any).native;
+    const admins = nativeRestored.findIndexPaths('byRole', 'admin');
+    if (JSON.stringify(admins) !== '["staff.a","staff.e"]') throw new Error(`An imported index should follow new writes: ${JSON.stringify(admins)}`);
+    const roleKeys = nativeRestored.indexKeys('byRole').map((k: any) => k.key);
+    if (JSON.stringify(roleKeys) !== '["admin","dev"]') throw new Error(`An imported sparse index should stay sparse: ${JSON.stringify(roleKeys)}`);
+    const restoredAdmins = await dbRestored.parallelQuery('staff', [{ field: 'role', op: 'eq', value: 'admin' }]);
+    if (restoredAdmins.length !== 2 || nativeRestored.explainQuery('staff', [{ field: 'role', op: 'eq', value: 'admin' }]).strategy !== 'index') {
+        throw new Error('Queries on the restored database should use the imported index');
+    }
+    await dbRestored.close();
+    for (const f of archiveFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 78: Damaged Archives
+    console.log('📝 TEST 78: Damaged Archives');
+    const damagedSource = `${TEST_DB}.damaged`;
+    const damagedArchive = `${TEST_DB}.damaged.jdbarchive`;
+    const damagedTarget = `${TEST_DB}.damaged.restored`;
+    const damagedCopies = [`${damagedArchive}.truncated`, `${damagedArchive}.flipped`];
+    const damagedFiles = [damagedSource, `${damagedSource}.wal`, `${damagedSource}.process_lock`, `${damagedSource}.byN.idx`, damagedArchive, ...damagedCopies];
+    for (const f of damagedFiles) if (existsSync(f)) unlinkSync(f);
+    const dbDamaged = new JSONDatabase(damagedSource, {
+        durability: 'batched',
+        lockMode: 'exclusive',
+        indices: [{ name: 'byN', path: 'items', field: 'n' }],
+    });
+    await dbDamaged.set('items', { a: { n: 1 }, b: { n: 2 } });
+    await dbDamaged.exportArchive(damagedArchive);
+    await dbDamaged.close();
+    const intact = readFileSync(damagedArchive);
+    writeFileSync(damagedCopies[0], intact.subarray(0, intact.length - 20));
+    const flipped = Buffer.from(intact);
+    flipped[flipped.length - 30] ^= 0xff;
+    writeFileSync(damagedCopies[1], flipped);
+    for (const copy of damagedCopies) {
+        const outcome = await JSONDatabase.importArchive(copy, damagedTarget).then(
+            async (db) => { await db.close(); return 'imported'; },
+            (e: any) => String(e.message),
+        );
+        if (!outcome.startsWith('ERR_CORRUPT')) throw new Error(`A damaged archive should fail to import: ${outcome}`);
+        const leftovers = [damagedTarget, `${damagedTarget}.wal`, `${damagedTarget}.byN.idx`, `${damagedTarget}.import`].filter(existsSync);
+        if (leftovers.length > 0) throw new Error(`A failed import should leave nothing behind: ${leftovers.join(', ')}`);
+    }
+    // Importing over an existing database refuses before touching it
+    const sourceBefore = readFileSync(damagedSource, 'utf8');
+    const overExisting = await JSONDatabase.importArchive(damagedArchive, damagedSource).then(
+        async (db) => { await db.close(); return 'imported'; },
+        (e: any) => String(e.message),
+    );
+    if (!overExisting.startsWith('ERR_INVALID_ARGUMENT') || readFileSync(damagedSource, 'utf8') !== sourceBefore) {
+        throw new Error(`An import should never overwrite an existing database: ${overExisting}`);
+    }
+    const dbDamagedAgain = new JSONDatabase(damagedSource, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbDamagedAgain.get('items')) !== '{"a":{"n":1},"b":{"n":2}}') throw new Error('The source database should be untouched');
+    await dbDamagedAgain.close();
+    for (const f of damagedFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 79: Lazy Collections
+    console.log('📝 TEST 79: Lazy Collections');
+    const lazyFile = `${TEST_DB}.lazycol`;
+    const lazyFiles = [lazyFile, `${lazyFile}.wal`, `${lazyFile}.process_lock`];
+    const clearLazy = () => {
+        for (const f of lazyFiles) if (existsSync(f)) unlinkSync(f);
+        rmSync(`${lazyFile}.lazy`, { recursive: true, force: true });
+    };
+    clearLazy();
+    const lazyOptions = { durability: 'batched' as const, lockMode: 'exclusive' as const, lazyCollections: ['events'], lazyMaxResident: 2 };
+    const dbLazy = new JSONDatabase(lazyFile, lazyOptions);
+    const lazyEvents: Record<string, { kind: string; n: number }> = {};
+    for (let i = 0; i < 5; i++) lazyEvents[`e${i}`] = { kind: i % 2 ? 'odd' : 'even', n: i };
+    await dbLazy.set('events', lazyEvents);
+    await dbLazy.set('plain', { x: 1 });
+    await dbLazy.close();
+    const mainFile = JSON.parse(readFileSync(lazyFile, 'utf8'));
+    if ('events' in mainFile || !existsSync(`${lazyFile}.lazy/events.data`)) throw new Error('A lazy collection should be saved outside the main file');
+
+    // Opening reads only the index; the first access loads one entry
+    const dbLazyOpen = new JSONDatabase(lazyFile, lazyOptions);
+    const opened = dbLazyOpen.lazyStatus();
+    if (opened.collections!.events.stored !== 5 || opened.collections!.events.resident !== 0) {
+        throw new Error(`No entry should load on open: ${JSON.stringify(opened.collections!.events)}`);
+    }
+    if (JSON.stringify(await dbLazyOpen.get('events.e1')) !== '{"kind":"odd","n":1}') throw new Error('A lazy entry should load on first access');
+    const afterGet = dbLazyOpen.lazyStatus();
+    if (afterGet.loads !== 1 || afterGet.collections!.events.resident !== 1) throw new Error(`Only the read entry should load: ${JSON.stringify(afterGet)}`);
+
+    // Writes to entries never loaded: a new one and a field of a stored one
+    await dbLazyOpen.set('events.e9', { kind: 'odd', n: 9 });
+    await dbLazyOpen.set('events.e2.n', 20);
+    if (JSON.stringify(await dbLazyOpen.get('events.e2')) !== '{"kind":"even","n":20}') throw new Error('A write into an unloaded entry should keep its other fields');
+    if (dbLazyOpen.lazyStatus().collections!.events.dirty !== 2) throw new Error('Written entries should stay resident until saved');
+
+    // A query covers loaded, written and unloaded entries alike
+    const odd = await dbLazyOpen.parallelQuery<{ n: number }>('events', [{ field: 'kind', op: 'eq', value: 'odd' }]);
+    if (JSON.stringify(odd.map(e => e.n).sort((a, b) => a - b)) !== '[1,3,9]') throw new Error(`A query should see every entry: ${JSON.stringify(odd)}`);
+    if (!dbLazyOpen.lazyStatus().collections!.events.complete) throw new Error('A query should load the collection in full');
+    await dbLazyOpen.close();
+
+    const dbLazyReopen = new JSONDatabase(lazyFile, lazyOptions);
+    const reopenedEvents = await dbLazyReopen.get<Record<string, { n: number }>>('events');
+    const reopenedNs = Object.keys(reopenedEvents ?? {}).sort().map(key => reopenedEvents![key].n);
+    if (JSON.stringify(reopenedNs) !== '[0,1,20,3,4,9]') throw new Error(`Saved lazy writes should survive a reopen: ${JSON.stringify(reopenedNs)}`);
+    if ((await dbLazyReopen.get('plain.x')) !== 1) throw new Error('Other collections should be unaffected');
+    await dbLazyReopen.close();
+    clearLazy();
+    console.log('   ✅ Passed\n');
+
+    // TEST 80: Reorder
+    console.log('📝 TEST 80: Reorder');
+    const reorderFile = `${TEST_DB}.reorder`;
+    const reorderFiles = [reorderFile, `${reorderFile}.wal`, `${reorderFile}.process_lock`];
+    for (const f of reorderFiles) if (existsSync(f)) unlinkSync(f);
+    const dbReorder = new JSONDatabase(reorderFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeReorder = (dbReorder as any).native;
+    await dbReorder.set('list', ['a', 'b', 'c', 'd']);
+    nativeReorder.reorder('list', 0, 99);
+    nativeReorder.reorderAll('list', [3, 0, 1, 2]);
+    if (JSON.stringify(await dbReorder.get('list')) !== '["a","b","c","d"]') throw new Error('Reorders should move elements and clamp the target');
+    const reorderErrors = [
+        () => nativeReorder.reorder('list', 4, 0),
+        () => nativeReorder.reorderAll('list', [0, 0, 1, 2]),
+        () => nativeReorder.reorderAll('list', [0, 1]),
+    ];
+    for (const call of reorderErrors) {
+        try { call(); throw new Error('should fail'); } catch (e: any) {
+            if (!e.message.startsWith('ERR_INVALID_ARGUMENT')) throw new Error(`Bad indexes should fail with ERR_INVALID_ARGUMENT: ${e.message}`);
+        }
+    }
+
+    // A tuple schema checks every position after a move
+    await dbReorder.set('tuple', ['id', 1, true]);
+    nativeReorder.registerSchema('tuple', JSON.stringify({ type: 'array', prefixItems: [{ type: 'string' }, { type: 'number' }, { type: 'boolean' }] }), null);
+    for (const call of [() => nativeReorder.reorder('tuple', 0, 2), () => nativeReorder.reorderAll('tuple', [2, 1, 0])]) {
+        try { call(); throw new Error('should fail'); } catch (e: any) {
+            if (!e.message.startsWith('ERR_VALIDATION')) throw new Error(`A move breaking prefixItems should fail: ${e.message}`);
+        }
+    }
+    if (JSON.stringify(await dbReorder.get('tuple')) !== '["id",1,true]') throw new Error('A rejected reorder should change nothing');
+
+    // Leases and transactions apply as to other array writes
+    nativeReorder.acquireLease('list', 'worker', 60_000);
+    nativeReorder.setLeaseEnforcement(true);
+    try { nativeReorder.reorder('list', 0, 1); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_CONFLICT')) throw new Error(`A leased array should refuse other writers: ${e.message}`);
+    }
+    nativeReorder.reorder('list', 0, 1, { owner: 'worker' });
+    nativeReorder.setLeaseEnforcement(false);
+    const reorderTxn: number = nativeReorder.beginTransaction();
+    nativeReorder.reorderAll('list', [3, 2, 1, 0], { txn: reorderTxn });
+    nativeReorder.rollbackTransaction(reorderTxn);
+    if (JSON.stringify(await dbReorder.get('list')) !== '["b","a","c","d"]') throw new Error('Rollback should undo a reorder');
+    await dbReorder.close();
+    const dbReorderReopened = new JSONDatabase(reorderFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbReorderReopened.get('list')) !== '["b","a","c","d"]') throw new Error('Reorders should be replayed from the WAL');
+    await dbReorderReopened.close();
+    for (const f of reorderFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 81: Sequences
+    console.log('📝 TEST 81: Sequences');
+    const seqFile = `${TEST_DB}.seq`;
+    const seqCopy = `${TEST_DB}.seq.copy`;
+    const seqFiles = [seqFile, `${seqFile}.wal`, `${seqFile}.process_lock`, seqCopy, `${seqCopy}.wal`, `${seqCopy}.process_lock`];
+    for (const f of seqFiles) if (existsSync(f)) unlinkSync(f);
+    const dbSeq = new JSONDatabase(seqFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeSeq = (dbSeq as any).native;
+    const firstIds = [nativeSeq.nextSequence('invoices'), nativeSeq.nextSequence('invoices'), nativeSeq.nextSequence('invoices')];
+    const block = nativeSeq.reserveSequence('invoices', 5);
+    if (JSON.stringify(firstIds) !== '[1,2,3]' || block.start !== 4 || block.end !== 8) {
+        throw new Error(`Sequence values should be consecutive: ${JSON.stringify(firstIds)} ${JSON.stringify(block)}`);
+    }
+    // A rolled-back transaction does not hand its value out again
+    const seqTxn: number = nativeSeq.beginTransaction();
+    await dbSeq.set('draft', 1, { txn: seqTxn });
+    const inTxn: number = nativeSeq.nextSequence('invoices');
+    nativeSeq.rollbackTransaction(seqTxn);
+    if (inTxn !== 9 || nativeSeq.currentSequence('invoices') !== 9) throw new Error('Rollback must not rewind a sequence');
+    if (JSON.stringify(await dbSeq.get('')) !== '{}') throw new Error('Sequences should stay in the metadata namespace');
+    await dbSeq.close();
+
+    const dbSeqReopened = new JSONDatabase(seqFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeSeqReopened = (dbSeqReopened as any).native;
+    if (nativeSeqReopened.currentSequence('invoices') !== 9 || nativeSeqReopened.nextSequence('invoices') !== 10) {
+        throw new Error('A sequence should continue after a reopen');
+    }
+    const lastHandedOut: number = nativeSeqReopened.nextSequence('invoices');
+    await dbSeqReopened.sync();
+    // What a crash right now would leave behind: the values exist only in the WAL
+    copyFileSync(seqFile, seqCopy);
+    copyFileSync(`${seqFile}.wal`, `${seqCopy}.wal`);
+    await dbSeqReopened.close();
+    const dbSeqCrash = new JSONDatabase(seqCopy, { durability: 'batched', lockMode: 'exclusive' });
+    const afterCrash: number = (dbSeqCrash as any).native.nextSequence('invoices');
+    if (afterCrash <= lastHandedOut) throw new Error(`Sequence ids must not be reused after a crash: ${lastHandedOut} then ${afterCrash}`);
+    if ((dbSeqCrash as any).native.currentSequence('unused') !== 0) throw new Error('An unused sequence should read 0');
+    await dbSeqCrash.close();
+    for (const f of seqFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 82: Ordered Object Maps
+    console.log('📝 TEST 82: Ordered Object Maps');
+    const orderFile = `${TEST_DB}.ordered`;
+    const orderOut = `${TEST_DB}.ordered.jdbarchive`;
+    const orderTarget = `${TEST_DB}.ordered.restored`;
+    const orderFiles = [
+        orderFile, `${orderFile}.wal`, `${orderFile}.process_lock`, orderOut,
+        orderTarget, `${orderTarget}.wal`, `${orderTarget}.process_lock`,
+    ];
+    for (const f of orderFiles) if (existsSync(f)) unlinkSync(f);
+    // A user key named like the reserved namespace is renamed in place
+    writeFileSync(orderFile, JSON.stringify({ menu: { home: 1 }, __jsondb__: { mine: true }, footer: {} }));
+    const dbOrder = new JSONDatabase(orderFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeOrder = (dbOrder as any).native;
+    if (JSON.stringify(Object.keys(await dbOrder.get('') as object)) !== '["menu","__jsondb__user","footer"]') {
+        throw new Error(`A relocated key should keep its position: ${JSON.stringify(Object.keys(await dbOrder.get('') as object))}`);
+    }
+    await dbOrder.set('menu', { home: 1, about: 2, contact: 3, blog: 4 });
+    nativeOrder.insertBefore('menu', 'news', 5, 'about');
+    const faq = nativeOrder.insertAfter('menu', 'faq', 6, 'contact');
+    const shop = nativeOrder.insertBefore('menu', 'shop', 7, 'missing');
+    if (faq.position !== 4 || faq.appended || !shop.appended) throw new Error(`Unexpected placements: ${JSON.stringify([faq, shop])}`);
+    nativeOrder.moveKey('menu', 'blog', 'home');
+    await dbOrder.delete('menu.news');
+    const expectedMenu = '["blog","home","about","contact","faq","shop"]';
+    if (JSON.stringify(Object.keys(await dbOrder.get('menu') as object)) !== expectedMenu) {
+        throw new Error(`Keys should keep their placed order: ${JSON.stringify(Object.keys(await dbOrder.get('menu') as object))}`);
+    }
+    // A rolled-back delete puts the key back where it was
+    const orderAborted = await dbOrder.transaction(async (tx) => {
+        await tx.delete('menu.home');
+        await tx.savepoint('mid');
+        await tx.delete('menu.contact');
+        await tx.rollbackTo('mid');
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (orderAborted !== 'undo' || JSON.stringify(Object.keys(await dbOrder.get('menu') as object)) !== expectedMenu) {
+        throw new Error(`Rollback should keep key order: ${JSON.stringify(Object.keys(await dbOrder.get('menu') as object))}`);
+    }
+    // Dropping a view keeps the others in definition order
+    for (const name of ['first', 'second', 'third']) nativeOrder.createView(name, { path: 'menu' });
+    nativeOrder.dropView('first');
+    if (JSON.stringify(nativeOrder.listViews().map((v: any) => v.name)) !== '["second","third"]') throw new Error('Dropping a view should not reorder the rest');
+    await dbOrder.exportArchive(orderOut);
+    await dbOrder.close();
+    if (JSON.stringify(Object.keys(JSON.parse(readFileSync(orderFile, 'utf8')).menu)) !== expectedMenu) throw new Error('Saving should keep key order');
+
+    const dbOrderReopened = new JSONDatabase(orderFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(Object.keys(await dbOrderReopened.get('menu') as object)) !== expectedMenu) throw new Error('Reopening should keep key order');
+    await dbOrderReopened.close();
+    const dbOrderRestored = await JSONDatabase.importArchive(orderOut, orderTarget);
+    if (JSON.stringify(Object.keys(await dbOrderRestored.get('menu') as object)) !== expectedMenu) throw new Error('An archive should keep key order');
+    if (JSON.stringify(Object.keys(await dbOrderRestored.get('') as object)) !== '["menu","__jsondb__user","footer"]') {
+        throw new Error('An archive should keep top-level key order');
+    }
+    await dbOrderRestored.close();
+    for (const f of orderFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 83: Rename Field
+    console.log('📝 TEST 83: Rename Field');
+    const renameFile = `${TEST_DB}.rename`;
+    const renameFiles = [renameFile, `${renameFile}.wal`, `${renameFile}.process_lock`, `${renameFile}.byUserName.idx`];
+    for (const f of renameFiles) if (existsSync(f)) unlinkSync(f);
+    const dbRename = new JSONDatabase(renameFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeRename = (dbRename as any).native;
+    await dbRename.set('users', { a: { userName: 'ann' }, b: { userName: 'bob', username: 'taken' }, c: { age: 3 } });
+    nativeRename.registerIndex('byUserName', 'userName');
+    const skipped = nativeRename.renameField('users', 'userName', 'username');
+    if (skipped.changed !== 1 || skipped.skipped !== 1 || JSON.stringify(skipped.affectedIndexes) !== '["byUserName"]') {
+        throw new Error(`An existing target should be skipped and the index flagged: ${JSON.stringify(skipped)}`);
+    }
+    const overwritten = nativeRename.renameField('users', 'userName', 'username', { overwrite: true });
+    if (overwritten.changed !== 1 || (await dbRename.get('users.b.username')) !== 'bob') throw new Error('overwrite should replace the target');
+    // Dot-notation targets create the nesting
+    nativeRename.renameField('users', 'username', 'profile.name');
+    const renamedUsers = '{"a":{"profile":{"name":"ann"}},"b":{"profile":{"name":"bob"}},"c":{"age":3}}';
+    if (JSON.stringify(await dbRename.get('users')) !== renamedUsers) throw new Error(`Unexpected documents: ${JSON.stringify(await dbRename.get('users'))}`);
+    const renameAborted = await dbRename.transaction(async (tx) => {
+        nativeRename.renameField('users', 'profile.name', 'handle', { txn: tx.id });
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (renameAborted !== 'undo' || JSON.stringify(await dbRename.get('users')) !== renamedUsers) throw new Error('Rollback should undo a rename');
+    // Keys holding '/' or '~' are documents like any other
+    await dbRename.set('files', { 'docs/a': { title: 'a' }, 'x~1': { title: 'x' } });
+    const escapedAborted = await dbRename.transaction(async (tx) => {
+        nativeRename.renameField('files', 'title', 'name', { txn: tx.id });
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (escapedAborted !== 'undo' || JSON.stringify(await dbRename.get('files')) !== '{"docs/a":{"title":"a"},"x~1":{"title":"x"}}') {
+        throw new Error(`Rollback should restore keys holding '/' or '~' in place: ${JSON.stringify(await dbRename.get('files'))}`);
+    }
+    if (nativeRename.renameField('files', 'title', 'name').changed !== 2) throw new Error("Keys holding '/' or '~' should be renamed");
+    if (nativeRename.mapCollection('files', null, (doc: any, key: string) => ({ ...doc, key })).updated !== 2) throw new Error("Keys holding '/' or '~' should be mapped");
+    const escapedFiles = '{"docs/a":{"name":"a","key":"docs/a"},"x~1":{"name":"x","key":"x~1"}}';
+    if (JSON.stringify(await dbRename.get('files')) !== escapedFiles) throw new Error(`Unexpected documents: ${JSON.stringify(await dbRename.get('files'))}`);
+    await dbRename.close();
+    const dbRenameReopened = new JSONDatabase(renameFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbRenameReopened.get('users')) !== renamedUsers) throw new Error('Renames should persist');
+    if (JSON.stringify(await dbRenameReopened.get('files')) !== escapedFiles) throw new Error("Keys holding '/' or '~' should persist where they were written");
+    await dbRenameReopened.close();
+    for (const f of renameFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 84: Map Collection
+    console.log('📝 TEST 84: Map Collection');
+    const mapFile = `${TEST_DB}.map`;
+    const mapFiles = [mapFile, `${mapFile}.wal`, `${mapFile}.process_lock`];
+    for (const f of mapFiles) if (existsSync(f)) unlinkSync(f);
+    const dbMap = new JSONDatabase(mapFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeMap = (dbMap as any).native;
+    const legacyItems = { a: { tags: 'x,y', kind: 't' }, b: { tags: 'z', kind: 't' }, c: { tags: 'w', kind: 'o' } };
+    await dbMap.set('items', legacyItems);
+    const splitTags = (doc: any) => ({ ...doc, tags: doc.tags.split(',') });
+    const preview = nativeMap.mapCollection('items', [{ field: 'kind', op: 'eq', value: 't' }], splitTags, { dryRun: true });
+    if (JSON.stringify(preview.changes.map((c: any) => [c.path, c.after.tags])) !== '[["items.a",["x","y"]],["items.b",["z"]]]') {
+        throw new Error(`A dry run should list the matching changes: ${JSON.stringify(preview)}`);
+    }
+    if (JSON.stringify(await dbMap.get('items')) !== JSON.stringify(legacyItems)) throw new Error('A dry run should change nothing');
+    // The mapper runs without a lock: a document written meanwhile is reported, not overwritten
+    const mapped = nativeMap.mapCollection('items', null, (doc: any, key: string) => {
+        if (key === 'a') nativeMap.set('items.b.kind', 'edited');
+        return key === 'c' ? undefined : splitTags(doc);
+    });
+    if (mapped.updated !== 1 || mapped.unchanged !== 1 || JSON.stringify(mapped.conflicts) !== '["b"]') {
+        throw new Error(`Unexpected mapCollection result: ${JSON.stringify(mapped)}`);
+    }
+    const mappedItems = '{"a":{"tags":["x","y"],"kind":"t"},"b":{"tags":"z","kind":"edited"},"c":{"tags":"w","kind":"o"}}';
+    if (JSON.stringify(await dbMap.get('items')) !== mappedItems) throw new Error(`Unexpected documents: ${JSON.stringify(await dbMap.get('items'))}`);
+    await dbMap.close();
+    const dbMapReopened = new JSONDatabase(mapFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbMapReopened.get('items')) !== mappedItems) throw new Error('Mapped documents should persist');
+    await dbMapReopened.close();
+    for (const f of mapFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 85: Snapshot Queries
+    console.log('📝 TEST 85: Snapshot Queries');
+    const snapFile = `${TEST_DB}.snapq`;
+    const snapFiles = [snapFile, `${snapFile}.wal`, `${snapFile}.process_lock`];
+    for (const f of snapFiles) if (existsSync(f)) unlinkSync(f);
+    const dbSnap = new JSONDatabase(snapFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeSnap = (dbSnap as any).native;
+    await dbSnap.set('orders', { o1: { total: 10, status: 'open' }, o2: { total: 20, status: 'open' } });
+    nativeSnap.setMaxSnapshots(2);
+    const snapHandle: number = nativeSnap.snapshotOpen();
+    await dbSnap.set('orders.o3', { total: 30, status: 'open' });
+    await dbSnap.set('orders.o1.status', 'paid');
+    // Every read through the handle sees the state at open
+    const openOrders = nativeSnap.snapshotQuery(snapHandle, 'orders', [{ field: 'status', op: 'eq', value: 'open' }]);
+    if (openOrders.length !== 2 || nativeSnap.snapshotAggregate(snapHandle, 'orders', 'sum', 'total') !== 30) {
+        throw new Error(`Snapshot reads should ignore later writes: ${JSON.stringify(openOrders)}`);
+    }
+    if (nativeSnap.snapshotGet(snapHandle, 'orders.o1.status') !== 'open' || (await dbSnap.get('orders.o1.status')) !== 'paid') {
+        throw new Error('A snapshot and live data should diverge');
+    }
+    const snapStats = nativeSnap.dbStats().snapshots;
+    if (snapStats.open !== 1 || snapStats.max !== 2 || !(snapStats.approxBytes > 0)) throw new Error(`Unexpected snapshot stats: ${JSON.stringify(snapStats)}`);
+    const secondHandle: number = nativeSnap.snapshotOpen();
+    try { nativeSnap.snapshotOpen(); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_LIMIT')) throw new Error(`Snapshots past the limit should fail: ${e.message}`);
+    }
+    if (!nativeSnap.snapshotClose(snapHandle) || nativeSnap.snapshotClose(snapHandle)) throw new Error('A handle should close once');
+    try { nativeSnap.snapshotGet(snapHandle, 'orders'); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_NOT_FOUND')) throw new Error(`A closed handle should be unknown: ${e.message}`);
+    }
+    if (nativeSnap.snapshotAggregate(secondHandle, 'orders', 'sum', 'total') !== 60) throw new Error('A later snapshot should see the later writes');
+    nativeSnap.snapshotClose(secondHandle);
+    if (nativeSnap.dbStats().snapshots.open !== 0) throw new Error('Closed snapshots should not be counted');
+    await dbSnap.close();
+    for (const f of snapFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 86: Three-Way Merge
+    console.log('📝 TEST 86: Three-Way Merge');
+    const mergeFile = `${TEST_DB}.merge3`;
+    const mergeFiles = [mergeFile, `${mergeFile}.wal`, `${mergeFile}.process_lock`];
+    for (const f of mergeFiles) if (existsSync(f)) unlinkSync(f);
+    const dbMerge = new JSONDatabase(mergeFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeMerge = (dbMerge as any).native;
+    const mergeBase = { title: 'A', body: 'draft', meta: { tags: ['a'], views: 1 }, legacy: true };
+    await dbMerge.set('doc', { title: 'A', body: 'server edit', meta: { tags: ['a'], views: 5 }, legacy: true });
+    const merged = nativeMerge.mergeThreeWay('doc', mergeBase, { title: 'B', body: 'client edit', meta: { tags: ['a', 'b'], views: 1, lang: 'en' } });
+    if (merged.applied !== 4 || JSON.stringify(merged.conflicts) !== '[{"path":"doc.body","base":"draft","ours":"server edit","theirs":"client edit"}]') {
+        throw new Error(`Only the field both sides changed should conflict: ${JSON.stringify(merged)}`);
+    }
+    // Nested fields merge key by key; arrays as whole values; a field they removed goes
+    const mergedDoc = '{"title":"B","body":"server edit","meta":{"tags":["a","b"],"views":5,"lang":"en"}}';
+    if (JSON.stringify(await dbMerge.get('doc')) !== mergedDoc) throw new Error(`Unexpected merge result: ${JSON.stringify(await dbMerge.get('doc'))}`);
+    await dbMerge.set('pair', { v: 1, w: 'server' });
+    const theirsWin = nativeMerge.mergeThreeWay('pair', { v: 1, w: 'base' }, { v: 2, w: 'client' }, { policy: 'theirs' });
+    if (theirsWin.resolved !== 1 || JSON.stringify(await dbMerge.get('pair')) !== '{"v":2,"w":"client"}') throw new Error('policy theirs should take incoming values');
+    await dbMerge.set('pair', { v: 1, w: 'server' });
+    nativeMerge.mergeThreeWay('pair', { v: 1, w: 'base' }, { v: 2, w: 'client' }, { policy: 'ours' });
+    if (JSON.stringify(await dbMerge.get('pair')) !== '{"v":2,"w":"server"}') throw new Error('policy ours should keep current values and apply the rest');
+    await dbMerge.close();
+    const dbMergeReopened = new JSONDatabase(mergeFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbMergeReopened.get('doc')) !== mergedDoc) throw new Error('A merge should persist');
+    await dbMergeReopened.close();
+    for (const f of mergeFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 87: Delete Prefix
+    console.log('📝 TEST 87: Delete Prefix');
+    const prefixFile = `${TEST_DB}.prefix`;
+    const prefixFiles = [prefixFile, `${prefixFile}.wal`, `${prefixFile}.process_lock`, `${prefixFile}.byUser.idx`];
+    for (const f of prefixFiles) if (existsSync(f)) unlinkSync(f);
+    const dbPrefix = new JSONDatabase(prefixFile, {
+        durability: 'batched',
+        lockMode: 'exclusive',
+        indices: [{ name: 'byUser', path: 'sessions', field: 'user' }],
+    });
+    const nativePrefix = (dbPrefix as any).native;
+    await dbPrefix.set('cache', { tenant_1: { a: 1, b: 2 }, tenant_2: { c: 3 }, other: { d: 4 } });
+    const dryPrefix = nativePrefix.deletePrefix('cache.tenant_1', { dryRun: true });
+    if (dryPrefix.deleted !== 2 || JSON.stringify(await dbPrefix.get('cache.tenant_1')) !== '{"a":1,"b":2}') throw new Error('A dry run should only count');
+    // An object path clears its children but keeps the object
+    if (nativePrefix.deletePrefix('cache.tenant_1.*').deleted !== 2 || JSON.stringify(await dbPrefix.get('cache.tenant_1')) !== '{}') {
+        throw new Error('A prefix naming an object should clear its children');
+    }
+    const prefixAborted = await dbPrefix.transaction(async (tx) => {
+        nativePrefix.deletePrefix('cache.tenant_*', { txn: tx.id });
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (prefixAborted !== 'undo' || JSON.stringify(Object.keys(await dbPrefix.get('cache') as object)) !== '["tenant_1","tenant_2","other"]') {
+        throw new Error('Rollback should restore a prefix delete');
+    }
+    // A key prefix removes the matching siblings and their index entries
+    await dbPrefix.set('sessions', { t1_a: { user: 'x' }, t1_b: { user: 'y' }, t2_c: { user: 'x' } });
+    if (nativePrefix.deletePrefix('sessions.t1_*').deleted !== 2) throw new Error('A key prefix should remove the matching children');
+    if (JSON.stringify(nativePrefix.findIndexPaths('byUser', 'x')) !== '["sessions.t2_c"]' || nativePrefix.findIndexPaths('byUser', 'y').length !== 0) {
+        throw new Error('Removed documents should leave the index');
+    }
+    nativePrefix.deletePrefix('cache.tenant_*');
+    await dbPrefix.close();
+    const dbPrefixReopened = new JSONDatabase(prefixFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbPrefixReopened.get('')) !== '{"cache":{"other":{"d":4}},"sessions":{"t2_c":{"user":"x"}}}') {
+        throw new Error(`Prefix deletes should persist: ${JSON.stringify(await dbPrefixReopened.get(''))}`);
+    }
+    await dbPrefixReopened.close();
+    for (const f of prefixFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 88: Import File
+    console.log('📝 TEST 88: Import File');
+    const importFile = `${TEST_DB}.imported`;
+    const importSource = `${TEST_DB}.lowdb.json`;
+    const importFiles = [importFile, `${importFile}.wal`, `${importFile}.process_lock`, importSource];
+    for (const f of importFiles) if (existsSync(f)) unlinkSync(f);
+    writeFileSync(importSource, JSON.stringify({
+        posts: [{ id: 1, title: 'a' }, { id: 2, title: 'b' }, { id: 3 }, { title: 'no id' }],
+        profile: { name: 'n' },
+    }));
+    const dbImport = new JSONDatabase(importFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeImport = (dbImport as any).native;
+    nativeImport.registerSchema('content.posts', JSON.stringify({ type: 'object', required: ['title'] }), null);
+    await dbImport.set('content.posts', { 2: { title: 'kept' } });
+    const importRules = [{ from: 'posts', to: 'content.posts', keyField: 'id' }, { from: 'profile', to: 'settings.profile' }];
+    try { nativeImport.importFile(importSource, importRules); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_CONFLICT')) throw new Error(`A duplicate key should fail the import by default: ${e.message}`);
+    }
+    const importDry = nativeImport.importFile(importSource, importRules, { onDuplicate: 'skip', dryRun: true });
+    if (importDry.imported !== 2 || importDry.duplicates !== 1 || (await dbImport.has('settings'))) throw new Error('A dry run should only report');
+    // Arrays are re-keyed by keyField; invalid and keyless items are skipped and reported
+    const importReport = nativeImport.importFile(importSource, importRules, { onDuplicate: 'skip' });
+    if (importReport.skipped !== 2 || JSON.stringify(importReport.invalid.map((i: any) => i.path)) !== '["content.posts.3"]') {
+        throw new Error(`Unexpected import report: ${JSON.stringify(importReport)}`);
+    }
+    if (JSON.stringify(await dbImport.get('content.posts')) !== '{"1":{"id":1,"title":"a"},"2":{"title":"kept"}}') throw new Error('skip should keep existing documents');
+    nativeImport.importFile(importSource, importRules, { onDuplicate: 'overwrite' });
+    const importedState = '{"content":{"posts":{"1":{"id":1,"title":"a"},"2":{"id":2,"title":"b"}}},"settings":{"profile":{"name":"n"}}}';
+    if (JSON.stringify(await dbImport.get('')) !== importedState) throw new Error(`overwrite should replace existing documents: ${JSON.stringify(await dbImport.get(''))}`);
+    await dbImport.close();
+    const dbImportReopened = new JSONDatabase(importFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbImportReopened.get('')) !== importedState) throw new Error('Imported documents should persist');
+    await dbImportReopened.close();
+    for (const f of importFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 89: Materialized Views
+    console.log('📝 TEST 89: Materialized Views');
+    const viewFile = `${TEST_DB}.views`;
+    const viewFiles = [viewFile, `${viewFile}.wal`, `${viewFile}.process_lock`];
+    for (const f of viewFiles) if (existsSync(f)) unlinkSync(f);
+    const dbView = new JSONDatabase(viewFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeView = (dbView as any).native;
+    await dbView.set('sales', { a: { amount: 5, region: 'eu' }, b: { amount: 7, region: 'us' } });
+    nativeView.createView('total', { path: 'sales', aggregate: 'sum', field: 'amount', autoRefresh: { onWriteUnder: 'sales' } });
+    nativeView.createView('eu', { path: 'sales', filters: [{ field: 'region', op: 'eq', value: 'eu' }] });
+    if (!nativeView.getView('total').stale || nativeView.getView('total').result !== null) throw new Error('A view should be stale until refreshed');
+    if (nativeView.refreshView('total') !== 12 || nativeView.getView('total').stale) throw new Error('A refresh should store the result');
+    // Writes elsewhere leave it fresh; covered writes mark it stale without recomputing
+    await dbView.set('other', 1);
+    if (nativeView.getView('total').stale) throw new Error('Writes outside onWriteUnder should not mark the view stale');
+    await dbView.set('sales.c', { amount: 1, region: 'eu' });
+    const staleView = nativeView.getView('total');
+    if (!staleView.stale || staleView.result !== 12) throw new Error(`A covered write should only mark the view stale: ${JSON.stringify(staleView)}`);
+    if (nativeView.refreshView('eu').length !== 2) throw new Error('A query view should store the matching documents');
+    if ('__jsondb__' in (await dbView.get('') as object)) throw new Error('Views should be hidden from reads');
+    try { nativeView.getView('missing'); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_NOT_FOUND')) throw new Error(`An unknown view should fail with ERR_NOT_FOUND: ${e.message}`);
+    }
+    await dbView.close();
+    // Results survive a restart; auto-refresh views report stale since writes were not tracked
+    const dbViewReopened = new JSONDatabase(viewFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeViewReopened = (dbViewReopened as any).native;
+    if (JSON.stringify(nativeViewReopened.listViews().map((v: any) => v.name)) !== '["total","eu"]') throw new Error('View definitions should persist');
+    const reopenedTotal = nativeViewReopened.getView('total');
+    const reopenedEu = nativeViewReopened.getView('eu');
+    if (reopenedTotal.result !== 12 || !reopenedTotal.stale || reopenedEu.result.length !== 2 || reopenedEu.stale) {
+        throw new Error(`Unexpected views after a restart: ${JSON.stringify([reopenedTotal, reopenedEu])}`);
+    }
+    if (!nativeViewReopened.dropView('eu') || nativeViewReopened.dropView('eu')) throw new Error('A view should drop once');
+    await dbViewReopened.close();
+    for (const f of viewFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 90: Collection Quotas
+    console.log('📝 TEST 90: Collection Quotas');
+    const quotaFile = `${TEST_DB}.quota`;
+    const quotaFiles = [quotaFile, `${quotaFile}.wal`, `${quotaFile}.process_lock`];
+    for (const f of quotaFiles) if (existsSync(f)) unlinkSync(f);
+    const dbQuota = new JSONDatabase(quotaFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeQuota = (dbQuota as any).native;
+    await dbQuota.set('events', { e1: { at: 3 }, e2: { at: 1 } });
+    nativeQuota.setQuota('events', 3, null, 'reject', null);
+    await dbQuota.set('events.e3', { at: 2 });
+    const quotaOutcome = await dbQuota.set('events.e4', { at: 4 }).then(() => 'written', (e: any) => String(e.message));
+    if (!quotaOutcome.startsWith('ERR_QUOTA')) throw new Error(`A write past maxEntries should fail with ERR_QUOTA: ${quotaOutcome}`);
+    await dbQuota.set('events.e3', { at: 5 }); // replacing an entry adds none
+    nativeQuota.batchSetParallel([['events.e4', { at: 4 }]]);
+    if (await dbQuota.has('events.e4')) throw new Error('Batches should be held to the quota too');
+    const quotaUsage = nativeQuota.quotaStatus('events');
+    if (quotaUsage.entries !== 3 || quotaUsage.rejected !== 2 || quotaUsage.exceeded) throw new Error(`Unexpected quota status: ${JSON.stringify(quotaUsage)}`);
+    // evict_oldest makes room by the order field
+    nativeQuota.setQuota('events', 3, null, 'evict_oldest', 'at');
+    await dbQuota.set('events.e5', { at: 9 });
+    if (JSON.stringify(Object.keys(await dbQuota.get('events') as object)) !== '["e1","e3","e5"]') throw new Error('The oldest entry should be evicted');
+    nativeQuota.setQuota('logs', null, 200, 'reject', null);
+    await dbQuota.set('logs', { a: 'x'.repeat(100) });
+    const bytesOutcome = await dbQuota.set('logs.b', 'y'.repeat(100)).then(() => 'written', (e: any) => String(e.message));
+    if (!bytesOutcome.startsWith('ERR_QUOTA')) throw new Error(`A write past maxBytes should fail with ERR_QUOTA: ${bytesOutcome}`);
+    await dbQuota.close();
+    const dbQuotaReopened = new JSONDatabase(quotaFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeQuotaReopened = (dbQuotaReopened as any).native;
+    if (nativeQuotaReopened.quotaStatus('events').policy !== 'evict_oldest') throw new Error('Quotas should persist');
+    await dbQuotaReopened.set('events.e6', { at: 10 });
+    if (JSON.stringify(Object.keys(await dbQuotaReopened.get('events') as object)) !== '["e3","e5","e6"]') throw new Error('A reopened quota should still evict');
+    if (!nativeQuotaReopened.removeQuota('events') || nativeQuotaReopened.removeQuota('events')) throw new Error('A quota should be removed once');
+    await dbQuotaReopened.close();
+    for (const f of quotaFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 91: forEach Iteration
+    console.log('📝 TEST 91: forEach Iteration');
+    const eachFile = `${TEST_DB}.each`;
+    const eachFiles = [eachFile, `${eachFile}.wal`, `${eachFile}.process_lock`];
+    for (const f of eachFiles) if (existsSync(f)) unlinkSync(f);
+    const dbEach = new JSONDatabase(eachFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeEach = (dbEach as any).native;
+    const eachAccounts: Record<string, { overdue: boolean; n: number }> = {};
+    for (let i = 0; i < 10; i++) eachAccounts[`a${i}`] = { overdue: i % 2 === 0, n: i };
+    await dbEach.set('accounts', eachAccounts);
+    // No lock is held in the callback: it can write, and later batches see its writes
+    const visited: string[] = [];
+    const eachResult = nativeEach.forEach('accounts', null, (doc: any, key: string) => {
+        visited.push(`${key}:${doc.n}`);
+        if (key === 'a1') {
+            nativeEach.delete('accounts.a5');
+            nativeEach.set('accounts.a2.n', 20);
+        }
+        if (key === 'a3') throw new Error('mail failed');
+    }, { batchSize: 2 });
+    if (eachResult.processed !== 8 || eachResult.skipped !== 1 || JSON.stringify(eachResult.errors.map((e: any) => e.key)) !== '["a3"]') {
+        throw new Error(`Unexpected forEach result: ${JSON.stringify(eachResult)}`);
+    }
+    if (visited.includes('a5:5') || !visited.includes('a2:20')) throw new Error(`Deleted keys should be skipped and batches read fresh: ${visited.join(',')}`);
+    const overdue: string[] = [];
+    nativeEach.forEach('accounts', [{ field: 'overdue', op: 'eq', value: true }], (_doc: any, key: string) => { overdue.push(key); });
+    if (JSON.stringify(overdue) !== '["a0","a2","a4","a6","a8"]') throw new Error(`Filters should select the documents: ${JSON.stringify(overdue)}`);
+    let threadsafeCalls = 0;
+    const threadsafeResult = await nativeEach.forEach('accounts', null, () => { threadsafeCalls++; }, { mode: 'threadsafe', batchSize: 3 });
+    if (threadsafeResult.processed !== 9 || threadsafeCalls !== 9) throw new Error(`threadsafe mode should visit every document: ${JSON.stringify(threadsafeResult)}`);
+    await dbEach.close();
+    for (const f of eachFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 92: Uniqueness Constraints
+    console.log('📝 TEST 92: Uniqueness Constraints');
+    const constraintFile = `${TEST_DB}.constraint`;
+    const constraintFiles = [constraintFile, `${constraintFile}.wal`, `${constraintFile}.process_lock`];
+    for (const f of constraintFiles) if (existsSync(f)) unlinkSync(f);
+    const dbConstraint = new JSONDatabase(constraintFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeConstraint = (dbConstraint as any).native;
+    await dbConstraint.set('bookings', { b1: { room: 1, date: 'd1' }, b2: { room: 1, date: 'd1' }, b3: { room: 2, date: 'd1' } });
+    // Registering reports existing duplicates instead of failing
+    const existing = nativeConstraint.registerConstraint('roomDate', 'bookings', ['room', 'date']);
+    if (JSON.stringify(existing) !== '[{"values":[1,"d1"],"keys":["b1","b2"]}]') throw new Error(`Existing duplicates should be reported: ${JSON.stringify(existing)}`);
+    await dbConstraint.delete('bookings.b2');
+    const constraintRefusals = [
+        () => dbConstraint.set('bookings.b4', { room: 2, date: 'd1' }),
+        () => nativeConstraint.insert('bookings', { room: 2, date: 'd1' }),
+    ];
+    for (const call of constraintRefusals) {
+        const outcome = await Promise.resolve().then(call).then(() => 'written', (e: any) => String(e.message));
+        if (!outcome.startsWith('ERR_CONSTRAINT') || !outcome.includes("'b3'")) throw new Error(`A duplicate tuple should name the holder: ${outcome}`);
+    }
+    // Rewriting the holder, other tuples and documents missing a field are fine
+    await dbConstraint.set('bookings.b3', { room: 2, date: 'd1', note: 'late' });
+    await dbConstraint.set('bookings.b4', { room: 2, date: 'd2' });
+    await dbConstraint.set('bookings.b5', { room: 3 });
+    await dbConstraint.set('bookings.b6', { room: 3 });
+    const fieldOutcome = await dbConstraint.set('bookings.b4.date', 'd1').then(() => 'written', (e: any) => String(e.message));
+    if (!fieldOutcome.startsWith('ERR_CONSTRAINT')) throw new Error(`A nested write completing a duplicate tuple should fail: ${fieldOutcome}`);
+    const audit = nativeConstraint.checkConstraints('bookings');
+    if (audit.length !== 1 || audit[0].violations.length !== 0 || audit[0].rejected !== 3) throw new Error(`Unexpected audit: ${JSON.stringify(audit)}`);
+    await dbConstraint.close();
+    const dbConstraintReopened = new JSONDatabase(constraintFile, { durability: 'batched', lockMode: 'exclusive' });
+    const reopenedOutcome = await dbConstraintReopened.set('bookings.b7', { room: 1, date: 'd1' }).then(() => 'written', (e: any) => String(e.message));
+    if (!reopenedOutcome.startsWith('ERR_CONSTRAINT')) throw new Error('Constraints should persist');
+    if (!(dbConstraintReopened as any).native.dropConstraint('roomDate')) throw new Error('dropConstraint should report the removal');
+    await dbConstraintReopened.set('bookings.b7', { room: 1, date: 'd1' });
+    await dbConstraintReopened.close();
+    for (const f of constraintFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 93: ID Strategies
+    console.log('📝 TEST 93: ID Strategies');
+    const idFile = `${TEST_DB}.ids`;
+    const idCopy = `${TEST_DB}.ids.copy`;
+    const idFiles = [idFile, `${idFile}.wal`, `${idFile}.process_lock`, idCopy, `${idCopy}.wal`, `${idCopy}.process_lock`];
+    for (const f of idFiles) if (existsSync(f)) unlinkSync(f);
+    const dbIds = new JSONDatabase(idFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeIds = (dbIds as any).native;
+    if (JSON.stringify(nativeIds.idStrategy('events')) !== '{"type":"uuid4","configured":false}') throw new Error('uuid4 should be the default');
+    nativeIds.setIdStrategy('events', 'ulid');
+    const firstEvent: string = nativeIds.insert('events', { n: 1 });
+    const secondEvent: string = nativeIds.insert('events', { n: 2 });
+    if (!/^[0-9A-HJKMNP-TV-Z]{26}$/.test(firstEvent) || !(firstEvent < secondEvent)) throw new Error(`ULIDs should sort by creation: ${firstEvent} ${secondEvent}`);
+    nativeIds.setIdStrategy('links', { type: 'nanoid', length: 8 });
+    if (!/^[A-Za-z0-9_-]{8}$/.test(nativeIds.insert('links', { url: '/a' }))) throw new Error('nanoid keys should have the configured length');
+    nativeIds.setIdStrategy('invoices', { type: 'sequence', name: 'invoices' });
+    const invoiceIds = [nativeIds.insert('invoices', { total: 1 }), ...nativeIds.insertMany('invoices', [{ total: 2 }, { total: 3 }])];
+    if (JSON.stringify(invoiceIds.map(String)) !== '["1","2","3"]') throw new Error(`Sequence keys should count up: ${JSON.stringify(invoiceIds)}`);
+    // field keys must name a usable path segment
+    nativeIds.setIdStrategy('users', { type: 'field', field: 'email' });
+    if (nativeIds.insert('users', { email: 'a@example' }) !== 'a@example') throw new Error('field should key by the named field');
+    const idRefusals: Array<[unknown, string]> = [[{ email: 'dot.ted' }, 'ERR_INVALID_PATH'], [{ name: 'none' }, 'ERR_INVALID_ARGUMENT']];
+    for (const [doc, code] of idRefusals) {
+        try { nativeIds.insert('users', doc); throw new Error('should fail'); } catch (e: any) {
+            if (!e.message.startsWith(code)) throw new Error(`Expected ${code}: ${e.message}`);
+        }
+    }
+    try { nativeIds.setIdStrategy('users', { type: 'random' }); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_INVALID_ARGUMENT')) throw new Error(`An unknown strategy should be rejected: ${e.message}`);
+    }
+    await dbIds.sync();
+    // What a crash right now would leave behind
+    copyFileSync(`${idFile}.wal`, `${idCopy}.wal`);
+    await dbIds.close();
+    const dbIdsCrash = new JSONDatabase(idCopy, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeIdsCrash = (dbIdsCrash as any).native;
+    if (nativeIdsCrash.idStrategy('invoices').name !== 'invoices' || String(nativeIdsCrash.insert('invoices', { total: 4 })) !== '4') {
+        throw new Error('Strategies and their sequences should survive a crash without repeating ids');
+    }
+    await dbIdsCrash.close();
+    for (const f of idFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 94: Cascade Rules
+    console.log('📝 TEST 94: Cascade Rules');
+    const cascadeFile = `${TEST_DB}.cascade`;
+    const cascadeFiles = [cascadeFile, `${cascadeFile}.wal`, `${cascadeFile}.process_lock`];
+    for (const f of cascadeFiles) if (existsSync(f)) unlinkSync(f);
+    const dbCascade = new JSONDatabase(cascadeFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeCascade = (dbCascade as any).native;
+    await dbCascade.set('users', { u1: {}, u2: {} });
+    await dbCascade.set('sessions', { s1: { userId: 'u1' }, s2: { userId: 'u2' }, s3: { userId: 'u1' } });
+    await dbCascade.set('posts', { p1: { userId: 'u1' }, p2: { userId: 'u2' } });
+    nativeCascade.registerCascade('userSessions', 'users', 'sessions', 'userId', 'delete');
+    nativeCascade.registerCascade('userPosts', 'users', 'posts', 'userId', 'nullify');
+    try { nativeCascade.registerCascade('loop', 'sessions', 'users', 'sessionId', 'delete'); throw new Error('should fail'); } catch (e: any) {
+        if (!e.message.startsWith('ERR_INVALID_ARGUMENT') || !e.message.includes('cycle')) throw new Error(`A cycle should be rejected: ${e.message}`);
+    }
+    const beforeCascade = JSON.stringify(await dbCascade.get(''));
+    const cascadeAborted = await dbCascade.transaction(async (tx) => {
+        const counts = await tx.delete('users.u1');
+        if (JSON.stringify(counts) !== '{"userSessions":{"deleted":2,"nullified":0},"userPosts":{"deleted":0,"nullified":1}}') {
+            throw new Error(`Unexpected cascade counts: ${JSON.stringify(counts)}`);
+        }
+        throw new Error('undo');
+    }).catch((e: Error) => e.message);
+    if (cascadeAborted !== 'undo' || JSON.stringify(await dbCascade.get('')) !== beforeCascade) throw new Error(`Rollback should reverse the cascade: ${cascadeAborted}`);
+    await dbCascade.delete('users.u1');
+    const afterCascade = '{"users":{"u2":{}},"sessions":{"s2":{"userId":"u2"}},"posts":{"p1":{"userId":null},"p2":{"userId":"u2"}}}';
+    if (JSON.stringify(await dbCascade.get('')) !== afterCascade) throw new Error(`Unexpected state after the cascade: ${JSON.stringify(await dbCascade.get(''))}`);
+    // restrict refuses the parent delete while children exist, changing nothing
+    await dbCascade.set('orders', { o1: { userId: 'u2' } });
+    nativeCascade.registerCascade('userOrders', 'users', 'orders', 'userId', 'restrict');
+    const restricted = await dbCascade.delete('users.u2').then(() => 'deleted', (e: any) => String(e.message));
+    if (!restricted.startsWith('ERR_CONSTRAINT') || !(await dbCascade.has('users.u2')) || !(await dbCascade.has('sessions.s2'))) {
+        throw new Error(`restrict should refuse the delete: ${restricted}`);
+    }
+    await dbCascade.close();
+    const dbCascadeReopened = new JSONDatabase(cascadeFile, { durability: 'batched', lockMode: 'exclusive' });
+    const reopenedRules = (dbCascadeReopened as any).native.cascades();
+    if (JSON.stringify(Object.keys(reopenedRules)) !== '["userSessions","userPosts","userOrders"]' || reopenedRules.userPosts.action !== 'nullify') {
+        throw new Error(`Cascade rules should persist: ${JSON.stringify(reopenedRules)}`);
+    }
+    await dbCascadeReopened.delete('orders.o1');
+    await dbCascadeReopened.delete('users.u2');
+    if (await dbCascadeReopened.has('sessions.s2')) throw new Error('Reopened rules should still cascade');
+    await dbCascadeReopened.close();
+    for (const f of cascadeFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 95: Leases
+    console.log('📝 TEST 95: Leases');
+    const leaseFile = `${TEST_DB}.leases`;
+    const leaseFiles = [leaseFile, `${leaseFile}.wal`, `${leaseFile}.process_lock`];
+    for (const f of leaseFiles) if (existsSync(f)) unlinkSync(f);
+    const dbLease = new JSONDatabase(leaseFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeLease = (dbLease as any).native;
+    await dbLease.set('jobs', { j1: { state: 'new' }, j2: { state: 'new' }, j3: { state: 'done' } });
+    // Acquire: one owner per path, its ancestors and descendants
+    if (!nativeLease.acquireLease('jobs.j1', 'w1', 60000)) throw new Error('A free path should be leased');
+    if (nativeLease.acquireLease('jobs.j1', 'w2', 60000) || nativeLease.acquireLease('jobs', 'w2', 60000) || nativeLease.acquireLease('jobs.j1.state', 'w2', 60000)) {
+        throw new Error('A leased path, its ancestors and descendants should be refused to another owner');
+    }
+    if (!nativeLease.acquireLease('jobs.j1', 'w1', 60000) || !nativeLease.acquireLease('jobs.j2', 'w2', 60000)) throw new Error('The holder should renew and a sibling be free');
+    if (nativeLease.leaseInfo('jobs.j1')?.owner !== 'w1' || nativeLease.leaseInfo('jobs.j3') !== null) throw new Error('leaseInfo should name the holder');
+    // Advisory until strict mode: writes still land
+    await dbLease.set('jobs.j1.state', 'taken');
+    nativeLease.setLeaseEnforcement(true);
+    const leaseRefusals: Array<[string, () => unknown]> = [
+        ['set', () => dbLease.set('jobs.j1.state', 'stolen')],
+        ['updateMany', () => dbLease.updateMany('jobs', [{ field: 'state', op: 'ne', value: 'x' }], { state: 'x' })],
+        ['deleteMany', () => dbLease.deleteMany('jobs', [{ field: 'state', op: 'ne', value: 'done' }])],
+        ['upsertMany', () => dbLease.upsertMany('jobs', [{ id: 'j1', state: 'x' }], 'id')],
+        ['renameField', () => nativeLease.renameField('jobs', 'state', 'status')],
+        ['mapCollection', () => nativeLease.mapCollection('jobs', null, (doc: any) => ({ ...doc, seen: true }))],
+        ['atomic', () => dbLease.atomic([], [{ type: 'set', path: 'jobs.j1.state', value: 'x' }])],
+        ['moveKey', () => nativeLease.moveKey('jobs', 'j1')],
+    ];
+    const beforeRefusals = JSON.stringify(await dbLease.get('jobs'));
+    for (const [name, call] of leaseRefusals) {
+        const error = await Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+        if (!error?.startsWith('ERR_CONFLICT')) throw new Error(`${name} should be refused on a leased document, got ${error}`);
+    }
+    if (JSON.stringify(await dbLease.get('jobs')) !== beforeRefusals) throw new Error('Refused writes should change nothing');
+    // Documents nobody else holds stay writable, and the holder writes its own
+    if ((await dbLease.updateMany('jobs', [{ field: 'state', op: 'eq', value: 'done' }], { checked: true })).updated !== 1) throw new Error('An unleased document should be updated');
+    await dbLease.set('jobs.j1.state', 'running', { owner: 'w1' });
+    if (!(await dbLease.atomic([], [{ type: 'set', path: 'jobs.j1.state', value: 'done' }], { owner: 'w1' }))) throw new Error('The holder should write atomically');
+    const byOwner = await dbLease.updateMany('jobs', [{ field: 'state', op: 'eq', value: 'done' }], { state: 'archived' }, { owner: 'w1' });
+    if (JSON.stringify(byOwner.keys) !== '["j1","j3"]') throw new Error(`The holder should update its document with unleased ones: ${JSON.stringify(byOwner)}`);
+    const otherHeld = await dbLease.updateMany('jobs', [{ field: 'state', op: 'ne', value: 'x' }], { state: 'x' }, { owner: 'w1' }).then(() => null, (e: Error) => e.message);
+    if (!otherHeld?.startsWith('ERR_CONFLICT')) throw new Error(`An owner should not write a document leased by another: ${otherHeld}`);
+    // Release: only by the holder
+    if (nativeLease.releaseLease('jobs.j2', 'w1') || !nativeLease.releaseLease('jobs.j2', 'w2')) throw new Error('Only the holder should release a lease');
+    await dbLease.set('jobs.j2.state', 'free');
+    // Expiry: a lapsed lease stops guarding the path and can be taken over
+    if (!nativeLease.acquireLease('jobs.j3', 'w3', 50)) throw new Error('A short lease should be granted');
+    await sleep(100);
+    if (nativeLease.leaseInfo('jobs.j3') !== null) throw new Error('An expired lease should not be reported');
+    await dbLease.set('jobs.j3.state', 'reopened');
+    if (!nativeLease.acquireLease('jobs.j3', 'w4', 60000)) throw new Error('An expired lease should be free to take');
+    nativeLease.setLeaseEnforcement(false);
+    await dbLease.set('jobs.j3.state', 'advisory');
+    if ((await dbLease.get('jobs.j3.state')) !== 'advisory') throw new Error('Leases should be advisory once strict mode is off');
+    await dbLease.close();
+    for (const f of leaseFiles) if (existsSync(f)) unlinkSync(f);
+    console.log(`   ${leaseRefusals.length} writes refused on a leased document`);
+    console.log('   ✅ Passed\n');
+
+    // TEST 96: Path Rules
+    console.log('📝 TEST 96: Path Rules');
+    const rulesFile = `${TEST_DB}.pathrules`;
+    const rulesCopy = `${TEST_DB}.pathrules.copy`;
+    const rulesFiles = [rulesFile, `${rulesFile}.wal`, `${rulesFile}.process_lock`, rulesCopy, `${rulesCopy}.wal`, `${rulesCopy}.process_lock`];
+    for (const f of rulesFiles) if (existsSync(f)) unlinkSync(f);
+    const dbRules = new JSONDatabase(rulesFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeRules = (dbRules as any).native;
+    const tooDeep = Array.from({ length: 65 }, (_, i) => `k${i}`).join('.');
+    const refusedPaths: [string, () => unknown, string][] = [
+        ['control character', () => dbRules.set('users.bob\n.name', 'Bob'), '"bob\\n"'],
+        ['depth', () => dbRules.set(tooDeep, 1), '65 segments deep (max 64)'],
+        ['key length', () => dbRules.set(`users.${'x'.repeat(1025)}`, 1), '1025 characters long (max 1024)'],
+        ['setPointers', () => nativeRules.setPointers('users', [['/bad\u0001', 1]]), '"bad\\u{1}"'],
+    ];
+    for (const [label, call, detail] of refusedPaths) {
+        const message = await Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+        if (!message?.startsWith('ERR_INVALID_PATH') || !message.includes(detail)) {
+            throw new Error(`Invalid path (${label}) should be rejected naming ${detail}: ${message}`);
+        }
+    }
+    if (await dbRules.get('users') !== null) {
+        throw new Error(`Rejected writes should leave nothing behind: ${JSON.stringify(await dbRules.get('users'))}`);
+    }
+    const pathReport = dbRules.validatePathString('users.bob\n');
+    if (pathReport.valid || pathReport.depth !== 2 || pathReport.segment !== 'bob\n') {
+        throw new Error(`validatePathString should name the offending segment: ${JSON.stringify(pathReport)}`);
+    }
+    await dbRules.set('deep.a.b.c.d', 1);
+    await dbRules.set('flat', 2);
+    await dbRules.sync();
+    // Replay the same WAL under stricter rules
+    copyFileSync(`${rulesFile}.wal`, `${rulesCopy}.wal`);
+    await dbRules.close();
+    const dbStrict = new JSONDatabase(rulesCopy, { durability: 'batched', lockMode: 'exclusive', maxPathDepth: 3, forbiddenPathChars: '$' });
+    if (JSON.stringify(await dbStrict.get('')) !== '{"flat":2}') {
+        throw new Error(`WAL replay should skip paths the rules reject: ${JSON.stringify(await dbStrict.get(''))}`);
+    }
+    const rulesReport = (dbStrict as any).native.integrityReport().wal;
+    if (rulesReport.rejectedPaths !== 1 || rulesReport.replayedOps !== 1) {
+        throw new Error(`Unexpected replay counts: ${JSON.stringify(rulesReport)}`);
+    }
+    const dollarMessage = await Promise.resolve().then(() => dbStrict.set('price$', 1)).then(() => null, (e: Error) => e.message);
+    if (!dollarMessage?.startsWith('ERR_INVALID_PATH')) throw new Error(`forbiddenPathChars should be enforced: ${dollarMessage}`);
+    await dbStrict.close();
+    for (const f of rulesFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 97: Atomic Conditional Writes
+    console.log('📝 TEST 97: Atomic Conditional Writes');
+    const atomicFile = `${TEST_DB}.atomic`;
+    const atomicFiles = [atomicFile, `${atomicFile}.wal`, `${atomicFile}.process_lock`];
+    for (const f of atomicFiles) if (existsSync(f)) unlinkSync(f);
+    const dbAtomic = new JSONDatabase(atomicFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbAtomic.set('inventory', { widget: { stock: 3, holds: [] } });
+    const reserve = (id: string) => dbAtomic.atomic(
+        [{ path: 'inventory.widget.stock', op: 'gt', value: 0 }, { path: `reservations.${id}`, op: 'notexists' }],
+        [
+            { type: 'subtract', path: 'inventory.widget.stock', value: 1 },
+            { type: 'set', path: `reservations.${id}`, value: { item: 'widget' } },
+            { type: 'push', path: 'inventory.widget.holds', value: id },
+        ]
+    );
+    // Concurrent callers: only as many commit as there is stock
+    const reserved = await Promise.all(['r1', 'r2', 'r3', 'r4', 'r5'].map(reserve));
+    if (JSON.stringify(reserved) !== '[true,true,true,false,false]') throw new Error(`Unexpected commits: ${JSON.stringify(reserved)}`);
+    if (await reserve('r1')) throw new Error('A failed condition should not commit');
+    if (JSON.stringify(await dbAtomic.get('inventory.widget')) !== '{"stock":0,"holds":["r1","r2","r3"]}') {
+        throw new Error(`Unexpected inventory: ${JSON.stringify(await dbAtomic.get('inventory.widget'))}`);
+    }
+    if (await dbAtomic.has('reservations.r4')) throw new Error('Ops should not apply when a condition fails');
+    if (!(await dbAtomic.atomic([{ path: 'reservations.r1', op: 'exists' }], [{ type: 'delete', path: 'reservations.r1' }, { type: 'add', path: 'inventory.widget.stock', value: 1 }]))) {
+        throw new Error('A passing exists condition should commit');
+    }
+    // Invalid conditions and ops are rejected before anything is applied
+    const atomicBefore = JSON.stringify(await dbAtomic.get(''));
+    const refusedAtomics: [string, () => Promise<boolean>, string][] = [
+        ['unknown condition', () => dbAtomic.atomic([{ path: 'inventory.widget.stock', op: 'between' as any, value: 0 }], [{ type: 'set', path: 'x', value: 1 }]), 'ERR_INVALID_ARGUMENT'],
+        ['unknown op', () => dbAtomic.atomic([], [{ type: 'set', path: 'x', value: 1 }, { type: 'rename' as any, path: 'y', value: 1 }]), 'ERR_INVALID_ARGUMENT'],
+        ['missing value', () => dbAtomic.atomic([], [{ type: 'set', path: 'x' }]), 'ERR_INVALID_ARGUMENT'],
+        // The set runs first and is undone when the add fails
+        ['failing op', () => dbAtomic.atomic([], [{ type: 'set', path: 'inventory.widget.stock', value: 9 }, { type: 'add', path: 'inventory.widget.holds', value: 1 }]), 'ERR_INVALID_ARGUMENT'],
+    ];
+    for (const [label, call, code] of refusedAtomics) {
+        const message = await Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+        if (!message?.startsWith(code)) throw new Error(`atomic (${label}) should fail with ${code}: ${message}`);
+    }
+    if (JSON.stringify(await dbAtomic.get('')) !== atomicBefore) {
+        throw new Error(`A failed atomic should leave the data as it was: ${JSON.stringify(await dbAtomic.get(''))}`);
+    }
+    // Committed ops are logged
+    await dbAtomic.sync();
+    const loggedReservations = (dbAtomic as any).native.walInspect().records.filter((r: any) => r.path?.startsWith('reservations.')).map((r: any) => `${r.op} ${r.path}`);
+    if (JSON.stringify(loggedReservations) !== '["set reservations.r1","set reservations.r2","set reservations.r3","delete reservations.r1"]') {
+        throw new Error(`Atomic writes should reach the WAL: ${JSON.stringify(loggedReservations)}`);
+    }
+    await dbAtomic.close();
+    const dbAtomicReopened = new JSONDatabase(atomicFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbAtomicReopened.get('')) !== atomicBefore) {
+        throw new Error(`Atomic writes should survive a reopen: ${JSON.stringify(await dbAtomicReopened.get(''))}`);
+    }
+    await dbAtomicReopened.close();
+    for (const f of atomicFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 98: Index Range Queries
+    console.log('📝 TEST 98: Index Range Queries');
+    const rangeFile = `${TEST_DB}.range`;
+    const rangeFiles = [rangeFile, `${rangeFile}.wal`, `${rangeFile}.process_lock`, `${rangeFile}.score.idx`];
+    for (const f of rangeFiles) if (existsSync(f)) unlinkSync(f);
+    const dbRange = new JSONDatabase(rangeFile, {
+        durability: 'batched',
+        lockMode: 'exclusive',
+        indices: [{ name: 'score', path: 'players', field: 'score' }],
+    });
+    await dbRange.set('players', { a: { score: 10 }, b: { score: 20 }, c: { score: 25 }, d: { score: 30 }, e: { score: 40 }, f: { score: 50 } });
+    const nativeRange = (dbRange as any).native;
+    const expectRange = (label: string, paths: string[], expected: string) => {
+        const keys = paths.map(path => path.slice('players.'.length)).join('');
+        if (keys !== expected) throw new Error(`${label}: expected ${expected}, got ${keys}`);
+    };
+    expectRange('20..40', nativeRange.findIndexRange('score', 20, 40), 'bcde');
+    expectRange('(20..40)', nativeRange.findIndexRange('score', 20, 40, { startExclusive: true, endExclusive: true }), 'cd');
+    expectRange('..20', nativeRange.findIndexRange('score', null, 20), 'ab');
+    expectRange('30..', nativeRange.findIndexRange('score', 30, null), 'def');
+    expectRange('.. limit 3', nativeRange.findIndexRange('score', null, null, { limit: 3 }), 'abc');
+    // Paging: the last key seen becomes an exclusive start
+    const pages: string[] = [];
+    let after: number | null = null;
+    for (;;) {
+        const page: { score: number }[] = await dbRange.findByIndexRange('score', after, null, { startExclusive: after !== null, limit: 2 });
+        if (page.length === 0) break;
+        pages.push(page.map(p => p.score).join(','));
+        after = page[page.length - 1].score;
+    }
+    if (JSON.stringify(pages) !== '["10,20","25,30","40,50"]') throw new Error(`Unexpected pages: ${JSON.stringify(pages)}`);
+    // Writes reach the range at once
+    await dbRange.set('players.a.score', 45);
+    await dbRange.delete('players.d');
+    expectRange('25.. after writes', nativeRange.findIndexRange('score', 25, null), 'ceaf');
+    const missingIndex = await Promise.resolve().then(() => nativeRange.findIndexRange('nope', 1, 2)).then(() => null, (e: Error) => e.message);
+    if (!missingIndex?.startsWith('ERR_NOT_FOUND')) throw new Error(`An unknown index should fail with ERR_NOT_FOUND: ${missingIndex}`);
+    await dbRange.close();
+    for (const f of rangeFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 99: Sparse Indexes
+    console.log('📝 TEST 99: Sparse Indexes');
+    const sparseFile = `${TEST_DB}.sparse`;
+    const sparseFiles = [sparseFile, `${sparseFile}.wal`, `${sparseFile}.process_lock`, `${sparseFile}.phone.idx`, `${sparseFile}.phoneAll.idx`];
+    for (const f of sparseFiles) if (existsSync(f)) unlinkSync(f);
+    const sparseIndices = [
+        { name: 'phone', path: 'users', field: 'phone', sparse: true },
+        { name: 'phoneAll', path: 'users', field: 'phone' },
+    ];
+    const dbSparse = new JSONDatabase(sparseFile, { durability: 'batched', lockMode: 'exclusive', indices: sparseIndices });
+    await dbSparse.set('users', { a: { phone: '1' }, b: {}, c: { phone: null }, d: { phone: '2' } });
+    const nativeSparse = (dbSparse as any).native;
+    const expectEntries = (db: any, name: string, expected: string) => {
+        const entries = JSON.stringify(db.indexEntries(name));
+        if (entries !== expected) throw new Error(`Unexpected entries in '${name}': ${entries}`);
+    };
+    // Missing and null fields stay out of a sparse index; a plain one keeps null
+    expectEntries(nativeSparse, 'phone', '[["1",["users.a"]],["2",["users.d"]]]');
+    expectEntries(nativeSparse, 'phoneAll', '[["1",["users.a"]],["2",["users.d"]],["null",["users.c"]]]');
+    if (JSON.stringify(nativeSparse.findIndexPaths('phone', null)) !== '[]') throw new Error('Looking up null in a sparse index should find nothing');
+    if (JSON.stringify(nativeSparse.findIndexPaths('phoneAll', null)) !== '["users.c"]') throw new Error('A plain index should still index null');
+    // Removing the field drops the entry; adding it indexes the document
+    await dbSparse.set('users.a', { name: 'Ann' });
+    await dbSparse.set('users.b.phone', '3');
+    expectEntries(nativeSparse, 'phone', '[["2",["users.d"]],["3",["users.b"]]]');
+    const listedSparse = nativeSparse.listIndexes().map((i: any) => `${i.name}:${i.sparse}`).join(' ');
+    if (listedSparse !== 'phone:true phoneAll:false') throw new Error(`listIndexes should report sparseness: ${listedSparse}`);
+    // Saved and reloaded, the index stays sparse
+    await dbSparse.close();
+    const dbSparseReopened = new JSONDatabase(sparseFile, { durability: 'batched', lockMode: 'exclusive', indices: sparseIndices });
+    expectEntries((dbSparseReopened as any).native, 'phone', '[["2",["users.d"]],["3",["users.b"]]]');
+    expectEntries((dbSparseReopened as any).native, 'phoneAll', '[["2",["users.d"]],["3",["users.b"]],["null",["users.c"]]]');
+    // Registering again as sparse re-derives the entries
+    (dbSparseReopened as any).native.registerIndex('phoneAll', 'phone', 'users', { sparse: true });
+    expectEntries((dbSparseReopened as any).native, 'phoneAll', '[["2",["users.d"]],["3",["users.b"]]]');
+    await dbSparseReopened.close();
+    for (const f of sparseFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 100: Atomic Updates
+    console.log('📝 TEST 100: Atomic Updates');
+    const updateFile = `${TEST_DB}.atomicupdate`;
+    const updateFiles = [updateFile, `${updateFile}.wal`, `${updateFile}.process_lock`];
+    for (const f of updateFiles) if (existsSync(f)) unlinkSync(f);
+    const dbUpdate = new JSONDatabase(updateFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeUpdate = (dbUpdate as any).native;
+    // Interleaved handlers: a get, an await, then a set loses updates; atomicUpdate does not
+    await dbUpdate.set('counters', { racy: 0, safe: 0 });
+    await Promise.all(Array.from({ length: 10 }, async () => {
+        const racy = await dbUpdate.get<number>('counters.racy');
+        await sleep(1);
+        await dbUpdate.set('counters.racy', racy + 1);
+        await sleep(1);
+        await dbUpdate.atomicUpdate<number>('counters.safe', n => (n ?? 0) + 1);
+    }));
+    if (await dbUpdate.get<number>('counters.racy') >= 10) throw new Error('The unguarded counter should have lost updates');
+    if (await dbUpdate.get('counters.safe') !== 10) throw new Error(`atomicUpdate should not lose updates: ${await dbUpdate.get('counters.safe')}`);
+    // undefined leaves the value; a missing path reads as undefined and stays missing
+    if (await dbUpdate.atomicUpdate('counters.safe', () => undefined) !== 10) throw new Error('Returning undefined should keep the value');
+    let seenMissing: unknown = 'unset';
+    if (await dbUpdate.atomicUpdate('counters.none', (v) => { seenMissing = v; return undefined; }) !== null || seenMissing !== undefined) {
+        throw new Error('A missing path should be passed as undefined and resolve to null');
+    }
+    // Failures write nothing
+    const updateError = (call: () => Promise<unknown>) => call().then(() => null, (e: Error) => e.message);
+    const refusedUpdates: [string, () => Promise<unknown>, string][] = [
+        ['slow updater', () => dbUpdate.atomicUpdate('counters.safe', () => { const start = Date.now(); while (Date.now() - start < 30); return 99; }, { maxCallbackMs: 10 }), 'ERR_LIMIT'],
+        ['throwing updater', () => dbUpdate.atomicUpdate('counters.safe', () => { throw new Error('boom'); }), 'boom'],
+        ['nested write', () => dbUpdate.atomicUpdate('counters.safe', (n) => { nativeUpdate.set('counters.nested', 1); return n; }), 'ERR_CONFLICT'],
+        ['root', () => dbUpdate.atomicUpdate('', () => ({})), 'ERR_INVALID_ARGUMENT'],
+        ['overlapping paths', () => dbUpdate.atomicUpdateMany(['counters', 'counters.safe'], (v) => v), 'ERR_INVALID_ARGUMENT'],
+        ['no paths', () => dbUpdate.atomicUpdateMany([], (v) => v), 'ERR_INVALID_ARGUMENT'],
+    ];
+    for (const [label, call, expected] of refusedUpdates) {
+        const message = await updateError(call);
+        if (!message?.startsWith(expected)) throw new Error(`atomicUpdate (${label}) should fail with ${expected}: ${message}`);
+    }
+    if (await dbUpdate.get('counters.safe') !== 10 || await dbUpdate.has('counters.nested')) {
+        throw new Error(`Failed updates should write nothing: ${JSON.stringify(await dbUpdate.get('counters'))}`);
+    }
+    // Several paths: a transfer keeps the total
+    await dbUpdate.set('accounts', { a: 100, b: 0 });
+    const moved = await dbUpdate.atomicUpdateMany<[number, number]>(['accounts.a', 'accounts.b'], ([a, b]) => [a! - 30, b! + 30]);
+    if (JSON.stringify(moved) !== '[70,30]') throw new Error(`Unexpected transfer result: ${JSON.stringify(moved)}`);
+    const partly = await dbUpdate.atomicUpdateMany<[number, number]>(['accounts.a', 'accounts.c'], () => [undefined, 1]);
+    if (JSON.stringify(partly) !== '[70,1]') throw new Error(`undefined entries should keep their path: ${JSON.stringify(partly)}`);
+    await dbUpdate.close();
+    const dbUpdateReopened = new JSONDatabase(updateFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbUpdateReopened.get('accounts')) !== '{"a":70,"b":30,"c":1}' || await dbUpdateReopened.get('counters.safe') !== 10) {
+        throw new Error(`Atomic updates should persist: ${JSON.stringify(await dbUpdateReopened.get(''))}`);
+    }
+    await dbUpdateReopened.close();
+    for (const f of updateFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 101: Building Indexes From Existing Data
+    console.log('📝 TEST 101: Building Indexes From Existing Data');
+    const buildFile = `${TEST_DB}.buildindex`;
+    const buildFiles = [buildFile, `${buildFile}.wal`, `${buildFile}.process_lock`, `${buildFile}.age.idx`];
+    for (const f of buildFiles) if (existsSync(f)) unlinkSync(f);
+    const dbBuild = new JSONDatabase(buildFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeBuild = (dbBuild as any).native;
+    const buildPeople: Record<string, object> = { nofield: { name: 'x' } };
+    for (let i = 0; i < 5000; i++) buildPeople[`p${i}`] = { age: i % 50 };
+    await dbBuild.set('people', buildPeople);
+    // Registered after the data exists, the index starts empty; give it a stale entry too
+    nativeBuild.registerIndex('age', 'age');
+    nativeBuild.updateIndex('age', 7, 'ghost.g1', false);
+    const built = dbBuild.buildIndex('age', 'people');
+    if (JSON.stringify(built) !== '{"indexed":5000,"skipped":1,"cleared":1}') throw new Error(`Unexpected build result: ${JSON.stringify(built)}`);
+    const sevens: string[] = nativeBuild.findIndexPaths('age', 7);
+    if (sevens.length !== 100 || sevens.includes('ghost.g1')) throw new Error(`The build should replace stale entries: ${sevens.length}`);
+    if (!existsSync(`${buildFile}.age.idx`)) throw new Error('The built index should be saved');
+    if (!nativeBuild.verifyIndex('age', 'people').ok) throw new Error('A built index should verify clean');
+    // The index tracks the collection from then on
+    await dbBuild.set('people.p7.age', 8);
+    if (nativeBuild.findIndexPaths('age', 7).length !== 99) throw new Error('Writes after a build should reach the index');
+    const refusedBuilds: [string, () => unknown, string][] = [
+        ['unknown index', () => dbBuild.buildIndex('nope', 'people'), 'ERR_NOT_FOUND'],
+        ['root', () => dbBuild.buildIndex('age', ''), 'ERR_INVALID_ARGUMENT'],
+        ['not a collection', () => dbBuild.buildIndex('age', 'people.p1.age'), 'ERR_PATH_BLOCKED'],
+    ];
+    for (const [label, call, code] of refusedBuilds) {
+        const message = await Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+        if (!message?.startsWith(code)) throw new Error(`buildIndex (${label}) should fail with ${code}: ${message}`);
+    }
+    await dbBuild.close();
+    for (const f of buildFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 102: Anomaly Scan
+    console.log('📝 TEST 102: Anomaly Scan');
+    const anomalyFile = `${TEST_DB}.anomalies`;
+    const anomalyFiles = [anomalyFile, `${anomalyFile}.wal`, `${anomalyFile}.process_lock`];
+    for (const f of anomalyFiles) if (existsSync(f)) unlinkSync(f);
+    const dbAnomalies = new JSONDatabase(anomalyFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbAnomalies.set('clean', { n: 2 ** 53 - 1, text: 'tab\tand\nnewline', list: [1, null, 2] });
+    await dbAnomalies.set('bad', {
+        big: 2 ** 60,
+        lone: 'x\uD800y',
+        ctl: 'a\u0001b',
+        '': 1,
+        'a.b': 2,
+        'c/d': 3,
+        padded: [1, null, null],
+        deep: { l1: { l2: { l3: { l4: 1 } } } },
+    });
+    const kindsAt = (report: any) => report.anomalies.map((a: any) => `${a.kind}@${a.path}`).join(' ');
+    const cleanScan = dbAnomalies.scanAnomalies('clean');
+    if (cleanScan.anomalies.length !== 0) throw new Error(`Clean data should have no findings: ${kindsAt(cleanScan)}`);
+    const fullScan = dbAnomalies.scanAnomalies();
+    const expectedKinds = 'unsafeInteger@bad.big replacementChar@bad.lone controlChar@bad.ctl emptyKey@bad '
+        + 'unaddressableKey@bad unaddressableKey@bad trailingNulls@bad.padded';
+    if (kindsAt(fullScan) !== expectedKinds) throw new Error(`Unexpected findings: ${kindsAt(fullScan)}`);
+    if (!fullScan.anomalies.some(a => a.detail.includes("'c/d'")) || fullScan.truncated) {
+        throw new Error(`Unaddressable keys should be named: ${JSON.stringify(fullScan.anomalies)}`);
+    }
+    // limit caps the findings listed per kind, not the counts; maxDepth reports deep nesting
+    const cappedScan = dbAnomalies.scanAnomalies('bad', { limit: 1, maxDepth: 3 });
+    if (cappedScan.counts.unaddressableKey !== 2 || cappedScan.anomalies.filter(a => a.kind === 'unaddressableKey').length !== 1 || !cappedScan.truncated) {
+        throw new Error(`limit should cap listed findings only: ${JSON.stringify(cappedScan)}`);
+    }
+    if (!cappedScan.anomalies.some(a => a.kind === 'deepNesting' && a.path === 'bad.deep.l1.l2.l3')) {
+        throw new Error(`Nesting past maxDepth should be reported: ${kindsAt(cappedScan)}`);
+    }
+    // Scanning changes nothing unless asked to trim trailing nulls
+    if (JSON.stringify(await dbAnomalies.get('bad.padded')) !== '[1,null,null]') throw new Error('A scan should not change data');
+    const fixedScan = dbAnomalies.scanAnomalies('bad', { fixTrailingNulls: true });
+    if (JSON.stringify(fixedScan.fixed) !== '["bad.padded"]' || JSON.stringify(await dbAnomalies.get('bad.padded')) !== '[1]') {
+        throw new Error(`Trailing nulls should be trimmed: ${JSON.stringify(fixedScan.fixed)}`);
+    }
+    if (JSON.stringify(await dbAnomalies.get('clean.list')) !== '[1,null,2]') throw new Error('Inner nulls should be kept');
+    const missingScan = await Promise.resolve().then(() => dbAnomalies.scanAnomalies('missing')).then(() => null, (e: Error) => e.message);
+    if (!missingScan?.startsWith('ERR_PATH_NOT_FOUND')) throw new Error(`Scanning a missing path should fail: ${missingScan}`);
+    await dbAnomalies.close();
+    const dbAnomaliesReopened = new JSONDatabase(anomalyFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbAnomaliesReopened.get('bad.padded')) !== '[1]') throw new Error('A trim should persist');
+    await dbAnomaliesReopened.close();
+    for (const f of anomalyFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 103: Deferred Recovery
+    console.log('📝 TEST 103: Deferred Recovery');
+    const deferFile = `${TEST_DB}.defer`;
+    const deferFiles = [deferFile, `${deferFile}.wal`, `${deferFile}.process_lock`];
+    for (const f of deferFiles) if (existsSync(f)) unlinkSync(f);
+    const deferOptions = { durability: 'batched' as const, lockMode: 'exclusive' as const };
+    const dbDeferSource = new JSONDatabase(deferFile, deferOptions);
+    await dbDeferSource.set('base', 1);
+    await dbDeferSource.save();
+    const nativeDeferSource = (dbDeferSource as any).native;
+    for (let i = 0; i < 3000; i++) nativeDeferSource.set(`items.i${i}`, i);
+    await dbDeferSource.sync();
+    // Closed without a save: the items only exist in the WAL
+    nativeDeferSource.close();
+    const pendingError = (call: () => unknown) => Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+
+    const dbDeferred = new JSONDatabase(deferFile, { ...deferOptions, deferRecovery: true });
+    if (dbDeferred.recoveryStatus().state !== 'pending') throw new Error('Recovery should wait for recover()');
+    for (const call of [() => dbDeferred.get('base'), () => dbDeferred.set('x', 1)]) {
+        const message = await pendingError(call);
+        if (!message?.startsWith('ERR_RECOVERY_PENDING')) throw new Error(`Calls before recovery should fail: ${message}`);
+    }
+    const progress: RecoveryProgress[] = [];
+    const deferReport = dbDeferred.recover(p => progress.push(p));
+    const lastProgress = progress[progress.length - 1];
+    if (progress.length < 2 || lastProgress.opsReplayed !== 3000 || lastProgress.bytesRead !== lastProgress.totalBytes) {
+        throw new Error(`Progress should be reported up to the whole WAL: ${JSON.stringify(progress)}`);
+    }
+    if (deferReport.replayedOps !== 3000 || dbDeferred.recoveryStatus().state !== 'done') throw new Error(`Unexpected report: ${JSON.stringify(deferReport)}`);
+    if (await dbDeferred.get('items.i2999') !== 2999 || await dbDeferred.get('base') !== 1) throw new Error('Recovered data should be readable');
+    if (JSON.stringify(dbDeferred.recover()) !== JSON.stringify(deferReport)) throw new Error('A second recover() should return the same report');
+    (dbDeferred as any).native.close();
+
+    // In the background: calls fail until the promise resolves
+    const dbBackground = new JSONDatabase(deferFile, { ...deferOptions, deferRecovery: true });
+    const recovering = dbBackground.recoverAsync();
+    const runningMessage = await pendingError(() => dbBackground.get('base'));
+    if (!runningMessage?.startsWith('ERR_RECOVERY_PENDING')) throw new Error(`Calls during recovery should fail: ${runningMessage}`);
+    if ((await recovering).replayedOps !== 3000 || await dbBackground.get('items.i5') !== 5) throw new Error('Background recovery should replay the WAL');
+    (dbBackground as any).native.close();
+
+    // open() recovers in the background; onRecoveryProgress reports from the constructor
+    const dbOpened = await JSONDatabase.open(deferFile, deferOptions);
+    if (await dbOpened.get('items.i5') !== 5) throw new Error('open() should resolve once recovered');
+    (dbOpened as any).native.close();
+    let constructorProgress = 0;
+    const dbWatched = new JSONDatabase(deferFile, { ...deferOptions, onRecoveryProgress: () => constructorProgress++ });
+    if (constructorProgress === 0 || await dbWatched.get('items.i5') !== 5) throw new Error('onRecoveryProgress should be called while the constructor recovers');
+    (dbWatched as any).native.close();
+
+    // A throwing callback fails recover() once the replay is done
+    const dbThrowing = new JSONDatabase(deferFile, { ...deferOptions, deferRecovery: true });
+    const callbackMessage = await pendingError(() => dbThrowing.recover(() => { throw new Error('progress failed'); }));
+    if (callbackMessage !== 'progress failed' || await dbThrowing.get('items.i5') !== 5) {
+        throw new Error(`A throwing callback should not stop the replay: ${callbackMessage}`);
+    }
+    await dbThrowing.close();
+    for (const f of deferFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 104: WAL Backpressure
+    console.log('📝 TEST 104: WAL Backpressure');
+    const pressureFile = `${TEST_DB}.walpressure`;
+    const pressureFiles = [pressureFile, `${pressureFile}.wal`, `${pressureFile}.process_lock`];
+    const pressureDb = (options: object) => {
+        for (const f of pressureFiles) if (existsSync(f)) unlinkSync(f);
+        return new JSONDatabase(pressureFile, { durability: 'batched', lockMode: 'exclusive', ...options });
+    };
+    const PRESSURE_WRITES = 20000;
+    const flood = (db: JSONDatabase) => {
+        const native = (db as any).native;
+        const errors: string[] = [];
+        for (let i = 0; i < PRESSURE_WRITES; i++) {
+            try { native.set(`queue.k${i}`, i); } catch (e) { errors.push((e as Error).message); }
+        }
+        return errors;
+    };
+
+    const dbDefaultQueue = pressureDb({});
+    const idle = dbDefaultQueue.walPressure();
+    if (!idle.enabled || idle.queueCapacity !== 100000 || idle.policy !== 'block' || idle.rejected !== 0 || idle.dropped !== 0) {
+        throw new Error(`Unexpected default WAL pressure: ${JSON.stringify(idle)}`);
+    }
+    await dbDefaultQueue.close();
+    const badPolicy = await Promise.resolve().then(() => pressureDb({ walOverflowPolicy: 'sometimes' })).then(() => null, (e: Error) => e.message);
+    if (!badPolicy?.startsWith('ERR_INVALID_ARGUMENT')) throw new Error(`An unknown overflow policy should be rejected: ${badPolicy}`);
+
+    // 'error': a full queue fails the write, which is then not applied
+    const dbErrorPolicy = pressureDb({ walQueueCapacity: 1, walOverflowPolicy: 'error' });
+    const busy = flood(dbErrorPolicy);
+    if (busy.some(message => !message.startsWith('ERR_WAL_BUSY'))) throw new Error(`A full queue should fail with ERR_WAL_BUSY: ${busy[0]}`);
+    const errorPressure = dbErrorPolicy.walPressure();
+    if (errorPressure.queueCapacity !== 1 || errorPressure.policy !== 'error' || errorPressure.rejected !== busy.length) {
+        throw new Error(`Rejected appends should be counted: ${JSON.stringify(errorPressure)} vs ${busy.length}`);
+    }
+    if (Object.keys(await dbErrorPolicy.get<object>('queue')).length !== PRESSURE_WRITES - busy.length) {
+        throw new Error('Rejected writes should not be applied');
+    }
+    await dbErrorPolicy.close();
+
+    // 'block': every write waits its turn and lands
+    const dbBlockPolicy = pressureDb({ walQueueCapacity: 1 });
+    if (flood(dbBlockPolicy).length !== 0) throw new Error('A blocking queue should not fail writes');
+    const blockPressure = dbBlockPolicy.walPressure();
+    if (blockPressure.rejected !== 0 || blockPressure.dropped !== 0 || (blockPressure.blockedAppends! > 0) !== (blockPressure.blockedMs! > 0)) {
+        throw new Error(`Unexpected blocking counters: ${JSON.stringify(blockPressure)}`);
+    }
+    await dbBlockPolicy.sync();
+    (dbBlockPolicy as any).native.close();
+    const dbBlockReopened = new JSONDatabase(pressureFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (Object.keys(await dbBlockReopened.get<object>('queue')).length !== PRESSURE_WRITES) throw new Error('Blocked writes should all reach the WAL');
+    await dbBlockReopened.close();
+
+    // 'drop_oldest': writes never fail or wait; queued ones may be discarded
+    const dbDropPolicy = pressureDb({ durability: 'lazy', walQueueCapacity: 1, walOverflowPolicy: 'drop_oldest' });
+    if (flood(dbDropPolicy).length !== 0) throw new Error('drop_oldest should not fail writes');
+    const dropPressure = dbDropPolicy.walPressure();
+    if (dropPressure.policy !== 'drop_oldest' || dropPressure.rejected !== 0 || dropPressure.blockedAppends !== 0) {
+        throw new Error(`Unexpected drop_oldest counters: ${JSON.stringify(dropPressure)}`);
+    }
+    if (Object.keys(await dbDropPolicy.get<object>('queue')).length !== PRESSURE_WRITES) throw new Error('Dropped WAL records should still be applied in memory');
+    await dbDropPolicy.close();
+    for (const f of pressureFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 105: Write Coalescing
+    console.log('📝 TEST 105: Write Coalescing');
+    const coalesceFile = `${TEST_DB}.coalesce`;
+    const coalesceFiles = [coalesceFile, `${coalesceFile}.wal`, `${coalesceFile}.process_lock`];
+    for (const f of coalesceFiles) if (existsSync(f)) unlinkSync(f);
+    const dbCoalesce = new JSONDatabase(coalesceFile, { durability: 'batched', lockMode: 'exclusive' });
+    const nativeCoalesce = (dbCoalesce as any).native;
+    const coalesceStats = () => nativeCoalesce.dbStats().coalescing;
+    const loggedPaths = () => nativeCoalesce.walInspect().records.map((r: any) => r.path).join(' ');
+    for (const prefixes of [[], ['']]) {
+        const message = await Promise.resolve().then(() => dbCoalesce.enableWriteCoalescing(50, prefixes)).then(() => null, (e: Error) => e.message);
+        if (!message?.startsWith('ERR_INVALID_ARGUMENT')) throw new Error(`Prefixes ${JSON.stringify(prefixes)} should be rejected: ${message}`);
+    }
+    dbCoalesce.enableWriteCoalescing(60000, ['telemetry']);
+    for (let i = 0; i < 100; i++) {
+        await dbCoalesce.set('telemetry.cpu', i);
+        await dbCoalesce.set('telemetry.mem', i * 2);
+    }
+    await dbCoalesce.set('other', 1);
+    // Staged writes are held back from the WAL
+    const staged = coalesceStats();
+    if (staged.pending !== 2 || staged.stagedWrites !== 200 || staged.coalescedWrites !== 198 || staged.flushes !== 0) {
+        throw new Error(`Unexpected staging counters: ${JSON.stringify(staged)}`);
+    }
+    await sleep(100);
+    if (loggedPaths() !== 'other') throw new Error(`Staged writes should not be logged before a flush: ${loggedPaths()}`);
+    // A staged path reads back its staged value and stays staged
+    if (await dbCoalesce.get('telemetry.cpu') !== 99 || coalesceStats().pending !== 2) {
+        throw new Error(`Reading a staged path should not flush it: ${JSON.stringify(coalesceStats())}`);
+    }
+    // Reading above a staged path applies its latest value first
+    if (JSON.stringify(await dbCoalesce.get('telemetry')) !== '{"cpu":99,"mem":198}') {
+        throw new Error(`Reads should see staged writes: ${JSON.stringify(await dbCoalesce.get('telemetry'))}`);
+    }
+    if (coalesceStats().pending !== 0 || coalesceStats().flushedWrites !== 2) throw new Error(`A read should apply staged writes: ${JSON.stringify(coalesceStats())}`);
+    // A flush logs only the latest value per path
+    await dbCoalesce.set('telemetry.cpu', 100);
+    await dbCoalesce.set('telemetry.mem', 200);
+    if (dbCoalesce.flushCoalesced() !== 2 || dbCoalesce.flushCoalesced() !== 0) throw new Error('A flush should write each staged path once');
+    await dbCoalesce.sync();
+    if (loggedPaths() !== 'other telemetry.cpu telemetry.mem telemetry.cpu telemetry.mem') throw new Error(`Unexpected WAL after a flush: ${loggedPaths()}`);
+    // save() applies staged writes first
+    await dbCoalesce.set('telemetry.disk', 7);
+    await dbCoalesce.save();
+    if (JSON.parse(readFileSync(coalesceFile, 'utf8')).telemetry.disk !== 7) throw new Error('save() should include staged writes');
+    // The window flushes on its own
+    dbCoalesce.enableWriteCoalescing(20, ['telemetry']);
+    await dbCoalesce.set('telemetry.disk', 8);
+    await sleep(100);
+    if (coalesceStats().pending !== 0) throw new Error('Staged writes should be applied once the window passes');
+    // Disabling applies what is left; later writes go straight through
+    await dbCoalesce.set('telemetry.net', 1);
+    if (dbCoalesce.disableWriteCoalescing() !== 1 || coalesceStats().enabled) throw new Error('Disabling should apply the staged write');
+    await dbCoalesce.set('telemetry.net', 2);
+    await dbCoalesce.set('telemetry.cpu', 101);
+    // close() applies staged writes too
+    dbCoalesce.enableWriteCoalescing(60000, ['telemetry']);
+    await dbCoalesce.set('telemetry.cpu', 102);
+    await dbCoalesce.close();
+    const dbCoalesceReopened = new JSONDatabase(coalesceFile, { durability: 'batched', lockMode: 'exclusive' });
+    if (JSON.stringify(await dbCoalesceReopened.get('telemetry')) !== '{"cpu":102,"mem":200,"disk":8,"net":2}') {
+        throw new Error(`Staged writes should survive close(): ${JSON.stringify(await dbCoalesceReopened.get('telemetry'))}`);
+    }
+    await dbCoalesceReopened.close();
+    for (const f of coalesceFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 106: Maintained Counters
+    console.log('📝 TEST 106: Maintained Counters');
+    const counterFile = `${TEST_DB}.counters`;
+    const counterFiles = [counterFile, `${counterFile}.wal`, `${counterFile}.process_lock`];
+    for (const f of counterFiles) if (existsSync(f)) unlinkSync(f);
+    const counterOptions = { durability: 'batched' as const, lockMode: 'exclusive' as const };
+    const dbCounter = new JSONDatabase(counterFile, counterOptions);
+    const nativeCounter = (dbCounter as any).native;
+    await dbCounter.set('users', { a: { status: 'active' }, b: { status: 'idle' }, c: { status: 'active' } });
+    if (nativeCounter.registerCounter('active', 'users', [{ field: 'status', op: 'eq', value: 'active' }]) !== 2) {
+        throw new Error('Registering should return the initial count');
+    }
+    // Each write moves the count by what it changed
+    const countSteps: [string, () => Promise<void>, number][] = [
+        ['field now matches', () => dbCounter.set('users.b.status', 'active'), 3],
+        ['new matching doc', () => dbCounter.set('users.d', { status: 'active' }), 4],
+        ['matching doc deleted', () => dbCounter.delete('users.a'), 3],
+        ['doc replaced', () => dbCounter.set('users.c', { status: 'gone' }), 2],
+        ['write outside the collection', () => dbCounter.set('guests.g1', { status: 'active' }), 2],
+    ];
+    for (const [label, write, expected] of countSteps) {
+        await write();
+        const count = nativeCounter.counterValue('active');
+        if (count !== expected) throw new Error(`After ${label} the count should be ${expected}, got ${count}`);
+    }
+    const verified = nativeCounter.verifyCounter('active');
+    if (!verified.consistent || verified.actual !== 2) throw new Error(`The maintained count should match a scan: ${JSON.stringify(verified)}`);
+    const refusedCounters: [string, () => unknown, string][] = [
+        ['unknown counter', () => nativeCounter.counterValue('nope'), 'ERR_NOT_FOUND'],
+        ['unknown operator', () => nativeCounter.registerCounter('bad', 'users', [{ field: 'status', op: 'bogus', value: 1 }]), 'ERR_INVALID_ARGUMENT'],
+        ['root collection', () => nativeCounter.registerCounter('all', '', []), 'ERR_INVALID_ARGUMENT'],
+    ];
+    for (const [label, call, code] of refusedCounters) {
+        const message = await Promise.resolve().then(call).then(() => null, (e: Error) => e.message);
+        if (!message?.startsWith(code)) throw new Error(`Counter (${label}) should fail with ${code}: ${message}`);
+    }
+    // Replacing the whole collection recounts it
+    await dbCounter.set('users', { z: { status: 'active' } });
+    if (nativeCounter.counterValue('active') !== 1) throw new Error('Replacing the collection should recount it');
+    await dbCounter.close();
+
+    // Saved counters are trusted on reopen, with no rescan
+    const dbCounterSaved = new JSONDatabase(counterFile, counterOptions);
+    const savedCounter = (dbCounterSaved as any).native.listCounters().active;
+    if (savedCounter?.dirty !== false || savedCounter.count !== 1) throw new Error(`A saved counter should load clean: ${JSON.stringify(savedCounter)}`);
+    await dbCounterSaved.set('users.y', { status: 'active' });
+    await dbCounterSaved.sync();
+    (dbCounterSaved as any).native.close();
+    // Replayed WAL writes make it recount on first read
+    const dbCounterReplayed = new JSONDatabase(counterFile, counterOptions);
+    const nativeReplayed = (dbCounterReplayed as any).native;
+    if (nativeReplayed.listCounters().active.dirty !== true) throw new Error('A counter under replayed writes should be recounted');
+    if (nativeReplayed.counterValue('active') !== 2 || nativeReplayed.rebuildCounter('active') !== 2) throw new Error('The recount should include replayed writes');
+    if (!nativeReplayed.dropCounter('active') || nativeReplayed.dropCounter('active')) throw new Error('dropCounter should report whether it removed one');
+    await dbCounterReplayed.close();
+    const dbCounterDropped = new JSONDatabase(counterFile, counterOptions);
+    if (JSON.stringify((dbCounterDropped as any).native.listCounters()) !== '{}') throw new Error('A dropped counter should stay dropped');
+    await dbCounterDropped.close();
+    for (const f of counterFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 107: Undo Log Limits
+    console.log('📝 TEST 107: Undo Log Limits');
+    const undoFile = `${TEST_DB}.undo`;
+    const undoFiles = [undoFile, `${undoFile}.wal`, `${undoFile}.process_lock`];
+    const bigValue = 'x'.repeat(6000);
+    const undoDb = async (options: object) => {
+        for (const f of undoFiles) if (existsSync(f)) unlinkSync(f);
+        const db = new JSONDatabase(undoFile, { durability: 'batched', lockMode: 'exclusive', maxUndoBytes: 10000, ...options });
+        await db.set('docs', { a: bigValue, b: bigValue, c: 1 });
+        return db;
+    };
+
+    const dbUndoCapped = await undoDb({});
+    const nativeUndoCapped = (dbUndoCapped as any).native;
+    const cappedTxn: number = nativeUndoCapped.beginTransaction();
+    // Only the first old value per path is kept
+    for (let i = 0; i < 50; i++) await dbUndoCapped.set('docs.c', i, { txn: cappedTxn });
+    const dedupedInfo = nativeUndoCapped.transactionInfo(cappedTxn);
+    if (dedupedInfo.entries !== 1 || dedupedInfo.undoBytes > 100 || dedupedInfo.maxUndoBytes !== 10000) {
+        throw new Error(`Repeated writes to one path should keep one undo entry: ${JSON.stringify(dedupedInfo)}`);
+    }
+    await dbUndoCapped.set('docs.a', 'small', { txn: cappedTxn });
+    if (nativeUndoCapped.transactionInfo(cappedTxn).undoBytes < 6000) throw new Error('undoBytes should include the large old value');
+    // Past the cap a write fails and is not applied
+    const tooLarge = await dbUndoCapped.set('docs.b', 'small', { txn: cappedTxn }).then(() => null, (e: Error) => e.message);
+    if (!tooLarge?.startsWith('ERR_LIMIT') || await dbUndoCapped.get('docs.b') !== bigValue) {
+        throw new Error(`A write past maxUndoBytes should fail with ERR_LIMIT: ${tooLarge}`);
+    }
+    // A savepoint needs the old value again
+    nativeUndoCapped.createSavepoint('sp', cappedTxn);
+    await dbUndoCapped.set('docs.c', 99, { txn: cappedTxn });
+    if (nativeUndoCapped.transactionInfo(cappedTxn).entries !== 3) throw new Error('A write after a savepoint should record its own undo entry');
+    nativeUndoCapped.rollbackTransaction(cappedTxn);
+    if (await dbUndoCapped.get('docs.a') !== bigValue || await dbUndoCapped.get('docs.c') !== 1) throw new Error('Rollback should restore the old values');
+    // The cap applies to transactions only
+    await dbUndoCapped.set('docs.b', 'outside');
+    if (nativeUndoCapped.transactionInfo().undoBytes !== 0) throw new Error('No undo should be held without a transaction');
+    await dbUndoCapped.close();
+
+    // undoSpill: entries past the cap go to a temp file and come back on rollback
+    const dbUndoSpill = await undoDb({ undoSpill: true });
+    const nativeUndoSpill = (dbUndoSpill as any).native;
+    const spillTxn: number = nativeUndoSpill.beginTransaction();
+    await dbUndoSpill.set('docs.a', 'small', { txn: spillTxn });
+    await dbUndoSpill.set('docs.b', 'small', { txn: spillTxn });
+    await dbUndoSpill.delete('docs.c', { txn: spillTxn });
+    const spillInfo = nativeUndoSpill.transactionInfo(spillTxn);
+    if (spillInfo.entries !== 3 || spillInfo.spilledBytes < 6000 || spillInfo.undoBytes > 10000) {
+        throw new Error(`Undo past the cap should spill: ${JSON.stringify(spillInfo)}`);
+    }
+    nativeUndoSpill.rollbackTransaction(spillTxn);
+    if (await dbUndoSpill.get('docs.a') !== bigValue || await dbUndoSpill.get('docs.b') !== bigValue || await dbUndoSpill.get('docs.c') !== 1) {
+        throw new Error('Rollback should restore spilled values');
+    }
+    await dbUndoSpill.close();
+    for (const f of undoFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 108: Field-to-Field Filters
+    console.log('📝 TEST 108: Field-to-Field Filters');
+    const fieldRefFile = `${TEST_DB}.fieldref`;
+    const fieldRefFiles = [fieldRefFile, `${fieldRefFile}.wal`, `${fieldRefFile}.process_lock`, `${fieldRefFile}.spent.idx`];
+    for (const f of fieldRefFiles) if (existsSync(f)) unlinkSync(f);
+    const dbFieldRef = new JSONDatabase(fieldRefFile, {
+        durability: 'batched',
+        lockMode: 'exclusive',
+        indices: [{ name: 'spent', path: 'projects', field: 'spent' }],
+    });
+    await dbFieldRef.set('projects', {
+        p1: { spent: 50, budget: 100, limits: { max: 40 } },
+        p2: { spent: 100, budget: 100, limits: { max: 200 } },
+        p3: { spent: 150, budget: 100 },
+        p4: { spent: 10 },
+    });
+    const keysWhere = async (filters: QueryFilter[]) => {
+        const entries = await dbFieldRef.parallelQuery<{ key: string }>('projects', filters, { emit: 'entries' });
+        return entries.map(e => e.key).join(' ');
+    };
+    // Each comparison against `budget`; p4 has no budget and never matches
+    const fieldComparisons: [QueryFilter['op'], string][] = [
+        ['eq', 'p2'], ['ne', 'p1 p3'], ['gt', 'p3'], ['gte', 'p2 p3'], ['lt', 'p1'], ['lte', 'p1 p2'],
+    ];
+    for (const [op, expected] of fieldComparisons) {
+        const keys = await keysWhere([{ field: 'spent', op, value: { $field: 'budget' } }]);
+        if (keys !== expected) throw new Error(`spent ${op} budget: expected ${expected}, got ${keys}`);
+    }
+    const nestedKeys = await keysWhere([{ field: 'spent', op: 'gt', value: { $field: 'limits.max' } }]);
+    if (nestedKeys !== 'p1') throw new Error(`A nested reference should resolve: ${nestedKeys}`);
+    const missingRefKeys = await keysWhere([{ field: 'spent', op: 'ne', value: { $field: 'budget' }, missing: 'match' }]);
+    if (missingRefKeys !== 'p1 p3 p4') throw new Error(`A missing side should follow the missing policy: ${missingRefKeys}`);
+    // An index on the field cannot answer a comparison with another field
+    const refPlan = (dbFieldRef as any).native.explainQuery('projects', [{ field: 'spent', op: 'eq', value: { $field: 'budget' } }]);
+    if (refPlan.strategy !== 'scan') throw new Error(`A field reference should not use an index: ${JSON.stringify(refPlan)}`);
+    // Bulk writes take references too
+    const overBudget = await dbFieldRef.updateMany('projects', [{ field: 'spent', op: 'gt', value: { $field: 'budget' } }], { over: true });
+    if (JSON.stringify(overBudget) !== '{"updated":1,"keys":["p3"]}' || await dbFieldRef.get('projects.p3.over') !== true) {
+        throw new Error(`updateMany should compare the fields: ${JSON.stringify(overBudget)}`);
+    }
+    await dbFieldRef.close();
+    for (const f of fieldRefFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
+    // TEST 109: Query Emit Modes
+    console.log('📝 TEST 109: Query Emit Modes');
+    const emitFile = `${TEST_DB}.emit`;
+    const emitFiles = [emitFile, `${emitFile}.wal`, `${emitFile}.process_lock`];
+    for (const f of emitFiles) if (existsSync(f)) unlinkSync(f);
+    const dbEmit = new JSONDatabase(emitFile, { durability: 'batched', lockMode: 'exclusive' });
+    await dbEmit.set('members', {
+        ann: { age: 31, team: 'red' },
+        bob: { age: 17, team: 'blue' },
+        cyd: { age: 45, team: 'red' },
+    });
+    await dbEmit.set('queue', [{ age: 20 }, { age: 12 }, { age: 50 }]);
+    const adults: QueryFilter[] = [{ field: 'age', op: 'gte', value: 18 }];
+    // "values" stays the default and drops the keys
+    const emitValues = await dbEmit.parallelQuery('members', adults);
+    const emitValuesExplicit = await dbEmit.parallelQuery('members', adults, { emit: 'values' });
+    if (JSON.stringify(emitValues) !== '[{"age":31,"team":"red"},{"age":45,"team":"red"}]' || JSON.stringify(emitValuesExplicit) !== JSON.stringify(emitValues)) {
+        throw new Error(`"values" should return the bare documents: ${JSON.stringify(emitValues)}`);
+    }
+    const emitEntries = await dbEmit.parallelQuery('members', adults, { emit: 'entries' });
+    if (JSON.stringify(emitEntries) !== '[{"key":"ann","value":{"age":31,"team":"red"}},{"key":"cyd","value":{"age":45,"team":"red"}}]') {
+        throw new Error(`"entries" should pair each match with its key: ${JSON.stringify(emitEntries)}`);
+    }
+    const emitObject = await dbEmit.parallelQuery('members', adults, { emit: 'object' }) as unknown;
+    if (JSON.stringify(emitObject) !== '{"ann":{"age":31,"team":"red"},"cyd":{"age":45,"team":"red"}}') {
+        throw new Error(`"object" should keep matches under their keys: ${JSON.stringify(emitObject)}`);
+    }
+    // Array collections use the stringified indices as keys
+    const queueEntries = await dbEmit.parallelQuery('queue', adults, { emit: 'entries' });
+    const queueObject = await dbEmit.parallelQuery('queue', adults, { emit: 'object' }) as unknown;
+    const queueIndexed = await dbEmit.parallelQuery('queue', adults, { emit: 'indexed' });
+    if (JSON.stringify(queueEntries) !== '[{"key":"0","value":{"age":20}},{"key":"2","value":{"age":50}}]'
+        || JSON.stringify(queueObject) !== '{"0":{"age":20},"2":{"age":50}}'
+        || JSON.stringify(queueIndexed) !== '[{"index":0,"value":{"age":20}},{"index":2,"value":{"age":50}}]') {
+        throw new Error(`Array keys should be indices: ${JSON.stringify([queueEntries, queueObject, queueIndexed])}`);
+    }
+    const emitErrors = await Promise.all([
+        () => dbEmit.parallelQuery('members', adults, { emit: 'indexed' }),
+        () => dbEmit.parallelQuery('members', adults, { emit: 'keys' as any }),
+    ].map(call => Promise.resolve().then(call).then(() => null, (e: Error) => e.message)));
+    if (!emitErrors[0]?.startsWith('ERR_NOT_AN_ARRAY') || !emitErrors[1]?.startsWith('ERR_INVALID_ARGUMENT')) {
+        throw new Error(`Bad emit modes should be refused: ${JSON.stringify(emitErrors)}`);
+    }
+    // Bulk writes report keys for object collections and indices for arrays
+    const emitUpdated = await dbEmit.updateMany('members', [{ field: 'team', op: 'eq', value: 'red' }], { checked: true });
+    const queueUpdated = await dbEmit.updateMany('queue', adults, { checked: true });
+    if (JSON.stringify(emitUpdated) !== '{"updated":2,"keys":["ann","cyd"]}' || JSON.stringify(queueUpdated) !== '{"updated":2,"indices":[0,2]}') {
+        throw new Error(`updateMany should report what it matched: ${JSON.stringify([emitUpdated, queueUpdated])}`);
+    }
+    const emitDeleted = await dbEmit.deleteMany('members', [{ field: 'age', op: 'lt', value: 18 }]);
+    const queueDeleted = await dbEmit.deleteMany('queue', adults);
+    if (JSON.stringify(emitDeleted) !== '{"deleted":1,"keys":["bob"]}' || JSON.stringify(queueDeleted) !== '{"deleted":2,"indices":[0,2]}') {
+        throw new Error(`deleteMany should report what it matched: ${JSON.stringify([emitDeleted, queueDeleted])}`);
+    }
+    if (JSON.stringify(await dbEmit.get('queue')) !== '[{"age":12}]' || await dbEmit.has('members.bob')) {
+        throw new Error('deleteMany should remove exactly the reported matches');
+    }
+    await dbEmit.close();
+    for (const f of emitFiles) if (existsSync(f)) unlinkSync(f);
+    console.log('   ✅ Passed\n');
+
     // Cleanup
     await dbWithIndex.close();
     cleanup();
@@ -771,6 +4645,81 @@ async function runTests() {
     console.log('   • Batched Write Performance');
     console.log('   • New Query Operators (containsAll, containsAny)');
     console.log('   • Parallel Join/Lookup');
+    console.log('   • WAL Compaction');
+    console.log('   • Value Compression');
+    console.log('   • Index Aggregates');
+    console.log('   • Missing-Field Policies');
+    console.log('   • Array Collection Bulk Writes');
+    console.log('   • Parallel WAL Recovery');
+    console.log('   • String Queries');
+    console.log('   • Delete Notifications');
+    console.log('   • Read Replicas');
+    console.log('   • Canonical JSON');
+    console.log('   • Storage Advisor');
+    console.log('   • Automatic Index Maintenance');
+    console.log('   • Stable Pagination');
+    console.log('   • Numeric Index Key Order');
+    console.log('   • Array Padding Policy');
+    console.log('   • Index-Aware Query Planner');
+    console.log('   • Binary Index Files');
+    console.log('   • Bulk Load Mode');
+    console.log('   • Write Throttling');
+    console.log('   • Close Safety');
+    console.log('   • Filter Validation');
+    console.log('   • Canonical Equality');
+    console.log('   • Frozen Paths');
+    console.log('   • Planner Statistics');
+    console.log('   • TTL Indexes');
+    console.log('   • Root Path');
+    console.log('   • Schema Defaults');
+    console.log('   • Schema Composition');
+    console.log('   • String Formats');
+    console.log('   • Enforced Schemas');
+    console.log('   • Schema References');
+    console.log('   • Dynamic Keys');
+    console.log('   • Precompiled Schemas');
+    console.log('   • Conditional Rules');
+    console.log('   • Tuples');
+    console.log('   • Schema Inspection');
+    console.log('   • Sample Values');
+    console.log('   • Concurrent Transactions');
+    console.log('   • Transaction Isolation');
+    console.log('   • Crash-Atomic Transactions');
+    console.log('   • Batches in Transactions');
+    console.log('   • Push Rollback');
+    console.log('   • Archive Round Trip');
+    console.log('   • Damaged Archives');
+    console.log('   • Lazy Collections');
+    console.log('   • Reorder');
+    console.log('   • Sequences');
+    console.log('   • Ordered Object Maps');
+    console.log('   • Rename Field');
+    console.log('   • Map Collection');
+    console.log('   • Snapshot Queries');
+    console.log('   • Three-Way Merge');
+    console.log('   • Delete Prefix');
+    console.log('   • Import File');
+    console.log('   • Materialized Views');
+    console.log('   • Collection Quotas');
+    console.log('   • forEach Iteration');
+    console.log('   • Uniqueness Constraints');
+    console.log('   • ID Strategies');
+    console.log('   • Cascade Rules');
+    console.log('   • Leases');
+    console.log('   • Path rules in set, setPointers and WAL replay');
+    console.log('   • Atomic conditional writes');
+    console.log('   • Index Range Queries');
+    console.log('   • Sparse Indexes');
+    console.log('   • Atomic Updates');
+    console.log('   • Building Indexes From Existing Data');
+    console.log('   • Anomaly Scan');
+    console.log('   • Deferred Recovery');
+    console.log('   • WAL Backpressure');
+    console.log('   • Write Coalescing');
+    console.log('   • Maintained Counters');
+    console.log('   • Undo Log Limits');
+    console.log('   • Field-to-Field Filters');
+    console.log('   • Query Emit Modes');
 }
 
 runTests().catch(e => {